@@ -0,0 +1,358 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cross-checks the data served by several RPC endpoints of the same chain.
+//!
+//! Finalized blocks can't be reverted, so all endpoints must agree on the hash of a finalized block, its events and
+//! the runtime version it was executed with. The majority of endpoints decides what the right data is. Best heads
+//! legitimately differ while blocks propagate, so they are only checked for lagging behind the other endpoints.
+
+use clap::Parser;
+use color_eyre::eyre::eyre;
+use colored::Colorize;
+use log::{debug, warn};
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::RequestExecutor,
+	output::{OutputFormat, OutputRecord, OutputWriter},
+	types::{BlockNumber, H256},
+	utils::RetryOptions,
+};
+use serde::Serialize;
+use std::{
+	fmt::{self, Debug, Display, Formatter},
+	hash::Hash,
+	time::Duration,
+};
+use subxt::config::{substrate::BlakeTwo256, Hasher};
+use tokio::sync::broadcast;
+
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct ConsistencyOptions {
+	/// Seconds between the checks
+	#[clap(long, default_value = "12")]
+	interval: u64,
+	/// Number of blocks the heads of an endpoint may be behind the other endpoints before it's reported as lagging
+	#[clap(long, default_value = "3")]
+	max_lag: u32,
+	/// Compare the event payloads of the checked finalized block at every N-th check
+	#[clap(long, default_value = "5")]
+	events_every: u32,
+}
+
+/// Data cross-checked between the endpoints
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ConsistencyCheck {
+	/// The endpoint can't serve the data
+	Availability,
+	/// Number of the best head
+	BestHead,
+	/// Number of the finalized head
+	FinalizedHead,
+	/// Hash of a finalized block
+	FinalizedHash,
+	/// Hash of the events of a finalized block
+	Events,
+	/// Runtime spec version of a finalized block
+	RuntimeVersion,
+}
+
+impl Display for ConsistencyCheck {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		let name = match self {
+			ConsistencyCheck::Availability => "availability",
+			ConsistencyCheck::BestHead => "best head",
+			ConsistencyCheck::FinalizedHead => "finalized head",
+			ConsistencyCheck::FinalizedHash => "finalized block hash",
+			ConsistencyCheck::Events => "events",
+			ConsistencyCheck::RuntimeVersion => "runtime version",
+		};
+		write!(f, "{}", name)
+	}
+}
+
+/// An endpoint serving data different from the majority
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct Divergence {
+	node: String,
+	check: ConsistencyCheck,
+	/// The checked block, if the data is of a particular block
+	block_number: Option<BlockNumber>,
+	/// Data served by the majority of endpoints
+	expected: String,
+	/// Data served by the endpoint
+	actual: String,
+}
+
+impl OutputRecord for Divergence {
+	const CSV_COLUMNS: &'static [&'static str] = &["node", "check", "block_number", "expected", "actual"];
+}
+
+impl Display for Divergence {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "[{}] {} {}", self.node, "Divergent".red().bold(), self.check.to_string().bold())?;
+		if let Some(block_number) = self.block_number {
+			write!(f, " of #{}", block_number)?;
+		}
+		write!(f, ": expected {}, got {}", self.expected, self.actual.bright_red())
+	}
+}
+
+/// Returns the value served by most endpoints and the endpoints serving a different one,
+/// the first endpoints in the list win a tie
+pub(crate) fn find_divergent<T: Clone + Eq + Hash>(values: &[(String, T)]) -> Option<(T, Vec<(String, T)>)> {
+	let mut counts: Vec<(&T, usize)> = vec![];
+	for (_, value) in values {
+		match counts.iter_mut().find(|(counted, _)| *counted == value) {
+			Some((_, count)) => *count += 1,
+			None => counts.push((value, 1)),
+		}
+	}
+	let expected = counts
+		.iter()
+		.fold(None, |best: Option<(&T, usize)>, (value, count)| match best {
+			Some((_, best_count)) if best_count >= *count => best,
+			_ => Some((*value, *count)),
+		})?
+		.0
+		.clone();
+	let divergent = values.iter().filter(|(_, value)| *value != expected).cloned().collect();
+
+	Some((expected, divergent))
+}
+
+/// Heads served by an endpoint
+struct EndpointHeads {
+	node: String,
+	best: BlockNumber,
+	finalized: BlockNumber,
+}
+
+pub(crate) struct ConsistencyChecker {
+	opts: ConsistencyOptions,
+	endpoints: Vec<String>,
+	executor: RequestExecutor,
+	output: OutputWriter,
+	/// Number of checks done
+	checks_count: u32,
+}
+
+impl ConsistencyChecker {
+	pub(crate) fn new(
+		endpoints: Vec<String>,
+		opts: ConsistencyOptions,
+		retry: RetryOptions,
+		format: OutputFormat,
+	) -> color_eyre::Result<Self> {
+		if endpoints.len() < 2 {
+			return Err(eyre!("At least two endpoints are needed to cross-check them"))
+		}
+		Ok(Self {
+			opts,
+			endpoints,
+			executor: RequestExecutor::new(retry, Default::default()),
+			output: OutputWriter::new(format),
+			checks_count: 0,
+		})
+	}
+
+	/// Checks the endpoints every `interval` seconds until shut down
+	pub(crate) async fn run(mut self, mut shutdown_rx: broadcast::Receiver<()>) {
+		let mut interval = tokio::time::interval(Duration::from_secs(self.opts.interval.max(1)));
+		loop {
+			tokio::select! {
+				_ = interval.tick() => {
+					for divergence in self.check().await {
+						if let Err(e) = self.output.print(&divergence) {
+							warn!("Cannot print a divergence: {:?}", e);
+						}
+					}
+				},
+				_ = shutdown_rx.recv() => break,
+			}
+		}
+	}
+
+	async fn check(&mut self) -> Vec<Divergence> {
+		let mut divergences = vec![];
+		let mut heads = vec![];
+		for node in self.endpoints.clone() {
+			match self.heads(&node).await {
+				Ok(v) => heads.push(v),
+				Err(e) => divergences.push(Divergence {
+					node,
+					check: ConsistencyCheck::Availability,
+					block_number: None,
+					expected: "heads".to_string(),
+					actual: e.to_string(),
+				}),
+			}
+		}
+		let (Some(max_best), Some(max_finalized), Some(min_finalized)) = (
+			heads.iter().map(|v| v.best).max(),
+			heads.iter().map(|v| v.finalized).max(),
+			heads.iter().map(|v| v.finalized).min(),
+		) else {
+			return divergences
+		};
+		for v in heads.iter() {
+			if max_best.saturating_sub(v.best) > self.opts.max_lag {
+				divergences.push(Divergence {
+					node: v.node.clone(),
+					check: ConsistencyCheck::BestHead,
+					block_number: None,
+					expected: format!("#{}", max_best),
+					actual: format!("#{}", v.best),
+				});
+			}
+			if max_finalized.saturating_sub(v.finalized) > self.opts.max_lag {
+				divergences.push(Divergence {
+					node: v.node.clone(),
+					check: ConsistencyCheck::FinalizedHead,
+					block_number: None,
+					expected: format!("#{}", max_finalized),
+					actual: format!("#{}", v.finalized),
+				});
+			}
+		}
+
+		// Every endpoint has the lowest finalized block, the rest of the checks are done on it
+		let mut hashes = vec![];
+		for v in heads.iter() {
+			match self.executor.get_block_hash(&v.node, Some(min_finalized)).await {
+				Ok(Some(hash)) => hashes.push((v.node.clone(), hash)),
+				Ok(None) => divergences.push(self.unavailable(&v.node, min_finalized, "no block hash")),
+				Err(e) => divergences.push(self.unavailable(&v.node, min_finalized, e)),
+			}
+		}
+		let Some((block_hash, divergent)) = find_divergent(&hashes) else { return divergences };
+		divergences.extend(divergent.into_iter().map(|(node, hash)| Divergence {
+			node,
+			check: ConsistencyCheck::FinalizedHash,
+			block_number: Some(min_finalized),
+			expected: format!("{:?}", block_hash),
+			actual: format!("{:?}", hash),
+		}));
+		let nodes: Vec<String> = hashes
+			.into_iter()
+			.filter(|(_, hash)| *hash == block_hash)
+			.map(|(node, _)| node)
+			.collect();
+
+		let mut spec_versions = vec![];
+		for node in nodes.iter() {
+			match self.executor.get_spec_version(node, block_hash).await {
+				Ok(spec_version) => spec_versions.push((node.clone(), spec_version)),
+				Err(e) => divergences.push(self.unavailable(node, min_finalized, e)),
+			}
+		}
+		divergences.extend(self.divergences(ConsistencyCheck::RuntimeVersion, min_finalized, &spec_versions));
+
+		if self.checks_count % self.opts.events_every.max(1) == 0 {
+			let mut events = vec![];
+			for node in nodes.iter() {
+				match self.executor.get_events(node, block_hash).await {
+					Ok(Some(v)) => events.push((node.clone(), BlakeTwo256::hash(v.bytes()))),
+					Ok(None) => divergences.push(self.unavailable(node, min_finalized, "no events")),
+					Err(e) => divergences.push(self.unavailable(node, min_finalized, e)),
+				}
+			}
+			divergences.extend(self.divergences(ConsistencyCheck::Events, min_finalized, &events));
+		}
+		self.checks_count += 1;
+
+		if divergences.is_empty() {
+			debug!("{} endpoints are consistent at #{}", self.endpoints.len(), min_finalized);
+		}
+		divergences
+	}
+
+	async fn heads(&mut self, node: &str) -> color_eyre::Result<EndpointHeads> {
+		let best = self
+			.executor
+			.get_block_head(node, None)
+			.await?
+			.ok_or_else(|| eyre!("no best head"))?
+			.number;
+		let finalized_hash = self
+			.executor
+			.get_finalized_block_hash(node)
+			.await?
+			.ok_or_else(|| eyre!("no finalized head"))?;
+		let finalized = self
+			.executor
+			.get_block_head(node, Some(finalized_hash))
+			.await?
+			.ok_or_else(|| eyre!("no finalized head"))?
+			.number;
+
+		Ok(EndpointHeads { node: node.to_string(), best, finalized })
+	}
+
+	fn divergences<T: Clone + Eq + Hash + Debug>(
+		&self,
+		check: ConsistencyCheck,
+		block_number: BlockNumber,
+		values: &[(String, T)],
+	) -> Vec<Divergence> {
+		let Some((expected, divergent)) = find_divergent(values) else { return vec![] };
+		divergent
+			.into_iter()
+			.map(|(node, value)| Divergence {
+				node,
+				check,
+				block_number: Some(block_number),
+				expected: format!("{:?}", expected),
+				actual: format!("{:?}", value),
+			})
+			.collect()
+	}
+
+	fn unavailable(&self, node: &str, block_number: BlockNumber, error: impl Display) -> Divergence {
+		Divergence {
+			node: node.to_string(),
+			check: ConsistencyCheck::Availability,
+			block_number: Some(block_number),
+			expected: "block data".to_string(),
+			actual: error.to_string(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn served<T>(values: Vec<(&str, T)>) -> Vec<(String, T)> {
+		values.into_iter().map(|(node, value)| (node.to_string(), value)).collect()
+	}
+
+	#[test]
+	fn test_finds_divergent_endpoints() {
+		let values =
+			served(vec![("a", H256::repeat_byte(1)), ("b", H256::repeat_byte(2)), ("c", H256::repeat_byte(1))]);
+		let (expected, divergent) = find_divergent(&values).unwrap();
+		assert_eq!(expected, H256::repeat_byte(1));
+		assert_eq!(divergent, served(vec![("b", H256::repeat_byte(2))]));
+
+		let (expected, divergent) = find_divergent(&served(vec![("a", 1000), ("b", 1001)])).unwrap();
+		assert_eq!(expected, 1000);
+		assert_eq!(divergent, served(vec![("b", 1001)]));
+
+		assert!(find_divergent::<u32>(&[]).is_none());
+	}
+}
@@ -16,30 +16,40 @@
 
 use clap::Parser;
 use colored::Colorize;
+use consistency::{ConsistencyChecker, ConsistencyOptions};
 use crossterm::{
 	cursor,
 	terminal::{Clear, ClearType},
 	QueueableCommand,
 };
-use log::{debug, info, warn};
+use log::{debug, error, info, warn};
 use polkadot_introspector_essentials::{
 	api::subxt_wrapper::RequestExecutor,
 	chain_head_subscription::ChainHeadSubscription,
 	chain_subscription::ChainSubscriptionEvent,
+	charts::{self, TimeSeries},
 	constants::MAX_MSG_QUEUE_SIZE,
 	consumer::{EventConsumerInit, EventStream},
-	init, utils,
+	init,
+	metrics::{self, MetricsRegistry},
+	output::{OutputOptions, OutputRecord, OutputWriter},
+	utils,
 };
 use polkadot_introspector_priority_channel::{channel, Receiver, Sender};
-use prometheus_endpoint::{HistogramVec, Registry};
+use prometheus_endpoint::HistogramVec;
+use serde::Serialize;
 use std::{
-	collections::{HashMap, VecDeque},
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
 	io::{stdout, Write},
 	net::ToSocketAddrs,
+	sync::Arc,
 };
 use subxt::config::Header;
 use tokio::select;
 
+mod consistency;
+
 #[derive(Clone, Debug, Parser)]
 #[clap(author, version, about = "Observe block times using an RPC node")]
 struct BlockTimeOptions {
@@ -48,6 +58,9 @@ struct BlockTimeOptions {
 	pub nodes: Vec<String>,
 	#[clap(subcommand)]
 	mode: BlockTimeMode,
+	// In CLI mode, block times are printed as records instead of charts unless the output is pretty
+	#[clap(flatten)]
+	pub output: OutputOptions,
 	#[clap(flatten)]
 	pub verbose: init::VerbosityOptions,
 	#[clap(flatten)]
@@ -60,6 +73,10 @@ enum BlockTimeMode {
 	Cli(BlockTimeCliOptions),
 	/// Prometheus endpoint mode.
 	Prometheus(BlockTimePrometheusOptions),
+	/// Cross-check heads, events and runtime versions served by the endpoints and report the divergent ones.
+	Consistency(ConsistencyOptions),
+	/// Print the Prometheus metrics exported in Prometheus mode and exit.
+	ListMetrics,
 }
 
 #[derive(Clone, Debug, Parser, Default)]
@@ -84,6 +101,24 @@ struct BlockTimePrometheusOptions {
 	port: u16,
 }
 
+/// Block time printed in CLI mode with a machine-readable output format
+#[derive(Debug, Serialize)]
+struct BlockTimeRecord<'a> {
+	node: &'a str,
+	block_number: u32,
+	block_time_ms: u64,
+}
+
+impl OutputRecord for BlockTimeRecord<'_> {
+	const CSV_COLUMNS: &'static [&'static str] = &["node", "block_number", "block_time_ms"];
+}
+
+impl Display for BlockTimeRecord<'_> {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "[{}] Block time of #{}: {} ms", self.node, self.block_number, self.block_time_ms)
+	}
+}
+
 #[derive(Debug)]
 enum BlockTimeMessage {
 	EndpointDisconected,
@@ -96,27 +131,29 @@ struct BlockTimeMonitor {
 	endpoints: Vec<String>,
 	executor: RequestExecutor,
 	active_endpoints: usize,
+	output: Arc<OutputWriter>,
 }
 
 impl BlockTimeMonitor {
 	pub fn new(opts: BlockTimeOptions) -> color_eyre::Result<Self> {
-		let executor = RequestExecutor::new(opts.retry.clone());
+		let executor = RequestExecutor::new(opts.retry.clone(), Default::default());
 		let endpoints = opts.nodes.clone();
 		let active_endpoints = endpoints.len();
+		let output = Arc::new(OutputWriter::new(opts.output.format));
 
 		match opts.clone().mode {
 			BlockTimeMode::Prometheus(prometheus_opts) => {
-				let prometheus_registry = Registry::new_custom(Some("introspector".into()), None)?;
+				let prometheus_registry = MetricsRegistry::new("introspector")?;
 				let block_time_metric = Some(register_metric(&prometheus_registry));
 
 				let socket_addr_str = format!("{}:{}", prometheus_opts.address, prometheus_opts.port);
 				socket_addr_str.to_socket_addrs()?.for_each(|addr| {
-					tokio::spawn(prometheus_endpoint::init_prometheus(addr, prometheus_registry.clone()));
+					tokio::spawn(prometheus_endpoint::init_prometheus(addr, prometheus_registry.registry().clone()));
 				});
-				Ok(BlockTimeMonitor { opts, block_time_metric, endpoints, executor, active_endpoints })
+				Ok(BlockTimeMonitor { opts, block_time_metric, endpoints, executor, active_endpoints, output })
 			},
-			BlockTimeMode::Cli(_) =>
-				Ok(BlockTimeMonitor { opts, block_time_metric: None, endpoints, executor, active_endpoints }),
+			BlockTimeMode::Cli(_) | BlockTimeMode::Consistency(_) | BlockTimeMode::ListMetrics =>
+				Ok(BlockTimeMonitor { opts, block_time_metric: None, endpoints, executor, active_endpoints, output }),
 		}
 	}
 
@@ -140,6 +177,7 @@ impl BlockTimeMonitor {
 					update_channel,
 					self.executor.clone(),
 					message_tx.clone(),
+					self.output.clone(),
 				))
 			})
 			.collect::<Vec<_>>();
@@ -160,8 +198,11 @@ impl BlockTimeMonitor {
 		active_endpoints: usize,
 		message_rx: Receiver<BlockTimeMessage>,
 	) {
+		if !opts.output.format.is_pretty() {
+			return
+		}
 		if let BlockTimeMode::Cli(opts) = opts.mode {
-			let mut values: HashMap<String, VecDeque<u64>> = HashMap::new();
+			let mut values: HashMap<String, TimeSeries> = HashMap::new();
 			let mut update_interval = std::time::Duration::from_secs(0); // The first time to start at once
 
 			loop {
@@ -174,15 +215,8 @@ impl BlockTimeMonitor {
 							Ok(BlockTimeMessage::NewBlockTime(url, block_time)) => {
 								values
 									.entry(url)
-									.and_modify(|v| {
-										v.push_back(block_time);
-										// Remove old data points.
-										let len = v.len();
-										if len > opts.chart_width {
-											v.drain(0..len - opts.chart_width);
-										}
-									})
-									.or_insert(VecDeque::from([block_time]));
+									.or_insert_with(|| TimeSeries::new(opts.chart_width))
+									.push(block_time as f64 / 1000.0);
 							}
 							_ => {}
 						}
@@ -206,28 +240,21 @@ impl BlockTimeMonitor {
 		}
 	}
 
-	fn display_chart(uri: &str, row: u32, values: Option<&VecDeque<u64>>, opts: BlockTimeCliOptions) {
-		use rasciigraph::{plot, Config};
-
+	fn display_chart(uri: &str, row: u32, values: Option<&TimeSeries>, opts: BlockTimeCliOptions) {
 		let _ = stdout().queue(cursor::MoveTo(0, row as u16));
-		if values.is_none() {
-			return
-		}
+		let Some(series) = values.filter(|series| !series.is_empty()) else { return };
 
-		// Get last `term_width` blocks.
+		// The series keeps the last `chart_width` block times, in seconds.
 		let blocks_to_show = opts.chart_width;
-		let current_values = values.unwrap().clone();
-		let len = current_values.len();
-
-		let scaled_values: VecDeque<f64> = current_values.iter().map(|v| *v as f64 / 1000.0).collect();
-		let avg: f64 = scaled_values.iter().sum::<f64>() / len as f64;
-		let min: f64 = scaled_values.iter().fold(f64::INFINITY, |a, &b| a.min(b));
-		let max: f64 = scaled_values.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-		let last = *scaled_values.back().unwrap_or(&0.0);
+		let last = series.last().unwrap_or_default();
+		let avg = series.avg().unwrap_or_default();
+		let min = series.min().unwrap_or_default();
+		let max = series.max().unwrap_or_default();
 		let _ = stdout().write(
-			plot(
-				scaled_values.into(),
-				Config::default().with_height(opts.chart_height as u32).with_caption(format!(
+			charts::plot(
+				series,
+				opts.chart_height,
+				format!(
 					"[DATA: {}] [LAST: {}] [AVG: {}] [MIN: {}] [MAX: {}] [ {} ]",
 					blocks_to_show.to_string().bold(),
 					format!("{last:.2}").bright_purple().underline(),
@@ -235,7 +262,7 @@ impl BlockTimeMonitor {
 					format!("{min:.2}").green().bold(),
 					format!("{max:.2}").red().bold(),
 					format!("Block production latency via '{uri}'").yellow(),
-				)),
+				),
 			)
 			.as_bytes(),
 		);
@@ -249,14 +276,18 @@ impl BlockTimeMonitor {
 		consumer_config: Receiver<ChainSubscriptionEvent>,
 		mut executor: RequestExecutor,
 		mut message_tx: Sender<BlockTimeMessage>,
+		output: Arc<OutputWriter>,
 	) {
 		// Make static string out of uri so we can use it as Prometheus label.
 		let url = leak_static_str(url);
 		match opts.clone().mode {
-			BlockTimeMode::Prometheus(_) => {},
-			BlockTimeMode::Cli(cli_opts) => {
+			BlockTimeMode::Cli(cli_opts) if opts.output.format.is_pretty() => {
 				populate_view(url, cli_opts, message_tx.clone(), executor.clone()).await;
 			},
+			BlockTimeMode::Cli(_) |
+			BlockTimeMode::Prometheus(_) |
+			BlockTimeMode::Consistency(_) |
+			BlockTimeMode::ListMetrics => {},
 		}
 
 		let mut prev_ts = 0;
@@ -270,6 +301,10 @@ impl BlockTimeMonitor {
 					ChainSubscriptionEvent::NewBestHead(v) => v,
 					ChainSubscriptionEvent::NewFinalizedBlock(v) => v,
 					ChainSubscriptionEvent::Heartbeat => continue,
+					ChainSubscriptionEvent::Unreachable(e) => {
+						error!("[{}] {}", url, e);
+						break
+					},
 				};
 				let ts = executor.get_block_timestamp(url, hash).await;
 				if let Ok(ts) = ts {
@@ -279,13 +314,21 @@ impl BlockTimeMonitor {
 						info!("[{}] Block time of #{}: {} ms", url, header.number, block_time_ms);
 
 						match opts.mode {
+							BlockTimeMode::Cli(_) if !opts.output.format.is_pretty() => {
+								let record = BlockTimeRecord { node: url, block_number: header.number, block_time_ms };
+								if let Err(e) = output.print(&record) {
+									warn!("[{}] Cannot print block time: {:?}", url, e);
+								}
+							},
 							BlockTimeMode::Cli(_) => {
 								message_tx
 									.send(BlockTimeMessage::NewBlockTime(url.to_string(), block_time_ms))
 									.await
 									.unwrap();
 							},
-							BlockTimeMode::Prometheus(_) =>
+							BlockTimeMode::Prometheus(_) |
+							BlockTimeMode::Consistency(_) |
+							BlockTimeMode::ListMetrics =>
 								if let Some(metric) = metric.clone() {
 									metric.with_label_values(&[url]).observe(block_time_ms as f64)
 								},
@@ -355,8 +398,8 @@ fn leak_static_str(string: String) -> &'static str {
 	Box::leak(string.into_boxed_str())
 }
 
-fn register_metric(registry: &Registry) -> HistogramVec {
-	prometheus_endpoint::register(
+fn register_metric(registry: &MetricsRegistry) -> HistogramVec {
+	metrics::register(
 		HistogramVec::new(
 			prometheus_endpoint::HistogramOpts::new("block_time", "Time it takes for blocks to be authored.")
 				.buckets(vec![7000.0, 13000.0, 19000.0, 25000.0, 31000.0, 37000.0, 61000.0]),
@@ -371,8 +414,26 @@ fn register_metric(registry: &Registry) -> HistogramVec {
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
 	let opts = BlockTimeOptions::parse();
+	if let BlockTimeMode::ListMetrics = opts.mode {
+		let registry = MetricsRegistry::new("introspector")?;
+		register_metric(&registry);
+		registry.print_catalog();
+		return Ok(())
+	}
 	init::init_cli(&opts.verbose)?;
 
+	if let BlockTimeMode::Consistency(ref consistency_opts) = opts.mode {
+		let checker = ConsistencyChecker::new(
+			opts.nodes.clone(),
+			consistency_opts.clone(),
+			opts.retry.clone(),
+			opts.output.format,
+		)?;
+		let shutdown_tx = init::init_shutdown();
+		init::run(vec![tokio::spawn(checker.run(shutdown_tx.subscribe()))], &shutdown_tx).await?;
+		return Ok(())
+	}
+
 	let monitor = BlockTimeMonitor::new(opts.clone())?;
 	let shutdown_tx = init::init_shutdown();
 	let mut futures = vec![];
@@ -0,0 +1,8 @@
+// Generated by scripts/update_metadata.sh, do not edit.
+[
+	VendoredMetadata {
+		spec_name: "polkadot",
+		spec_version: 1000001,
+		path: "assets/polkadot_metadata.scale",
+	},
+]
@@ -0,0 +1,178 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Decoding of the paras inherent data by the parachain trackers.
+//!
+//! Every tracker used to read the encoded inherent data of a relay chain block from the collector storage and decode
+//! it, so the data of a block was copied and decoded once per traced parachain. Now the collector decodes it once into
+//! the `BlockContext` of the block, which the trackers share behind an `Arc`. Compare both with
+//! `cargo bench -p polkadot-introspector-essentials --bench inherent_data`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use parity_scale_codec::{Decode, Encode};
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::InherentData,
+	collector::block_context::BlockContext,
+	metadata::{
+		polkadot::runtime_types::{
+			bounded_collections::bounded_vec::BoundedVec,
+			polkadot_parachain::primitives::{HeadData, Id, ValidationCodeHash},
+			sp_core::sr25519::{Public, Signature},
+			sp_runtime::generic::{digest::Digest, header::Header as RuntimeHeader},
+		},
+		polkadot_primitives::{
+			collator_app, signed::UncheckedSigned, validator_app, AvailabilityBitfield, BackedCandidate,
+			CandidateCommitments, CandidateDescriptor, CommittedCandidateReceipt, ValidatorIndex,
+		},
+	},
+	types::{Header, H256},
+};
+use std::{collections::BTreeMap, sync::Arc};
+use subxt::utils::bits::DecodedBits;
+
+/// Parachains backing a candidate in every block, each one traced
+const PARAS: u32 = 50;
+/// Validators signing a bitfield in every block
+const VALIDATORS: u32 = 300;
+
+fn backed_candidate(para_id: u32) -> BackedCandidate<H256> {
+	BackedCandidate {
+		candidate: CommittedCandidateReceipt {
+			descriptor: CandidateDescriptor {
+				para_id: Id(para_id),
+				relay_parent: H256::repeat_byte(1),
+				collator: collator_app::Public(Public([0; 32])),
+				persisted_validation_data_hash: Default::default(),
+				pov_hash: Default::default(),
+				erasure_root: Default::default(),
+				signature: collator_app::Signature(Signature([0; 64])),
+				para_head: Default::default(),
+				validation_code_hash: ValidationCodeHash(Default::default()),
+			},
+			commitments: CandidateCommitments {
+				upward_messages: BoundedVec(vec![vec![0; 128]; 4]),
+				horizontal_messages: BoundedVec(Default::default()),
+				new_validation_code: Default::default(),
+				head_data: HeadData(vec![0; 256]),
+				processed_downward_messages: 2,
+				hrmp_watermark: 0,
+			},
+		},
+		validity_votes: Default::default(),
+		validator_indices: DecodedBits::from_iter([true; 5]),
+	}
+}
+
+fn inherent_data() -> InherentData {
+	InherentData {
+		bitfields: (0..VALIDATORS)
+			.map(|index| UncheckedSigned {
+				payload: AvailabilityBitfield(DecodedBits::from_iter((0..PARAS).map(|core| core % 3 != 0))),
+				validator_index: ValidatorIndex(index),
+				signature: validator_app::Signature(Signature([0; 64])),
+				__subxt_unused_type_params: Default::default(),
+			})
+			.collect(),
+		backed_candidates: (0..PARAS).map(|para_id| backed_candidate(1000 + para_id)).collect(),
+		disputes: vec![],
+		parent_header: RuntimeHeader {
+			parent_hash: H256::repeat_byte(2),
+			number: 42,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Digest { logs: Default::default() },
+			__subxt_unused_type_params: Default::default(),
+		},
+	}
+}
+
+/// Decodes the inherent data into the context of a block, as the collector does once per block
+fn block_context(encoded: &[u8]) -> Arc<BlockContext> {
+	Arc::new(BlockContext {
+		block_hash: H256::repeat_byte(3),
+		block_number: 43,
+		header: Header {
+			parent_hash: H256::repeat_byte(2),
+			number: 43,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		},
+		timestamp: 0,
+		session_index: 0,
+		spec_version: 0,
+		inherent_data: Some(Arc::new(InherentData::decode(&mut &encoded[..]).unwrap())),
+		occupied_cores: vec![],
+		backing_groups: vec![],
+		core_assignments: BTreeMap::new(),
+	})
+}
+
+/// Counts the candidate and availability bits a tracker looks up in the inherent data
+fn process(data: &InherentData, para_id: u32) -> usize {
+	let core = (para_id - 1000) as usize;
+	let backed = data
+		.backed_candidates
+		.iter()
+		.filter(|candidate| candidate.candidate.descriptor.para_id.0 == para_id)
+		.count();
+	let bits = data
+		.bitfields
+		.iter()
+		.filter(|bitfield| bitfield.payload.0.as_bits().get(core).unwrap_or_default())
+		.count();
+	backed + bits
+}
+
+fn bench_inherent_data(c: &mut Criterion) {
+	let encoded = inherent_data().encode();
+	let mut group = c.benchmark_group("inherent_data");
+	for trackers in [1, 10, PARAS] {
+		group.bench_with_input(BenchmarkId::new("decode_per_tracker", trackers), &trackers, |b, trackers| {
+			b.iter(|| {
+				(1000..1000 + trackers)
+					.map(|para_id| {
+						// A storage read returns a copy of the encoded entry
+						let entry = encoded.clone();
+						let data = InherentData::decode(&mut entry.as_slice()).unwrap();
+						process(&data, para_id)
+					})
+					.sum::<usize>()
+			})
+		});
+		group.bench_with_input(BenchmarkId::new("shared_block_context", trackers), &trackers, |b, trackers| {
+			b.iter(|| {
+				let contexts = vec![block_context(&encoded)];
+				(1000..1000 + trackers)
+					.map(|para_id| {
+						// Every tracker gets the contexts of the new head event and looks the block up
+						let contexts = contexts.clone();
+						let context = contexts
+							.iter()
+							.find(|context| context.block_hash == H256::repeat_byte(3))
+							.unwrap();
+						let data = context.inherent_data.clone().unwrap();
+						process(&data, para_id)
+					})
+					.sum::<usize>()
+			})
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, bench_inherent_data);
+criterion_main!(benches);
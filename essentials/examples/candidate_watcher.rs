@@ -0,0 +1,107 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Candidate watcher: follows the candidates of a parachain through the collector storage.
+//!
+//! The collector stores a record per candidate under the `Candidate(para_id)` prefix and replaces it when the
+//! candidate is included, timed out or disputed, so a subscription to the prefix yields every state change.
+//!
+//! ```text
+//! cargo run -p polkadot-introspector-essentials --example candidate_watcher -- --ws wss://rpc.polkadot.io:443 --para-id 1000
+//! ```
+
+use clap::Parser;
+use polkadot_introspector_essentials::{
+	chain_head_subscription::ChainHeadSubscription,
+	chain_subscription::ChainSubscriptionEvent,
+	collector::{candidate_record::CandidateRecord, Collector, CollectorOptions, CollectorPrefixType},
+	consumer::EventStream,
+	init,
+	storage::{StorageUpdate, StorageUpdateKind},
+	types::H256,
+	utils::RetryOptions,
+};
+use polkadot_introspector_priority_channel::Receiver;
+use tokio::sync::{broadcast, mpsc};
+
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+struct CandidateWatcherOptions {
+	/// Websockets URL of a relay chain node
+	#[clap(name = "ws", long, default_value = "wss://rpc.polkadot.io:443")]
+	node: String,
+	/// Parachain id
+	#[clap(long)]
+	para_id: u32,
+	#[clap(flatten)]
+	collector_opts: CollectorOptions,
+	#[clap(flatten)]
+	verbose: init::VerbosityOptions,
+	#[clap(flatten)]
+	retry: RetryOptions,
+}
+
+async fn watch(
+	mut updates: mpsc::Receiver<StorageUpdate<H256, CollectorPrefixType>>,
+	mut shutdown_rx: broadcast::Receiver<()>,
+) {
+	loop {
+		let update = tokio::select! {
+			Some(update) = updates.recv() => update,
+			_ = shutdown_rx.recv() => break,
+		};
+		let record = match update.entry.into_inner::<CandidateRecord>() {
+			Ok(record) => record.candidate_inclusion,
+			Err(err) => {
+				log::warn!("cannot decode candidate {:?}: {:?}", update.key, err);
+				continue
+			},
+		};
+		let state = match (update.kind, record.included, record.timedout) {
+			(StorageUpdateKind::Inserted, _, _) => format!("backed at #{}", record.backed),
+			(StorageUpdateKind::Replaced, Some(included), _) => format!("included at #{}", included),
+			(StorageUpdateKind::Replaced, _, Some(timedout)) => format!("timed out at #{}", timedout),
+			(StorageUpdateKind::Replaced, None, None) => "disputed".to_string(),
+		};
+		println!("[{}] candidate {:?} {}", record.parachain_id, update.key, state);
+	}
+}
+
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
+	let opts = CandidateWatcherOptions::parse();
+	init::init_cli(&opts.verbose)?;
+
+	let shutdown_tx = init::init_shutdown();
+	let mut collector =
+		Collector::new(&opts.node, opts.collector_opts.clone(), opts.retry.clone(), Default::default())?;
+	collector.spawn(&shutdown_tx).await?;
+	let updates = collector
+		.api()
+		.storage()
+		.storage_subscribe_prefixed(CollectorPrefixType::Candidate(opts.para_id))
+		.await?;
+
+	let mut sub = ChainHeadSubscription::new(vec![opts.node.clone()], opts.retry.clone());
+	let consumer_channels: Vec<Receiver<ChainSubscriptionEvent>> = sub.create_consumer().into();
+	let mut futures = vec![
+		tokio::spawn(watch(updates, shutdown_tx.subscribe())),
+		collector
+			.run_with_consumer_channel(consumer_channels.into_iter().next().unwrap())
+			.await,
+	];
+	futures.extend(sub.run(&shutdown_tx).await?);
+	init::run(futures, &shutdown_tx).await
+}
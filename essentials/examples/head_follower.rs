@@ -0,0 +1,92 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Minimal head follower: prints every new best and finalized relay chain block.
+//!
+//! ```text
+//! cargo run -p polkadot-introspector-essentials --example head_follower -- --ws wss://rpc.polkadot.io:443
+//! ```
+
+use clap::Parser;
+use futures::StreamExt;
+use polkadot_introspector_essentials::{
+	chain_head_subscription::ChainHeadSubscription,
+	chain_subscription::ChainSubscriptionEvent,
+	consumer::{EventConsumerInit, EventStream},
+	init,
+	utils::RetryOptions,
+};
+use polkadot_introspector_priority_channel::Receiver;
+use tokio::{sync::broadcast::Sender, task::JoinHandle};
+
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+struct HeadFollowerOptions {
+	/// Websockets URLs of the relay chain nodes
+	#[clap(name = "ws", long, value_delimiter = ',', default_value = "wss://rpc.polkadot.io:443")]
+	nodes: Vec<String>,
+	#[clap(flatten)]
+	verbose: init::VerbosityOptions,
+	#[clap(flatten)]
+	retry: RetryOptions,
+}
+
+/// Spawns a task per node printing the heads it sends
+fn follow(
+	nodes: Vec<String>,
+	consumer_init: EventConsumerInit<ChainSubscriptionEvent>,
+	shutdown_tx: &Sender<()>,
+) -> Vec<JoinHandle<()>> {
+	let consumer_channels: Vec<Receiver<ChainSubscriptionEvent>> = consumer_init.into();
+	let mut futures = vec![];
+	for (node, mut from_node) in nodes.into_iter().zip(consumer_channels) {
+		let mut shutdown_rx = shutdown_tx.subscribe();
+		futures.push(tokio::spawn(async move {
+			loop {
+				tokio::select! {
+					event = from_node.next() => match event {
+						Some(ChainSubscriptionEvent::NewBestHead((hash, header))) =>
+							println!("[{}] best #{} {:?}", node, header.number, hash),
+						Some(ChainSubscriptionEvent::NewFinalizedBlock((hash, header))) =>
+							println!("[{}] finalized #{} {:?}", node, header.number, hash),
+						Some(ChainSubscriptionEvent::Heartbeat) => continue,
+						Some(ChainSubscriptionEvent::Unreachable(e)) => {
+							eprintln!("[{}] {}", node, e);
+							break
+						},
+						None => break,
+					},
+					_ = shutdown_rx.recv() => break,
+				}
+			}
+		}));
+	}
+
+	futures
+}
+
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
+	let opts = HeadFollowerOptions::parse();
+	init::init_cli(&opts.verbose)?;
+
+	let shutdown_tx = init::init_shutdown();
+	let mut sub = ChainHeadSubscription::new(opts.nodes.clone(), opts.retry.clone());
+	let consumer_init = sub.create_consumer();
+
+	let mut futures = follow(opts.nodes, consumer_init, &shutdown_tx);
+	futures.extend(sub.run(&shutdown_tx).await?);
+	init::run(futures, &shutdown_tx).await
+}
@@ -0,0 +1,107 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Custom Prometheus exporter: exports the finalized block number and timestamp of the relay chain.
+//!
+//! Metrics are registered in a [`MetricsRegistry`] with [`metrics::register`], so they are also listed in its catalog.
+//!
+//! ```text
+//! cargo run -p polkadot-introspector-essentials --example prometheus_exporter -- --ws wss://rpc.polkadot.io:443
+//! curl -s localhost:65433/metrics | grep example_
+//! ```
+
+use clap::Parser;
+use futures::StreamExt;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::RequestExecutor,
+	chain_head_subscription::ChainHeadSubscription,
+	chain_subscription::ChainSubscriptionEvent,
+	consumer::EventStream,
+	init,
+	metrics::{self, MetricsRegistry},
+	utils::RetryOptions,
+};
+use polkadot_introspector_priority_channel::Receiver;
+use prometheus_endpoint::prometheus::IntGauge;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+struct PrometheusExporterOptions {
+	/// Websockets URL of a relay chain node
+	#[clap(name = "ws", long, default_value = "wss://rpc.polkadot.io:443")]
+	node: String,
+	/// Address to bind Prometheus listener
+	#[clap(short = 'a', long = "address", default_value = "0.0.0.0:65433")]
+	address: String,
+	#[clap(flatten)]
+	verbose: init::VerbosityOptions,
+	#[clap(flatten)]
+	retry: RetryOptions,
+}
+
+#[derive(Clone)]
+struct Metrics {
+	finalized_block_number: IntGauge,
+	finalized_block_timestamp: IntGauge,
+}
+
+fn register_metrics(registry: &MetricsRegistry) -> color_eyre::Result<Metrics> {
+	Ok(Metrics {
+		finalized_block_number: metrics::register(
+			IntGauge::new("example_finalized_block_number", "Number of the last finalized relay chain block")?,
+			registry,
+		)?,
+		finalized_block_timestamp: metrics::register(
+			IntGauge::new("example_finalized_block_timestamp", "Timestamp of the last finalized relay chain block")?,
+			registry,
+		)?,
+	})
+}
+
+async fn export(node: String, mut from_node: Receiver<ChainSubscriptionEvent>, retry: RetryOptions, metrics: Metrics) {
+	let mut executor = RequestExecutor::new(retry, Default::default());
+	while let Some(event) = from_node.next().await {
+		if let ChainSubscriptionEvent::NewFinalizedBlock((hash, header)) = event {
+			metrics.finalized_block_number.set(header.number as i64);
+			match executor.get_block_timestamp(&node, hash).await {
+				Ok(ts) => metrics.finalized_block_timestamp.set(ts as i64),
+				Err(err) => log::warn!("cannot get timestamp of {:?}: {:?}", hash, err),
+			}
+		}
+	}
+}
+
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
+	let opts = PrometheusExporterOptions::parse();
+	init::init_cli(&opts.verbose)?;
+
+	let registry = MetricsRegistry::new("introspector")?;
+	let metrics = register_metrics(&registry)?;
+	let addrs: Vec<SocketAddr> = opts.address.to_socket_addrs()?.collect();
+	for addr in addrs {
+		tokio::spawn(prometheus_endpoint::init_prometheus(addr, registry.registry().clone()));
+	}
+
+	let shutdown_tx = init::init_shutdown();
+	let mut sub = ChainHeadSubscription::new(vec![opts.node.clone()], opts.retry.clone()).finalized_only();
+	let consumer_channels: Vec<Receiver<ChainSubscriptionEvent>> = sub.create_consumer().into();
+	let from_node = consumer_channels.into_iter().next().unwrap();
+
+	let mut futures = vec![tokio::spawn(export(opts.node, from_node, opts.retry, metrics))];
+	futures.extend(sub.run(&shutdown_tx).await?);
+	init::run(futures, &shutdown_tx).await
+}
@@ -0,0 +1,27 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Decodes arbitrary frames as they may come from a third-party telemetry server, any panic is a bug.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use polkadot_introspector_essentials::telemetry_feed::TelemetryFeed;
+
+fuzz_target!(|data: &[u8]| {
+	let _ = TelemetryFeed::from_bytes(data);
+});
@@ -0,0 +1,381 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Anomaly detection on normalized per-block observations
+//!
+//! A detector consumes observations of relay chain blocks in order and returns the anomalies it has found.
+//! Built-in detectors are created by name, custom ones are registered with `AnomalyDetectors::register`,
+//! so tools built on this library can add their own checks without changes to the tracer.
+
+use crate::{
+	constants::STANDARD_BLOCK_TIME,
+	incidents::{Incident, IncidentSource, Severity},
+	observation::BlockObservation,
+	types::{BlockNumber, Timestamp},
+};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+};
+
+/// An anomaly found in a relay chain block
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anomaly {
+	/// Name of the detector
+	pub detector: String,
+	pub severity: Severity,
+	/// Relay chain block the anomaly was found in
+	pub block_number: BlockNumber,
+	/// Relay chain block timestamp
+	pub timestamp: Timestamp,
+	/// Parachain the anomaly relates to, if any
+	pub para_id: Option<u32>,
+	pub description: String,
+}
+
+impl Display for Anomaly {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "[#{}] {} anomaly ({}): {}", self.block_number, self.detector, self.severity, self.description)
+	}
+}
+
+impl From<&Anomaly> for Incident {
+	fn from(v: &Anomaly) -> Self {
+		Incident::new(
+			v.timestamp,
+			v.severity,
+			IncidentSource::Anomaly,
+			Some(v.block_number),
+			format!("{}: {}", v.detector, v.description),
+		)
+	}
+}
+
+/// Finds anomalies in normalized per-block observations
+pub trait AnomalyDetector: Send {
+	/// Name of the detector, reported with its anomalies
+	fn name(&self) -> &str;
+	/// Processes the observation of the next relay chain block, forks included
+	fn on_observation(&mut self, observation: &BlockObservation) -> Vec<Anomaly>;
+}
+
+/// Names of the built-in detectors
+pub const BUILTIN_DETECTORS: [&str; 3] = [SlowBlockDetector::NAME, LowBitfieldsDetector::NAME, IdleCoreDetector::NAME];
+
+/// Returns a built-in detector with the default settings
+pub fn builtin_detector(name: &str) -> Option<Box<dyn AnomalyDetector>> {
+	match name {
+		SlowBlockDetector::NAME => Some(Box::<SlowBlockDetector>::default()),
+		LowBitfieldsDetector::NAME => Some(Box::new(LowBitfieldsDetector)),
+		IdleCoreDetector::NAME => Some(Box::<IdleCoreDetector>::default()),
+		_ => None,
+	}
+}
+
+/// Registered detectors, all of them see every observation
+#[derive(Default)]
+pub struct AnomalyDetectors {
+	detectors: Vec<Box<dyn AnomalyDetector>>,
+}
+
+impl AnomalyDetectors {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a detector
+	pub fn register(&mut self, detector: Box<dyn AnomalyDetector>) -> &mut Self {
+		self.detectors.push(detector);
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.detectors.is_empty()
+	}
+
+	/// Returns names of the registered detectors
+	pub fn names(&self) -> Vec<&str> {
+		self.detectors.iter().map(|detector| detector.name()).collect()
+	}
+
+	/// Passes the observation to all detectors, returns anomalies in the order of registration
+	pub fn on_observation(&mut self, observation: &BlockObservation) -> Vec<Anomaly> {
+		self.detectors
+			.iter_mut()
+			.flat_map(|detector| detector.on_observation(observation))
+			.collect()
+	}
+}
+
+/// Relay chain blocks produced later than expected
+pub struct SlowBlockDetector {
+	/// Time between blocks after which a block is slow, in milliseconds
+	threshold: u64,
+	/// The last block on the best chain
+	previous: Option<(BlockNumber, Timestamp)>,
+}
+
+impl SlowBlockDetector {
+	pub const NAME: &'static str = "slow-block";
+
+	pub fn new(threshold_ms: u64) -> Self {
+		Self { threshold: threshold_ms, previous: None }
+	}
+}
+
+impl Default for SlowBlockDetector {
+	/// A block is slow if at least one slot was missed
+	fn default() -> Self {
+		Self::new((STANDARD_BLOCK_TIME * 2000.0) as u64)
+	}
+}
+
+impl AnomalyDetector for SlowBlockDetector {
+	fn name(&self) -> &str {
+		Self::NAME
+	}
+
+	fn on_observation(&mut self, observation: &BlockObservation) -> Vec<Anomaly> {
+		let previous = self.previous;
+		// Forks and gaps are not compared
+		if previous.is_some_and(|(block_number, _)| observation.block_number <= block_number) {
+			return vec![]
+		}
+		self.previous = Some((observation.block_number, observation.timestamp));
+
+		match previous {
+			Some((block_number, timestamp))
+				if observation.block_number == block_number + 1 &&
+					observation.timestamp.saturating_sub(timestamp) >= self.threshold =>
+				vec![Anomaly {
+					detector: Self::NAME.to_string(),
+					severity: Severity::Warning,
+					block_number: observation.block_number,
+					timestamp: observation.timestamp,
+					para_id: None,
+					description: format!(
+						"block produced {:.1}s after its parent",
+						observation.timestamp.saturating_sub(timestamp) as f64 / 1000.0
+					),
+				}],
+			_ => vec![],
+		}
+	}
+}
+
+/// Relay chain blocks with bitfields of less than the supermajority of validators, so no candidate can become
+/// available in them
+pub struct LowBitfieldsDetector;
+
+impl LowBitfieldsDetector {
+	pub const NAME: &'static str = "low-bitfields";
+}
+
+impl AnomalyDetector for LowBitfieldsDetector {
+	fn name(&self) -> &str {
+		Self::NAME
+	}
+
+	fn on_observation(&mut self, observation: &BlockObservation) -> Vec<Anomaly> {
+		if observation.validators_count == 0 || observation.bitfields_count * 3 > observation.validators_count * 2 {
+			return vec![]
+		}
+
+		vec![Anomaly {
+			detector: Self::NAME.to_string(),
+			severity: Severity::Warning,
+			block_number: observation.block_number,
+			timestamp: observation.timestamp,
+			para_id: None,
+			description: format!(
+				"{} bitfields of {} validators, less than the supermajority",
+				observation.bitfields_count, observation.validators_count
+			),
+		}]
+	}
+}
+
+/// Cores which stay free while parachains are scheduled on them, but no candidates are backed
+pub struct IdleCoreDetector {
+	/// Number of consecutive blocks after which an idle core is reported
+	threshold: u32,
+	/// Consecutive idle blocks per core
+	idle_blocks: HashMap<u32, u32>,
+	/// The last processed block, forks are skipped
+	last_block: Option<BlockNumber>,
+}
+
+impl IdleCoreDetector {
+	pub const NAME: &'static str = "idle-core";
+
+	pub fn new(threshold: u32) -> Self {
+		Self { threshold: threshold.max(1), idle_blocks: Default::default(), last_block: None }
+	}
+}
+
+impl Default for IdleCoreDetector {
+	fn default() -> Self {
+		Self::new(3)
+	}
+}
+
+impl AnomalyDetector for IdleCoreDetector {
+	fn name(&self) -> &str {
+		Self::NAME
+	}
+
+	fn on_observation(&mut self, observation: &BlockObservation) -> Vec<Anomaly> {
+		if self
+			.last_block
+			.is_some_and(|block_number| observation.block_number <= block_number)
+		{
+			return vec![]
+		}
+		self.last_block = Some(observation.block_number);
+
+		let mut anomalies = vec![];
+		for core in &observation.cores {
+			let is_idle = !core.occupied &&
+				!core.scheduled_paras.is_empty() &&
+				!observation
+					.candidates
+					.iter()
+					.any(|candidate| core.scheduled_paras.contains(&candidate.para_id));
+			if !is_idle {
+				self.idle_blocks.remove(&core.core_index);
+				continue
+			}
+
+			let idle_blocks = self.idle_blocks.entry(core.core_index).or_default();
+			*idle_blocks += 1;
+			// Reported once when the threshold is reached
+			if *idle_blocks == self.threshold {
+				anomalies.push(Anomaly {
+					detector: Self::NAME.to_string(),
+					severity: Severity::Info,
+					block_number: observation.block_number,
+					timestamp: observation.timestamp,
+					para_id: core.scheduled_paras.first().copied(),
+					description: format!(
+						"core {} is idle for {} blocks with parachain(s) {:?} scheduled",
+						core.core_index, idle_blocks, core.scheduled_paras
+					),
+				});
+			}
+		}
+		anomalies
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::observation::{CandidateObservation, CoreObservation};
+
+	fn observation(block_number: BlockNumber, timestamp: Timestamp) -> BlockObservation {
+		BlockObservation { block_number, timestamp, bitfields_count: 3, validators_count: 3, ..Default::default() }
+	}
+
+	#[test]
+	fn test_detects_slow_blocks() {
+		let mut detector = SlowBlockDetector::default();
+		assert!(detector.on_observation(&observation(1, 0)).is_empty());
+		assert!(detector.on_observation(&observation(2, 6000)).is_empty());
+		// A fork of the previous block
+		assert!(detector.on_observation(&observation(2, 30000)).is_empty());
+
+		let anomalies = detector.on_observation(&observation(3, 18000));
+		assert_eq!(anomalies.len(), 1);
+		assert_eq!(anomalies[0].description, "block produced 12.0s after its parent");
+		// Blocks are missing in between
+		assert!(detector.on_observation(&observation(5, 60000)).is_empty());
+	}
+
+	#[test]
+	fn test_detects_low_bitfields() {
+		let mut detector = LowBitfieldsDetector;
+		assert!(detector.on_observation(&observation(1, 0)).is_empty());
+		let anomalies = detector.on_observation(&BlockObservation { bitfields_count: 2, ..observation(2, 0) });
+		assert_eq!(anomalies[0].severity, Severity::Warning);
+	}
+
+	#[test]
+	fn test_detects_idle_cores() {
+		let mut detector = IdleCoreDetector::new(2);
+		let idle = BlockObservation {
+			cores: vec![CoreObservation { core_index: 1, occupied: false, scheduled_paras: vec![100] }],
+			..observation(1, 0)
+		};
+		let backed = BlockObservation {
+			candidates: vec![CandidateObservation { para_id: 100, ..Default::default() }],
+			..idle.clone()
+		};
+
+		assert!(detector.on_observation(&idle).is_empty());
+		let anomalies = detector.on_observation(&BlockObservation { block_number: 2, ..idle.clone() });
+		assert_eq!((anomalies.len(), anomalies[0].para_id), (1, Some(100)));
+		// Reported once
+		assert!(detector
+			.on_observation(&BlockObservation { block_number: 3, ..idle.clone() })
+			.is_empty());
+		// A backed candidate resets the counter
+		assert!(detector
+			.on_observation(&BlockObservation { block_number: 4, ..backed })
+			.is_empty());
+		assert!(detector
+			.on_observation(&BlockObservation { block_number: 5, ..idle.clone() })
+			.is_empty());
+		assert_eq!(detector.on_observation(&BlockObservation { block_number: 6, ..idle }).len(), 1);
+	}
+
+	struct EmptyBlocks;
+
+	impl AnomalyDetector for EmptyBlocks {
+		fn name(&self) -> &str {
+			"empty-blocks"
+		}
+
+		fn on_observation(&mut self, observation: &BlockObservation) -> Vec<Anomaly> {
+			if !observation.candidates.is_empty() {
+				return vec![]
+			}
+			vec![Anomaly {
+				detector: self.name().to_string(),
+				severity: Severity::Info,
+				block_number: observation.block_number,
+				timestamp: observation.timestamp,
+				para_id: None,
+				description: "no candidates backed".to_string(),
+			}]
+		}
+	}
+
+	#[test]
+	fn test_registers_custom_detectors() {
+		let mut detectors = AnomalyDetectors::new();
+		assert!(detectors.is_empty());
+		detectors
+			.register(builtin_detector(LowBitfieldsDetector::NAME).unwrap())
+			.register(Box::new(EmptyBlocks));
+		assert_eq!(detectors.names(), vec!["low-bitfields", "empty-blocks"]);
+		assert!(builtin_detector("unknown").is_none());
+
+		let anomalies = detectors.on_observation(&BlockObservation { bitfields_count: 0, ..observation(1, 0) });
+		let names: Vec<_> = anomalies.iter().map(|anomaly| anomaly.detector.as_str()).collect();
+		assert_eq!(names, vec!["low-bitfields", "empty-blocks"]);
+		assert_eq!(Incident::from(&anomalies[1]).description, "empty-blocks: no candidates backed");
+	}
+}
@@ -15,7 +15,11 @@
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use crate::types::{BlockNumber, Header, H256};
+use crate::{
+	runtime_support::RuntimeVersion,
+	types::{BlockNumber, Header, H256},
+};
+use std::fmt::{self, Debug, Formatter};
 use subxt::{
 	backend::{
 		legacy::{rpc_methods::NumberOrHex, LegacyRpcMethods},
@@ -28,6 +32,34 @@ use subxt::{
 	OnlineClient, PolkadotConfig,
 };
 
+/// Builds the RPC clients of the API clients, connecting to the nodes by their URLs unless a client served within
+/// the process, e.g. from a node database, is registered for the URL
+#[derive(Clone, Default)]
+pub struct RpcClientFactory(Vec<(String, RpcClient)>);
+
+impl RpcClientFactory {
+	/// Registers an RPC client for a URL, clients built for the URL use it instead of connecting to a node
+	pub fn register(&mut self, url: &str, rpc_client: RpcClient) {
+		self.0.retain(|(client_url, _)| client_url != url);
+		self.0.push((url.to_string(), rpc_client));
+	}
+
+	pub async fn build(&self, url: &str) -> Result<RpcClient, String> {
+		match self.0.iter().find(|(client_url, _)| client_url == url) {
+			Some((_, rpc_client)) => Ok(rpc_client.clone()),
+			None => RpcClient::from_url(url)
+				.await
+				.map_err(|e| format!("Cannot construct RPC client: {e}")),
+		}
+	}
+}
+
+impl Debug for RpcClientFactory {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		f.debug_list().entries(self.0.iter().map(|(url, _)| url)).finish()
+	}
+}
+
 #[derive(Clone)]
 pub struct ApiClient {
 	client: OnlineClient<PolkadotConfig>,
@@ -37,10 +69,8 @@ pub struct ApiClient {
 pub type HeaderStream = StreamOf<Result<(Header, BlockRef<H256>), subxt::Error>>;
 
 impl ApiClient {
-	pub async fn build(url: &str) -> Result<ApiClient, String> {
-		let rpc_client = RpcClient::from_url(url)
-			.await
-			.map_err(|e| format!("Cannot construct RPC client: {e}"))?;
+	pub async fn build(url: &str, client_factory: &RpcClientFactory) -> Result<ApiClient, String> {
+		let rpc_client = client_factory.build(url).await?;
 		let client = OnlineClient::from_rpc_client(rpc_client.clone())
 			.await
 			.map_err(|e| format!("Cannot construct OnlineClient from rpc client: {e}"))?;
@@ -70,6 +100,33 @@ impl ApiClient {
 		self.legacy_rpc_methods.chain_get_block_hash(maybe_block_number).await
 	}
 
+	// Used to follow the finalized chain by polling when subscriptions are not available
+	pub async fn legacy_get_finalized_head(&self) -> Result<H256, subxt::Error> {
+		self.legacy_rpc_methods.chain_get_finalized_head().await
+	}
+
+	// Runtime API calls need the runtime of the block, but the version itself is available only via legacy RPC
+	pub async fn legacy_get_spec_version(&self, hash: H256) -> Result<u32, subxt::Error> {
+		Ok(self
+			.legacy_rpc_methods
+			.state_get_runtime_version(Some(hash))
+			.await?
+			.spec_version)
+	}
+
+	// The spec name is not a part of the metadata, so it's also only available via legacy RPC
+	pub async fn legacy_get_runtime_version(&self) -> Result<RuntimeVersion, subxt::Error> {
+		let version = self.legacy_rpc_methods.state_get_runtime_version(None).await?;
+		let spec_name = version
+			.other
+			.get("specName")
+			.and_then(|spec_name| spec_name.as_str())
+			.unwrap_or_default()
+			.to_string();
+
+		Ok(RuntimeVersion { spec_name, spec_version: version.spec_version })
+	}
+
 	pub async fn stream_best_block_headers(&self) -> Result<HeaderStream, subxt::Error> {
 		self.client.backend().stream_best_block_headers().await
 	}
@@ -1,4 +1,7 @@
-use super::subxt_wrapper::SubxtWrapperError::{self, DecodeDynamicError};
+use super::subxt_wrapper::{
+	SubxtHrmpChannel,
+	SubxtWrapperError::{self, DecodeDynamicError},
+};
 use crate::{
 	metadata::{
 		polkadot::runtime_types::{
@@ -6,7 +9,10 @@ use crate::{
 		},
 		polkadot_primitives::{CoreIndex, ValidatorIndex},
 	},
-	types::{Assignment, BlockNumber, ClaimQueue, CoreAssignment, CoreOccupied, OnDemandOrder, ParasEntry},
+	types::{
+		AccountId32, Assignment, BlockNumber, ClaimQueue, CoreAssignment, CoreOccupied, ExtrinsicWeight, OnDemandOrder,
+		ParasEntry, H256,
+	},
 };
 use log::error;
 use std::collections::{BTreeMap, VecDeque};
@@ -30,6 +36,69 @@ pub(crate) fn decode_validator_groups(raw_groups: &Value<u32>) -> Result<Vec<Vec
 	Ok(groups)
 }
 
+pub(crate) fn decode_session_start_block(raw_block_number: &Value<u32>) -> Result<u32, SubxtWrapperError> {
+	Ok(decode_u128_value(raw_block_number)? as u32)
+}
+
+pub(crate) fn decode_timestamp(raw_timestamp: &Value<u32>) -> Result<u64, SubxtWrapperError> {
+	Ok(decode_u128_value(raw_timestamp)? as u64)
+}
+
+pub(crate) fn decode_session_index(raw_session_index: &Value<u32>) -> Result<u32, SubxtWrapperError> {
+	Ok(decode_u128_value(raw_session_index)? as u32)
+}
+
+pub(crate) fn decode_head_data(raw_head: &Value<u32>) -> Result<Vec<u8>, SubxtWrapperError> {
+	match decode_unnamed_composite(raw_head)?[..] {
+		[ref bytes] => decode_bytes(bytes),
+		_ => Err(DecodeDynamicError("head data".to_string(), raw_head.value.clone())),
+	}
+}
+
+pub(crate) fn decode_account_keys(raw_keys: &Value<u32>) -> Result<Vec<AccountId32>, SubxtWrapperError> {
+	decode_unnamed_composite(raw_keys)?
+		.iter()
+		.map(|raw_account| decode_fixed_bytes(raw_account).map(AccountId32))
+		.collect()
+}
+
+pub(crate) fn decode_active_validator_indices(raw_session_info: &Value<u32>) -> Result<Vec<u32>, SubxtWrapperError> {
+	decode_unnamed_composite(value_at("active_validator_indices", raw_session_info)?)?
+		.iter()
+		.map(|raw_index| decode_composite_u128_value(raw_index).map(|v| v as u32))
+		.collect()
+}
+
+pub(crate) fn decode_para_ids(raw_ids: &Value<u32>) -> Result<Vec<u32>, SubxtWrapperError> {
+	decode_unnamed_composite(raw_ids)?
+		.iter()
+		.map(|raw_id| decode_composite_u128_value(raw_id).map(|v| v as u32))
+		.collect()
+}
+
+pub(crate) fn decode_hrmp_channel(raw_channel: &Value<u32>) -> Result<SubxtHrmpChannel, SubxtWrapperError> {
+	let u32_at = |field: &str| decode_u128_value(value_at(field, raw_channel)?).map(|v| v as u32);
+	let mqc_head = match decode_option(value_at("mqc_head", raw_channel)?)? {
+		Some(raw_head) => Some(H256(decode_fixed_bytes(raw_head)?)),
+		None => None,
+	};
+
+	Ok(SubxtHrmpChannel {
+		max_capacity: u32_at("max_capacity")?,
+		max_total_size: u32_at("max_total_size")?,
+		max_message_size: u32_at("max_message_size")?,
+		msg_count: u32_at("msg_count")?,
+		total_size: u32_at("total_size")?,
+		mqc_head,
+		sender_deposit: decode_u128_value(value_at("sender_deposit", raw_channel)?)?,
+		recipient_deposit: decode_u128_value(value_at("recipient_deposit", raw_channel)?)?,
+	})
+}
+
+pub(crate) fn decode_message_count(raw_book_state: &Value<u32>) -> Result<u32, SubxtWrapperError> {
+	Ok(decode_u128_value(value_at("message_count", raw_book_state)?)? as u32)
+}
+
 pub(crate) fn decode_availability_cores(raw_cores: &Value<u32>) -> Result<Vec<CoreOccupied>, SubxtWrapperError> {
 	let decoded_cores = decode_unnamed_composite(raw_cores)?;
 	let mut cores = Vec::with_capacity(decoded_cores.len());
@@ -129,6 +198,29 @@ pub(crate) fn decode_on_demand_order(raw: &Composite<u32>) -> Result<OnDemandOrd
 	}
 }
 
+pub(crate) fn decode_extrinsic_weight(raw: &Composite<u32>) -> Result<ExtrinsicWeight, SubxtWrapperError> {
+	let raw_dispatch_info = match raw {
+		Composite::Named(v) => v
+			.iter()
+			.find_map(|(field, value)| if field == "dispatch_info" { Some(value) } else { None })
+			.ok_or(SubxtWrapperError::DecodeDynamicError(
+				"named composite with field `dispatch_info`".to_string(),
+				ValueDef::Composite(raw.clone()),
+			))?,
+		_ =>
+			return Err(SubxtWrapperError::DecodeDynamicError(
+				"named composite".to_string(),
+				ValueDef::Composite(raw.clone()),
+			)),
+	};
+	let raw_weight = value_at("weight", raw_dispatch_info)?;
+
+	Ok(ExtrinsicWeight {
+		ref_time: decode_u128_value(value_at("ref_time", raw_weight)?)? as u64,
+		proof_size: decode_u128_value(value_at("proof_size", raw_weight)?)? as u64,
+	})
+}
+
 fn decode_paras_entry_option(raw: &Value<u32>) -> Result<Option<ParasEntry>, SubxtWrapperError> {
 	match decode_option(raw)? {
 		Some(v) => Ok(Some(decode_paras_entry(v)?)),
@@ -173,6 +265,23 @@ fn decode_unnamed_composite(value: &Value<u32>) -> Result<&Vec<Value<u32>>, Subx
 	}
 }
 
+fn decode_bytes(value: &Value<u32>) -> Result<Vec<u8>, SubxtWrapperError> {
+	decode_unnamed_composite(value)?
+		.iter()
+		.map(|byte| decode_u128_value(byte).map(|v| v as u8))
+		.collect()
+}
+
+/// Decodes a newtype over a byte array, e.g. a hash or an account id
+fn decode_fixed_bytes<const N: usize>(value: &Value<u32>) -> Result<[u8; N], SubxtWrapperError> {
+	match decode_unnamed_composite(value)?[..] {
+		[ref bytes] => decode_bytes(bytes)?
+			.try_into()
+			.map_err(|_| DecodeDynamicError(format!("[u8; {N}]"), value.value.clone())),
+		_ => Err(DecodeDynamicError("vector of one element".to_string(), value.value.clone())),
+	}
+}
+
 fn decode_composite_u128_value(value: &Value<u32>) -> Result<u128, SubxtWrapperError> {
 	match decode_unnamed_composite(value)?[..] {
 		[ref first, ..] => decode_u128_value(first),
@@ -20,9 +20,19 @@ pub mod dynamic;
 pub mod storage;
 pub mod subxt_wrapper;
 
-use crate::{constants::MAX_MSG_QUEUE_SIZE, storage::RecordsStorageConfig, utils::RetryOptions};
+#[cfg(feature = "persistent-storage")]
+use crate::persistent_storage::SledRecordsStorage;
+use crate::{
+	constants::MAX_MSG_QUEUE_SIZE,
+	storage::{HashedPrefixedRecordsStorage, RecordsStorage, RecordsStorageConfig},
+	utils::RetryOptions,
+};
+#[cfg(feature = "persistent-storage")]
+use parity_scale_codec::{Decode, Encode};
+#[cfg(feature = "persistent-storage")]
+use std::path::Path;
 use std::{fmt::Debug, hash::Hash};
-use subxt_wrapper::RequestExecutor;
+use subxt_wrapper::{ExecutorContext, RequestExecutor};
 use tokio::sync::mpsc::{channel, Sender};
 
 // Provides access to subxt and storage APIs, more to come.
@@ -30,6 +40,7 @@ use tokio::sync::mpsc::{channel, Sender};
 pub struct ApiService<K, P = ()> {
 	storage_tx: Sender<storage::Request<K, P>>,
 	retry: RetryOptions,
+	context: ExecutorContext,
 }
 
 // Common methods
@@ -43,7 +54,7 @@ where
 	}
 
 	pub fn subxt(&self) -> subxt_wrapper::RequestExecutor {
-		RequestExecutor::new(self.retry.clone())
+		RequestExecutor::new(self.retry.clone(), self.context.clone())
 	}
 }
 
@@ -52,12 +63,16 @@ impl<K> ApiService<K, ()>
 where
 	K: Eq + Sized + Hash + Debug + Clone + Send + 'static,
 {
-	pub fn new_with_storage(storage_config: RecordsStorageConfig, retry: RetryOptions) -> ApiService<K> {
+	pub fn new_with_storage(
+		storage_config: RecordsStorageConfig,
+		retry: RetryOptions,
+		context: ExecutorContext,
+	) -> ApiService<K> {
 		let (storage_tx, storage_rx) = channel(MAX_MSG_QUEUE_SIZE);
 
 		tokio::spawn(storage::api_handler_task(storage_rx, storage_config));
 
-		Self { storage_tx, retry }
+		Self { storage_tx, retry, context }
 	}
 }
 
@@ -67,12 +82,39 @@ where
 	K: Eq + Sized + Hash + Debug + Clone + Send + Sync + 'static,
 	P: Eq + Sized + Hash + Debug + Clone + Send + Sync + 'static,
 {
-	pub fn new_with_prefixed_storage(storage_config: RecordsStorageConfig, retry: RetryOptions) -> ApiService<K, P> {
+	pub fn new_with_prefixed_storage(
+		storage_config: RecordsStorageConfig,
+		retry: RetryOptions,
+		context: ExecutorContext,
+	) -> ApiService<K, P> {
 		let (storage_tx, storage_rx) = channel(MAX_MSG_QUEUE_SIZE);
 
-		tokio::spawn(storage::api_handler_task_prefixed(storage_rx, storage_config));
+		tokio::spawn(storage::api_handler_task_prefixed(
+			storage_rx,
+			HashedPrefixedRecordsStorage::<K, P>::new(storage_config),
+		));
 
-		Self { storage_tx, retry }
+		Self { storage_tx, retry, context }
+	}
+
+	/// Keeps the records in a database at the path, so they survive restarts
+	#[cfg(feature = "persistent-storage")]
+	pub fn new_with_persistent_storage(
+		storage_config: RecordsStorageConfig,
+		path: &Path,
+		retry: RetryOptions,
+		context: ExecutorContext,
+	) -> color_eyre::Result<ApiService<K, P>>
+	where
+		K: Encode + Decode,
+		P: Encode + Decode,
+	{
+		let the_storage = SledRecordsStorage::<K, P>::open(path, storage_config)?;
+		let (storage_tx, storage_rx) = channel(MAX_MSG_QUEUE_SIZE);
+
+		tokio::task::spawn_blocking(move || storage::api_handler_blocking_prefixed(storage_rx, the_storage));
+
+		Ok(Self { storage_tx, retry, context })
 	}
 }
 #[cfg(test)]
@@ -93,7 +135,11 @@ mod tests {
 
 	#[tokio::test]
 	async fn basic_storage_test() {
-		let api = ApiService::new_with_storage(RecordsStorageConfig { max_blocks: 10 }, RetryOptions::default());
+		let api = ApiService::new_with_storage(
+			RecordsStorageConfig { max_blocks: 10 },
+			RetryOptions::default(),
+			Default::default(),
+		);
 		let storage = api.storage();
 		let key = BlakeTwo256::hash_of(&100);
 		storage
@@ -102,12 +148,16 @@ mod tests {
 			.unwrap();
 		let value = storage.storage_read(key).await.unwrap();
 		assert_eq!(value.into_inner::<String>().unwrap(), "some data");
+		assert!(storage.storage_subscribe_prefixed(()).await.is_err());
 	}
 
 	#[tokio::test]
 	async fn basic_subxt_test() {
-		let api =
-			ApiService::<H256>::new_with_storage(RecordsStorageConfig { max_blocks: 10 }, RetryOptions::default());
+		let api = ApiService::<H256>::new_with_storage(
+			RecordsStorageConfig { max_blocks: 10 },
+			RetryOptions::default(),
+			Default::default(),
+		);
 		let mut subxt = api.subxt();
 
 		let head = subxt.get_block_head(rpc_node_url(), None).await.unwrap().unwrap();
@@ -118,7 +168,11 @@ mod tests {
 
 	#[tokio::test]
 	async fn extract_parainherent_data() {
-		let api = ApiService::<H256>::new_with_storage(RecordsStorageConfig { max_blocks: 1 }, RetryOptions::default());
+		let api = ApiService::<H256>::new_with_storage(
+			RecordsStorageConfig { max_blocks: 1 },
+			RetryOptions::default(),
+			Default::default(),
+		);
 		let mut subxt = api.subxt();
 
 		subxt
@@ -130,7 +184,11 @@ mod tests {
 
 	#[tokio::test]
 	async fn get_scheduled_paras() {
-		let api = ApiService::<H256>::new_with_storage(RecordsStorageConfig { max_blocks: 1 }, RetryOptions::default());
+		let api = ApiService::<H256>::new_with_storage(
+			RecordsStorageConfig { max_blocks: 1 },
+			RetryOptions::default(),
+			Default::default(),
+		);
 		let mut subxt = api.subxt();
 
 		let head = subxt.get_block_head(rpc_node_url(), None).await.unwrap().unwrap();
@@ -141,7 +199,11 @@ mod tests {
 
 	#[tokio::test]
 	async fn get_occupied_cores() {
-		let api = ApiService::<H256>::new_with_storage(RecordsStorageConfig { max_blocks: 1 }, RetryOptions::default());
+		let api = ApiService::<H256>::new_with_storage(
+			RecordsStorageConfig { max_blocks: 1 },
+			RetryOptions::default(),
+			Default::default(),
+		);
 		let mut subxt = api.subxt();
 
 		let head = subxt.get_block_head(rpc_node_url(), None).await.unwrap().unwrap();
@@ -152,7 +214,11 @@ mod tests {
 
 	#[tokio::test]
 	async fn get_backing_groups() {
-		let api = ApiService::<H256>::new_with_storage(RecordsStorageConfig { max_blocks: 1 }, RetryOptions::default());
+		let api = ApiService::<H256>::new_with_storage(
+			RecordsStorageConfig { max_blocks: 1 },
+			RetryOptions::default(),
+			Default::default(),
+		);
 		let mut subxt = api.subxt();
 
 		let head = subxt.get_block_head(rpc_node_url(), None).await.unwrap().unwrap();
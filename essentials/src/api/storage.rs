@@ -17,8 +17,8 @@
 #![allow(dead_code)]
 
 use crate::storage::{
-	HashedPlainRecordsStorage, HashedPrefixedRecordsStorage, PrefixedRecordsStorage, RecordsStorage,
-	RecordsStorageConfig, StorageEntry,
+	HashedPlainRecordsStorage, PrefixedRecordsStorage, RecordsStorage, RecordsStorageConfig, StorageEntry,
+	StorageUpdate,
 };
 use color_eyre::eyre::eyre;
 use std::{fmt::Debug, hash::Hash};
@@ -39,9 +39,11 @@ pub enum RequestType<K, P> {
 	Replace(K, StorageEntry),
 	ReplacePrefix(P, K, StorageEntry),
 	Size,
+	BlocksCount,
 	Keys,
 	Prefixes,
 	KeysWithPrefix(P),
+	SubscribePrefix(P),
 }
 
 #[derive(Debug)]
@@ -57,6 +59,7 @@ pub enum Response<K, P> {
 	Keys(Vec<K>),
 	Prefixes(Vec<P>),
 	Status(color_eyre::Result<()>),
+	Subscription(Receiver<StorageUpdate<K, P>>),
 }
 #[derive(Clone)]
 pub struct RequestExecutor<K, P> {
@@ -173,6 +176,19 @@ where
 		}
 	}
 
+	/// Returns number of blocks with ephemeral entries in a storage
+	pub async fn storage_blocks_len(&self) -> usize {
+		let (sender, receiver) = oneshot::channel::<Response<K, P>>();
+		let request = Request { request_type: RequestType::BlocksCount, response_sender: Some(sender) };
+		self.to_api.send(request).await.expect("Channel closed");
+
+		match receiver.await {
+			Ok(Response::Size(len)) => len,
+			Ok(_) => panic!("Storage API error: invalid size reply"),
+			Err(err) => panic!("Storage API error {}", err),
+		}
+	}
+
 	/// Returns all keys from a storage
 	pub async fn storage_keys(&self) -> Vec<K> {
 		let (sender, receiver) = oneshot::channel::<Response<K, P>>();
@@ -210,6 +226,22 @@ where
 			Err(err) => panic!("Storage API error {}", err),
 		}
 	}
+
+	/// Subscribes to the entries inserted or replaced at a specific prefix, e.g. candidates of a parachain.
+	/// The subscription ends when the returned receiver is dropped, or when the subscriber lags behind.
+	/// Returns an error if the storage has no prefixes.
+	pub async fn storage_subscribe_prefixed(&self, prefix: P) -> color_eyre::Result<Receiver<StorageUpdate<K, P>>> {
+		let (sender, receiver) = oneshot::channel::<Response<K, P>>();
+		let request = Request { request_type: RequestType::SubscribePrefix(prefix), response_sender: Some(sender) };
+		self.to_api.send(request).await.expect("Channel closed");
+
+		match receiver.await {
+			Ok(Response::Subscription(updates)) => Ok(updates),
+			Ok(Response::Status(Err(err))) => Err(err),
+			Ok(_) => panic!("Storage API error: invalid subscription reply"),
+			Err(err) => panic!("Storage API error {}", err),
+		}
+	}
 }
 
 /// Creates a task that handles storage API calls (generic version with no prefixes support).
@@ -259,6 +291,14 @@ where
 					.send(Response::Size(size))
 					.unwrap();
 			},
+			RequestType::BlocksCount => {
+				let size = the_storage.blocks_len();
+				request
+					.response_sender
+					.expect("no sender provided")
+					.send(Response::Size(size))
+					.unwrap();
+			},
 			RequestType::Keys => {
 				let keys = the_storage.keys();
 				request
@@ -285,113 +325,154 @@ where
 			RequestType::Prefixes => {
 				unimplemented!()
 			},
+			RequestType::SubscribePrefix(_) => {
+				request
+					.response_sender
+					.expect("no sender provided")
+					.send(Response::Status(Err(eyre!("Storage without prefixes has no prefix subscriptions"))))
+					.unwrap();
+			},
 		}
 	}
 }
 
-/// Creates the API handler with prefixes support.
-pub(crate) async fn api_handler_task_prefixed<K, P>(
-	mut api: Receiver<Request<K, P>>,
-	storage_config: RecordsStorageConfig,
-) where
+/// Creates the API handler with prefixes support over the given storage in memory.
+pub(crate) async fn api_handler_task_prefixed<K, P, S>(mut api: Receiver<Request<K, P>>, mut the_storage: S)
+where
 	K: Eq + Sized + Hash + Debug + Clone,
 	P: Eq + Sized + Hash + Debug + Clone,
+	S: RecordsStorage<K> + PrefixedRecordsStorage<K, P>,
 {
-	// The storage lives here.
-	let mut the_storage = HashedPrefixedRecordsStorage::new(storage_config);
-
 	while let Some(request) = api.recv().await {
-		match request.request_type {
-			RequestType::Read(key) => {
-				request
-					.response_sender
-					.expect("no sender provided")
-					.send(Response::Read(the_storage.get(&key)))
-					.unwrap();
-			},
-			RequestType::Delete(key) => {
-				request
-					.response_sender
-					.expect("no sender provided")
-					.send(Response::Read(the_storage.delete(&key)))
-					.unwrap();
-			},
-			RequestType::Write(key, value) => {
-				let res = the_storage.insert(key, value);
-
-				if let Some(sender) = request.response_sender {
-					// A callee wants to know about the errors
-					sender.send(Response::Status(res)).unwrap();
-				}
-			},
-			RequestType::Replace(key, value) => {
-				let res = the_storage.replace(&key, value);
-
-				if let Some(sender) = request.response_sender {
-					sender.send(Response::Read(res)).unwrap();
-				}
-			},
-			RequestType::Size => {
-				let size = the_storage.len();
-				request
-					.response_sender
-					.expect("no sender provided")
-					.send(Response::Size(size))
-					.unwrap();
-			},
-			RequestType::Keys => {
-				let keys = the_storage.keys();
-				request
-					.response_sender
-					.expect("no sender provided")
-					.send(Response::Keys(keys))
-					.unwrap();
-			},
-			RequestType::KeysWithPrefix(prefix) => {
-				let keys = the_storage.prefixed_keys(&prefix);
-				request
-					.response_sender
-					.expect("no sender provided")
-					.send(Response::Keys(keys))
-					.unwrap();
-			},
-			RequestType::Prefixes => {
-				let prefixes = the_storage.prefixes();
-				request
-					.response_sender
-					.expect("no sender provided")
-					.send(Response::Prefixes(prefixes))
-					.unwrap();
-			},
-			RequestType::WritePrefix(prefix, key, value) => {
-				let res = the_storage.insert_prefix(prefix, key, value);
+		handle_prefixed_request(&mut the_storage, request);
+	}
+}
 
-				if let Some(sender) = request.response_sender {
-					// A callee wants to know about the errors
-					sender.send(Response::Status(res)).unwrap();
-				}
-			},
-			RequestType::ReadPrefix(prefix, key) => {
-				request
-					.response_sender
-					.expect("no sender provided")
-					.send(Response::Read(the_storage.get_prefix(&prefix, &key)))
-					.unwrap();
-			},
-			RequestType::DeletePrefix(prefix, key) => {
-				request
-					.response_sender
-					.expect("no sender provided")
-					.send(Response::Read(the_storage.delete_prefix(&prefix, &key)))
-					.unwrap();
-			},
-			RequestType::ReplacePrefix(prefix, key, value) => {
-				let res = the_storage.replace_prefixed(&prefix, &key, value);
+/// Handles the storage API calls with prefixes support over a storage doing blocking I/O, e.g. on disk.
+/// Must be run on a blocking thread.
+pub(crate) fn api_handler_blocking_prefixed<K, P, S>(mut api: Receiver<Request<K, P>>, mut the_storage: S)
+where
+	K: Eq + Sized + Hash + Debug + Clone,
+	P: Eq + Sized + Hash + Debug + Clone,
+	S: RecordsStorage<K> + PrefixedRecordsStorage<K, P>,
+{
+	while let Some(request) = api.blocking_recv() {
+		handle_prefixed_request(&mut the_storage, request);
+	}
+}
 
-				if let Some(sender) = request.response_sender {
-					sender.send(Response::Read(res)).unwrap();
-				}
-			},
-		}
+fn handle_prefixed_request<K, P, S>(the_storage: &mut S, request: Request<K, P>)
+where
+	K: Eq + Sized + Hash + Debug + Clone,
+	P: Eq + Sized + Hash + Debug + Clone,
+	S: RecordsStorage<K> + PrefixedRecordsStorage<K, P>,
+{
+	match request.request_type {
+		RequestType::Read(key) => {
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Read(the_storage.get(&key)))
+				.unwrap();
+		},
+		RequestType::Delete(key) => {
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Read(the_storage.delete(&key)))
+				.unwrap();
+		},
+		RequestType::Write(key, value) => {
+			let res = the_storage.insert(key, value);
+
+			if let Some(sender) = request.response_sender {
+				// A callee wants to know about the errors
+				sender.send(Response::Status(res)).unwrap();
+			}
+		},
+		RequestType::Replace(key, value) => {
+			let res = the_storage.replace(&key, value);
+
+			if let Some(sender) = request.response_sender {
+				sender.send(Response::Read(res)).unwrap();
+			}
+		},
+		RequestType::Size => {
+			let size = the_storage.len();
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Size(size))
+				.unwrap();
+		},
+		RequestType::BlocksCount => {
+			let size = the_storage.blocks_len();
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Size(size))
+				.unwrap();
+		},
+		RequestType::Keys => {
+			let keys = the_storage.keys();
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Keys(keys))
+				.unwrap();
+		},
+		RequestType::KeysWithPrefix(prefix) => {
+			let keys = the_storage.prefixed_keys(&prefix);
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Keys(keys))
+				.unwrap();
+		},
+		RequestType::Prefixes => {
+			let prefixes = the_storage.prefixes();
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Prefixes(prefixes))
+				.unwrap();
+		},
+		RequestType::SubscribePrefix(prefix) => {
+			let updates = the_storage.subscribe_prefix(prefix);
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Subscription(updates))
+				.unwrap();
+		},
+		RequestType::WritePrefix(prefix, key, value) => {
+			let res = the_storage.insert_prefix(prefix, key, value);
+
+			if let Some(sender) = request.response_sender {
+				// A callee wants to know about the errors
+				sender.send(Response::Status(res)).unwrap();
+			}
+		},
+		RequestType::ReadPrefix(prefix, key) => {
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Read(the_storage.get_prefix(&prefix, &key)))
+				.unwrap();
+		},
+		RequestType::DeletePrefix(prefix, key) => {
+			request
+				.response_sender
+				.expect("no sender provided")
+				.send(Response::Read(the_storage.delete_prefix(&prefix, &key)))
+				.unwrap();
+		},
+		RequestType::ReplacePrefix(prefix, key, value) => {
+			let res = the_storage.replace_prefixed(&prefix, &key, value);
+
+			if let Some(sender) = request.response_sender {
+				sender.send(Response::Read(res)).unwrap();
+			}
+		},
 	}
 }
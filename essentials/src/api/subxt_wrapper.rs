@@ -17,21 +17,33 @@
 
 use crate::{
 	api::{
-		api_client::{ApiClient, HeaderStream},
-		dynamic::{decode_availability_cores, decode_claim_queue, decode_scheduled_paras, decode_validator_groups},
+		api_client::{ApiClient, HeaderStream, RpcClientFactory},
+		dynamic::{
+			decode_account_keys, decode_active_validator_indices, decode_availability_cores, decode_claim_queue,
+			decode_head_data, decode_hrmp_channel, decode_message_count, decode_para_ids, decode_scheduled_paras,
+			decode_session_index, decode_session_start_block, decode_timestamp, decode_validator_groups,
+		},
+	},
+	chaos::{ChaosInjector, Fault},
+	metadata::{decoding_for, polkadot, polkadot_primitives, Decoding},
+	rpc_budget::RpcBudgetCounter,
+	runtime_support::RuntimeVersion,
+	types::{
+		AccountId32, BlockNumber, BlockWeight, ClaimQueue, CoreAssignment, CoreOccupied, Header, SessionKeys,
+		Timestamp, H256,
 	},
-	metadata::{polkadot, polkadot_primitives},
-	types::{AccountId32, BlockNumber, ClaimQueue, CoreAssignment, CoreOccupied, SessionKeys, Timestamp, H256},
 	utils::{Retry, RetryOptions},
 };
-use log::{error, warn};
+use log::{error, info, warn};
+use parity_scale_codec::{Compact, Decode};
 use std::{
 	collections::{hash_map::HashMap, BTreeMap},
 	fmt::Debug,
 };
 use subxt::{
+	config::{substrate::BlakeTwo256, Hasher},
 	dynamic::{At, Value},
-	ext::scale_value::ValueDef,
+	ext::scale_value::{Primitive, ValueDef},
 	OnlineClient, PolkadotConfig,
 };
 use thiserror::Error;
@@ -46,6 +58,8 @@ pub enum RequestType {
 	GetBlock(Option<<PolkadotConfig as subxt::Config>::Hash>),
 	/// Get a block hash.
 	GetBlockHash(Option<BlockNumber>),
+	/// Get the last finalized block hash.
+	GetFinalizedBlockHash,
 	/// Get block events.
 	GetEvents(<PolkadotConfig as subxt::Config>::Hash),
 	/// Extract the `ParaInherentData` from a given block.
@@ -60,20 +74,41 @@ pub enum RequestType {
 	GetBackingGroups(<PolkadotConfig as subxt::Config>::Hash),
 	/// Get session index for a specific block.
 	GetSessionIndex(<PolkadotConfig as subxt::Config>::Hash),
-	/// Get information about validators account keys in some session.
-	GetSessionAccountKeys(u32),
+	/// Get the block number the current session started at a given block.
+	GetSessionStartBlock(<PolkadotConfig as subxt::Config>::Hash),
+	/// Get information about validators account keys in some session, accepts block hash and session index
+	GetSessionAccountKeys(<PolkadotConfig as subxt::Config>::Hash, u32),
+	/// Get the indices of the validators taking part in parachain consensus in some session, accepts block hash and
+	/// session index
+	GetActiveValidatorIndices(<PolkadotConfig as subxt::Config>::Hash, u32),
 	/// Get information about validator's next session keys.
 	GetSessionNextKeys(AccountId32),
 	/// Get information about inbound HRMP channels, accepts block hash and destination ParaId
 	GetInboundHRMPChannels(<PolkadotConfig as subxt::Config>::Hash, u32),
 	/// Get information about inbound HRMP channels, accepts block hash and destination ParaId
 	GetOutboundHRMPChannels(<PolkadotConfig as subxt::Config>::Hash, u32),
+	/// Get the number of downward messages queued for a parachain, accepts block hash and ParaId
+	GetDownwardQueueLen(<PolkadotConfig as subxt::Config>::Hash, u32),
+	/// Get the number of upward messages queued from a parachain, accepts block hash and ParaId
+	GetUpwardQueueLen(<PolkadotConfig as subxt::Config>::Hash, u32),
+	/// Get the hash of the head data of a parachain, accepts block hash and ParaId
+	GetParaHead(<PolkadotConfig as subxt::Config>::Hash, u32),
+	/// Get the block number in the head data of a parachain, accepts block hash and ParaId
+	GetParaHeadNumber(<PolkadotConfig as subxt::Config>::Hash, u32),
 	/// Get active host configuration
-	GetHostConfiguration(()),
+	GetHostConfiguration,
 	/// Get a subscription to the best blocks chain
-	GetBestBlockSubscription(()),
+	GetBestBlockSubscription,
 	/// Get a subscription to the finalized blocks chain
-	GetFinalizedBlockSubscription(()),
+	GetFinalizedBlockSubscription,
+	/// Get the runtime spec version at a given block.
+	GetSpecVersion(<PolkadotConfig as subxt::Config>::Hash),
+	/// Get the runtime name and version at the best block.
+	GetRuntimeVersion,
+	/// Get the id of a parachain from its own node, at the best block.
+	GetParachainId,
+	/// Get the weight consumed by a block of a parachain from its own node.
+	GetBlockWeight(<PolkadotConfig as subxt::Config>::Hash),
 }
 
 // Required after subxt changes that removed Debug trait from the generated structures
@@ -92,6 +127,7 @@ impl Debug for RequestType {
 			RequestType::GetBlockHash(h) => {
 				format!("get block hash: {:?}", h)
 			},
+			RequestType::GetFinalizedBlockHash => "get finalized block hash".to_string(),
 			RequestType::GetEvents(h) => {
 				format!("get events: {:?}", h)
 			},
@@ -113,8 +149,14 @@ impl Debug for RequestType {
 			RequestType::GetSessionIndex(h) => {
 				format!("get session index: {:?}", h)
 			},
-			RequestType::GetSessionAccountKeys(id) => {
-				format!("get session account keys: {:?}", id)
+			RequestType::GetSessionStartBlock(h) => {
+				format!("get session start block: {:?}", h)
+			},
+			RequestType::GetSessionAccountKeys(h, id) => {
+				format!("get session account keys: {:?}; session: {}", h, id)
+			},
+			RequestType::GetActiveValidatorIndices(h, id) => {
+				format!("get active validator indices: {:?}; session: {}", h, id)
 			},
 			RequestType::GetSessionNextKeys(account) => {
 				format!("get next session account keys: {:?}", account)
@@ -125,9 +167,29 @@ impl Debug for RequestType {
 			RequestType::GetOutboundHRMPChannels(h, para_id) => {
 				format!("get outbount channels: {:?}; para id: {}", h, para_id)
 			},
-			RequestType::GetHostConfiguration(_) => "get host configuration".to_string(),
-			RequestType::GetBestBlockSubscription(_) => "get best block subscription".to_string(),
-			RequestType::GetFinalizedBlockSubscription(_) => "get finalized block subscription".to_string(),
+			RequestType::GetDownwardQueueLen(h, para_id) => {
+				format!("get downward queue length: {:?}; para id: {}", h, para_id)
+			},
+			RequestType::GetUpwardQueueLen(h, para_id) => {
+				format!("get upward queue length: {:?}; para id: {}", h, para_id)
+			},
+			RequestType::GetParaHead(h, para_id) => {
+				format!("get para head: {:?}; para id: {}", h, para_id)
+			},
+			RequestType::GetParaHeadNumber(h, para_id) => {
+				format!("get para head number: {:?}; para id: {}", h, para_id)
+			},
+			RequestType::GetHostConfiguration => "get host configuration".to_string(),
+			RequestType::GetBestBlockSubscription => "get best block subscription".to_string(),
+			RequestType::GetFinalizedBlockSubscription => "get finalized block subscription".to_string(),
+			RequestType::GetSpecVersion(h) => {
+				format!("get spec version: {:?}", h)
+			},
+			RequestType::GetRuntimeVersion => "get runtime version".to_string(),
+			RequestType::GetParachainId => "get parachain id".to_string(),
+			RequestType::GetBlockWeight(h) => {
+				format!("get block weight: {:?}", h)
+			},
 		};
 		write!(f, "Subxt request: {}", description)
 	}
@@ -165,20 +227,38 @@ pub enum Response {
 	BackingGroups(Vec<Vec<polkadot_primitives::ValidatorIndex>>),
 	/// Returns a session index
 	SessionIndex(u32),
+	/// Returns the block number a session started at
+	SessionStartBlock(u32),
 	/// Session info
 	SessionInfo(Option<polkadot_primitives::SessionInfo>),
 	/// Session keys
 	SessionAccountKeys(Option<Vec<AccountId32>>),
+	/// Session indices of the validators taking part in parachain consensus, ordered by their parachain indices
+	ActiveValidatorIndices(Option<Vec<u32>>),
 	/// Session next keys for a validator
 	SessionNextKeys(Option<SessionKeys>),
 	/// HRMP channels for some parachain (e.g. who are sending messages to us)
 	HRMPChannels(BTreeMap<u32, SubxtHrmpChannel>),
+	/// Number of messages in a message queue
+	MessageQueueLen(u32),
+	/// Hash of the head data of a parachain, if it has one
+	ParaHead(Option<H256>),
+	/// Block number in the head data of a parachain, if it has one
+	ParaHeadNumber(Option<BlockNumber>),
 	/// HRMP content for a specific channel
 	HRMPContent(Vec<Vec<u8>>),
 	/// The current host configuration
 	HostConfiguration(DynamicHostConfiguration),
 	/// Chain subscription
 	ChainSubscription(HeaderStream),
+	/// Runtime spec version
+	SpecVersion(u32),
+	/// Runtime name and version
+	RuntimeVersion(RuntimeVersion),
+	/// Id of a parachain, not set if the chain has no `ParachainInfo` pallet
+	ParachainId(Option<u32>),
+	/// Weight consumed by a block, not set if the chain has no `System::BlockWeight`
+	BlockWeight(Option<BlockWeight>),
 }
 
 impl Debug for Response {
@@ -187,14 +267,64 @@ impl Debug for Response {
 	}
 }
 
+/// State of a run shared by the executors built with it
+#[derive(Clone, Debug, Default)]
+pub struct ExecutorContext {
+	rpc_budget: RpcBudgetCounter,
+	client_factory: RpcClientFactory,
+	chaos: ChaosInjector,
+}
+
+impl ExecutorContext {
+	/// Counts the RPC interactions of the executors in the given counter
+	pub fn with_rpc_budget(mut self, rpc_budget: RpcBudgetCounter) -> Self {
+		self.rpc_budget = rpc_budget;
+		self
+	}
+
+	pub fn rpc_budget(&self) -> &RpcBudgetCounter {
+		&self.rpc_budget
+	}
+
+	/// Builds the RPC clients of the executors with the given factory
+	pub fn with_client_factory(mut self, client_factory: RpcClientFactory) -> Self {
+		self.client_factory = client_factory;
+		self
+	}
+
+	pub fn client_factory(&self) -> &RpcClientFactory {
+		&self.client_factory
+	}
+
+	/// Injects the faults of the chaos mode into the executors and the subscriptions built with the context
+	pub fn with_chaos(mut self, chaos: ChaosInjector) -> Self {
+		self.chaos = chaos;
+		self
+	}
+
+	pub fn chaos(&self) -> &ChaosInjector {
+		&self.chaos
+	}
+}
+
 /// Represents a pool for subxt requests
 #[derive(Clone)]
 pub struct RequestExecutor {
 	connection_pool: HashMap<String, ApiClient>,
+	/// Decoding selected for the runtime of each connection
+	decodings: HashMap<String, Decoding>,
 	retry: RetryOptions,
+	context: ExecutorContext,
 }
 
 macro_rules! wrap_subxt_call {
+	($self: ident, $req_ty: ident, $rep_ty: ident, $url: ident) => (
+		match $self.execute_request(RequestType::$req_ty, $url).await {
+			Ok(Response::$rep_ty(data)) => Ok(data),
+			Err(e) => Err(e),
+			_ => panic!("Expected {}, got something else.", stringify!($rep_ty)),
+		}
+	);
 	($self: ident, $req_ty: ident, $rep_ty: ident, $url: ident, $($arg:expr),*) => (
 		match $self.execute_request(RequestType::$req_ty($($arg),*), $url).await {
 			Ok(Response::$rep_ty(data)) => Ok(data),
@@ -205,50 +335,94 @@ macro_rules! wrap_subxt_call {
 }
 
 impl RequestExecutor {
-	pub fn new(retry: RetryOptions) -> Self {
-		Self { retry, connection_pool: HashMap::new() }
+	pub fn new(retry: RetryOptions, context: ExecutorContext) -> Self {
+		Self { retry, context, connection_pool: HashMap::new(), decodings: HashMap::new() }
 	}
 
 	async fn execute_request(&mut self, request: RequestType, url: &str) -> Result {
+		self.context.rpc_budget().on_call();
+		let reply = self.execute_request_with_retries(request, url).await;
+		if reply.is_err() {
+			self.context.rpc_budget().on_failed_call();
+		}
+		reply
+	}
+
+	async fn execute_request_with_retries(&mut self, request: RequestType, url: &str) -> Result {
 		let connection_pool = &mut self.connection_pool;
-		let mut retry = Retry::new(&self.retry);
+		let decodings = &mut self.decodings;
+		let mut retry = Retry::new(&self.retry).with_rpc_budget(self.context.rpc_budget().clone());
 
 		loop {
 			let api = match connection_pool.get(url) {
 				Some(api) => api.clone(),
 				None => {
-					let new_api = new_client_fn(url, &self.retry).await;
+					let new_api = new_client_fn(url, &self.retry, &self.context).await;
 					if let Some(api) = new_api {
 						connection_pool.insert(url.to_owned(), api.clone());
+						decodings.insert(url.to_owned(), select_decoding(url, &api).await);
 						api
 					} else {
 						return Err(SubxtWrapperError::ConnectionError)
 					}
 				},
 			};
+			let decoding = decodings.get(url).copied().unwrap_or(Decoding::Dynamic);
 			let reply = match request {
-				RequestType::GetBlockTimestamp(hash) => subxt_get_block_ts(&api, hash).await,
+				RequestType::GetBlockTimestamp(hash) => subxt_get_block_ts(&api, hash, decoding).await,
 				RequestType::GetHead(maybe_hash) => subxt_get_head(&api, maybe_hash).await,
 				RequestType::GetBlock(maybe_hash) => subxt_get_block(&api, maybe_hash).await,
 				RequestType::GetBlockHash(maybe_block_number) => subxt_get_block_hash(&api, maybe_block_number).await,
+				RequestType::GetFinalizedBlockHash => subxt_get_finalized_block_hash(&api).await,
 				RequestType::GetEvents(hash) => subxt_get_events(&api, hash).await,
 				RequestType::ExtractParaInherent(ref block) => subxt_extract_parainherent(block).await,
 				RequestType::GetScheduledParas(hash) => subxt_get_sheduled_paras(&api, hash).await,
 				RequestType::GetClaimQueue(hash) => subxt_get_claim_queue(&api, hash).await,
 				RequestType::GetOccupiedCores(hash) => subxt_get_occupied_cores(&api, hash).await,
 				RequestType::GetBackingGroups(hash) => subxt_get_validator_groups(&api, hash).await,
-				RequestType::GetSessionIndex(hash) => subxt_get_session_index(&api, hash).await,
-				RequestType::GetSessionAccountKeys(session_index) =>
-					subxt_get_session_account_keys(&api, session_index).await,
-				RequestType::GetSessionNextKeys(ref account) => subxt_get_session_next_keys(&api, account).await,
+				RequestType::GetSessionIndex(hash) => subxt_get_session_index(&api, hash, decoding).await,
+				RequestType::GetSessionStartBlock(hash) => subxt_get_session_start_block(&api, hash).await,
+				RequestType::GetSessionAccountKeys(hash, session_index) =>
+					subxt_get_session_account_keys(&api, hash, session_index, decoding).await,
+				RequestType::GetActiveValidatorIndices(hash, session_index) =>
+					subxt_get_active_validator_indices(&api, hash, session_index, decoding).await,
+				RequestType::GetSessionNextKeys(ref account) =>
+					subxt_get_session_next_keys(&api, account, decoding).await,
 				RequestType::GetInboundHRMPChannels(hash, para_id) =>
-					subxt_get_inbound_hrmp_channels(&api, hash, para_id).await,
+					subxt_get_hrmp_channels(&api, hash, para_id, HrmpDirection::Inbound, decoding).await,
 				RequestType::GetOutboundHRMPChannels(hash, para_id) =>
-					subxt_get_outbound_hrmp_channels(&api, hash, para_id).await,
-				RequestType::GetHostConfiguration(_) => subxt_get_host_configuration(&api).await,
-				RequestType::GetBestBlockSubscription(_) => subxt_get_best_block_subscription(&api).await,
-				RequestType::GetFinalizedBlockSubscription(_) => subxt_get_finalized_block_subscription(&api).await,
+					subxt_get_hrmp_channels(&api, hash, para_id, HrmpDirection::Outbound, decoding).await,
+				RequestType::GetDownwardQueueLen(hash, para_id) =>
+					subxt_get_downward_queue_len(&api, hash, para_id).await,
+				RequestType::GetUpwardQueueLen(hash, para_id) => subxt_get_upward_queue_len(&api, hash, para_id).await,
+				RequestType::GetParaHead(hash, para_id) => subxt_get_para_head(&api, hash, para_id, decoding).await,
+				RequestType::GetParaHeadNumber(hash, para_id) =>
+					subxt_get_para_head_number(&api, hash, para_id, decoding).await,
+				RequestType::GetHostConfiguration => subxt_get_host_configuration(&api).await,
+				RequestType::GetBestBlockSubscription => subxt_get_best_block_subscription(&api).await,
+				RequestType::GetFinalizedBlockSubscription => subxt_get_finalized_block_subscription(&api).await,
+				RequestType::GetSpecVersion(hash) => subxt_get_spec_version(&api, hash).await,
+				RequestType::GetRuntimeVersion => subxt_get_runtime_version(&api).await,
+				RequestType::GetParachainId => subxt_get_parachain_id(&api).await,
+				RequestType::GetBlockWeight(hash) => subxt_get_block_weight(&api, hash).await,
 			};
+			// As if the reply was lost on the way
+			let reply = if self.context.chaos().inject(Fault::RpcError) {
+				Err(SubxtWrapperError::SubxtError(subxt::Error::Io(std::io::Error::new(
+					std::io::ErrorKind::Other,
+					"injected by the chaos mode",
+				))))
+			} else {
+				reply
+			};
+
+			if decoding == Decoding::Static &&
+				matches!(reply, Err(SubxtWrapperError::SubxtError(subxt::Error::Metadata(_))))
+			{
+				warn!("[{}] Runtime is incompatible with the vendored metadata, falling back to dynamic decoding", url);
+				decodings.insert(url.to_owned(), Decoding::Dynamic);
+				continue
+			}
 
 			if let Err(e) = reply {
 				let need_to_retry = matches!(
@@ -302,6 +476,13 @@ impl RequestExecutor {
 		wrap_subxt_call!(self, GetBlockHash, MaybeBlockHash, url, maybe_block_number)
 	}
 
+	pub async fn get_finalized_block_hash(
+		&mut self,
+		url: &str,
+	) -> std::result::Result<Option<H256>, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetFinalizedBlockHash, MaybeBlockHash, url)
+	}
+
 	pub async fn get_events(
 		&mut self,
 		url: &str,
@@ -365,12 +546,30 @@ impl RequestExecutor {
 		wrap_subxt_call!(self, GetSessionIndex, SessionIndex, url, block_hash)
 	}
 
+	pub async fn get_session_start_block(
+		&mut self,
+		url: &str,
+		block_hash: <PolkadotConfig as subxt::Config>::Hash,
+	) -> std::result::Result<u32, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetSessionStartBlock, SessionStartBlock, url, block_hash)
+	}
+
 	pub async fn get_session_account_keys(
 		&mut self,
 		url: &str,
+		block_hash: <PolkadotConfig as subxt::Config>::Hash,
 		session_index: u32,
 	) -> std::result::Result<Option<Vec<AccountId32>>, SubxtWrapperError> {
-		wrap_subxt_call!(self, GetSessionAccountKeys, SessionAccountKeys, url, session_index)
+		wrap_subxt_call!(self, GetSessionAccountKeys, SessionAccountKeys, url, block_hash, session_index)
+	}
+
+	pub async fn get_active_validator_indices(
+		&mut self,
+		url: &str,
+		block_hash: <PolkadotConfig as subxt::Config>::Hash,
+		session_index: u32,
+	) -> std::result::Result<Option<Vec<u32>>, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetActiveValidatorIndices, ActiveValidatorIndices, url, block_hash, session_index)
 	}
 
 	pub async fn get_session_next_keys(
@@ -399,34 +598,96 @@ impl RequestExecutor {
 		wrap_subxt_call!(self, GetOutboundHRMPChannels, HRMPChannels, url, block_hash, para_id)
 	}
 
+	pub async fn get_downward_queue_len(
+		&mut self,
+		url: &str,
+		block_hash: <PolkadotConfig as subxt::Config>::Hash,
+		para_id: u32,
+	) -> std::result::Result<u32, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetDownwardQueueLen, MessageQueueLen, url, block_hash, para_id)
+	}
+
+	pub async fn get_upward_queue_len(
+		&mut self,
+		url: &str,
+		block_hash: <PolkadotConfig as subxt::Config>::Hash,
+		para_id: u32,
+	) -> std::result::Result<u32, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetUpwardQueueLen, MessageQueueLen, url, block_hash, para_id)
+	}
+
+	pub async fn get_para_head(
+		&mut self,
+		url: &str,
+		block_hash: <PolkadotConfig as subxt::Config>::Hash,
+		para_id: u32,
+	) -> std::result::Result<Option<H256>, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetParaHead, ParaHead, url, block_hash, para_id)
+	}
+
+	pub async fn get_para_head_number(
+		&mut self,
+		url: &str,
+		block_hash: <PolkadotConfig as subxt::Config>::Hash,
+		para_id: u32,
+	) -> std::result::Result<Option<BlockNumber>, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetParaHeadNumber, ParaHeadNumber, url, block_hash, para_id)
+	}
+
 	pub async fn get_host_configuration(
 		&mut self,
 		url: &str,
 	) -> std::result::Result<DynamicHostConfiguration, SubxtWrapperError> {
-		wrap_subxt_call!(self, GetHostConfiguration, HostConfiguration, url, ())
+		wrap_subxt_call!(self, GetHostConfiguration, HostConfiguration, url)
+	}
+
+	pub async fn get_spec_version(
+		&mut self,
+		url: &str,
+		hash: <PolkadotConfig as subxt::Config>::Hash,
+	) -> std::result::Result<u32, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetSpecVersion, SpecVersion, url, hash)
+	}
+
+	pub async fn get_runtime_version(&mut self, url: &str) -> std::result::Result<RuntimeVersion, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetRuntimeVersion, RuntimeVersion, url)
+	}
+
+	/// Reads the id of a parachain from a node of the parachain
+	pub async fn get_parachain_id(&mut self, url: &str) -> std::result::Result<Option<u32>, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetParachainId, ParachainId, url)
+	}
+
+	/// Reads the weight consumed by a block of a parachain from a node of the parachain
+	pub async fn get_block_weight(
+		&mut self,
+		url: &str,
+		hash: <PolkadotConfig as subxt::Config>::Hash,
+	) -> std::result::Result<Option<BlockWeight>, SubxtWrapperError> {
+		wrap_subxt_call!(self, GetBlockWeight, BlockWeight, url, hash)
 	}
 
 	pub async fn get_best_block_subscription(
 		&mut self,
 		url: &str,
 	) -> std::result::Result<HeaderStream, SubxtWrapperError> {
-		wrap_subxt_call!(self, GetBestBlockSubscription, ChainSubscription, url, ())
+		wrap_subxt_call!(self, GetBestBlockSubscription, ChainSubscription, url)
 	}
 
 	pub async fn get_finalized_block_subscription(
 		&mut self,
 		url: &str,
 	) -> std::result::Result<HeaderStream, SubxtWrapperError> {
-		wrap_subxt_call!(self, GetFinalizedBlockSubscription, ChainSubscription, url, ())
+		wrap_subxt_call!(self, GetFinalizedBlockSubscription, ChainSubscription, url)
 	}
 }
 
 // Attempts to connect to websocket and returns an RuntimeApi instance if successful.
-async fn new_client_fn(url: &str, retry: &RetryOptions) -> Option<ApiClient> {
-	let mut retry = Retry::new(retry);
+async fn new_client_fn(url: &str, retry: &RetryOptions, context: &ExecutorContext) -> Option<ApiClient> {
+	let mut retry = Retry::new(retry).with_rpc_budget(context.rpc_budget().clone());
 
 	loop {
-		match ApiClient::build(url).await {
+		match ApiClient::build(url, context.client_factory()).await {
 			Ok(client) => return Some(client),
 			Err(err) => {
 				error!("[{}] Client error: {:?}", url, err);
@@ -438,6 +699,20 @@ async fn new_client_fn(url: &str, retry: &RetryOptions) -> Option<ApiClient> {
 	}
 }
 
+async fn select_decoding(url: &str, api: &ApiClient) -> Decoding {
+	match api.legacy_get_runtime_version().await {
+		Ok(version) => {
+			let decoding = decoding_for(&version);
+			info!("[{}] Using {} decoding for {}", url, decoding, version);
+			decoding
+		},
+		Err(err) => {
+			warn!("[{}] Cannot get runtime version, using dynamic decoding: {:?}", url, err);
+			Decoding::Dynamic
+		},
+	}
+}
+
 async fn subxt_get_head(api: &ApiClient, maybe_hash: Option<H256>) -> Result {
 	let block_res = match maybe_hash {
 		Some(hash) => api.blocks().at(hash).await,
@@ -452,9 +727,17 @@ async fn subxt_get_head(api: &ApiClient, maybe_hash: Option<H256>) -> Result {
 	Ok(Response::MaybeHead(block.map(|v| v.header().clone())))
 }
 
-async fn subxt_get_block_ts(api: &ApiClient, hash: H256) -> Result {
-	let timestamp = polkadot::storage().timestamp().now();
-	Ok(Response::Timestamp(api.storage().at(hash).fetch(&timestamp).await?.unwrap_or_default()))
+async fn subxt_get_block_ts(api: &ApiClient, hash: H256, decoding: Decoding) -> Result {
+	let timestamp = match decoding {
+		Decoding::Static => api
+			.storage()
+			.at(hash)
+			.fetch(&polkadot::storage().timestamp().now())
+			.await?
+			.unwrap_or_default(),
+		Decoding::Dynamic => decode_timestamp(&fetch_dynamic_storage(api, hash, "Timestamp", "Now").await?)?,
+	};
+	Ok(Response::Timestamp(timestamp))
 }
 
 async fn subxt_get_block(api: &ApiClient, maybe_hash: Option<H256>) -> Result {
@@ -469,6 +752,59 @@ async fn subxt_get_block_hash(api: &ApiClient, maybe_block_number: Option<BlockN
 	Ok(Response::MaybeBlockHash(api.legacy_get_block_hash(maybe_block_number).await?))
 }
 
+async fn subxt_get_finalized_block_hash(api: &ApiClient) -> Result {
+	Ok(Response::MaybeBlockHash(Some(api.legacy_get_finalized_head().await?)))
+}
+
+async fn subxt_get_spec_version(api: &ApiClient, hash: H256) -> Result {
+	Ok(Response::SpecVersion(api.legacy_get_spec_version(hash).await?))
+}
+
+async fn subxt_get_runtime_version(api: &ApiClient) -> Result {
+	Ok(Response::RuntimeVersion(api.legacy_get_runtime_version().await?))
+}
+
+/// Parachains built with Cumulus keep their id in the `ParachainInfo` pallet
+async fn subxt_get_parachain_id(api: &ApiClient) -> Result {
+	let addr = subxt::dynamic::storage("ParachainInfo", "ParachainId", Vec::<Value>::new());
+	let key = api.storage().address_bytes(&addr)?;
+	let para_id = match api.storage().at_latest().await?.fetch_raw(&key[..]).await? {
+		Some(raw_id) => Some(u32::decode(&mut &raw_id[..]).map_err(|e| SubxtWrapperError::DecodeError {
+			type_path: "ParachainInfo.ParachainId".to_string(),
+			error: e.to_string(),
+			bytes: raw_id.clone(),
+		})?),
+		None => None,
+	};
+	Ok(Response::ParachainId(para_id))
+}
+
+async fn subxt_get_block_weight(api: &ApiClient, hash: H256) -> Result {
+	let addr = subxt::dynamic::storage("System", "BlockWeight", Vec::<Value>::new());
+	let key = api.storage().address_bytes(&addr)?;
+	let weight = match api.storage().at(hash).fetch_raw(&key[..]).await? {
+		// The weight of the normal, operational and mandatory dispatch classes
+		Some(raw_weight) => Some(
+			<[(Compact<u64>, Compact<u64>); 3]>::decode(&mut &raw_weight[..])
+				.map(|classes| {
+					classes
+						.iter()
+						.fold(BlockWeight::default(), |total, (ref_time, proof_size)| BlockWeight {
+							ref_time: total.ref_time.saturating_add(ref_time.0),
+							proof_size: total.proof_size.saturating_add(proof_size.0),
+						})
+				})
+				.map_err(|e| SubxtWrapperError::DecodeError {
+					type_path: "System.BlockWeight".to_string(),
+					error: e.to_string(),
+					bytes: raw_weight.clone(),
+				})?,
+		),
+		None => None,
+	};
+	Ok(Response::BlockWeight(weight))
+}
+
 async fn subxt_get_events(api: &ApiClient, hash: H256) -> Result {
 	Ok(Response::MaybeEvents(Some(api.events().at(hash).await?)))
 }
@@ -499,6 +835,25 @@ async fn fetch_dynamic_storage(
 		})
 }
 
+/// Fetches a storage map entry, `None` if the key is not present
+async fn fetch_dynamic_storage_entry(
+	api: &ApiClient,
+	block_hash: H256,
+	pallet_name: &str,
+	entry_name: &str,
+	keys: Vec<Value>,
+) -> std::result::Result<Option<Value<u32>>, SubxtWrapperError> {
+	match api
+		.storage()
+		.at(block_hash)
+		.fetch(&subxt::dynamic::storage(pallet_name, entry_name, keys))
+		.await?
+	{
+		Some(v) => Ok(Some(v.to_value()?)),
+		None => Ok(None),
+	}
+}
+
 async fn subxt_get_sheduled_paras(api: &ApiClient, block_hash: H256) -> Result {
 	let value = fetch_dynamic_storage(api, block_hash, "ParaScheduler", "Scheduled").await?;
 	let paras = decode_scheduled_paras(&value)?;
@@ -527,21 +882,94 @@ async fn subxt_get_validator_groups(api: &ApiClient, block_hash: H256) -> Result
 	Ok(Response::BackingGroups(groups))
 }
 
-async fn subxt_get_session_index(api: &ApiClient, block_hash: H256) -> Result {
-	let addr = polkadot::storage().session().current_index();
-	let session_index = api.storage().at(block_hash).fetch(&addr).await?.unwrap_or_default();
+async fn subxt_get_session_start_block(api: &ApiClient, block_hash: H256) -> Result {
+	let value = fetch_dynamic_storage(api, block_hash, "ParaScheduler", "SessionStartBlock").await?;
+	let block_number = decode_session_start_block(&value)?;
+
+	Ok(Response::SessionStartBlock(block_number))
+}
+
+async fn subxt_get_session_index(api: &ApiClient, block_hash: H256, decoding: Decoding) -> Result {
+	let session_index = match decoding {
+		Decoding::Static => {
+			let addr = polkadot::storage().session().current_index();
+			api.storage().at(block_hash).fetch(&addr).await?.unwrap_or_default()
+		},
+		Decoding::Dynamic =>
+			decode_session_index(&fetch_dynamic_storage(api, block_hash, "Session", "CurrentIndex").await?)?,
+	};
 	Ok(Response::SessionIndex(session_index))
 }
 
-async fn subxt_get_session_account_keys(api: &ApiClient, session_index: u32) -> Result {
-	let addr = polkadot::storage().para_session_info().account_keys(session_index);
-	let session_keys = api.storage().at_latest().await?.fetch(&addr).await?;
+async fn subxt_get_session_account_keys(
+	api: &ApiClient,
+	block_hash: H256,
+	session_index: u32,
+	decoding: Decoding,
+) -> Result {
+	let session_keys = match decoding {
+		Decoding::Static => {
+			let addr = polkadot::storage().para_session_info().account_keys(session_index);
+			api.storage().at(block_hash).fetch(&addr).await?
+		},
+		Decoding::Dynamic => {
+			let keys = vec![Value::u128(session_index as u128)];
+			match fetch_dynamic_storage_entry(api, block_hash, "ParaSessionInfo", "AccountKeys", keys).await? {
+				Some(raw_keys) => Some(decode_account_keys(&raw_keys)?),
+				None => None,
+			}
+		},
+	};
 	Ok(Response::SessionAccountKeys(session_keys))
 }
 
-async fn subxt_get_session_next_keys(api: &ApiClient, account: &AccountId32) -> Result {
-	let addr = polkadot::storage().session().next_keys(account);
-	let next_keys = api.storage().at_latest().await?.fetch(&addr).await?;
+async fn subxt_get_active_validator_indices(
+	api: &ApiClient,
+	block_hash: H256,
+	session_index: u32,
+	decoding: Decoding,
+) -> Result {
+	let indices = match decoding {
+		Decoding::Static => {
+			let addr = polkadot::storage().para_session_info().sessions(session_index);
+			api.storage()
+				.at(block_hash)
+				.fetch(&addr)
+				.await?
+				.map(|info| info.active_validator_indices.into_iter().map(|index| index.0).collect())
+		},
+		Decoding::Dynamic => {
+			let keys = vec![Value::u128(session_index as u128)];
+			match fetch_dynamic_storage_entry(api, block_hash, "ParaSessionInfo", "Sessions", keys).await? {
+				Some(raw_info) => Some(decode_active_validator_indices(&raw_info)?),
+				None => None,
+			}
+		},
+	};
+	Ok(Response::ActiveValidatorIndices(indices))
+}
+
+async fn subxt_get_session_next_keys(api: &ApiClient, account: &AccountId32, decoding: Decoding) -> Result {
+	let next_keys = match decoding {
+		Decoding::Static => {
+			let addr = polkadot::storage().session().next_keys(account);
+			api.storage().at_latest().await?.fetch(&addr).await?
+		},
+		Decoding::Dynamic => {
+			let account_value = Value::unnamed_composite([Value::from_bytes(account.0)]);
+			let addr = subxt::dynamic::storage("Session", "NextKeys", vec![account_value]);
+			match api.storage().at_latest().await?.fetch(&addr).await? {
+				// The keys of a runtime are decoded by their names, so an upgrade reordering them still decodes
+				Some(raw_keys) =>
+					Some(raw_keys.as_type::<SessionKeys>().map_err(|e| SubxtWrapperError::DecodeError {
+						type_path: "Session.NextKeys".to_string(),
+						error: e.to_string(),
+						bytes: raw_keys.encoded().to_vec(),
+					})?),
+				None => None,
+			}
+		},
+	};
 	Ok(Response::SessionNextKeys(next_keys))
 }
 
@@ -573,43 +1001,164 @@ impl From<polkadot::runtime_types::polkadot_runtime_parachains::hrmp::HrmpChanne
 	}
 }
 
-async fn subxt_get_inbound_hrmp_channels(api: &ApiClient, block_hash: H256, para_id: u32) -> Result {
-	use polkadot::runtime_types::polkadot_parachain::primitives::{HrmpChannelId, Id};
-	let addr = polkadot::storage().hrmp().hrmp_ingress_channels_index(&Id(para_id));
-	let hrmp_channels = api.storage().at(block_hash).fetch(&addr).await?.unwrap_or_default();
+/// Direction of the HRMP channels of a parachain
+#[derive(Clone, Copy, Debug)]
+enum HrmpDirection {
+	Inbound,
+	Outbound,
+}
+
+/// Channels of a parachain keyed by the peer parachain
+async fn subxt_get_hrmp_channels(
+	api: &ApiClient,
+	block_hash: H256,
+	para_id: u32,
+	direction: HrmpDirection,
+	decoding: Decoding,
+) -> Result {
 	let mut channels_configuration: BTreeMap<u32, SubxtHrmpChannel> = BTreeMap::new();
-	for peer_parachain_id in hrmp_channels.into_iter().map(|id| id.0) {
-		let id = HrmpChannelId { sender: Id(peer_parachain_id), recipient: Id(para_id) };
-		let addr = polkadot::storage().hrmp().hrmp_channels(&id);
-		api.storage()
-			.at(block_hash)
-			.fetch(&addr)
-			.await?
-			.map(|hrmp_channel_configuration| {
-				channels_configuration.insert(peer_parachain_id, hrmp_channel_configuration.into())
-			});
+	for peer_parachain_id in fetch_hrmp_peers(api, block_hash, para_id, direction, decoding).await? {
+		let (sender, recipient) = match direction {
+			HrmpDirection::Inbound => (peer_parachain_id, para_id),
+			HrmpDirection::Outbound => (para_id, peer_parachain_id),
+		};
+		if let Some(channel) = fetch_hrmp_channel(api, block_hash, sender, recipient, decoding).await? {
+			channels_configuration.insert(peer_parachain_id, channel);
+		}
 	}
 	Ok(Response::HRMPChannels(channels_configuration))
 }
 
-async fn subxt_get_outbound_hrmp_channels(api: &ApiClient, block_hash: H256, para_id: u32) -> Result {
+async fn fetch_hrmp_peers(
+	api: &ApiClient,
+	block_hash: H256,
+	para_id: u32,
+	direction: HrmpDirection,
+	decoding: Decoding,
+) -> std::result::Result<Vec<u32>, SubxtWrapperError> {
+	use polkadot::runtime_types::polkadot_parachain::primitives::Id;
+
+	let peers = match (decoding, direction) {
+		(Decoding::Static, HrmpDirection::Inbound) => {
+			let addr = polkadot::storage().hrmp().hrmp_ingress_channels_index(&Id(para_id));
+			api.storage().at(block_hash).fetch(&addr).await?.unwrap_or_default()
+		},
+		(Decoding::Static, HrmpDirection::Outbound) => {
+			let addr = polkadot::storage().hrmp().hrmp_egress_channels_index(&Id(para_id));
+			api.storage().at(block_hash).fetch(&addr).await?.unwrap_or_default()
+		},
+		(Decoding::Dynamic, _) => {
+			let entry_name = match direction {
+				HrmpDirection::Inbound => "HrmpIngressChannelsIndex",
+				HrmpDirection::Outbound => "HrmpEgressChannelsIndex",
+			};
+			let keys = vec![para_id_value(para_id)];
+			return match fetch_dynamic_storage_entry(api, block_hash, "Hrmp", entry_name, keys).await? {
+				Some(raw_ids) => decode_para_ids(&raw_ids),
+				None => Ok(vec![]),
+			}
+		},
+	};
+	Ok(peers.into_iter().map(|id| id.0).collect())
+}
+
+async fn fetch_hrmp_channel(
+	api: &ApiClient,
+	block_hash: H256,
+	sender: u32,
+	recipient: u32,
+	decoding: Decoding,
+) -> std::result::Result<Option<SubxtHrmpChannel>, SubxtWrapperError> {
 	use polkadot::runtime_types::polkadot_parachain::primitives::{HrmpChannelId, Id};
 
-	let addr = polkadot::storage().hrmp().hrmp_egress_channels_index(&Id(para_id));
-	let hrmp_channels = api.storage().at(block_hash).fetch(&addr).await?.unwrap_or_default();
-	let mut channels_configuration: BTreeMap<u32, SubxtHrmpChannel> = BTreeMap::new();
-	for peer_parachain_id in hrmp_channels.into_iter().map(|id| id.0) {
-		let id = HrmpChannelId { sender: Id(peer_parachain_id), recipient: Id(para_id) };
-		let addr = polkadot::storage().hrmp().hrmp_channels(&id);
-		api.storage()
-			.at(block_hash)
-			.fetch(&addr)
-			.await?
-			.map(|hrmp_channel_configuration| {
-				channels_configuration.insert(peer_parachain_id, hrmp_channel_configuration.into())
-			});
-	}
-	Ok(Response::HRMPChannels(channels_configuration))
+	Ok(match decoding {
+		Decoding::Static => {
+			let addr = polkadot::storage()
+				.hrmp()
+				.hrmp_channels(&HrmpChannelId { sender: Id(sender), recipient: Id(recipient) });
+			api.storage().at(block_hash).fetch(&addr).await?.map(|channel| channel.into())
+		},
+		Decoding::Dynamic => {
+			let id =
+				Value::named_composite([("sender", para_id_value(sender)), ("recipient", para_id_value(recipient))]);
+			match fetch_dynamic_storage_entry(api, block_hash, "Hrmp", "HrmpChannels", vec![id]).await? {
+				Some(raw_channel) => Some(decode_hrmp_channel(&raw_channel)?),
+				None => None,
+			}
+		},
+	})
+}
+
+/// The `Dmp` pallet keeps no length of a queue, so only the length prefix of the stored queue is decoded,
+/// the messages are skipped
+async fn subxt_get_downward_queue_len(api: &ApiClient, block_hash: H256, para_id: u32) -> Result {
+	let addr = subxt::dynamic::storage("Dmp", "DownwardMessageQueues", vec![para_id_value(para_id)]);
+	let key = api.storage().address_bytes(&addr)?;
+	let queue_len = match api.storage().at(block_hash).fetch_raw(&key[..]).await? {
+		Some(raw_queue) => match Compact::<u32>::decode(&mut &raw_queue[..]) {
+			Ok(len) => len.0,
+			Err(e) => Err(SubxtWrapperError::DecodeError {
+				type_path: "Dmp.DownwardMessageQueues".to_string(),
+				error: e.to_string(),
+				bytes: raw_queue,
+			})?,
+		},
+		None => 0,
+	};
+	Ok(Response::MessageQueueLen(queue_len))
+}
+
+/// Upward messages are queued in the `MessageQueue` pallet, keyed by the `Ump(Para(id))` origin
+async fn subxt_get_upward_queue_len(api: &ApiClient, block_hash: H256, para_id: u32) -> Result {
+	let origin = Value::unnamed_variant("Ump", [Value::unnamed_variant("Para", [para_id_value(para_id)])]);
+	let addr = subxt::dynamic::storage("MessageQueue", "BookStateFor", vec![origin]);
+	let message_count = match api.storage().at(block_hash).fetch(&addr).await? {
+		Some(book_state) => decode_message_count(&book_state.to_value()?)?,
+		None => 0,
+	};
+	Ok(Response::MessageQueueLen(message_count))
+}
+
+async fn subxt_get_para_head(api: &ApiClient, block_hash: H256, para_id: u32, decoding: Decoding) -> Result {
+	let head = fetch_para_head(api, block_hash, para_id, decoding).await?;
+	Ok(Response::ParaHead(head.map(|head| BlakeTwo256::hash(&head))))
+}
+
+async fn subxt_get_para_head_number(api: &ApiClient, block_hash: H256, para_id: u32, decoding: Decoding) -> Result {
+	let head = fetch_para_head(api, block_hash, para_id, decoding).await?;
+	// Head data of parachains built with Cumulus is an encoded header
+	let number = head
+		.and_then(|head| Header::decode(&mut &head[..]).ok())
+		.map(|header| header.number);
+	Ok(Response::ParaHeadNumber(number))
+}
+
+async fn fetch_para_head(
+	api: &ApiClient,
+	block_hash: H256,
+	para_id: u32,
+	decoding: Decoding,
+) -> std::result::Result<Option<Vec<u8>>, SubxtWrapperError> {
+	use polkadot::runtime_types::polkadot_parachain::primitives::Id;
+
+	Ok(match decoding {
+		Decoding::Static => {
+			let addr = polkadot::storage().paras().heads(&Id(para_id));
+			api.storage().at(block_hash).fetch(&addr).await?.map(|head| head.0)
+		},
+		Decoding::Dynamic => {
+			let addr = subxt::dynamic::storage("Paras", "Heads", vec![para_id_value(para_id)]);
+			match api.storage().at(block_hash).fetch(&addr).await? {
+				Some(head) => Some(decode_head_data(&head.to_value()?)?),
+				None => None,
+			}
+		},
+	})
+}
+
+/// `ParaId` as a dynamic storage key
+fn para_id_value(para_id: u32) -> Value {
+	Value::unnamed_composite([Value::u128(para_id as u128)])
 }
 
 async fn subxt_get_host_configuration(api: &ApiClient) -> Result {
@@ -635,21 +1184,27 @@ async fn subxt_get_finalized_block_subscription(api: &ApiClient) -> Result {
 async fn subxt_extract_parainherent(
 	block: &subxt::blocks::Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
 ) -> Result {
-	let ex = block
-		.extrinsics()
-		.await?
-		.iter()
-		.take(2)
-		.last()
-		.expect("`ParaInherent` data is always at index #1")
-		.expect("`ParaInherent` data must exist");
-
-	let enter = ex
-		.as_extrinsic::<polkadot::para_inherent::calls::types::Enter>()
-		.expect("Failed to decode `ParaInherent`")
-		.expect("`ParaInherent` must exist");
+	let extrinsics = block.extrinsics().await?;
+	let decode_error = |error: String, bytes: &[u8]| SubxtWrapperError::DecodeError {
+		type_path: "ParaInherent.enter".to_string(),
+		error,
+		bytes: bytes.to_vec(),
+	};
+	// `ParaInherent` data is always at index #1
+	let ex = match extrinsics.iter().nth(1) {
+		Some(Ok(v)) => v,
+		Some(Err(e)) => return Err(decode_error(e.to_string(), &[])),
+		None => return Err(decode_error("no extrinsic at index #1".to_string(), &[])),
+	};
 
-	Ok(Response::ParaInherentData(enter.data))
+	match ex.as_extrinsic::<polkadot::para_inherent::calls::types::Enter>() {
+		Ok(Some(enter)) => Ok(Response::ParaInherentData(enter.data)),
+		Ok(None) => Err(decode_error(
+			format!("unexpected extrinsic {}.{}", ex.pallet_name().unwrap_or("?"), ex.variant_name().unwrap_or("?")),
+			ex.bytes(),
+		)),
+		Err(e) => Err(decode_error(e.to_string(), ex.bytes())),
+	}
 }
 
 #[derive(Debug, Error)]
@@ -666,6 +1221,8 @@ pub enum SubxtWrapperError {
 	EmptyResponseFromDynamicStorage(String),
 	#[error("decode dynamic value error: expected `{0}`, got {1}")]
 	DecodeDynamicError(String, ValueDef<u32>),
+	#[error("cannot decode {type_path}: {error}")]
+	DecodeError { type_path: String, error: String, bytes: Vec<u8> },
 }
 pub type Result = std::result::Result<Response, SubxtWrapperError>;
 
@@ -686,6 +1243,49 @@ impl DynamicHostConfiguration {
 			None => format!("{}", 0),
 		}
 	}
+
+	/// Number of relay chain blocks before the most recent one allowed to be used as relay parents,
+	/// `None` before async backing
+	pub fn allowed_ancestry_len(&self) -> Option<u32> {
+		self.async_backing_param("allowed_ancestry_len")
+	}
+
+	/// Number of relay chain blocks after that the validator groups rotate to the next availability core
+	pub fn group_rotation_frequency(&self) -> Option<u32> {
+		self.u32_param("group_rotation_frequency")
+	}
+
+	/// Number of relay chain blocks after that an approval checker who hasn't voted is considered a no-show
+	pub fn no_show_slots(&self) -> Option<u32> {
+		self.u32_param("no_show_slots")
+	}
+
+	/// Minimum number of backing votes a candidate needs to be included, `None` before it was configurable
+	pub fn minimum_backing_votes(&self) -> Option<u32> {
+		self.u32_param("minimum_backing_votes")
+	}
+
+	fn u32_param(&self, field: &str) -> Option<u32> {
+		match self.0.at(field).map(|value| &value.value) {
+			Some(ValueDef::Primitive(Primitive::U128(v))) => Some(*v as u32),
+			_ => None,
+		}
+	}
+
+	/// Maximum number of candidates of a parachain built on top of an unincluded one, `None` before async backing
+	pub fn max_candidate_depth(&self) -> Option<u32> {
+		self.async_backing_param("max_candidate_depth")
+	}
+
+	fn async_backing_param(&self, field: &str) -> Option<u32> {
+		self.0
+			.at("async_backing_params")
+			.and_then(|params| params.at(field))
+			.and_then(|value| match value.value {
+				ValueDef::Primitive(Primitive::U128(v)) => Some(v as u32),
+				_ => None,
+			})
+	}
 }
 
 impl std::fmt::Display for DynamicHostConfiguration {
@@ -695,12 +1295,18 @@ impl std::fmt::Display for DynamicHostConfiguration {
 			"\t👀 Max validators: {} / {} per core
 \t👍 Needed approvals: {}
 \t🥔 No show slots: {}
-\t⏳ Delay tranches: {}",
+\t⏳ Delay tranches: {}
+\t⚓ Allowed relay parent ancestry: {}
+\t🪜 Max candidate depth: {}",
 			self.at("max_validators"),
 			self.at("max_validators_per_core"),
 			self.at("needed_approvals"),
 			self.at("no_show_slots"),
 			self.at("n_delay_tranches"),
+			self.allowed_ancestry_len()
+				.map_or_else(|| "NA".to_owned(), |len| format!("{} blocks", len)),
+			self.max_candidate_depth()
+				.map_or_else(|| "NA".to_owned(), |depth| depth.to_string()),
 		)
 	}
 }
@@ -16,7 +16,8 @@
 //
 
 use crate::{
-	api::dynamic::decode_on_demand_order,
+	api::dynamic::{decode_extrinsic_weight, decode_on_demand_order},
+	event_decoding::EventDecodingCounter,
 	metadata::{
 		polkadot::{
 			para_inclusion::events::{CandidateBacked, CandidateIncluded, CandidateTimedOut},
@@ -24,16 +25,22 @@ use crate::{
 		},
 		polkadot_primitives::CandidateDescriptor,
 	},
-	types::{Header, OnDemandOrder, H256},
+	types::{ExtrinsicWeight, Header, OnDemandOrder, H256},
 };
+use clap::ValueEnum;
 use color_eyre::{eyre::eyre, Result};
 use parity_scale_codec::{Decode, Encode};
 use serde::Serialize;
+use std::time::Instant;
 use subxt::{
 	config::{substrate::BlakeTwo256, Hasher},
+	events::Phase,
 	PolkadotConfig,
 };
 
+/// `ParaInherent` is always the second extrinsic of a relay chain block, after the timestamp
+const PARA_INHERENT_EXTRINSIC_INDEX: u32 = 1;
+
 #[derive(Debug)]
 pub enum ChainEvent<T: subxt::Config> {
 	/// New best relay chain head
@@ -48,10 +55,48 @@ pub enum ChainEvent<T: subxt::Config> {
 	CandidateChanged(Box<SubxtCandidateEvent>),
 	/// On-demand parachain placed its order
 	OnDemandOrderPlaced(<PolkadotConfig as subxt::Config>::Hash, OnDemandOrder),
+	/// Actual weight of the `ParaInherent` extrinsic
+	ParaInherentWeight(<PolkadotConfig as subxt::Config>::Hash, ExtrinsicWeight),
 	/// Anything undecoded
 	RawEvent(<PolkadotConfig as subxt::Config>::Hash, subxt::events::EventDetails<T>),
 }
 
+/// Kinds of the chain events the collector can decode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ChainEventKind {
+	/// Initiated and concluded disputes
+	Disputes,
+	/// Backed, included and timed out candidates
+	Candidates,
+	/// Placed on-demand orders
+	OnDemandOrders,
+	/// Actual weight of the `ParaInherent` extrinsic
+	ParaInherentWeight,
+}
+
+impl ChainEventKind {
+	/// Returns the kind of an event, `None` for the events never decoded
+	pub fn of<T: subxt::Config>(event: &subxt::events::EventDetails<T>) -> Option<ChainEventKind> {
+		if is_specific_event::<DisputeInitiated, T>(event) || is_specific_event::<DisputeConcluded, T>(event) {
+			Some(ChainEventKind::Disputes)
+		} else if is_specific_event::<CandidateBacked, T>(event) ||
+			is_specific_event::<CandidateIncluded, T>(event) ||
+			is_specific_event::<CandidateTimedOut, T>(event)
+		{
+			Some(ChainEventKind::Candidates)
+		} else if event.pallet_name() == "OnDemandAssignmentProvider" && event.variant_name() == "OnDemandOrderPlaced" {
+			Some(ChainEventKind::OnDemandOrders)
+		} else if event.pallet_name() == "System" &&
+			event.variant_name() == "ExtrinsicSuccess" &&
+			matches!(event.phase(), Phase::ApplyExtrinsic(PARA_INHERENT_EXTRINSIC_INDEX))
+		{
+			Some(ChainEventKind::ParaInherentWeight)
+		} else {
+			None
+		}
+	}
+}
+
 #[derive(Debug)]
 pub enum SubxtCandidateEventType {
 	/// Candidate has been backed
@@ -73,6 +118,10 @@ pub struct SubxtCandidateEvent {
 	pub parachain_id: u32,
 	/// The event type
 	pub event_type: SubxtCandidateEventType,
+	/// Core the candidate was backed on, known for backed candidates
+	pub core_idx: Option<u32>,
+	/// Backing group of the candidate, known for backed candidates
+	pub group_idx: Option<u32>,
 }
 
 /// A helper structure to keep track of a dispute and it's relay parent
@@ -96,9 +145,31 @@ pub enum SubxtDisputeResult {
 	TimedOut,
 }
 
+/// Decodes an event of the given kinds, events of the other kinds are returned as raw events
 pub async fn decode_chain_event<T: subxt::Config>(
 	block_hash: <PolkadotConfig as subxt::Config>::Hash,
 	event: subxt::events::EventDetails<T>,
+	kinds: &[ChainEventKind],
+	counter: &EventDecodingCounter,
+) -> Result<ChainEvent<T>> {
+	match ChainEventKind::of(&event) {
+		Some(kind) if kinds.contains(&kind) => {
+			let started_at = Instant::now();
+			let decoded = decode_specific_event(block_hash, event);
+			counter.on_decoded(started_at.elapsed());
+			decoded
+		},
+		Some(_) => {
+			counter.on_skipped();
+			Ok(ChainEvent::RawEvent(block_hash, event))
+		},
+		None => Ok(ChainEvent::RawEvent(block_hash, event)),
+	}
+}
+
+fn decode_specific_event<T: subxt::Config>(
+	block_hash: <PolkadotConfig as subxt::Config>::Hash,
+	event: subxt::events::EventDetails<T>,
 ) -> Result<ChainEvent<T>> {
 	if is_specific_event::<DisputeInitiated, T>(&event) {
 		let decoded = decode_to_specific_event::<DisputeInitiated, T>(&event)?;
@@ -123,11 +194,11 @@ pub async fn decode_chain_event<T: subxt::Config>(
 
 	if is_specific_event::<CandidateBacked, T>(&event) {
 		let decoded = decode_to_specific_event::<CandidateBacked, T>(&event)?;
-		return Ok(ChainEvent::CandidateChanged(Box::new(create_candidate_event(
-			decoded.0.commitments_hash,
-			decoded.0.descriptor,
-			SubxtCandidateEventType::Backed,
-		))))
+		let mut candidate_event =
+			create_candidate_event(decoded.0.commitments_hash, decoded.0.descriptor, SubxtCandidateEventType::Backed);
+		candidate_event.core_idx = Some(decoded.2 .0);
+		candidate_event.group_idx = Some(decoded.3 .0);
+		return Ok(ChainEvent::CandidateChanged(Box::new(candidate_event)))
 	}
 
 	if is_specific_event::<CandidateIncluded, T>(&event) {
@@ -154,6 +225,14 @@ pub async fn decode_chain_event<T: subxt::Config>(
 		return Ok(ChainEvent::OnDemandOrderPlaced(block_hash, decoded))
 	}
 
+	if event.pallet_name() == "System" &&
+		event.variant_name() == "ExtrinsicSuccess" &&
+		matches!(event.phase(), Phase::ApplyExtrinsic(PARA_INHERENT_EXTRINSIC_INDEX))
+	{
+		let decoded = decode_extrinsic_weight(&event.field_values()?)?;
+		return Ok(ChainEvent::ParaInherentWeight(block_hash, decoded))
+	}
+
 	Ok(ChainEvent::RawEvent(block_hash, event))
 }
 
@@ -194,5 +273,12 @@ fn create_candidate_event(
 ) -> SubxtCandidateEvent {
 	let candidate_hash = BlakeTwo256::hash_of(&(&candidate_descriptor, commitments_hash));
 	let parachain_id = candidate_descriptor.para_id.0;
-	SubxtCandidateEvent { event_type, candidate_descriptor, parachain_id, candidate_hash }
+	SubxtCandidateEvent {
+		event_type,
+		candidate_descriptor,
+		parachain_id,
+		candidate_hash,
+		core_idx: None,
+		group_idx: None,
+	}
 }
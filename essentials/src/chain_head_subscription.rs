@@ -16,25 +16,48 @@
 //
 
 use crate::{
-	api::subxt_wrapper::RequestExecutor,
+	api::subxt_wrapper::{ExecutorContext, RequestExecutor, SubxtWrapperError},
 	chain_subscription::ChainSubscriptionEvent,
-	constants::MAX_MSG_QUEUE_SIZE,
+	chaos::{ChaosInjector, Fault},
+	constants::{MAX_MSG_QUEUE_SIZE, STANDARD_BLOCK_TIME},
 	consumer::{EventConsumerInit, EventStream},
+	incidents::{Incident, IncidentSource, Incidents, Severity},
+	types::{BlockNumber, Header, H256},
 	utils::RetryOptions,
 };
 use async_trait::async_trait;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use polkadot_introspector_priority_channel::{channel, Sender};
+use subxt::config::Header as _;
 use tokio::{
-	sync::broadcast::Sender as BroadcastSender,
-	time::{interval_at, Duration},
+	sync::broadcast::{error::TryRecvError, Receiver as BroadcastReceiver, Sender as BroadcastSender},
+	time::{interval_at, sleep, Duration, Instant, Interval},
 };
 
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(200);
+/// A subscription is considered broken if it hasn't produced any blocks for this time
+const SUBSCRIPTION_SILENCE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Interval between polls if the previous poll hasn't found a new block
+const POLLING_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// Maximum number of blocks to fetch if several blocks were produced between polls
+const MAX_POLLING_BACKFILL: usize = 16;
+/// Maximum number of blocks fetched in parallel when backfilling from a past block
+const MAX_BACKFILL_REQUESTS: usize = 8;
+/// Interval after which subscriptions are tried again while polling
+const SUBSCRIPTION_RETRY_INTERVAL: Duration = Duration::from_secs(300);
+
 pub struct ChainHeadSubscription {
 	urls: Vec<String>,
 	/// One sender per consumer per URL.
 	consumers: Vec<Vec<Sender<ChainSubscriptionEvent>>>,
 	retry: RetryOptions,
+	context: ExecutorContext,
+	/// Follow only finalized blocks, without best heads
+	finalized_only: bool,
+	/// Past block to send the blocks from before following the chain
+	backfill_from: Option<BlockNumber>,
+	/// Log of broken subscriptions, failed polling and backfilled outages
+	incidents: Incidents,
 }
 
 #[async_trait]
@@ -60,7 +83,16 @@ impl EventStream for ChainHeadSubscription {
 
 	async fn run(self, shutdown_tx: &BroadcastSender<()>) -> color_eyre::Result<Vec<tokio::task::JoinHandle<()>>> {
 		let futures = self.consumers.into_iter().map(|update_channels| {
-			Self::run_per_consumer(update_channels, self.urls.clone(), shutdown_tx.clone(), self.retry.clone())
+			Self::run_per_consumer(
+				update_channels,
+				self.urls.clone(),
+				shutdown_tx.clone(),
+				self.retry.clone(),
+				self.context.clone(),
+				self.finalized_only,
+				self.backfill_from,
+				self.incidents.clone(),
+			)
 		});
 
 		Ok(futures.flatten().collect::<Vec<_>>())
@@ -69,39 +101,368 @@ impl EventStream for ChainHeadSubscription {
 
 impl ChainHeadSubscription {
 	pub fn new(urls: Vec<String>, retry: RetryOptions) -> ChainHeadSubscription {
-		ChainHeadSubscription { urls, consumers: Vec::new(), retry }
+		ChainHeadSubscription {
+			urls,
+			consumers: Vec::new(),
+			retry,
+			context: Default::default(),
+			finalized_only: false,
+			backfill_from: None,
+			incidents: Default::default(),
+		}
+	}
+
+	/// Sends only finalized blocks, so consumers are not affected by forks at the cost of finality delay
+	pub fn finalized_only(mut self) -> Self {
+		self.finalized_only = true;
+		self
+	}
+
+	/// Sends the finalized blocks starting from the given one before following the chain,
+	/// so the consumers continue from where they were after a restart
+	pub fn backfill_from(mut self, block_number: BlockNumber) -> Self {
+		self.backfill_from = Some(block_number);
+		self
+	}
+
+	/// Builds the executors of the subscription tasks with the given context
+	pub fn with_executor_context(mut self, context: ExecutorContext) -> Self {
+		self.context = context;
+		self
+	}
+
+	/// Records the connection problems of the nodes in the given incident log
+	pub fn with_incidents(mut self, incidents: Incidents) -> Self {
+		self.incidents = incidents;
+		self
 	}
 
 	// Per node
 	async fn run_per_node(
-		mut update_channel: Sender<ChainSubscriptionEvent>,
+		update_channel: Sender<ChainSubscriptionEvent>,
 		url: String, // `String` rather than `&str` because we spawn this method as an asynchronous task
 		shutdown_tx: BroadcastSender<()>,
 		retry: RetryOptions,
+		context: ExecutorContext,
+		finalized_only: bool,
+		backfill_from: Option<BlockNumber>,
+		incidents: Incidents,
 	) {
-		use futures::stream::{select, StreamExt};
-		use futures_util::TryStreamExt;
+		let mut follower = NodeFollower {
+			chaos: context.chaos().clone(),
+			executor: RequestExecutor::new(retry, context),
+			shutdown_rx: shutdown_tx.subscribe(),
+			heartbeat: interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL),
+			state: Default::default(),
+			update_channel,
+			url,
+			finalized_only,
+			held_head: None,
+			incidents,
+		};
+
+		if let Some(mut from) = backfill_from {
+			loop {
+				match follower.backfill(from).await {
+					FollowOutcome::Terminated => return,
+					FollowOutcome::CaughtUp | FollowOutcome::RetrySubscription => break,
+					// Following the chain sends only new blocks, so the rest of the range is fetched again
+					FollowOutcome::Broken => {
+						from = follower.state.finalized.map_or(from, |(_, number)| number + 1);
+						warn!("[{}] Resuming backfilling from #{}", follower.url, from);
+						sleep(POLLING_RETRY_INTERVAL).await;
+						if let Ok(()) | Err(TryRecvError::Closed) = follower.shutdown_rx.try_recv() {
+							info!("Received interrupt signal shutting down backfilling");
+							return
+						}
+					},
+				}
+			}
+		}
+
+		// Only the first switch to polling is recorded as an incident
+		let mut is_polling = false;
+		loop {
+			if let FollowOutcome::Terminated = follower.follow_subscription().await {
+				return
+			}
+			warn!("Switching to polling {} for new blocks", follower.url);
+			if !std::mem::replace(&mut is_polling, true) {
+				follower.incidents.record(Incident::now(
+					Severity::Warning,
+					IncidentSource::RpcDisconnect,
+					format!("Subscription to {} is broken, switched to polling", follower.url),
+				));
+			}
+			if let FollowOutcome::Terminated = follower.follow_polling().await {
+				return
+			}
+			info!("Trying subscriptions to {} again", follower.url);
+		}
+	}
+
+	// Sets up per websocket tasks to handle updates and reconnects on errors.
+	fn run_per_consumer(
+		update_channels: Vec<Sender<ChainSubscriptionEvent>>,
+		urls: Vec<String>,
+		shutdown_tx: BroadcastSender<()>,
+		retry: RetryOptions,
+		context: ExecutorContext,
+		finalized_only: bool,
+		backfill_from: Option<BlockNumber>,
+		incidents: Incidents,
+	) -> Vec<tokio::task::JoinHandle<()>> {
+		update_channels
+			.into_iter()
+			.zip(urls.into_iter())
+			.map(|(update_channel, url)| {
+				tokio::spawn(Self::run_per_node(
+					update_channel,
+					url,
+					shutdown_tx.clone(),
+					retry.clone(),
+					context.clone(),
+					finalized_only,
+					backfill_from,
+					incidents.clone(),
+				))
+			})
+			.collect()
+	}
+}
+
+/// Reason to stop following the chain or backfilling
+enum FollowOutcome {
+	/// Shutdown requested or the consumer has terminated
+	Terminated,
+	/// Subscription is unsupported, broken or silent
+	Broken,
+	/// Backfilling has reached the finalized head
+	CaughtUp,
+	/// Polling has run for a while, subscriptions may work again
+	RetrySubscription,
+}
+
+/// The last blocks sent to the consumer
+#[derive(Default)]
+struct FollowState {
+	best: Option<(H256, BlockNumber)>,
+	finalized: Option<(H256, BlockNumber)>,
+}
+
+impl FollowState {
+	fn on_event(&mut self, event: &ChainSubscriptionEvent) {
+		match event {
+			ChainSubscriptionEvent::NewBestHead((hash, header)) => self.best = Some((*hash, header.number)),
+			ChainSubscriptionEvent::NewFinalizedBlock((hash, header)) => self.finalized = Some((*hash, header.number)),
+			ChainSubscriptionEvent::Heartbeat | ChainSubscriptionEvent::Unreachable(_) => {},
+		}
+	}
+
+	fn is_new_best(&self, hash: H256) -> bool {
+		self.best.map_or(true, |(best_hash, _)| best_hash != hash)
+	}
+
+	fn is_new_finalized(&self, hash: H256) -> bool {
+		self.finalized.map_or(true, |(finalized_hash, _)| finalized_hash != hash)
+	}
+
+	/// Whether blocks between the last best block and the given header were not sent
+	fn is_missing_parent(&self, header: &Header) -> bool {
+		self.best.map_or(false, |(_, best_number)| header.number > best_number + 1)
+	}
+
+	/// Returns the first block to backfill if more blocks than polling fetches were produced up to the given
+	/// finalized head since the last block sent, e.g. while the node was unreachable
+	fn first_missing_block(&self, finalized_head: BlockNumber, finalized_only: bool) -> Option<BlockNumber> {
+		let (_, last_number) = if finalized_only { self.finalized } else { self.best }?;
+		(finalized_head.saturating_sub(last_number) > MAX_POLLING_BACKFILL as BlockNumber).then_some(last_number + 1)
+	}
+
+	/// Whether blocks between the last finalized block and the given header were not sent
+	fn is_missing_finalized_parent(&self, header: &Header) -> bool {
+		self.finalized
+			.map_or(false, |(_, finalized_number)| header.number > finalized_number + 1)
+	}
+}
+
+/// Follows the chain of a single node, using subscriptions or polling if they are not available
+struct NodeFollower {
+	url: String,
+	update_channel: Sender<ChainSubscriptionEvent>,
+	executor: RequestExecutor,
+	shutdown_rx: BroadcastReceiver<()>,
+	heartbeat: Interval,
+	state: FollowState,
+	finalized_only: bool,
+	/// Best head held back by the chaos mode to be sent after the following event
+	held_head: Option<ChainSubscriptionEvent>,
+	incidents: Incidents,
+	chaos: ChaosInjector,
+}
+
+impl NodeFollower {
+	/// Sends an event to the consumer, returns `false` if the consumer has terminated
+	async fn send(&mut self, event: ChainSubscriptionEvent) -> bool {
+		let is_block =
+			matches!(event, ChainSubscriptionEvent::NewBestHead(_) | ChainSubscriptionEvent::NewFinalizedBlock(_));
+		if is_block {
+			if let Some(delay) = self.chaos.delay(Fault::DelayedEvent) {
+				sleep(delay).await;
+			}
+		}
+		if matches!(event, ChainSubscriptionEvent::NewBestHead(_)) &&
+			self.held_head.is_none() &&
+			self.chaos.inject(Fault::ReorderedHead)
+		{
+			self.held_head = Some(event);
+			return true
+		}
+
+		self.state.on_event(&event);
+		if let Err(e) = self.update_channel.send(event).await {
+			info!("Event consumer has terminated: {:?}, shutting down", e);
+			return false
+		}
+		// The held head goes after the following block, the state stays at the newer one
+		let held_head = if is_block { self.held_head.take() } else { None };
+		if let Some(held_head) = held_head {
+			if let Err(e) = self.update_channel.send(held_head).await {
+				info!("Event consumer has terminated: {:?}, shutting down", e);
+				return false
+			}
+		}
+
+		true
+	}
+
+	/// Sends the blocks from the given one up to the finalized head, fetching them in parallel
+	async fn backfill(&mut self, from: BlockNumber) -> FollowOutcome {
+		use futures::stream::{self, StreamExt};
+
+		let mut next = from;
+		loop {
+			// The head moves while backfilling, so the blocks finalized meanwhile are fetched in the next round
+			let head = match self.finalized_head().await {
+				Ok(Some((_, header))) => header.number,
+				Ok(None) => {
+					error!("Backfilling from {} failed, finalized head not found", self.url);
+					return FollowOutcome::Broken
+				},
+				Err(e) => {
+					error!("Backfilling from {} failed: {:?}", self.url, e);
+					return FollowOutcome::Broken
+				},
+			};
+			if next > head {
+				info!("[{}] Backfilled blocks #{}..#{}", self.url, from, head);
+				return FollowOutcome::CaughtUp
+			}
+
+			info!("[{}] Backfilling blocks #{}..#{}", self.url, next, head);
+			let executor = self.executor.clone();
+			let url = self.url.clone();
+			let mut blocks = stream::iter(next..=head)
+				.map(move |block_number| {
+					let mut executor = executor.clone();
+					let url = url.clone();
+					async move { fetch_block(&mut executor, &url, block_number).await }
+				})
+				.buffered(MAX_BACKFILL_REQUESTS);
+
+			while let Some(block) = blocks.next().await {
+				let (hash, header) = match block {
+					Ok(Some(v)) => v,
+					Ok(None) => {
+						error!("Backfilling from {} failed, block #{} not found", self.url, next);
+						return FollowOutcome::Broken
+					},
+					Err(e) => {
+						error!("Backfilling from {} failed: {:?}", self.url, e);
+						return FollowOutcome::Broken
+					},
+				};
+				// Backfilled blocks are already finalized, so they are sent as best heads as well
+				if !self.finalized_only && !self.send(ChainSubscriptionEvent::NewBestHead((hash, header.clone()))).await
+				{
+					return FollowOutcome::Terminated
+				}
+				if !self.send(ChainSubscriptionEvent::NewFinalizedBlock((hash, header))).await {
+					return FollowOutcome::Terminated
+				}
+				if let Ok(()) | Err(TryRecvError::Closed) = self.shutdown_rx.try_recv() {
+					info!("Received interrupt signal shutting down backfilling");
+					return FollowOutcome::Terminated
+				}
+				next += 1;
+			}
+		}
+	}
 
-		let mut shutdown_rx = shutdown_tx.subscribe();
-		let mut executor = RequestExecutor::new(retry);
-		let best_sub = match executor.get_best_block_subscription(&url).await {
-			Ok(v) => v.map_ok(|v| ChainSubscriptionEvent::NewBestHead((v.1.hash(), v.0))),
+	/// Backfills the blocks missed during an outage before resuming, so the consumers don't skip them
+	async fn repair_gap(&mut self) -> FollowOutcome {
+		let head = match self.finalized_head().await {
+			Ok(Some((_, header))) => header.number,
+			Ok(None) => {
+				error!("Checking {} for missed blocks failed, finalized head not found", self.url);
+				return FollowOutcome::Broken
+			},
 			Err(e) => {
-				error!("Subscription to {} failed: {:?}", url, e);
-				std::process::exit(1)
+				error!("Checking {} for missed blocks failed: {:?}", self.url, e);
+				return FollowOutcome::Broken
 			},
 		};
-		let finalized_sub = match executor.get_finalized_block_subscription(&url).await {
+		let Some(from) = self.state.first_missing_block(head, self.finalized_only) else {
+			return FollowOutcome::CaughtUp
+		};
+
+		warn!("[{}] Backfilling blocks #{}..#{} missed during the outage", self.url, from, head);
+		self.incidents.record(Incident::now(
+			Severity::Warning,
+			IncidentSource::RpcDisconnect,
+			format!("Backfilling blocks #{}..#{} missed by {}", from, head, self.url),
+		));
+		self.backfill(from).await
+	}
+
+	async fn finalized_head(&mut self) -> Result<Option<(H256, Header)>, SubxtWrapperError> {
+		let hash = match self.executor.get_finalized_block_hash(&self.url).await? {
+			Some(v) => v,
+			None => return Ok(None),
+		};
+
+		Ok(self
+			.executor
+			.get_block_head(&self.url, Some(hash))
+			.await?
+			.map(|header| (hash, header)))
+	}
+
+	async fn follow_subscription(&mut self) -> FollowOutcome {
+		use futures::stream::{select, StreamExt};
+		use futures_util::TryStreamExt;
+
+		let finalized_sub = match self.executor.get_finalized_block_subscription(&self.url).await {
 			Ok(v) => v.map_ok(|v| ChainSubscriptionEvent::NewFinalizedBlock((v.1.hash(), v.0))),
 			Err(e) => {
-				error!("Subscription to {} failed: {:?}", url, e);
-				std::process::exit(1)
+				error!("Subscription to {} failed: {:?}", self.url, e);
+				return FollowOutcome::Broken
 			},
 		};
-		let mut sub = select(best_sub, finalized_sub);
-
-		const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(200);
-		let mut heartbeat_periodic = interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+		let mut sub = if self.finalized_only {
+			finalized_sub.boxed()
+		} else {
+			let best_sub = match self.executor.get_best_block_subscription(&self.url).await {
+				Ok(v) => v.map_ok(|v| ChainSubscriptionEvent::NewBestHead((v.1.hash(), v.0))),
+				Err(e) => {
+					error!("Subscription to {} failed: {:?}", self.url, e);
+					return FollowOutcome::Broken
+				},
+			};
+			select(best_sub, finalized_sub).boxed()
+		};
+		let silence = sleep(SUBSCRIPTION_SILENCE_TIMEOUT);
+		tokio::pin!(silence);
 
 		loop {
 			tokio::select! {
@@ -109,49 +470,259 @@ impl ChainHeadSubscription {
 					let event = match message {
 						Some(Ok(v)) => v,
 						Some(Err(e)) => {
-							error!("Subscription to {} failed: {:?}", url, e);
-							std::process::exit(1)
+							error!("Subscription to {} failed: {:?}", self.url, e);
+							return FollowOutcome::Broken
 						},
 						None => {
-							error!("Subscription to {} failed, received None instead of an event", url);
-							std::process::exit(1);
+							error!("Subscription to {} failed, received None instead of an event", self.url);
+							return FollowOutcome::Broken
 						}
 					};
 
-					if let Err(e) = update_channel.send(event).await {
-						info!("Event consumer has terminated: {:?}, shutting down", e);
-						return;
+					silence.as_mut().reset(Instant::now() + SUBSCRIPTION_SILENCE_TIMEOUT);
+					if !self.send(event).await {
+						return FollowOutcome::Terminated
 					}
+					if self.chaos.inject(Fault::DroppedSubscription) {
+						error!("Subscription to {} dropped by the chaos mode", self.url);
+						return FollowOutcome::Broken
+					}
+				},
+				_ = &mut silence => {
+					error!("Subscription to {} is silent for {:?}", self.url, SUBSCRIPTION_SILENCE_TIMEOUT);
+					return FollowOutcome::Broken
 				},
-				_ = shutdown_rx.recv() => {
+				_ = self.shutdown_rx.recv() => {
 					info!("Received interrupt signal shutting down subscription");
-					return;
+					return FollowOutcome::Terminated
 				}
-				_ = heartbeat_periodic.tick() => {
+				_ = self.heartbeat.tick() => {
 					debug!("sent heartbeat to subscribers");
-					let res = update_channel.send(ChainSubscriptionEvent::Heartbeat).await;
-					if let Err(e) = res {
-						info!("Event consumer has terminated: {:?}, shutting down", e);
-						return;
+					if !self.send(ChainSubscriptionEvent::Heartbeat).await {
+						return FollowOutcome::Terminated
 					}
 				}
 			}
 		}
 	}
 
-	// Sets up per websocket tasks to handle updates and reconnects on errors.
-	fn run_per_consumer(
-		update_channels: Vec<Sender<ChainSubscriptionEvent>>,
-		urls: Vec<String>,
-		shutdown_tx: BroadcastSender<()>,
-		retry: RetryOptions,
-	) -> Vec<tokio::task::JoinHandle<()>> {
-		update_channels
-			.into_iter()
-			.zip(urls.into_iter())
-			.map(|(update_channel, url)| {
-				tokio::spawn(Self::run_per_node(update_channel, url, shutdown_tx.clone(), retry.clone()))
-			})
-			.collect()
+	// Polls at the slot cadence, and more often if a block is overdue, until it's time to try subscriptions again.
+	// A node that has never been reached is reported to the consumer instead of being polled forever.
+	async fn follow_polling(&mut self) -> FollowOutcome {
+		let slot = Duration::from_secs_f64(STANDARD_BLOCK_TIME);
+		let next_poll = sleep(Duration::ZERO);
+		tokio::pin!(next_poll);
+		let retry_subscription = sleep(SUBSCRIPTION_RETRY_INTERVAL);
+		tokio::pin!(retry_subscription);
+		// Only the first failure and the recovery are recorded as incidents
+		let mut is_failing = false;
+		// Blocks may have been missed while the subscription was broken or the polls were failing
+		let mut needs_repair = true;
+
+		loop {
+			tokio::select! {
+				_ = &mut next_poll => {
+					if needs_repair {
+						match self.repair_gap().await {
+							FollowOutcome::Terminated => return FollowOutcome::Terminated,
+							FollowOutcome::Broken => {
+								next_poll.as_mut().reset(Instant::now() + slot);
+								continue
+							},
+							FollowOutcome::CaughtUp | FollowOutcome::RetrySubscription => needs_repair = false,
+						}
+					}
+					let delay = match self.poll().await {
+						Ok(events) => {
+							if std::mem::take(&mut is_failing) {
+								self.incidents.record(Incident::now(
+									Severity::Info,
+									IncidentSource::RpcDisconnect,
+									format!("Polling {} recovered", self.url),
+								));
+							}
+							let has_new_head = events.iter().any(|v| match v {
+								ChainSubscriptionEvent::NewBestHead(_) => !self.finalized_only,
+								ChainSubscriptionEvent::NewFinalizedBlock(_) => self.finalized_only,
+								ChainSubscriptionEvent::Heartbeat | ChainSubscriptionEvent::Unreachable(_) => false,
+							});
+							for event in events {
+								if !self.send(event).await {
+									return FollowOutcome::Terminated
+								}
+							}
+							if has_new_head { slot } else { POLLING_RETRY_INTERVAL }
+						},
+						Err(e) if self.state.best.is_none() && self.state.finalized.is_none() => {
+							error!("Polling {} failed: {:?}", self.url, e);
+							let reason = format!("Cannot reach {}: {:?}", self.url, e);
+							let _ = self.update_channel.send(ChainSubscriptionEvent::Unreachable(reason)).await;
+							return FollowOutcome::Terminated
+						},
+						Err(e) => {
+							error!("Polling {} failed: {:?}", self.url, e);
+							needs_repair = true;
+							if !std::mem::replace(&mut is_failing, true) {
+								self.incidents.record(Incident::now(
+									Severity::Critical,
+									IncidentSource::RpcDisconnect,
+									format!("Polling {} failed: {:?}", self.url, e),
+								));
+							}
+							slot
+						},
+					};
+					next_poll.as_mut().reset(Instant::now() + delay);
+				},
+				_ = &mut retry_subscription => return FollowOutcome::RetrySubscription,
+				_ = self.shutdown_rx.recv() => {
+					info!("Received interrupt signal shutting down polling");
+					return FollowOutcome::Terminated
+				}
+				_ = self.heartbeat.tick() => {
+					debug!("sent heartbeat to subscribers");
+					if !self.send(ChainSubscriptionEvent::Heartbeat).await {
+						return FollowOutcome::Terminated
+					}
+				}
+			}
+		}
+	}
+
+	/// Returns events for the blocks produced since the last poll
+	async fn poll(&mut self) -> Result<Vec<ChainSubscriptionEvent>, SubxtWrapperError> {
+		let mut events = vec![];
+
+		if !self.finalized_only {
+			if let Some(best) = self.executor.get_block_head(&self.url, None).await? {
+				let best_hash = best.hash();
+				if self.state.is_new_best(best_hash) {
+					let headers = self
+						.with_missing_parents((best_hash, best), |state, header| state.is_missing_parent(header))
+						.await?;
+					events.extend(headers.into_iter().map(ChainSubscriptionEvent::NewBestHead));
+				}
+			}
+		}
+
+		if let Some(finalized_hash) = self.executor.get_finalized_block_hash(&self.url).await? {
+			if self.state.is_new_finalized(finalized_hash) {
+				if let Some(header) = self.executor.get_block_head(&self.url, Some(finalized_hash)).await? {
+					if self.finalized_only {
+						// Finalized blocks are the only heads, so none of them can be skipped
+						let headers = self
+							.with_missing_parents((finalized_hash, header), |state, header| {
+								state.is_missing_finalized_parent(header)
+							})
+							.await?;
+						events.extend(headers.into_iter().map(ChainSubscriptionEvent::NewFinalizedBlock));
+					} else {
+						events.push(ChainSubscriptionEvent::NewFinalizedBlock((finalized_hash, header)));
+					}
+				}
+			}
+		}
+
+		Ok(events)
+	}
+
+	/// Returns the block preceded by its parents that were not sent yet, oldest first
+	async fn with_missing_parents(
+		&mut self,
+		head: (H256, Header),
+		is_missing_parent: impl Fn(&FollowState, &Header) -> bool,
+	) -> Result<Vec<(H256, Header)>, SubxtWrapperError> {
+		let mut headers = vec![head];
+		while headers.len() < MAX_POLLING_BACKFILL {
+			let (_, oldest) = headers.last().expect("headers are not empty; qed");
+			if !is_missing_parent(&self.state, oldest) {
+				break
+			}
+			let parent_hash = oldest.parent_hash;
+			match self.executor.get_block_head(&self.url, Some(parent_hash)).await? {
+				Some(parent) => headers.push((parent_hash, parent)),
+				None => break,
+			}
+		}
+		headers.reverse();
+
+		Ok(headers)
+	}
+}
+
+async fn fetch_block(
+	executor: &mut RequestExecutor,
+	url: &str,
+	block_number: BlockNumber,
+) -> Result<Option<(H256, Header)>, SubxtWrapperError> {
+	let hash = match executor.get_block_hash(url, Some(block_number)).await? {
+		Some(v) => v,
+		None => return Ok(None),
+	};
+
+	Ok(executor.get_block_head(url, Some(hash)).await?.map(|header| (hash, header)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use subxt::config::substrate::Digest;
+
+	fn create_header(number: BlockNumber, parent_hash: H256) -> Header {
+		Header {
+			parent_hash,
+			number,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Digest::default(),
+		}
+	}
+
+	#[test]
+	fn test_follow_state_tracks_sent_blocks() {
+		let mut state = FollowState::default();
+		let hash = H256::random();
+		assert!(state.is_new_best(hash));
+
+		state.on_event(&ChainSubscriptionEvent::NewBestHead((hash, create_header(10, H256::random()))));
+		assert!(!state.is_new_best(hash));
+		assert!(state.is_new_best(H256::random()));
+
+		assert!(state.is_new_finalized(hash));
+		state.on_event(&ChainSubscriptionEvent::NewFinalizedBlock((hash, create_header(10, H256::random()))));
+		assert_eq!(state.finalized, Some((hash, 10)));
+		assert!(!state.is_new_finalized(hash));
+	}
+
+	#[test]
+	fn test_follow_state_detects_missing_parents() {
+		let mut state = FollowState::default();
+		// Nothing to backfill before the first block
+		assert!(!state.is_missing_parent(&create_header(12, H256::random())));
+
+		let hash = H256::random();
+		state.on_event(&ChainSubscriptionEvent::NewBestHead((hash, create_header(10, H256::random()))));
+		assert!(!state.is_missing_parent(&create_header(11, hash)));
+		assert!(state.is_missing_parent(&create_header(12, H256::random())));
+		// Reorgs to the same height are not backfilled
+		assert!(!state.is_missing_parent(&create_header(10, H256::random())));
+
+		state.on_event(&ChainSubscriptionEvent::NewFinalizedBlock((hash, create_header(10, H256::random()))));
+		assert!(!state.is_missing_finalized_parent(&create_header(11, hash)));
+		assert!(state.is_missing_finalized_parent(&create_header(14, H256::random())));
+	}
+
+	#[test]
+	fn test_follow_state_detects_outage_gaps() {
+		let mut state = FollowState::default();
+		// Nothing was missed before the first block
+		assert_eq!(state.first_missing_block(1000, false), None);
+
+		state.on_event(&ChainSubscriptionEvent::NewBestHead((H256::random(), create_header(100, H256::random()))));
+		state.on_event(&ChainSubscriptionEvent::NewFinalizedBlock((H256::random(), create_header(98, H256::random()))));
+		// Small gaps are filled by polling
+		assert_eq!(state.first_missing_block(100 + MAX_POLLING_BACKFILL as BlockNumber, false), None);
+		assert_eq!(state.first_missing_block(200, false), Some(101));
+		assert_eq!(state.first_missing_block(200, true), Some(99));
 	}
 }
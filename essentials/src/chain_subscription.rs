@@ -25,4 +25,6 @@ pub enum ChainSubscriptionEvent {
 	NewFinalizedBlock((H256, Header)),
 	/// Heartbeat event
 	Heartbeat,
+	/// The node has never been reachable, no events follow
+	Unreachable(String),
 }
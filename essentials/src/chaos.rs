@@ -0,0 +1,310 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Fault injection to check the behavior of the collector and trackers under stress.
+//!
+//! The chaos mode is meant for developers and CI: RPC calls fail with a simulated IO error and are retried as real
+//! ones, chain events are delayed, subscriptions are dropped, so the polling fallback takes over, and best heads
+//! are sent after the following ones as if a fork was reported late. On the channels from the collector to the
+//! trackers, trackers are slowed down, so the channels fill up and the collector is held back, and their channels
+//! are dropped as if the trackers had died. Every fault has its own rate and nothing is injected unless a rate is set.
+//! The injector is given to the executors and the subscriptions in their `ExecutorContext`, so everything built with
+//! the context of a run injects the faults.
+//!
+//! The chaos mode is built with the `chaos` feature only. Without it the options don't exist and the checks are
+//! no-ops, with it the injector is locked only when a fault rate is set.
+
+use serde::Serialize;
+use std::{
+	fmt::{self, Display, Formatter},
+	time::Duration,
+};
+#[cfg(feature = "chaos")]
+use {
+	clap::Parser,
+	log::warn,
+	rand::{rngs::StdRng, Rng, SeedableRng},
+	std::sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "chaos")]
+#[derive(Clone, Debug, Default, Parser)]
+pub struct ChaosOptions {
+	/// Chaos testing: share (0..1) of the RPC calls failing with a simulated IO error
+	#[clap(long, default_value = "0", value_parser = parse_rate)]
+	pub chaos_rpc_errors: f64,
+	/// Chaos testing: share (0..1) of the chain events delayed by up to `--chaos-max-delay-ms`
+	#[clap(long, default_value = "0", value_parser = parse_rate)]
+	pub chaos_delayed_events: f64,
+	/// Chaos testing: maximum delay of a chain event or a tracker, in milliseconds
+	#[clap(long, default_value = "2000")]
+	pub chaos_max_delay_ms: u64,
+	/// Chaos testing: share (0..1) of the chain events after which the subscription is dropped as broken
+	#[clap(long, default_value = "0", value_parser = parse_rate)]
+	pub chaos_dropped_subscriptions: f64,
+	/// Chaos testing: share (0..1) of the best heads sent after the following one
+	#[clap(long, default_value = "0", value_parser = parse_rate)]
+	pub chaos_reordered_heads: f64,
+	/// Chaos testing: share (0..1) of the collector updates after which a tracker stalls for up to
+	/// `--chaos-max-delay-ms`
+	#[clap(long, default_value = "0", value_parser = parse_rate)]
+	pub chaos_slow_trackers: f64,
+	/// Chaos testing: share (0..1) of the collector updates after which the channel to the tracker is dropped
+	#[clap(long, default_value = "0", value_parser = parse_rate)]
+	pub chaos_dropped_channels: f64,
+	/// Chaos testing: seed of the injected faults to reproduce a run, random by default
+	#[clap(long)]
+	pub chaos_seed: Option<u64>,
+}
+
+#[cfg(feature = "chaos")]
+impl ChaosOptions {
+	fn rate(&self, fault: Fault) -> f64 {
+		match fault {
+			Fault::RpcError => self.chaos_rpc_errors,
+			Fault::DelayedEvent => self.chaos_delayed_events,
+			Fault::DroppedSubscription => self.chaos_dropped_subscriptions,
+			Fault::ReorderedHead => self.chaos_reordered_heads,
+			Fault::SlowTracker => self.chaos_slow_trackers,
+			Fault::DroppedChannel => self.chaos_dropped_channels,
+		}
+	}
+
+	/// Whether any fault is injected
+	pub fn is_enabled(&self) -> bool {
+		Fault::ALL.iter().any(|fault| self.rate(*fault) > 0.0)
+	}
+}
+
+#[cfg(feature = "chaos")]
+fn parse_rate(s: &str) -> Result<f64, String> {
+	let rate: f64 = s.parse().map_err(|e| format!("{}", e))?;
+	if !(0.0..=1.0).contains(&rate) {
+		return Err(format!("{} is not in 0..1", rate))
+	}
+	Ok(rate)
+}
+
+/// A simulated fault
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+	/// An RPC call fails with an IO error
+	RpcError,
+	/// A chain event is delayed
+	DelayedEvent,
+	/// The subscription is dropped after a chain event
+	DroppedSubscription,
+	/// A best head is sent after the following one
+	ReorderedHead,
+	/// A tracker stalls after a collector update
+	SlowTracker,
+	/// The channel to a tracker is dropped after a collector update
+	DroppedChannel,
+}
+
+#[cfg(feature = "chaos")]
+impl Fault {
+	const ALL: [Fault; 6] = [
+		Fault::RpcError,
+		Fault::DelayedEvent,
+		Fault::DroppedSubscription,
+		Fault::ReorderedHead,
+		Fault::SlowTracker,
+		Fault::DroppedChannel,
+	];
+}
+
+#[cfg(feature = "chaos")]
+#[derive(Debug)]
+struct Chaos {
+	opts: ChaosOptions,
+	rng: StdRng,
+	report: ChaosReport,
+}
+
+#[cfg(feature = "chaos")]
+impl Chaos {
+	fn new(opts: ChaosOptions) -> Self {
+		let rng = match opts.chaos_seed {
+			Some(seed) => StdRng::seed_from_u64(seed),
+			None => StdRng::from_entropy(),
+		};
+		Self { opts, rng, report: Default::default() }
+	}
+
+	fn inject(&mut self, fault: Fault) -> bool {
+		let rate = self.opts.rate(fault);
+		if rate == 0.0 || !self.rng.gen_bool(rate) {
+			return false
+		}
+		match fault {
+			Fault::RpcError => self.report.rpc_errors += 1,
+			Fault::DelayedEvent => self.report.delayed_events += 1,
+			Fault::DroppedSubscription => self.report.dropped_subscriptions += 1,
+			Fault::ReorderedHead => self.report.reordered_heads += 1,
+			Fault::SlowTracker => self.report.slow_trackers += 1,
+			Fault::DroppedChannel => self.report.dropped_channels += 1,
+		}
+		true
+	}
+
+	fn delay(&mut self, fault: Fault) -> Option<Duration> {
+		if !self.inject(fault) {
+			return None
+		}
+		Some(Duration::from_millis(self.rng.gen_range(0..=self.opts.chaos_max_delay_ms)))
+	}
+}
+
+/// Faults injected since the start of the run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ChaosReport {
+	pub rpc_errors: u64,
+	pub delayed_events: u64,
+	pub dropped_subscriptions: u64,
+	pub reordered_heads: u64,
+	pub slow_trackers: u64,
+	pub dropped_channels: u64,
+}
+
+/// Injects the faults of the chaos mode, the clones share the injector. The default one injects nothing.
+#[cfg(feature = "chaos")]
+#[derive(Clone, Debug, Default)]
+pub struct ChaosInjector(Option<Arc<Mutex<Chaos>>>);
+
+/// Injects the faults of the chaos mode, nothing without the `chaos` feature
+#[cfg(not(feature = "chaos"))]
+#[derive(Clone, Debug, Default)]
+pub struct ChaosInjector;
+
+#[cfg(feature = "chaos")]
+impl ChaosInjector {
+	/// Returns an injector of the faults, it injects nothing if no rate is set
+	pub fn new(opts: &ChaosOptions) -> Self {
+		if !opts.is_enabled() {
+			return Self::default()
+		}
+		warn!(
+			"Chaos mode: injecting RPC errors at {}, delayed events at {}, dropped subscriptions at {}, \
+			 reordered heads at {}, slow trackers at {}, dropped channels at {}",
+			opts.chaos_rpc_errors,
+			opts.chaos_delayed_events,
+			opts.chaos_dropped_subscriptions,
+			opts.chaos_reordered_heads,
+			opts.chaos_slow_trackers,
+			opts.chaos_dropped_channels
+		);
+		Self(Some(Arc::new(Mutex::new(Chaos::new(opts.clone())))))
+	}
+
+	/// Runs a closure on the injector if faults are injected
+	fn with_chaos<T>(&self, f: impl FnOnce(&mut Chaos) -> Option<T>) -> Option<T> {
+		let chaos = self.0.as_ref()?;
+		f(&mut chaos.lock().expect("chaos lock is poisoned"))
+	}
+
+	/// Whether the fault should be injected now, always `false` out of the chaos mode
+	pub fn inject(&self, fault: Fault) -> bool {
+		self.with_chaos(|chaos| chaos.inject(fault).then_some(())).is_some()
+	}
+
+	/// Returns how long a chain event or a tracker should be delayed if the fault should be injected now
+	pub fn delay(&self, fault: Fault) -> Option<Duration> {
+		self.with_chaos(|chaos| chaos.delay(fault))
+	}
+
+	/// Returns the faults injected so far, `None` out of the chaos mode
+	pub fn snapshot(&self) -> Option<ChaosReport> {
+		self.with_chaos(|chaos| Some(chaos.report))
+	}
+}
+
+#[cfg(not(feature = "chaos"))]
+impl ChaosInjector {
+	/// Whether the fault should be injected now, always `false` out of the chaos mode
+	#[inline(always)]
+	pub fn inject(&self, _fault: Fault) -> bool {
+		false
+	}
+
+	/// Returns how long a chain event or a tracker should be delayed if the fault should be injected now
+	#[inline(always)]
+	pub fn delay(&self, _fault: Fault) -> Option<Duration> {
+		None
+	}
+
+	/// Returns the faults injected so far, `None` out of the chaos mode
+	#[inline(always)]
+	pub fn snapshot(&self) -> Option<ChaosReport> {
+		None
+	}
+}
+
+impl Display for ChaosReport {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "--- Injected faults ---")?;
+		writeln!(
+			f,
+			"RPC errors: {}, delayed events: {}, dropped subscriptions: {}, reordered heads: {}, slow trackers: {}, \
+			 dropped channels: {}",
+			self.rpc_errors,
+			self.delayed_events,
+			self.dropped_subscriptions,
+			self.reordered_heads,
+			self.slow_trackers,
+			self.dropped_channels
+		)
+	}
+}
+
+#[cfg(all(test, feature = "chaos"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_injects_faults_at_rates() {
+		let opts = ChaosOptions {
+			chaos_rpc_errors: 1.0,
+			chaos_delayed_events: 0.5,
+			chaos_max_delay_ms: 100,
+			chaos_seed: Some(42),
+			..Default::default()
+		};
+		assert!(opts.is_enabled());
+		assert!(!ChaosOptions::default().is_enabled());
+		assert!(parse_rate("1.5").is_err());
+
+		let mut chaos = Chaos::new(opts.clone());
+		let delays: Vec<_> = (0..100).map(|_| chaos.delay(Fault::DelayedEvent)).collect();
+		assert!(delays.iter().flatten().all(|delay| *delay <= Duration::from_millis(100)));
+		assert!((1..100).contains(&chaos.report.delayed_events));
+		assert!(chaos.inject(Fault::RpcError));
+		assert!(!chaos.inject(Fault::DroppedSubscription));
+		assert!(!chaos.inject(Fault::DroppedChannel));
+		assert_eq!(chaos.report.rpc_errors, 1);
+		assert_eq!(chaos.report.dropped_subscriptions, 0);
+
+		// The same seed injects the same faults
+		let mut replayed = Chaos::new(opts.clone());
+		assert_eq!((0..100).map(|_| replayed.delay(Fault::DelayedEvent)).collect::<Vec<_>>(), delays);
+
+		// Nothing is injected by the default injector, the clones share the injected faults
+		assert!(!ChaosInjector::default().inject(Fault::RpcError));
+		assert_eq!(ChaosInjector::default().snapshot(), None);
+		let injector = ChaosInjector::new(&opts);
+		assert!(injector.clone().inject(Fault::RpcError));
+		assert_eq!(injector.snapshot().map(|report| report.rpc_errors), Some(1));
+	}
+}
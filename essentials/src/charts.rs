@@ -0,0 +1,146 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Time series charts drawn in a terminal: a multi-line ASCII plot with a caption and a one line braille sparkline.
+//!
+//! A [`TimeSeries`] keeps the last values of a metric, e.g. block times, dropping the oldest ones once it is full,
+//! so the charts always show the most recent window of the metric.
+
+use std::collections::VecDeque;
+
+/// First braille pattern, without dots
+const BRAILLE_BLANK: u32 = 0x2800;
+/// Braille dots of the left column of a cell, from the bottom row up
+const BRAILLE_LEFT_DOTS: [u32; 4] = [0x40, 0x04, 0x02, 0x01];
+/// Braille dots of the right column of a cell, from the bottom row up
+const BRAILLE_RIGHT_DOTS: [u32; 4] = [0x80, 0x20, 0x10, 0x08];
+
+/// A ring buffer of the last values of a metric
+#[derive(Clone, Debug)]
+pub struct TimeSeries {
+	values: VecDeque<f64>,
+	capacity: usize,
+}
+
+impl TimeSeries {
+	/// Creates an empty series keeping at most `capacity` values
+	pub fn new(capacity: usize) -> Self {
+		let capacity = capacity.max(1);
+		Self { values: VecDeque::with_capacity(capacity), capacity }
+	}
+
+	/// Appends a value, dropping the oldest one if the series is full
+	pub fn push(&mut self, value: f64) {
+		if self.values.len() == self.capacity {
+			self.values.pop_front();
+		}
+		self.values.push_back(value);
+	}
+
+	/// Values of the series, oldest first
+	pub fn values(&self) -> impl DoubleEndedIterator<Item = f64> + ExactSizeIterator + '_ {
+		self.values.iter().copied()
+	}
+
+	pub fn len(&self) -> usize {
+		self.values.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.values.is_empty()
+	}
+
+	pub fn last(&self) -> Option<f64> {
+		self.values.back().copied()
+	}
+
+	pub fn avg(&self) -> Option<f64> {
+		(!self.is_empty()).then(|| self.values().sum::<f64>() / self.len() as f64)
+	}
+
+	pub fn min(&self) -> Option<f64> {
+		self.values().reduce(f64::min)
+	}
+
+	pub fn max(&self) -> Option<f64> {
+		self.values().reduce(f64::max)
+	}
+}
+
+/// Draws the series as an ASCII chart of `height` rows with the caption below it
+pub fn plot(series: &TimeSeries, height: usize, caption: String) -> String {
+	use rasciigraph::Config;
+
+	rasciigraph::plot(series.values().collect(), Config::default().with_height(height as u32).with_caption(caption))
+}
+
+/// Draws the last values of the series as a braille sparkline of at most `width` characters,
+/// every character shows two values scaled between the minimum and the maximum of the shown ones
+pub fn sparkline(series: &TimeSeries, width: usize) -> String {
+	let shown: Vec<f64> = series.values().rev().take(width * 2).rev().collect();
+	let (min, max) = shown
+		.iter()
+		.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)));
+	// Every value is drawn with one to four dots, so the smallest values are still visible
+	let dots = |value: f64| {
+		if max > min {
+			(((value - min) / (max - min) * 3.0).round() as usize).min(3) + 1
+		} else {
+			2
+		}
+	};
+
+	shown
+		.chunks(2)
+		.map(|pair| {
+			let mut cell = BRAILLE_LEFT_DOTS[..dots(pair[0])].iter().sum::<u32>();
+			if let Some(&right) = pair.get(1) {
+				cell += BRAILLE_RIGHT_DOTS[..dots(right)].iter().sum::<u32>();
+			}
+			char::from_u32(BRAILLE_BLANK + cell).expect("braille patterns are valid chars")
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_keeps_last_values_and_draws_sparkline() {
+		let mut series = TimeSeries::new(4);
+		assert_eq!(sparkline(&series, 8), "");
+		assert_eq!(series.avg(), None);
+
+		for value in [1.0, 6.0, 6.0, 12.0, 6.0] {
+			series.push(value);
+		}
+		assert_eq!(series.values().collect::<Vec<_>>(), vec![6.0, 6.0, 12.0, 6.0]);
+		assert_eq!(series.last(), Some(6.0));
+		assert_eq!(series.avg(), Some(7.5));
+		assert_eq!(series.min(), Some(6.0));
+		assert_eq!(series.max(), Some(12.0));
+
+		// The lowest values have one dot, the highest have four
+		assert_eq!(sparkline(&series, 2), "\u{28c0}\u{28c7}");
+		// Only the last two values fit
+		assert_eq!(sparkline(&series, 1), "\u{28c7}");
+		// A flat series is drawn at the same height
+		assert_eq!(sparkline(&TimeSeries::new(1), 1), "");
+		let mut flat = TimeSeries::new(2);
+		flat.push(6.0);
+		assert_eq!(sparkline(&flat, 1), "\u{2844}");
+	}
+}
@@ -0,0 +1,43 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Data of a relay chain block fetched and decoded once by the collector and shared by all trackers.
+
+use crate::{
+	api::subxt_wrapper::InherentData,
+	metadata::polkadot_primitives::ValidatorIndex,
+	types::{BlockNumber, CoreOccupied, Header, Timestamp, H256},
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// A relay chain block as seen by the collector, sent to the subscribers behind an `Arc` along with the new head
+/// events, so the trackers don't read and decode the same data from the storage every one for itself
+#[derive(Clone, Debug)]
+pub struct BlockContext {
+	pub block_hash: H256,
+	pub block_number: BlockNumber,
+	pub header: Header,
+	pub timestamp: Timestamp,
+	pub session_index: u32,
+	/// Runtime spec version active at the block
+	pub spec_version: u32,
+	/// The paras inherent, `None` if the node returned no inherent data
+	pub inherent_data: Option<Arc<InherentData>>,
+	pub occupied_cores: Vec<CoreOccupied>,
+	pub backing_groups: Vec<Vec<ValidatorIndex>>,
+	/// Parachain ids scheduled per availability core
+	pub core_assignments: BTreeMap<u32, Vec<u32>>,
+}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{chain_events::SubxtDisputeResult, metadata::polkadot_primitives, types::H256};
+use crate::{chain_events::SubxtDisputeResult, metadata::polkadot_primitives, observation::ValidityVote, types::H256};
 use parity_scale_codec::{Decode, Encode};
 use serde::{
 	ser::{SerializeStruct, Serializer},
@@ -36,6 +36,8 @@ pub struct CandidateInclusionRecord<T: Encode + Decode + Clone> {
 	pub timedout: Option<u32>,
 	/// Observed core index
 	pub core_idx: Option<u32>,
+	/// Observed backing group index
+	pub group_idx: Option<u32>,
 	/// Stated relay parent
 	pub relay_parent: T,
 	/// Stated relay parent number
@@ -106,6 +108,8 @@ pub struct CandidateRecord {
 	pub candidate_inclusion: CandidateInclusionRecord<H256>,
 	/// Dispute data
 	pub candidate_disputed: Option<CandidateDisputed>,
+	/// Members of the backing group and their validity votes, empty if the backing group is unknown
+	pub validity_votes: Vec<ValidityVote>,
 }
 
 impl CandidateRecord {
@@ -0,0 +1,613 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! GraphQL API over the collector's storage, served at `/v1/graphql` next to the REST endpoints.
+//!
+//! Candidates, parachains, sessions and anomalies are queried with nested resolvers, e.g.
+//! `{ para(id: 2004) { candidates(limit: 10) { hash included backingGroup { validators { account } } } } }`.
+//! Queries are limited in depth and complexity, lists of candidates are paginated and the backing groups of the
+//! candidates in a response are read from the storage once per relay parent.
+
+use crate::{
+	chain_events::SubxtDisputeResult,
+	collector::{candidate_record::CandidateRecord, CollectorPrefixType, CollectorStorageApi},
+	incidents::{IncidentSource, Incidents, Severity},
+	metadata::polkadot_primitives::ValidatorIndex,
+	types::{AccountId32, BlockNumber, Timestamp, H256},
+};
+use async_graphql::{
+	dataloader::{DataLoader, Loader},
+	Context, EmptyMutation, EmptySubscription, Enum, Object, Result, Schema, SimpleObject,
+};
+use std::{
+	collections::{BTreeSet, HashMap},
+	str::FromStr,
+	sync::Arc,
+};
+use subxt::config::{substrate::BlakeTwo256, Hasher};
+
+/// Maximum nesting of the fields of a query
+const MAX_QUERY_DEPTH: usize = 8;
+/// Maximum number of fields resolved by a query, a list counts as a single element
+const MAX_QUERY_COMPLEXITY: usize = 256;
+/// Number of candidates returned when no limit is given
+const DEFAULT_CANDIDATES_LIMIT: usize = 100;
+/// Maximum number of candidates returned at once
+const MAX_CANDIDATES_LIMIT: usize = 1000;
+
+pub(crate) type CollectorSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Builds the schema resolving queries from the collector's storage
+pub(crate) fn schema(api: CollectorStorageApi, incidents: Incidents) -> CollectorSchema {
+	let backing_groups = DataLoader::new(BackingGroupsLoader { api: api.clone() }, tokio::spawn);
+	Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+		.data(api)
+		.data(incidents)
+		.data(backing_groups)
+		.limit_depth(MAX_QUERY_DEPTH)
+		.limit_complexity(MAX_QUERY_COMPLEXITY)
+		.finish()
+}
+
+fn api<'a>(ctx: &Context<'a>) -> Result<&'a CollectorStorageApi> {
+	ctx.data::<CollectorStorageApi>()
+}
+
+/// Reads a page of the candidates of the parachains, in the order of the parachains and the candidate hashes.
+/// Only the records of the candidates up to the end of the page are read.
+async fn read_candidates(
+	api: &CollectorStorageApi,
+	para_ids: Vec<u32>,
+	not_before: Option<Timestamp>,
+	offset: Option<usize>,
+	limit: Option<usize>,
+) -> Result<Vec<Candidate>> {
+	let limit = limit.unwrap_or(DEFAULT_CANDIDATES_LIMIT);
+	if limit > MAX_CANDIDATES_LIMIT {
+		return Err(format!("limit must not exceed {}", MAX_CANDIDATES_LIMIT).into())
+	}
+	let mut to_skip = offset.unwrap_or_default();
+	let storage = api.storage();
+	let mut candidates = vec![];
+	for para_id in para_ids {
+		let mut hashes = storage.storage_keys_prefix(CollectorPrefixType::Candidate(para_id)).await;
+		hashes.sort();
+		for hash in hashes {
+			if candidates.len() == limit {
+				return Ok(candidates)
+			}
+			let Some(candidate) = read_candidate(api, para_id, hash).await else { continue };
+			if not_before.is_some_and(|not_before| candidate.record.candidate_first_seen.as_secs() < not_before) {
+				continue
+			}
+			if to_skip > 0 {
+				to_skip -= 1;
+			} else {
+				candidates.push(candidate);
+			}
+		}
+	}
+
+	Ok(candidates)
+}
+
+async fn read_candidate(api: &CollectorStorageApi, para_id: u32, hash: H256) -> Option<Candidate> {
+	let record = api
+		.storage()
+		.storage_read_prefixed(CollectorPrefixType::Candidate(para_id), hash)
+		.await?
+		.into_inner()
+		.ok()?;
+
+	Some(Candidate { hash, record })
+}
+
+async fn para_ids(api: &CollectorStorageApi) -> Vec<u32> {
+	api.storage()
+		.storage_prefixes()
+		.await
+		.into_iter()
+		.filter_map(|prefix| match prefix {
+			CollectorPrefixType::Candidate(para_id) => Some(para_id),
+			_ => None,
+		})
+		.collect::<BTreeSet<_>>()
+		.into_iter()
+		.collect()
+}
+
+async fn session_keys(api: &CollectorStorageApi, session_index: u32) -> Option<Vec<AccountId32>> {
+	api.storage()
+		.storage_read_prefixed(CollectorPrefixType::AccountKeys, BlakeTwo256::hash(&session_index.to_be_bytes()[..]))
+		.await?
+		.into_inner()
+		.ok()
+}
+
+fn format_hash(hash: &H256) -> String {
+	format!("{:?}", hash)
+}
+
+pub(crate) struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+	/// Candidates of a parachain, or of all parachains, first seen not before the given UNIX time in seconds.
+	/// At most `limit` candidates (100 by default, up to 1000) are returned after skipping `offset` of them
+	async fn candidates(
+		&self,
+		ctx: &Context<'_>,
+		para_id: Option<u32>,
+		not_before: Option<Timestamp>,
+		offset: Option<usize>,
+		limit: Option<usize>,
+	) -> Result<Vec<Candidate>> {
+		let api = api(ctx)?;
+		let para_ids = match para_id {
+			Some(para_id) => vec![para_id],
+			None => para_ids(api).await,
+		};
+
+		read_candidates(api, para_ids, not_before, offset, limit).await
+	}
+
+	/// A candidate by its hash
+	async fn candidate(&self, ctx: &Context<'_>, hash: String) -> Result<Option<Candidate>> {
+		let api = api(ctx)?;
+		let hash = H256::from_str(hash.as_str())?;
+		let para_id: Option<u32> = match api
+			.storage()
+			.storage_read_prefixed(CollectorPrefixType::CandidatesParachains, hash)
+			.await
+		{
+			Some(entry) => Some(entry.into_inner()?),
+			None => None,
+		};
+
+		Ok(match para_id {
+			Some(para_id) => read_candidate(api, para_id, hash).await,
+			None => None,
+		})
+	}
+
+	/// Parachains with candidates in the storage
+	async fn paras(&self, ctx: &Context<'_>) -> Result<Vec<Para>> {
+		Ok(para_ids(api(ctx)?).await.into_iter().map(|id| Para { id }).collect())
+	}
+
+	/// A parachain by its id
+	async fn para(&self, id: u32) -> Para {
+		Para { id }
+	}
+
+	/// Sessions with validators in the storage, the current and the previous one
+	async fn sessions(&self, ctx: &Context<'_>) -> Result<Vec<Session>> {
+		let storage = api(ctx)?.storage();
+		let mut indices = BTreeSet::new();
+		for session_hash in storage.storage_keys_prefix(CollectorPrefixType::Session).await {
+			if let Some(entry) = storage.storage_read_prefixed(CollectorPrefixType::Session, session_hash).await {
+				indices.insert(entry.into_inner::<u32>()?);
+			}
+		}
+
+		Ok(indices.into_iter().map(|index| Session { index }).collect())
+	}
+
+	/// A session by its index, validators are kept for the current and the previous session only
+	async fn session(&self, index: u32) -> Session {
+		Session { index }
+	}
+
+	/// Anomalies recorded by the detectors, if recording of incidents is enabled
+	async fn anomalies(&self, ctx: &Context<'_>, min_severity: Option<AnomalySeverity>) -> Result<Vec<Anomaly>> {
+		let min_severity: Severity = min_severity.unwrap_or(AnomalySeverity::Info).into();
+		Ok(ctx
+			.data::<Incidents>()?
+			.timeline()
+			.filter(min_severity)
+			.incidents
+			.into_iter()
+			.filter(|incident| incident.source == IncidentSource::Anomaly)
+			.map(|incident| Anomaly {
+				timestamp: incident.timestamp,
+				severity: incident.severity.into(),
+				block_number: incident.block_number,
+				description: incident.description,
+			})
+			.collect())
+	}
+}
+
+/// A candidate seen by the collector
+pub(crate) struct Candidate {
+	hash: H256,
+	record: CandidateRecord,
+}
+
+#[Object]
+impl Candidate {
+	async fn hash(&self) -> String {
+		format_hash(&self.hash)
+	}
+
+	async fn para_id(&self) -> u32 {
+		self.record.parachain_id()
+	}
+
+	async fn relay_parent(&self) -> String {
+		format_hash(&self.record.candidate_inclusion.relay_parent)
+	}
+
+	async fn relay_parent_number(&self) -> BlockNumber {
+		self.record.candidate_inclusion.relay_parent_number
+	}
+
+	/// Relay chain block number the candidate was backed in
+	async fn backed(&self) -> BlockNumber {
+		self.record.candidate_inclusion.backed
+	}
+
+	/// Relay chain block number the candidate was included in
+	async fn included(&self) -> Option<BlockNumber> {
+		self.record.candidate_inclusion.included
+	}
+
+	/// Relay chain block number the candidate was timed out in
+	async fn timed_out(&self) -> Option<BlockNumber> {
+		self.record.candidate_inclusion.timedout
+	}
+
+	async fn core_index(&self) -> Option<u32> {
+		self.record.candidate_inclusion.core_idx
+	}
+
+	/// UNIX time in seconds the candidate was first seen at
+	async fn first_seen(&self) -> u64 {
+		self.record.candidate_first_seen.as_secs()
+	}
+
+	/// Number of relay chain blocks from the relay parent to backing
+	async fn backing_time(&self) -> Option<u32> {
+		self.record.backing_time()
+	}
+
+	/// Number of relay chain blocks from backing to inclusion
+	async fn inclusion_time(&self) -> Option<u32> {
+		self.record.inclusion_time()
+	}
+
+	async fn dispute(&self) -> Option<Dispute> {
+		self.record.candidate_disputed.as_ref().map(|disputed| Dispute {
+			initiated: disputed.disputed,
+			concluded: disputed.concluded.as_ref().map(|concluded| concluded.concluded_block),
+			outcome: disputed.concluded.as_ref().map(|concluded| concluded.outcome.into()),
+		})
+	}
+
+	/// The group of validators that backed the candidate, as of its relay parent
+	async fn backing_group(&self, ctx: &Context<'_>) -> Result<Option<BackingGroup>> {
+		let Some(index) = self.record.candidate_inclusion.group_idx else { return Ok(None) };
+		let loader = ctx.data::<DataLoader<BackingGroupsLoader>>()?;
+		let Some(relay_parent) = loader.load_one(self.record.candidate_inclusion.relay_parent).await? else {
+			return Ok(None)
+		};
+		let Some(group) = relay_parent.groups.get(index as usize) else { return Ok(None) };
+		let validators = group
+			.iter()
+			.map(|validator_index| Validator {
+				index: validator_index.0,
+				account: relay_parent
+					.session_keys
+					.as_ref()
+					.and_then(|keys| keys.get(validator_index.0 as usize))
+					.map(|account| account.to_string()),
+			})
+			.collect();
+
+		Ok(Some(BackingGroup { index, validators }))
+	}
+
+	/// Members of the backing group and their validity votes, empty if the backing group is unknown
+	async fn votes(&self) -> Vec<Vote> {
+		self.record
+			.validity_votes
+			.iter()
+			.map(|vote| Vote { index: vote.validator_index, account: vote.address.clone(), voted: vote.voted })
+			.collect()
+	}
+}
+
+/// Backing groups and validators as of a relay parent
+pub(crate) struct RelayParentGroups {
+	groups: Vec<Vec<ValidatorIndex>>,
+	/// Accounts of the validators, if the session keys are still in the storage
+	session_keys: Option<Vec<AccountId32>>,
+}
+
+/// Reads the backing groups of the relay parents of all candidates in a response at once
+pub(crate) struct BackingGroupsLoader {
+	api: CollectorStorageApi,
+}
+
+#[async_trait::async_trait]
+impl Loader<H256> for BackingGroupsLoader {
+	type Value = Arc<RelayParentGroups>;
+	type Error = async_graphql::Error;
+
+	async fn load(&self, relay_parents: &[H256]) -> Result<HashMap<H256, Self::Value>> {
+		let storage = self.api.storage();
+		let mut sessions: HashMap<u32, Option<Vec<AccountId32>>> = HashMap::new();
+		let mut loaded = HashMap::new();
+		for relay_parent in relay_parents {
+			let Some(groups) = storage
+				.storage_read_prefixed(CollectorPrefixType::BackingGroups, *relay_parent)
+				.await
+			else {
+				continue
+			};
+			let session_keys = match storage
+				.storage_read_prefixed(CollectorPrefixType::SessionIndex, *relay_parent)
+				.await
+			{
+				Some(entry) => {
+					let session_index: u32 = entry.into_inner()?;
+					if !sessions.contains_key(&session_index) {
+						sessions.insert(session_index, session_keys(&self.api, session_index).await);
+					}
+					sessions[&session_index].clone()
+				},
+				None => None,
+			};
+			loaded.insert(*relay_parent, Arc::new(RelayParentGroups { groups: groups.into_inner()?, session_keys }));
+		}
+
+		Ok(loaded)
+	}
+}
+
+/// Dispute of a candidate
+#[derive(SimpleObject)]
+pub(crate) struct Dispute {
+	/// Relay chain block number the dispute was initiated in
+	initiated: BlockNumber,
+	/// Relay chain block number the dispute was concluded in
+	concluded: Option<BlockNumber>,
+	outcome: Option<DisputeOutcome>,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DisputeOutcome {
+	Valid,
+	Invalid,
+	TimedOut,
+}
+
+impl From<SubxtDisputeResult> for DisputeOutcome {
+	fn from(v: SubxtDisputeResult) -> Self {
+		match v {
+			SubxtDisputeResult::Valid => DisputeOutcome::Valid,
+			SubxtDisputeResult::Invalid => DisputeOutcome::Invalid,
+			SubxtDisputeResult::TimedOut => DisputeOutcome::TimedOut,
+		}
+	}
+}
+
+/// A group of validators backing candidates
+#[derive(SimpleObject)]
+pub(crate) struct BackingGroup {
+	index: u32,
+	validators: Vec<Validator>,
+}
+
+#[derive(SimpleObject)]
+pub(crate) struct Validator {
+	/// Index of the validator in the session
+	index: u32,
+	/// The validator's account, if the session keys are still in the storage
+	account: Option<String>,
+}
+
+/// Validity vote of a backing group member for a candidate
+#[derive(SimpleObject)]
+pub(crate) struct Vote {
+	/// Index of the validator in the session
+	index: u32,
+	/// The validator's account, if the session keys were known when the candidate was backed
+	account: Option<String>,
+	/// If the validator has cast a validity vote
+	voted: bool,
+}
+
+/// A parachain with candidates in the storage
+pub(crate) struct Para {
+	id: u32,
+}
+
+#[Object]
+impl Para {
+	async fn id(&self) -> u32 {
+		self.id
+	}
+
+	/// Candidates of the parachain, at most `limit` (100 by default, up to 1000) after skipping `offset` of them
+	async fn candidates(
+		&self,
+		ctx: &Context<'_>,
+		not_before: Option<Timestamp>,
+		offset: Option<usize>,
+		limit: Option<usize>,
+	) -> Result<Vec<Candidate>> {
+		read_candidates(api(ctx)?, vec![self.id], not_before, offset, limit).await
+	}
+}
+
+/// A relay chain session
+pub(crate) struct Session {
+	index: u32,
+}
+
+#[Object]
+impl Session {
+	async fn index(&self) -> u32 {
+		self.index
+	}
+
+	/// Validators of the session, empty if their keys are not in the storage anymore
+	async fn validators(&self, ctx: &Context<'_>) -> Result<Vec<Validator>> {
+		Ok(session_keys(api(ctx)?, self.index)
+			.await
+			.unwrap_or_default()
+			.into_iter()
+			.enumerate()
+			.map(|(index, account)| Validator { index: index as u32, account: Some(account.to_string()) })
+			.collect())
+	}
+}
+
+/// An anomaly found by a detector
+#[derive(SimpleObject)]
+pub(crate) struct Anomaly {
+	/// Relay chain block timestamp, in milliseconds
+	timestamp: Timestamp,
+	severity: AnomalySeverity,
+	block_number: Option<BlockNumber>,
+	description: String,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AnomalySeverity {
+	Info,
+	Warning,
+	Critical,
+}
+
+impl From<AnomalySeverity> for Severity {
+	fn from(v: AnomalySeverity) -> Self {
+		match v {
+			AnomalySeverity::Info => Severity::Info,
+			AnomalySeverity::Warning => Severity::Warning,
+			AnomalySeverity::Critical => Severity::Critical,
+		}
+	}
+}
+
+impl From<Severity> for AnomalySeverity {
+	fn from(v: Severity) -> Self {
+		match v {
+			Severity::Info => AnomalySeverity::Info,
+			Severity::Warning => AnomalySeverity::Warning,
+			Severity::Critical => AnomalySeverity::Critical,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		api::ApiService,
+		collector::candidate_record::CandidateInclusionRecord,
+		observation::ValidityVote,
+		storage::{RecordTime, RecordsStorageConfig, StorageEntry},
+	};
+	use std::time::Duration;
+
+	#[tokio::test]
+	async fn test_resolves_backing_group_of_candidate() {
+		let api: CollectorStorageApi = ApiService::new_with_prefixed_storage(
+			RecordsStorageConfig { max_blocks: 4 },
+			Default::default(),
+			Default::default(),
+		);
+		let storage = api.storage();
+		let (candidate_hash, relay_parent) = (H256::repeat_byte(1), H256::repeat_byte(2));
+		let record_time = RecordTime::with_ts(10, Duration::from_secs(60));
+		let record = CandidateRecord {
+			candidate_first_seen: Duration::from_secs(60),
+			candidate_inclusion: CandidateInclusionRecord {
+				parachain_id: 100,
+				backed: 11,
+				included: Some(13),
+				timedout: None,
+				core_idx: Some(0),
+				group_idx: Some(1),
+				relay_parent,
+				relay_parent_number: 10,
+			},
+			candidate_disputed: None,
+			validity_votes: vec![ValidityVote {
+				validator_index: 2,
+				address: Some(AccountId32([2; 32]).to_string()),
+				voted: true,
+			}],
+		};
+		let groups = vec![vec![ValidatorIndex(0)], vec![ValidatorIndex(1), ValidatorIndex(2)]];
+		for (prefix, key, entry) in [
+			(CollectorPrefixType::Candidate(100), candidate_hash, StorageEntry::new_onchain(record_time, record)),
+			(CollectorPrefixType::CandidatesParachains, candidate_hash, StorageEntry::new_onchain(record_time, 100u32)),
+			(CollectorPrefixType::BackingGroups, relay_parent, StorageEntry::new_onchain(record_time, groups)),
+			(CollectorPrefixType::SessionIndex, relay_parent, StorageEntry::new_onchain(record_time, 5u32)),
+			(
+				CollectorPrefixType::Session,
+				BlakeTwo256::hash(&5u32.to_be_bytes()[..]),
+				StorageEntry::new_persistent(record_time, 5u32),
+			),
+			(
+				CollectorPrefixType::AccountKeys,
+				BlakeTwo256::hash(&5u32.to_be_bytes()[..]),
+				StorageEntry::new_persistent(
+					record_time,
+					vec![AccountId32([0; 32]), AccountId32([1; 32]), AccountId32([2; 32])],
+				),
+			),
+		] {
+			storage.storage_write_prefixed(prefix, key, entry).await.unwrap();
+		}
+
+		let query = format!(
+			"{{ candidate(hash: \"{:?}\") {{ paraId inclusionTime backingGroup {{ index validators {{ index account }} }} \
+			 votes {{ index voted }} }} }}",
+			candidate_hash
+		);
+		let response = schema(api.clone(), Default::default()).execute(query).await;
+		assert!(response.errors.is_empty(), "{:?}", response.errors);
+		let data = response.data.into_json().unwrap();
+		assert_eq!(data["candidate"]["paraId"], 100);
+		assert_eq!(data["candidate"]["inclusionTime"], 2);
+		assert_eq!(data["candidate"]["backingGroup"]["index"], 1);
+		assert_eq!(data["candidate"]["backingGroup"]["validators"][1]["account"], AccountId32([2; 32]).to_string());
+		assert_eq!(data["candidate"]["votes"][0]["index"], 2);
+		assert_eq!(data["candidate"]["votes"][0]["voted"], true);
+
+		let response = schema(api.clone(), Default::default())
+			.execute("{ paras { id candidates { backed } } sessions { index } }")
+			.await;
+		let data = response.data.into_json().unwrap();
+		assert_eq!(data["paras"][0]["id"], 100);
+		assert_eq!(data["paras"][0]["candidates"][0]["backed"], 11);
+		assert_eq!(data["sessions"][0]["index"], 5);
+
+		let response = schema(api.clone(), Default::default())
+			.execute("{ candidates(offset: 1) { backed } }")
+			.await;
+		assert!(response.errors.is_empty(), "{:?}", response.errors);
+		assert_eq!(response.data.into_json().unwrap()["candidates"].as_array().unwrap().len(), 0);
+		let response = schema(api, Default::default())
+			.execute("{ candidates(limit: 1001) { backed } }")
+			.await;
+		assert_eq!(response.errors.len(), 1);
+	}
+}
@@ -0,0 +1,93 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Progress of the collector shared with the health endpoint.
+
+use crate::types::{BlockNumber, Timestamp};
+use std::{
+	collections::BTreeMap,
+	sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Default)]
+struct HealthState {
+	/// The last processed relay chain block and its timestamp in milliseconds
+	last_block: Option<(BlockNumber, Timestamp)>,
+	/// Number of messages waiting in the output channels, by channel name
+	queue_depths: BTreeMap<String, usize>,
+}
+
+/// Updated by the collector on every processed block, read by the health endpoint
+#[derive(Clone, Debug)]
+pub(crate) struct CollectorHealth {
+	/// Maximum number of blocks kept in the storage
+	max_blocks: usize,
+	state: Arc<Mutex<HealthState>>,
+}
+
+impl CollectorHealth {
+	pub(crate) fn new(max_blocks: usize) -> Self {
+		Self { max_blocks, state: Default::default() }
+	}
+
+	/// Saves the last processed block
+	pub(crate) fn on_block(&self, block_number: BlockNumber, ts: Timestamp) {
+		self.state.lock().unwrap().last_block = Some((block_number, ts));
+	}
+
+	/// Saves the number of messages waiting in the output channels
+	pub(crate) fn set_queue_depths(&self, queue_depths: BTreeMap<String, usize>) {
+		self.state.lock().unwrap().queue_depths = queue_depths;
+	}
+
+	pub(crate) fn max_blocks(&self) -> usize {
+		self.max_blocks
+	}
+
+	pub(crate) fn last_block_number(&self) -> Option<BlockNumber> {
+		self.state.lock().unwrap().last_block.map(|(number, _)| number)
+	}
+
+	pub(crate) fn queue_depths(&self) -> BTreeMap<String, usize> {
+		self.state.lock().unwrap().queue_depths.clone()
+	}
+
+	/// Returns seconds passed since the timestamp of the last processed block
+	pub(crate) fn processing_lag(&self, now: Timestamp) -> Option<u64> {
+		self.state
+			.lock()
+			.unwrap()
+			.last_block
+			.map(|(_, ts)| now.saturating_sub(ts) / 1000)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_returns_processing_lag() {
+		let health = CollectorHealth::new(64);
+		assert_eq!(health.processing_lag(1694095332000), None);
+
+		health.on_block(42, 1694095320000);
+		assert_eq!(health.last_block_number(), Some(42));
+		assert_eq!(health.processing_lag(1694095332000), Some(12));
+		// Local clock is behind the block author's one
+		assert_eq!(health.processing_lag(1694095310000), Some(0));
+	}
+}
@@ -14,27 +14,40 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod block_context;
 pub mod candidate_record;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod health;
 mod ws;
+mod ws_clients;
 
 use crate::{
 	api::{
-		subxt_wrapper::{InherentData, RequestExecutor, SubxtWrapperError},
+		subxt_wrapper::{ExecutorContext, InherentData, RequestExecutor, SubxtWrapperError},
 		ApiService,
 	},
 	chain_events::{
-		decode_chain_event, ChainEvent, SubxtCandidateEvent, SubxtCandidateEventType, SubxtDispute, SubxtDisputeResult,
+		decode_chain_event, ChainEvent, ChainEventKind, SubxtCandidateEvent, SubxtCandidateEventType, SubxtDispute,
+		SubxtDisputeResult,
 	},
 	chain_subscription::ChainSubscriptionEvent,
-	metadata::polkadot_primitives::DisputeStatement,
+	decode_diagnostics::{DecodeDiagnostics, DecodeFailure, DecodeFailuresCounter},
+	event_decoding::EventDecodingCounter,
+	incidents::Incidents,
+	metadata::polkadot_primitives::{DisputeStatement, ValidatorIndex},
+	observation::{validity_votes, ValidityVote},
+	rpc_budget::RpcBudgetCounter,
 	storage::{RecordTime, RecordsStorageConfig, StorageEntry},
-	types::{Header, OnDemandOrder, Timestamp, H256},
+	types::{AccountId32, CoreOccupied, ExtrinsicWeight, Header, OnDemandOrder, Timestamp, H256},
 	utils::RetryOptions,
 };
+use block_context::BlockContext;
 use candidate_record::{CandidateDisputed, CandidateInclusionRecord, CandidateRecord, DisputeResult};
 use clap::{Parser, ValueEnum};
 use color_eyre::eyre::eyre;
 use futures_util::StreamExt;
+use health::CollectorHealth;
 use log::{debug, error, info, warn};
 use parity_scale_codec::{Decode, Encode};
 use polkadot_introspector_priority_channel::{
@@ -46,6 +59,8 @@ use std::{
 	default::Default,
 	hash::Hash,
 	net::SocketAddr,
+	path::PathBuf,
+	sync::Arc,
 	time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use subxt::{
@@ -67,11 +82,32 @@ pub struct CollectorOptions {
 	/// Maximum blocks to store
 	#[clap(name = "max-blocks", long)]
 	max_blocks: Option<usize>,
-	/// WS listen address to bind to
+	/// Address to serve the collector data at: REST, Web-Socket and GraphQL (`/v1/graphql`) endpoints
 	#[clap(short = 'l', long = "listen")]
 	listen_addr: Option<SocketAddr>,
+	/// Origins allowed to make cross-origin requests to the `--listen` endpoints, e.g. `https://example.com`.
+	/// Cross-origin requests are not allowed by default
+	#[clap(long = "api-cors-origin", value_delimiter = ',')]
+	api_cors_origins: Vec<String>,
+	/// Serve a GraphiQL page to explore the GraphQL schema at `/v1/graphiql`
+	#[cfg(feature = "graphql")]
+	#[clap(long)]
+	graphiql: bool,
 	#[clap(short = 's', long = "subscribe-mode", default_value_t, value_enum)]
 	pub subscribe_mode: CollectorSubscribeMode,
+	/// Kinds of chain events to decode, all by default. Events of the other kinds are passed on without decoding their
+	/// fields, saving CPU time on busy chains at the cost of the data derived from them
+	#[clap(long, value_delimiter = ',', value_enum)]
+	pub decode_events: Vec<ChainEventKind>,
+	/// Write the context and raw bytes of the chain data that cannot be decoded to this file as JSON lines, once per
+	/// type and runtime version. Such data is skipped and the processing goes on
+	#[clap(long)]
+	pub decode_diagnostics: Option<PathBuf>,
+	/// Keep the collected records in a database at this path, so they survive restarts and can be queried after
+	/// the fact, e.g. via `--listen`. Records are kept in memory by default
+	#[cfg(feature = "persistent-storage")]
+	#[clap(long)]
+	pub storage_path: Option<PathBuf>,
 }
 
 /// How to subscribe to subxt blocks
@@ -84,8 +120,9 @@ pub enum CollectorSubscribeMode {
 	Finalized,
 }
 
-/// This type is used to distinguish different keys in the storage
-#[derive(Clone, Copy, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+/// This type is used to distinguish different keys in the storage.
+/// It's encoded in the keys of the persistent storage, so new variants go at the end
+#[derive(Clone, Copy, Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Encode, Decode)]
 pub enum CollectorPrefixType {
 	/// A block's timestamp
 	Timestamp,
@@ -111,6 +148,16 @@ pub enum CollectorPrefixType {
 	Dispute(u32),
 	/// On-demand order information by parachain id
 	OnDemandOrder(u32),
+	/// All on-demand orders placed in a relay chain block
+	OnDemandOrders,
+	/// Actual weight of the `ParaInherent` extrinsic
+	ParaInherentWeight,
+	/// Runtime spec version active at a relay chain block
+	SpecVersion,
+	/// Session index of a relay chain block
+	SessionIndex,
+	/// Index of a session with validators account keys, keyed by session index hash (blake2b(session_index))
+	Session,
 }
 
 /// A type that defines prefix + hash itself
@@ -136,6 +183,8 @@ struct CollectorState {
 	current_relay_chain_block_number: u32,
 	/// A list of hashes at this height (e.g. if we see forks)
 	current_relay_chain_block_hashes: Vec<H256>,
+	/// Contexts of the blocks at this height
+	current_block_contexts: Vec<Arc<BlockContext>>,
 	/// A list of candidates seen, indexed by parachain id
 	candidates_seen: BTreeMap<u32, Vec<H256>>,
 	/// A list of disputes seen, indexed by parachain id
@@ -144,6 +193,8 @@ struct CollectorState {
 	current_session_index: u32,
 	/// Last finalized block number
 	last_finalized_block_number: Option<u32>,
+	/// Runtime spec version of the processed blocks, reset by a runtime upgrade
+	spec_version: Option<u32>,
 }
 
 /// Provides collector new head events split by parachain
@@ -159,6 +210,19 @@ pub struct NewHeadEvent {
 	pub candidates_seen: Vec<H256>,
 	/// Disputes concluded in this block
 	pub disputes_concluded: Vec<DisputeInfo>,
+	/// Contexts of the relay parent blocks, missing for blocks the collector failed to process
+	pub block_contexts: Vec<Arc<BlockContext>>,
+}
+
+/// Provides collector new head events for the relay chain as a whole
+#[derive(Clone, Debug)]
+pub struct NewRelayHeadEvent {
+	/// Relay parent block number
+	pub relay_parent_number: u32,
+	/// Relay parent block hash (or hashes in case of the forks)
+	pub relay_parent_hashes: Vec<H256>,
+	/// Contexts of the relay parent blocks, missing for blocks the collector failed to process
+	pub block_contexts: Vec<Arc<BlockContext>>,
 }
 
 /// Handles collector updates
@@ -166,6 +230,9 @@ pub struct NewHeadEvent {
 pub enum CollectorUpdateEvent {
 	/// Occurs on new block processing with the information about the previous block
 	NewHead(NewHeadEvent),
+	/// Occurs on new block processing with the information about the previous relay chain block,
+	/// sent only to relay chain subscribers
+	NewRelayHead(NewRelayHeadEvent),
 	/// Occurs on a new session
 	NewSession(u32),
 	/// Occurs when collector is disconnected and is about to terminate
@@ -198,27 +265,55 @@ pub struct Collector {
 	endpoint: String,
 	subscribe_channels: BTreeMap<u32, Vec<Sender<CollectorUpdateEvent>>>,
 	broadcast_channels: Vec<Sender<CollectorUpdateEvent>>,
+	relay_channels: Vec<Sender<CollectorUpdateEvent>>,
 	state: CollectorState,
 	executor: RequestExecutor,
 	subscribe_mode: CollectorSubscribeMode,
+	/// Kinds of chain events to decode
+	decode_events: Vec<ChainEventKind>,
+	event_decoding: EventDecodingCounter,
+	health: CollectorHealth,
+	decode_diagnostics: DecodeDiagnostics,
+	rpc_budget: RpcBudgetCounter,
+	/// The last block counted as a data gap, a block is counted once however many of its reads failed
+	last_data_gap: Option<H256>,
 }
 
 impl Collector {
-	pub fn new(endpoint: &str, opts: CollectorOptions, retry: RetryOptions) -> Self {
-		let api: CollectorStorageApi = ApiService::new_with_prefixed_storage(
-			RecordsStorageConfig { max_blocks: opts.max_blocks.unwrap_or(64) },
-			retry,
-		);
+	pub fn new(
+		endpoint: &str,
+		opts: CollectorOptions,
+		retry: RetryOptions,
+		context: ExecutorContext,
+	) -> color_eyre::Result<Self> {
+		let max_blocks = opts.max_blocks.unwrap_or(64);
+		let rpc_budget = context.rpc_budget().clone();
+		#[cfg(feature = "persistent-storage")]
+		let api: CollectorStorageApi = match opts.storage_path {
+			Some(ref path) =>
+				ApiService::new_with_persistent_storage(RecordsStorageConfig { max_blocks }, path, retry, context)?,
+			None => ApiService::new_with_prefixed_storage(RecordsStorageConfig { max_blocks }, retry, context),
+		};
+		#[cfg(not(feature = "persistent-storage"))]
+		let api: CollectorStorageApi =
+			ApiService::new_with_prefixed_storage(RecordsStorageConfig { max_blocks }, retry, context);
+		let health = CollectorHealth::new(max_blocks);
 		let ws_listener = if let Some(listen_addr) = opts.listen_addr {
-			let ws_listener_config = WebSocketListenerConfig::builder().listen_addr(listen_addr).build();
-			let ws_listener = WebSocketListener::new(ws_listener_config, api.clone());
+			let ws_listener_config = WebSocketListenerConfig::builder()
+				.listen_addr(listen_addr)
+				.cors_origins(opts.api_cors_origins.clone());
+			#[cfg(feature = "graphql")]
+			let ws_listener_config = ws_listener_config.graphiql(opts.graphiql);
+			let ws_listener_config = ws_listener_config.build();
+			let ws_listener = WebSocketListener::new(ws_listener_config, api.clone(), health.clone());
 
 			Some(ws_listener)
 		} else {
 			None
 		};
 		let executor = api.subxt();
-		Self {
+		let decode_diagnostics = DecodeDiagnostics::new(opts.decode_diagnostics);
+		Ok(Self {
 			api,
 			ws_listener,
 			to_websocket: None,
@@ -226,9 +321,31 @@ impl Collector {
 			subscribe_channels: Default::default(),
 			state: Default::default(),
 			broadcast_channels: Default::default(),
+			relay_channels: Default::default(),
 			executor,
 			subscribe_mode: opts.subscribe_mode,
+			decode_events: if opts.decode_events.is_empty() {
+				ChainEventKind::value_variants().to_vec()
+			} else {
+				opts.decode_events
+			},
+			event_decoding: Default::default(),
+			health,
+			decode_diagnostics,
+			rpc_budget,
+			last_data_gap: None,
+		})
+	}
+
+	/// Serves the anomalies recorded in the given incident log by the API
+	pub fn with_incidents(mut self, incidents: Incidents) -> Self {
+		#[cfg(feature = "graphql")]
+		if let Some(ws_listener) = self.ws_listener.as_mut() {
+			ws_listener.set_incidents(incidents);
 		}
+		#[cfg(not(feature = "graphql"))]
+		let _ = incidents;
+		self
 	}
 
 	/// Spawns a collector futures (e.g. websocket server)
@@ -255,9 +372,14 @@ impl Collector {
 				match consumer_channel.next().await {
 					Some(event) => match self.collect_chain_events(&event).await {
 						Ok(subxt_events) =>
-							for event in subxt_events.iter() {
-								if let Err(error) = self.process_chain_event(event).await {
+							for chain_event in subxt_events.iter() {
+								if let Err(error) = self.process_chain_event(chain_event).await {
 									error!("collector service could not process event: {}", error);
+									if let ChainSubscriptionEvent::NewBestHead((hash, _)) |
+									ChainSubscriptionEvent::NewFinalizedBlock((hash, _)) = event
+									{
+										self.on_data_gap(hash);
+									}
 									match error {
 										CollectorError::ExecutorFatal(e) => {
 											self.broadcast_event_priority(CollectorUpdateEvent::Termination(
@@ -317,13 +439,43 @@ impl Collector {
 			ChainSubscriptionEvent::NewFinalizedBlock((hash, header)) =>
 				ChainEvent::NewFinalizedHead((*hash, header.clone())),
 			ChainSubscriptionEvent::Heartbeat => return Ok(vec![]),
+			ChainSubscriptionEvent::Unreachable(e) => return Err(eyre!("{}", e)),
 		};
 		let mut chain_events = vec![new_head_event];
 
 		if let Some(hash) = new_head_hash(event, self.subscribe_mode) {
+			let block_number = match event {
+				ChainSubscriptionEvent::NewBestHead((_, header)) |
+				ChainSubscriptionEvent::NewFinalizedBlock((_, header)) => Some(header.number),
+				ChainSubscriptionEvent::Heartbeat | ChainSubscriptionEvent::Unreachable(_) => None,
+			};
 			if let Some(block_events) = self.executor.get_events(self.endpoint.as_str(), *hash).await? {
 				for block_event in block_events.iter() {
-					chain_events.push(decode_chain_event(*hash, block_event.unwrap()).await?);
+					let block_event = match block_event {
+						Ok(v) => v,
+						Err(e) => {
+							// The following events of the block cannot be split either
+							let bytes = block_events.bytes().to_vec();
+							self.report_decode_failure(*hash, block_number, "EventRecord", e.to_string(), bytes)
+								.await;
+							break
+						},
+					};
+					// The new runtime is active from the next block
+					if block_event.pallet_name() == "System" && block_event.variant_name() == "CodeUpdated" {
+						self.state.spec_version = None;
+					}
+					let raw_event = block_event.clone();
+					match decode_chain_event(*hash, block_event, &self.decode_events, &self.event_decoding).await {
+						Ok(v) => chain_events.push(v),
+						Err(e) => {
+							let type_path = format!("{}.{}", raw_event.pallet_name(), raw_event.variant_name());
+							let bytes = raw_event.bytes().to_vec();
+							self.report_decode_failure(*hash, block_number, &type_path, e.to_string(), bytes)
+								.await;
+							chain_events.push(ChainEvent::RawEvent(*hash, raw_event));
+						},
+					}
 				}
 			}
 		};
@@ -345,6 +497,8 @@ impl Collector {
 				self.process_dispute_concluded(dispute_event, dispute_outcome).await,
 			ChainEvent::OnDemandOrderPlaced(block_hash, order) =>
 				self.process_on_demand_order_placed(block_hash, order).await,
+			ChainEvent::ParaInherentWeight(block_hash, weight) =>
+				self.process_para_inherent_weight(block_hash, weight).await,
 			_ => Ok(()),
 		}
 	}
@@ -368,6 +522,14 @@ impl Collector {
 		Ok(receiver)
 	}
 
+	/// Subscribe for relay chain updates
+	pub async fn subscribe_relay_updates(&mut self) -> color_eyre::Result<Receiver<CollectorUpdateEvent>> {
+		let (sender, receiver) = priority_channel_with_capacities(COLLECTOR_NORMAL_CHANNEL_CAPACITY, 1);
+		self.relay_channels.push(sender);
+
+		Ok(receiver)
+	}
+
 	/// Returns API endpoint for storage and request executor
 	pub fn api(&self) -> CollectorStorageApi {
 		self.api.clone()
@@ -378,7 +540,19 @@ impl Collector {
 		self.executor.clone()
 	}
 
+	/// Returns the counter of the chain data that failed to decode
+	pub fn decode_failures(&self) -> DecodeFailuresCounter {
+		self.decode_diagnostics.counter()
+	}
+
+	/// Returns the counter of the decoded and skipped chain events
+	pub fn event_decoding(&self) -> EventDecodingCounter {
+		self.event_decoding.clone()
+	}
+
 	async fn update_state(&mut self, block_number: u32, block_hash: H256) -> color_eyre::Result<()> {
+		self.update_queue_depths();
+
 		for (para_id, channels) in self.subscribe_channels.iter_mut() {
 			let candidates = self.state.candidates_seen.get(para_id);
 			let disputes_concluded = self.state.disputes_seen.get(para_id).map(|disputes_seen| {
@@ -397,6 +571,7 @@ impl Collector {
 						candidates_seen: candidates.cloned().unwrap_or_default(),
 						disputes_concluded: disputes_concluded.clone().unwrap_or_default(),
 						para_id: *para_id,
+						block_contexts: self.state.current_block_contexts.clone(),
 					}))
 					.await?;
 			}
@@ -418,18 +593,50 @@ impl Collector {
 						candidates_seen: candidates.clone(),
 						disputes_concluded: disputes_concluded.clone().unwrap_or_default(),
 						para_id: *para_id,
+						block_contexts: self.state.current_block_contexts.clone(),
 					}))
 					.await?;
 			}
 		}
 
+		for relay_channel in self.relay_channels.iter_mut() {
+			relay_channel
+				.send(CollectorUpdateEvent::NewRelayHead(NewRelayHeadEvent {
+					relay_parent_hashes: self.state.current_relay_chain_block_hashes.clone(),
+					relay_parent_number: self.state.current_relay_chain_block_number,
+					block_contexts: self.state.current_block_contexts.clone(),
+				}))
+				.await?;
+		}
+
 		self.state.candidates_seen.clear();
 		self.state.current_relay_chain_block_hashes.clear();
+		self.state.current_block_contexts.clear();
 		self.state.current_relay_chain_block_number = block_number;
 		self.state.current_relay_chain_block_hashes.push(block_hash);
 		Ok(())
 	}
 
+	/// Saves the number of messages not yet received by the subscribers for the health endpoint
+	fn update_queue_depths(&self) {
+		let mut queue_depths = BTreeMap::new();
+		for (para_id, channels) in self.subscribe_channels.iter() {
+			for (idx, channel) in channels.iter().enumerate() {
+				queue_depths.insert(format!("parachain-{}-{}", para_id, idx), channel.len());
+			}
+		}
+		for (idx, channel) in self.broadcast_channels.iter().enumerate() {
+			queue_depths.insert(format!("broadcast-{}", idx), channel.len());
+		}
+		for (idx, channel) in self.relay_channels.iter().enumerate() {
+			queue_depths.insert(format!("relay-{}", idx), channel.len());
+		}
+		if let Some(to_websocket) = self.to_websocket.as_ref() {
+			queue_depths.insert("websocket".to_owned(), to_websocket.len());
+		}
+		self.health.set_queue_depths(queue_depths);
+	}
+
 	/// Send event to all open channels
 	async fn broadcast_event(&mut self, event: CollectorUpdateEvent) -> color_eyre::Result<()> {
 		for (_, channels) in self.subscribe_channels.iter_mut() {
@@ -442,6 +649,10 @@ impl Collector {
 			broadcast_channel.send(event.clone()).await?;
 		}
 
+		for relay_channel in self.relay_channels.iter_mut() {
+			relay_channel.send(event.clone()).await?;
+		}
+
 		Ok(())
 	}
 
@@ -457,6 +668,10 @@ impl Collector {
 			broadcast_channel.send_priority(event.clone()).await?;
 		}
 
+		for relay_channel in self.relay_channels.iter_mut() {
+			relay_channel.send_priority(event.clone()).await?;
+		}
+
 		Ok(())
 	}
 
@@ -544,6 +759,12 @@ impl Collector {
 		)
 		.await?;
 		let cur_session = self.executor.get_session_index(self.endpoint.as_str(), block_hash).await?;
+		self.storage_write_prefixed(
+			CollectorPrefixType::SessionIndex,
+			block_hash,
+			StorageEntry::new_onchain(RecordTime::with_ts(block_number, Duration::from_secs(ts)), cur_session),
+		)
+		.await?;
 		let cur_session_hash = BlakeTwo256::hash(&cur_session.to_be_bytes()[..]);
 		let maybe_existing_session = self
 			.storage_read_prefixed(CollectorPrefixType::AccountKeys, cur_session_hash)
@@ -553,7 +774,7 @@ impl Collector {
 			debug!("new session: {}, hash: {}", cur_session, cur_session_hash);
 			let accounts_keys = self
 				.executor
-				.get_session_account_keys(self.endpoint.as_str(), cur_session)
+				.get_session_account_keys(self.endpoint.as_str(), block_hash, cur_session)
 				.await?
 				.ok_or_else(|| eyre!("Missing account keys for session {}", cur_session))?;
 			self.storage_write_prefixed(
@@ -562,6 +783,12 @@ impl Collector {
 				StorageEntry::new_persistent(RecordTime::with_ts(block_number, Duration::from_secs(ts)), accounts_keys),
 			)
 			.await?;
+			self.storage_write_prefixed(
+				CollectorPrefixType::Session,
+				cur_session_hash,
+				StorageEntry::new_persistent(RecordTime::with_ts(block_number, Duration::from_secs(ts)), cur_session),
+			)
+			.await?;
 			// Remove old session with the index `cur_session - 2` ignoring possible errors
 			if cur_session > 1 {
 				let prev_session = cur_session.saturating_sub(2);
@@ -570,6 +797,9 @@ impl Collector {
 				let _ = self
 					.storage_delete_prefixed(CollectorPrefixType::AccountKeys, prev_session_hash)
 					.await;
+				let _ = self
+					.storage_delete_prefixed(CollectorPrefixType::Session, prev_session_hash)
+					.await;
 			}
 		}
 
@@ -577,11 +807,26 @@ impl Collector {
 			self.state.current_session_index = cur_session;
 			self.broadcast_event(CollectorUpdateEvent::NewSession(cur_session)).await?;
 		}
-		self.write_parainherent_data(block_hash, block_number, ts).await?;
+		let inherent_data = self.write_parainherent_data(block_hash, block_number, ts).await?;
 		self.write_ts(block_hash, block_number, ts).await?;
-		self.write_occupied_cores(block_hash, block_number, ts).await?;
-		self.write_backing_groups(block_hash, block_number, ts).await?;
-		self.write_core_assignments(block_hash, block_number, ts).await?;
+		let occupied_cores = self.write_occupied_cores(block_hash, block_number, ts).await?;
+		let backing_groups = self.write_backing_groups(block_hash, block_number, ts).await?;
+		let core_assignments = self.write_core_assignments(block_hash, block_number, ts).await?;
+		let spec_version = self.write_spec_version(block_hash, block_number, ts).await?;
+		self.state.current_block_contexts.push(Arc::new(BlockContext {
+			block_hash,
+			block_number,
+			header: header.clone(),
+			timestamp: ts,
+			session_index: cur_session,
+			spec_version,
+			inherent_data: inherent_data.map(Arc::new),
+			occupied_cores,
+			backing_groups,
+			core_assignments,
+		}));
+
+		self.health.on_block(block_number, ts);
 
 		debug!(
 			"Success! new block hash: {:?}, number: {}, previous number: {}, previous hashes: {:?}",
@@ -594,18 +839,34 @@ impl Collector {
 		Ok(())
 	}
 
+	/// Counts a block whose data could not be fetched, once however many of its reads failed
+	fn on_data_gap(&mut self, block_hash: H256) {
+		if self.last_data_gap.replace(block_hash) != Some(block_hash) {
+			self.rpc_budget.on_data_gap();
+		}
+	}
+
 	async fn write_parainherent_data(
 		&mut self,
 		block_hash: H256,
 		block_number: u32,
 		ts: Timestamp,
-	) -> color_eyre::Result<(), CollectorError> {
-		let inherent_data = self
+	) -> color_eyre::Result<Option<InherentData>, CollectorError> {
+		let inherent_data = match self
 			.executor
 			.extract_parainherent_data(self.endpoint.as_str(), Some(block_hash))
-			.await?;
+			.await
+		{
+			Err(SubxtWrapperError::DecodeError { type_path, error, bytes }) => {
+				self.report_decode_failure(block_hash, Some(block_number), &type_path, error, bytes)
+					.await;
+				self.on_data_gap(block_hash);
+				return Ok(None)
+			},
+			v => v?,
+		};
 
-		if let Some(inherent_data) = inherent_data {
+		if let Some(ref inherent_data) = inherent_data {
 			self.storage_write_prefixed(
 				CollectorPrefixType::InherentData,
 				block_hash,
@@ -614,9 +875,30 @@ impl Collector {
 			.await?;
 		} else {
 			warn!("cannot get inherent data for block number {} ({})", block_number, block_hash);
+			self.on_data_gap(block_hash);
 		}
 
-		Ok(())
+		Ok(inherent_data)
+	}
+
+	/// Reports the data of a block that cannot be decoded, with the spec version of the block if it can be fetched
+	async fn report_decode_failure(
+		&mut self,
+		block_hash: H256,
+		block_number: Option<u32>,
+		type_path: &str,
+		error: String,
+		bytes: Vec<u8>,
+	) {
+		let spec_version = self.spec_version(block_hash).await.ok();
+		self.decode_diagnostics.report(DecodeFailure {
+			block_hash,
+			block_number,
+			spec_version,
+			type_path: type_path.to_string(),
+			error,
+			bytes,
+		});
 	}
 
 	async fn write_ts(
@@ -640,16 +922,44 @@ impl Collector {
 		block_hash: H256,
 		block_number: u32,
 		ts: Timestamp,
-	) -> color_eyre::Result<(), CollectorError> {
+	) -> color_eyre::Result<Vec<CoreOccupied>, CollectorError> {
 		let cores = self.executor.get_occupied_cores(self.endpoint.as_str(), block_hash).await?;
 		self.storage_write_prefixed(
 			CollectorPrefixType::OccupiedCores,
 			block_hash,
-			StorageEntry::new_onchain(RecordTime::with_ts(block_number, Duration::from_secs(ts)), cores),
+			StorageEntry::new_onchain(RecordTime::with_ts(block_number, Duration::from_secs(ts)), &cores),
 		)
 		.await?;
 
-		Ok(())
+		Ok(cores)
+	}
+
+	/// Returns the runtime spec version, fetched once and again only after a runtime upgrade
+	async fn spec_version(&mut self, block_hash: H256) -> color_eyre::Result<u32, SubxtWrapperError> {
+		if let Some(spec_version) = self.state.spec_version {
+			return Ok(spec_version)
+		}
+		let spec_version = self.executor.get_spec_version(self.endpoint.as_str(), block_hash).await?;
+		self.state.spec_version = Some(spec_version);
+
+		Ok(spec_version)
+	}
+
+	async fn write_spec_version(
+		&mut self,
+		block_hash: H256,
+		block_number: u32,
+		ts: Timestamp,
+	) -> color_eyre::Result<u32, CollectorError> {
+		let spec_version = self.spec_version(block_hash).await?;
+		self.storage_write_prefixed(
+			CollectorPrefixType::SpecVersion,
+			block_hash,
+			StorageEntry::new_onchain(RecordTime::with_ts(block_number, Duration::from_secs(ts)), spec_version),
+		)
+		.await?;
+
+		Ok(spec_version)
 	}
 
 	async fn write_backing_groups(
@@ -657,16 +967,16 @@ impl Collector {
 		block_hash: H256,
 		block_number: u32,
 		ts: Timestamp,
-	) -> color_eyre::Result<(), CollectorError> {
+	) -> color_eyre::Result<Vec<Vec<ValidatorIndex>>, CollectorError> {
 		let groups = self.executor.get_backing_groups(self.endpoint.as_str(), block_hash).await?;
 		self.storage_write_prefixed(
 			CollectorPrefixType::BackingGroups,
 			block_hash,
-			StorageEntry::new_onchain(RecordTime::with_ts(block_number, Duration::from_secs(ts)), groups),
+			StorageEntry::new_onchain(RecordTime::with_ts(block_number, Duration::from_secs(ts)), &groups),
 		)
 		.await?;
 
-		Ok(())
+		Ok(groups)
 	}
 
 	async fn write_core_assignments(
@@ -674,7 +984,7 @@ impl Collector {
 		block_hash: H256,
 		block_number: u32,
 		ts: Timestamp,
-	) -> color_eyre::Result<(), CollectorError> {
+	) -> color_eyre::Result<BTreeMap<u32, Vec<u32>>, CollectorError> {
 		// After adding On-demand Parachains, `ParaScheduler.Scheduled` API call will be removed
 		let mut assignments = self.core_assignments_via_scheduled_paras(block_hash).await;
 		// `ParaScheduler,Scheduled` not found, try to fetch `ParaScheduler.ClaimQueue`
@@ -689,14 +999,15 @@ impl Collector {
 			assignments = Ok(BTreeMap::default());
 		}
 
+		let assignments = assignments?;
 		self.storage_write_prefixed(
 			CollectorPrefixType::CoreAssignments,
 			block_hash,
-			StorageEntry::new_onchain(RecordTime::with_ts(block_number, Duration::from_secs(ts)), assignments?),
+			StorageEntry::new_onchain(RecordTime::with_ts(block_number, Duration::from_secs(ts)), &assignments),
 		)
 		.await?;
 
-		Ok(())
+		Ok(assignments)
 	}
 
 	async fn core_assignments_via_scheduled_paras(
@@ -727,6 +1038,33 @@ impl Collector {
 			.collect())
 	}
 
+	// Resolves the validity votes of the backing group members from the paras inherent of the block that has backed
+	// a candidate, returns nothing if the backing group is unknown
+	async fn read_validity_votes(&self, change_event: &SubxtCandidateEvent) -> Vec<ValidityVote> {
+		let Some(group_idx) = change_event.group_idx else { return vec![] };
+		for context in self.state.current_block_contexts.iter() {
+			let Some(group) = context.backing_groups.get(group_idx as usize) else { continue };
+			let Some(candidate) = context.inherent_data.as_ref().and_then(|inherent| {
+				inherent.backed_candidates.iter().find(|candidate| {
+					let commitments_hash = BlakeTwo256::hash_of(&candidate.candidate.commitments);
+					BlakeTwo256::hash_of(&(&candidate.candidate.descriptor, commitments_hash)) ==
+						change_event.candidate_hash
+				})
+			}) else {
+				continue
+			};
+			let session_hash = BlakeTwo256::hash(&context.session_index.to_be_bytes()[..]);
+			let session_keys: Option<Vec<AccountId32>> =
+				match self.storage_read_prefixed(CollectorPrefixType::AccountKeys, session_hash).await {
+					Some(entry) => entry.into_inner().ok(),
+					None => None,
+				};
+			return validity_votes(&candidate.validator_indices, group, session_keys.as_ref())
+		}
+
+		vec![]
+	}
+
 	async fn process_candidate_change(
 		&mut self,
 		change_event: &SubxtCandidateEvent,
@@ -781,7 +1119,8 @@ impl Collector {
 							relay_parent_number: relay_parent.number,
 							parachain_id: change_event.parachain_id,
 							backed: relay_block_number,
-							core_idx: None,
+							core_idx: change_event.core_idx,
+							group_idx: change_event.group_idx,
 							timedout: None,
 							included: None,
 						};
@@ -789,6 +1128,7 @@ impl Collector {
 							candidate_inclusion,
 							candidate_first_seen: now,
 							candidate_disputed: None,
+							validity_votes: self.read_validity_votes(change_event).await,
 						};
 						self.storage_write_prefixed(
 							CollectorPrefixType::Candidate(change_event.parachain_id),
@@ -1089,12 +1429,40 @@ impl Collector {
 		block_hash: &H256,
 		order: &OnDemandOrder,
 	) -> Result<(), CollectorError> {
+		let record_time = RecordTime::with_ts(self.state.current_relay_chain_block_number, get_unix_time_unwrap());
 		self.storage_write_prefixed(
 			CollectorPrefixType::OnDemandOrder(order.para_id),
 			*block_hash,
+			StorageEntry::new_onchain(record_time, order),
+		)
+		.await?;
+
+		let mut block_orders: Vec<OnDemandOrder> = self
+			.storage_read_prefixed(CollectorPrefixType::OnDemandOrders, *block_hash)
+			.await
+			.and_then(|v| v.into_inner().ok())
+			.unwrap_or_default();
+		block_orders.push(order.clone());
+		self.storage_replace_prefixed(
+			CollectorPrefixType::OnDemandOrders,
+			*block_hash,
+			StorageEntry::new_onchain(record_time, block_orders),
+		)
+		.await;
+		Ok(())
+	}
+
+	async fn process_para_inherent_weight(
+		&self,
+		block_hash: &H256,
+		weight: &ExtrinsicWeight,
+	) -> Result<(), CollectorError> {
+		self.storage_write_prefixed(
+			CollectorPrefixType::ParaInherentWeight,
+			*block_hash,
 			StorageEntry::new_onchain(
 				RecordTime::with_ts(self.state.current_relay_chain_block_number, get_unix_time_unwrap()),
-				order,
+				weight,
 			),
 		)
 		.await?;
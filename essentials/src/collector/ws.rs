@@ -13,16 +13,31 @@
 //
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+use super::health::CollectorHealth;
 use crate::{
 	chain_events::SubxtDisputeResult,
-	collector::{candidate_record::CandidateRecord, CollectorPrefixType, CollectorStorageApi},
-	types::{Timestamp, H256},
+	collector::{
+		candidate_record::CandidateRecord,
+		ws_clients::{WebSocketClientStats, WebSocketClients},
+		CollectorPrefixType, CollectorStorageApi,
+	},
+	types::{BlockNumber, Timestamp, H256},
 };
+#[cfg(feature = "graphql")]
+use crate::{
+	collector::graphql::{self, CollectorSchema},
+	incidents::Incidents,
+};
+#[cfg(feature = "graphql")]
+use async_graphql::http::GraphiQLSource;
+#[cfg(feature = "graphql")]
+use async_graphql_warp::{GraphQLBadRequest, GraphQLResponse};
 use futures::{SinkExt, StreamExt};
 use log::{debug, warn};
 use polkadot_introspector_priority_channel::Receiver;
 use serde::{Deserialize, Serialize};
 use std::{
+	collections::BTreeMap,
 	convert::Infallible,
 	error::Error,
 	fmt::Debug,
@@ -52,6 +67,19 @@ pub struct WebSocketListenerConfig {
 	/// SSL certificate for HTTP server
 	#[builder(default)]
 	cert: Option<PathBuf>,
+	/// Maximum number of updates queued per client
+	#[builder(default = 256)]
+	client_queue_size: usize,
+	/// Number of updates dropped in a row after that a slow client is disconnected
+	#[builder(default = 64)]
+	max_client_dropped: usize,
+	/// Origins allowed to make cross-origin requests, none if empty
+	#[builder(default)]
+	cors_origins: Vec<String>,
+	/// Serve a GraphiQL page at `/v1/graphiql`
+	#[cfg_attr(not(feature = "graphql"), allow(dead_code))]
+	#[builder(default)]
+	graphiql: bool,
 }
 
 /// Starts a Web-Socket listener given the config
@@ -60,6 +88,11 @@ pub struct WebSocketListener {
 	config: WebSocketListenerConfig,
 	/// Storage to access
 	api: CollectorStorageApi,
+	/// Progress of the collector
+	health: CollectorHealth,
+	/// Anomalies served by the GraphQL API
+	#[cfg(feature = "graphql")]
+	incidents: Incidents,
 }
 
 /// Defines Web-Socket event types
@@ -89,9 +122,26 @@ pub(crate) struct WebSocketUpdateEvent {
 #[derive(Deserialize, Serialize)]
 struct CandidatesQuery {
 	/// Filter candidates by parachain
+	#[serde(alias = "para_id")]
 	parachain_id: Option<u32>,
-	/// Filter candidates by time
+	/// Filter candidates first seen at or after this unix time, in seconds
 	not_before: Option<Timestamp>,
+	/// Filter candidates backed at or after this relay chain block
+	from: Option<BlockNumber>,
+	/// Filter candidates backed at or before this relay chain block
+	to: Option<BlockNumber>,
+}
+
+impl CandidatesQuery {
+	fn matches(&self, record: &CandidateRecord) -> bool {
+		self.in_block_range(record.candidate_inclusion.backed) &&
+			self.not_before
+				.map_or(true, |not_before| record.candidate_first_seen.as_secs() >= not_before)
+	}
+
+	fn in_block_range(&self, block_number: BlockNumber) -> bool {
+		self.from.map_or(true, |from| block_number >= from) && self.to.map_or(true, |to| block_number <= to)
+	}
 }
 
 /// Used to handle requests to get a specific candidate info
@@ -111,8 +161,20 @@ struct HealthQuery {
 /// Common functions for a listener
 impl WebSocketListener {
 	/// Creates a new socket listener with the specific config
-	pub(crate) fn new(config: WebSocketListenerConfig, api: CollectorStorageApi) -> Self {
-		Self { config, api }
+	pub(crate) fn new(config: WebSocketListenerConfig, api: CollectorStorageApi, health: CollectorHealth) -> Self {
+		Self {
+			config,
+			api,
+			health,
+			#[cfg(feature = "graphql")]
+			incidents: Default::default(),
+		}
+	}
+
+	/// Serves the anomalies of the given incident log
+	#[cfg(feature = "graphql")]
+	pub(crate) fn set_incidents(&mut self, incidents: Incidents) {
+		self.incidents = incidents;
 	}
 
 	/// Spawn an async HTTP server
@@ -126,6 +188,8 @@ impl WebSocketListener {
 		Update: Send + Sync + 'static + Clone + Serialize + Debug,
 	{
 		let has_sane_tls = self.config.privkey.is_some() && self.config.cert.is_some();
+		let clients = WebSocketClients::new(self.config.client_queue_size, self.config.max_client_dropped);
+		tokio::task::spawn(broadcast_updates(updates_broadcast, clients.clone()));
 
 		// Setup routes
 		let opt_ping = warp::query::<HealthQuery>()
@@ -133,6 +197,8 @@ impl WebSocketListener {
 			.or_else(|_| async { Ok::<(Option<HealthQuery>,), std::convert::Infallible>((None,)) });
 		let health_route = warp::path!("v1" / "health")
 			.and(with_api_service(self.api.clone()))
+			.and(with_health(self.health.clone()))
+			.and(with_clients(clients.clone()))
 			.and(opt_ping)
 			.and_then(health_handler);
 
@@ -148,17 +214,49 @@ impl WebSocketListener {
 			.and(with_api_service(self.api.clone()))
 			.and(warp::query::<CandidateGetQuery>())
 			.and_then(candidate_get_handler);
+		let get_candidate_by_path_route = warp::path!("v1" / "candidate" / String)
+			.and(with_api_service(self.api.clone()))
+			.map(|hash, api| (api, CandidateGetQuery { hash }))
+			.untuple_one()
+			.and_then(candidate_get_handler);
 		let ws_route = warp::path!("v1" / "ws")
 			.and(warp::ws())
-			.and(with_updates_channel(updates_broadcast))
+			.and(with_clients(clients))
 			.and(warp::addr::remote())
 			.and_then(ws_handler);
 		let routes = health_route
 			.or(candidates_route)
 			.or(get_candidate_route)
+			.or(get_candidate_by_path_route)
 			.or(ws_route)
-			.with(warp::cors().allow_any_origin())
-			.recover(handle_rejection);
+			.map(|reply| Box::new(reply) as Box<dyn Reply>)
+			.boxed();
+		#[cfg(feature = "graphql")]
+		let routes = {
+			let graphql_route = warp::path!("v1" / "graphql")
+				.and(async_graphql_warp::graphql(graphql::schema(self.api.clone(), self.incidents.clone())))
+				.and_then(graphql_handler);
+			let routes = routes.or(graphql_route).unify().boxed();
+			if self.config.graphiql {
+				let graphiql_route = warp::path!("v1" / "graphiql").and(warp::get()).map(|| {
+					Box::new(warp::reply::html(GraphiQLSource::build().endpoint("/v1/graphql").finish()))
+						as Box<dyn Reply>
+				});
+				routes.or(graphiql_route).unify().boxed()
+			} else {
+				routes
+			}
+		};
+		let routes = if self.config.cors_origins.is_empty() {
+			routes
+		} else {
+			let cors = warp::cors()
+				.allow_origins(self.config.cors_origins.iter().map(String::as_str))
+				.allow_methods(["GET", "POST"])
+				.allow_header("content-type");
+			routes.with(cors).map(|reply| Box::new(reply) as Box<dyn Reply>).boxed()
+		};
+		let routes = routes.recover(handle_rejection);
 		let server = warp::serve(routes);
 
 		if has_sane_tls {
@@ -190,40 +288,90 @@ fn with_api_service(
 	warp::any().map(move || api.clone())
 }
 
-fn with_updates_channel<T: Send>(
-	updates_rx: Receiver<T>,
-) -> impl Filter<Extract = (Receiver<T>,), Error = Infallible> + Clone {
-	warp::any().map(move || updates_rx.clone())
+fn with_health(health: CollectorHealth) -> impl Filter<Extract = (CollectorHealth,), Error = Infallible> + Clone {
+	warp::any().map(move || health.clone())
+}
+
+fn with_clients<T: Send + Sync + Clone>(
+	clients: WebSocketClients<T>,
+) -> impl Filter<Extract = (WebSocketClients<T>,), Error = Infallible> + Clone {
+	warp::any().map(move || clients.clone())
+}
+
+/// Queues the collector updates for the connected clients
+async fn broadcast_updates<T: Clone>(updates_rx: Receiver<T>, clients: WebSocketClients<T>) {
+	while let Ok(update) = updates_rx.recv().await {
+		for client in clients.broadcast(&update) {
+			warn!(
+				"{:?} disconnected as too slow, {} updates dropped, {} queued",
+				client.remote, client.dropped, client.queued
+			);
+		}
+	}
 }
 
-#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+#[derive(Serialize, Clone, PartialEq, Debug)]
 pub struct HealthReply {
 	/// How many candidates have we processed
 	pub candidates_stored: usize,
 	/// Timestamp from a request or our local timestamp
 	pub ts: Timestamp,
+	/// Number of messages not yet received by the subscribers, by channel
+	pub queue_depths: BTreeMap<String, usize>,
+	/// The last relay chain block processed by the collector
+	pub last_processed_block: Option<BlockNumber>,
+	/// Seconds passed since the timestamp of the last processed block
+	pub processing_lag_secs: Option<u64>,
+	/// Number of relay chain blocks kept in the storage
+	pub storage_blocks: usize,
+	/// Maximum number of relay chain blocks kept in the storage
+	pub storage_max_blocks: usize,
+	/// Share of the storage capacity in use, from 0 to 1
+	pub storage_utilization: f64,
+	/// Delivery statistics of the connected Web-Socket clients
+	pub websocket_clients: Vec<WebSocketClientStats>,
 }
 
-async fn health_handler(api: CollectorStorageApi, ping: Option<HealthQuery>) -> Result<impl Reply, Rejection> {
+async fn health_handler<T: Clone + Send>(
+	api: CollectorStorageApi,
+	health: CollectorHealth,
+	clients: WebSocketClients<T>,
+	ping: Option<HealthQuery>,
+) -> Result<impl Reply, Rejection> {
 	let storage_size = api.storage().storage_len().await;
+	let storage_blocks = api.storage().storage_blocks_len().await;
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
 	let ts = match ping {
 		Some(h) => h.ts,
-		None => SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+		None => now.as_secs(),
 	};
-	Ok(warp::reply::json(&HealthReply { candidates_stored: storage_size, ts }))
+	Ok(warp::reply::json(&HealthReply {
+		candidates_stored: storage_size,
+		ts,
+		queue_depths: health.queue_depths(),
+		last_processed_block: health.last_block_number(),
+		processing_lag_secs: health.processing_lag(now.as_millis() as Timestamp),
+		storage_blocks,
+		storage_max_blocks: health.max_blocks(),
+		storage_utilization: storage_blocks as f64 / health.max_blocks().max(1) as f64,
+		websocket_clients: clients.stats(),
+	}))
 }
 
-#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
-pub struct CandidatesReply {
-	/// How many candidates have we processed
-	pub candidates: Vec<H256>,
+/// A stored candidate with its hash
+#[derive(Serialize, Debug)]
+pub struct CandidateReply {
+	/// Candidate hash
+	pub hash: H256,
+	#[serde(flatten)]
+	pub record: CandidateRecord,
 }
 
 async fn candidates_handler(
 	api: CollectorStorageApi,
 	filter: Option<CandidatesQuery>,
 ) -> Result<impl Reply, Rejection> {
-	let keys = if let Some(para_id) = filter.and_then(|filt| filt.parachain_id) {
+	let keys = if let Some(para_id) = filter.as_ref().and_then(|filt| filt.parachain_id) {
 		api.storage().storage_keys_prefix(CollectorPrefixType::Candidate(para_id)).await
 	} else {
 		let mut output: Vec<H256> = vec![];
@@ -236,7 +384,22 @@ async fn candidates_handler(
 		output
 	};
 
-	Ok(warp::reply::json(&keys.into_iter().collect::<Vec<_>>().as_slice()))
+	let mut candidates: Vec<CandidateReply> = vec![];
+	for hash in keys {
+		let Some(record) = api
+			.storage()
+			.storage_read(hash)
+			.await
+			.and_then(|rec| rec.into_inner::<CandidateRecord>().ok())
+		else {
+			continue
+		};
+		if filter.as_ref().map_or(true, |filter| filter.matches(&record)) {
+			candidates.push(CandidateReply { hash, record });
+		}
+	}
+
+	Ok(warp::reply::json(&candidates))
 }
 
 async fn candidate_get_handler(
@@ -255,58 +418,72 @@ async fn candidate_get_handler(
 	}
 }
 
-pub async fn ws_handler<T>(
+#[cfg(feature = "graphql")]
+async fn graphql_handler(
+	(schema, request): (CollectorSchema, async_graphql::Request),
+) -> Result<Box<dyn Reply>, Rejection> {
+	Ok(Box::new(GraphQLResponse::from(schema.execute(request).await)))
+}
+
+pub(crate) async fn ws_handler<T>(
 	ws: warp::ws::Ws,
-	update_channel: Receiver<T>,
+	clients: WebSocketClients<T>,
 	remote: Option<SocketAddr>,
 ) -> Result<impl Reply, Rejection>
 where
 	T: Send + Sync + Clone + Debug + Serialize + 'static,
 {
-	Ok(ws.on_upgrade(move |socket| handle_ws_connection(socket, update_channel, remote)))
+	Ok(ws.on_upgrade(move |socket| handle_ws_connection(socket, clients, remote)))
 }
 
-async fn handle_ws_connection<T>(ws: WebSocket, update_channel: Receiver<T>, remote: Option<SocketAddr>)
+async fn handle_ws_connection<T>(ws: WebSocket, clients: WebSocketClients<T>, remote: Option<SocketAddr>)
 where
 	T: Send + Sync + Clone + Debug + Serialize + 'static,
 {
 	let (mut client_ws_sender, _client_ws_rcv) = ws.split();
+	let (id, mut updates) = clients.register(remote);
 	debug!("connected to ws: {:?}", remote.as_ref());
 
 	tokio::task::spawn(async move {
-		loop {
-			match update_channel.recv().await {
-				Ok(update) => {
-					debug!("received event: {:?}", &update);
-
-					match client_ws_sender
-						.send(Message::text(serde_json::to_string(&update).unwrap()))
-						.await
-					{
-						Ok(_) => {
-							debug!("{:?} sent update to ws client", remote.as_ref());
-						},
-						Err(err) => {
-							warn!("{:?} cannot send data: {:?}", remote.as_ref(), err);
-							return
-						},
-					}
+		// The queue is closed when the client is evicted
+		while let Some(update) = updates.recv().await {
+			debug!("received event: {:?}", &update);
+
+			match client_ws_sender
+				.send(Message::text(serde_json::to_string(&update).unwrap()))
+				.await
+			{
+				Ok(_) => {
+					debug!("{:?} sent update to ws client", remote.as_ref());
 				},
 				Err(err) => {
-					warn!("{:?} update channel error = {:?}", remote.as_ref(), err);
-
-					return
+					warn!("{:?} cannot send data: {:?}", remote.as_ref(), err);
+					break
 				},
 			}
 		}
+		clients.unregister(id);
+		let _ = client_ws_sender.close().await;
 	});
 }
 
+#[cfg(feature = "graphql")]
+fn is_graphql_bad_request(err: &Rejection) -> bool {
+	err.find::<GraphQLBadRequest>().is_some()
+}
+
+#[cfg(not(feature = "graphql"))]
+fn is_graphql_bad_request(_: &Rejection) -> bool {
+	false
+}
+
 async fn handle_rejection(err: Rejection) -> std::result::Result<impl Reply, Infallible> {
 	let (code, message) = if err.is_not_found() {
 		(StatusCode::NOT_FOUND, "Not Found")
 	} else if err.find::<warp::filters::body::BodyDeserializeError>().is_some() {
 		(StatusCode::BAD_REQUEST, "Invalid Body")
+	} else if is_graphql_bad_request(&err) {
+		(StatusCode::BAD_REQUEST, "Invalid GraphQL Request")
 	} else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
 		(StatusCode::METHOD_NOT_ALLOWED, "Method Not Allowed")
 	} else {
@@ -316,3 +493,76 @@ async fn handle_rejection(err: Rejection) -> std::result::Result<impl Reply, Inf
 
 	Ok(warp::reply::with_status(message, code))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		api::ApiService,
+		collector::candidate_record::CandidateInclusionRecord,
+		storage::{RecordTime, RecordsStorageConfig, StorageEntry},
+	};
+
+	#[tokio::test]
+	async fn test_filters_candidates() {
+		let api: CollectorStorageApi = ApiService::new_with_prefixed_storage(
+			RecordsStorageConfig { max_blocks: 64 },
+			Default::default(),
+			Default::default(),
+		);
+		for (hash, para_id, backed, first_seen) in [(1, 100, 10, 60), (2, 100, 15, 90), (3, 200, 20, 120)] {
+			let record = CandidateRecord {
+				candidate_first_seen: Duration::from_secs(first_seen),
+				candidate_inclusion: CandidateInclusionRecord {
+					parachain_id: para_id,
+					backed,
+					included: None,
+					timedout: None,
+					core_idx: None,
+					group_idx: None,
+					relay_parent: H256::zero(),
+					relay_parent_number: backed - 1,
+				},
+				candidate_disputed: None,
+				validity_votes: vec![],
+			};
+			let entry = StorageEntry::new_onchain(RecordTime::with_ts(backed, Duration::from_secs(first_seen)), record);
+			api.storage()
+				.storage_write_prefixed(CollectorPrefixType::Candidate(para_id), H256::repeat_byte(hash), entry)
+				.await
+				.unwrap();
+		}
+
+		let candidates = |path: &'static str| {
+			let api = api.clone();
+			async move {
+				let query = warp::test::request()
+					.path(path)
+					.filter(&warp::query::<CandidatesQuery>())
+					.await
+					.ok();
+				let reply = candidates_handler(api, query).await.unwrap().into_response();
+				let body = warp::hyper::body::to_bytes(reply.into_body()).await.unwrap();
+				let mut candidates: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+				candidates.sort_by_key(|candidate| candidate["candidate_inclusion"]["backed"].as_u64());
+				candidates
+			}
+		};
+
+		let all = candidates("/v1/candidates").await;
+		assert_eq!(all.len(), 3);
+		assert_eq!(all[0]["hash"], serde_json::to_value(H256::repeat_byte(1)).unwrap());
+		assert_eq!(all[0]["candidate_inclusion"]["parachain_id"], 100);
+
+		let para = candidates("/v1/candidates?para_id=100&from=11").await;
+		assert_eq!(para.len(), 1);
+		assert_eq!(para[0]["candidate_inclusion"]["backed"], 15);
+
+		let range = candidates("/v1/candidates?from=10&to=15").await;
+		assert_eq!(range.len(), 2);
+
+		let recent = candidates("/v1/candidates?not_before=90").await;
+		assert_eq!(recent.len(), 2);
+		assert_eq!(recent[0]["candidate_inclusion"]["backed"], 15);
+	}
+}
@@ -0,0 +1,178 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-client queues of the Web-Socket updates.
+//!
+//! Every connected client has a bounded queue, updates are dropped for a client whose queue is full instead of
+//! waiting for it, and a client dropping too many updates in a row is evicted, so a stalled connection can neither
+//! block the collector nor make it buffer updates without bounds.
+
+use serde::Serialize;
+use std::{
+	collections::BTreeMap,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+/// Delivery statistics of a connected client, reported by the health endpoint
+#[derive(Serialize, Clone, PartialEq, Eq, Debug)]
+pub struct WebSocketClientStats {
+	pub remote: Option<SocketAddr>,
+	/// Number of updates waiting in the client's queue
+	pub queued: usize,
+	/// Number of updates dropped because the client's queue was full
+	pub dropped: u64,
+}
+
+struct Client<T> {
+	remote: Option<SocketAddr>,
+	tx: mpsc::Sender<T>,
+	/// Number of updates dropped since the client was connected
+	dropped: u64,
+	/// Number of updates dropped since the last delivered one
+	dropped_in_row: usize,
+}
+
+struct Clients<T> {
+	next_id: u64,
+	clients: BTreeMap<u64, Client<T>>,
+}
+
+/// Connected Web-Socket clients and their queues
+pub(crate) struct WebSocketClients<T> {
+	/// Maximum number of updates queued per client
+	queue_size: usize,
+	/// Number of updates dropped in a row after that a client is evicted
+	max_dropped_in_row: usize,
+	clients: Arc<Mutex<Clients<T>>>,
+}
+
+impl<T> Clone for WebSocketClients<T> {
+	fn clone(&self) -> Self {
+		Self { queue_size: self.queue_size, max_dropped_in_row: self.max_dropped_in_row, clients: self.clients.clone() }
+	}
+}
+
+impl<T: Clone> WebSocketClients<T> {
+	pub(crate) fn new(queue_size: usize, max_dropped_in_row: usize) -> Self {
+		Self {
+			queue_size: queue_size.max(1),
+			max_dropped_in_row: max_dropped_in_row.max(1),
+			clients: Arc::new(Mutex::new(Clients { next_id: 0, clients: BTreeMap::new() })),
+		}
+	}
+
+	/// Adds a client, returns its id and the queue to send updates to it from
+	pub(crate) fn register(&self, remote: Option<SocketAddr>) -> (u64, mpsc::Receiver<T>) {
+		let (tx, rx) = mpsc::channel(self.queue_size);
+		let mut clients = self.clients.lock().expect("websocket clients lock is poisoned");
+		let id = clients.next_id;
+		clients.next_id += 1;
+		clients.clients.insert(id, Client { remote, tx, dropped: 0, dropped_in_row: 0 });
+
+		(id, rx)
+	}
+
+	/// Removes a disconnected client
+	pub(crate) fn unregister(&self, id: u64) {
+		self.clients
+			.lock()
+			.expect("websocket clients lock is poisoned")
+			.clients
+			.remove(&id);
+	}
+
+	/// Queues an update for every client without waiting for any of them, returns the evicted clients.
+	/// The queue of an evicted client is closed, so its connection is closed once the queued updates are sent.
+	pub(crate) fn broadcast(&self, update: &T) -> Vec<WebSocketClientStats> {
+		let mut clients = self.clients.lock().expect("websocket clients lock is poisoned");
+		let mut evicted = vec![];
+		clients.clients.retain(|_, client| match client.tx.try_send(update.clone()) {
+			Ok(()) => {
+				client.dropped_in_row = 0;
+				true
+			},
+			Err(TrySendError::Full(_)) => {
+				client.dropped += 1;
+				client.dropped_in_row += 1;
+				if client.dropped_in_row < self.max_dropped_in_row {
+					return true
+				}
+				evicted.push(client.stats(self.queue_size));
+				false
+			},
+			Err(TrySendError::Closed(_)) => false,
+		});
+
+		evicted
+	}
+
+	/// Returns delivery statistics of the connected clients
+	pub(crate) fn stats(&self) -> Vec<WebSocketClientStats> {
+		self.clients
+			.lock()
+			.expect("websocket clients lock is poisoned")
+			.clients
+			.values()
+			.map(|client| client.stats(self.queue_size))
+			.collect()
+	}
+}
+
+impl<T> Client<T> {
+	fn stats(&self, queue_size: usize) -> WebSocketClientStats {
+		WebSocketClientStats { remote: self.remote, queued: queue_size - self.tx.capacity(), dropped: self.dropped }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_drops_updates_and_evicts_slow_clients() {
+		let clients = WebSocketClients::new(2, 3);
+		let (_, mut fast) = clients.register(None);
+		let (_, _slow) = clients.register(None);
+
+		for update in 0..4 {
+			assert!(clients.broadcast(&update).is_empty());
+			assert_eq!(fast.recv().await, Some(update));
+		}
+		let stats = clients.stats();
+		assert_eq!(stats[0], WebSocketClientStats { remote: None, queued: 0, dropped: 0 });
+		assert_eq!(stats[1], WebSocketClientStats { remote: None, queued: 2, dropped: 2 });
+
+		// The third update dropped in a row evicts the slow client
+		let evicted = clients.broadcast(&4);
+		assert_eq!(evicted, vec![WebSocketClientStats { remote: None, queued: 2, dropped: 3 }]);
+		assert_eq!(clients.stats().len(), 1);
+	}
+
+	#[tokio::test]
+	async fn test_removes_disconnected_clients() {
+		let clients = WebSocketClients::new(2, 3);
+		let (id, _rx) = clients.register(None);
+		let (_, rx) = clients.register(None);
+		drop(rx);
+
+		clients.broadcast(&0);
+		assert_eq!(clients.stats().len(), 1);
+		clients.unregister(id);
+		assert!(clients.stats().is_empty());
+	}
+}
@@ -17,3 +17,5 @@
 
 pub const MAX_MSG_QUEUE_SIZE: usize = 1024;
 pub const STANDARD_BLOCK_TIME: f64 = 6.0;
+/// Default value of `minimum_backing_votes`, used when the host configuration doesn't have it.
+pub const MINIMUM_BACKING_VOTES: u32 = 2;
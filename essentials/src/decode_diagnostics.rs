@@ -0,0 +1,191 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Diagnostics of the chain data that cannot be decoded.
+//!
+//! Decoding usually starts failing after a runtime upgrade changes a type the vendored metadata doesn't know.
+//! Instead of an identical error for every block, the first failure of a type in a runtime version is logged
+//! once and written with its context to the diagnostic file: the block, the spec version, the type path and
+//! the raw bytes. The data is skipped and the processing goes on, the repeated failures are only counted.
+
+use crate::types::{BlockNumber, H256};
+use log::{debug, error};
+use serde::Serialize;
+use std::{
+	collections::BTreeSet,
+	fmt::{self, Display, Formatter},
+	fs::OpenOptions,
+	io::Write,
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+};
+
+/// Reports the decode failures of the collector
+#[derive(Debug, Default)]
+pub struct DecodeDiagnostics {
+	/// Types that failed to decode by the spec version
+	seen: BTreeSet<(Option<u32>, String)>,
+	counter: DecodeFailuresCounter,
+}
+
+/// Counts the decode failures, shared with the consumers of the collector
+#[derive(Clone, Debug, Default)]
+pub struct DecodeFailuresCounter {
+	/// File the first failures are written to, as JSON lines
+	path: Option<PathBuf>,
+	/// Number of all failures
+	failures: Arc<AtomicU64>,
+	/// Number of distinct types by the spec version that failed to decode
+	distinct: Arc<AtomicU64>,
+}
+
+/// Data that cannot be decoded with its context
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DecodeFailure {
+	/// Relay chain block the data belongs to
+	pub block_hash: H256,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub block_number: Option<BlockNumber>,
+	/// Runtime spec version of the block, if known
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub spec_version: Option<u32>,
+	/// Path of the type that failed to decode, e.g. `ParaInherent.enter`
+	pub type_path: String,
+	/// The decoding error
+	pub error: String,
+	/// Raw bytes of the data
+	#[serde(serialize_with = "serialize_hex")]
+	pub bytes: Vec<u8>,
+}
+
+fn serialize_hex<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+}
+
+/// Counters of the decode failures since the start of the run
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DecodeFailures {
+	/// Number of all failures
+	pub failures: u64,
+	/// Number of distinct types by the spec version that failed to decode
+	pub distinct: u64,
+	/// The diagnostic file, if set
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub path: Option<PathBuf>,
+}
+
+impl DecodeDiagnostics {
+	/// Writes the first failures of every type to the file if given, they are only logged without it
+	pub fn new(path: Option<PathBuf>) -> Self {
+		Self { seen: Default::default(), counter: DecodeFailuresCounter { path, ..Default::default() } }
+	}
+
+	/// Returns the counter of the failures
+	pub fn counter(&self) -> DecodeFailuresCounter {
+		self.counter.clone()
+	}
+
+	/// Reports data that cannot be decoded, returns whether it's the first failure of its type in the runtime version
+	pub fn report(&mut self, failure: DecodeFailure) -> bool {
+		self.counter.failures.fetch_add(1, Ordering::Relaxed);
+		if !self.seen.insert((failure.spec_version, failure.type_path.clone())) {
+			debug!("Cannot decode {} at block {:?}: {}", failure.type_path, failure.block_hash, failure.error);
+			return false
+		}
+		self.counter.distinct.fetch_add(1, Ordering::Relaxed);
+
+		error!(
+			"Cannot decode {} at block {}{:?} (spec version {}): {}. The runtime is likely newer than this build \
+			 supports, the data is skipped in this and the following blocks{}",
+			failure.type_path,
+			failure.block_number.map(|v| format!("#{} ", v)).unwrap_or_default(),
+			failure.block_hash,
+			failure.spec_version.map_or("unknown".to_string(), |v| v.to_string()),
+			failure.error,
+			self.counter
+				.path
+				.as_ref()
+				.map(|path| format!(", see {} for the raw bytes", path.display()))
+				.unwrap_or_default()
+		);
+		if let Some(ref path) = self.counter.path {
+			let line = serde_json::to_string(&failure).expect("failures are serializable; qed");
+			if let Err(e) = OpenOptions::new()
+				.create(true)
+				.append(true)
+				.open(path)
+				.and_then(|mut file| writeln!(file, "{}", line))
+			{
+				error!("Cannot write the decode diagnostics to {}: {:?}", path.display(), e);
+			}
+		}
+
+		true
+	}
+}
+
+impl DecodeFailuresCounter {
+	/// Returns the counters of the run so far
+	pub fn snapshot(&self) -> DecodeFailures {
+		DecodeFailures {
+			failures: self.failures.load(Ordering::Relaxed),
+			distinct: self.distinct.load(Ordering::Relaxed),
+			path: self.path.clone(),
+		}
+	}
+}
+
+impl Display for DecodeFailures {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "--- Decode failures ---")?;
+		writeln!(f, "Failures: {}, distinct types by spec version: {}", self.failures, self.distinct)?;
+		match self.path {
+			Some(ref path) => writeln!(f, "Diagnostics: {}", path.display()),
+			None => writeln!(f, "Run with `--decode-diagnostics <FILE>` to save the raw bytes"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_reports_first_failure_of_type() {
+		let path = std::env::temp_dir().join(format!("decode-diagnostics-{}.jsonl", rand::random::<u64>()));
+		let mut diagnostics = DecodeDiagnostics::new(Some(path.clone()));
+		let failure = DecodeFailure {
+			block_hash: H256::zero(),
+			block_number: Some(42),
+			spec_version: Some(1_002_000),
+			type_path: "ParaInherent.enter".to_string(),
+			error: "Not enough data to fill buffer".to_string(),
+			bytes: vec![0xde, 0xad],
+		};
+
+		assert!(diagnostics.report(failure.clone()));
+		assert!(!diagnostics.report(DecodeFailure { block_number: Some(43), ..failure.clone() }));
+		assert!(diagnostics.report(DecodeFailure { spec_version: Some(1_003_000), ..failure }));
+
+		let content = std::fs::read_to_string(&path).unwrap();
+		let _ = std::fs::remove_file(&path);
+		assert_eq!(content.lines().count(), 2);
+		assert!(content.contains(r#""bytes":"0xdead""#));
+		assert_eq!(diagnostics.counter().snapshot(), DecodeFailures { failures: 3, distinct: 2, path: Some(path) });
+	}
+}
@@ -0,0 +1,121 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Cost of the chain events decoding
+//!
+//! The collector decodes only the kinds of chain events it's configured with, the events of the other kinds are
+//! passed on undecoded without decoding their fields. The decoded and skipped events are counted, so the time saved
+//! by skipping can be estimated from the average decoding time.
+
+use serde::Serialize;
+use std::{
+	fmt::{self, Display, Formatter},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+/// Counts the decoded and skipped chain events, shared with the consumers of the collector
+#[derive(Clone, Debug, Default)]
+pub struct EventDecodingCounter {
+	decoded: Arc<AtomicU64>,
+	skipped: Arc<AtomicU64>,
+	decode_nanos: Arc<AtomicU64>,
+}
+
+/// Counters of the chain events decoding since the start of the run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct EventDecodingStats {
+	/// Number of decoded events
+	pub decoded: u64,
+	/// Number of events of the kinds not configured for decoding
+	pub skipped: u64,
+	/// Time spent decoding events
+	pub decode_time: Duration,
+}
+
+impl EventDecodingStats {
+	/// Time the skipped events would have taken to decode at the average decoding time, zero without decoded events
+	pub fn saved_time(&self) -> Duration {
+		if self.decoded == 0 {
+			Duration::ZERO
+		} else {
+			Duration::from_nanos((self.decode_time.as_nanos() * self.skipped as u128 / self.decoded as u128) as u64)
+		}
+	}
+}
+
+impl EventDecodingCounter {
+	/// Counts a decoded event
+	pub fn on_decoded(&self, decode_time: Duration) {
+		self.decoded.fetch_add(1, Ordering::Relaxed);
+		self.decode_nanos.fetch_add(decode_time.as_nanos() as u64, Ordering::Relaxed);
+	}
+
+	/// Counts an event passed on undecoded
+	pub fn on_skipped(&self) {
+		self.skipped.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Returns the counters of the run so far
+	pub fn snapshot(&self) -> EventDecodingStats {
+		EventDecodingStats {
+			decoded: self.decoded.load(Ordering::Relaxed),
+			skipped: self.skipped.load(Ordering::Relaxed),
+			decode_time: Duration::from_nanos(self.decode_nanos.load(Ordering::Relaxed)),
+		}
+	}
+}
+
+impl Display for EventDecodingStats {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "--- Event decoding ---")?;
+		writeln!(
+			f,
+			"Decoded: {} in {} ms, skipped: {}, saving about {} ms",
+			self.decoded,
+			self.decode_time.as_millis(),
+			self.skipped,
+			self.saved_time().as_millis()
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_saved_time() {
+		assert_eq!(EventDecodingStats::default().saved_time(), Duration::ZERO);
+		let stats = EventDecodingStats { decoded: 100, skipped: 300, decode_time: Duration::from_millis(20) };
+		assert_eq!(stats.saved_time(), Duration::from_millis(60));
+	}
+
+	#[test]
+	fn test_counter_is_shared() {
+		let counter = EventDecodingCounter::default();
+		let shared = counter.clone();
+		shared.on_decoded(Duration::from_millis(2));
+		shared.on_skipped();
+		shared.on_skipped();
+		assert_eq!(
+			counter.snapshot(),
+			EventDecodingStats { decoded: 1, skipped: 2, decode_time: Duration::from_millis(2) }
+		);
+	}
+}
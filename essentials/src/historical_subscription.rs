@@ -18,7 +18,7 @@
 use tokio::sync::broadcast::error::TryRecvError;
 
 use crate::{
-	api::subxt_wrapper::RequestExecutor,
+	api::subxt_wrapper::{ExecutorContext, RequestExecutor},
 	chain_subscription::ChainSubscriptionEvent,
 	constants::MAX_MSG_QUEUE_SIZE,
 	consumer::{EventConsumerInit, EventStream},
@@ -40,6 +40,7 @@ pub struct HistoricalSubscription {
 	/// One sender per consumer per URL.
 	consumers: Vec<Vec<Sender<ChainSubscriptionEvent>>>,
 	retry: RetryOptions,
+	context: ExecutorContext,
 }
 
 #[async_trait]
@@ -72,6 +73,7 @@ impl EventStream for HistoricalSubscription {
 				self.to_block_number,
 				shutdown_tx.clone(),
 				self.retry.clone(),
+				self.context.clone(),
 			)
 		});
 
@@ -86,7 +88,20 @@ impl HistoricalSubscription {
 		to_block_number: BlockNumber,
 		retry: RetryOptions,
 	) -> HistoricalSubscription {
-		HistoricalSubscription { urls, from_block_number, to_block_number, consumers: Vec::new(), retry }
+		HistoricalSubscription {
+			urls,
+			from_block_number,
+			to_block_number,
+			consumers: Vec::new(),
+			retry,
+			context: Default::default(),
+		}
+	}
+
+	/// Builds the executors of the subscription tasks with the given context
+	pub fn with_executor_context(mut self, context: ExecutorContext) -> Self {
+		self.context = context;
+		self
 	}
 
 	// Per node
@@ -97,9 +112,10 @@ impl HistoricalSubscription {
 		to_block_number: BlockNumber,
 		shutdown_tx: BroadcastSender<()>,
 		retry: RetryOptions,
+		context: ExecutorContext,
 	) {
 		let mut shutdown_rx = shutdown_tx.subscribe();
-		let mut executor = RequestExecutor::new(retry);
+		let mut executor = RequestExecutor::new(retry, context);
 		const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(1000);
 		let mut heartbeat_periodic = interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
 
@@ -195,6 +211,7 @@ impl HistoricalSubscription {
 		to_block_number: BlockNumber,
 		shutdown_tx: BroadcastSender<()>,
 		retry: RetryOptions,
+		context: ExecutorContext,
 	) -> Vec<tokio::task::JoinHandle<()>> {
 		update_channels
 			.into_iter()
@@ -207,6 +224,7 @@ impl HistoricalSubscription {
 					to_block_number,
 					shutdown_tx.clone(),
 					retry.clone(),
+					context.clone(),
 				))
 			})
 			.collect()
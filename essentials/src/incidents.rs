@@ -0,0 +1,394 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A single chronological timeline of anomalies from all sources
+//!
+//! Skipped slots, disputes, finality stalls, stale telemetry nodes and RPC disconnects are recorded by the parts of
+//! a tool that observe them, each holding a clone of the same [`Incidents`] handle. The default handle records
+//! nothing, so the sources don't need to know whether a timeline is requested. The timeline can be printed or
+//! exported as Markdown for postmortems.
+
+use crate::types::{BlockNumber, Timestamp};
+use clap::ValueEnum;
+use std::{
+	collections::VecDeque,
+	fmt::{self, Display, Formatter},
+	sync::{Arc, Mutex},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The oldest incidents are dropped after this number is recorded
+pub const MAX_INCIDENTS: usize = 10_000;
+
+/// How bad an incident is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum Severity {
+	Info,
+	Warning,
+	Critical,
+}
+
+impl Display for Severity {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Severity::Info => "info",
+			Severity::Warning => "warning",
+			Severity::Critical => "critical",
+		})
+	}
+}
+
+/// Where an incident was observed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IncidentSource {
+	SkippedSlot,
+	Dispute,
+	FinalityStall,
+	StaleNode,
+	TelemetryDisconnect,
+	RpcDisconnect,
+	/// Found by an anomaly detector
+	Anomaly,
+	/// Reported by a user script
+	Hook,
+}
+
+impl Display for IncidentSource {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		f.write_str(match self {
+			IncidentSource::SkippedSlot => "skipped slot",
+			IncidentSource::Dispute => "dispute",
+			IncidentSource::FinalityStall => "finality stall",
+			IncidentSource::StaleNode => "stale node",
+			IncidentSource::TelemetryDisconnect => "telemetry disconnect",
+			IncidentSource::RpcDisconnect => "RPC disconnect",
+			IncidentSource::Anomaly => "anomaly",
+			IncidentSource::Hook => "hook",
+		})
+	}
+}
+
+/// An anomaly on the timeline
+#[derive(Clone, Debug, PartialEq)]
+pub struct Incident {
+	/// Time in milliseconds, the block timestamp for on-chain incidents and the wall clock for the others
+	pub timestamp: Timestamp,
+	pub severity: Severity,
+	pub source: IncidentSource,
+	/// Relay chain block the incident was observed in
+	pub block_number: Option<BlockNumber>,
+	pub description: String,
+}
+
+impl Incident {
+	pub fn new(
+		timestamp: Timestamp,
+		severity: Severity,
+		source: IncidentSource,
+		block_number: Option<BlockNumber>,
+		description: impl Into<String>,
+	) -> Self {
+		Self { timestamp, severity, source, block_number, description: description.into() }
+	}
+
+	/// An incident happening now, not related to a block
+	pub fn now(severity: Severity, source: IncidentSource, description: impl Into<String>) -> Self {
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |v| v.as_millis() as Timestamp);
+		Self::new(timestamp, severity, source, None, description)
+	}
+}
+
+#[derive(Default)]
+struct IncidentLog {
+	incidents: VecDeque<Incident>,
+	dropped: usize,
+}
+
+impl IncidentLog {
+	fn record(&mut self, incident: Incident) {
+		if self.incidents.len() >= MAX_INCIDENTS {
+			self.incidents.pop_front();
+			self.dropped += 1;
+		}
+		self.incidents.push_back(incident);
+	}
+}
+
+/// Handle of the incident log shared by the sources, the default one is disabled and records nothing
+#[derive(Clone, Default)]
+pub struct Incidents(Option<Arc<Mutex<IncidentLog>>>);
+
+impl Incidents {
+	/// A new log recording incidents
+	pub fn enabled() -> Self {
+		Self(Some(Default::default()))
+	}
+
+	/// Records an incident, does nothing if recording is not enabled
+	pub fn record(&self, incident: Incident) {
+		if let Some(log) = &self.0 {
+			log.lock().expect("incidents lock is poisoned").record(incident);
+		}
+	}
+
+	/// Returns the incidents recorded so far
+	pub fn timeline(&self) -> Timeline {
+		match &self.0 {
+			Some(log) => {
+				let log = log.lock().expect("incidents lock is poisoned");
+				Timeline::new(log.incidents.iter().cloned().collect(), log.dropped)
+			},
+			None => Timeline::default(),
+		}
+	}
+}
+
+/// Incidents in chronological order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timeline {
+	pub incidents: Vec<Incident>,
+	/// Number of the oldest incidents dropped from the timeline
+	pub dropped: usize,
+}
+
+impl Timeline {
+	pub fn new(mut incidents: Vec<Incident>, dropped: usize) -> Self {
+		// Sources record with some delay, the order of incidents with the same time is kept
+		incidents.sort_by_key(|incident| incident.timestamp);
+		Self { incidents, dropped }
+	}
+
+	/// Returns the incidents of at least the given severity
+	pub fn filter(&self, min_severity: Severity) -> Self {
+		let incidents = self
+			.incidents
+			.iter()
+			.filter(|incident| incident.severity >= min_severity)
+			.cloned()
+			.collect();
+		Self { incidents, dropped: self.dropped }
+	}
+
+	fn count(&self, severity: Severity) -> usize {
+		self.incidents.iter().filter(|incident| incident.severity == severity).count()
+	}
+
+	/// Renders the timeline as a Markdown document for postmortems
+	pub fn to_markdown(&self) -> String {
+		let mut markdown = String::from("# Incidents\n\n");
+		match (self.incidents.first(), self.incidents.last()) {
+			(Some(first), Some(last)) => markdown.push_str(&format!(
+				"{} incidents from {} to {}: {} critical, {} warnings, {} info.\n",
+				self.incidents.len(),
+				format_time(first.timestamp),
+				format_time(last.timestamp),
+				self.count(Severity::Critical),
+				self.count(Severity::Warning),
+				self.count(Severity::Info)
+			)),
+			_ => markdown.push_str("No incidents recorded.\n"),
+		}
+		if self.dropped > 0 {
+			markdown.push_str(&format!("\n{} earlier incidents are not included.\n", self.dropped));
+		}
+		if self.incidents.is_empty() {
+			return markdown
+		}
+
+		markdown.push_str("\n| Time | Severity | Source | Block | Description |\n");
+		markdown.push_str("|------|----------|--------|-------|-------------|\n");
+		for incident in &self.incidents {
+			markdown.push_str(&format!(
+				"| {} | {} | {} | {} | {} |\n",
+				format_time(incident.timestamp),
+				incident.severity,
+				incident.source,
+				incident.block_number.map_or(String::new(), |v| format!("#{}", v)),
+				escape_markdown_cell(&incident.description)
+			));
+		}
+		markdown
+	}
+}
+
+impl Display for Timeline {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "--- Incidents ---")?;
+		if self.dropped > 0 {
+			writeln!(f, "{} earlier incidents dropped", self.dropped)?;
+		}
+		if self.incidents.is_empty() {
+			return writeln!(f, "No incidents recorded")
+		}
+		for incident in &self.incidents {
+			write!(f, "{} [{:<8}] {}", format_time(incident.timestamp), incident.severity, incident.source)?;
+			if let Some(block_number) = incident.block_number {
+				write!(f, " at #{}", block_number)?;
+			}
+			writeln!(f, ": {}", incident.description)?;
+		}
+		Ok(())
+	}
+}
+
+fn format_time(timestamp: Timestamp) -> String {
+	time::OffsetDateTime::from_unix_timestamp_nanos(timestamp as i128 * 1_000_000)
+		.ok()
+		.and_then(|dt| dt.format(&time::format_description::well_known::Rfc3339).ok())
+		.unwrap_or_else(|| timestamp.to_string())
+}
+
+fn escape_markdown_cell(text: &str) -> String {
+	text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Turns the finality lag of relay chain blocks into incidents when finality stalls and recovers
+#[derive(Debug)]
+pub struct FinalityStallDetector {
+	/// Finality lag in blocks at which finality is considered stalled
+	threshold: u32,
+	/// Block and time the current stall was detected at
+	stalled_since: Option<(BlockNumber, Timestamp)>,
+	/// The largest finality lag of the current stall
+	max_lag: u32,
+}
+
+impl FinalityStallDetector {
+	pub fn new(threshold: u32) -> Self {
+		Self { threshold: threshold.max(1), stalled_since: None, max_lag: 0 }
+	}
+
+	/// Returns an incident when finality stalls or recovers
+	pub fn on_block(&mut self, block_number: BlockNumber, timestamp: Timestamp, finality_lag: u32) -> Option<Incident> {
+		match self.stalled_since {
+			None if finality_lag >= self.threshold => {
+				self.stalled_since = Some((block_number, timestamp));
+				self.max_lag = finality_lag;
+				Some(Incident::new(
+					timestamp,
+					Severity::Critical,
+					IncidentSource::FinalityStall,
+					Some(block_number),
+					format!("Finality is lagging {} blocks behind the best block", finality_lag),
+				))
+			},
+			Some((since_block, since_timestamp)) if finality_lag < self.threshold => {
+				self.stalled_since = None;
+				Some(Incident::new(
+					timestamp,
+					Severity::Info,
+					IncidentSource::FinalityStall,
+					Some(block_number),
+					format!(
+						"Finality recovered after {}s since #{}, the largest lag was {} blocks",
+						timestamp.saturating_sub(since_timestamp) / 1000,
+						since_block,
+						self.max_lag
+					),
+				))
+			},
+			Some(_) => {
+				self.max_lag = self.max_lag.max(finality_lag);
+				None
+			},
+			None => None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn incident(timestamp: Timestamp, severity: Severity, description: &str) -> Incident {
+		Incident::new(timestamp, severity, IncidentSource::RpcDisconnect, None, description)
+	}
+
+	#[test]
+	fn test_orders_incidents_chronologically() {
+		let timeline = Timeline::new(
+			vec![
+				incident(2000, Severity::Info, "second"),
+				incident(1000, Severity::Critical, "first"),
+				incident(2000, Severity::Warning, "third"),
+			],
+			0,
+		);
+		let descriptions: Vec<_> = timeline.incidents.iter().map(|v| v.description.as_str()).collect();
+		assert_eq!(descriptions, vec!["first", "second", "third"]);
+
+		let filtered = timeline.filter(Severity::Warning);
+		assert_eq!(filtered.incidents.len(), 2);
+	}
+
+	#[test]
+	fn test_renders_markdown() {
+		let mut timeline = Timeline::new(
+			vec![Incident::new(
+				1_700_000_000_000,
+				Severity::Critical,
+				IncidentSource::Dispute,
+				Some(42),
+				"Dispute | concluded invalid",
+			)],
+			0,
+		);
+		assert_eq!(
+			timeline.to_markdown(),
+			"# Incidents\n\n\
+			1 incidents from 2023-11-14T22:13:20Z to 2023-11-14T22:13:20Z: 1 critical, 0 warnings, 0 info.\n\n\
+			| Time | Severity | Source | Block | Description |\n\
+			|------|----------|--------|-------|-------------|\n\
+			| 2023-11-14T22:13:20Z | critical | dispute | #42 | Dispute \\| concluded invalid |\n"
+		);
+
+		timeline.incidents.clear();
+		assert_eq!(timeline.to_markdown(), "# Incidents\n\nNo incidents recorded.\n");
+	}
+
+	#[test]
+	fn test_records_only_if_enabled() {
+		let disabled = Incidents::default();
+		disabled.record(incident(1000, Severity::Info, "ignored"));
+		assert!(disabled.timeline().incidents.is_empty());
+
+		let incidents = Incidents::enabled();
+		let source = incidents.clone();
+		for timestamp in 0..MAX_INCIDENTS as Timestamp + 2 {
+			source.record(incident(timestamp, Severity::Info, "recorded"));
+		}
+		let timeline = incidents.timeline();
+		assert_eq!((timeline.incidents.len(), timeline.dropped), (MAX_INCIDENTS, 2));
+		assert_eq!(timeline.incidents[0].timestamp, 2);
+	}
+
+	#[test]
+	fn test_detects_finality_stalls() {
+		let mut detector = FinalityStallDetector::new(10);
+		assert!(detector.on_block(100, 0, 2).is_none());
+
+		let stall = detector.on_block(101, 6000, 10).unwrap();
+		assert_eq!((stall.severity, stall.block_number), (Severity::Critical, Some(101)));
+		assert!(detector.on_block(102, 12000, 15).is_none());
+
+		let recovery = detector.on_block(103, 18000, 3).unwrap();
+		assert_eq!(recovery.severity, Severity::Info);
+		assert_eq!(recovery.description, "Finality recovered after 12s since #101, the largest lag was 15 blocks");
+		assert!(detector.on_block(104, 24000, 2).is_none());
+	}
+}
@@ -1,6 +1,8 @@
 use clap::{ArgAction, Args};
+use env_logger::Target;
 use futures::future;
 use log::LevelFilter;
+use std::io::Write;
 use tokio::{signal, sync::broadcast};
 
 #[derive(Clone, Debug, Args)]
@@ -11,6 +13,16 @@ pub struct VerbosityOptions {
 }
 
 pub fn init_cli(opts: &VerbosityOptions) -> color_eyre::Result<()> {
+	init_logger(opts, Target::Stderr)
+}
+
+/// Initializes the CLI writing the log to the given writer instead of stderr, e.g. while a terminal interface takes
+/// the screen
+pub fn init_cli_with_log_writer(opts: &VerbosityOptions, writer: Box<dyn Write + Send>) -> color_eyre::Result<()> {
+	init_logger(opts, Target::Pipe(writer))
+}
+
+fn init_logger(opts: &VerbosityOptions, target: Target) -> color_eyre::Result<()> {
 	color_eyre::install()?;
 	let log_level = match opts.verbose {
 		0 => LevelFilter::Warn,
@@ -21,6 +33,7 @@ pub fn init_cli(opts: &VerbosityOptions) -> color_eyre::Result<()> {
 	env_logger::Builder::from_default_env()
 		.filter(None, log_level)
 		.format_timestamp(Some(env_logger::fmt::TimestampPrecision::Micros))
+		.target(target)
 		.try_init()?;
 
 	Ok(())
@@ -31,6 +44,29 @@ pub fn init_shutdown() -> broadcast::Sender<()> {
 	shutdown_tx
 }
 
+/// Creates a channel notified every time the process receives `SIGUSR1`,
+/// used to print the current summaries of a long run without shutting it down
+pub fn init_summary_signal() -> broadcast::Sender<()> {
+	let (summary_tx, _) = broadcast::channel(1);
+	#[cfg(unix)]
+	tokio::spawn(on_summary_signal(summary_tx.clone()));
+	summary_tx
+}
+
+#[cfg(unix)]
+async fn on_summary_signal(summary_tx: broadcast::Sender<()>) {
+	let mut summary_signal = match signal::unix::signal(signal::unix::SignalKind::user_defined1()) {
+		Ok(v) => v,
+		Err(e) => {
+			log::warn!("cannot listen for SIGUSR1, on-demand summaries are disabled: {:?}", e);
+			return
+		},
+	};
+	while summary_signal.recv().await.is_some() {
+		let _ = summary_tx.send(());
+	}
+}
+
 pub async fn on_shutdown(shutdown_tx: broadcast::Sender<()>) {
 	signal::ctrl_c().await.unwrap();
 	let _ = shutdown_tx.send(());
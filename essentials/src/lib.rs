@@ -15,18 +15,36 @@
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+pub mod anomaly;
 pub mod api;
 pub mod chain_events;
 pub mod chain_head_subscription;
 pub mod chain_subscription;
+pub mod chaos;
+#[cfg(feature = "charts")]
+pub mod charts;
 pub mod collector;
 pub mod constants;
 pub mod consumer;
+pub mod decode_diagnostics;
+pub mod event_decoding;
 pub mod historical_subscription;
+pub mod incidents;
 pub mod init;
 pub mod metadata;
+pub mod metrics;
+pub mod observation;
+pub mod output;
+#[cfg(feature = "persistent-storage")]
+pub mod persistent_storage;
+pub mod rpc_budget;
+pub mod runtime_support;
+pub mod sequence;
 pub mod storage;
+pub mod telemetry_churn;
 pub mod telemetry_feed;
+pub mod telemetry_health;
+pub mod telemetry_hwbench;
 pub mod telemetry_subscription;
 pub mod types;
 pub mod utils;
@@ -14,8 +14,82 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 //
+//! Runtime metadata vendored with this build.
+//!
+//! Static types are generated at build time from the Polkadot metadata checked in under `assets`. Each vendored file is
+//! registered in [`VENDORED_METADATA`] together with the spec version it was fetched at, and a connection uses the
+//! static types only if its runtime is exactly that version. Other runtimes, e.g. a network that upgraded ahead of the
+//! others, are decoded dynamically against the metadata of the connected node. The registry is generated by
+//! `scripts/update_metadata.sh` together with the files.
+
+use crate::runtime_support::RuntimeVersion;
+use std::fmt::{Display, Formatter};
 
 #[subxt::subxt(runtime_metadata_path = "assets/polkadot_metadata.scale")]
 pub mod polkadot {}
 
 pub use polkadot::runtime_types::polkadot_primitives::v4 as polkadot_primitives;
+
+/// Metadata checked in under `assets`
+#[derive(Clone, Copy, Debug)]
+pub struct VendoredMetadata {
+	/// Runtime spec name the metadata was fetched from
+	pub spec_name: &'static str,
+	/// Spec version the metadata was fetched at
+	pub spec_version: u32,
+	/// Path of the metadata relative to the crate root
+	pub path: &'static str,
+}
+
+impl VendoredMetadata {
+	fn covers(&self, version: &RuntimeVersion) -> bool {
+		self.spec_name == version.spec_name && self.spec_version == version.spec_version
+	}
+}
+
+/// Vendored metadata of this build
+pub const VENDORED_METADATA: &[VendoredMetadata] = &include!("../assets/vendored_metadata.rs");
+
+/// How storage of a runtime is decoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decoding {
+	/// With the types generated from the vendored metadata
+	Static,
+	/// With the metadata of the connected node
+	Dynamic,
+}
+
+impl Display for Decoding {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Decoding::Static => write!(f, "static"),
+			Decoding::Dynamic => write!(f, "dynamic"),
+		}
+	}
+}
+
+/// Selects the decoding of a runtime, static types are used only for runtimes covered by the vendored metadata
+pub fn decoding_for(version: &RuntimeVersion) -> Decoding {
+	if VENDORED_METADATA.iter().any(|metadata| metadata.covers(version)) {
+		Decoding::Static
+	} else {
+		Decoding::Dynamic
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(spec_name: &str, spec_version: u32) -> RuntimeVersion {
+		RuntimeVersion { spec_name: spec_name.to_string(), spec_version }
+	}
+
+	#[test]
+	fn test_selects_decoding() {
+		assert_eq!(decoding_for(&version("polkadot", 1_000_001)), Decoding::Static);
+		assert_eq!(decoding_for(&version("polkadot", 1_001_000)), Decoding::Dynamic);
+		assert_eq!(decoding_for(&version("polkadot", 1_000_000)), Decoding::Dynamic);
+		assert_eq!(decoding_for(&version("westend", 1_000_001)), Decoding::Dynamic);
+	}
+}
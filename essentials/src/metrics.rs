@@ -0,0 +1,159 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Registration of Prometheus metrics keeping a catalog of them.
+//!
+//! The tools register their metrics in a [`MetricsRegistry`] with [`register`] instead of
+//! `prometheus_endpoint::register`, so their `list-metrics` commands can print the names, types, labels and help
+//! texts without connecting anywhere.
+
+use prometheus_endpoint::{
+	prometheus::{
+		core::{Atomic, Collector, GenericCounter, GenericCounterVec, GenericGauge, GenericGaugeVec},
+		Histogram, HistogramVec,
+	},
+	PrometheusError, Registry,
+};
+use std::sync::{Arc, Mutex};
+
+/// Type of a metric as it's exported
+pub trait MetricKind {
+	const KIND: &'static str;
+}
+
+impl<P: Atomic> MetricKind for GenericCounter<P> {
+	const KIND: &'static str = "counter";
+}
+
+impl<P: Atomic> MetricKind for GenericCounterVec<P> {
+	const KIND: &'static str = "counter";
+}
+
+impl<P: Atomic> MetricKind for GenericGauge<P> {
+	const KIND: &'static str = "gauge";
+}
+
+impl<P: Atomic> MetricKind for GenericGaugeVec<P> {
+	const KIND: &'static str = "gauge";
+}
+
+impl MetricKind for Histogram {
+	const KIND: &'static str = "histogram";
+}
+
+impl MetricKind for HistogramVec {
+	const KIND: &'static str = "histogram";
+}
+
+/// A registered metric
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricDescription {
+	/// Name without the prefix of the registry
+	pub name: String,
+	pub kind: &'static str,
+	pub labels: Vec<String>,
+	pub help: String,
+}
+
+/// Prometheus registry keeping the catalog of the metrics registered in it, the clones share the catalog
+#[derive(Clone)]
+pub struct MetricsRegistry {
+	prefix: String,
+	registry: Registry,
+	catalog: Arc<Mutex<Vec<MetricDescription>>>,
+}
+
+impl MetricsRegistry {
+	/// Returns a registry prepending the prefix to the names of the metrics
+	pub fn new(prefix: &str) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			prefix: prefix.to_string(),
+			registry: Registry::new_custom(Some(prefix.to_string()), None)?,
+			catalog: Default::default(),
+		})
+	}
+
+	/// Returns the registry to serve
+	pub fn registry(&self) -> &Registry {
+		&self.registry
+	}
+
+	/// Returns the metrics registered so far, sorted by name
+	pub fn catalog(&self) -> Vec<MetricDescription> {
+		let mut catalog = self.catalog.lock().expect("metrics catalog lock is poisoned").clone();
+		catalog.sort_by(|a, b| a.name.cmp(&b.name));
+		catalog
+	}
+
+	/// Prints the metrics registered so far with the prefix, one per line with tab separated columns
+	pub fn print_catalog(&self) {
+		println!("NAME\tTYPE\tLABELS\tHELP");
+		for metric in self.catalog() {
+			println!("{}_{}\t{}\t{}\t{}", self.prefix, metric.name, metric.kind, metric.labels.join(","), metric.help);
+		}
+	}
+}
+
+/// Registers a metric and adds it to the catalog
+pub fn register<T: Clone + Collector + MetricKind + 'static>(
+	metric: T,
+	registry: &MetricsRegistry,
+) -> Result<T, PrometheusError> {
+	let metric = prometheus_endpoint::register(metric, &registry.registry)?;
+	let mut catalog = registry.catalog.lock().expect("metrics catalog lock is poisoned");
+	for desc in metric.desc() {
+		catalog.push(MetricDescription {
+			name: desc.fq_name.clone(),
+			kind: T::KIND,
+			labels: desc.variable_labels.clone(),
+			help: desc.help.clone(),
+		});
+	}
+
+	Ok(metric)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use prometheus_endpoint::prometheus::{IntCounter, IntGaugeVec, Opts};
+
+	#[test]
+	fn test_catalogs_registered_metrics() {
+		let registry = MetricsRegistry::new("test").unwrap();
+		register(IntGaugeVec::new(Opts::new("catalog_test_gauge", "A gauge"), &["para", "kind"]).unwrap(), &registry)
+			.unwrap();
+		register(IntCounter::new("catalog_test_counter", "A counter").unwrap(), &registry.clone()).unwrap();
+
+		assert_eq!(
+			registry.catalog(),
+			vec![
+				MetricDescription {
+					name: "catalog_test_counter".to_string(),
+					kind: "counter",
+					labels: vec![],
+					help: "A counter".to_string()
+				},
+				MetricDescription {
+					name: "catalog_test_gauge".to_string(),
+					kind: "gauge",
+					labels: vec!["para".to_string(), "kind".to_string()],
+					help: "A gauge".to_string()
+				},
+			]
+		);
+	}
+}
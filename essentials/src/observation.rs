@@ -0,0 +1,135 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Normalized per-block observations of the relay chain.
+//!
+//! Observations are built by the parachain tracer for every relay chain block and passed to its output sinks
+//! and anomaly detectors.
+
+use crate::{
+	metadata::polkadot_primitives::ValidatorIndex,
+	sequence::StreamPosition,
+	types::{AccountId32, BlockNumber, Timestamp, H256},
+};
+use parity_scale_codec::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use subxt::utils::bits::DecodedBits;
+
+/// Observation of a relay chain block
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BlockObservation {
+	/// Relay chain block number
+	pub block_number: BlockNumber,
+	/// Relay chain block hash
+	pub block_hash: H256,
+	/// Relay chain block timestamp
+	pub timestamp: Timestamp,
+	/// Runtime spec version active at the block
+	#[serde(default)]
+	pub spec_version: u32,
+	/// Number of signed bitfields in the block inherent
+	pub bitfields_count: u32,
+	/// Number of active validators, assigned to backing groups
+	pub validators_count: u32,
+	/// Availability cores state
+	pub cores: Vec<CoreObservation>,
+	/// Candidates backed in the block
+	pub candidates: Vec<CandidateObservation>,
+	/// Position in the observations stream, assigned before passing to the sinks
+	#[serde(flatten)]
+	pub position: StreamPosition,
+}
+
+/// Observation of an availability core at a relay chain block
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoreObservation {
+	/// Core index
+	pub core_index: u32,
+	/// If the core is occupied by a candidate pending availability
+	pub occupied: bool,
+	/// Parachains scheduled on the core
+	pub scheduled_paras: Vec<u32>,
+}
+
+/// Observation of a candidate backed at a relay chain block
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CandidateObservation {
+	/// Parachain id
+	pub para_id: u32,
+	/// Candidate hash
+	pub candidate_hash: H256,
+	/// Stated relay parent
+	pub relay_parent: H256,
+	/// Number of validity votes
+	pub validity_votes: u32,
+	/// Size of the backing group
+	pub group_size: u32,
+	/// Members of the backing group and their validity votes, empty if the group is unknown
+	#[serde(default)]
+	pub votes: Vec<ValidityVote>,
+}
+
+/// Validity vote of a backing group member for a candidate
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct ValidityVote {
+	/// Index of the validator in the session
+	pub validator_index: u32,
+	/// The validator's account, resolved from the session keys
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub address: Option<String>,
+	/// If the validator has cast a validity vote
+	pub voted: bool,
+}
+
+/// Returns the members of the backing group of a candidate and whether they have cast validity votes, according to
+/// the `validator_indices` bitfield of the backed candidate
+pub fn validity_votes(
+	validator_indices: &DecodedBits,
+	group: &[ValidatorIndex],
+	session_keys: Option<&Vec<AccountId32>>,
+) -> Vec<ValidityVote> {
+	let bits = validator_indices.as_bits();
+	group
+		.iter()
+		.enumerate()
+		.map(|(position, validator_index)| ValidityVote {
+			validator_index: validator_index.0,
+			address: session_keys
+				.and_then(|keys| keys.get(validator_index.0 as usize))
+				.map(|account| account.to_string()),
+			voted: bits.get(position).unwrap_or_default(),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_resolves_group_members() {
+		let group = [ValidatorIndex(1), ValidatorIndex(2)];
+		let keys = vec![AccountId32([0; 32]), AccountId32([1; 32])];
+
+		assert_eq!(
+			validity_votes(&DecodedBits::from_iter([true]), &group, Some(&keys)),
+			vec![
+				ValidityVote { validator_index: 1, address: Some(AccountId32([1; 32]).to_string()), voted: true },
+				ValidityVote { validator_index: 2, address: None, voted: false },
+			]
+		);
+	}
+}
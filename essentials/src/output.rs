@@ -0,0 +1,212 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Output of command results in the format selected with `--output`
+//!
+//! Results are records implementing `Display` for the pretty format and `Serialize` for the others. CSV columns are
+//! fixed by the record type, fields of nested objects are joined with dots, arrays are written as JSON.
+
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+	fmt::Display,
+	io::{self, Write},
+	sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A result printed in the format selected with `--output`
+pub trait OutputRecord: Display + Serialize {
+	/// Columns of the CSV header, the fields of nested objects are joined with dots
+	const CSV_COLUMNS: &'static [&'static str];
+}
+
+/// Format of the command results
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+	/// Human readable output
+	#[default]
+	Pretty,
+	/// A JSON object per line
+	Json,
+	/// Comma separated values with a header
+	Csv,
+	/// Records encoded with bincode
+	Bincode,
+}
+
+impl OutputFormat {
+	pub fn is_pretty(&self) -> bool {
+		matches!(self, OutputFormat::Pretty)
+	}
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct OutputOptions {
+	/// Format of the results printed to stdout
+	#[clap(long = "output", value_enum, default_value = "pretty")]
+	pub format: OutputFormat,
+}
+
+/// Formats records, writing the CSV header once
+#[derive(Debug, Default)]
+pub struct OutputWriter {
+	format: OutputFormat,
+	is_header_written: AtomicBool,
+}
+
+impl OutputWriter {
+	pub fn new(format: OutputFormat) -> Self {
+		Self { format, is_header_written: AtomicBool::new(false) }
+	}
+
+	pub fn format(&self) -> OutputFormat {
+		self.format
+	}
+
+	/// Returns a record in the output format, text formats end with a newline and the first CSV record is preceded
+	/// by a header
+	pub fn render<T: OutputRecord>(&self, record: &T) -> color_eyre::Result<Vec<u8>> {
+		Ok(match self.format {
+			OutputFormat::Pretty => format!("{}\n", record).into_bytes(),
+			OutputFormat::Json => format!("{}\n", serde_json::to_string(record)?).into_bytes(),
+			OutputFormat::Csv => self.render_csv(T::CSV_COLUMNS, &serde_json::to_value(record)?).into_bytes(),
+			OutputFormat::Bincode => bincode::serialize(record)?,
+		})
+	}
+
+	/// Prints a record to stdout
+	pub fn print<T: OutputRecord>(&self, record: &T) -> color_eyre::Result<()> {
+		io::stdout().lock().write_all(&self.render(record)?)?;
+		Ok(())
+	}
+
+	/// Arrays are written as a row per item
+	fn render_csv(&self, columns: &[&str], value: &Value) -> String {
+		let rows: Vec<Vec<(String, String)>> = match value {
+			Value::Array(items) => items.iter().map(csv_row).collect(),
+			value => vec![csv_row(value)],
+		};
+		let mut output = String::new();
+		if !self.is_header_written.swap(true, Ordering::Relaxed) {
+			let header: Vec<String> = columns.iter().map(|column| column.to_string()).collect();
+			output.push_str(&csv_line(&header));
+		}
+
+		for row in rows {
+			let values: Vec<String> = columns
+				.iter()
+				.map(|column| {
+					row.iter()
+						.find(|(name, _)| name == column)
+						.map(|(_, value)| value.clone())
+						.unwrap_or_default()
+				})
+				.collect();
+			output.push_str(&csv_line(&values));
+		}
+
+		output
+	}
+}
+
+fn csv_row(value: &Value) -> Vec<(String, String)> {
+	let mut row = vec![];
+	flatten("", value, &mut row);
+	row
+}
+
+fn flatten(prefix: &str, value: &Value, row: &mut Vec<(String, String)>) {
+	let name = if prefix.is_empty() { "value".to_string() } else { prefix.to_string() };
+	match value {
+		Value::Object(fields) =>
+			for (key, value) in fields {
+				let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+				flatten(&key, value, row);
+			},
+		Value::Null => row.push((name, String::new())),
+		Value::String(value) => row.push((name, value.clone())),
+		value => row.push((name, value.to_string())),
+	}
+}
+
+fn csv_line(fields: &[String]) -> String {
+	let fields: Vec<String> = fields
+		.iter()
+		.map(|field| {
+			if field.contains([',', '"', '\n', '\r']) {
+				format!("\"{}\"", field.replace('"', "\"\""))
+			} else {
+				field.clone()
+			}
+		})
+		.collect();
+	format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fmt::{self, Formatter};
+
+	// Fields are sorted to have the same JSON with and without `preserve_order` of `serde_json`
+	#[derive(Serialize)]
+	struct Record {
+		count: u32,
+		name: String,
+		nested: Option<Nested>,
+	}
+
+	#[derive(Serialize)]
+	struct Nested {
+		values: Vec<u32>,
+	}
+
+	impl Display for Record {
+		fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+			write!(f, "{}: {}", self.name, self.count)
+		}
+	}
+
+	impl OutputRecord for Record {
+		const CSV_COLUMNS: &'static [&'static str] = &["count", "name", "nested.values"];
+	}
+
+	fn render(writer: &OutputWriter, record: &Record) -> String {
+		String::from_utf8(writer.render(record).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn test_renders_records() {
+		let record = Record { count: 1, name: "a, \"b\"".to_string(), nested: Some(Nested { values: vec![1, 2] }) };
+
+		assert_eq!(render(&OutputWriter::new(OutputFormat::Pretty), &record), "a, \"b\": 1\n");
+		assert_eq!(
+			render(&OutputWriter::new(OutputFormat::Json), &record),
+			"{\"count\":1,\"name\":\"a, \\\"b\\\"\",\"nested\":{\"values\":[1,2]}}\n"
+		);
+		assert_eq!(
+			OutputWriter::new(OutputFormat::Bincode).render(&record).unwrap(),
+			bincode::serialize(&record).unwrap()
+		);
+
+		// The header is fixed even if the first record has no nested fields, and written once
+		let writer = OutputWriter::new(OutputFormat::Csv);
+		let empty = Record { count: 2, name: "c".to_string(), nested: None };
+		assert_eq!(render(&writer, &empty), "count,name,nested.values\n2,c,\n");
+		assert_eq!(render(&writer, &record), "1,\"a, \"\"b\"\"\",\"[1,2]\"\n");
+	}
+}
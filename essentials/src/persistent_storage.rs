@@ -0,0 +1,359 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Persistent storage of the collector records on disk.
+//!
+//! `SledRecordsStorage` implements the same `RecordsStorage` and `PrefixedRecordsStorage` traits as
+//! `HashedPrefixedRecordsStorage` over a sled database, so the records survive restarts and can be queried after
+//! the fact. Entries are kept on disk under the encoded prefix and key, only the index of the keys by prefix and
+//! block is kept in memory and rebuilt when the database is opened. Records are pruned as in memory, so the history
+//! is limited by `max_blocks` across restarts as well.
+//!
+//! Inserting a key that is already stored overwrites it, as the blocks collected before a restart are collected
+//! again when a tool is run over the same range. The storage does blocking I/O, so its API handler runs on
+//! a blocking thread.
+
+use crate::storage::{
+	PrefixSubscribers, PrefixedRecordsStorage, RecordsStorage, RecordsStorageConfig, StorageEntry, StorageInfo,
+	StorageUpdate, StorageUpdateKind,
+};
+use color_eyre::eyre::eyre;
+use log::{error, info};
+use parity_scale_codec::{Decode, Encode};
+use std::{
+	borrow::Borrow,
+	collections::{BTreeMap, HashMap, HashSet},
+	fmt::Debug,
+	hash::Hash,
+	path::Path,
+};
+use tokio::sync::mpsc::Receiver;
+
+use crate::types::BlockNumber;
+
+/// Prefixed storage with the entries kept in a sled database
+pub struct SledRecordsStorage<K: Hash + Clone, P: Hash + Clone> {
+	/// The configuration.
+	config: RecordsStorageConfig,
+	/// The entries by the encoded prefix and key.
+	db: sled::Db,
+	/// Keys with expire dates.
+	ephemeral_records: BTreeMap<BlockNumber, HashSet<K>>,
+	/// Keys stored under a prefix.
+	prefixed_keys: HashMap<P, HashSet<K>>,
+	/// Subscribers to the updates of a prefix.
+	subscribers: PrefixSubscribers<K, P>,
+}
+
+impl<K, P> SledRecordsStorage<K, P>
+where
+	K: Hash + Clone + Eq + Debug + Encode + Decode,
+	P: Hash + Clone + Eq + Debug + Encode + Decode,
+{
+	/// Opens the database at the path, creating it if needed, and indexes the records kept in it
+	pub fn open(path: &Path, config: RecordsStorageConfig) -> color_eyre::Result<Self> {
+		let db = sled::open(path).map_err(|e| eyre!("cannot open the storage at {}: {:?}", path.display(), e))?;
+		let mut storage = Self::with_db(db, config);
+		for item in storage.db.iter() {
+			let (db_key, value) = item?;
+			let (prefix, key) = <(P, K)>::decode(&mut db_key.as_ref())
+				.map_err(|e| eyre!("cannot decode a key of the storage at {}: {:?}", path.display(), e))?;
+			let entry = StorageEntry::decode(&mut value.as_ref())
+				.map_err(|e| eyre!("cannot decode {:?} {:?} of the storage: {:?}", prefix, key, e))?;
+			storage.index(prefix, key, entry.time().block_number());
+		}
+		while storage.ephemeral_records.len() > storage.config.max_blocks {
+			storage.prune();
+		}
+		info!(
+			"Opened the storage at {} with {} records of {} blocks",
+			path.display(),
+			storage.len(),
+			storage.blocks_len()
+		);
+
+		Ok(storage)
+	}
+
+	fn with_db(db: sled::Db, config: RecordsStorageConfig) -> Self {
+		Self {
+			config,
+			db,
+			ephemeral_records: BTreeMap::new(),
+			prefixed_keys: HashMap::new(),
+			subscribers: PrefixSubscribers::default(),
+		}
+	}
+
+	fn index(&mut self, prefix: P, key: K, block_number: BlockNumber) {
+		self.prefixed_keys.entry(prefix).or_default().insert(key.clone());
+		self.ephemeral_records.entry(block_number).or_default().insert(key);
+	}
+
+	// Removes a key from the index, returns if it was indexed under the prefix
+	fn unindex(&mut self, prefix: &P, key: &K) -> bool {
+		if !self.prefixed_keys.get_mut(prefix).is_some_and(|keys| keys.remove(key)) {
+			return false
+		}
+		self.ephemeral_records.retain(|_, keys| {
+			keys.remove(key);
+			!keys.is_empty()
+		});
+		true
+	}
+
+	// Returns the prefix and the key stored under it, as the prefixed storage expects the keys to be unique
+	fn find<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<(P, K)>
+	where
+		K: Borrow<Q>,
+	{
+		self.prefixed_keys
+			.iter()
+			.find_map(|(prefix, keys)| keys.get(key).map(|key| (prefix.clone(), key.clone())))
+	}
+
+	fn read(&self, prefix: &P, key: &K) -> Option<StorageEntry> {
+		match self.db.get((prefix, key).encode()) {
+			Ok(value) => value.and_then(|value| decode_entry(prefix, key, &value)),
+			Err(e) => {
+				error!("Cannot read {:?} {:?} from the storage: {:?}", prefix, key, e);
+				None
+			},
+		}
+	}
+
+	fn write(&self, prefix: &P, key: &K, entry: &StorageEntry) -> color_eyre::Result<Option<StorageEntry>> {
+		let previous = self.db.insert((prefix, key).encode(), entry.encode())?;
+		Ok(previous.and_then(|value| decode_entry(prefix, key, &value)))
+	}
+
+	fn remove(&self, prefix: &P, key: &K) -> Option<StorageEntry> {
+		match self.db.remove((prefix, key).encode()) {
+			Ok(value) => value.and_then(|value| decode_entry(prefix, key, &value)),
+			Err(e) => {
+				error!("Cannot remove {:?} {:?} from the storage: {:?}", prefix, key, e);
+				None
+			},
+		}
+	}
+}
+
+fn decode_entry<P: Debug, K: Debug>(prefix: &P, key: &K, value: &[u8]) -> Option<StorageEntry> {
+	StorageEntry::decode(&mut &value[..])
+		.map_err(|e| error!("Cannot decode {:?} {:?} from the storage: {:?}", prefix, key, e))
+		.ok()
+}
+
+impl<K, P> RecordsStorage<K> for SledRecordsStorage<K, P>
+where
+	K: Hash + Clone + Eq + Debug + Encode + Decode,
+	P: Hash + Clone + Eq + Debug + Encode + Decode,
+{
+	/// Creates a storage in a temporary database removed on drop, `open` keeps the records
+	fn new(config: RecordsStorageConfig) -> Self {
+		let db = sled::Config::new()
+			.temporary(true)
+			.open()
+			.expect("cannot create a temporary database");
+		Self::with_db(db, config)
+	}
+
+	// We cannot insert non prefixed key into a prefixed storage
+	fn insert(&mut self, key: K, _: StorageEntry) -> color_eyre::Result<()> {
+		Err(eyre!("trying to insert key with no prefix to the prefixed storage: {:?}", key))
+	}
+
+	fn replace<Q: ?Sized + Hash + Eq>(&mut self, key: &Q, entry: StorageEntry) -> Option<StorageEntry>
+	where
+		K: Borrow<Q>,
+	{
+		let (prefix, key) = self.find(key)?;
+		self.replace_prefixed(&prefix, &key, entry)
+	}
+
+	fn prune(&mut self) {
+		// Check if the chain has advanced more than maximum allowed blocks.
+		if self.ephemeral_records.len() <= self.config.max_blocks {
+			return
+		}
+		// Prune all entries at oldest block
+		let (_, keys) = self.ephemeral_records.pop_first().expect("checked above; qed");
+		for key in keys.iter() {
+			let prefixes: Vec<P> = self
+				.prefixed_keys
+				.iter_mut()
+				.filter_map(|(prefix, keys)| keys.remove(key).then(|| prefix.clone()))
+				.collect();
+			for prefix in prefixes {
+				self.remove(&prefix, key);
+			}
+		}
+	}
+
+	fn get<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<StorageEntry>
+	where
+		K: Borrow<Q>,
+	{
+		let (prefix, key) = self.find(key)?;
+		self.read(&prefix, &key)
+	}
+
+	fn delete<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<StorageEntry>
+	where
+		K: Borrow<Q>,
+	{
+		let (prefix, key) = self.find(key)?;
+		self.delete_prefix(&prefix, &key)
+	}
+
+	fn len(&self) -> usize {
+		self.prefixed_keys.values().map(|keys| keys.len()).sum()
+	}
+
+	fn blocks_len(&self) -> usize {
+		self.ephemeral_records.len()
+	}
+
+	fn keys(&self) -> Vec<K> {
+		self.prefixed_keys.values().flatten().cloned().collect()
+	}
+}
+
+impl<K, P> PrefixedRecordsStorage<K, P> for SledRecordsStorage<K, P>
+where
+	K: Hash + Clone + Eq + Debug + Encode + Decode,
+	P: Hash + Clone + Eq + Debug + Encode + Decode,
+{
+	fn insert_prefix(&mut self, prefix: P, key: K, entry: StorageEntry) -> color_eyre::Result<()> {
+		// Records of the blocks stored by a previous run are overwritten when these blocks are collected again
+		let kind = if self.unindex(&prefix, &key) { StorageUpdateKind::Replaced } else { StorageUpdateKind::Inserted };
+		self.write(&prefix, &key, &entry)?;
+		self.subscribers.notify(kind, &prefix, &key, &entry);
+		self.index(prefix, key, entry.time().block_number());
+
+		self.prune();
+		Ok(())
+	}
+
+	fn replace_prefixed<Q: ?Sized + Hash + Eq, PQ: ?Sized + Hash + Eq>(
+		&mut self,
+		prefix: &PQ,
+		key: &Q,
+		entry: StorageEntry,
+	) -> Option<StorageEntry>
+	where
+		K: Borrow<Q>,
+		P: Borrow<PQ>,
+	{
+		let (prefix, keys) = self.prefixed_keys.get_key_value(prefix)?;
+		let (prefix, key) = (prefix.clone(), keys.get(key)?.clone());
+		self.subscribers.notify(StorageUpdateKind::Replaced, &prefix, &key, &entry);
+		self.write(&prefix, &key, &entry)
+			.map_err(|e| error!("Cannot write {:?} {:?} to the storage: {:?}", prefix, key, e))
+			.ok()
+			.flatten()
+	}
+
+	fn get_prefix<Q: ?Sized + Hash + Eq, PQ: ?Sized + Hash + Eq>(&self, prefix: &PQ, key: &Q) -> Option<StorageEntry>
+	where
+		K: Borrow<Q>,
+		P: Borrow<PQ>,
+	{
+		let (prefix, keys) = self.prefixed_keys.get_key_value(prefix)?;
+		self.read(prefix, keys.get(key)?)
+	}
+
+	fn delete_prefix<Q: ?Sized + Hash + Eq, PQ: ?Sized + Hash + Eq>(
+		&mut self,
+		prefix: &PQ,
+		key: &Q,
+	) -> Option<StorageEntry>
+	where
+		K: Borrow<Q>,
+		P: Borrow<PQ>,
+	{
+		let (prefix, keys) = self.prefixed_keys.get_key_value(prefix)?;
+		let (prefix, key) = (prefix.clone(), keys.get(key)?.clone());
+		self.prefixed_keys.get_mut(&prefix)?.remove(&key);
+		self.remove(&prefix, &key)
+	}
+
+	fn prefixed_keys<PQ: ?Sized + Hash + Eq>(&self, prefix: &PQ) -> Vec<K>
+	where
+		P: Borrow<PQ>,
+	{
+		self.prefixed_keys
+			.get(prefix)
+			.map(|keys| keys.iter().cloned().collect())
+			.unwrap_or_default()
+	}
+
+	fn prefixes(&self) -> Vec<P> {
+		self.prefixed_keys.keys().cloned().collect()
+	}
+
+	fn subscribe_prefix(&mut self, prefix: P) -> Receiver<StorageUpdate<K, P>> {
+		self.subscribers.subscribe(prefix)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_records_survive_reopening() {
+		let path = std::env::temp_dir().join(format!("introspector-storage-{}", std::process::id()));
+		let config = RecordsStorageConfig { max_blocks: 2 };
+		let _ = std::fs::remove_dir_all(&path);
+		{
+			let mut storage = SledRecordsStorage::<u32, u8>::open(&path, config).unwrap();
+			storage
+				.insert_prefix(1, 10, StorageEntry::new_onchain(1.into(), "first"))
+				.unwrap();
+			storage
+				.insert_prefix(2, 20, StorageEntry::new_onchain(2.into(), "second"))
+				.unwrap();
+			storage.replace(&20, StorageEntry::new_onchain(2.into(), "replaced")).unwrap();
+			storage.db.flush().unwrap();
+		}
+		{
+			let mut storage = SledRecordsStorage::<u32, u8>::open(&path, config).unwrap();
+			assert_eq!(storage.len(), 2);
+			assert_eq!(storage.get_prefix(&1, &10).unwrap().into_inner::<String>().unwrap(), "first");
+			assert_eq!(storage.get(&20).unwrap().into_inner::<String>().unwrap(), "replaced");
+			// The oldest block is pruned from the disk as well
+			storage
+				.insert_prefix(1, 30, StorageEntry::new_onchain(3.into(), "third"))
+				.unwrap();
+			assert!(storage.get(&10).is_none());
+			storage.db.flush().unwrap();
+		}
+		let mut storage = SledRecordsStorage::<u32, u8>::open(&path, config).unwrap();
+		let mut keys = storage.keys();
+		keys.sort();
+		assert_eq!(keys, vec![20, 30]);
+		// Collecting a stored block again overwrites its records
+		let mut updates = storage.subscribe_prefix(2);
+		storage
+			.insert_prefix(2, 20, StorageEntry::new_onchain(2.into(), "collected again"))
+			.unwrap();
+		assert_eq!(updates.try_recv().unwrap().kind, StorageUpdateKind::Replaced);
+		assert_eq!(storage.get(&20).unwrap().into_inner::<String>().unwrap(), "collected again");
+		assert_eq!((storage.len(), storage.blocks_len()), (2, 2));
+		drop(storage);
+		std::fs::remove_dir_all(&path).unwrap();
+	}
+}
@@ -0,0 +1,145 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Error budget of the RPC interactions of a run
+//!
+//! Failed RPC calls, retries and blocks with missing data are counted by the API and the collector, so the
+//! summaries can tell whether the chain was unhealthy or the monitoring itself was. The counter is given to the
+//! executors in their `ExecutorContext`, so everything built with the same context of a run shares it.
+
+use serde::Serialize;
+use std::{
+	fmt::{self, Display, Formatter},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+};
+
+/// Counters of the RPC interactions since the start of the run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RpcErrorBudget {
+	/// Number of RPC calls, a call is counted once however many times it was retried
+	pub calls: u64,
+	/// Number of RPC calls which failed after all retries
+	pub failed_calls: u64,
+	/// Number of retries of RPC calls and connections
+	pub retries: u64,
+	/// Number of blocks whose data could not be fetched
+	pub data_gaps: u64,
+}
+
+impl RpcErrorBudget {
+	/// Share of the failed calls, zero without calls
+	pub fn failure_ratio(&self) -> f64 {
+		if self.calls == 0 {
+			0.0
+		} else {
+			self.failed_calls as f64 / self.calls as f64
+		}
+	}
+
+	/// Whether the share of the failed calls is above `max_ratio`
+	pub fn is_exceeded(&self, max_ratio: f64) -> bool {
+		self.failure_ratio() > max_ratio
+	}
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+	calls: AtomicU64,
+	failed_calls: AtomicU64,
+	retries: AtomicU64,
+	data_gaps: AtomicU64,
+}
+
+/// Counts the RPC interactions of a run, the clones share the counters
+#[derive(Clone, Debug, Default)]
+pub struct RpcBudgetCounter(Arc<Counters>);
+
+impl RpcBudgetCounter {
+	/// Counts an RPC call
+	pub fn on_call(&self) {
+		self.0.calls.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Counts an RPC call which failed after all retries
+	pub fn on_failed_call(&self) {
+		self.0.failed_calls.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Counts a retry of an RPC call or connection
+	pub fn on_retry(&self) {
+		self.0.retries.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Counts a block whose data could not be fetched
+	pub fn on_data_gap(&self) {
+		self.0.data_gaps.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Returns the counters of the run so far
+	pub fn snapshot(&self) -> RpcErrorBudget {
+		RpcErrorBudget {
+			calls: self.0.calls.load(Ordering::Relaxed),
+			failed_calls: self.0.failed_calls.load(Ordering::Relaxed),
+			retries: self.0.retries.load(Ordering::Relaxed),
+			data_gaps: self.0.data_gaps.load(Ordering::Relaxed),
+		}
+	}
+}
+
+impl Display for RpcErrorBudget {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "--- RPC error budget ---")?;
+		writeln!(
+			f,
+			"Calls: {}, failed: {} ({:.2}%), retries: {}, data gaps: {}",
+			self.calls,
+			self.failed_calls,
+			self.failure_ratio() * 100.0,
+			self.retries,
+			self.data_gaps
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_failure_ratio() {
+		assert_eq!(RpcErrorBudget::default().failure_ratio(), 0.0);
+		let budget = RpcErrorBudget { calls: 200, failed_calls: 3, retries: 10, data_gaps: 1 };
+		assert_eq!(budget.failure_ratio(), 0.015);
+		assert!(budget.is_exceeded(0.01));
+		assert!(!budget.is_exceeded(0.02));
+	}
+
+	#[test]
+	fn test_counts_interactions() {
+		let counter = RpcBudgetCounter::default();
+		let clone = counter.clone();
+		counter.on_call();
+		clone.on_call();
+		counter.on_failed_call();
+		clone.on_retry();
+		counter.on_data_gap();
+		assert_eq!(counter.snapshot(), RpcErrorBudget { calls: 2, failed_calls: 1, retries: 1, data_gaps: 1 });
+		assert_eq!(RpcBudgetCounter::default().snapshot(), RpcErrorBudget::default());
+	}
+}
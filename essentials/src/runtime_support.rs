@@ -0,0 +1,152 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Runtimes this build was tested with.
+//!
+//! Storage and inherents are decoded with the embedded metadata, so a runtime upgrade of the chain may break
+//! decoding in subtle ways. The tested runtimes are the ones of the vendored metadata in
+//! [`VENDORED_METADATA`](crate::metadata::VENDORED_METADATA), and the connected chain is checked against them on
+//! startup.
+
+use crate::{api::subxt_wrapper::RequestExecutor, metadata::VENDORED_METADATA};
+use clap::Parser;
+use color_eyre::eyre::eyre;
+use log::{info, warn};
+use std::fmt::{Display, Formatter};
+
+/// Version of this build
+pub const INTROSPECTOR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Version of a chain runtime
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeVersion {
+	/// Runtime spec name, e.g. `polkadot`
+	pub spec_name: String,
+	/// Runtime spec version
+	pub spec_version: u32,
+}
+
+impl Display for RuntimeVersion {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} runtime {}", self.spec_name, self.spec_version)
+	}
+}
+
+/// Result of checking a runtime against the vendored metadata
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuntimeSupport {
+	/// The runtime was tested with this build
+	Supported,
+	/// The runtime is newer than the tested ones
+	Newer { max_spec_version: u32 },
+	/// The runtime is older than the tested ones
+	Older { min_spec_version: u32 },
+	/// No metadata of the runtime is vendored, e.g. a custom relay chain
+	Unknown,
+}
+
+/// Checks a runtime against the spec versions of its vendored metadata
+pub fn runtime_support(version: &RuntimeVersion) -> RuntimeSupport {
+	let tested = VENDORED_METADATA
+		.iter()
+		.filter(|metadata| metadata.spec_name == version.spec_name)
+		.map(|metadata| metadata.spec_version);
+	let (Some(min_spec_version), Some(max_spec_version)) = (tested.clone().min(), tested.max()) else {
+		return RuntimeSupport::Unknown
+	};
+
+	if version.spec_version > max_spec_version {
+		RuntimeSupport::Newer { max_spec_version }
+	} else if version.spec_version < min_spec_version {
+		RuntimeSupport::Older { min_spec_version }
+	} else {
+		RuntimeSupport::Supported
+	}
+}
+
+#[derive(Clone, Debug, Parser, Default)]
+#[clap(rename_all = "kebab-case")]
+pub struct RuntimeSupportOptions {
+	/// Refuse to start if the chain runtime is newer than the ones this build was tested with, or cannot be read
+	#[clap(long)]
+	strict: bool,
+}
+
+/// Warns, or fails with `--strict`, if the runtime of the chain at `url` is newer than the tested ones or cannot be
+/// read
+pub async fn check_runtime_support(
+	url: &str,
+	executor: &mut RequestExecutor,
+	opts: &RuntimeSupportOptions,
+) -> color_eyre::Result<()> {
+	let version = match executor.get_runtime_version(url).await {
+		Ok(version) => version,
+		Err(e) => {
+			let message = format!("Cannot get runtime version, skipping the runtime support check: {:?}", e);
+			if opts.strict {
+				return Err(eyre!(message))
+			}
+			warn!("{}", message);
+			return Ok(())
+		},
+	};
+
+	match runtime_support(&version) {
+		RuntimeSupport::Supported =>
+			info!("{} is supported by polkadot-introspector {}", version, INTROSPECTOR_VERSION),
+		RuntimeSupport::Newer { max_spec_version } => {
+			let message = format!(
+				"{} is newer than {} this build was tested with, decoding may fail or be wrong. \
+				 A release of polkadot-introspector newer than {} is required",
+				version, max_spec_version, INTROSPECTOR_VERSION
+			);
+			if opts.strict {
+				return Err(eyre!(message))
+			}
+			warn!("{}", message);
+		},
+		RuntimeSupport::Older { min_spec_version } => warn!(
+			"{} is older than {} this build was tested with, use an older release of polkadot-introspector \
+			 if decoding fails",
+			version, min_spec_version
+		),
+		RuntimeSupport::Unknown =>
+			warn!("{} was not tested with polkadot-introspector {}", version, INTROSPECTOR_VERSION),
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(spec_name: &str, spec_version: u32) -> RuntimeVersion {
+		RuntimeVersion { spec_name: spec_name.to_string(), spec_version }
+	}
+
+	#[test]
+	fn test_checks_runtime_support() {
+		assert_eq!(runtime_support(&version("polkadot", 1_000_001)), RuntimeSupport::Supported);
+		assert_eq!(
+			runtime_support(&version("polkadot", 1_001_000)),
+			RuntimeSupport::Newer { max_spec_version: 1_000_001 }
+		);
+		assert_eq!(runtime_support(&version("polkadot", 9_370)), RuntimeSupport::Older { min_spec_version: 1_000_001 });
+		assert_eq!(runtime_support(&version("custom", 1)), RuntimeSupport::Unknown);
+	}
+}
@@ -0,0 +1,163 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Sequence numbers of exported events and consumer-side gap detection.
+//!
+//! Every exported stream has an epoch (the time it was started at, in milliseconds)
+//! and a sequence number starting from 1 within the epoch. A restart of the introspector
+//! starts a new epoch, so consumers can tell restarts from lost events.
+
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Position of an event in a stream
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamPosition {
+	/// Time the stream was started at, in milliseconds
+	pub epoch: u64,
+	/// Sequence number of the event within the epoch, starting from 1
+	pub sequence: u64,
+}
+
+/// Assigns monotonically increasing sequence numbers to the events of a stream
+#[derive(Debug)]
+pub struct SequenceGenerator {
+	epoch: u64,
+	last: u64,
+}
+
+impl Default for SequenceGenerator {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl SequenceGenerator {
+	/// Starts a new epoch using the current time
+	pub fn new() -> Self {
+		let epoch = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |v| v.as_millis() as u64);
+		Self::with_epoch(epoch)
+	}
+
+	pub fn with_epoch(epoch: u64) -> Self {
+		Self { epoch, last: 0 }
+	}
+
+	/// Returns the position of the next event
+	pub fn next_position(&mut self) -> StreamPosition {
+		self.last += 1;
+		StreamPosition { epoch: self.epoch, sequence: self.last }
+	}
+}
+
+/// Result of checking an event position against the previously seen events
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SequenceCheck {
+	/// The first event seen by the consumer
+	First,
+	/// The event follows the previous one
+	InOrder,
+	/// Events with sequence numbers from `from` to `to` (inclusive) were missed
+	Gap { from: u64, to: u64 },
+	/// The event has already been seen or belongs to an older epoch
+	Duplicate,
+	/// The producer was restarted, `missed` events of the new epoch were not seen
+	Restarted { missed: u64 },
+}
+
+/// Detects missed and duplicated events on the consumer side
+#[derive(Debug, Default)]
+pub struct GapDetector {
+	/// The last seen position
+	last: Option<StreamPosition>,
+	/// Number of events known to be missed
+	missed: u64,
+}
+
+impl GapDetector {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Checks the position of a received event
+	pub fn observe(&mut self, position: StreamPosition) -> SequenceCheck {
+		let check = match self.last {
+			None => SequenceCheck::First,
+			Some(last) if position.epoch < last.epoch => return SequenceCheck::Duplicate,
+			Some(last) if position.epoch > last.epoch => {
+				let missed = position.sequence.saturating_sub(1);
+				self.missed += missed;
+				SequenceCheck::Restarted { missed }
+			},
+			Some(last) if position.sequence <= last.sequence => return SequenceCheck::Duplicate,
+			Some(last) if position.sequence == last.sequence + 1 => SequenceCheck::InOrder,
+			Some(last) => {
+				self.missed += position.sequence - last.sequence - 1;
+				SequenceCheck::Gap { from: last.sequence + 1, to: position.sequence - 1 }
+			},
+		};
+		self.last = Some(position);
+
+		check
+	}
+
+	/// Returns the number of events known to be missed
+	pub fn missed(&self) -> u64 {
+		self.missed
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_generates_sequence() {
+		let mut generator = SequenceGenerator::with_epoch(100);
+
+		assert_eq!(generator.next_position(), StreamPosition { epoch: 100, sequence: 1 });
+		assert_eq!(generator.next_position(), StreamPosition { epoch: 100, sequence: 2 });
+	}
+
+	#[test]
+	fn test_detects_gaps_and_duplicates() {
+		let mut detector = GapDetector::new();
+		let position = |sequence| StreamPosition { epoch: 100, sequence };
+
+		assert_eq!(detector.observe(position(1)), SequenceCheck::First);
+		assert_eq!(detector.observe(position(2)), SequenceCheck::InOrder);
+		assert_eq!(detector.observe(position(2)), SequenceCheck::Duplicate);
+		assert_eq!(detector.observe(position(5)), SequenceCheck::Gap { from: 3, to: 4 });
+		assert_eq!(detector.observe(position(4)), SequenceCheck::Duplicate);
+		assert_eq!(detector.observe(position(6)), SequenceCheck::InOrder);
+		assert_eq!(detector.missed(), 2);
+	}
+
+	#[test]
+	fn test_detects_restarts() {
+		let mut detector = GapDetector::new();
+
+		assert_eq!(detector.observe(StreamPosition { epoch: 100, sequence: 10 }), SequenceCheck::First);
+		assert_eq!(
+			detector.observe(StreamPosition { epoch: 200, sequence: 3 }),
+			SequenceCheck::Restarted { missed: 2 }
+		);
+		assert_eq!(detector.observe(StreamPosition { epoch: 100, sequence: 11 }), SequenceCheck::Duplicate);
+		assert_eq!(detector.observe(StreamPosition { epoch: 200, sequence: 4 }), SequenceCheck::InOrder);
+		assert_eq!(detector.missed(), 2);
+	}
+}
@@ -21,10 +21,15 @@
 //! Values are stored as scale encoded byte chunks and are **copied** on calling of the
 //! `get` method. This is done for the API simplicity as the performance is not a
 //! goal here.
+//! Prefixed storage can also be used as a source of storage update events: consumers
+//! subscribe to a prefix, e.g. candidates of a parachain, and receive every entry
+//! inserted or replaced under it. A subscriber lagging behind by `SUBSCRIPTION_QUEUE_SIZE`
+//! updates is dropped, which ends its subscription.
 #![allow(dead_code)]
 
 use color_eyre::eyre::eyre;
-use parity_scale_codec::{Decode, Encode};
+use log::warn;
+use parity_scale_codec::{Decode, Encode, Input, Output};
 use std::{
 	borrow::Borrow,
 	collections::{BTreeMap, HashMap, HashSet},
@@ -32,11 +37,12 @@ use std::{
 	hash::Hash,
 	time::Duration,
 };
+use tokio::sync::mpsc::{channel, error::TrySendError, Receiver, Sender};
 
 use crate::types::BlockNumber;
 
 /// A type to identify the record type
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Encode, Decode)]
 pub enum RecordType {
 	/// For onchain data.
 	Onchain,
@@ -102,6 +108,29 @@ impl StorageEntry {
 	}
 }
 
+// Entries are encoded to be kept on disk by the persistent storage
+impl Encode for StorageEntry {
+	fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+		self.record_type.encode_to(dest);
+		self.record_time.block_number.encode_to(dest);
+		self.record_time
+			.timestamp
+			.map(|v| (v.as_secs(), v.subsec_nanos()))
+			.encode_to(dest);
+		self.data.encode_to(dest);
+	}
+}
+
+impl Decode for StorageEntry {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, parity_scale_codec::Error> {
+		let record_type = RecordType::decode(input)?;
+		let block_number = BlockNumber::decode(input)?;
+		let timestamp = Option::<(u64, u32)>::decode(input)?.map(|(secs, nanos)| Duration::new(secs, nanos));
+		let data = Vec::<u8>::decode(input)?;
+		Ok(StorageEntry { record_type, record_time: RecordTime { block_number, timestamp }, data })
+	}
+}
+
 /// A required trait to implement for storing records.
 pub trait StorageInfo {
 	/// Returns the type of the data.
@@ -164,6 +193,8 @@ pub trait RecordsStorage<K> {
 		K: Borrow<Q>;
 	/// Size of the storage
 	fn len(&self) -> usize;
+	/// Number of blocks which have ephemeral entries in the storage
+	fn blocks_len(&self) -> usize;
 	/// Checks if the storage is empty
 	fn is_empty(&self) -> bool {
 		self.len() == 0
@@ -267,11 +298,90 @@ where
 		self.direct_records.len()
 	}
 
+	fn blocks_len(&self) -> usize {
+		self.ephemeral_records.len()
+	}
+
 	fn keys(&self) -> Vec<K> {
 		self.direct_records.keys().cloned().collect()
 	}
 }
 
+/// The kind of a storage update
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum StorageUpdateKind {
+	/// A new entry was inserted.
+	Inserted,
+	/// An existing entry was replaced.
+	Replaced,
+}
+
+/// A notification sent to the subscribers of a prefix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageUpdate<K, P> {
+	/// The kind of the update.
+	pub kind: StorageUpdateKind,
+	/// The prefix of the entry.
+	pub prefix: P,
+	/// The key of the entry.
+	pub key: K,
+	/// The new entry.
+	pub entry: StorageEntry,
+}
+
+/// How many updates a subscriber may lag behind before it is dropped.
+pub const SUBSCRIPTION_QUEUE_SIZE: usize = 1024;
+
+/// Subscribers to the updates of the prefixes of a storage.
+pub(crate) struct PrefixSubscribers<K, P> {
+	senders: HashMap<P, Vec<Sender<StorageUpdate<K, P>>>>,
+}
+
+impl<K, P> Default for PrefixSubscribers<K, P> {
+	fn default() -> Self {
+		Self { senders: HashMap::new() }
+	}
+}
+
+impl<K, P> PrefixSubscribers<K, P>
+where
+	K: Clone,
+	P: Hash + Clone + Eq + Debug,
+{
+	pub(crate) fn subscribe(&mut self, prefix: P) -> Receiver<StorageUpdate<K, P>> {
+		// Forget the subscriptions dropped since the last update of their prefixes
+		self.senders.retain(|_, senders| {
+			senders.retain(|sender| !sender.is_closed());
+			!senders.is_empty()
+		});
+		let (sender, receiver) = channel(SUBSCRIPTION_QUEUE_SIZE);
+		self.senders.entry(prefix).or_default().push(sender);
+		receiver
+	}
+
+	pub(crate) fn notify(&mut self, kind: StorageUpdateKind, prefix: &P, key: &K, entry: &StorageEntry) {
+		if let Some(senders) = self.senders.get_mut(prefix) {
+			senders.retain(|sender| {
+				let update = StorageUpdate { kind, prefix: prefix.clone(), key: key.clone(), entry: entry.clone() };
+				match sender.try_send(update) {
+					Ok(()) => true,
+					Err(TrySendError::Full(_)) => {
+						warn!(
+							"Dropping a subscriber of {:?} lagging behind by {} updates",
+							prefix, SUBSCRIPTION_QUEUE_SIZE
+						);
+						false
+					},
+					Err(TrySendError::Closed(_)) => false,
+				}
+			});
+			if senders.is_empty() {
+				self.senders.remove(prefix);
+			}
+		}
+	}
+}
+
 /// This trait is used to define a storage that can store items organized in prefixes.
 /// Prefixes are used to group elements by some characteristic. For example, to get
 /// elements that belong to some particular parachain.
@@ -310,6 +420,9 @@ pub trait PrefixedRecordsStorage<K, P> {
 		P: Borrow<PQ>;
 	/// Get all prefixes from a storage
 	fn prefixes(&self) -> Vec<P>;
+	/// Subscribe to the entries inserted or replaced under a prefix, the prefix is not required to exist yet.
+	/// A subscription is dropped once its receiver is dropped
+	fn subscribe_prefix(&mut self, prefix: P) -> Receiver<StorageUpdate<K, P>>;
 }
 
 /// Prefixed storage is distinct as it organise data stored using prefixes,
@@ -328,6 +441,8 @@ pub struct HashedPrefixedRecordsStorage<K: Hash + Clone, P: Hash + Clone> {
 	ephemeral_records: BTreeMap<BlockNumber, HashSet<K>>,
 	/// Direct mapping to values.
 	prefixed_records: HashMap<P, HashMap<K, StorageEntry>>,
+	/// Subscribers to the updates of a prefix.
+	subscribers: PrefixSubscribers<K, P>,
 }
 
 impl<K, P> RecordsStorage<K> for HashedPrefixedRecordsStorage<K, P>
@@ -338,7 +453,8 @@ where
 	fn new(config: RecordsStorageConfig) -> Self {
 		let ephemeral_records = BTreeMap::new();
 		let prefixed_records = HashMap::new();
-		Self { config, last_block: None, ephemeral_records, prefixed_records }
+		let subscribers = PrefixSubscribers::default();
+		Self { config, last_block: None, ephemeral_records, prefixed_records, subscribers }
 	}
 
 	// We cannot insert non prefixed key into a prefixed storage
@@ -350,13 +466,13 @@ where
 	where
 		K: Borrow<Q>,
 	{
-		for direct_map in self.prefixed_records.values_mut() {
-			if let Some(record) = direct_map.get_mut(key) {
-				return Some(std::mem::replace(record, entry))
-			}
-		}
+		let (prefix, key) = self.prefixed_records.iter().find_map(|(prefix, direct_map)| {
+			direct_map.get_key_value(key).map(|(key, _)| (prefix.clone(), key.clone()))
+		})?;
+		self.subscribers.notify(StorageUpdateKind::Replaced, &prefix, &key, &entry);
+		let record = self.prefixed_records.get_mut(&prefix)?.get_mut(&key)?;
 
-		None
+		Some(std::mem::replace(record, entry))
 	}
 
 	fn prune(&mut self) {
@@ -407,6 +523,10 @@ where
 		self.prefixed_records.values().map(|direct_map| direct_map.len()).sum()
 	}
 
+	fn blocks_len(&self) -> usize {
+		self.ephemeral_records.len()
+	}
+
 	fn keys(&self) -> Vec<K> {
 		self.prefixed_records
 			.values()
@@ -422,13 +542,17 @@ where
 	P: Hash + Clone + Eq + Debug,
 {
 	fn insert_prefix(&mut self, prefix: P, key: K, entry: StorageEntry) -> color_eyre::Result<()> {
-		let direct_storage = self.prefixed_records.entry(prefix).or_default();
-		if direct_storage.contains_key(&key) {
+		if self
+			.prefixed_records
+			.get(&prefix)
+			.is_some_and(|direct_storage| direct_storage.contains_key(&key))
+		{
 			return Err(eyre!("duplicate key: {:?}", key))
 		}
 		let block_number = entry.time().block_number();
 		self.last_block = Some(block_number);
-		direct_storage.insert(key.clone(), entry);
+		self.subscribers.notify(StorageUpdateKind::Inserted, &prefix, &key, &entry);
+		self.prefixed_records.entry(prefix).or_default().insert(key.clone(), entry);
 
 		self.ephemeral_records
 			.entry(block_number)
@@ -449,13 +573,13 @@ where
 		K: Borrow<Q>,
 		P: Borrow<PQ>,
 	{
-		let direct_storage = self.prefixed_records.get_mut(prefix)?;
-		if !direct_storage.contains_key(key) {
-			None
-		} else {
-			let record = direct_storage.get_mut(key).unwrap();
-			Some(std::mem::replace(record, entry))
-		}
+		let (prefix, direct_storage) = self.prefixed_records.get_key_value(prefix)?;
+		let (key, _) = direct_storage.get_key_value(key)?;
+		let (prefix, key) = (prefix.clone(), key.clone());
+		self.subscribers.notify(StorageUpdateKind::Replaced, &prefix, &key, &entry);
+		let record = self.prefixed_records.get_mut(&prefix)?.get_mut(&key)?;
+
+		Some(std::mem::replace(record, entry))
 	}
 
 	fn get_prefix<Q: ?Sized + Hash + Eq, PQ: ?Sized + Hash + Eq>(&self, prefix: &PQ, key: &Q) -> Option<StorageEntry>
@@ -500,6 +624,10 @@ where
 	fn prefixes(&self) -> Vec<P> {
 		self.prefixed_records.keys().cloned().collect()
 	}
+
+	fn subscribe_prefix(&mut self, prefix: P) -> Receiver<StorageUpdate<K, P>> {
+		self.subscribers.subscribe(prefix)
+	}
 }
 
 #[cfg(test)]
@@ -571,6 +699,7 @@ mod tests {
 
 		// 10 keys per block * 2 max blocks.
 		assert_eq!(st.len(), 20);
+		assert_eq!(st.blocks_len(), 2);
 	}
 
 	#[test]
@@ -614,4 +743,55 @@ mod tests {
 		let prefixed_search = st.prefixed_keys("no");
 		assert_eq!(prefixed_search.len(), 0);
 	}
+
+	#[test]
+	fn test_prefix_subscriptions() {
+		let mut st = HashedPrefixedRecordsStorage::new(RecordsStorageConfig { max_blocks: 10 });
+		let mut updates = st.subscribe_prefix(100_u32);
+		let dropped = st.subscribe_prefix(100_u32);
+		drop(dropped);
+
+		st.insert_prefix(100, "a".to_owned(), StorageEntry::new_onchain(1.into(), 1))
+			.unwrap();
+		st.insert_prefix(200, "b".to_owned(), StorageEntry::new_onchain(1.into(), 2))
+			.unwrap();
+		st.replace_prefixed(&100, "a", StorageEntry::new_onchain(2.into(), 3)).unwrap();
+		st.replace("a", StorageEntry::new_onchain(3.into(), 4)).unwrap();
+		// Replacing a missing entry is not an update
+		assert!(st.replace_prefixed(&100, "b", StorageEntry::new_onchain(3.into(), 5)).is_none());
+
+		let update = updates.try_recv().unwrap();
+		assert_eq!(update.kind, StorageUpdateKind::Inserted);
+		assert_eq!((update.prefix, update.key.as_str()), (100, "a"));
+		assert_eq!(update.entry.into_inner::<u32>().unwrap(), 1);
+		let update = updates.try_recv().unwrap();
+		assert_eq!(update.kind, StorageUpdateKind::Replaced);
+		assert_eq!(update.entry.into_inner::<u32>().unwrap(), 3);
+		assert_eq!(updates.try_recv().unwrap().entry.into_inner::<u32>().unwrap(), 4);
+		assert!(updates.try_recv().is_err());
+		assert_eq!(st.subscribers.senders.get(&100).map(|senders| senders.len()), Some(1));
+	}
+
+	#[test]
+	fn test_drops_lagging_and_closed_subscribers() {
+		let mut subscribers = PrefixSubscribers::<u32, u32>::default();
+		let mut lagging = subscribers.subscribe(100);
+		let closed = subscribers.subscribe(200);
+		drop(closed);
+		// Closed subscriptions are forgotten on the next subscription even without updates of their prefix
+		let _other = subscribers.subscribe(300);
+		assert!(!subscribers.senders.contains_key(&200));
+
+		let entry = StorageEntry::new_onchain(1.into(), 1);
+		for key in 0..=SUBSCRIPTION_QUEUE_SIZE as u32 {
+			subscribers.notify(StorageUpdateKind::Inserted, &100, &key, &entry);
+		}
+		assert!(!subscribers.senders.contains_key(&100));
+		let mut received = 0;
+		while lagging.try_recv().is_ok() {
+			received += 1;
+		}
+		assert_eq!(received, SUBSCRIPTION_QUEUE_SIZE);
+		assert!(matches!(lagging.try_recv(), Err(tokio::sync::mpsc::error::TryRecvError::Disconnected)));
+	}
 }
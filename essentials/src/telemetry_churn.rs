@@ -0,0 +1,381 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Node churn analytics from the telemetry feed
+//!
+//! The feed assigns a new id to a node every time it connects, so sessions are tracked by the node identity:
+//! its network id, or its name if the network id is not reported. A node that connects again is a reconnection,
+//! and a node reconnecting at least `flap_threshold` times within `flap_window` is flapping.
+//!
+//! After a gap in the feed the server sends all nodes again, the nodes that are sent within `resync_timeout`
+//! continue their sessions, the others are considered removed at the beginning of the gap.
+
+use crate::{
+	output::OutputRecord,
+	telemetry_feed::{AddedNode, FeedNodeId, NodeDetails, TelemetryFeed},
+	telemetry_subscription::TelemetryEvent,
+	types::Timestamp,
+};
+use serde::Serialize;
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt::{Display, Formatter},
+	time::Duration,
+};
+
+/// Window of the hourly rates
+const HOUR_MS: Timestamp = 3_600_000;
+
+/// Identity of a node which survives reconnections
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum NodeIdentity {
+	/// libp2p network id
+	NetworkId(String),
+	/// Node name, used if the network id is not reported
+	Name(String),
+}
+
+impl NodeIdentity {
+	pub fn new(details: &NodeDetails) -> Self {
+		match &details.network_id {
+			Some(network_id) => Self::NetworkId(network_id.clone()),
+			None => Self::Name(details.name.clone()),
+		}
+	}
+}
+
+/// Options of the churn analytics
+#[derive(Clone, Debug)]
+pub struct ChurnOptions {
+	/// Window in which reconnections are counted to detect flapping nodes
+	pub flap_window: Duration,
+	/// Number of reconnections within the window after which a node is flapping
+	pub flap_threshold: usize,
+	/// Time to wait for the nodes sent again after a gap in the feed
+	pub resync_timeout: Duration,
+}
+
+impl Default for ChurnOptions {
+	fn default() -> Self {
+		Self { flap_window: Duration::from_secs(3600), flap_threshold: 3, resync_timeout: Duration::from_secs(60) }
+	}
+}
+
+#[derive(Debug, Default)]
+struct NodeHistory {
+	name: String,
+	/// Start of the current session
+	connected_at: Option<Timestamp>,
+	/// Number of finished sessions
+	sessions: u64,
+	/// Recent reconnection times within the flap window
+	reconnects: VecDeque<Timestamp>,
+}
+
+/// A node reconnecting repeatedly
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FlappingNode {
+	pub identity: NodeIdentity,
+	pub name: String,
+	/// Number of reconnections within the flap window
+	pub reconnects: usize,
+}
+
+/// Churn metrics at a point of time
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ChurnReport {
+	/// Time since the first message
+	pub elapsed: Duration,
+	/// Nodes connected now
+	pub live_nodes: usize,
+	/// Nodes added in total, except the nodes sent again after gaps
+	pub added_total: u64,
+	/// Nodes removed in total
+	pub removed_total: u64,
+	/// Nodes added within the last hour
+	pub added_last_hour: usize,
+	/// Nodes removed within the last hour
+	pub removed_last_hour: usize,
+	/// Number of finished sessions
+	pub finished_sessions: u64,
+	/// Average length of finished sessions
+	pub average_session: Option<Duration>,
+	/// Flapping nodes, most reconnections first
+	pub flapping: Vec<FlappingNode>,
+}
+
+impl OutputRecord for ChurnReport {
+	const CSV_COLUMNS: &'static [&'static str] = &[
+		"elapsed.secs",
+		"elapsed.nanos",
+		"live_nodes",
+		"added_total",
+		"removed_total",
+		"added_last_hour",
+		"removed_last_hour",
+		"finished_sessions",
+		"average_session.secs",
+		"average_session.nanos",
+		"flapping",
+	];
+}
+
+impl Display for ChurnReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		writeln!(f, "Node churn over the last {}s:", self.elapsed.as_secs())?;
+		writeln!(f, "\tlive nodes: {}", self.live_nodes)?;
+		writeln!(
+			f,
+			"\tadded: {} ({} in the last hour), removed: {} ({} in the last hour)",
+			self.added_total, self.added_last_hour, self.removed_total, self.removed_last_hour
+		)?;
+		match self.average_session {
+			Some(average) =>
+				writeln!(f, "\taverage session: {}s over {} sessions", average.as_secs(), self.finished_sessions)?,
+			None => writeln!(f, "\taverage session: no finished sessions")?,
+		}
+		write!(f, "\tflapping nodes: {}", self.flapping.len())?;
+		for node in &self.flapping {
+			write!(f, "\n\t\t{} ({:?}): {} reconnections", node.name, node.identity, node.reconnects)?;
+		}
+		Ok(())
+	}
+}
+
+/// Tracks node sessions from telemetry events
+#[derive(Debug, Default)]
+pub struct ChurnTracker {
+	opts: ChurnOptions,
+	started_at: Option<Timestamp>,
+	/// Feed ids of connected nodes
+	ids: HashMap<FeedNodeId, NodeIdentity>,
+	nodes: HashMap<NodeIdentity, NodeHistory>,
+	added: VecDeque<Timestamp>,
+	removed: VecDeque<Timestamp>,
+	added_total: u64,
+	removed_total: u64,
+	sessions_length: Duration,
+	/// Nodes expected to be sent again after a gap which started at the time, until the deadline
+	resync: Option<(Timestamp, Timestamp)>,
+}
+
+impl ChurnTracker {
+	pub fn new(opts: ChurnOptions) -> Self {
+		Self { opts, ..Default::default() }
+	}
+
+	/// Handles an event received at `now`, in milliseconds
+	pub fn on_event(&mut self, event: &TelemetryEvent, now: Timestamp) {
+		self.started_at.get_or_insert(now);
+		self.maybe_finish_resync(now);
+		match event {
+			TelemetryEvent::NewMessage(TelemetryFeed::AddedNode(node)) => self.on_added(node, now),
+			TelemetryEvent::NewMessage(TelemetryFeed::RemovedNode(node)) => self.on_removed(node.node_id, now),
+			TelemetryEvent::Gap { from, to } => self.on_gap(*from, *to),
+			_ => {},
+		}
+	}
+
+	fn on_added(&mut self, node: &AddedNode, now: Timestamp) {
+		let identity = NodeIdentity::new(&node.details);
+		let history = self.nodes.entry(identity.clone()).or_default();
+		history.name = node.details.name.clone();
+		self.ids.insert(node.node_id, identity);
+
+		if self.resync.is_some() && history.connected_at.is_some() {
+			// Sent again after a gap, the session continues
+			return
+		}
+		if let Some(connected_at) = history.connected_at.take() {
+			// Added twice without a removal, e.g. connected twice with the same identity
+			history.sessions += 1;
+			self.sessions_length += Duration::from_millis(now.saturating_sub(connected_at));
+		}
+		if history.sessions > 0 {
+			history.reconnects.push_back(now);
+		}
+		history.connected_at = Some(now);
+		self.added.push_back(now);
+		self.added_total += 1;
+	}
+
+	fn on_removed(&mut self, node_id: FeedNodeId, now: Timestamp) {
+		let Some(identity) = self.ids.remove(&node_id) else { return };
+		if let Some(history) = self.nodes.get_mut(&identity) {
+			self.removed.push_back(now);
+			self.removed_total += 1;
+			if let Some(connected_at) = history.connected_at.take() {
+				history.sessions += 1;
+				self.sessions_length += Duration::from_millis(now.saturating_sub(connected_at));
+			}
+		}
+	}
+
+	fn on_gap(&mut self, from: Timestamp, to: Timestamp) {
+		// Feed ids are not valid in the new connection
+		self.ids.clear();
+		// A gap during the resync keeps its beginning
+		let from = self.resync.map_or(from, |(resync_from, _)| resync_from);
+		self.resync = Some((from, to + self.opts.resync_timeout.as_millis() as Timestamp));
+	}
+
+	fn maybe_finish_resync(&mut self, now: Timestamp) {
+		let Some((gap_from, deadline)) = self.resync else { return };
+		if now < deadline {
+			return
+		}
+		self.resync = None;
+
+		let connected: Vec<&NodeIdentity> = self.ids.values().collect();
+		for (identity, history) in self.nodes.iter_mut() {
+			if connected.contains(&identity) {
+				continue
+			}
+			if let Some(connected_at) = history.connected_at.take() {
+				history.sessions += 1;
+				self.sessions_length += Duration::from_millis(gap_from.saturating_sub(connected_at));
+				self.removed.push_back(gap_from);
+				self.removed_total += 1;
+			}
+		}
+	}
+
+	/// Returns the churn metrics at `now`, in milliseconds
+	pub fn report(&mut self, now: Timestamp) -> ChurnReport {
+		self.maybe_finish_resync(now);
+		let hour_ago = now.saturating_sub(HOUR_MS);
+		self.added.retain(|v| *v > hour_ago);
+		self.removed.retain(|v| *v > hour_ago);
+
+		let flap_window_start = now.saturating_sub(self.opts.flap_window.as_millis() as Timestamp);
+		let mut flapping = vec![];
+		let mut finished_sessions = 0;
+		let mut live_nodes = 0;
+		for (identity, history) in self.nodes.iter_mut() {
+			history.reconnects.retain(|v| *v > flap_window_start);
+			finished_sessions += history.sessions;
+			if history.connected_at.is_some() {
+				live_nodes += 1;
+			}
+			if history.reconnects.len() >= self.opts.flap_threshold {
+				flapping.push(FlappingNode {
+					identity: identity.clone(),
+					name: history.name.clone(),
+					reconnects: history.reconnects.len(),
+				});
+			}
+		}
+		flapping.sort_by(|a, b| b.reconnects.cmp(&a.reconnects).then_with(|| a.name.cmp(&b.name)));
+
+		ChurnReport {
+			elapsed: Duration::from_millis(self.started_at.map_or(0, |started_at| now.saturating_sub(started_at))),
+			live_nodes,
+			added_total: self.added_total,
+			removed_total: self.removed_total,
+			added_last_hour: self.added.len(),
+			removed_last_hour: self.removed.len(),
+			finished_sessions,
+			average_session: (finished_sessions > 0)
+				.then(|| self.sessions_length / finished_sessions.try_into().unwrap_or(u32::MAX)),
+			flapping,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::telemetry_feed::RemovedNode;
+
+	const MINUTE: Timestamp = 60_000;
+
+	fn added(node_id: FeedNodeId, network_id: &str) -> TelemetryEvent {
+		let msg = format!(
+			r#"[3,[{},["node-{}","Parity Polkadot","1.0.0",null,"{}",null,null,null],[1,0],[[0]],[[0],[0],[0]],[1,"0x0000000000000000000000000000000000000000000000000000000000000000",0,0,null],null,null]]"#,
+			node_id, network_id, network_id
+		);
+		TelemetryFeed::from_bytes(msg.as_bytes())
+			.unwrap()
+			.pop()
+			.map(TelemetryEvent::NewMessage)
+			.unwrap()
+	}
+
+	fn removed(node_id: FeedNodeId) -> TelemetryEvent {
+		TelemetryEvent::NewMessage(TelemetryFeed::RemovedNode(RemovedNode { node_id }))
+	}
+
+	#[test]
+	fn test_tracks_sessions() {
+		let mut tracker = ChurnTracker::new(ChurnOptions::default());
+		tracker.on_event(&added(1, "a"), 0);
+		tracker.on_event(&added(2, "b"), 0);
+		tracker.on_event(&removed(1), 10 * MINUTE);
+		tracker.on_event(&added(3, "a"), 40 * MINUTE);
+		tracker.on_event(&removed(2), 50 * MINUTE);
+
+		let report = tracker.report(90 * MINUTE);
+		assert_eq!(report.elapsed, Duration::from_secs(90 * 60));
+		assert_eq!(report.live_nodes, 1);
+		assert_eq!(report.added_total, 3);
+		assert_eq!(report.removed_total, 2);
+		assert_eq!(report.added_last_hour, 1);
+		assert_eq!(report.removed_last_hour, 1);
+		assert_eq!(report.finished_sessions, 2);
+		assert_eq!(report.average_session, Some(Duration::from_secs(30 * 60)));
+		assert!(report.flapping.is_empty());
+	}
+
+	#[test]
+	fn test_detects_flapping_nodes() {
+		let opts = ChurnOptions { flap_threshold: 2, ..Default::default() };
+		let mut tracker = ChurnTracker::new(opts);
+		for (i, node_id) in (0..3).enumerate() {
+			let now = i as Timestamp * MINUTE;
+			tracker.on_event(&added(node_id, "a"), now);
+			tracker.on_event(&removed(node_id), now + 1000);
+		}
+		tracker.on_event(&added(10, "b"), 0);
+
+		let report = tracker.report(3 * MINUTE);
+		assert_eq!(report.flapping.len(), 1);
+		assert_eq!(report.flapping[0].identity, NodeIdentity::NetworkId("a".to_string()));
+		assert_eq!(report.flapping[0].reconnects, 2);
+
+		// Reconnections leave the window
+		assert!(tracker.report(2 * HOUR_MS).flapping.is_empty());
+	}
+
+	#[test]
+	fn test_continues_sessions_after_gap() {
+		let mut tracker = ChurnTracker::new(ChurnOptions::default());
+		tracker.on_event(&added(1, "a"), 0);
+		tracker.on_event(&added(2, "b"), 0);
+		tracker.on_event(&TelemetryEvent::Gap { from: 10 * MINUTE, to: 11 * MINUTE }, 11 * MINUTE);
+		// The server sends the nodes again with new ids
+		tracker.on_event(&added(5, "a"), 11 * MINUTE);
+
+		let report = tracker.report(20 * MINUTE);
+		assert_eq!(report.added_total, 2);
+		assert_eq!(report.live_nodes, 1);
+		// The node which is not sent again is removed at the beginning of the gap
+		assert_eq!(report.removed_total, 1);
+		assert_eq!(report.average_session, Some(Duration::from_secs(10 * 60)));
+
+		tracker.on_event(&removed(5), 30 * MINUTE);
+		assert_eq!(tracker.report(30 * MINUTE).removed_total, 2);
+	}
+}
@@ -266,14 +266,14 @@ pub struct Version(usize);
 
 #[derive(Debug, PartialEq)]
 pub struct BestBlock {
-	block_number: BlockNumber,
+	pub block_number: BlockNumber,
 	timestamp: Timestamp,
 	avg_block_time: Option<u64>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct BestFinalized {
-	block_number: BlockNumber,
+	pub block_number: BlockNumber,
 	block_hash: H256,
 }
 
@@ -284,10 +284,10 @@ pub struct AddedNode {
 	stats: NodeStats,
 	io: NodeIO,
 	hardware: NodeHardware,
-	block_details: BlockDetails,
+	pub block_details: BlockDetails,
 	location: Option<NodeLocation>,
 	startup_time: Option<Timestamp>,
-	hwbench: Option<NodeHwBench>,
+	pub hwbench: Option<NodeHwBench>,
 }
 
 impl std::fmt::Display for AddedNode {
@@ -324,7 +324,7 @@ pub struct LocatedNode {
 #[derive(Debug, PartialEq)]
 pub struct ImportedBlock {
 	pub node_id: FeedNodeId,
-	block_details: BlockDetails,
+	pub block_details: BlockDetails,
 }
 
 #[derive(Debug, PartialEq)]
@@ -371,7 +371,7 @@ pub struct RemovedChain {
 
 #[derive(Debug, PartialEq)]
 pub struct SubscribedTo {
-	genesis_hash: H256,
+	pub genesis_hash: H256,
 }
 
 #[derive(Debug, PartialEq)]
@@ -430,17 +430,37 @@ pub enum TelemetryFeed {
 	UnknownValue(UnknownValue),
 }
 
+/// Errors of decoding telemetry feed frames, which come from servers we don't control
+#[derive(Debug, thiserror::Error)]
+pub enum TelemetryFeedError {
+	#[error("frame is not a JSON array: {0}")]
+	InvalidFrame(#[source] serde_json::Error),
+	#[error("invalid action code: {0}")]
+	InvalidAction(String),
+	#[error("action {0} has no payload")]
+	MissingPayload(u8),
+	#[error("invalid payload of action {action}: {source}")]
+	InvalidPayload {
+		action: u8,
+		#[source]
+		source: serde_json::Error,
+	},
+}
+
 impl TelemetryFeed {
 	/// Decodes a slice of bytes into a vector of feed messages.
 	/// Telemetry sends encoded messages in an array format like [0,32,1,[14783932,1679657352067,5998]]
 	/// where odd values represent action codes and even values represent their payloads.
-	pub fn from_bytes(bytes: &[u8]) -> color_eyre::Result<Vec<TelemetryFeed>> {
-		let v: Vec<&RawValue> = serde_json::from_slice(bytes)?;
+	pub fn from_bytes(bytes: &[u8]) -> Result<Vec<TelemetryFeed>, TelemetryFeedError> {
+		let v: Vec<&RawValue> = serde_json::from_slice(bytes).map_err(TelemetryFeedError::InvalidFrame)?;
 
-		let mut feed_messages = vec![];
-		for raw in v.chunks_exact(2) {
-			let action: u8 = serde_json::from_str(raw[0].get())?;
-			let msg = TelemetryFeed::decode(action, raw[1])?;
+		let mut feed_messages = Vec::with_capacity(v.len() / 2);
+		for raw in v.chunks(2) {
+			let action: u8 = serde_json::from_str(raw[0].get())
+				.map_err(|_| TelemetryFeedError::InvalidAction(raw[0].get().to_string()))?;
+			let raw_payload = raw.get(1).ok_or(TelemetryFeedError::MissingPayload(action))?;
+			let msg = TelemetryFeed::decode(action, raw_payload)
+				.map_err(|source| TelemetryFeedError::InvalidPayload { action, source })?;
 
 			feed_messages.push(msg);
 		}
@@ -449,7 +469,7 @@ impl TelemetryFeed {
 	}
 
 	// Deserializes the feed message to a value based on the "action" key
-	fn decode(action: u8, raw_payload: &RawValue) -> color_eyre::Result<TelemetryFeed> {
+	fn decode(action: u8, raw_payload: &RawValue) -> serde_json::Result<TelemetryFeed> {
 		let feed_message = match action {
 			// Version:
 			0 => {
@@ -590,6 +610,9 @@ impl TelemetryFeed {
 #[cfg(test)]
 mod test {
 	use super::*;
+	use arbitrary::{Arbitrary, Unstructured};
+	use rand::{rngs::StdRng, RngCore, SeedableRng};
+	use serde_json::Value;
 
 	#[test]
 	fn decode_version_best_block_best_finalized() {
@@ -887,6 +910,75 @@ mod test {
 		);
 	}
 
+	/// JSON values generated from arbitrary bytes, `Null` goes first to stop the recursion when the bytes run out
+	#[derive(Arbitrary, Debug)]
+	enum ArbitraryJson {
+		Null,
+		Bool(bool),
+		Number(u64),
+		Float(f64),
+		String(String),
+		Array(Vec<ArbitraryJson>),
+	}
+
+	impl From<&ArbitraryJson> for Value {
+		fn from(value: &ArbitraryJson) -> Self {
+			match value {
+				ArbitraryJson::Null => Value::Null,
+				ArbitraryJson::Bool(v) => Value::from(*v),
+				ArbitraryJson::Number(v) => Value::from(*v),
+				ArbitraryJson::Float(v) => serde_json::Number::from_f64(*v).map_or(Value::Null, Value::Number),
+				ArbitraryJson::String(v) => Value::from(v.as_str()),
+				ArbitraryJson::Array(v) => Value::Array(v.iter().map(Value::from).collect()),
+			}
+		}
+	}
+
+	#[test]
+	fn decode_arbitrary_frames() {
+		let mut buf = [0_u8; 256];
+		for seed in 0..2000 {
+			StdRng::seed_from_u64(seed).fill_bytes(&mut buf);
+			let frames = Vec::<(u8, ArbitraryJson)>::arbitrary(&mut Unstructured::new(&buf)).unwrap();
+			let bytes = serde_json::to_vec(&Value::Array(
+				frames
+					.iter()
+					.flat_map(|(action, payload)| [Value::from(*action), Value::from(payload)])
+					.collect(),
+			))
+			.unwrap();
+
+			match TelemetryFeed::from_bytes(&bytes) {
+				Ok(messages) => assert_eq!(messages.len(), frames.len(), "seed {}", seed),
+				Err(TelemetryFeedError::InvalidPayload { action, .. }) =>
+					assert!(frames.iter().any(|(v, _)| *v == action), "seed {}", seed),
+				Err(e) => panic!("seed {}: unexpected error {:?}", seed, e),
+			}
+		}
+	}
+
+	#[test]
+	fn decode_malformed_frames() {
+		let msg = r#"[0,32,1,[14783932,1679657352067,5998],2,[14783934,"0x0000000000000000000000000000000000000000000000000000000000000000"]]"#;
+		for len in 0..msg.len() {
+			assert!(matches!(
+				TelemetryFeed::from_bytes(&msg.as_bytes()[..len]),
+				Err(TelemetryFeedError::InvalidFrame(_))
+			));
+		}
+
+		assert!(matches!(TelemetryFeed::from_bytes(br#"{"0": 32}"#), Err(TelemetryFeedError::InvalidFrame(_))));
+		assert!(matches!(TelemetryFeed::from_bytes(b"[256,32]"), Err(TelemetryFeedError::InvalidAction(_))));
+		assert!(matches!(TelemetryFeed::from_bytes(b"[0,32,1]"), Err(TelemetryFeedError::MissingPayload(1))));
+		assert!(matches!(
+			TelemetryFeed::from_bytes(b"[1,[1,2]]"),
+			Err(TelemetryFeedError::InvalidPayload { action: 1, .. })
+		));
+		// Too deep to be decoded without exhausting the stack
+		let deep = format!("[3,{}{}]", "[".repeat(10_000), "]".repeat(10_000));
+		assert!(TelemetryFeed::from_bytes(deep.as_bytes()).is_err());
+	}
+
 	#[test]
 	fn decode_unknown() {
 		let msg = r#"[0,32,42,["0x0000000000000000000000000000000000000000000000000000000000000000", 1]]"#;
@@ -0,0 +1,277 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Composite health score of a chain from the telemetry feed
+//!
+//! The score combines three components, each between 0 and 1:
+//! - block propagation: the share of nodes which imported their last block within `max_propagation`
+//! - finality: 1 while the finality lag of the chain is within `healthy_finality_lag`, falling linearly to 0 at
+//!   `max_finality_lag`
+//! - stale nodes: the share of nodes which are not stale
+//!
+//! The node components are shares of nodes rather than averages of their values, so every node has the same
+//! weight and a few outliers can't hide a degradation of many nodes. The score is their mean scaled to 0..100.
+
+use crate::{
+	output::OutputRecord,
+	telemetry_feed::{FeedNodeId, TelemetryFeed},
+	telemetry_subscription::TelemetryEvent,
+	types::{BlockNumber, H256},
+};
+use serde::Serialize;
+use std::{
+	collections::HashMap,
+	fmt::{Display, Formatter},
+	time::Duration,
+};
+
+/// Options of the health score
+#[derive(Clone, Debug)]
+pub struct HealthOptions {
+	/// Propagation time after that a block is imported late
+	pub max_propagation: Duration,
+	/// Finality lag which is still healthy
+	pub healthy_finality_lag: u32,
+	/// Finality lag at which the finality component is 0
+	pub max_finality_lag: u32,
+}
+
+impl Default for HealthOptions {
+	fn default() -> Self {
+		Self { max_propagation: Duration::from_secs(1), healthy_finality_lag: 3, max_finality_lag: 30 }
+	}
+}
+
+#[derive(Debug, Default)]
+struct NodeHealth {
+	/// Propagation time of the last imported block in milliseconds, not reported for the blocks authored by the node
+	propagation_time: Option<u64>,
+	is_stale: bool,
+}
+
+/// Health of a chain at a point of time
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HealthReport {
+	/// Name of the chain if announced by the feed
+	pub chain: Option<String>,
+	/// Nodes connected now
+	pub nodes: usize,
+	/// Best block of the chain
+	pub best_block: Option<BlockNumber>,
+	/// Best finalized block of the chain
+	pub finalized_block: Option<BlockNumber>,
+	/// Share of nodes which imported their last block in time, of the nodes reporting a propagation time
+	pub propagation: f64,
+	/// Finality component
+	pub finality: f64,
+	/// Share of stale nodes
+	pub stale_ratio: f64,
+	/// Composite score, 0..100
+	pub score: f64,
+}
+
+impl HealthReport {
+	pub fn finality_lag(&self) -> Option<u32> {
+		Some(self.best_block?.saturating_sub(self.finalized_block?))
+	}
+}
+
+impl OutputRecord for HealthReport {
+	const CSV_COLUMNS: &'static [&'static str] =
+		&["chain", "nodes", "best_block", "finalized_block", "propagation", "finality", "stale_ratio", "score"];
+}
+
+impl Display for HealthReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		writeln!(f, "=== {} health: {:.0}/100 ===", self.chain.as_deref().unwrap_or("Chain"), self.score)?;
+		writeln!(f, "\tnodes: {}", self.nodes)?;
+		writeln!(f, "\tblock propagation: {:.1}% of nodes in time", self.propagation * 100.0)?;
+		match self.finality_lag() {
+			Some(lag) => writeln!(f, "\tfinality lag: {} blocks, score {:.2}", lag, self.finality)?,
+			None => writeln!(f, "\tfinality lag: unknown")?,
+		}
+		write!(f, "\tstale nodes: {:.1}%", self.stale_ratio * 100.0)
+	}
+}
+
+/// Tracks the health of a chain from telemetry events
+#[derive(Debug, Default)]
+pub struct HealthTracker {
+	opts: HealthOptions,
+	nodes: HashMap<FeedNodeId, NodeHealth>,
+	best_block: Option<BlockNumber>,
+	finalized_block: Option<BlockNumber>,
+	/// Names of the chains announced by the feed
+	chains: HashMap<H256, String>,
+	/// Genesis hash of the subscribed chain
+	subscribed_to: Option<H256>,
+}
+
+impl HealthTracker {
+	pub fn new(opts: HealthOptions) -> Self {
+		Self { opts, ..Default::default() }
+	}
+
+	pub fn on_event(&mut self, event: &TelemetryEvent) {
+		let message = match event {
+			TelemetryEvent::NewMessage(message) => message,
+			// The server sends all nodes again after resubscription
+			TelemetryEvent::Gap { .. } => {
+				self.nodes.clear();
+				return
+			},
+		};
+		match message {
+			TelemetryFeed::AddedNode(node) => {
+				self.nodes.insert(
+					node.node_id,
+					NodeHealth { propagation_time: node.block_details.propagation_time, is_stale: false },
+				);
+			},
+			TelemetryFeed::RemovedNode(node) => {
+				self.nodes.remove(&node.node_id);
+			},
+			TelemetryFeed::ImportedBlock(block) => {
+				let health = self.nodes.entry(block.node_id).or_default();
+				health.propagation_time = block.block_details.propagation_time;
+				health.is_stale = false;
+			},
+			TelemetryFeed::StaleNode(node) =>
+				if let Some(health) = self.nodes.get_mut(&node.node_id) {
+					health.is_stale = true;
+				},
+			TelemetryFeed::BestBlock(block) => self.best_block = Some(block.block_number),
+			TelemetryFeed::BestFinalized(block) => self.finalized_block = Some(block.block_number),
+			TelemetryFeed::AddedChain(chain) => {
+				self.chains.insert(chain.genesis_hash, chain.name.clone());
+			},
+			TelemetryFeed::SubscribedTo(chain) => self.subscribed_to = Some(chain.genesis_hash),
+			_ => {},
+		}
+	}
+
+	pub fn report(&self) -> HealthReport {
+		let max_propagation = self.opts.max_propagation.as_millis() as u64;
+		let propagation_times: Vec<u64> = self.nodes.values().filter_map(|node| node.propagation_time).collect();
+		let propagation =
+			share(propagation_times.iter().filter(|time| **time <= max_propagation).count(), propagation_times.len());
+		let stale_ratio = 1.0 - share(self.nodes.values().filter(|node| !node.is_stale).count(), self.nodes.len());
+		let mut report = HealthReport {
+			chain: self.subscribed_to.and_then(|hash| self.chains.get(&hash).cloned()),
+			nodes: self.nodes.len(),
+			best_block: self.best_block,
+			finalized_block: self.finalized_block,
+			propagation,
+			finality: 1.0,
+			stale_ratio,
+			score: 0.0,
+		};
+		if let Some(lag) = report.finality_lag() {
+			report.finality = self.finality_score(lag);
+		}
+		report.score = (report.propagation + report.finality + (1.0 - report.stale_ratio)) / 3.0 * 100.0;
+
+		report
+	}
+
+	fn finality_score(&self, lag: u32) -> f64 {
+		let healthy = self.opts.healthy_finality_lag;
+		let max = self.opts.max_finality_lag.max(healthy + 1);
+		if lag <= healthy {
+			1.0
+		} else {
+			max.saturating_sub(lag) as f64 / (max - healthy) as f64
+		}
+	}
+}
+
+/// Share of `count` in `total`, an empty set is healthy
+fn share(count: usize, total: usize) -> f64 {
+	if total == 0 {
+		1.0
+	} else {
+		count as f64 / total as f64
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::telemetry_feed::StaleNode;
+
+	fn imported(node_id: FeedNodeId, propagation_time: Option<u64>) -> TelemetryEvent {
+		let msg = format!(
+			r#"[6,[{},[100,"0x0000000000000000000000000000000000000000000000000000000000000000",6000,0,{}]]]"#,
+			node_id,
+			propagation_time.map_or("null".to_string(), |time| time.to_string())
+		);
+		TelemetryFeed::from_bytes(msg.as_bytes())
+			.unwrap()
+			.pop()
+			.map(TelemetryEvent::NewMessage)
+			.unwrap()
+	}
+
+	fn finality(best: BlockNumber, finalized: BlockNumber) -> Vec<TelemetryEvent> {
+		let msg = format!(
+			r#"[1,[{},0,null],2,[{},"0x0000000000000000000000000000000000000000000000000000000000000000"]]"#,
+			best, finalized
+		);
+		TelemetryFeed::from_bytes(msg.as_bytes())
+			.unwrap()
+			.into_iter()
+			.map(TelemetryEvent::NewMessage)
+			.collect()
+	}
+
+	#[test]
+	fn test_healthy_chain() {
+		let mut tracker = HealthTracker::new(HealthOptions::default());
+		assert_eq!(tracker.report().score, 100.0);
+
+		tracker.on_event(&imported(1, Some(200)));
+		tracker.on_event(&imported(2, None));
+		for event in finality(100, 98) {
+			tracker.on_event(&event);
+		}
+		let report = tracker.report();
+		assert_eq!((report.nodes, report.finality_lag()), (2, Some(2)));
+		assert_eq!(report.score, 100.0);
+	}
+
+	#[test]
+	fn test_degraded_chain() {
+		let mut tracker = HealthTracker::new(HealthOptions::default());
+		for node_id in 1..=4 {
+			tracker.on_event(&imported(node_id, Some(if node_id == 1 { 3000 } else { 500 })));
+		}
+		tracker.on_event(&TelemetryEvent::NewMessage(TelemetryFeed::StaleNode(StaleNode { node_id: 2 })));
+		for event in finality(100, 84) {
+			tracker.on_event(&event);
+		}
+
+		let report = tracker.report();
+		assert_eq!(report.propagation, 0.75);
+		assert_eq!(report.stale_ratio, 0.25);
+		// The lag of 16 blocks is 14 of the 27 blocks between the healthy lag and the maximum away from the maximum
+		assert_eq!(report.finality, 14.0 / 27.0);
+		assert_eq!(report.score, (0.75 + 14.0 / 27.0 + 0.75) / 3.0 * 100.0);
+
+		// A node importing a block is not stale anymore
+		tracker.on_event(&imported(2, Some(500)));
+		assert_eq!(tracker.report().stale_ratio, 0.0);
+	}
+}
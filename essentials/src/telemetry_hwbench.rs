@@ -0,0 +1,425 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Hardware benchmark distributions from the telemetry feed
+//!
+//! Nodes report their hardware benchmark scores when they connect. The scores of a chain's nodes are aggregated
+//! into percentile distributions, and validators scoring below the reference hardware are flagged.
+
+use crate::{
+	output::OutputRecord,
+	telemetry_churn::NodeIdentity,
+	telemetry_feed::{AddedNode, NodeHwBench, TelemetryFeed},
+	telemetry_subscription::TelemetryEvent,
+};
+use serde::Serialize;
+use std::{
+	collections::HashMap,
+	fmt::{Display, Formatter},
+	io::Write,
+};
+
+/// Percentiles of the distributions
+pub const PERCENTILES: [u8; 5] = [10, 25, 50, 75, 90];
+
+/// A score of the hardware benchmark
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum HwBenchMetric {
+	Cpu,
+	Memory,
+	DiskSequentialWrite,
+	DiskRandomWrite,
+}
+
+impl HwBenchMetric {
+	pub const ALL: [HwBenchMetric; 4] =
+		[HwBenchMetric::Cpu, HwBenchMetric::Memory, HwBenchMetric::DiskSequentialWrite, HwBenchMetric::DiskRandomWrite];
+
+	/// Returns the name of the score in the feed
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			HwBenchMetric::Cpu => "cpu_hashrate_score",
+			HwBenchMetric::Memory => "memory_memcpy_score",
+			HwBenchMetric::DiskSequentialWrite => "disk_sequential_write_score",
+			HwBenchMetric::DiskRandomWrite => "disk_random_write_score",
+		}
+	}
+
+	/// Returns the score, disk scores are not reported if the node's database path is not benchmarked
+	pub fn score(&self, hwbench: &NodeHwBench) -> Option<u64> {
+		match self {
+			HwBenchMetric::Cpu => Some(hwbench.cpu_hashrate_score),
+			HwBenchMetric::Memory => Some(hwbench.memory_memcpy_score),
+			HwBenchMetric::DiskSequentialWrite => hwbench.disk_sequential_write_score,
+			HwBenchMetric::DiskRandomWrite => hwbench.disk_random_write_score,
+		}
+	}
+}
+
+/// Minimal scores, in MiB/s
+#[derive(Clone, Debug, PartialEq)]
+pub struct HwBenchThresholds {
+	pub cpu_hashrate_score: u64,
+	pub memory_memcpy_score: u64,
+	pub disk_sequential_write_score: u64,
+	pub disk_random_write_score: u64,
+}
+
+/// Scores of the reference hardware of Polkadot validators
+pub const REFERENCE_HW_THRESHOLDS: HwBenchThresholds = HwBenchThresholds {
+	cpu_hashrate_score: 1028,
+	memory_memcpy_score: 14666,
+	disk_sequential_write_score: 450,
+	disk_random_write_score: 200,
+};
+
+impl Default for HwBenchThresholds {
+	fn default() -> Self {
+		REFERENCE_HW_THRESHOLDS
+	}
+}
+
+impl HwBenchThresholds {
+	pub fn get(&self, metric: HwBenchMetric) -> u64 {
+		match metric {
+			HwBenchMetric::Cpu => self.cpu_hashrate_score,
+			HwBenchMetric::Memory => self.memory_memcpy_score,
+			HwBenchMetric::DiskSequentialWrite => self.disk_sequential_write_score,
+			HwBenchMetric::DiskRandomWrite => self.disk_random_write_score,
+		}
+	}
+
+	/// Returns the scores below the thresholds, with the thresholds
+	pub fn failed(&self, hwbench: &NodeHwBench) -> Vec<(HwBenchMetric, u64, u64)> {
+		HwBenchMetric::ALL
+			.into_iter()
+			.filter_map(|metric| {
+				let score = metric.score(hwbench)?;
+				let threshold = self.get(metric);
+				(score < threshold).then_some((metric, score, threshold))
+			})
+			.collect()
+	}
+}
+
+/// Hardware benchmark of a node
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeHwBenchEntry {
+	pub identity: NodeIdentity,
+	pub name: String,
+	/// The validator's authority key, if the node is a validator
+	pub validator: Option<String>,
+	pub hwbench: NodeHwBench,
+}
+
+/// Distribution of a score across nodes
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HwBenchDistribution {
+	pub metric: HwBenchMetric,
+	/// Number of nodes reported the score
+	pub count: usize,
+	pub min: u64,
+	pub max: u64,
+	/// Scores at `PERCENTILES`
+	pub percentiles: Vec<u64>,
+	/// Number of nodes below the threshold
+	pub below_threshold: usize,
+}
+
+impl HwBenchDistribution {
+	/// Returns `None` if no scores are given
+	pub fn new(metric: HwBenchMetric, mut scores: Vec<u64>, threshold: u64) -> Option<Self> {
+		if scores.is_empty() {
+			return None
+		}
+		scores.sort_unstable();
+
+		Some(Self {
+			metric,
+			count: scores.len(),
+			min: scores[0],
+			max: scores[scores.len() - 1],
+			percentiles: PERCENTILES.iter().map(|p| percentile(&scores, *p)).collect(),
+			below_threshold: scores.iter().filter(|score| **score < threshold).count(),
+		})
+	}
+}
+
+/// Nearest-rank percentile of sorted scores
+fn percentile(sorted: &[u64], p: u8) -> u64 {
+	let rank = (p as usize * sorted.len()).div_ceil(100).max(1);
+	sorted[rank - 1]
+}
+
+/// A validator with scores below the thresholds
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UnderpoweredValidator {
+	pub name: String,
+	pub validator: String,
+	/// Failed scores with their thresholds
+	pub failed: Vec<(HwBenchMetric, u64, u64)>,
+}
+
+/// Hardware benchmark report of a chain
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HwBenchReport {
+	/// Number of nodes seen
+	pub nodes_count: usize,
+	/// Number of nodes reported their hardware benchmark
+	pub benchmarked_count: usize,
+	pub distributions: Vec<HwBenchDistribution>,
+	/// Validators below the thresholds, sorted by name
+	pub underpowered: Vec<UnderpoweredValidator>,
+}
+
+impl OutputRecord for HwBenchReport {
+	const CSV_COLUMNS: &'static [&'static str] = &["nodes_count", "benchmarked_count", "distributions", "underpowered"];
+}
+
+impl Display for HwBenchReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		writeln!(
+			f,
+			"Hardware benchmarks of {} nodes out of {} seen, MiB/s:",
+			self.benchmarked_count, self.nodes_count
+		)?;
+		write!(f, "\t{:<30} {:>6} {:>8}", "score", "nodes", "min")?;
+		for p in PERCENTILES {
+			write!(f, " {:>8}", format!("p{}", p))?;
+		}
+		writeln!(f, " {:>8} {:>6}", "max", "below")?;
+		for distribution in &self.distributions {
+			write!(f, "\t{:<30} {:>6} {:>8}", distribution.metric.as_str(), distribution.count, distribution.min)?;
+			for value in &distribution.percentiles {
+				write!(f, " {:>8}", value)?;
+			}
+			writeln!(f, " {:>8} {:>6}", distribution.max, distribution.below_threshold)?;
+		}
+
+		write!(f, "Validators below the recommended hardware: {}", self.underpowered.len())?;
+		for validator in &self.underpowered {
+			let failed = validator
+				.failed
+				.iter()
+				.map(|(metric, score, threshold)| format!("{} {} < {}", metric.as_str(), score, threshold))
+				.collect::<Vec<_>>()
+				.join(", ");
+			write!(f, "\n\t{} ({}): {}", validator.name, validator.validator, failed)?;
+		}
+		Ok(())
+	}
+}
+
+/// Collects hardware benchmarks of the nodes from telemetry events
+#[derive(Debug, Default)]
+pub struct HwBenchCollector {
+	thresholds: HwBenchThresholds,
+	/// Number of nodes seen, including the ones without benchmarks
+	nodes: HashMap<NodeIdentity, Option<NodeHwBenchEntry>>,
+}
+
+impl HwBenchCollector {
+	pub fn new(thresholds: HwBenchThresholds) -> Self {
+		Self { thresholds, nodes: Default::default() }
+	}
+
+	pub fn on_event(&mut self, event: &TelemetryEvent) {
+		if let TelemetryEvent::NewMessage(TelemetryFeed::AddedNode(node)) = event {
+			self.on_added(node);
+		}
+	}
+
+	fn on_added(&mut self, node: &AddedNode) {
+		let identity = NodeIdentity::new(&node.details);
+		let entry = node.hwbench.as_ref().map(|hwbench| NodeHwBenchEntry {
+			identity: identity.clone(),
+			name: node.details.name.clone(),
+			validator: node.details.validator.clone(),
+			hwbench: hwbench.clone(),
+		});
+		// Keep the last benchmark if the node reconnected without one
+		let known = self.nodes.entry(identity).or_default();
+		if entry.is_some() || known.is_none() {
+			*known = entry;
+		}
+	}
+
+	/// Returns benchmarked nodes sorted by name
+	pub fn entries(&self) -> Vec<&NodeHwBenchEntry> {
+		let mut entries: Vec<&NodeHwBenchEntry> = self.nodes.values().flatten().collect();
+		entries.sort_by(|a, b| a.name.cmp(&b.name));
+		entries
+	}
+
+	pub fn report(&self) -> HwBenchReport {
+		let entries = self.entries();
+		let distributions = HwBenchMetric::ALL
+			.into_iter()
+			.filter_map(|metric| {
+				let scores = entries.iter().filter_map(|entry| metric.score(&entry.hwbench)).collect();
+				HwBenchDistribution::new(metric, scores, self.thresholds.get(metric))
+			})
+			.collect();
+		let underpowered = entries
+			.iter()
+			.filter_map(|entry| {
+				let validator = entry.validator.clone()?;
+				let failed = self.thresholds.failed(&entry.hwbench);
+				(!failed.is_empty()).then(|| UnderpoweredValidator { name: entry.name.clone(), validator, failed })
+			})
+			.collect();
+
+		HwBenchReport { nodes_count: self.nodes.len(), benchmarked_count: entries.len(), distributions, underpowered }
+	}
+
+	/// Writes the benchmarks of all nodes as CSV
+	pub fn write_csv(&self, mut writer: impl Write) -> std::io::Result<()> {
+		writeln!(
+			writer,
+			"name,network_id,validator,{},below_threshold",
+			HwBenchMetric::ALL.map(|metric| metric.as_str()).join(",")
+		)?;
+		for entry in self.entries() {
+			let network_id = match &entry.identity {
+				NodeIdentity::NetworkId(network_id) => network_id.as_str(),
+				NodeIdentity::Name(_) => "",
+			};
+			let scores =
+				HwBenchMetric::ALL.map(|metric| metric.score(&entry.hwbench).map_or(String::new(), |v| v.to_string()));
+			let below_threshold = self
+				.thresholds
+				.failed(&entry.hwbench)
+				.iter()
+				.map(|(metric, _, _)| metric.as_str())
+				.collect::<Vec<_>>()
+				.join(" ");
+			writeln!(
+				writer,
+				"{},{},{},{},{}",
+				csv_field(&entry.name),
+				csv_field(network_id),
+				csv_field(entry.validator.as_deref().unwrap_or_default()),
+				scores.join(","),
+				below_threshold
+			)?;
+		}
+		Ok(())
+	}
+}
+
+/// Quotes a field containing separators, quotes or line breaks
+fn csv_field(value: &str) -> String {
+	if value.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_string()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn added(
+		name: &str,
+		validator: Option<&str>,
+		hwbench: Option<(u64, u64, Option<u64>, Option<u64>)>,
+	) -> TelemetryEvent {
+		let hwbench = match hwbench {
+			Some((cpu, memory, disk_seq, disk_rnd)) => serde_json::json!({
+				"cpu_hashrate_score": cpu,
+				"memory_memcpy_score": memory,
+				"disk_sequential_write_score": disk_seq,
+				"disk_random_write_score": disk_rnd,
+			}),
+			None => serde_json::Value::Null,
+		};
+		let msg = serde_json::json!([
+			3,
+			[
+				1,
+				[name, "Parity Polkadot", "1.0.0", validator, format!("id-{}", name), null, null, hwbench],
+				[1, 0],
+				[[0]],
+				[[0], [0], [0]],
+				[1, "0x0000000000000000000000000000000000000000000000000000000000000000", 0, 0, null],
+				null,
+				null
+			]
+		]);
+		TelemetryFeed::from_bytes(msg.to_string().as_bytes())
+			.unwrap()
+			.pop()
+			.map(TelemetryEvent::NewMessage)
+			.unwrap()
+	}
+
+	#[test]
+	fn test_calculates_percentiles() {
+		let scores: Vec<u64> = (1..=10).collect();
+		assert_eq!(percentile(&scores, 10), 1);
+		assert_eq!(percentile(&scores, 25), 3);
+		assert_eq!(percentile(&scores, 50), 5);
+		assert_eq!(percentile(&scores, 90), 9);
+		assert_eq!(percentile(&[42], 10), 42);
+
+		assert!(HwBenchDistribution::new(HwBenchMetric::Cpu, vec![], 0).is_none());
+		let distribution = HwBenchDistribution::new(HwBenchMetric::Cpu, vec![5, 1, 3], 2).unwrap();
+		assert_eq!((distribution.min, distribution.max, distribution.below_threshold), (1, 5, 1));
+		assert_eq!(distribution.percentiles, vec![1, 1, 3, 5, 5]);
+	}
+
+	#[test]
+	fn test_flags_underpowered_validators() {
+		let mut collector = HwBenchCollector::new(HwBenchThresholds::default());
+		collector.on_event(&added("fast", Some("validator-1"), Some((2000, 20000, Some(1000), Some(500)))));
+		collector.on_event(&added("slow", Some("validator-2"), Some((500, 20000, Some(100), None))));
+		collector.on_event(&added("slow-full-node", None, Some((500, 20000, None, None))));
+		collector.on_event(&added("unknown", Some("validator-3"), None));
+
+		let report = collector.report();
+		assert_eq!((report.nodes_count, report.benchmarked_count), (4, 3));
+		assert_eq!(report.distributions.len(), 4);
+		assert_eq!(report.distributions[0].count, 3);
+		assert_eq!(report.distributions[0].below_threshold, 2);
+		// Only one node reported the random write score
+		assert_eq!(report.distributions[3].count, 1);
+		assert_eq!(
+			report.underpowered,
+			vec![UnderpoweredValidator {
+				name: "slow".to_string(),
+				validator: "validator-2".to_string(),
+				failed: vec![(HwBenchMetric::Cpu, 500, 1028), (HwBenchMetric::DiskSequentialWrite, 100, 450)],
+			}]
+		);
+	}
+
+	#[test]
+	fn test_writes_csv() {
+		let mut collector = HwBenchCollector::default();
+		collector.on_event(&added("node, \"quoted\"", Some("validator-1"), Some((500, 20000, None, None))));
+		// Reconnected without a benchmark
+		collector.on_event(&added("node, \"quoted\"", Some("validator-1"), None));
+
+		let mut csv = vec![];
+		collector.write_csv(&mut csv).unwrap();
+		assert_eq!(
+			String::from_utf8(csv).unwrap(),
+			"name,network_id,validator,cpu_hashrate_score,memory_memcpy_score,disk_sequential_write_score,disk_random_write_score,below_threshold\n\
+			\"node, \"\"quoted\"\"\",\"id-node, \"\"quoted\"\"\",validator-1,500,20000,,,cpu_hashrate_score\n"
+		);
+	}
+}
@@ -15,15 +15,14 @@
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use super::telemetry_feed::TelemetryFeed;
+use super::telemetry_feed::{TelemetryFeed, TelemetryFeedError};
 use crate::{
 	constants::MAX_MSG_QUEUE_SIZE,
 	consumer::{EventConsumerInit, EventStream},
 	telemetry_feed::AddedChain,
-	types::H256,
+	types::{Timestamp, H256},
 };
 use async_trait::async_trait;
-use color_eyre::Report;
 use futures::{SinkExt, Stream, StreamExt};
 use itertools::Itertools;
 use log::{debug, info, warn};
@@ -32,14 +31,23 @@ use std::{
 	cmp::{min, Reverse},
 	collections::HashMap,
 	io::{stdin, BufRead},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+	net::TcpStream,
+	sync::broadcast::{Receiver as BroadcastReceiver, Sender as BroadcastSender},
 };
-use tokio::{net::TcpStream, sync::broadcast::Sender as BroadcastSender};
 use tokio_tungstenite::{
 	connect_async,
 	tungstenite::{Error as WsError, Message},
 	MaybeTlsStream, WebSocketStream,
 };
 
+/// Delay before the first reconnection attempt, doubled after every failed attempt
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+/// Maximum delay between reconnection attempts
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(60);
+
 struct TelemetryStream(WebSocketStream<MaybeTlsStream<TcpStream>>);
 
 impl TelemetryStream {
@@ -71,6 +79,9 @@ impl Stream for TelemetryStream {
 pub enum TelemetryEvent {
 	/// New message
 	NewMessage(TelemetryFeed),
+	/// The connection was lost and restored, messages between these times (in milliseconds) were missed.
+	/// The server sends the state of the chain again after resubscription.
+	Gap { from: Timestamp, to: Timestamp },
 }
 
 pub struct TelemetrySubscription {
@@ -126,12 +137,29 @@ impl TelemetrySubscription {
 			Ok(v) => v,
 			Err(e) => return on_stream_error(e),
 		};
-		let mut subscribed: bool = false;
+		let mut subscribed_to: Option<H256> = None;
 		let mut chains: HashMap<H256, AddedChain> = Default::default();
 
 		loop {
 			tokio::select! {
-				Some(Ok(msg)) = stream.next() => {
+				msg = stream.next() => {
+					let msg = match msg {
+						Some(Ok(msg)) => msg,
+						disconnected => {
+							if let Some(Err(e)) = disconnected {
+								on_stream_error(e);
+							}
+							let from = now_ms();
+							stream = match reconnect(&url, subscribed_to, &mut shutdown_rx).await {
+								Some(v) => v,
+								None => return on_ctrl_c(),
+							};
+							if let Err(e) = update_channel.send(TelemetryEvent::Gap { from, to: now_ms() }).await {
+								return on_consumer_error(e);
+							}
+							continue;
+						},
+					};
 					let bytes = match msg {
 						Message::Text(text) => text.into_bytes(),
 						Message::Binary(bytes) => bytes,
@@ -145,7 +173,7 @@ impl TelemetrySubscription {
 
 					for message in feed.unwrap() {
 						debug!("[telemetry] {:?}", message);
-						if !subscribed {
+						if subscribed_to.is_none() {
 							if let TelemetryFeed::AddedChain(chain) = &message {
 								chains.insert(chain.genesis_hash, chain.clone());
 							}
@@ -155,13 +183,13 @@ impl TelemetrySubscription {
 						}
 					}
 
-					if !subscribed {
+					if subscribed_to.is_none() {
 						match choose_chain(&chains, &maybe_chain_name).await {
 							Ok(hash) => {
 								if let Err(e) = stream.subscribe_to(&hash).await {
 									on_stream_error(e);
 								} else {
-									subscribed = true;
+									subscribed_to = Some(hash);
 								}
 							},
 							Err(e) => {
@@ -178,6 +206,49 @@ impl TelemetrySubscription {
 	}
 }
 
+/// Reconnects with an exponential backoff and subscribes to the previously selected chain, `None` on shutdown
+async fn reconnect(
+	url: &str,
+	subscribed_to: Option<H256>,
+	shutdown_rx: &mut BroadcastReceiver<()>,
+) -> Option<TelemetryStream> {
+	let mut delay = RECONNECT_DELAY_MIN;
+	loop {
+		warn!("Telemetry connection lost, reconnecting in {:?}", delay);
+		tokio::select! {
+			_ = tokio::time::sleep(delay) => {},
+			_ = shutdown_rx.recv() => return None,
+		}
+		delay = next_reconnect_delay(delay);
+
+		let mut stream = match TelemetryStream::connect(url).await {
+			Ok(v) => v,
+			Err(e) => {
+				on_stream_error(e);
+				continue
+			},
+		};
+		if let Some(hash) = subscribed_to {
+			if let Err(e) = stream.subscribe_to(&hash).await {
+				on_stream_error(e);
+				continue
+			}
+		}
+
+		return Some(stream)
+	}
+}
+
+fn next_reconnect_delay(delay: Duration) -> Duration {
+	(delay * 2).min(RECONNECT_DELAY_MAX)
+}
+
+fn now_ms() -> Timestamp {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |v| v.as_millis() as Timestamp)
+}
+
 /// Number of chain choices displayed on a single screen
 const CHAINS_CHUNK_SIZE: usize = 10;
 /// Command that interupts user input
@@ -276,7 +347,7 @@ fn on_stream_error(e: WsError) {
 	warn!("WebSocketError: {:?}", e);
 }
 
-fn on_error(e: Report) {
+fn on_error(e: TelemetryFeedError) {
 	warn!("Cannot parse telemetry feed: {:?}", e);
 }
 
@@ -287,3 +358,21 @@ fn on_choose_chain_error(e: ChooseChainError) {
 fn on_ctrl_c() {
 	info!("received interrupt signal shutting down subscription");
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_backs_off_reconnections() {
+		let delays: Vec<Duration> =
+			std::iter::successors(Some(RECONNECT_DELAY_MIN), |v| Some(next_reconnect_delay(*v)))
+				.take(8)
+				.collect();
+
+		assert_eq!(delays[1], Duration::from_secs(2));
+		assert_eq!(delays[5], Duration::from_secs(32));
+		assert_eq!(delays[6], RECONNECT_DELAY_MAX);
+		assert_eq!(delays[7], RECONNECT_DELAY_MAX);
+	}
+}
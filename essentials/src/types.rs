@@ -23,7 +23,11 @@ use crate::metadata::{
 	polkadot_primitives::CoreIndex,
 };
 use parity_scale_codec::{Decode, Encode};
-use std::collections::{BTreeMap, VecDeque};
+use std::{
+	collections::{BTreeMap, VecDeque},
+	fmt::{self, Display, Formatter},
+	str::FromStr,
+};
 use subxt::{
 	config::substrate::{BlakeTwo256, SubstrateHeader},
 	utils,
@@ -72,7 +76,7 @@ pub struct Assignment {
 
 // TODO: Take it from runtime types v5
 /// Temporary abstraction to cover core state until v5 types are released
-#[derive(Debug, Decode, Encode)]
+#[derive(Clone, Debug, Decode, Encode)]
 pub enum CoreOccupied {
 	/// The core is not occupied.
 	Free,
@@ -87,3 +91,54 @@ pub struct OnDemandOrder {
 	pub para_id: u32,
 	pub spot_price: u128,
 }
+
+/// Actual weight of an extrinsic reported in `System::ExtrinsicSuccess`
+#[derive(Debug, Decode, Encode, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExtrinsicWeight {
+	/// Computation time
+	pub ref_time: u64,
+	/// Size of the storage proof
+	pub proof_size: u64,
+}
+
+/// Weight consumed by a block over all dispatch classes, as stored in `System::BlockWeight`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BlockWeight {
+	/// Computation time
+	pub ref_time: u64,
+	/// Size of the storage proof
+	pub proof_size: u64,
+}
+
+/// A block referenced by its number or hash, e.g. `16080000` or `0x91b1...`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRef {
+	Number(BlockNumber),
+	Hash(H256),
+}
+
+impl FromStr for BlockRef {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		if s.starts_with("0x") {
+			H256::from_str(s)
+				.map(BlockRef::Hash)
+				.map_err(|_| format!("invalid block hash: {}", s))
+		} else {
+			s.parse()
+				.map(BlockRef::Number)
+				.map_err(|_| format!("invalid block number: {}", s))
+		}
+	}
+}
+
+impl Display for BlockRef {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			BlockRef::Number(v) => write!(f, "#{}", v),
+			BlockRef::Hash(v) => write!(f, "{:?}", v),
+		}
+	}
+}
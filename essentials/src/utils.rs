@@ -15,6 +15,7 @@
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::rpc_budget::RpcBudgetCounter;
 use clap::Parser;
 use log::info;
 use std::time::Duration;
@@ -39,6 +40,7 @@ pub struct Retry {
 	count: u32,
 	max_count: u32,
 	delay: u32,
+	rpc_budget: RpcBudgetCounter,
 }
 
 #[derive(Debug, Error)]
@@ -55,7 +57,13 @@ impl Default for Retry {
 
 impl Retry {
 	pub fn new(opts: &RetryOptions) -> Self {
-		Self { count: 0, max_count: opts.max_count, delay: opts.retry_delay }
+		Self { count: 0, max_count: opts.max_count, delay: opts.retry_delay, rpc_budget: Default::default() }
+	}
+
+	/// Counts the retries in the given counter
+	pub fn with_rpc_budget(mut self, rpc_budget: RpcBudgetCounter) -> Self {
+		self.rpc_budget = rpc_budget;
+		self
 	}
 
 	pub async fn sleep(&mut self) -> color_eyre::Result<(), RetryError> {
@@ -63,6 +71,7 @@ impl Retry {
 		if self.count > self.max_count {
 			return Err(RetryError::MaxCountReached)
 		}
+		self.rpc_budget.on_retry();
 
 		let ms = self.delay * (self.count + 1);
 		info!("Retrying in {}ms...", ms);
@@ -0,0 +1,288 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Per-block state changes recorded in a Substrate node database
+//!
+//! Nodes do not keep storage diffs, changes tries were removed from Substrate, but the state database journals
+//! trie nodes inserted and deleted by every block that is not finalized yet or is within the pruning window.
+//! Journal records are stored in the state meta column, keyed by the block number:
+//! - `(number: u64, index: u64) ++ "noncanonical_journal"` for blocks that are not finalized, with the nodes
+//! - `number: u64 ++ "pruning_journal"` for finalized blocks waiting to be pruned, only with the node keys
+//!
+//! Block hashes of the records are checked against the canonical chain from the key lookup column.
+
+use crate::{node::KEY_LOOKUP_COLUMN, IntrospectorKvdb};
+use color_eyre::Result;
+use log::warn;
+use parity_scale_codec::Decode;
+use polkadot_introspector_essentials::{output::OutputRecord, types::H256};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// State database journals
+pub const STATE_META_COLUMN: &str = "col2";
+
+const NON_CANONICAL_JOURNAL: &[u8] = b"noncanonical_journal";
+const PRUNING_JOURNAL: &[u8] = b"pruning_journal";
+
+#[derive(Decode)]
+struct NonCanonicalJournalRecord {
+	hash: H256,
+	parent_hash: H256,
+	inserted: Vec<(Vec<u8>, Vec<u8>)>,
+	deleted: Vec<Vec<u8>>,
+}
+
+#[derive(Decode)]
+struct PruningJournalRecord {
+	hash: H256,
+	inserted: Vec<Vec<u8>>,
+	deleted: Vec<Vec<u8>>,
+}
+
+/// Journal a block delta was found in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Journal {
+	/// Blocks which are not finalized, including forks
+	NonCanonical,
+	/// Finalized blocks waiting to be pruned
+	Pruning,
+}
+
+/// Trie nodes written by a block
+#[derive(Debug, Serialize)]
+pub struct BlockDelta {
+	/// Block number
+	pub block_number: u64,
+	/// Block hash
+	pub hash: H256,
+	/// Parent hash, recorded only for non-finalized blocks
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub parent_hash: Option<H256>,
+	/// The journal
+	pub journal: Journal,
+	/// If the block is on the canonical chain, unknown if the key lookup has no block with this number
+	pub is_canonical: Option<bool>,
+	/// Number of inserted trie nodes
+	pub inserted_count: usize,
+	/// Size of inserted trie nodes, known only for non-finalized blocks
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub inserted_size: Option<usize>,
+	/// Number of deleted trie nodes
+	pub deleted_count: usize,
+	/// Hex encoded keys of inserted trie nodes with values if known, if requested
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub inserted: Vec<(String, Option<String>)>,
+	/// Hex encoded keys of deleted trie nodes, if requested
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub deleted: Vec<String>,
+}
+
+impl OutputRecord for BlockDelta {
+	const CSV_COLUMNS: &'static [&'static str] = &[
+		"block_number",
+		"hash",
+		"parent_hash",
+		"journal",
+		"is_canonical",
+		"inserted_count",
+		"inserted_size",
+		"deleted_count",
+		"inserted",
+		"deleted",
+	];
+}
+
+impl Display for BlockDelta {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let chain = match self.is_canonical {
+			Some(true) => "canonical",
+			Some(false) => "fork",
+			None => "unknown chain",
+		};
+		write!(
+			f,
+			"#{} {:?} ({}, {:?} journal): {} trie nodes inserted",
+			self.block_number, self.hash, chain, self.journal, self.inserted_count
+		)?;
+		if let Some(size) = self.inserted_size {
+			write!(f, " ({} bytes)", size)?;
+		}
+		write!(f, ", {} deleted", self.deleted_count)?;
+		for (key, value) in &self.inserted {
+			write!(f, "\n\t+ 0x{}", key)?;
+			if let Some(value) = value {
+				write!(f, ": 0x{}", value)?;
+			}
+		}
+		for key in &self.deleted {
+			write!(f, "\n\t- 0x{}", key)?;
+		}
+		Ok(())
+	}
+}
+
+/// Options to extract block deltas
+pub struct BlockDeltasOptions {
+	/// First block number
+	pub from: u64,
+	/// Last block number, inclusive
+	pub to: u64,
+	/// Output trie node keys
+	pub with_keys: bool,
+	/// Output values of inserted trie nodes, if known
+	pub with_values: bool,
+}
+
+fn parse_journal_key(key: &[u8]) -> Option<(Journal, u64)> {
+	let block_number = |bytes: &[u8]| u64::from_le_bytes(bytes[..8].try_into().expect("8 bytes; qed"));
+	if key.len() == 16 + NON_CANONICAL_JOURNAL.len() && key.ends_with(NON_CANONICAL_JOURNAL) {
+		Some((Journal::NonCanonical, block_number(key)))
+	} else if key.len() == 8 + PRUNING_JOURNAL.len() && key.ends_with(PRUNING_JOURNAL) {
+		Some((Journal::Pruning, block_number(key)))
+	} else {
+		None
+	}
+}
+
+/// Returns the canonical hash of the block from the key lookup column, its keys are big-endian `u32` block numbers
+/// and values are lookup keys, the block number followed by the hash.
+fn canonical_hash<D: IntrospectorKvdb>(db: &D, block_number: u64) -> Result<Option<H256>> {
+	let Ok(block_number) = u32::try_from(block_number) else { return Ok(None) };
+	let key = block_number.to_be_bytes();
+	let mut upper_bound = key.to_vec();
+	upper_bound.push(0);
+
+	Ok(db
+		.iter_range(KEY_LOOKUP_COLUMN, &key, Some(upper_bound.as_slice()))?
+		.find(|(found, _)| found.as_ref() == key)
+		.and_then(|(_, lookup_key)| lookup_key.get(4..36).map(H256::from_slice)))
+}
+
+fn decode_delta(journal: Journal, block_number: u64, value: &[u8], opts: &BlockDeltasOptions) -> Result<BlockDelta> {
+	Ok(match journal {
+		Journal::NonCanonical => {
+			let record = NonCanonicalJournalRecord::decode(&mut &value[..])?;
+			BlockDelta {
+				block_number,
+				hash: record.hash,
+				parent_hash: Some(record.parent_hash),
+				journal,
+				is_canonical: None,
+				inserted_count: record.inserted.len(),
+				inserted_size: Some(record.inserted.iter().map(|(key, value)| key.len() + value.len()).sum()),
+				deleted_count: record.deleted.len(),
+				inserted: if opts.with_keys {
+					record
+						.inserted
+						.iter()
+						.map(|(key, value)| (hex::encode(key), opts.with_values.then(|| hex::encode(value))))
+						.collect()
+				} else {
+					vec![]
+				},
+				deleted: if opts.with_keys { record.deleted.iter().map(hex::encode).collect() } else { vec![] },
+			}
+		},
+		Journal::Pruning => {
+			let record = PruningJournalRecord::decode(&mut &value[..])?;
+			BlockDelta {
+				block_number,
+				hash: record.hash,
+				parent_hash: None,
+				journal,
+				is_canonical: None,
+				inserted_count: record.inserted.len(),
+				inserted_size: None,
+				deleted_count: record.deleted.len(),
+				inserted: if opts.with_keys {
+					record.inserted.iter().map(|key| (hex::encode(key), None)).collect()
+				} else {
+					vec![]
+				},
+				deleted: if opts.with_keys { record.deleted.iter().map(hex::encode).collect() } else { vec![] },
+			}
+		},
+	})
+}
+
+/// Extracts deltas of the blocks in the range from the state database journals, ordered by block numbers
+pub fn block_deltas<D: IntrospectorKvdb>(db: &D, opts: &BlockDeltasOptions) -> Result<Vec<BlockDelta>> {
+	let mut deltas = vec![];
+
+	for (key, value) in db.iter_values(STATE_META_COLUMN)? {
+		let Some((journal, block_number)) = parse_journal_key(&key) else { continue };
+		if block_number < opts.from || block_number > opts.to {
+			continue
+		}
+
+		match decode_delta(journal, block_number, &value, opts) {
+			Ok(delta) => deltas.push(delta),
+			Err(e) => warn!("Cannot decode {:?} journal record of block #{}: {:?}", journal, block_number, e),
+		}
+	}
+	deltas.sort_by_key(|delta| delta.block_number);
+
+	for delta in deltas.iter_mut() {
+		delta.is_canonical = canonical_hash(db, delta.block_number)?.map(|hash| hash == delta.hash);
+	}
+
+	Ok(deltas)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parity_scale_codec::Encode;
+
+	#[test]
+	fn test_parses_journal_keys() {
+		let mut key = (42_u64, 1_u64).encode();
+		key.extend(NON_CANONICAL_JOURNAL);
+		assert_eq!(parse_journal_key(&key), Some((Journal::NonCanonical, 42)));
+
+		let mut key = 43_u64.encode();
+		key.extend(PRUNING_JOURNAL);
+		assert_eq!(parse_journal_key(&key), Some((Journal::Pruning, 43)));
+
+		assert_eq!(parse_journal_key(b"last_canonical"), None);
+		assert_eq!(parse_journal_key(PRUNING_JOURNAL), None);
+	}
+
+	#[test]
+	fn test_decodes_journal_records() {
+		let hash = H256::repeat_byte(1);
+		let parent_hash = H256::repeat_byte(2);
+		let value = (hash, parent_hash, vec![(vec![0xaa_u8], vec![0xbb_u8, 0xcc])], vec![vec![0xdd_u8]]).encode();
+		let opts = BlockDeltasOptions { from: 0, to: 100, with_keys: true, with_values: true };
+
+		let delta = decode_delta(Journal::NonCanonical, 42, &value, &opts).unwrap();
+		assert_eq!(delta.hash, hash);
+		assert_eq!(delta.parent_hash, Some(parent_hash));
+		assert_eq!(delta.inserted_size, Some(3));
+		assert_eq!(delta.inserted, vec![("aa".to_string(), Some("bbcc".to_string()))]);
+		assert_eq!(delta.deleted, vec!["dd".to_string()]);
+
+		let value = (hash, vec![vec![0xaa_u8]], Vec::<Vec<u8>>::new()).encode();
+		let delta = decode_delta(Journal::Pruning, 42, &value, &opts).unwrap();
+		assert_eq!(delta.inserted_count, 1);
+		assert_eq!(delta.deleted_count, 0);
+		assert_eq!(delta.inserted_size, None);
+
+		assert!(decode_delta(Journal::Pruning, 42, &[0x01], &opts).is_err());
+	}
+}
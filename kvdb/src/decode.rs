@@ -27,7 +27,7 @@ use crate::IntrospectorKvdb;
 use color_eyre::{eyre::eyre, Result};
 use erased_serde::{serialize_trait_object, Serialize};
 use itertools::Itertools;
-use polkadot_introspector_essentials::types::H256;
+use polkadot_introspector_essentials::{output::OutputRecord, types::H256};
 use std::fmt::{Debug, Display, Formatter};
 
 /// Decode result trait, used to display and format output of the decoder
@@ -208,6 +208,10 @@ pub struct KeyDecodeOptions<'a> {
 #[derive(serde::Serialize)]
 pub struct DecodedOutput(Vec<KeyDecodeResult>);
 
+impl OutputRecord for DecodedOutput {
+	const CSV_COLUMNS: &'static [&'static str] = &["fields", "value_size"];
+}
+
 impl Display for DecodedOutput {
 	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 		for elt in &self.0 {
@@ -0,0 +1,601 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+mod block_deltas;
+mod decode;
+pub mod node;
+pub mod paritydb;
+#[cfg(feature = "rocksdb")]
+pub mod rocksdb;
+mod scan;
+mod search;
+pub mod sled;
+mod traits;
+mod verify;
+
+mod prometheus;
+#[cfg(test)]
+mod tests;
+
+#[cfg(feature = "rocksdb")]
+use crate::rocksdb::IntrospectorRocksDB;
+use crate::{paritydb::IntrospectorParityDB, prometheus::KvdbPrometheusOptions, sled::IntrospectorSled};
+use clap::{ArgAction, Parser};
+use color_eyre::{eyre::eyre, Result};
+use futures::future;
+use log::{error, info};
+use polkadot_introspector_essentials::{
+	init,
+	output::{OutputOptions, OutputRecord, OutputWriter},
+};
+use serde::Serialize;
+use std::{
+	fmt::{Display, Formatter},
+	fs,
+	fs::File,
+	io,
+	io::{BufWriter, Write},
+	path::{Path, PathBuf},
+};
+use strum::{Display, EnumString};
+
+pub use crate::traits::*;
+
+/// Specific options for the usage subcommand
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct KvdbUsageOpts {
+	/// Check only specific column(s)
+	#[clap(long, short = 'c')]
+	column: Vec<String>,
+	/// Limit scan by specific key prefix(es)
+	#[clap(long, short = 'p')]
+	keys_prefix: Vec<String>,
+}
+
+/// Specific options for the `decode_keys` subcommand
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct KvdbKeysOpts {
+	/// Check only specific column(s)
+	#[clap(long, short = 'c')]
+	column: String,
+	/// Decode keys matching the specific format (like `candidate-votes%i%h`, where `%i` represents a big-endian integer)
+	#[clap(long, short = 'f')]
+	fmt: String,
+	/// Limit number of output entries
+	#[clap(long, short = 'l')]
+	limit: Option<usize>,
+	/// Allow to ignore decode failures
+	#[clap(long, short = 'i', default_value = "false")]
+	ignore_failures: bool,
+}
+
+/// Specific options for the search subcommand
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct KvdbSearchOpts {
+	/// Column to search in
+	#[clap(long, short = 'c')]
+	column: String,
+	/// Hex string prefixed with `0x` contained in keys, or a regular expression matched against raw key bytes
+	#[clap(long, short = 'p')]
+	pattern: String,
+	/// Skip this number of matches
+	#[clap(long, default_value = "0")]
+	offset: usize,
+	/// Stop after this number of matches
+	#[clap(long, short = 'l', default_value = "1000")]
+	limit: usize,
+	/// Output values of the matching keys
+	#[clap(long)]
+	values: bool,
+}
+
+/// Specific options for the verify subcommand
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct KvdbVerifyOpts {
+	/// Verify only specific column(s)
+	#[clap(long, short = 'c')]
+	column: Vec<String>,
+}
+
+/// Specific options for the block deltas subcommand
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct KvdbBlockDeltasOpts {
+	/// First block number
+	#[clap(long)]
+	from: u64,
+	/// Last block number, the same as `--from` if not set
+	#[clap(long)]
+	to: Option<u64>,
+	/// Output keys of inserted and deleted trie nodes
+	#[clap(long)]
+	keys: bool,
+	/// Output values of inserted trie nodes along with their keys, known only for non-finalized blocks
+	#[clap(long, requires = "keys")]
+	values: bool,
+}
+
+/// Specific options for the dump subcommand
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct KvdbDumpOpts {
+	/// Dump only specific column(s)
+	#[clap(long, short = 'c')]
+	column: Vec<String>,
+	/// Limit dump by specific key prefix(es)
+	#[clap(long, short = 'p')]
+	keys_prefix: Vec<String>,
+	/// Output directory to use for a dump
+	#[clap(long = "output", short = 'o', value_parser)]
+	output: PathBuf,
+	/// Output type
+	#[clap(long, default_value_t)]
+	format: KvdbDumpMode,
+}
+
+impl<'a> From<&'a KvdbKeysOpts> for decode::KeyDecodeOptions<'a> {
+	fn from(cli_opts: &'a KvdbKeysOpts) -> Self {
+		decode::KeyDecodeOptions {
+			decode_fmt: cli_opts.fmt.as_str(),
+			column: cli_opts.column.as_str(),
+			lim: &cli_opts.limit,
+			ignore_failures: cli_opts.ignore_failures,
+		}
+	}
+}
+
+/// Mode of this command
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) enum KvdbMode {
+	/// Returns list of all columns in the database
+	Columns,
+	/// Returns usage in the database
+	Usage(KvdbUsageOpts),
+	/// Decode specific keys in the database
+	DecodeKeys(KvdbKeysOpts),
+	/// Search keys matching a pattern in the database
+	Search(KvdbSearchOpts),
+	/// Verify integrity of the database, reporting content hashes of columns
+	Verify(KvdbVerifyOpts),
+	/// Show trie nodes written by blocks from the state database journals of a Substrate node
+	BlockDeltas(KvdbBlockDeltasOpts),
+	/// Dump database (works with a live database for RocksDB)
+	Dump(KvdbDumpOpts),
+	/// Same as Usage, exposing metrics via a Prometheus endpoint
+	Prometheus(KvdbPrometheusOptions),
+	/// Print the Prometheus metrics exported in Prometheus mode, the database is not opened
+	ListMetrics,
+}
+
+/// Database type
+#[derive(Clone, Copy, Debug, Parser, EnumString, Display, PartialEq, Eq, Default)]
+#[clap(rename_all = "kebab-case")]
+pub enum KvdbType {
+	/// Automatically detect database type
+	#[strum(ascii_case_insensitive)]
+	#[default]
+	Auto,
+	#[strum(ascii_case_insensitive)]
+	/// RocksDB database
+	RocksDB,
+	#[strum(ascii_case_insensitive)]
+	/// ParityDB database
+	ParityDB,
+	#[strum(ascii_case_insensitive)]
+	/// sled database, e.g. a dump of RocksDB or ParityDB
+	Sled,
+}
+
+/// Database type
+#[derive(Clone, Debug, Parser, EnumString, Display, Default)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) enum KvdbDumpMode {
+	#[strum(ascii_case_insensitive)]
+	#[default]
+	/// RocksDB database
+	RocksDB,
+	#[strum(ascii_case_insensitive)]
+	/// ParityDB database
+	ParityDB,
+	/// sled database, can be introspected without RocksDB bindings
+	#[strum(ascii_case_insensitive)]
+	Sled,
+	/// Dump as new-line delimited JSON into files with a pattern `column_name.json`
+	#[strum(ascii_case_insensitive)]
+	Json,
+}
+
+#[derive(Clone, Debug, Parser)]
+#[clap(author, version, about = "Examine RocksDB, ParityDB and sled databases", rename_all = "kebab-case")]
+pub struct KvdbOptions {
+	/// Path to the database
+	#[clap(long)]
+	db: String,
+	#[clap(long, default_value_t)]
+	db_type: KvdbType,
+	/// Mode of running
+	#[clap(subcommand)]
+	mode: KvdbMode,
+	#[clap(flatten)]
+	output: OutputOptions,
+	/// Compress output with snappy
+	#[clap(long, short = 'c', action = ArgAction::SetTrue)]
+	compress: bool,
+	/// Number of threads iterating a column in chunks, used by `usage`, `search` and `dump` into a database
+	#[clap(long, default_value = "1")]
+	threads: usize,
+	#[clap(flatten)]
+	pub verbose: init::VerbosityOptions,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct UsageResults<'a> {
+	description: &'a str,
+	keys_count: usize,
+	keys_size: usize,
+	values_size: usize,
+}
+
+impl<'a> UsageResults<'a> {
+	fn add(&mut self, iter: DBIter) {
+		for (key, value) in iter {
+			self.keys_count += 1;
+			self.keys_size += key.len();
+			self.values_size += value.len();
+		}
+	}
+}
+
+impl OutputRecord for UsageResults<'_> {
+	const CSV_COLUMNS: &'static [&'static str] = &["description", "keys_count", "keys_size", "values_size"];
+}
+
+impl<'a> Display for UsageResults<'a> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		if self.keys_count > 0 {
+			write!(
+				f,
+				"{}: {} keys size: {} bytes ({:.2} bytes per key in average), values size: {} bytes ({:.2} bytes per value in average)",
+				self.description,
+				self.keys_count,
+				self.keys_size,
+				self.keys_size as f64 / self.keys_count as f64,
+				self.values_size,
+				self.values_size as f64 / self.keys_count as f64
+			)
+		} else {
+			write!(
+				f,
+				"{}: {} keys size: {} bytes (0 bytes per key in average), values size: {} bytes (0 bytes per value in average)",
+				self.description, self.keys_count, self.keys_size, self.values_size
+			)
+		}
+	}
+}
+
+/// Detects the type of a database by the files specific to it
+pub fn autodetect_db_type(db_path: &str) -> Result<KvdbType> {
+	let rocksdb_specific = Path::new(db_path).join("CURRENT");
+	let paritydb_specific = Path::new(db_path).join("metadata");
+	let sled_specific = Path::new(db_path).join("conf");
+
+	if rocksdb_specific.as_path().exists() {
+		info!("Detected db type: RocksDB");
+		Ok(KvdbType::RocksDB)
+	} else if paritydb_specific.as_path().exists() {
+		info!("Detected db type: ParityDB");
+		Ok(KvdbType::ParityDB)
+	} else if sled_specific.as_path().exists() {
+		info!("Detected db type: sled");
+		Ok(KvdbType::Sled)
+	} else {
+		Err(eyre!("Cannot detect database type in path: {}", db_path))
+	}
+}
+
+pub async fn introspect_kvdb(opts: KvdbOptions) -> Result<()> {
+	if let KvdbMode::ListMetrics = opts.mode {
+		return prometheus::list_metrics()
+	}
+	let db_type = if opts.db_type == KvdbType::Auto { autodetect_db_type(opts.db.as_str())? } else { opts.db_type };
+
+	match db_type {
+		KvdbType::Auto => unreachable!(),
+		#[cfg(feature = "rocksdb")]
+		KvdbType::RocksDB => run_with_db(rocksdb::IntrospectorRocksDB::new(Path::new(opts.db.as_str()))?, opts).await,
+		#[cfg(not(feature = "rocksdb"))]
+		KvdbType::RocksDB => Err(rocksdb_disabled()),
+		KvdbType::ParityDB =>
+			run_with_db(paritydb::IntrospectorParityDB::new(Path::new(opts.db.as_str()))?, opts).await,
+		KvdbType::Sled => run_with_db(sled::IntrospectorSled::new(Path::new(opts.db.as_str()))?, opts).await,
+	}
+}
+
+#[cfg(not(feature = "rocksdb"))]
+fn rocksdb_disabled() -> color_eyre::Report {
+	eyre!("RocksDB support is disabled in this build, dump the database with `--format sled` where it is enabled")
+}
+
+async fn run_with_db<D: IntrospectorKvdb + Sync + Send + 'static>(db: D, opts: KvdbOptions) -> Result<()> {
+	let output = OutputWriter::new(opts.output.format);
+	match opts.mode {
+		KvdbMode::Columns => {
+			let columns = db.list_columns()?;
+
+			for col in columns {
+				println!("{}", col.as_str());
+			}
+		},
+		KvdbMode::Usage(ref usage_opts) => {
+			let columns = db
+				.list_columns()?
+				.iter()
+				.filter(|col| usage_opts.column.is_empty() || usage_opts.column.contains(col));
+
+			for col in columns {
+				let mut usage = UsageResults { description: col.as_str(), keys_count: 0, keys_size: 0, values_size: 0 };
+				let is_parallel = opts.threads > 1 && usage_opts.keys_prefix.is_empty();
+				let progress = scan::ScanProgress::new(col.as_str(), is_parallel.then_some(scan::CHUNKS_COUNT));
+
+				if is_parallel {
+					let chunks = scan::scan_chunks(&db, col.as_str(), opts.threads, &progress, |iter| {
+						let mut chunk_usage =
+							UsageResults { description: "", keys_count: 0, keys_size: 0, values_size: 0 };
+						chunk_usage.add(iter);
+						Ok(chunk_usage)
+					})?;
+					for chunk_usage in chunks {
+						usage.keys_count += chunk_usage.keys_count;
+						usage.keys_size += chunk_usage.keys_size;
+						usage.values_size += chunk_usage.values_size;
+					}
+				} else if usage_opts.keys_prefix.is_empty() {
+					usage.add(progress.track(db.iter_values(col.as_str())?));
+				} else {
+					// Iterate over all requested prefixes
+					for prefix in &usage_opts.keys_prefix {
+						usage.add(progress.track(db.prefixed_iter_values(col.as_str(), prefix.as_str())?));
+					}
+				}
+				progress.finish();
+
+				output_result(&usage, &output, &opts)?;
+			}
+		},
+		KvdbMode::DecodeKeys(ref kvdb_keys_opts) => {
+			let res = decode::decode_keys(&db, &kvdb_keys_opts.into())?;
+			output_result(&res, &output, &opts)?;
+		},
+		KvdbMode::Search(ref search_opts) => {
+			let search_opts = search::KeySearchOptions {
+				column: search_opts.column.as_str(),
+				pattern: search::parse_pattern(search_opts.pattern.as_str())?,
+				offset: search_opts.offset,
+				limit: search_opts.limit,
+				with_values: search_opts.values,
+			};
+			let summary = if opts.threads > 1 {
+				let progress = scan::ScanProgress::new(search_opts.column, Some(scan::CHUNKS_COUNT));
+				let summary = search::par_search_keys(&db, &search_opts, opts.threads, &progress, |res| {
+					output_result(&res, &output, &opts)
+				});
+				progress.finish();
+				summary?
+			} else {
+				let progress = scan::ScanProgress::new(search_opts.column, None);
+				let summary =
+					search::search_keys(&db, &search_opts, &progress, |res| output_result(&res, &output, &opts));
+				progress.finish();
+				summary?
+			};
+			info!("{} keys scanned, {} matches returned", summary.scanned, summary.returned);
+			if let Some(next_offset) = summary.next_offset {
+				info!("More matches are available with --offset {}", next_offset);
+			}
+		},
+		KvdbMode::Verify(ref verify_opts) => {
+			let columns = db
+				.list_columns()?
+				.iter()
+				.filter(|col| verify_opts.column.is_empty() || verify_opts.column.contains(col));
+			let mut corrupted_count = 0_usize;
+
+			for col in columns {
+				info!("verifying column {}", col.as_str());
+				let res = verify::verify_column(&db, col.as_str())?;
+				if !res.is_ok() {
+					corrupted_count += 1;
+				}
+				output_result(&res, &output, &opts)?;
+			}
+
+			if corrupted_count > 0 {
+				return Err(eyre!("{} column(s) have unreadable entries", corrupted_count))
+			}
+		},
+		KvdbMode::BlockDeltas(ref deltas_opts) => {
+			let deltas_opts = block_deltas::BlockDeltasOptions {
+				from: deltas_opts.from,
+				to: deltas_opts.to.unwrap_or(deltas_opts.from),
+				with_keys: deltas_opts.keys,
+				with_values: deltas_opts.values,
+			};
+			let deltas = block_deltas::block_deltas(&db, &deltas_opts)?;
+			if deltas.is_empty() {
+				info!(
+					"No journal records for blocks #{}..#{}, they are finalized and pruned or not imported yet",
+					deltas_opts.from, deltas_opts.to
+				);
+			}
+			for delta in &deltas {
+				output_result(delta, &output, &opts)?;
+			}
+		},
+		KvdbMode::Dump(ref dump_opts) => {
+			if !Path::exists(&dump_opts.output) {
+				fs::create_dir_all(&dump_opts.output)?;
+			}
+
+			let output_dir = &dump_opts.output;
+			match dump_opts.format {
+				#[cfg(feature = "rocksdb")]
+				KvdbDumpMode::RocksDB => {
+					let dest_db = IntrospectorRocksDB::new_dumper(&db, output_dir.as_path())?;
+					dump_into_db(db, dest_db, dump_opts, opts.threads)?
+				},
+				#[cfg(not(feature = "rocksdb"))]
+				KvdbDumpMode::RocksDB => return Err(rocksdb_disabled()),
+				KvdbDumpMode::ParityDB => {
+					let dest_db = IntrospectorParityDB::new_dumper(&db, output_dir.as_path())?;
+					dump_into_db(db, dest_db, dump_opts, opts.threads)?
+				},
+				KvdbDumpMode::Sled => {
+					let dest_db = IntrospectorSled::new_dumper(&db, output_dir.as_path())?;
+					dump_into_db(db, dest_db, dump_opts, opts.threads)?
+				},
+				KvdbDumpMode::Json => dump_into_json(db, dump_opts, output_dir.as_path())?,
+			};
+		},
+		KvdbMode::Prometheus(prometheus_opts) => {
+			match prometheus::run_prometheus_endpoint_with_db(db, prometheus_opts).await {
+				Ok(futures) => {
+					future::try_join_all(futures).await.map_err(|e| eyre!("Join error: {:?}", e))?;
+				},
+				Err(err) => error!("FATAL: cannot start kvdb command in prometheus mode: {}", err),
+			}
+		},
+		KvdbMode::ListMetrics => prometheus::list_metrics()?,
+	}
+
+	Ok(())
+}
+
+fn dump_into_db<S: IntrospectorKvdb + Sync, D: IntrospectorKvdb + Sync>(
+	source: S,
+	destination: D,
+	dump_opts: &KvdbDumpOpts,
+	threads: usize,
+) -> Result<()> {
+	let columns = source
+		.list_columns()?
+		.iter()
+		.filter(|col| dump_opts.column.is_empty() || dump_opts.column.contains(col));
+
+	for col in columns {
+		info!("dumping column {}", col.as_str());
+		let is_parallel = threads > 1 && dump_opts.keys_prefix.is_empty();
+		let progress = scan::ScanProgress::new(col.as_str(), is_parallel.then_some(scan::CHUNKS_COUNT));
+
+		if is_parallel {
+			scan::scan_chunks(&source, col.as_str(), threads, &progress, |iter| {
+				destination.write_iter(col.as_str(), iter)
+			})?;
+		} else if dump_opts.keys_prefix.is_empty() {
+			// A read error fails the dump instead of leaving a truncated column behind
+			let mut read_error = None;
+			let iter = source
+				.try_iter_values(col.as_str())?
+				.map_while(|entry| entry.map_err(|e| read_error = Some(e)).ok());
+			destination.write_iter(col.as_str(), progress.track(Box::new(iter)))?;
+			if let Some(e) = read_error {
+				return Err(e)
+			}
+		} else {
+			// Iterate over all requested prefixes
+			for prefix in &dump_opts.keys_prefix {
+				info!("dumping prefix {} in column {}", prefix.as_str(), col.as_str());
+				let iter = source.prefixed_iter_values(col.as_str(), prefix.as_str())?;
+				destination.write_iter(col.as_str(), progress.track(iter))?;
+			}
+		}
+		progress.finish();
+	}
+
+	Ok(())
+}
+
+fn dump_into_json<D: IntrospectorKvdb>(db: D, dump_opts: &KvdbDumpOpts, output_dir: &Path) -> Result<()> {
+	let columns = db
+		.list_columns()?
+		.iter()
+		.filter(|col| dump_opts.column.is_empty() || dump_opts.column.contains(col));
+
+	for col in columns {
+		let mut output_fname: PathBuf = output_dir.to_path_buf();
+		output_fname.set_file_name(format!("{}.json", col));
+		let output_file = File::create(output_fname.as_path())?;
+		{
+			let mut writer = BufWriter::new(output_file);
+			info!("dumping column {}", col.as_str());
+
+			if dump_opts.keys_prefix.is_empty() {
+				let iter = db.iter_values(col.as_str())?;
+				write_db_iter_into_json(iter, &mut writer)?;
+			} else {
+				// Iterate over all requested prefixes
+				for prefix in &dump_opts.keys_prefix {
+					info!("dumping prefix {} in column {}", prefix.as_str(), col.as_str());
+					let iter = db.prefixed_iter_values(col.as_str(), prefix.as_str())?;
+					write_db_iter_into_json(iter, &mut writer)?;
+				}
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[derive(Serialize)]
+struct KeyValueDumpElement<'a> {
+	#[serde(with = "serde_bytes")]
+	key: &'a [u8],
+	#[serde(with = "serde_bytes")]
+	value: &'a [u8],
+}
+
+fn write_db_iter_into_json<I, K, V, W>(iter: I, writer: &mut BufWriter<W>) -> Result<()>
+where
+	I: IntoIterator<Item = (K, V)>,
+	K: AsRef<[u8]>,
+	V: AsRef<[u8]>,
+	W: std::io::Write,
+{
+	for (key, value) in iter {
+		let dump_struct = KeyValueDumpElement { key: key.as_ref(), value: value.as_ref() };
+
+		let json = serde_json::to_string(&dump_struct)?;
+		writeln!(writer, "{}", json)?;
+	}
+
+	Ok(())
+}
+
+fn output_result<T: OutputRecord>(res: &T, writer: &OutputWriter, opts: &KvdbOptions) -> Result<()> {
+	let output = writer.render(res)?;
+
+	if opts.compress {
+		snap::write::FrameEncoder::new(io::stdout().lock()).write_all(output.as_slice())?;
+	} else {
+		io::stdout().write_all(output.as_slice())?;
+	}
+
+	Ok(())
+}
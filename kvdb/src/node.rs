@@ -0,0 +1,284 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Blocks and state of a Substrate node read from its database
+//!
+//! Blocks are stored in numbered columns:
+//! - the meta column keeps lookup keys of the best and the finalized blocks
+//! - the key lookup column maps block numbers and hashes to lookup keys `number: u32 ++ hash`
+//! - the header and body columns keep SCALE-encoded headers and extrinsics by the lookup keys
+//! - the state column keeps trie nodes by their hashes, prefixed with the nibbles of their position in the trie
+//!   unless the database counts references (ParityDB)
+//!
+//! Storage values are found walking the trie from the state root of a block, so only states that have not been
+//! pruned can be read.
+
+use crate::IntrospectorKvdb;
+use color_eyre::{eyre::eyre, Result};
+use parity_scale_codec::{Decode, Input};
+use polkadot_introspector_essentials::types::{BlockNumber, Header, H256};
+
+/// Lookup keys of the best and the finalized blocks
+pub const META_COLUMN: &str = "col0";
+/// Trie nodes
+pub const STATE_COLUMN: &str = "col1";
+/// Mapping from block numbers and hashes to lookup keys
+pub const KEY_LOOKUP_COLUMN: &str = "col3";
+/// Block headers
+pub const HEADER_COLUMN: &str = "col4";
+/// Block bodies
+pub const BODY_COLUMN: &str = "col5";
+
+const BEST_BLOCK_KEY: &[u8] = b"best";
+const FINALIZED_BLOCK_KEY: &[u8] = b"final";
+const HASH_LENGTH: usize = 32;
+
+/// A Substrate node database
+pub struct NodeDb<D> {
+	db: D,
+}
+
+impl<D: IntrospectorKvdb> NodeDb<D> {
+	pub fn new(db: D) -> Self {
+		Self { db }
+	}
+
+	/// Returns the hash of the best block
+	pub fn best_hash(&self) -> Result<H256> {
+		self.meta_block_hash(BEST_BLOCK_KEY)
+	}
+
+	/// Returns the hash of the last finalized block
+	pub fn finalized_hash(&self) -> Result<H256> {
+		self.meta_block_hash(FINALIZED_BLOCK_KEY)
+	}
+
+	/// Returns the hash of the canonical block with a given number
+	pub fn block_hash(&self, number: BlockNumber) -> Result<Option<H256>> {
+		self.db
+			.get(KEY_LOOKUP_COLUMN, &number.to_be_bytes())?
+			.map(|lookup_key| hash_of_lookup_key(&lookup_key))
+			.transpose()
+	}
+
+	/// Returns the header of a block
+	pub fn header(&self, hash: H256) -> Result<Option<Header>> {
+		self.block_data(HEADER_COLUMN, hash)?
+			.map(|header| Header::decode(&mut header.as_slice()).map_err(|e| eyre!("cannot decode header: {:?}", e)))
+			.transpose()
+	}
+
+	/// Returns the extrinsics of a block, without their length prefixes
+	pub fn body(&self, hash: H256) -> Result<Option<Vec<Vec<u8>>>> {
+		self.block_data(BODY_COLUMN, hash)?
+			.map(|body| Vec::<Vec<u8>>::decode(&mut body.as_slice()).map_err(|e| eyre!("cannot decode body: {:?}", e)))
+			.transpose()
+	}
+
+	/// Returns a storage value in the state with a given root
+	pub fn storage(&self, state_root: H256, key: &[u8]) -> Result<Option<Vec<u8>>> {
+		let key_nibbles = nibbles(key);
+		let mut consumed = 0;
+		let mut node_data = self.trie_node(state_root.as_bytes(), &[])?;
+
+		loop {
+			let node = TrieNode::decode(&node_data)?;
+			if !key_nibbles[consumed..].starts_with(&node.partial) {
+				return Ok(None)
+			}
+			consumed += node.partial.len();
+
+			if consumed == key_nibbles.len() {
+				return match node.value {
+					Some(NodeValue::Inline(value)) => Ok(Some(value)),
+					// Hashed values are prefixed with the whole key
+					Some(NodeValue::Hashed(hash)) => self.trie_node(&hash, &key_nibbles).map(Some),
+					None => Ok(None),
+				}
+			}
+
+			let child = match node.children.into_iter().nth(key_nibbles[consumed] as usize).flatten() {
+				Some(child) => child,
+				None => return Ok(None),
+			};
+			consumed += 1;
+			node_data = match child {
+				NodeChild::Inline(data) => data,
+				NodeChild::Hash(hash) => self.trie_node(&hash, &key_nibbles[..consumed])?,
+			};
+		}
+	}
+
+	fn meta_block_hash(&self, key: &[u8]) -> Result<H256> {
+		let lookup_key = self
+			.db
+			.get(META_COLUMN, key)?
+			.ok_or_else(|| eyre!("missing {} block in the meta column", String::from_utf8_lossy(key)))?;
+		hash_of_lookup_key(&lookup_key)
+	}
+
+	fn block_data(&self, column: &str, hash: H256) -> Result<Option<Vec<u8>>> {
+		match self.db.get(KEY_LOOKUP_COLUMN, hash.as_bytes())? {
+			Some(lookup_key) => self.db.get(column, &lookup_key),
+			None => Ok(None),
+		}
+	}
+
+	fn trie_node(&self, hash: &[u8], prefix: &[u8]) -> Result<Vec<u8>> {
+		if let Some(node) = self.db.get(STATE_COLUMN, &prefixed_key(hash, prefix))? {
+			return Ok(node)
+		}
+		self.db
+			.get(STATE_COLUMN, hash)?
+			.ok_or_else(|| eyre!("missing trie node 0x{}, the state may be pruned", hex::encode(hash)))
+	}
+}
+
+fn hash_of_lookup_key(lookup_key: &[u8]) -> Result<H256> {
+	match lookup_key.get(4..4 + HASH_LENGTH) {
+		Some(hash) => Ok(H256::from_slice(hash)),
+		None => Err(eyre!("invalid lookup key: 0x{}", hex::encode(lookup_key))),
+	}
+}
+
+fn nibbles(key: &[u8]) -> Vec<u8> {
+	key.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+/// Key of a trie node in the state column, an odd nibble of the prefix is padded to a byte
+fn prefixed_key(hash: &[u8], prefix: &[u8]) -> Vec<u8> {
+	let mut key: Vec<u8> = prefix
+		.chunks(2)
+		.map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or_default())
+		.collect();
+	key.extend_from_slice(hash);
+	key
+}
+
+#[derive(Debug, PartialEq)]
+enum NodeValue {
+	Inline(Vec<u8>),
+	Hashed(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq)]
+enum NodeChild {
+	Inline(Vec<u8>),
+	Hash(Vec<u8>),
+}
+
+/// A trie node in the codec of Substrate, leaves have no children
+#[derive(Debug, Default, PartialEq)]
+struct TrieNode {
+	partial: Vec<u8>,
+	value: Option<NodeValue>,
+	children: Vec<Option<NodeChild>>,
+}
+
+impl TrieNode {
+	fn decode(data: &[u8]) -> Result<Self> {
+		let input = &mut &data[..];
+		let header = input.read_byte()?;
+		// Whether the node is a branch, whether its value is hashed and the number of the header bits of its type
+		let (is_branch, value, type_bits) = match header {
+			0 => return Ok(Self::default()),
+			header if header >> 6 == 0b01 => (false, Some(false), 2),
+			header if header >> 6 == 0b10 => (true, None, 2),
+			header if header >> 6 == 0b11 => (true, Some(false), 2),
+			header if header >> 5 == 0b001 => (false, Some(true), 3),
+			header if header >> 4 == 0b0001 => (true, Some(true), 4),
+			header => return Err(eyre!("unsupported trie node header: {:#04x}", header)),
+		};
+
+		let max_count = 0xff_u8 >> type_bits;
+		let mut nibble_count = (header & max_count) as usize;
+		if nibble_count == max_count as usize {
+			loop {
+				let next = input.read_byte()?;
+				nibble_count += next as usize;
+				if next < 0xff {
+					break
+				}
+			}
+		}
+		let partial = nibbles(&read_bytes(input, nibble_count.div_ceil(2))?)[nibble_count % 2..].to_vec();
+
+		let bitmap = if is_branch { u16::decode(input)? } else { 0 };
+		let value = match value {
+			Some(true) => Some(NodeValue::Hashed(read_bytes(input, HASH_LENGTH)?)),
+			Some(false) => Some(NodeValue::Inline(Vec::<u8>::decode(input)?)),
+			None => None,
+		};
+		let children = if is_branch {
+			(0..16)
+				.map(|index| {
+					if bitmap & (1 << index) == 0 {
+						return Ok(None)
+					}
+					let child = Vec::<u8>::decode(input)?;
+					Ok(Some(if child.len() == HASH_LENGTH { NodeChild::Hash(child) } else { NodeChild::Inline(child) }))
+				})
+				.collect::<Result<Vec<_>>>()?
+		} else {
+			vec![]
+		};
+
+		Ok(Self { partial, value, children })
+	}
+}
+
+fn read_bytes(input: &mut &[u8], len: usize) -> Result<Vec<u8>> {
+	let mut bytes = vec![0; len];
+	input.read(&mut bytes)?;
+	Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_decodes_trie_nodes() {
+		// A leaf with 3 nibbles of the partial key and an inline value
+		let leaf = TrieNode::decode(&[0x43, 0x01, 0x23, 0x0c, 0xaa, 0xbb, 0xcc]).unwrap();
+		assert_eq!(leaf.partial, vec![1, 2, 3]);
+		assert_eq!(leaf.value, Some(NodeValue::Inline(vec![0xaa, 0xbb, 0xcc])));
+		assert!(leaf.children.is_empty());
+
+		// A branch with a hashed value, an inline child at index 1 and a child hash at index 15
+		let mut data = vec![0x10, 0x02, 0x80];
+		data.extend([0xdd; HASH_LENGTH]);
+		data.extend([0x08, 0x41, 0x05]);
+		data.push(0x80);
+		data.extend([0xee; HASH_LENGTH]);
+		let branch = TrieNode::decode(&data).unwrap();
+		assert!(branch.partial.is_empty());
+		assert_eq!(branch.value, Some(NodeValue::Hashed(vec![0xdd; HASH_LENGTH])));
+		assert_eq!(branch.children[1], Some(NodeChild::Inline(vec![0x41, 0x05])));
+		assert_eq!(branch.children[15], Some(NodeChild::Hash(vec![0xee; HASH_LENGTH])));
+		assert_eq!(branch.children.iter().flatten().count(), 2);
+
+		assert_eq!(TrieNode::decode(&[0x00]).unwrap(), TrieNode::default());
+		assert!(TrieNode::decode(&[0x01]).is_err());
+	}
+
+	#[test]
+	fn test_prefixes_node_keys() {
+		assert_eq!(prefixed_key(&[0xff], &[]), vec![0xff]);
+		assert_eq!(prefixed_key(&[0xff], &[1, 2, 3]), vec![0x12, 0x30, 0xff]);
+		assert!(hash_of_lookup_key(&[0; 4]).is_err());
+	}
+}
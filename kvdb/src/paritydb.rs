@@ -16,8 +16,9 @@
 
 //! Implementation of the introspection using ParityDB
 
-use super::{DBIter, IntrospectorKvdb};
+use super::{DBIter, DBTryIter, IntrospectorKvdb};
 use color_eyre::{eyre::eyre, Result};
+use log::error;
 use parity_db::{Db, Options as ParityDBOptions};
 use std::path::{Path, PathBuf};
 
@@ -56,13 +57,72 @@ impl IntrospectorKvdb for IntrospectorParityDB {
 			.position(|col| col.as_str() == column)
 			.ok_or_else(|| eyre!("invalid column: {}", column))? as u8;
 		let mut iter = self.inner.iter(column_idx)?;
+		let column = column.to_string();
 
-		Ok(Box::new(std::iter::from_fn(move || {
-			if let Some((key, value)) = iter.next().unwrap_or(None) {
-				Some((key.into_boxed_slice(), value.into_boxed_slice()))
-			} else {
+		Ok(Box::new(std::iter::from_fn(move || match iter.next() {
+			Ok(Some((key, value))) => Some((key.into_boxed_slice(), value.into_boxed_slice())),
+			Ok(None) => None,
+			Err(e) => {
+				error!("Error reading column {}, iteration stopped: {:?}", column, e);
 				None
+			},
+		})))
+	}
+
+	fn try_iter_values(&self, column: &str) -> Result<DBTryIter> {
+		let column_idx = self
+			.columns
+			.iter()
+			.position(|col| col.as_str() == column)
+			.ok_or_else(|| eyre!("invalid column: {}", column))? as u8;
+		let mut iter = self.inner.iter(column_idx)?;
+		let mut finished = false;
+
+		Ok(Box::new(std::iter::from_fn(move || {
+			if finished {
+				return None
 			}
+			match iter.next() {
+				Ok(Some((key, value))) => Some(Ok((key.into_boxed_slice(), value.into_boxed_slice()))),
+				Ok(None) => {
+					finished = true;
+					None
+				},
+				Err(e) => {
+					finished = true;
+					Some(Err(eyre!("read error: {:?}", e)))
+				},
+			}
+		})))
+	}
+
+	fn get(&self, column: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+		let column_idx = self
+			.columns
+			.iter()
+			.position(|col| col.as_str() == column)
+			.ok_or_else(|| eyre!("invalid column: {}", column))? as u8;
+		self.inner.get(column_idx, key).map_err(|e| eyre!("read error: {:?}", e))
+	}
+
+	fn iter_range(&self, column: &str, from: &[u8], to: Option<&[u8]>) -> Result<DBIter> {
+		let column_idx = self
+			.columns
+			.iter()
+			.position(|col| col.as_str() == column)
+			.ok_or_else(|| eyre!("invalid column: {}", column))? as u8;
+		let mut iter = self.inner.iter(column_idx)?;
+		iter.seek(from)?;
+		let to = to.map(|to| to.to_vec());
+
+		Ok(Box::new(std::iter::from_fn(move || match iter.next() {
+			Ok(Some((key, _))) if to.as_ref().is_some_and(|to| key.as_slice() >= to.as_slice()) => None,
+			Ok(Some((key, value))) => Some((key.into_boxed_slice(), value.into_boxed_slice())),
+			Ok(None) => None,
+			Err(e) => {
+				error!("Error reading column {}, iteration stopped: {:?}", column_idx, e);
+				None
+			},
 		})))
 	}
 
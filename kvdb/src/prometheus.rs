@@ -18,7 +18,8 @@ use crate::IntrospectorKvdb;
 use clap::Parser;
 use color_eyre::Result;
 use log::{error, info, trace};
-use prometheus_endpoint::{prometheus::IntGaugeVec, Opts, Registry};
+use polkadot_introspector_essentials::metrics::{self, MetricsRegistry};
+use prometheus_endpoint::{prometheus::IntGaugeVec, Opts};
 use rand::{thread_rng, Rng};
 use std::net::ToSocketAddrs;
 
@@ -52,12 +53,12 @@ pub async fn run_prometheus_endpoint_with_db<D: IntrospectorKvdb + Send + Sync +
 	db: D,
 	prometheus_opts: KvdbPrometheusOptions,
 ) -> Result<Vec<tokio::task::JoinHandle<()>>> {
-	let prometheus_registry = Registry::new_custom(Some("introspector".into()), None)?;
+	let prometheus_registry = MetricsRegistry::new("introspector")?;
 	let metrics = register_metrics(&prometheus_registry);
 	let socket_addr_str = format!("{}:{}", prometheus_opts.address, prometheus_opts.port);
 	let mut futures: Vec<tokio::task::JoinHandle<()>> = vec![];
 	for addr in socket_addr_str.to_socket_addrs()? {
-		let prometheus_registry = prometheus_registry.clone();
+		let prometheus_registry = prometheus_registry.registry().clone();
 		futures.push(tokio::spawn(async move {
 			prometheus_endpoint::init_prometheus(addr, prometheus_registry).await.unwrap()
 		}));
@@ -164,18 +165,27 @@ async fn update_db<D: IntrospectorKvdb>(
 	}
 }
 
-fn register_metrics(registry: &Registry) -> KvdbPrometheusMetrics {
-	let elements_count_gauge = prometheus_endpoint::register(
+/// Prints the metrics exported in Prometheus mode
+pub fn list_metrics() -> Result<()> {
+	let registry = MetricsRegistry::new("introspector")?;
+	register_metrics(&registry);
+	registry.print_catalog();
+
+	Ok(())
+}
+
+fn register_metrics(registry: &MetricsRegistry) -> KvdbPrometheusMetrics {
+	let elements_count_gauge = metrics::register(
 		IntGaugeVec::new(Opts::new("kvdb_elements_count", "Number of keys in kvdb"), &["column"]).unwrap(),
 		registry,
 	)
 	.expect("Failed to register metric");
-	let keys_size_gauge = prometheus_endpoint::register(
+	let keys_size_gauge = metrics::register(
 		IntGaugeVec::new(Opts::new("kvdb_keys_size", "Size of keys in KVDB"), &["column"]).unwrap(),
 		registry,
 	)
 	.expect("Failed to register metric");
-	let values_size_gauge = prometheus_endpoint::register(
+	let values_size_gauge = metrics::register(
 		IntGaugeVec::new(Opts::new("kvdb_values_size", "Size of keys in KVDB"), &["column"]).unwrap(),
 		registry,
 	)
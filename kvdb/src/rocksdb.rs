@@ -16,7 +16,7 @@
 
 //! Implementation of the introspection using RocksDB
 
-use super::{DBIter, IntrospectorKvdb};
+use super::{DBIter, DBTryIter, IntrospectorKvdb};
 use color_eyre::{eyre::eyre, Result};
 use rocksdb::{Options as RocksdbOptions, DB};
 use std::path::{Path, PathBuf};
@@ -30,6 +30,42 @@ pub struct IntrospectorRocksDB {
 
 const DEFAULT_COLUMN: &str = "default";
 
+/// Options for reading large ranges of a column
+fn scan_read_options() -> rocksdb::ReadOptions {
+	let mut iter_config = rocksdb::ReadOptions::default();
+	// Do not cache values we read
+	iter_config.fill_cache(false);
+	// Optimize for iterations
+	iter_config.set_readahead_size(4_194_304);
+	// We don't care about checksums in this tool
+	iter_config.set_verify_checksums(false);
+	// We never need to store elements when iterating
+	iter_config.set_pin_data(false);
+	iter_config
+}
+
+impl IntrospectorRocksDB {
+	fn raw_iter(&self, column: &str, iter_config: rocksdb::ReadOptions) -> Result<DBIter> {
+		let cf_handle = self
+			.inner
+			.cf_handle(column)
+			.ok_or_else(|| eyre!("invalid column: {}", column))?;
+		let mut iter = self.inner.raw_iterator_cf_opt(cf_handle, iter_config);
+		iter.seek_to_first();
+		Ok(Box::new(std::iter::from_fn(move || {
+			if !iter.valid() {
+				None
+			} else if let Some((key, value)) = iter.item() {
+				let ret = Some((Box::from(key), Box::from(value)));
+				iter.next();
+				ret
+			} else {
+				None
+			}
+		})))
+	}
+}
+
 impl IntrospectorKvdb for IntrospectorRocksDB {
 	fn new(path: &std::path::Path) -> Result<Self> {
 		let mut cf_opts = RocksdbOptions::default();
@@ -50,32 +86,56 @@ impl IntrospectorKvdb for IntrospectorRocksDB {
 	}
 
 	fn iter_values(&self, column: &str) -> Result<DBIter> {
+		let mut iter_config = scan_read_options();
+		// We never iterate backwards
+		iter_config.set_tailing(true);
+		self.raw_iter(column, iter_config)
+	}
+
+	fn get(&self, column: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+		let cf_handle = self
+			.inner
+			.cf_handle(column)
+			.ok_or_else(|| eyre!("invalid column: {}", column))?;
+		Ok(self.inner.get_cf(cf_handle, key)?)
+	}
+
+	fn iter_range(&self, column: &str, from: &[u8], to: Option<&[u8]>) -> Result<DBIter> {
+		let mut iter_config = scan_read_options();
+		iter_config.set_iterate_lower_bound(from);
+		if let Some(to) = to {
+			iter_config.set_iterate_upper_bound(to);
+		}
+		self.raw_iter(column, iter_config)
+	}
+
+	fn try_iter_values(&self, column: &str) -> Result<DBTryIter> {
 		let mut iter_config = rocksdb::ReadOptions::default();
 		// Do not cache values we read
 		iter_config.fill_cache(false);
 		// Optimize for iterations
 		iter_config.set_readahead_size(4_194_304);
-		// We don't care about checksums in this tool
-		iter_config.set_verify_checksums(false);
-		// We never iterate backwards
-		iter_config.set_tailing(true);
-		// We never need to store elements when iterating
-		iter_config.set_pin_data(false);
+		// Detect corrupted blocks
+		iter_config.set_verify_checksums(true);
 		let cf_handle = self
 			.inner
 			.cf_handle(column)
 			.ok_or_else(|| eyre!("invalid column: {}", column))?;
 		let mut iter = self.inner.raw_iterator_cf_opt(cf_handle, iter_config);
 		iter.seek_to_first();
+		let mut finished = false;
 		Ok(Box::new(std::iter::from_fn(move || {
-			if !iter.valid() {
-				None
-			} else if let Some((key, value)) = iter.item() {
-				let ret = Some((Box::from(key), Box::from(value)));
+			if finished {
+				return None
+			}
+			if let Some((key, value)) = iter.item() {
+				let ret = Some(Ok((Box::from(key), Box::from(value))));
 				iter.next();
 				ret
 			} else {
-				None
+				// The iterator is invalid either at the end or after an error
+				finished = true;
+				iter.status().err().map(|e| Err(eyre!("read error: {:?}", e)))
 			}
 		})))
 	}
@@ -0,0 +1,347 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Scanning of large columns
+//!
+//! A column is split into chunks by the two bytes following the prefix shared by all its keys, which are iterated
+//! by a pool of threads. Chunks are picked by threads as they become free, so a few large chunks do not leave other
+//! threads idle. The progress and the read rate are reported while scanning.
+
+use crate::{DBIter, IntrospectorKvdb};
+use color_eyre::{eyre::eyre, Result};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use log::info;
+use std::{
+	collections::BTreeMap,
+	panic::{self, AssertUnwindSafe},
+	sync::{
+		atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+		Condvar, Mutex,
+	},
+	time::Duration,
+};
+
+/// Number of chunks a column is split into
+pub const CHUNKS_COUNT: usize = 1 << 16;
+/// Number of keys read between updates of the progress in sequential scans
+const PROGRESS_UPDATE_KEYS: u64 = 100_000;
+/// Longest prefix shared by the keys of a column that is looked up, bounds the number of lookups
+const MAX_COMMON_PREFIX_LEN: usize = 64;
+/// Number of chunks per thread that are scanned ahead of the first unprocessed one in ordered scans
+const CHUNKS_AHEAD_PER_THREAD: usize = 4;
+
+/// Returns the range of keys `[from, to)` of the chunk of a column which keys all start with `prefix`
+///
+/// Chunks partition the key space: keys shorter than the prefix and two bytes belong to the chunk of their
+/// lexicographic predecessors, the first chunk starts from the empty key and the last one is unbounded.
+pub fn chunk_bounds(prefix: &[u8], idx: usize) -> (Vec<u8>, Option<Vec<u8>>) {
+	let bound = |idx: usize| [prefix, (idx as u16).to_be_bytes().as_slice()].concat();
+	let from = if idx == 0 { vec![] } else { bound(idx) };
+	let to = (idx + 1 < CHUNKS_COUNT).then(|| bound(idx + 1));
+	(from, to)
+}
+
+/// Returns the prefix shared by all keys of the column, so chunks are split by the bytes that differ
+///
+/// Keys are sorted, so the prefix is extended by a byte of the first key while no key is greater than the prefix
+/// followed by this byte and `0xff` bytes, which takes a lookup per byte.
+pub fn common_prefix<D: IntrospectorKvdb>(db: &D, column: &str) -> Result<Vec<u8>> {
+	let Some((first_key, _)) = db.iter_values(column)?.next() else { return Ok(vec![]) };
+	let mut prefix = vec![];
+	for &byte in first_key.iter().take(MAX_COMMON_PREFIX_LEN) {
+		if byte < 0xff {
+			let next = [prefix.as_slice(), &[byte + 1]].concat();
+			if db.iter_range(column, next.as_slice(), None)?.next().is_some() {
+				break
+			}
+		}
+		prefix.push(byte);
+	}
+
+	Ok(prefix)
+}
+
+/// Reports the progress of a column scan
+pub struct ScanProgress {
+	description: String,
+	bar: ProgressBar,
+	/// Log the totals when finished, also when the bar is not drawn, e.g. if stderr is not a terminal
+	log_totals: bool,
+	keys: AtomicU64,
+	bytes: AtomicU64,
+}
+
+impl ScanProgress {
+	/// Creates a progress of a scan split into chunks, or a sequential one if the number is not known
+	pub fn new(description: &str, chunks: Option<usize>) -> Self {
+		let bar = match chunks {
+			Some(chunks) => {
+				let bar = ProgressBar::new(chunks as u64);
+				bar.set_style(
+					ProgressStyle::with_template("{prefix} [{elapsed_precise}] [{bar:40}] {pos}/{len} chunks, {msg}")
+						.expect("valid template; qed"),
+				);
+				bar
+			},
+			None => {
+				let bar = ProgressBar::new_spinner();
+				bar.set_style(
+					ProgressStyle::with_template("{spinner} {prefix} [{elapsed_precise}] {msg}")
+						.expect("valid template; qed"),
+				);
+				bar.enable_steady_tick(Duration::from_millis(200));
+				bar
+			},
+		};
+		bar.set_prefix(description.to_string());
+
+		Self {
+			description: description.to_string(),
+			bar,
+			log_totals: true,
+			keys: AtomicU64::new(0),
+			bytes: AtomicU64::new(0),
+		}
+	}
+
+	/// Creates a progress which is never displayed
+	pub fn hidden() -> Self {
+		Self {
+			description: String::new(),
+			bar: ProgressBar::hidden(),
+			log_totals: false,
+			keys: AtomicU64::new(0),
+			bytes: AtomicU64::new(0),
+		}
+	}
+
+	/// Counts entries read by the iterator
+	pub fn track<'a>(&'a self, iter: DBIter<'a>) -> DBIter<'a> {
+		Box::new(iter.inspect(move |(key, value)| self.on_entry(key.len() + value.len())))
+	}
+
+	fn on_entry(&self, size: usize) {
+		let keys = self.keys.fetch_add(1, Ordering::Relaxed) + 1;
+		self.bytes.fetch_add(size as u64, Ordering::Relaxed);
+		if keys % PROGRESS_UPDATE_KEYS == 0 {
+			self.update_message();
+		}
+	}
+
+	fn on_chunk(&self) {
+		self.bar.inc(1);
+		self.update_message();
+	}
+
+	fn update_message(&self) {
+		let keys = self.keys.load(Ordering::Relaxed);
+		let bytes = self.bytes.load(Ordering::Relaxed);
+		self.bar
+			.set_message(format!("{} keys, {} ({}/s)", keys, HumanBytes(bytes), HumanBytes(self.rate(bytes))));
+	}
+
+	fn rate(&self, bytes: u64) -> u64 {
+		let elapsed = self.bar.elapsed().as_secs_f64();
+		if elapsed > 0.0 {
+			(bytes as f64 / elapsed) as u64
+		} else {
+			0
+		}
+	}
+
+	/// Removes the progress bar and logs the totals
+	pub fn finish(&self) {
+		self.bar.finish_and_clear();
+		if !self.log_totals {
+			return
+		}
+		let bytes = self.bytes.load(Ordering::Relaxed);
+		info!(
+			"{}: {} keys, {} read in {:.1}s ({}/s)",
+			self.description,
+			self.keys.load(Ordering::Relaxed),
+			HumanBytes(bytes),
+			self.bar.elapsed().as_secs_f64(),
+			HumanBytes(self.rate(bytes))
+		);
+	}
+}
+
+/// Processes all chunks of the column with `threads` threads, returns the results in the key order
+pub fn scan_chunks<D, S, F>(db: &D, column: &str, threads: usize, progress: &ScanProgress, f: F) -> Result<Vec<S>>
+where
+	D: IntrospectorKvdb + Sync,
+	S: Send,
+	F: Fn(DBIter) -> Result<S> + Sync,
+{
+	let prefix = common_prefix(db, column)?;
+	let next_chunk = AtomicUsize::new(0);
+
+	let mut results = std::thread::scope(|scope| {
+		let handles = (0..threads.max(1))
+			.map(|_| {
+				scope.spawn(|| -> Result<Vec<(usize, S)>> {
+					let mut chunk_results = vec![];
+					loop {
+						let idx = next_chunk.fetch_add(1, Ordering::Relaxed);
+						if idx >= CHUNKS_COUNT {
+							break
+						}
+						let (from, to) = chunk_bounds(&prefix, idx);
+						let iter = db.iter_range(column, from.as_slice(), to.as_deref())?;
+						chunk_results.push((idx, f(progress.track(iter))?));
+						progress.on_chunk();
+					}
+					Ok(chunk_results)
+				})
+			})
+			.collect::<Vec<_>>();
+
+		let mut results = vec![];
+		for handle in handles {
+			results.extend(handle.join().map_err(|_| eyre!("scan thread panicked"))??);
+		}
+		Ok::<_, color_eyre::Report>(results)
+	})?;
+	results.sort_unstable_by_key(|(idx, _)| *idx);
+
+	Ok(results.into_iter().map(|(_, res)| res).collect())
+}
+
+/// State of an ordered scan shared by the threads
+struct OrderedScan<S> {
+	/// The next chunk to pick
+	next_chunk: usize,
+	/// Chunks passed to the consumer, all chunks before this one
+	consumed: usize,
+	/// Results of the chunks scanned ahead of the consumer
+	scanned: BTreeMap<usize, Result<S>>,
+	/// The consumer doesn't need more chunks
+	is_stopped: bool,
+}
+
+/// Processes the chunks of the column with `threads` threads passing the results to `on_chunk` in the key order
+/// as soon as they are available, stops once `on_chunk` returns `false`
+///
+/// Only a few chunks per thread are scanned ahead of the first one not passed to `on_chunk` yet, so the results
+/// waiting for it are bounded, and the chunks being scanned are interrupted when the scan stops.
+pub fn scan_chunks_ordered<D, S, F, C>(
+	db: &D,
+	column: &str,
+	threads: usize,
+	progress: &ScanProgress,
+	f: F,
+	mut on_chunk: C,
+) -> Result<()>
+where
+	D: IntrospectorKvdb + Sync,
+	S: Send,
+	F: Fn(DBIter) -> Result<S> + Sync,
+	C: FnMut(S) -> Result<bool>,
+{
+	let prefix = common_prefix(db, column)?;
+	let threads = threads.max(1);
+	let max_ahead = threads * CHUNKS_AHEAD_PER_THREAD;
+	let state = Mutex::new(OrderedScan { next_chunk: 0, consumed: 0, scanned: BTreeMap::new(), is_stopped: false });
+	let changed = Condvar::new();
+	let is_stopped = AtomicBool::new(false);
+	let lock = || state.lock().expect("scan lock is poisoned");
+
+	std::thread::scope(|scope| {
+		for _ in 0..threads {
+			scope.spawn(|| loop {
+				let idx = {
+					let mut state = lock();
+					while !state.is_stopped &&
+						state.next_chunk < CHUNKS_COUNT &&
+						state.next_chunk >= state.consumed + max_ahead
+					{
+						state = changed.wait(state).expect("scan lock is poisoned");
+					}
+					if state.is_stopped || state.next_chunk >= CHUNKS_COUNT {
+						break
+					}
+					state.next_chunk += 1;
+					state.next_chunk - 1
+				};
+				let res = panic::catch_unwind(AssertUnwindSafe(|| {
+					let (from, to) = chunk_bounds(&prefix, idx);
+					let iter = db.iter_range(column, from.as_slice(), to.as_deref())?;
+					let iter: DBIter =
+						Box::new(progress.track(iter).take_while(|_| !is_stopped.load(Ordering::Relaxed)));
+					f(iter)
+				}))
+				.unwrap_or_else(|_| Err(eyre!("scan thread panicked")));
+				progress.on_chunk();
+				lock().scanned.insert(idx, res);
+				changed.notify_all();
+			});
+		}
+
+		let outcome = loop {
+			let res = {
+				let mut state = lock();
+				if state.consumed == CHUNKS_COUNT {
+					break Ok(())
+				}
+				loop {
+					let consumed = state.consumed;
+					if let Some(res) = state.scanned.remove(&consumed) {
+						state.consumed += 1;
+						break res
+					}
+					state = changed.wait(state).expect("scan lock is poisoned");
+				}
+			};
+			changed.notify_all();
+			match res.and_then(&mut on_chunk) {
+				Ok(true) => {},
+				Ok(false) => break Ok(()),
+				Err(e) => break Err(e),
+			}
+		};
+
+		is_stopped.store(true, Ordering::Relaxed);
+		lock().is_stopped = true;
+		changed.notify_all();
+		outcome
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_chunks_partition_key_space() {
+		assert_eq!(chunk_bounds(&[], 0), (vec![], Some(vec![0, 1])));
+		assert_eq!(chunk_bounds(&[], 0x0102), (vec![1, 2], Some(vec![1, 3])));
+		assert_eq!(chunk_bounds(&[], CHUNKS_COUNT - 1), (vec![0xff, 0xff], None));
+		assert_eq!(chunk_bounds(&[0xaa], 0x0102), (vec![0xaa, 1, 2], Some(vec![0xaa, 1, 3])));
+
+		for prefix in [vec![], vec![0x01]] {
+			for key in [vec![], vec![0x00], vec![0x01], vec![0x01, 0x02, 0x03], vec![0xff, 0xff, 0xff]] {
+				let containing = (0..CHUNKS_COUNT)
+					.filter(|idx| {
+						let (from, to) = chunk_bounds(&prefix, *idx);
+						key >= from && !to.is_some_and(|to| key >= to)
+					})
+					.count();
+				assert_eq!(containing, 1, "key {:?}, prefix {:?}", key, prefix);
+			}
+		}
+	}
+}
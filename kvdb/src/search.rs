@@ -0,0 +1,209 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Streaming search of keys in a column
+//!
+//! Patterns are either hex strings prefixed with `0x`, matching keys that contain these bytes anywhere,
+//! or regular expressions matched against raw key bytes, e.g. `^candidate-votes`.
+
+use crate::{scan, scan::ScanProgress, DBIter, IntrospectorKvdb};
+use color_eyre::{eyre::eyre, Result};
+use polkadot_introspector_essentials::output::OutputRecord;
+use regex::bytes::Regex;
+use serde::Serialize;
+use std::fmt::{Display, Formatter, Write};
+
+/// Builds a regular expression matching raw key bytes from a hex or regex pattern
+pub fn parse_pattern(pattern: &str) -> Result<Regex> {
+	let expr = match pattern.strip_prefix("0x") {
+		Some(hex_pattern) => {
+			let bytes = hex::decode(hex_pattern).map_err(|e| eyre!("invalid hex pattern {}: {:?}", pattern, e))?;
+			// Disable Unicode to match arbitrary bytes
+			bytes.iter().fold(String::from("(?-u)"), |mut expr, byte| {
+				let _ = write!(expr, "\\x{:02x}", byte);
+				expr
+			})
+		},
+		None => pattern.to_string(),
+	};
+
+	Regex::new(expr.as_str()).map_err(|e| eyre!("invalid pattern {}: {:?}", pattern, e))
+}
+
+/// Options to search keys
+pub struct KeySearchOptions<'a> {
+	/// Column to search in
+	pub column: &'a str,
+	/// Pattern matched against keys
+	pub pattern: Regex,
+	/// Number of matches to skip
+	pub offset: usize,
+	/// Maximum number of matches to return
+	pub limit: usize,
+	/// Return values of the matching keys
+	pub with_values: bool,
+}
+
+/// A key matching the pattern
+#[derive(Serialize)]
+pub struct KeySearchMatch {
+	/// Hex encoded key
+	pub key: String,
+	/// Hex encoded value if requested
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub value: Option<String>,
+}
+
+impl OutputRecord for KeySearchMatch {
+	const CSV_COLUMNS: &'static [&'static str] = &["key", "value"];
+}
+
+impl Display for KeySearchMatch {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "0x{}", self.key)?;
+		if let Some(value) = &self.value {
+			write!(f, ": 0x{}", value)?;
+		}
+		Ok(())
+	}
+}
+
+/// Outcome of a search
+#[derive(Debug, PartialEq, Eq)]
+pub struct KeySearchSummary {
+	/// Number of keys scanned
+	pub scanned: usize,
+	/// Number of returned matches
+	pub returned: usize,
+	/// Offset of the next page if the search stopped at the limit
+	pub next_offset: Option<usize>,
+}
+
+impl KeySearchMatch {
+	fn new(key: &[u8], value: &[u8], with_values: bool) -> Self {
+		Self { key: hex::encode(key), value: with_values.then(|| hex::encode(value)) }
+	}
+}
+
+/// Streams keys matching the pattern to `on_match` in the column order, stops after `limit` matches
+pub fn search_keys<D, F>(
+	db: &D,
+	opts: &KeySearchOptions,
+	progress: &ScanProgress,
+	mut on_match: F,
+) -> Result<KeySearchSummary>
+where
+	D: IntrospectorKvdb,
+	F: FnMut(KeySearchMatch) -> Result<()>,
+{
+	let mut summary = KeySearchSummary { scanned: 0, returned: 0, next_offset: None };
+	let mut matched = 0_usize;
+
+	for (key, value) in progress.track(db.iter_values(opts.column)?) {
+		summary.scanned += 1;
+		if !opts.pattern.is_match(&key) {
+			continue
+		}
+
+		matched += 1;
+		if matched <= opts.offset {
+			continue
+		}
+		if summary.returned == opts.limit {
+			summary.next_offset = Some(opts.offset + opts.limit);
+			break
+		}
+
+		summary.returned += 1;
+		on_match(KeySearchMatch::new(&key, &value, opts.with_values))?;
+	}
+
+	Ok(summary)
+}
+
+/// Same as `search_keys`, but the column is scanned by `threads` threads in chunks
+///
+/// Matches are collected per chunk, up to `offset + limit + 1` in each of them, and passed to `on_match`
+/// in the column order as soon as the preceding chunks are scanned. Only a few chunks are scanned ahead,
+/// and the scan stops once `limit` matches are passed.
+pub fn par_search_keys<D, F>(
+	db: &D,
+	opts: &KeySearchOptions,
+	threads: usize,
+	progress: &ScanProgress,
+	mut on_match: F,
+) -> Result<KeySearchSummary>
+where
+	D: IntrospectorKvdb + Sync,
+	F: FnMut(KeySearchMatch) -> Result<()>,
+{
+	let max_chunk_matches = opts.offset.saturating_add(opts.limit).saturating_add(1);
+	let mut summary = KeySearchSummary { scanned: 0, returned: 0, next_offset: None };
+	let mut skipped = 0_usize;
+	let scan_chunk = |iter: DBIter| {
+		let mut scanned = 0_usize;
+		let mut matches = vec![];
+		for (key, value) in iter {
+			scanned += 1;
+			if opts.pattern.is_match(&key) {
+				matches.push(KeySearchMatch::new(&key, &value, opts.with_values));
+				if matches.len() == max_chunk_matches {
+					break
+				}
+			}
+		}
+		Ok((scanned, matches))
+	};
+	scan::scan_chunks_ordered(db, opts.column, threads, progress, scan_chunk, |(scanned, matches)| {
+		summary.scanned += scanned;
+		for res in matches {
+			if skipped < opts.offset {
+				skipped += 1;
+				continue
+			}
+			if summary.returned == opts.limit {
+				summary.next_offset = Some(opts.offset + opts.limit);
+				return Ok(false)
+			}
+			summary.returned += 1;
+			on_match(res)?;
+		}
+		Ok(true)
+	})?;
+
+	Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parses_patterns() {
+		let hex_pattern = parse_pattern("0x0102").unwrap();
+		assert!(hex_pattern.is_match(&[0x00, 0x01, 0x02, 0x03]));
+		assert!(!hex_pattern.is_match(&[0x01, 0x03, 0x02]));
+		// Bytes which are not valid UTF-8
+		assert!(parse_pattern("0xff").unwrap().is_match(&[0xff]));
+
+		let regex_pattern = parse_pattern("^candidate-votes").unwrap();
+		assert!(regex_pattern.is_match(b"candidate-votes\x00\x01"));
+		assert!(!regex_pattern.is_match(b"xcandidate-votes"));
+
+		assert!(parse_pattern("0x012").is_err());
+		assert!(parse_pattern("(").is_err());
+	}
+}
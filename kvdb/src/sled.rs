@@ -0,0 +1,195 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implementation of the introspection using sled
+//!
+//! Nodes never use sled, but it is written in pure Rust and builds everywhere. RocksDB and ParityDB databases
+//! can be dumped into sled on a machine where they can be opened, and introspected as sled anywhere else.
+
+use super::{DBIter, DBTryIter, IntrospectorKvdb};
+use color_eyre::{eyre::eyre, Result};
+use log::error;
+use sled::{Db, IVec, Tree};
+use std::{
+	ops::Bound,
+	path::{Path, PathBuf},
+};
+
+pub struct IntrospectorSled {
+	inner: Db,
+	columns: Vec<String>,
+	read_only: bool,
+	path: PathBuf,
+}
+
+/// The tree sled always creates, columns are stored in named trees
+const DEFAULT_TREE: &[u8] = b"__sled__default";
+
+impl IntrospectorSled {
+	fn tree(&self, column: &str) -> Result<Tree> {
+		if !self.columns.iter().any(|col| col.as_str() == column) {
+			return Err(eyre!("invalid column: {}", column))
+		}
+
+		self.inner
+			.open_tree(column)
+			.map_err(|e| eyre!("cannot open column {}: {:?}", column, e))
+	}
+}
+
+/// Stops at the first read error like the other databases, logging it instead of ending as if the column was read
+fn entries<'a, I>(column: &str, mut iter: I) -> DBIter<'a>
+where
+	I: Iterator<Item = sled::Result<(IVec, IVec)>> + Send + Sync + 'a,
+{
+	let column = column.to_string();
+	let mut finished = false;
+
+	Box::new(std::iter::from_fn(move || {
+		if finished {
+			return None
+		}
+		match iter.next()? {
+			Ok((key, value)) => Some((Box::from(key.as_ref()), Box::from(value.as_ref()))),
+			Err(e) => {
+				finished = true;
+				error!("Error reading column {}, iteration stopped: {:?}", column, e);
+				None
+			},
+		}
+	}))
+}
+
+impl IntrospectorKvdb for IntrospectorSled {
+	fn new(path: &std::path::Path) -> Result<Self> {
+		// Sled creates a database at any path it opens, a read-only one must exist
+		if !path.join("conf").is_file() {
+			return Err(eyre!("no sled database at {}", path.display()))
+		}
+		let db = sled::open(path)?;
+		// Ignore the default tree to be compatible with ParityDB
+		let columns = db
+			.tree_names()
+			.into_iter()
+			.filter(|name| name.as_ref() != DEFAULT_TREE)
+			.map(|name| String::from_utf8_lossy(name.as_ref()).into_owned())
+			.collect::<Vec<_>>();
+		Ok(Self { inner: db, columns, read_only: true, path: path.into() })
+	}
+
+	fn list_columns(&self) -> color_eyre::Result<&Vec<String>> {
+		Ok(&self.columns)
+	}
+
+	fn iter_values(&self, column: &str) -> Result<DBIter> {
+		Ok(entries(column, self.tree(column)?.iter()))
+	}
+
+	fn try_iter_values(&self, column: &str) -> Result<DBTryIter> {
+		let mut iter = self.tree(column)?.iter();
+		let mut finished = false;
+
+		Ok(Box::new(std::iter::from_fn(move || {
+			if finished {
+				return None
+			}
+			match iter.next()? {
+				Ok((key, value)) => Some(Ok((Box::from(key.as_ref()), Box::from(value.as_ref())))),
+				Err(e) => {
+					finished = true;
+					Some(Err(eyre!("read error: {:?}", e)))
+				},
+			}
+		})))
+	}
+
+	fn get(&self, column: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+		Ok(self.tree(column)?.get(key)?.map(|value| value.to_vec()))
+	}
+
+	fn iter_range(&self, column: &str, from: &[u8], to: Option<&[u8]>) -> Result<DBIter> {
+		let upper_bound = to.map_or(Bound::Unbounded, |to| Bound::Excluded(to.to_vec()));
+		let iter = self.tree(column)?.range((Bound::Included(from.to_vec()), upper_bound));
+
+		Ok(entries(column, iter))
+	}
+
+	fn prefixed_iter_values<'a>(&'a self, column: &str, prefix: &'a str) -> Result<DBIter<'a>> {
+		Ok(entries(column, self.tree(column)?.scan_prefix(prefix.as_bytes())))
+	}
+
+	fn read_only(&self) -> bool {
+		self.read_only
+	}
+
+	fn get_db_path(&self) -> &Path {
+		self.path.as_path()
+	}
+
+	fn write_iter<I, K, V>(&self, column: &str, iter: I) -> Result<()>
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: AsRef<[u8]>,
+		V: AsRef<[u8]>,
+	{
+		if self.read_only {
+			return Err(eyre!("cannot write a read-only database"))
+		}
+
+		let tree = self.tree(column)?;
+		let mut is_written = false;
+		for (key, value) in iter {
+			tree.insert(key.as_ref(), value.as_ref())
+				.map_err(|e| eyre!("error putting the key: {:?}", e))?;
+			is_written = true;
+		}
+		// Empty iterators are common when a column is written in chunks
+		if is_written {
+			self.inner.flush().map_err(|e| eyre!("flush error: {:?}", e))?;
+		}
+
+		Ok(())
+	}
+
+	fn new_dumper<D: IntrospectorKvdb>(input: &D, output_path: &std::path::Path) -> Result<Self> {
+		let columns = input.list_columns()?.clone();
+		let db = sled::open(output_path)?;
+		for column in &columns {
+			db.open_tree(column)?;
+		}
+
+		Ok(IntrospectorSled { inner: db, columns, read_only: false, path: output_path.into() })
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use std::path::Path;
+
+	pub fn new_test_sled_db(output_path: &Path, num_columns: usize) -> IntrospectorSled {
+		let db = sled::open(output_path).unwrap();
+		let mut columns: Vec<String> = vec![];
+
+		for i in 0..num_columns {
+			let column = format!("col{}", i);
+			db.open_tree(&column).unwrap();
+			columns.push(column);
+		}
+
+		IntrospectorSled { inner: db, columns, read_only: false, path: output_path.into() }
+	}
+}
@@ -72,6 +72,7 @@ fn check_db<D: IntrospectorKvdb>(db: &D, ncolumns: usize) {
 	}
 }
 
+#[cfg(feature = "rocksdb")]
 #[test]
 fn test_migration_rocksdb_rocksdb() {
 	let ncolumns = 10;
@@ -112,6 +113,7 @@ fn test_migration_paritydb_paritydb() {
 	std::fs::remove_dir_all(dst_dir).unwrap();
 }
 
+#[cfg(feature = "rocksdb")]
 #[test]
 fn test_migration_paritydb_rocksdb() {
 	let ncolumns = 10;
@@ -132,6 +134,7 @@ fn test_migration_paritydb_rocksdb() {
 	std::fs::remove_dir_all(dst_dir).unwrap();
 }
 
+#[cfg(feature = "rocksdb")]
 #[test]
 fn test_migration_rocksdb_paritydb() {
 	let ncolumns = 10;
@@ -151,3 +154,131 @@ fn test_migration_rocksdb_paritydb() {
 	std::fs::remove_dir_all(src_dir).unwrap();
 	std::fs::remove_dir_all(dst_dir).unwrap();
 }
+
+#[test]
+fn test_migration_paritydb_sled() {
+	let ncolumns = 10;
+	let src_dir = make_temp_dir();
+	let src_db = crate::paritydb::tests::new_test_parity_db(src_dir.as_path(), ncolumns);
+
+	write_db(&src_db, ncolumns);
+	let dst_dir = make_temp_dir();
+
+	{
+		let dest_db = crate::sled::IntrospectorSled::new_dumper(&src_db, dst_dir.as_path()).unwrap();
+		copy_db(&src_db, &dest_db, ncolumns);
+	}
+
+	let dest_db = crate::sled::IntrospectorSled::new(dst_dir.as_path()).unwrap();
+	check_db(&dest_db, ncolumns);
+	assert!(dest_db.read_only());
+	assert!(dest_db.write_iter("col0", test_data_iter()).is_err());
+	std::fs::remove_dir_all(src_dir).unwrap();
+	std::fs::remove_dir_all(dst_dir).unwrap();
+}
+
+#[test]
+fn test_sled_opens_only_existing_databases() {
+	let dir = make_temp_dir();
+
+	assert!(crate::sled::IntrospectorSled::new(dir.as_path()).is_err());
+	assert!(std::fs::read_dir(dir.as_path()).unwrap().next().is_none());
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "rocksdb")]
+#[test]
+fn test_migration_sled_rocksdb() {
+	let ncolumns = 10;
+	let src_dir = make_temp_dir();
+	let src_db = crate::sled::tests::new_test_sled_db(src_dir.as_path(), ncolumns);
+
+	write_db(&src_db, ncolumns);
+	let dst_dir = make_temp_dir();
+
+	{
+		let dest_db = crate::rocksdb::IntrospectorRocksDB::new_dumper(&src_db, dst_dir.as_path()).unwrap();
+		copy_db(&src_db, &dest_db, ncolumns);
+	}
+
+	let dest_db = crate::rocksdb::IntrospectorRocksDB::new(dst_dir.as_path()).unwrap();
+	check_db(&dest_db, ncolumns);
+	std::fs::remove_dir_all(src_dir).unwrap();
+	std::fs::remove_dir_all(dst_dir).unwrap();
+}
+
+#[test]
+fn test_search_paritydb() {
+	let src_dir = make_temp_dir();
+	let db = crate::paritydb::tests::new_test_parity_db(src_dir.as_path(), 1);
+	let keys = (0_u8..10).map(|idx| vec![0xaa, idx, 0xbb]).collect::<Vec<_>>();
+	db.write_iter("col0", keys.iter().map(|key| (key.as_slice(), TEST_VALUE.as_slice())))
+		.unwrap();
+	assert_eq!(crate::scan::common_prefix(&db, "col0").unwrap(), vec![0xaa]);
+
+	let mut opts = crate::search::KeySearchOptions {
+		column: "col0",
+		pattern: crate::search::parse_pattern(r"(?-u)^\xaa[\x00-\x04]\xbb$").unwrap(),
+		offset: 1,
+		limit: 2,
+		with_values: true,
+	};
+	let progress = crate::scan::ScanProgress::hidden();
+	let mut found = vec![];
+	let summary = crate::search::search_keys(&db, &opts, &progress, |res| {
+		found.push(res.key);
+		Ok(())
+	})
+	.unwrap();
+	assert_eq!(found, vec!["aa01bb", "aa02bb"]);
+	assert_eq!(summary.next_offset, Some(3));
+
+	let mut par_found = vec![];
+	let par_summary = crate::search::par_search_keys(&db, &opts, 4, &progress, |res| {
+		par_found.push(res.key);
+		Ok(())
+	})
+	.unwrap();
+	assert_eq!(par_found, found);
+	assert_eq!(par_summary.next_offset, Some(3));
+
+	opts.pattern = crate::search::parse_pattern("0x04bb").unwrap();
+	opts.offset = 0;
+	let summary = crate::search::search_keys(&db, &opts, &progress, |res| {
+		assert_eq!(res.value.as_deref(), Some("000102"));
+		Ok(())
+	})
+	.unwrap();
+	assert_eq!(summary.returned, 1);
+	assert_eq!(summary.next_offset, None);
+	std::fs::remove_dir_all(src_dir).unwrap();
+}
+
+#[test]
+fn test_verify_migration_paritydb_sled() {
+	let ncolumns = 2;
+	let src_dir = make_temp_dir();
+	let src_db = crate::paritydb::tests::new_test_parity_db(src_dir.as_path(), ncolumns);
+
+	write_db(&src_db, ncolumns);
+	let dst_dir = make_temp_dir();
+	let dest_db = crate::sled::IntrospectorSled::new_dumper(&src_db, dst_dir.as_path()).unwrap();
+	copy_db(&src_db, &dest_db, ncolumns);
+
+	for col_idx in 0..ncolumns {
+		let column = format!("col{}", col_idx);
+		let src_report = crate::verify::verify_column(&src_db, column.as_str()).unwrap();
+		let dest_report = crate::verify::verify_column(&dest_db, column.as_str()).unwrap();
+		assert!(src_report.is_ok() && dest_report.is_ok());
+		assert_eq!(src_report.keys_count, 1);
+		assert_eq!(src_report.content_hash, dest_report.content_hash);
+	}
+
+	dest_db.write_iter("col1", [(b"extra", b"value")]).unwrap();
+	let src_report = crate::verify::verify_column(&src_db, "col1").unwrap();
+	let dest_report = crate::verify::verify_column(&dest_db, "col1").unwrap();
+	assert_ne!(src_report.content_hash, dest_report.content_hash);
+
+	std::fs::remove_dir_all(src_dir).unwrap();
+	std::fs::remove_dir_all(dst_dir).unwrap();
+}
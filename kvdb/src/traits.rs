@@ -17,6 +17,7 @@
 use color_eyre::Result;
 
 pub type DBIter<'a> = Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a + Send + Sync>;
+pub type DBTryIter<'a> = Box<dyn Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>> + 'a + Send + Sync>;
 /// A minimum subset of the functions required to open a database for introspection
 pub trait IntrospectorKvdb {
 	/// Opens database with some configuration
@@ -27,6 +28,12 @@ pub trait IntrospectorKvdb {
 	fn list_columns(&self) -> Result<&Vec<String>>;
 	/// Iterates over all keys in a specific column
 	fn iter_values<'a>(&'a self, column: &str) -> Result<DBIter<'a>>;
+	/// Iterates over all keys in a specific column verifying the data, ends after a read error
+	fn try_iter_values<'a>(&'a self, column: &str) -> Result<DBTryIter<'a>>;
+	/// Returns the value of a key in a specific column
+	fn get(&self, column: &str, key: &[u8]) -> Result<Option<Vec<u8>>>;
+	/// Iterates over keys in the range `[from, to)`, column must have order defined
+	fn iter_range<'a>(&'a self, column: &str, from: &[u8], to: Option<&[u8]>) -> Result<DBIter<'a>>;
 	/// Iterates over all keys that begin with the specific prefix, column must have order defined
 	fn prefixed_iter_values<'a>(&'a self, column: &str, prefix: &'a str) -> Result<DBIter<'a>>;
 	/// Returns if kvdb is in read-only mode
@@ -0,0 +1,123 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Integrity verification of columns
+//!
+//! Every column is read to the end, and its content is hashed in the key order, so the hashes of the same data
+//! are equal in all supported databases and can be compared before and after a migration.
+
+use crate::IntrospectorKvdb;
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use color_eyre::Result;
+use polkadot_introspector_essentials::output::OutputRecord;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+type Blake2b256 = Blake2b<U32>;
+
+/// Integrity report of a column
+#[derive(Clone, Debug, Serialize)]
+pub struct ColumnIntegrity {
+	/// Column name
+	pub column: String,
+	/// Number of entries read
+	pub keys_count: usize,
+	/// Size of keys read
+	pub keys_size: usize,
+	/// Size of values read
+	pub values_size: usize,
+	/// Hex encoded hash of the entries read
+	pub content_hash: String,
+	/// Hex encoded last key read before an error
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_key: Option<String>,
+	/// Error which stopped reading the column
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+impl ColumnIntegrity {
+	/// Returns if the column was read to the end
+	pub fn is_ok(&self) -> bool {
+		self.error.is_none()
+	}
+}
+
+impl OutputRecord for ColumnIntegrity {
+	const CSV_COLUMNS: &'static [&'static str] =
+		&["column", "keys_count", "keys_size", "values_size", "content_hash", "last_key", "error"];
+}
+
+impl Display for ColumnIntegrity {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match &self.error {
+			None => write!(
+				f,
+				"{}: OK, {} keys, keys size: {} bytes, values size: {} bytes, content hash: 0x{}",
+				self.column, self.keys_count, self.keys_size, self.values_size, self.content_hash
+			),
+			Some(error) => write!(
+				f,
+				"{}: CORRUPTED after {} keys (last readable key: {}): {}",
+				self.column,
+				self.keys_count,
+				self.last_key
+					.as_ref()
+					.map_or_else(|| "none".to_owned(), |key| format!("0x{}", key)),
+				error
+			),
+		}
+	}
+}
+
+/// Reads the column to the end or to the first read error
+pub fn verify_column<D: IntrospectorKvdb>(db: &D, column: &str) -> Result<ColumnIntegrity> {
+	let mut hasher = Blake2b256::new();
+	let mut report = ColumnIntegrity {
+		column: column.to_string(),
+		keys_count: 0,
+		keys_size: 0,
+		values_size: 0,
+		content_hash: String::new(),
+		last_key: None,
+		error: None,
+	};
+	let mut last_key: Option<Box<[u8]>> = None;
+
+	for entry in db.try_iter_values(column)? {
+		match entry {
+			Ok((key, value)) => {
+				// Prefix with lengths to make the concatenation unambiguous
+				hasher.update((key.len() as u64).to_le_bytes());
+				hasher.update(&key);
+				hasher.update((value.len() as u64).to_le_bytes());
+				hasher.update(&value);
+				report.keys_count += 1;
+				report.keys_size += key.len();
+				report.values_size += value.len();
+				last_key = Some(key);
+			},
+			Err(e) => {
+				report.error = Some(e.to_string());
+				report.last_key = last_key.as_ref().map(hex::encode);
+				break
+			},
+		}
+	}
+	report.content_hash = hex::encode(hasher.finalize());
+
+	Ok(report)
+}
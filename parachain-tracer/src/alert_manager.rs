@@ -0,0 +1,271 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Deduplication, flap suppression and resolution of the alerts posted to the sinks.
+//!
+//! An alert is identified by its name and parachain. It's posted when it fires for the first time, and resolved
+//! when its condition stays clear for a cooldown number of relay chain blocks: explicitly cleared conditions like
+//! stalls count from the block they were cleared in, events like anomalies count from their last occurrence.
+//! Firing again within the cooldown doesn't post anything, it's counted as a duplicate occurrence or, after the
+//! condition was cleared, as a flap. The counts are reported with the resolution. An alert is registered only once it
+//! has been posted, so the sinks never receive a resolution of an alert they haven't received, e.g. a muted one.
+
+use crate::explorer::Explorer;
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::types::BlockNumber;
+use serde::Serialize;
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+};
+
+/// Identifies alerts of the same condition
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub(crate) struct AlertKey {
+	/// Kind of the stall or name of the anomaly detector
+	pub name: String,
+	pub para_id: Option<u32>,
+}
+
+impl AlertKey {
+	pub fn new(name: impl Into<String>, para_id: Option<u32>) -> Self {
+		Self { name: name.into(), para_id }
+	}
+}
+
+impl Display for AlertKey {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self.para_id {
+			Some(para_id) => write!(f, "{} of parachain {}", self.name, para_id),
+			None => write!(f, "{}", self.name),
+		}
+	}
+}
+
+/// An alert that has cleared
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct AlertResolution {
+	#[serde(flatten)]
+	pub key: AlertKey,
+	/// Always `resolved`, tells resolutions from alerts posted to the same webhook
+	pub status: &'static str,
+	/// Relay chain block the alert was fired at
+	pub fired_at: BlockNumber,
+	/// Relay chain block the alert was resolved at
+	pub block_number: BlockNumber,
+	/// Number of times the alert fired, including the first one
+	pub occurrences: u32,
+	/// Number of times the condition was cleared and fired again within the cooldown
+	pub flaps: u32,
+	#[serde(skip)]
+	pub explorer: Explorer,
+}
+
+impl Display for AlertResolution {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"[#{}] {} {}, fired at #{}, {} occurrences",
+			self.block_number,
+			"RESOLVED".to_string().bold().green(),
+			self.key,
+			self.fired_at,
+			self.occurrences
+		)?;
+		if self.flaps > 0 {
+			write!(f, ", flapped {} times", self.flaps.to_string().bright_yellow())?;
+		}
+		writeln!(f)?;
+		self.explorer.write_block_link(f, self.block_number)
+	}
+}
+
+struct AlertState {
+	fired_at: BlockNumber,
+	last_seen: BlockNumber,
+	occurrences: u32,
+	flaps: u32,
+	/// Block the condition was cleared in, if it was
+	cleared_at: Option<BlockNumber>,
+	/// Events are never cleared explicitly
+	is_event: bool,
+}
+
+impl AlertState {
+	/// Returns the block since that the condition is clear
+	fn clear_since(&self) -> Option<BlockNumber> {
+		if self.is_event {
+			Some(self.last_seen)
+		} else {
+			self.cleared_at
+		}
+	}
+}
+
+/// Keeps the state of the alerts posted to the sinks
+pub(crate) struct AlertManager {
+	/// Number of relay chain blocks an alert stays open after its condition has cleared
+	cooldown: u32,
+	alerts: HashMap<AlertKey, AlertState>,
+	explorer: Explorer,
+}
+
+impl AlertManager {
+	pub fn new(cooldown: u32) -> Self {
+		Self { cooldown, alerts: Default::default(), explorer: Default::default() }
+	}
+
+	/// Links the resolutions to the block explorer
+	pub fn with_explorer(mut self, explorer: Explorer) -> Self {
+		self.explorer = explorer;
+		self
+	}
+
+	/// Processes a condition that has fired, returns if the alert should be posted.
+	/// A muted alert is not posted, it only counts as an occurrence of an alert posted before.
+	pub fn on_fire(&mut self, key: AlertKey, block_number: BlockNumber, is_muted: bool) -> bool {
+		self.fire(key, block_number, false, is_muted)
+	}
+
+	/// Processes an event, returns if the alert should be posted.
+	/// A muted alert is not posted, it only counts as an occurrence of an alert posted before.
+	pub fn on_event(&mut self, key: AlertKey, block_number: BlockNumber, is_muted: bool) -> bool {
+		self.fire(key, block_number, true, is_muted)
+	}
+
+	fn fire(&mut self, key: AlertKey, block_number: BlockNumber, is_event: bool, is_muted: bool) -> bool {
+		match self.alerts.get_mut(&key) {
+			Some(state) => {
+				state.occurrences += 1;
+				state.last_seen = block_number;
+				if state.cleared_at.take().is_some() {
+					state.flaps += 1;
+				}
+				false
+			},
+			None if is_muted => false,
+			None => {
+				self.alerts.insert(
+					key,
+					AlertState {
+						fired_at: block_number,
+						last_seen: block_number,
+						occurrences: 1,
+						flaps: 0,
+						cleared_at: None,
+						is_event,
+					},
+				);
+				true
+			},
+		}
+	}
+
+	/// Processes a condition that has cleared, the alert is resolved after the cooldown
+	pub fn on_clear(&mut self, key: &AlertKey, block_number: BlockNumber) {
+		if let Some(state) = self.alerts.get_mut(key) {
+			state.cleared_at.get_or_insert(block_number);
+		}
+	}
+
+	/// Returns the alerts resolved by a new relay chain block
+	pub fn on_block(&mut self, block_number: BlockNumber) -> Vec<AlertResolution> {
+		let cooldown = self.cooldown;
+		let resolved: Vec<AlertKey> = self
+			.alerts
+			.iter()
+			.filter(|(_, state)| state.clear_since().is_some_and(|since| block_number >= since + cooldown))
+			.map(|(key, _)| key.clone())
+			.collect();
+		let mut resolutions: Vec<AlertResolution> = resolved
+			.into_iter()
+			.filter_map(|key| {
+				let state = self.alerts.remove(&key)?;
+				Some(AlertResolution {
+					key,
+					status: "resolved",
+					fired_at: state.fired_at,
+					block_number,
+					occurrences: state.occurrences,
+					flaps: state.flaps,
+					explorer: self.explorer.clone(),
+				})
+			})
+			.collect();
+		resolutions.sort_by_key(|v| v.fired_at);
+
+		resolutions
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_collapses_flapping_conditions() {
+		let mut manager = AlertManager::new(5);
+		let key = AlertKey::new("no_backed", Some(100));
+
+		assert!(manager.on_fire(key.clone(), 10, false));
+		manager.on_clear(&key, 12);
+		assert!(manager.on_block(14).is_empty());
+		// Fires again before the cooldown ends
+		assert!(!manager.on_fire(key.clone(), 15, false));
+		manager.on_clear(&key, 16);
+		assert!(manager.on_block(20).is_empty());
+
+		let resolutions = manager.on_block(21);
+		assert_eq!(resolutions.len(), 1);
+		assert_eq!((resolutions[0].fired_at, resolutions[0].occurrences, resolutions[0].flaps), (10, 2, 1));
+		// Posted again once resolved
+		assert!(manager.on_fire(key, 30, false));
+	}
+
+	#[test]
+	fn test_deduplicates_events() {
+		let mut manager = AlertManager::new(3);
+		let key = AlertKey::new("slow-block", None);
+
+		assert!(manager.on_event(key.clone(), 10, false));
+		assert!(!manager.on_event(key.clone(), 11, false));
+		assert!(!manager.on_event(key.clone(), 13, false));
+		assert!(manager.on_event(AlertKey::new("slow-block", Some(100)), 13, false));
+		assert!(manager.on_block(15).is_empty());
+
+		let resolutions = manager.on_block(16);
+		assert_eq!(resolutions.len(), 2);
+		assert_eq!((resolutions[0].key.para_id, resolutions[0].occurrences, resolutions[0].flaps), (None, 3, 0));
+	}
+
+	#[test]
+	fn test_registers_only_posted_alerts() {
+		let mut manager = AlertManager::new(3);
+		let key = AlertKey::new("slow-block", None);
+
+		// A muted alert is not resolved later
+		assert!(!manager.on_event(key.clone(), 10, true));
+		assert!(manager.on_block(20).is_empty());
+
+		// Muted occurrences of a posted alert are counted
+		assert!(manager.on_event(key.clone(), 21, false));
+		assert!(!manager.on_event(key.clone(), 22, true));
+		let resolutions = manager.on_block(25);
+		assert_eq!(resolutions.len(), 1);
+		assert_eq!(resolutions[0].occurrences, 2);
+	}
+}
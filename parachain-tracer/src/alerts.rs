@@ -0,0 +1,260 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Alerts on parachains that have not backed or included a candidate for a number of relay chain blocks.
+//!
+//! An alert is raised once when a threshold is reached, and again only after the parachain has recovered.
+//! Alerts can be posted as JSON to a webhook, e.g. to open PagerDuty incidents.
+
+use crate::{
+	explorer::Explorer,
+	para_names::DisplayPara,
+	types::{ParachainConsensusEvent, ParachainProgressUpdate},
+};
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use log::warn;
+use polkadot_introspector_essentials::types::{BlockNumber, Timestamp, H256};
+use serde::Serialize;
+use std::{
+	fmt::{self, Display, Formatter},
+	time::Duration,
+};
+
+const HTTP_UA: &str = "polkadot-introspector";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a parachain has not done for too long
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StallKind {
+	NoBacked,
+	NoIncluded,
+}
+
+impl StallKind {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			StallKind::NoBacked => "no_backed",
+			StallKind::NoIncluded => "no_included",
+		}
+	}
+}
+
+/// A parachain has not backed or included a candidate for a number of relay chain blocks
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StallAlert {
+	pub para_id: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub para_name: Option<String>,
+	pub kind: StallKind,
+	/// Relay chain block the alert was raised at
+	pub block_number: BlockNumber,
+	pub block_hash: H256,
+	pub timestamp: Timestamp,
+	/// Number of relay chain blocks without a candidate backed or included
+	pub blocks: u32,
+	/// Relay chain block of the last candidate backed or included, not set if none was seen since the start
+	pub last_seen: Option<BlockNumber>,
+	#[serde(skip)]
+	pub explorer: Explorer,
+}
+
+#[derive(Default)]
+struct StallState {
+	/// Relay chain block of the last event or the first block seen
+	since: Option<BlockNumber>,
+	/// Relay chain block of the last event
+	last_seen: Option<BlockNumber>,
+	alerted: bool,
+}
+
+impl StallState {
+	/// Returns the number of blocks without the event when the threshold is reached for the first time
+	fn on_block(&mut self, block_number: BlockNumber, happened: bool, threshold: u32) -> Option<u32> {
+		if happened {
+			*self = StallState { since: Some(block_number), last_seen: Some(block_number), alerted: false };
+			return None
+		}
+		let blocks = block_number.saturating_sub(*self.since.get_or_insert(block_number));
+		if blocks >= threshold && !self.alerted {
+			self.alerted = true;
+			Some(blocks)
+		} else {
+			None
+		}
+	}
+}
+
+/// Raises alerts on a parachain which stopped backing or including candidates
+pub struct StallDetector {
+	/// Number of blocks without a backed candidate to alert after
+	no_backed: Option<u32>,
+	/// Number of blocks without an included candidate to alert after
+	no_included: Option<u32>,
+	backed: StallState,
+	included: StallState,
+}
+
+impl StallDetector {
+	pub fn new(no_backed: Option<u32>, no_included: Option<u32>) -> Self {
+		Self { no_backed, no_included, backed: Default::default(), included: Default::default() }
+	}
+
+	/// Replaces the thresholds keeping the blocks counted so far
+	pub fn set_thresholds(&mut self, no_backed: Option<u32>, no_included: Option<u32>) {
+		self.no_backed = no_backed;
+		self.no_included = no_included;
+	}
+
+	/// Processes a progress of the parachain, returns the alerts raised in its block
+	pub fn on_progress(&mut self, progress: &ParachainProgressUpdate) -> Vec<StallAlert> {
+		if progress.is_fork {
+			return vec![]
+		}
+
+		let is_backed = progress.events.iter().any(|v| matches!(v, ParachainConsensusEvent::Backed(..)));
+		let is_included = progress
+			.events
+			.iter()
+			.any(|v| matches!(v, ParachainConsensusEvent::Included(..)));
+		let mut alerts = vec![];
+		for (kind, state, happened, threshold) in [
+			(StallKind::NoBacked, &mut self.backed, is_backed, self.no_backed),
+			(StallKind::NoIncluded, &mut self.included, is_included, self.no_included),
+		] {
+			let Some(threshold) = threshold else { continue };
+			if let Some(blocks) = state.on_block(progress.block_number, happened, threshold) {
+				alerts.push(StallAlert {
+					para_id: progress.para_id,
+					para_name: progress.para_names.get(progress.para_id).map(|name| name.to_string()),
+					kind,
+					block_number: progress.block_number,
+					block_hash: progress.block_hash,
+					timestamp: progress.timestamp,
+					blocks,
+					last_seen: state.last_seen,
+					explorer: progress.explorer.clone(),
+				});
+			}
+		}
+
+		alerts
+	}
+
+	/// Returns if an alert of the kind was raised and the parachain hasn't recovered since
+	pub fn is_stalled(&self, kind: StallKind) -> bool {
+		match kind {
+			StallKind::NoBacked => self.backed.alerted,
+			StallKind::NoIncluded => self.included.alerted,
+		}
+	}
+}
+
+impl Display for StallAlert {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		let action = match self.kind {
+			StallKind::NoBacked => "backed",
+			StallKind::NoIncluded => "included",
+		};
+		write!(
+			f,
+			"[#{}] {} parachain {} has not {} a candidate for {} blocks",
+			self.block_number,
+			"STALL ALERT".to_string().bold().red(),
+			DisplayPara(self.para_id, self.para_name.as_deref().map(Into::into)),
+			action,
+			self.blocks.to_string().bright_red()
+		)?;
+		match self.last_seen {
+			Some(block_number) => writeln!(f, ", last one at #{}", block_number)?,
+			None => writeln!(f, ", none since the start")?,
+		}
+		self.explorer.write_block_link(f, self.block_number)
+	}
+}
+
+/// Posts alerts as JSON to a URL
+#[derive(Clone)]
+pub struct AlertWebhook {
+	url: reqwest::Url,
+	http_client: reqwest::Client,
+}
+
+impl AlertWebhook {
+	pub fn new(url: &str) -> color_eyre::Result<Self> {
+		let http_client = reqwest::Client::builder().timeout(HTTP_TIMEOUT).user_agent(HTTP_UA).build()?;
+
+		Ok(Self { url: reqwest::Url::parse(url)?, http_client })
+	}
+
+	/// Posts an alert or its resolution in the background, failures are logged
+	pub fn post(&self, alert: &impl Serialize) {
+		let request = self
+			.http_client
+			.post(self.url.clone())
+			.header(reqwest::header::CONTENT_TYPE, "application/json")
+			.body(serde_json::to_string(alert).expect("alerts are serializable; qed"));
+		tokio::spawn(async move {
+			match request.send().await {
+				Ok(response) if !response.status().is_success() =>
+					warn!("Alert webhook returned {}", response.status()),
+				Ok(_) => {},
+				Err(e) => warn!("Cannot post an alert to the webhook: {:?}", e),
+			}
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::create_progress;
+
+	#[test]
+	fn test_alerts_once_per_stall() {
+		let mut detector = StallDetector::new(Some(3), None);
+		let backed = || vec![ParachainConsensusEvent::Backed(H256::random(), 2, 3)];
+
+		assert!(detector.on_progress(&create_progress(10, backed())).is_empty());
+		assert!(detector.on_progress(&create_progress(12, vec![])).is_empty());
+		let alerts = detector.on_progress(&create_progress(13, vec![]));
+		assert_eq!(alerts.len(), 1);
+		assert_eq!((alerts[0].kind, alerts[0].blocks, alerts[0].last_seen), (StallKind::NoBacked, 3, Some(10)));
+		assert!(detector.on_progress(&create_progress(14, vec![])).is_empty());
+
+		assert!(detector.is_stalled(StallKind::NoBacked));
+
+		// Raised again after the parachain has recovered
+		assert!(detector.on_progress(&create_progress(15, backed())).is_empty());
+		assert!(!detector.is_stalled(StallKind::NoBacked));
+		assert_eq!(detector.on_progress(&create_progress(18, vec![])).len(), 1);
+	}
+
+	#[test]
+	fn test_counts_from_the_first_block() {
+		let mut detector = StallDetector::new(Some(5), Some(2));
+
+		assert!(detector.on_progress(&create_progress(10, vec![])).is_empty());
+		let alerts = detector.on_progress(&create_progress(12, vec![]));
+		assert_eq!(alerts.len(), 1);
+		assert_eq!((alerts[0].kind, alerts[0].last_seen), (StallKind::NoIncluded, None));
+		// Forks are ignored
+		let mut fork = create_progress(20, vec![]);
+		fork.is_fork = true;
+		assert!(detector.on_progress(&fork).is_empty());
+	}
+}
@@ -0,0 +1,203 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Detects suspected approval no-shows for the candidates of a parachain.
+//!
+//! Approval votes are not recorded on chain, but a relay chain block can't be finalized until the candidates included
+//! in it are approved. GRANDPA always lags a few blocks behind, so the lag is measured against the median finality
+//! lag of the recent blocks: a candidate that stays unfinalized for more than `no_show_slots` relay chain blocks
+//! beyond it suggests that some of the assigned approval checkers haven't voted in time.
+
+use crate::types::{ParachainConsensusEvent, ParachainProgressUpdate};
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::types::{BlockNumber, H256};
+use std::{
+	collections::VecDeque,
+	fmt::{self, Display, Formatter},
+};
+
+/// Number of recent relay chain blocks the expected finality lag is taken over
+const FINALITY_LAG_WINDOW: usize = 100;
+
+/// A candidate not finalized in time after its inclusion
+#[derive(Clone, Debug, PartialEq)]
+pub struct SuspectedNoShow {
+	/// Relay chain block the no-show is detected at
+	pub block_number: BlockNumber,
+	pub candidate_hash: H256,
+	/// Relay chain block the candidate was included in
+	pub included_at: BlockNumber,
+	/// Number of relay chain blocks the candidate has been waiting for finality
+	pub unfinalized_blocks: u32,
+	/// The median finality lag of the recent blocks
+	pub expected_lag: u32,
+	/// The `no_show_slots` of the host configuration
+	pub no_show_slots: u32,
+}
+
+/// An included candidate waiting for finality
+struct UnfinalizedCandidate {
+	candidate_hash: H256,
+	included_at: BlockNumber,
+	/// A no-show has already been reported for the candidate
+	is_reported: bool,
+}
+
+/// Follows the included candidates of a parachain until their relay chain blocks are finalized
+pub struct NoShowDetector {
+	/// Number of relay chain blocks after that an approval checker is considered a no-show
+	no_show_slots: u32,
+	/// Finality lags of the recent relay chain blocks
+	recent_lags: VecDeque<u32>,
+	/// Included candidates not yet finalized
+	unfinalized: Vec<UnfinalizedCandidate>,
+}
+
+impl NoShowDetector {
+	pub fn new(no_show_slots: u32) -> Self {
+		Self { no_show_slots, recent_lags: VecDeque::with_capacity(FINALITY_LAG_WINDOW), unfinalized: vec![] }
+	}
+
+	/// Processes the progress of the parachain, returns the candidates that have just exceeded the expected finality
+	/// lag by more than `no_show_slots`
+	pub fn on_progress(&mut self, progress: &ParachainProgressUpdate) -> Vec<SuspectedNoShow> {
+		for event in &progress.events {
+			if let ParachainConsensusEvent::Included(candidate_hash, _, _) = event {
+				self.unfinalized.push(UnfinalizedCandidate {
+					candidate_hash: *candidate_hash,
+					included_at: progress.block_number,
+					is_reported: false,
+				});
+			}
+		}
+
+		let Some(finality_lag) = progress.finality_lag else { return vec![] };
+		let finalized = progress.block_number.saturating_sub(finality_lag);
+		self.unfinalized.retain(|candidate| candidate.included_at > finalized);
+		if self.recent_lags.len() == FINALITY_LAG_WINDOW {
+			self.recent_lags.pop_front();
+		}
+		self.recent_lags.push_back(finality_lag);
+		let expected_lag = self.expected_lag();
+
+		let mut suspected = vec![];
+		for candidate in self.unfinalized.iter_mut().filter(|candidate| !candidate.is_reported) {
+			let unfinalized_blocks = progress.block_number.saturating_sub(candidate.included_at);
+			if unfinalized_blocks > expected_lag + self.no_show_slots {
+				candidate.is_reported = true;
+				suspected.push(SuspectedNoShow {
+					block_number: progress.block_number,
+					candidate_hash: candidate.candidate_hash,
+					included_at: candidate.included_at,
+					unfinalized_blocks,
+					expected_lag,
+					no_show_slots: self.no_show_slots,
+				});
+			}
+		}
+		suspected
+	}
+
+	/// The median finality lag of the recent blocks
+	fn expected_lag(&self) -> u32 {
+		let mut lags: Vec<u32> = self.recent_lags.iter().copied().collect();
+		lags.sort_unstable();
+		lags.get(lags.len() / 2).copied().unwrap_or_default()
+	}
+}
+
+impl Display for SuspectedNoShow {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(
+			f,
+			"[#{}] {}: candidate {:?} included at #{} is not finalized after {} blocks, expected lag: {}, no-show slots: {}",
+			self.block_number,
+			"APPROVAL NO-SHOW SUSPECTED".to_string().bold().yellow(),
+			self.candidate_hash,
+			self.included_at,
+			self.unfinalized_blocks.to_string().bright_red(),
+			self.expected_lag,
+			self.no_show_slots
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::create_progress_with_finality_lag;
+
+	#[test]
+	fn test_detects_unfinalized_candidates() {
+		let mut detector = NoShowDetector::new(2);
+		let candidate_hash = H256::random();
+
+		// The usual finality lag of 2 blocks
+		for block_number in 1..10 {
+			assert!(detector
+				.on_progress(&create_progress_with_finality_lag(block_number, Some(2), vec![]))
+				.is_empty());
+		}
+		assert!(detector
+			.on_progress(&create_progress_with_finality_lag(
+				10,
+				Some(2),
+				vec![ParachainConsensusEvent::Included(candidate_hash, 5, 5)]
+			))
+			.is_empty());
+		// Not finalized within the usual lag and the no-show slots
+		for (block_number, lag) in [(11, 3), (12, 4), (13, 5), (14, 6)] {
+			assert!(detector
+				.on_progress(&create_progress_with_finality_lag(block_number, Some(lag), vec![]))
+				.is_empty());
+		}
+		let suspected = detector.on_progress(&create_progress_with_finality_lag(15, Some(7), vec![]));
+		assert_eq!(
+			suspected,
+			vec![SuspectedNoShow {
+				block_number: 15,
+				candidate_hash,
+				included_at: 10,
+				unfinalized_blocks: 5,
+				expected_lag: 2,
+				no_show_slots: 2
+			}]
+		);
+		// Reported once
+		assert!(detector
+			.on_progress(&create_progress_with_finality_lag(16, Some(8), vec![]))
+			.is_empty());
+	}
+
+	#[test]
+	fn test_forgets_finalized_candidates() {
+		let mut detector = NoShowDetector::new(2);
+
+		detector.on_progress(&create_progress_with_finality_lag(
+			10,
+			Some(1),
+			vec![ParachainConsensusEvent::Included(H256::random(), 5, 5)],
+		));
+		assert!(detector
+			.on_progress(&create_progress_with_finality_lag(11, Some(1), vec![]))
+			.is_empty());
+		assert!(detector
+			.on_progress(&create_progress_with_finality_lag(20, None, vec![]))
+			.is_empty());
+		assert!(detector.unfinalized.is_empty());
+	}
+}
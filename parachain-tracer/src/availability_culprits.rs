@@ -0,0 +1,336 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module attributes missing availability bits to the relay chain validators.
+//!
+//! A validator misses a block if it hasn't signed a bitfield in it, or hasn't set the bit of a core that the
+//! supermajority of validators has set. Validators missing a share of blocks in a window are reported as culprits.
+
+use crate::utils::extract_validator_address;
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::{
+	metadata::polkadot_primitives::AvailabilityBitfield,
+	types::{AccountId32, BlockNumber},
+};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+};
+
+/// A validator missing availability bits
+#[derive(Clone, Debug, PartialEq)]
+pub struct AvailabilityCulprit {
+	/// Index of the validator in the session
+	pub validator_index: u32,
+	/// The validator's account, resolved from the session keys
+	pub address: Option<String>,
+	/// Number of blocks where the validator missed bits
+	pub missed_blocks: u32,
+	/// Number of bits of available cores the validator hasn't set
+	pub missed_bits: u32,
+}
+
+/// Culprits of a window of relay chain blocks
+#[derive(Clone, Debug, PartialEq)]
+pub struct AvailabilityCulpritsReport {
+	/// First relay chain block of the window
+	pub from: BlockNumber,
+	/// Last relay chain block of the window
+	pub to: BlockNumber,
+	/// Number of blocks processed in the window
+	pub blocks_count: u32,
+	/// Session of the window
+	pub session_index: Option<u32>,
+	/// Percentage of missed blocks after that a validator is a culprit
+	pub threshold: f64,
+	/// The top offenders, most missed blocks first
+	pub culprits: Vec<AvailabilityCulprit>,
+}
+
+impl AvailabilityCulpritsReport {
+	/// Resolves the culprits' accounts
+	pub fn set_addresses(&mut self, session_keys: Option<&Vec<AccountId32>>) {
+		for culprit in self.culprits.iter_mut() {
+			culprit.address = Some(extract_validator_address(session_keys, culprit.validator_index).1);
+		}
+	}
+}
+
+#[derive(Default)]
+struct MissedAvailability {
+	blocks: u32,
+	bits: u32,
+}
+
+/// Accumulates missing availability bits per validator in windows of relay chain blocks
+pub struct AvailabilityCulpritsTracker {
+	/// Number of relay chain blocks in a window
+	window: u32,
+	/// Number of top offenders to report
+	top: usize,
+	/// Percentage of missed blocks after that a validator is a culprit
+	threshold: f64,
+	/// Current session
+	session_index: Option<u32>,
+	/// First block of the current window
+	from: Option<BlockNumber>,
+	/// Last block of the current window
+	to: BlockNumber,
+	/// Number of blocks processed in the current window
+	blocks_count: u32,
+	/// Missed availability per validator in the current window
+	missed: HashMap<u32, MissedAvailability>,
+	/// Number of reports with culprits
+	reports_with_culprits: u32,
+	/// Number of reports
+	reports_count: u32,
+}
+
+impl AvailabilityCulpritsTracker {
+	/// Returns a new tracker
+	///
+	/// # Arguments
+	///
+	/// * `window` - The number of blocks to attribute missing bits over
+	/// * `top` - The number of top offenders to report
+	/// * `threshold` - Percentage of missed blocks after that a validator is a culprit
+	pub fn new(window: u32, top: usize, threshold: f64) -> Self {
+		Self {
+			window: window.max(1),
+			top,
+			threshold,
+			session_index: None,
+			from: None,
+			to: 0,
+			blocks_count: 0,
+			missed: Default::default(),
+			reports_with_culprits: 0,
+			reports_count: 0,
+		}
+	}
+
+	/// Processes bitfields of a relay chain block signed by the validators with given indices,
+	/// returns a report when the window is complete
+	pub fn on_block(
+		&mut self,
+		block_number: BlockNumber,
+		bitfields: &[(u32, AvailabilityBitfield)],
+		validators_count: u32,
+	) -> Option<AvailabilityCulpritsReport> {
+		if validators_count == 0 {
+			return None
+		}
+
+		let cores_count = bitfields
+			.iter()
+			.map(|(_, bitfield)| bitfield.0.as_bits().len())
+			.max()
+			.unwrap_or_default();
+		let available_cores: Vec<usize> = (0..cores_count)
+			.filter(|core| {
+				let bits = bitfields
+					.iter()
+					.filter(|(_, bitfield)| bitfield.0.as_bits().get(*core).unwrap_or_default())
+					.count() as u32;
+				bits * 3 > validators_count * 2
+			})
+			.collect();
+
+		let mut signed: HashMap<u32, &AvailabilityBitfield> = HashMap::new();
+		for (validator_index, bitfield) in bitfields {
+			signed.insert(*validator_index, bitfield);
+		}
+		for validator_index in 0..validators_count {
+			let missed_bits = match signed.get(&validator_index) {
+				Some(bitfield) => available_cores
+					.iter()
+					.filter(|core| !bitfield.0.as_bits().get(**core).unwrap_or_default())
+					.count() as u32,
+				// Counted as a missed block even if no core became available
+				None => (available_cores.len() as u32).max(1),
+			};
+			if missed_bits > 0 {
+				let missed = self.missed.entry(validator_index).or_default();
+				missed.blocks += 1;
+				missed.bits += missed_bits;
+			}
+		}
+
+		self.from.get_or_insert(block_number);
+		self.to = block_number;
+		self.blocks_count += 1;
+		if self.blocks_count >= self.window {
+			self.report()
+		} else {
+			None
+		}
+	}
+
+	/// Validator indices are valid within a session, returns a report of the incomplete window of the previous one
+	pub fn on_new_session(&mut self, session_index: u32) -> Option<AvailabilityCulpritsReport> {
+		let report = self.report();
+		self.session_index = Some(session_index);
+		report
+	}
+
+	/// Returns a report of the current window and starts a new one
+	fn report(&mut self) -> Option<AvailabilityCulpritsReport> {
+		let from = self.from.take()?;
+		let blocks_count = std::mem::take(&mut self.blocks_count);
+		let mut culprits: Vec<AvailabilityCulprit> = self
+			.missed
+			.drain()
+			.filter(|(_, missed)| missed.blocks as f64 * 100.0 / blocks_count as f64 >= self.threshold)
+			.map(|(validator_index, missed)| AvailabilityCulprit {
+				validator_index,
+				address: None,
+				missed_blocks: missed.blocks,
+				missed_bits: missed.bits,
+			})
+			.collect();
+		culprits.sort_by(|a, b| {
+			b.missed_blocks
+				.cmp(&a.missed_blocks)
+				.then(b.missed_bits.cmp(&a.missed_bits))
+				.then(a.validator_index.cmp(&b.validator_index))
+		});
+		culprits.truncate(self.top);
+
+		self.reports_count += 1;
+		if !culprits.is_empty() {
+			self.reports_with_culprits += 1;
+		}
+
+		Some(AvailabilityCulpritsReport {
+			from,
+			to: self.to,
+			blocks_count,
+			session_index: self.session_index,
+			threshold: self.threshold,
+			culprits,
+		})
+	}
+}
+
+impl Display for AvailabilityCulpritsReport {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "[#{}..#{}] {}", self.from, self.to, "AVAILABILITY CULPRITS".to_string().bold().yellow())?;
+		if let Some(session_index) = self.session_index {
+			write!(f, " in session {}", session_index)?;
+		}
+		writeln!(f)?;
+		if self.culprits.is_empty() {
+			return writeln!(
+				f,
+				"\tNo validators missed availability bits in {:.0}% of {} blocks",
+				self.threshold, self.blocks_count
+			)
+		}
+		for culprit in &self.culprits {
+			writeln!(
+				f,
+				"\t{} {}: missed bits in {}/{} blocks, {} bits in total",
+				format!("#{}", culprit.validator_index).bold(),
+				culprit.address.as_deref().unwrap_or("unknown account").bright_purple(),
+				culprit.missed_blocks.to_string().bright_red(),
+				self.blocks_count,
+				culprit.missed_bits
+			)?;
+		}
+		Ok(())
+	}
+}
+
+impl Display for AvailabilityCulpritsTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- Availability culprits ---".to_string().bold().blue())?;
+		writeln!(
+			f,
+			"Windows of {} blocks reported: {}, with culprits: {}",
+			self.window,
+			self.reports_count,
+			self.reports_with_culprits.to_string().bright_red()
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use subxt::utils::bits::DecodedBits;
+
+	fn bitfield(bits: &[bool]) -> AvailabilityBitfield {
+		AvailabilityBitfield(DecodedBits::from_iter(bits.iter().copied()))
+	}
+
+	#[test]
+	fn test_attributes_missing_bits() {
+		let mut tracker = AvailabilityCulpritsTracker::new(2, 10, 50.0);
+		// Core 0 is available, validator 2 hasn't set its bit and validator 3 hasn't signed a bitfield
+		let mut bitfields: Vec<(u32, AvailabilityBitfield)> = [0, 1, 4, 5, 6]
+			.into_iter()
+			.map(|index| (index, bitfield(&[true, false])))
+			.collect();
+		bitfields.push((2, bitfield(&[false, true])));
+		assert!(tracker.on_block(10, &bitfields, 7).is_none());
+
+		let bitfields: Vec<(u32, AvailabilityBitfield)> =
+			[0, 1, 2, 4, 5, 6].into_iter().map(|index| (index, bitfield(&[true]))).collect();
+		let report = tracker.on_block(11, &bitfields, 7).unwrap();
+		assert_eq!((report.from, report.to, report.blocks_count), (10, 11, 2));
+		assert_eq!(
+			report.culprits,
+			vec![
+				AvailabilityCulprit { validator_index: 3, address: None, missed_blocks: 2, missed_bits: 2 },
+				AvailabilityCulprit { validator_index: 2, address: None, missed_blocks: 1, missed_bits: 1 },
+			]
+		);
+
+		// A new window is started
+		assert!(tracker.on_block(12, &bitfields, 7).is_none());
+	}
+
+	#[test]
+	fn test_reports_top_offenders_above_threshold() {
+		let mut tracker = AvailabilityCulpritsTracker::new(4, 1, 75.0);
+		let all = vec![(0, bitfield(&[true])), (1, bitfield(&[true])), (2, bitfield(&[true])), (3, bitfield(&[true]))];
+		let without_last = &all[..2];
+
+		tracker.on_block(1, without_last, 4);
+		tracker.on_block(2, without_last, 4);
+		tracker.on_block(3, without_last, 4);
+		let report = tracker.on_block(4, &all, 4).unwrap();
+		// Validators 2 and 3 missed 75% of blocks, only the top one is reported
+		assert_eq!(report.culprits.len(), 1);
+		assert_eq!(report.culprits[0].validator_index, 2);
+	}
+
+	#[test]
+	fn test_reports_on_new_session() {
+		let mut tracker = AvailabilityCulpritsTracker::new(100, 10, 50.0);
+
+		assert!(tracker.on_new_session(5).is_none());
+		tracker.on_block(1, &[(0, bitfield(&[true]))], 2);
+		let mut report = tracker.on_new_session(6).unwrap();
+		assert_eq!(report.session_index, Some(5));
+		assert_eq!(report.blocks_count, 1);
+
+		report.set_addresses(Some(&vec![AccountId32([0; 32]), AccountId32([1; 32])]));
+		assert_eq!(report.culprits[0].validator_index, 1);
+		assert_eq!(report.culprits[0].address, Some(AccountId32([1; 32]).to_string()));
+	}
+}
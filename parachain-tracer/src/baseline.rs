@@ -0,0 +1,195 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module learns per-parachain baselines for the availability and bitfield checks.
+//!
+//! The fixed thresholds report every relay chain block a candidate is not yet available in and every block with
+//! bitfields of less than the supermajority of validators, which is noisy for parachains that are slow by design.
+//! Instead, exponentially weighted moving averages and variances of the availability time and of the share of signed
+//! bitfields are learned during a warm-up window, after that only statistically significant deviations are reported.
+
+/// Minimal standard deviation of the availability time in relay chain blocks, so a parachain whose candidates
+/// are always available in the next block is not reported for a single extra block
+const MIN_AVAILABILITY_STD_DEV: f64 = 0.5;
+/// Minimal standard deviation of the share of signed bitfields
+const MIN_BITFIELDS_STD_DEV: f64 = 0.02;
+
+/// Options of the baseline learning
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BaselineOptions {
+	/// Number of samples to learn from before the baseline replaces the fixed threshold
+	pub warmup: u32,
+	/// Weight of a new sample in the moving average, between 0 and 1
+	pub alpha: f64,
+	/// Number of standard deviations a sample must differ from the average to be reported
+	pub sigma: f64,
+}
+
+impl Default for BaselineOptions {
+	fn default() -> Self {
+		Self { warmup: 100, alpha: 0.05, sigma: 3.0 }
+	}
+}
+
+/// Exponentially weighted moving average and variance of a metric
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Baseline {
+	/// Weight of a new sample
+	alpha: f64,
+	/// Minimal standard deviation
+	min_std_dev: f64,
+	/// Moving average
+	mean: f64,
+	/// Moving variance
+	variance: f64,
+	/// Number of samples learned from
+	samples: u32,
+}
+
+impl Baseline {
+	pub fn new(alpha: f64, min_std_dev: f64) -> Self {
+		Self { alpha: alpha.clamp(f64::EPSILON, 1.0), min_std_dev, ..Default::default() }
+	}
+
+	/// Learns from a new sample
+	pub fn update(&mut self, value: f64) {
+		if self.samples == 0 {
+			self.mean = value;
+		} else {
+			let diff = value - self.mean;
+			let increment = self.alpha * diff;
+			self.mean += increment;
+			self.variance = (1.0 - self.alpha) * (self.variance + diff * increment);
+		}
+		self.samples = self.samples.saturating_add(1);
+	}
+
+	pub fn mean(&self) -> f64 {
+		self.mean
+	}
+
+	pub fn std_dev(&self) -> f64 {
+		self.variance.sqrt().max(self.min_std_dev)
+	}
+
+	pub fn samples(&self) -> u32 {
+		self.samples
+	}
+
+	/// Number of standard deviations the value differs from the average by, negative if below it
+	pub fn z_score(&self, value: f64) -> f64 {
+		(value - self.mean()) / self.std_dev()
+	}
+}
+
+/// Baselines of a parachain
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParaBaselines {
+	options: BaselineOptions,
+	/// Relay chain blocks from backing to inclusion of a candidate
+	availability: Baseline,
+	/// Share of validators signed bitfields in a relay chain block
+	bitfields: Baseline,
+}
+
+impl ParaBaselines {
+	pub fn new(options: BaselineOptions) -> Self {
+		Self {
+			options,
+			availability: Baseline::new(options.alpha, MIN_AVAILABILITY_STD_DEV),
+			bitfields: Baseline::new(options.alpha, MIN_BITFIELDS_STD_DEV),
+		}
+	}
+
+	/// Learns the availability time of an included candidate
+	pub fn on_available(&mut self, blocks: u32) {
+		self.availability.update(blocks as f64)
+	}
+
+	/// Checks if a candidate pending availability for a number of blocks since backing is significantly slower than
+	/// usual, `None` during the warm-up
+	pub fn is_slow_availability(&self, pending_blocks: u32) -> Option<bool> {
+		if self.availability.samples() < self.options.warmup {
+			return None
+		}
+
+		Some(self.availability.z_score(pending_blocks as f64) > self.options.sigma)
+	}
+
+	/// Checks if the share of signed bitfields in a relay chain block is significantly lower than usual, `None` during
+	/// the warm-up
+	pub fn is_low_bitfields(&self, bitfield_count: u32, max_bitfield_count: u32) -> Option<bool> {
+		if max_bitfield_count == 0 || self.bitfields.samples() < self.options.warmup {
+			return None
+		}
+
+		Some(self.bitfields.z_score(bitfield_count as f64 / max_bitfield_count as f64) < -self.options.sigma)
+	}
+
+	/// Learns the share of signed bitfields in a relay chain block
+	pub fn on_bitfields(&mut self, bitfield_count: u32, max_bitfield_count: u32) {
+		if max_bitfield_count > 0 {
+			self.bitfields.update(bitfield_count as f64 / max_bitfield_count as f64)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_learns_average_and_variance() {
+		let mut baseline = Baseline::new(0.5, 0.0);
+		baseline.update(2.0);
+		assert_eq!((baseline.mean(), baseline.std_dev()), (2.0, 0.0));
+
+		baseline.update(4.0);
+		assert_eq!(baseline.mean(), 3.0);
+		assert_eq!(baseline.std_dev(), 1.0);
+		assert_eq!(baseline.z_score(5.0), 2.0);
+		assert_eq!(baseline.samples(), 2);
+	}
+
+	#[test]
+	fn test_reports_slow_availability_after_warmup() {
+		let mut baselines = ParaBaselines::new(BaselineOptions { warmup: 10, alpha: 0.1, sigma: 3.0 });
+		// A parachain which is usually available in 3 blocks
+		for _ in 0..9 {
+			baselines.on_available(3);
+		}
+		assert_eq!(baselines.is_slow_availability(10), None);
+
+		baselines.on_available(3);
+		assert_eq!(baselines.is_slow_availability(3), Some(false));
+		assert_eq!(baselines.is_slow_availability(4), Some(false));
+		assert_eq!(baselines.is_slow_availability(5), Some(true));
+	}
+
+	#[test]
+	fn test_reports_low_bitfields_after_warmup() {
+		let mut baselines = ParaBaselines::new(BaselineOptions { warmup: 3, alpha: 0.1, sigma: 3.0 });
+		baselines.on_bitfields(10, 0);
+		assert_eq!(baselines.is_low_bitfields(10, 0), None);
+		// Bitfields of a smaller share of validators are usual on this network
+		for _ in 0..3 {
+			assert_eq!(baselines.is_low_bitfields(60, 100), None);
+			baselines.on_bitfields(60, 100);
+		}
+		assert_eq!(baselines.is_low_bitfields(58, 100), Some(false));
+		assert_eq!(baselines.is_low_bitfields(40, 100), Some(true));
+	}
+}
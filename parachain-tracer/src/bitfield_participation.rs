@@ -0,0 +1,206 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module tracks bitfield participation of the relay chain validators as a whole.
+
+use crate::explorer::Explorer;
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::types::BlockNumber;
+use std::{
+	collections::VecDeque,
+	fmt::{self, Display, Formatter},
+};
+
+/// Bitfield participation observed in a relay chain block
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitfieldParticipationUpdate {
+	/// Relay chain block number
+	pub block_number: BlockNumber,
+	/// Percentage of active validators that contributed bitfields
+	pub participation: f64,
+	/// Rolling average of participation, including the current block
+	pub rolling_average: f64,
+	/// Participation dropped below the rolling average more than allowed
+	pub is_drop: bool,
+	pub explorer: Explorer,
+}
+
+/// Tracks the rolling average of bitfield participation and detects drops
+pub struct BitfieldParticipationTracker {
+	/// Number of relay chain blocks used to calculate the rolling average
+	window: usize,
+	/// Drop below the rolling average (in percentage points) considered as an alert
+	drop_threshold: f64,
+	/// Participation in the last `window` blocks
+	samples: VecDeque<f64>,
+	/// Number of blocks processed
+	blocks_count: usize,
+	/// Average participation (calculated using `CMA`: cumulative moving average)
+	avg: f64,
+	/// Minimal participation
+	min: f64,
+	/// Number of participation drops
+	drops_count: u32,
+	explorer: Explorer,
+}
+
+impl BitfieldParticipationTracker {
+	/// Returns a new tracker
+	///
+	/// # Arguments
+	///
+	/// * `window` - The number of blocks to calculate the rolling average
+	/// * `drop_threshold` - Percentage points below the rolling average to alert on
+	pub fn new(window: usize, drop_threshold: f64) -> Self {
+		Self {
+			window: window.max(1),
+			drop_threshold,
+			samples: VecDeque::with_capacity(window.max(1)),
+			blocks_count: 0,
+			avg: 0.0,
+			min: f64::MAX,
+			drops_count: 0,
+			explorer: Default::default(),
+		}
+	}
+
+	/// Links the drops to the block explorer
+	pub fn with_explorer(mut self, explorer: Explorer) -> Self {
+		self.explorer = explorer;
+		self
+	}
+
+	/// Processes bitfields of a relay chain block, returns `None` if there are no active validators
+	pub fn on_block(
+		&mut self,
+		block_number: BlockNumber,
+		bitfields_count: u32,
+		validators_count: u32,
+	) -> Option<BitfieldParticipationUpdate> {
+		if validators_count == 0 {
+			return None
+		}
+
+		let participation = (bitfields_count as f64 * 100.0 / validators_count as f64).min(100.0);
+		let is_drop = self
+			.rolling_average()
+			.map_or(false, |average| participation < average - self.drop_threshold);
+
+		if self.samples.len() >= self.window {
+			self.samples.pop_front();
+		}
+		self.samples.push_back(participation);
+
+		self.blocks_count += 1;
+		self.avg += (participation - self.avg) / self.blocks_count as f64;
+		if self.min > participation {
+			self.min = participation;
+		}
+		if is_drop {
+			self.drops_count += 1;
+		}
+
+		Some(BitfieldParticipationUpdate {
+			block_number,
+			participation,
+			rolling_average: self.rolling_average().expect("just added a sample; qed"),
+			is_drop,
+			explorer: self.explorer.clone(),
+		})
+	}
+
+	/// Returns the rolling average of the last blocks
+	pub fn rolling_average(&self) -> Option<f64> {
+		if self.samples.is_empty() {
+			None
+		} else {
+			Some(self.samples.iter().sum::<f64>() / self.samples.len() as f64)
+		}
+	}
+}
+
+impl Display for BitfieldParticipationUpdate {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(
+			f,
+			"[#{}] {} {}% of validators contributed bitfields, rolling average {}%",
+			self.block_number,
+			"BITFIELD PARTICIPATION DROP".to_string().bold().red(),
+			format!("{:.1}", self.participation).bright_red(),
+			format!("{:.1}", self.rolling_average).bold()
+		)?;
+		self.explorer.write_block_link(f, self.block_number)
+	}
+}
+
+impl Display for BitfieldParticipationTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- Relay chain bitfield participation ---".to_string().bold().blue())?;
+		if self.blocks_count == 0 {
+			return writeln!(f, "No blocks processed")
+		}
+		writeln!(
+			f,
+			"Average participation: {}% ({} blocks processed), minimal: {}%, drops: {}",
+			format!("{:.2}", self.avg).bold(),
+			self.blocks_count,
+			format!("{:.2}", self.min).bright_cyan(),
+			self.drops_count.to_string().bright_red()
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_skips_blocks_without_validators() {
+		let mut tracker = BitfieldParticipationTracker::new(3, 10.0);
+
+		assert!(tracker.on_block(1, 10, 0).is_none());
+		assert!(tracker.rolling_average().is_none());
+	}
+
+	#[test]
+	fn test_calculates_rolling_average() {
+		let mut tracker = BitfieldParticipationTracker::new(2, 10.0);
+
+		let update = tracker.on_block(1, 100, 200).unwrap();
+		assert_eq!(update.participation, 50.0);
+		assert_eq!(update.rolling_average, 50.0);
+
+		let update = tracker.on_block(2, 200, 200).unwrap();
+		assert_eq!(update.participation, 100.0);
+		assert_eq!(update.rolling_average, 75.0);
+
+		// The first sample is out of the window
+		let update = tracker.on_block(3, 180, 200).unwrap();
+		assert_eq!(update.rolling_average, 95.0);
+	}
+
+	#[test]
+	fn test_detects_drops() {
+		let mut tracker = BitfieldParticipationTracker::new(4, 10.0);
+
+		assert!(!tracker.on_block(1, 200, 200).unwrap().is_drop);
+		assert!(!tracker.on_block(2, 190, 200).unwrap().is_drop);
+		assert!(tracker.on_block(3, 150, 200).unwrap().is_drop);
+		assert_eq!(tracker.drops_count, 1);
+		assert_eq!(tracker.min, 75.0);
+	}
+}
@@ -0,0 +1,316 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Lifecycle timelines of the candidates of a parachain, written as JSON lines for own latency analysis.
+//!
+//! A timeline holds the relay chain block and time at which the candidate was seen backed, became available, was
+//! included, timed out in availability, had a dispute concluded and had its inclusion block finalized. On chain a
+//! candidate is included in the block where its availability threshold is reached, so both stages have the
+//! same block, the availability stage also carries the availability bits. A timeline is written once it is
+//! complete, i.e. its inclusion is finalized or it timed out, the incomplete ones are written when the tracer stops.
+//! The lines are described by the `candidate-timeline` schema.
+
+use crate::{
+	progress_record::DisputeRecord,
+	types::{ParachainConsensusEvent, ParachainProgressUpdate},
+	utils::candidate_hash,
+};
+use log::error;
+use polkadot_introspector_essentials::{
+	metadata::polkadot_primitives::BackedCandidate,
+	types::{BlockNumber, Timestamp, H256},
+};
+use serde::Serialize;
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::Write,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+
+/// Incomplete timelines kept per parachain, the oldest ones are written as they are when finality stalls
+const MAX_PENDING_TIMELINES: usize = 1024;
+
+/// A relay chain block at which a candidate reached a stage
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct TimelineStage {
+	pub block_number: BlockNumber,
+	/// Relay chain block timestamp, in milliseconds
+	pub timestamp: Timestamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct AvailabilityStage {
+	#[serde(flatten)]
+	pub stage: TimelineStage,
+	pub bits_available: u32,
+	pub max_bits: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct DisputeStage {
+	#[serde(flatten)]
+	pub stage: TimelineStage,
+	/// `valid`, `invalid` or `timed_out`
+	pub outcome: &'static str,
+}
+
+/// Stages of a candidate of a parachain, the stages not reached are left out
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct CandidateTimeline {
+	pub para_id: u32,
+	pub candidate_hash: H256,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub backed: Option<TimelineStage>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub available: Option<AvailabilityStage>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub included: Option<TimelineStage>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub timed_out: Option<TimelineStage>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub disputed: Option<DisputeStage>,
+	/// The first block by which the inclusion block was finalized
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub finalized: Option<TimelineStage>,
+}
+
+impl CandidateTimeline {
+	fn new(para_id: u32, candidate_hash: H256) -> Self {
+		Self {
+			para_id,
+			candidate_hash,
+			backed: None,
+			available: None,
+			included: None,
+			timed_out: None,
+			disputed: None,
+			finalized: None,
+		}
+	}
+
+	/// The first stage seen, orders the pending timelines
+	fn first_block_number(&self) -> BlockNumber {
+		[self.backed, self.included, self.timed_out]
+			.into_iter()
+			.flatten()
+			.map(|v| v.block_number)
+			.min()
+			.unwrap_or_default()
+	}
+
+	fn is_complete(&self) -> bool {
+		self.finalized.is_some() || self.timed_out.is_some()
+	}
+}
+
+/// Follows the candidates of a parachain through their stages
+pub(crate) struct CandidateTimelines {
+	para_id: u32,
+	/// Timelines not complete yet by the candidate hash
+	pending: HashMap<H256, CandidateTimeline>,
+}
+
+impl CandidateTimelines {
+	pub fn new(para_id: u32) -> Self {
+		Self { para_id, pending: Default::default() }
+	}
+
+	/// Processes the progress of the parachain at a relay chain block with the candidates backed in it,
+	/// returns the timelines completed in the block
+	pub fn on_progress(
+		&mut self,
+		progress: &ParachainProgressUpdate,
+		backed_candidates: &[BackedCandidate<H256>],
+	) -> Vec<CandidateTimeline> {
+		let stage = TimelineStage { block_number: progress.block_number, timestamp: progress.timestamp };
+		let mut completed = vec![];
+
+		for candidate in backed_candidates {
+			self.timeline(candidate_hash(candidate)).backed.get_or_insert(stage);
+		}
+		for event in &progress.events {
+			match event {
+				ParachainConsensusEvent::Included(candidate_hash, bits_available, max_bits) =>
+					self.on_included(*candidate_hash, stage, *bits_available, *max_bits),
+				ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash) => {
+					self.timeline(*candidate_hash).timed_out.get_or_insert(stage);
+				},
+				ParachainConsensusEvent::Disputed(dispute) => {
+					let disputed = DisputeStage { stage, outcome: DisputeRecord::from(dispute).outcome };
+					match self.pending.get_mut(&dispute.candidate) {
+						Some(timeline) => timeline.disputed = Some(disputed),
+						// Candidates seen before the start or already completed only get the dispute
+						None => completed.push(CandidateTimeline {
+							disputed: Some(disputed),
+							..CandidateTimeline::new(self.para_id, dispute.candidate)
+						}),
+					}
+				},
+				_ => {},
+			}
+		}
+		// With elastic scaling the candidates on the other cores are only reported per core
+		for core in &progress.cores {
+			match core.state {
+				"included" =>
+					self.on_included(core.candidate_hash, stage, core.available_count, core.max_availability_bits),
+				"timed_out" => {
+					self.timeline(core.candidate_hash).timed_out.get_or_insert(stage);
+				},
+				_ => {},
+			}
+		}
+
+		if let Some(finalized_number) = progress.finality_lag.and_then(|lag| progress.block_number.checked_sub(lag)) {
+			for timeline in self.pending.values_mut() {
+				if timeline.included.is_some_and(|v| v.block_number <= finalized_number) {
+					timeline.finalized.get_or_insert(stage);
+				}
+			}
+		}
+
+		let hashes: Vec<H256> = self
+			.pending
+			.iter()
+			.filter(|(_, timeline)| timeline.is_complete())
+			.map(|(hash, _)| *hash)
+			.collect();
+		completed.extend(hashes.iter().filter_map(|hash| self.pending.remove(hash)));
+		while self.pending.len() > MAX_PENDING_TIMELINES {
+			let oldest = self
+				.pending
+				.iter()
+				.min_by_key(|(_, timeline)| timeline.first_block_number())
+				.map(|(hash, _)| *hash)
+				.expect("not empty; qed");
+			completed.extend(self.pending.remove(&oldest));
+		}
+		completed.sort_by_key(CandidateTimeline::first_block_number);
+
+		completed
+	}
+
+	/// Returns the incomplete timelines, when the tracer stops
+	pub fn finish(self) -> Vec<CandidateTimeline> {
+		let mut timelines: Vec<_> = self.pending.into_values().collect();
+		timelines.sort_by_key(CandidateTimeline::first_block_number);
+		timelines
+	}
+
+	fn timeline(&mut self, candidate_hash: H256) -> &mut CandidateTimeline {
+		let para_id = self.para_id;
+		self.pending
+			.entry(candidate_hash)
+			.or_insert_with(|| CandidateTimeline::new(para_id, candidate_hash))
+	}
+
+	fn on_included(&mut self, candidate_hash: H256, stage: TimelineStage, bits_available: u32, max_bits: u32) {
+		let timeline = self.timeline(candidate_hash);
+		if timeline.included.is_none() {
+			timeline.available = Some(AvailabilityStage { stage, bits_available, max_bits });
+			timeline.included = Some(stage);
+		}
+	}
+}
+
+/// Writes the timelines of all traced parachains to a file as JSON lines
+#[derive(Clone)]
+pub(crate) struct CandidateTimelineFile {
+	path: PathBuf,
+	file: Arc<Mutex<File>>,
+}
+
+impl CandidateTimelineFile {
+	/// Creates the file, truncating an existing one
+	pub fn create(path: PathBuf) -> color_eyre::Result<Self> {
+		let file = File::create(&path)?;
+		Ok(Self { path, file: Arc::new(Mutex::new(file)) })
+	}
+
+	/// Appends the timelines to the file
+	pub fn write(&self, timelines: &[CandidateTimeline]) {
+		if timelines.is_empty() {
+			return
+		}
+		let mut content = String::new();
+		for timeline in timelines {
+			content.push_str(&serde_json::to_string(timeline).expect("timelines are serializable; qed"));
+			content.push('\n');
+		}
+		let mut file = self.file.lock().expect("timeline file lock is poisoned");
+		if let Err(e) = file.write_all(content.as_bytes()) {
+			error!("Cannot write the candidate timelines to {}: {:?}", self.path.display(), e);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		schema,
+		test_utils::{create_backed_candidate, create_progress_with_finality_lag},
+	};
+
+	fn stage(block_number: BlockNumber) -> TimelineStage {
+		TimelineStage { block_number, timestamp: block_number as Timestamp * 6000 }
+	}
+
+	#[test]
+	fn test_completes_timeline_on_finality() {
+		let mut timelines = CandidateTimelines::new(100);
+		let candidate = create_backed_candidate(100);
+		let hash = candidate_hash(&candidate);
+		let timed_out = H256::random();
+
+		assert!(timelines
+			.on_progress(&create_progress_with_finality_lag(10, Some(2), vec![]), &[candidate])
+			.is_empty());
+		let included = vec![ParachainConsensusEvent::Included(hash, 200, 300)];
+		assert!(timelines
+			.on_progress(&create_progress_with_finality_lag(12, Some(2), included), &[])
+			.is_empty());
+		let completed = timelines.on_progress(
+			&create_progress_with_finality_lag(
+				13,
+				Some(2),
+				vec![ParachainConsensusEvent::AvailabilityTimedOut(timed_out)],
+			),
+			&[],
+		);
+		assert_eq!(completed.len(), 1);
+		assert_eq!(completed[0].candidate_hash, timed_out);
+		assert_eq!(completed[0].timed_out, Some(stage(13)));
+
+		let completed = timelines.on_progress(&create_progress_with_finality_lag(14, Some(2), vec![]), &[]);
+		assert_eq!(
+			completed,
+			vec![CandidateTimeline {
+				backed: Some(stage(10)),
+				available: Some(AvailabilityStage { stage: stage(12), bits_available: 200, max_bits: 300 }),
+				included: Some(stage(12)),
+				finalized: Some(stage(14)),
+				..CandidateTimeline::new(100, hash)
+			}]
+		);
+		assert!(timelines.finish().is_empty());
+
+		let value = serde_json::to_value(&completed[0]).unwrap();
+		assert_eq!(value["available"]["bits_available"], 200);
+		schema::tests::assert_conforms("candidate-timeline", &value);
+	}
+}
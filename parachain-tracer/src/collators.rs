@@ -0,0 +1,257 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Attributes candidates of a parachain to the collators that produced them.
+//!
+//! The collator is taken from the descriptor of a backed candidate and followed until the candidate is included or
+//! times out in availability, so broken collator instances stand out in the summary.
+
+use crate::{
+	types::{ParachainConsensusEvent, ParachainProgressUpdate},
+	utils::candidate_hash,
+};
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::{
+	metadata::polkadot_primitives::BackedCandidate,
+	types::{AccountId32, BlockNumber, Timestamp, H256},
+};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt::{self, Display, Formatter},
+};
+
+/// Candidates produced by a collator
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CollatorStats {
+	/// Number of backed candidates
+	pub backed: u32,
+	/// Number of candidates included on chain
+	pub included: u32,
+	/// Number of candidates timed out in availability
+	pub timed_out: u32,
+	/// Sum of relay chain blocks from backing to inclusion
+	inclusion_blocks: u32,
+	/// Sum of time from backing to inclusion, in milliseconds
+	inclusion_ms: u64,
+}
+
+impl CollatorStats {
+	/// Share of the finished candidates included on chain, in percent, candidates still waiting for inclusion are
+	/// not counted
+	pub fn success_rate(&self) -> f64 {
+		let finished = self.included + self.timed_out;
+		if finished == 0 {
+			return 0.0
+		}
+		self.included as f64 * 100.0 / finished as f64
+	}
+
+	/// Average number of relay chain blocks from backing to inclusion
+	pub fn avg_inclusion_blocks(&self) -> Option<f64> {
+		(self.included > 0).then(|| self.inclusion_blocks as f64 / self.included as f64)
+	}
+
+	/// Average time from backing to inclusion, in milliseconds
+	pub fn avg_inclusion_ms(&self) -> Option<f64> {
+		(self.included > 0).then(|| self.inclusion_ms as f64 / self.included as f64)
+	}
+}
+
+/// Number of relay chain blocks after which a candidate that is neither included nor timed out is dropped, e.g. a
+/// candidate backed on a fork
+const MAX_PENDING_BLOCKS: BlockNumber = 64;
+
+/// A candidate waiting for inclusion
+struct PendingCandidate {
+	collator: [u8; 32],
+	block_number: BlockNumber,
+	timestamp: Timestamp,
+}
+
+/// Aggregates candidates of a parachain per collator
+pub struct CollatorTracker {
+	/// Number of worst-performing collators to list in the summary
+	top: usize,
+	/// Backed candidates not yet included or timed out
+	pending: HashMap<H256, PendingCandidate>,
+	/// Candidates per collator public key
+	collators: BTreeMap<[u8; 32], CollatorStats>,
+}
+
+impl CollatorTracker {
+	/// Returns a new tracker listing `top` worst-performing collators in the summary
+	pub fn new(top: usize) -> Self {
+		Self { top, pending: Default::default(), collators: Default::default() }
+	}
+
+	/// Processes the progress of the parachain at a relay chain block with the candidates backed in it
+	pub fn on_progress(&mut self, progress: &ParachainProgressUpdate, backed_candidates: &[BackedCandidate<H256>]) {
+		for candidate in backed_candidates {
+			let collator = candidate.candidate.descriptor.collator.0 .0;
+			self.collators.entry(collator).or_default().backed += 1;
+			self.pending.insert(
+				candidate_hash(candidate),
+				PendingCandidate { collator, block_number: progress.block_number, timestamp: progress.timestamp },
+			);
+		}
+
+		for event in &progress.events {
+			match event {
+				ParachainConsensusEvent::Included(candidate_hash, _, _) => {
+					if let Some(pending) = self.pending.remove(candidate_hash) {
+						let stats = self.collators.entry(pending.collator).or_default();
+						stats.included += 1;
+						stats.inclusion_blocks += progress.block_number.saturating_sub(pending.block_number);
+						stats.inclusion_ms += progress.timestamp.saturating_sub(pending.timestamp);
+					}
+				},
+				ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash) => {
+					if let Some(pending) = self.pending.remove(candidate_hash) {
+						self.collators.entry(pending.collator).or_default().timed_out += 1;
+					}
+				},
+				_ => {},
+			}
+		}
+		self.pending
+			.retain(|_, pending| progress.block_number.saturating_sub(pending.block_number) <= MAX_PENDING_BLOCKS);
+	}
+
+	/// Collators sorted from the worst-performing one: lowest success rate first, then slowest inclusion
+	pub fn worst(&self) -> Vec<(AccountId32, &CollatorStats)> {
+		let mut collators: Vec<_> = self
+			.collators
+			.iter()
+			.map(|(collator, stats)| (AccountId32(*collator), stats))
+			.collect();
+		collators.sort_by(|(_, a), (_, b)| {
+			a.success_rate().total_cmp(&b.success_rate()).then(
+				b.avg_inclusion_blocks()
+					.unwrap_or(f64::MAX)
+					.total_cmp(&a.avg_inclusion_blocks().unwrap_or(f64::MAX)),
+			)
+		});
+		collators.truncate(self.top);
+		collators
+	}
+}
+
+impl Display for CollatorTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- Collators ---".to_string().bold().blue())?;
+		writeln!(f, "Collators seen: {}, worst-performing:", self.collators.len())?;
+		for (collator, stats) in self.worst() {
+			write!(
+				f,
+				"\t{}: {} backed, {} included ({}), {} timed out",
+				collator.to_string().bright_purple(),
+				stats.backed,
+				stats.included,
+				format!("{:.1}%", stats.success_rate()).bold(),
+				stats.timed_out.to_string().bright_red()
+			)?;
+			if let (Some(blocks), Some(ms)) = (stats.avg_inclusion_blocks(), stats.avg_inclusion_ms()) {
+				write!(f, ", included in {:.1} blocks / {:.1}s on average", blocks, ms / 1000.0)?;
+			}
+			writeln!(f)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::{create_backed_candidate, create_progress};
+	use polkadot_introspector_essentials::metadata::{
+		polkadot::runtime_types::sp_core::sr25519::Public, polkadot_primitives::collator_app,
+	};
+
+	fn backed_by(collator: u8) -> BackedCandidate<H256> {
+		let mut candidate = create_backed_candidate(100);
+		candidate.candidate.descriptor.collator = collator_app::Public(Public([collator; 32]));
+		candidate
+	}
+
+	#[test]
+	fn test_aggregates_candidates_per_collator() {
+		let mut tracker = CollatorTracker::new(5);
+		let good = backed_by(1);
+		let bad = backed_by(2);
+
+		tracker.on_progress(&create_progress(10, vec![]), &[good.clone()]);
+		tracker.on_progress(
+			&create_progress(11, vec![ParachainConsensusEvent::Included(candidate_hash(&good), 5, 6)]),
+			&[bad.clone()],
+		);
+		tracker.on_progress(
+			&create_progress(16, vec![ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash(&bad))]),
+			&[],
+		);
+
+		let worst = tracker.worst();
+		assert_eq!(worst.len(), 2);
+		assert_eq!(worst[0].0, AccountId32([2; 32]));
+		assert_eq!(worst[0].1, &CollatorStats { backed: 1, timed_out: 1, ..Default::default() });
+		assert_eq!(worst[1].0, AccountId32([1; 32]));
+		assert_eq!(worst[1].1.success_rate(), 100.0);
+		assert_eq!(worst[1].1.avg_inclusion_blocks(), Some(1.0));
+		assert_eq!(worst[1].1.avg_inclusion_ms(), Some(6000.0));
+	}
+
+	#[test]
+	fn test_lists_top_worst_collators() {
+		let mut tracker = CollatorTracker::new(1);
+		let slow = backed_by(1);
+		let fast = backed_by(2);
+
+		tracker.on_progress(&create_progress(10, vec![]), &[slow.clone(), fast.clone()]);
+		tracker.on_progress(
+			&create_progress(11, vec![ParachainConsensusEvent::Included(candidate_hash(&fast), 5, 6)]),
+			&[],
+		);
+		tracker.on_progress(
+			&create_progress(13, vec![ParachainConsensusEvent::Included(candidate_hash(&slow), 5, 6)]),
+			&[],
+		);
+
+		let worst = tracker.worst();
+		assert_eq!(worst.len(), 1);
+		assert_eq!(worst[0].0, AccountId32([1; 32]));
+	}
+
+	#[test]
+	fn test_counts_only_finished_candidates() {
+		let mut tracker = CollatorTracker::new(1);
+		let included = backed_by(1);
+		let mut in_flight = backed_by(1);
+		in_flight.candidate.commitments.processed_downward_messages = 1;
+
+		tracker.on_progress(&create_progress(10, vec![]), &[included.clone(), in_flight]);
+		tracker.on_progress(
+			&create_progress(11, vec![ParachainConsensusEvent::Included(candidate_hash(&included), 5, 6)]),
+			&[],
+		);
+		assert_eq!(tracker.worst()[0].1.backed, 2);
+		assert_eq!(tracker.worst()[0].1.success_rate(), 100.0);
+		assert_eq!(tracker.pending.len(), 1);
+
+		// Never included, e.g. backed on a fork
+		tracker.on_progress(&create_progress(10 + MAX_PENDING_BLOCKS + 1, vec![]), &[]);
+		assert!(tracker.pending.is_empty());
+	}
+}
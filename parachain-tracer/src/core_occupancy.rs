@@ -0,0 +1,238 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module follows utilisation of the availability cores across the relay chain.
+//!
+//! Every core of a relay chain block is either occupied by a candidate pending availability, scheduled for a
+//! parachain that hasn't got a candidate backed on it, or free. Candidates evicted from the cores are counted as
+//! timed out in the block they were evicted in.
+
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::{
+	observation::BlockObservation,
+	types::{BlockNumber, H256},
+};
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Display, Formatter},
+};
+
+/// Backed candidates neither included nor timed out within this number of relay chain blocks are not followed
+const MAX_PENDING_AGE: u32 = 100;
+
+/// Utilisation of the availability cores in a relay chain block
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoreOccupancy {
+	/// Relay chain block number
+	pub block_number: BlockNumber,
+	/// Number of availability cores
+	pub cores: u32,
+	/// Cores occupied by candidates pending availability
+	pub occupied: u32,
+	/// Free cores with a parachain scheduled on them
+	pub scheduled: u32,
+	/// Free cores without a parachain scheduled on them
+	pub free: u32,
+	/// Candidates evicted from the cores without inclusion in the block
+	pub timed_out: u32,
+}
+
+impl Display for CoreOccupancy {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"[#{}] {}: {} occupied, {} scheduled, {} free of {}",
+			self.block_number,
+			"CORES".to_string().bold(),
+			self.occupied.to_string().green(),
+			self.scheduled,
+			self.free,
+			self.cores
+		)?;
+		if self.timed_out > 0 {
+			write!(f, ", {} timed out", self.timed_out.to_string().bright_red())?;
+		}
+		Ok(())
+	}
+}
+
+/// Follows utilisation of the availability cores and the candidates pending on them
+#[derive(Default)]
+pub struct CoreOccupancyTracker {
+	/// Candidates backed and not yet included or timed out, with parachain ids and the blocks they were backed in
+	pending: BTreeMap<H256, (u32, BlockNumber)>,
+	/// The last processed block, forks are skipped
+	last_block: Option<BlockNumber>,
+	blocks_count: u32,
+	/// Sums of the cores over the processed blocks
+	totals: CoreOccupancy,
+}
+
+impl CoreOccupancyTracker {
+	/// Candidates waiting to be included, with their parachain ids
+	pub fn awaiting_inclusion(&self) -> Vec<(u32, H256)> {
+		self.pending
+			.iter()
+			.map(|(candidate_hash, (para_id, _))| (*para_id, *candidate_hash))
+			.collect()
+	}
+
+	/// Processes a relay chain block with the candidates included or timed out in it
+	pub fn on_block(
+		&mut self,
+		observation: &BlockObservation,
+		included: &[H256],
+		timed_out: &[H256],
+	) -> Option<CoreOccupancy> {
+		if self
+			.last_block
+			.is_some_and(|block_number| observation.block_number <= block_number)
+		{
+			return None
+		}
+		self.last_block = Some(observation.block_number);
+
+		let timed_out_count = timed_out.iter().filter(|hash| self.pending.contains_key(hash)).count() as u32;
+		self.pending.retain(|candidate_hash, (_, backed_at)| {
+			!included.contains(candidate_hash) &&
+				!timed_out.contains(candidate_hash) &&
+				observation.block_number.saturating_sub(*backed_at) < MAX_PENDING_AGE
+		});
+		for candidate in observation.candidates.iter() {
+			self.pending
+				.insert(candidate.candidate_hash, (candidate.para_id, observation.block_number));
+		}
+
+		let occupied = observation.cores.iter().filter(|core| core.occupied).count() as u32;
+		let scheduled = observation
+			.cores
+			.iter()
+			.filter(|core| !core.occupied && !core.scheduled_paras.is_empty())
+			.count() as u32;
+		let occupancy = CoreOccupancy {
+			block_number: observation.block_number,
+			cores: observation.cores.len() as u32,
+			occupied,
+			scheduled,
+			free: observation.cores.len() as u32 - occupied - scheduled,
+			timed_out: timed_out_count,
+		};
+
+		self.blocks_count += 1;
+		self.totals.cores += occupancy.cores;
+		self.totals.occupied += occupancy.occupied;
+		self.totals.scheduled += occupancy.scheduled;
+		self.totals.free += occupancy.free;
+		self.totals.timed_out += occupancy.timed_out;
+
+		Some(occupancy)
+	}
+}
+
+impl Display for CoreOccupancyTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- Core occupancy ---".to_string().bold().blue())?;
+		if self.blocks_count == 0 {
+			return writeln!(f, "No blocks processed")
+		}
+		let avg = |total: u32| total as f64 / self.blocks_count as f64;
+		writeln!(
+			f,
+			"Average cores per block: {:.2} occupied, {:.2} scheduled, {:.2} free of {:.2}",
+			avg(self.totals.occupied),
+			avg(self.totals.scheduled),
+			avg(self.totals.free),
+			avg(self.totals.cores)
+		)?;
+		if self.totals.cores > 0 {
+			writeln!(f, "Utilisation: {:.2}%", self.totals.occupied as f64 * 100.0 / self.totals.cores as f64)?;
+		}
+		writeln!(f, "Timed out candidates: {}", self.totals.timed_out.to_string().bright_red())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_introspector_essentials::observation::{CandidateObservation, CoreObservation};
+
+	fn observation(block_number: BlockNumber, cores: &[(bool, Vec<u32>)], backed: &[(u32, H256)]) -> BlockObservation {
+		BlockObservation {
+			block_number,
+			cores: cores
+				.iter()
+				.enumerate()
+				.map(|(core_index, (occupied, scheduled_paras))| CoreObservation {
+					core_index: core_index as u32,
+					occupied: *occupied,
+					scheduled_paras: scheduled_paras.clone(),
+				})
+				.collect(),
+			candidates: backed
+				.iter()
+				.map(|(para_id, candidate_hash)| CandidateObservation {
+					para_id: *para_id,
+					candidate_hash: *candidate_hash,
+					..Default::default()
+				})
+				.collect(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_counts_cores() {
+		let mut tracker = CoreOccupancyTracker::default();
+		let (included_hash, timed_out_hash) = (H256::random(), H256::random());
+
+		let occupancy = tracker
+			.on_block(
+				&observation(
+					10,
+					&[(false, vec![2000]), (false, vec![2001]), (false, vec![])],
+					&[(2000, included_hash), (2001, timed_out_hash)],
+				),
+				&[],
+				&[],
+			)
+			.unwrap();
+		assert_eq!(
+			occupancy,
+			CoreOccupancy { block_number: 10, cores: 3, occupied: 0, scheduled: 2, free: 1, timed_out: 0 }
+		);
+		assert_eq!(tracker.awaiting_inclusion().len(), 2);
+
+		let occupancy = tracker
+			.on_block(&observation(11, &[(true, vec![2000]), (true, vec![2001]), (false, vec![])], &[]), &[], &[])
+			.unwrap();
+		assert_eq!(occupancy.occupied, 2);
+		// Forks are skipped
+		assert!(tracker.on_block(&observation(11, &[], &[]), &[], &[]).is_none());
+
+		let occupancy = tracker
+			.on_block(
+				&observation(12, &[(false, vec![2000]), (false, vec![2001]), (false, vec![])], &[]),
+				&[included_hash],
+				&[timed_out_hash],
+			)
+			.unwrap();
+		assert_eq!(occupancy.timed_out, 1);
+		assert!(tracker.awaiting_inclusion().is_empty());
+		assert_eq!(tracker.totals.occupied, 2);
+		assert_eq!(tracker.blocks_count, 3);
+	}
+}
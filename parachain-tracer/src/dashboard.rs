@@ -0,0 +1,172 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Grafana dashboard generated from the catalog of the registered metrics.
+//!
+//! Every metric exported in Prometheus mode gets a time series panel: gauges are plotted as they are, counters
+//! as rates and histograms as their 95th percentile. Series of the parachain metrics are filtered by the traced
+//! parachains, so the dashboard can't drift from the names and labels the tracer exports.
+
+use polkadot_introspector_essentials::metrics::MetricDescription;
+use serde_json::{json, Value};
+
+/// Label of the parachain metrics the series are filtered by
+const PARA_LABEL: &str = "parachain_id";
+/// Panels per dashboard row
+const PANELS_PER_ROW: usize = 2;
+const PANEL_WIDTH: usize = 24 / PANELS_PER_ROW;
+const PANEL_HEIGHT: usize = 8;
+
+fn selector(metric: &MetricDescription, para_ids: &[u32]) -> String {
+	if para_ids.is_empty() || !metric.labels.iter().any(|label| label == PARA_LABEL) {
+		return String::new()
+	}
+	let para_ids: Vec<String> = para_ids.iter().map(|para_id| para_id.to_string()).collect();
+	format!("{{{}=~\"{}\"}}", PARA_LABEL, para_ids.join("|"))
+}
+
+fn query(metric: &MetricDescription, prefix: &str, para_ids: &[u32]) -> String {
+	let name = format!("{}_{}", prefix, metric.name);
+	let selector = selector(metric, para_ids);
+	let labels = metric.labels.join(", ");
+	match metric.kind {
+		"counter" if labels.is_empty() => format!("rate({}{}[$__rate_interval])", name, selector),
+		"counter" => format!("sum by ({}) (rate({}{}[$__rate_interval]))", labels, name, selector),
+		"histogram" => {
+			let labels = if labels.is_empty() { String::from("le") } else { format!("le, {}", labels) };
+			format!(
+				"histogram_quantile(0.95, sum by ({}) (rate({}_bucket{}[$__rate_interval])))",
+				labels, name, selector
+			)
+		},
+		_ => format!("{}{}", name, selector),
+	}
+}
+
+fn legend(metric: &MetricDescription) -> String {
+	if metric.labels.is_empty() {
+		return metric.name.clone()
+	}
+	metric
+		.labels
+		.iter()
+		.map(|label| format!("{{{{{}}}}}", label))
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+fn panel(id: usize, metric: &MetricDescription, prefix: &str, para_ids: &[u32]) -> Value {
+	json!({
+		"id": id + 1,
+		"type": "timeseries",
+		"title": metric.name,
+		"description": metric.help,
+		"datasource": { "type": "prometheus", "uid": "${datasource}" },
+		"gridPos": {
+			"x": (id % PANELS_PER_ROW) * PANEL_WIDTH,
+			"y": (id / PANELS_PER_ROW) * PANEL_HEIGHT,
+			"w": PANEL_WIDTH,
+			"h": PANEL_HEIGHT,
+		},
+		"targets": [{
+			"refId": "A",
+			"datasource": { "type": "prometheus", "uid": "${datasource}" },
+			"expr": query(metric, prefix, para_ids),
+			"legendFormat": legend(metric),
+		}],
+	})
+}
+
+/// Generates a dashboard with a panel per metric
+///
+/// # Arguments
+///
+/// * `catalog` - The registered metrics
+/// * `prefix` - The prefix of the registry, prepended to the names as Prometheus does
+/// * `para_ids` - The parachains to show the series of, all if empty
+pub fn generate(catalog: &[MetricDescription], prefix: &str, para_ids: &[u32]) -> Value {
+	let paras: Vec<String> = para_ids.iter().map(|para_id| para_id.to_string()).collect();
+	let (title, uid) = if paras.is_empty() {
+		(String::from("Parachain tracer"), String::from("parachain-tracer"))
+	} else {
+		(format!("Parachain tracer: {}", paras.join(", ")), format!("parachain-tracer-{}", paras.join("-")))
+	};
+	let panels: Vec<Value> = catalog
+		.iter()
+		.enumerate()
+		.map(|(id, metric)| panel(id, metric, prefix, para_ids))
+		.collect();
+
+	json!({
+		"title": title,
+		"uid": uid,
+		"tags": ["polkadot-introspector"],
+		"schemaVersion": 38,
+		"refresh": "30s",
+		"time": { "from": "now-6h", "to": "now" },
+		"templating": {
+			"list": [{
+				"name": "datasource",
+				"label": "Data source",
+				"type": "datasource",
+				"query": "prometheus",
+			}],
+		},
+		"panels": panels,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn metric(name: &str, kind: &'static str, labels: &[&str]) -> MetricDescription {
+		MetricDescription {
+			name: name.to_string(),
+			kind,
+			labels: labels.iter().map(|label| label.to_string()).collect(),
+			help: String::new(),
+		}
+	}
+
+	#[test]
+	fn test_generates_queries() {
+		let catalog = vec![
+			metric("pc_backed_count", "counter", &["parachain_id"]),
+			metric("pc_relay_reorgs_total", "counter", &[]),
+			metric("pc_block_time", "histogram", &["parachain_id"]),
+			metric("pc_cores_free", "gauge", &[]),
+		];
+		let dashboard = generate(&catalog, "introspector", &[2000, 2004]);
+
+		assert_eq!(dashboard["uid"], "parachain-tracer-2000-2004");
+		let queries: Vec<&str> = dashboard["panels"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|panel| panel["targets"][0]["expr"].as_str().unwrap())
+			.collect();
+		assert_eq!(
+			queries,
+			vec![
+				"sum by (parachain_id) (rate(introspector_pc_backed_count{parachain_id=~\"2000|2004\"}[$__rate_interval]))",
+				"rate(introspector_pc_relay_reorgs_total[$__rate_interval])",
+				"histogram_quantile(0.95, sum by (le, parachain_id) (rate(introspector_pc_block_time_bucket{parachain_id=~\"2000|2004\"}[$__rate_interval])))",
+				"introspector_pc_cores_free",
+			]
+		);
+		assert_eq!(dashboard["panels"][3]["gridPos"], json!({ "x": 12, "y": 8, "w": 12, "h": 8 }));
+	}
+}
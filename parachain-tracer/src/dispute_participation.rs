@@ -0,0 +1,361 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module tracks participation of a single validator in dispute resolution.
+
+use crate::explorer::Explorer;
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::{
+	metadata::polkadot_primitives::DisputeStatementSet,
+	types::{AccountId32, BlockNumber, H256},
+};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+};
+
+/// Disputes not concluded after this number of blocks since they were seen for the first time are considered timed out
+const DISPUTE_TRACKING_BLOCKS: u32 = 600;
+
+/// A dispute where the validator hasn't cast a statement in time
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisputeParticipationAlert {
+	/// Relay chain block number
+	pub block_number: BlockNumber,
+	/// Disputed candidate
+	pub candidate_hash: H256,
+	/// Relay chain block number where the dispute was seen for the first time
+	pub initiated: BlockNumber,
+	/// Index of the validator in the session
+	pub validator_index: u32,
+	pub explorer: Explorer,
+}
+
+struct PendingDispute {
+	/// Relay chain block number where the dispute was seen for the first time
+	initiated: BlockNumber,
+	/// Index of the validator in the session of the dispute
+	validator_index: u32,
+	/// The validator has cast a statement
+	voted: bool,
+	/// The operator has been alerted
+	alerted: bool,
+	/// The dispute has concluded
+	concluded: bool,
+}
+
+impl PendingDispute {
+	/// Whether the validator's participation in the dispute is known and nothing more is expected from it
+	fn is_finished(&self, block_number: BlockNumber, max_delay: u32) -> bool {
+		let age = block_number.saturating_sub(self.initiated);
+		// Statements are still imported after a conclusion, so late ones are counted until the delay is over
+		(self.concluded && (self.voted || self.alerted || age >= max_delay)) || age >= DISPUTE_TRACKING_BLOCKS
+	}
+}
+
+/// Tracks disputes in the relay chain inherents and detects missing statements of the validator
+pub struct DisputeParticipationTracker {
+	/// The validator's account
+	validator: AccountId32,
+	/// Number of blocks for the validator to cast a statement
+	max_delay: u32,
+	/// Index of the validator in known sessions, `None` if not a validator in the session
+	validator_indices: HashMap<u32, Option<u32>>,
+	/// Disputes seen in the validator's sessions
+	disputes: HashMap<H256, PendingDispute>,
+	/// Number of disputes seen
+	disputes_count: u32,
+	/// Number of disputes the validator has participated in
+	voted_count: u32,
+	/// Number of disputes without a statement in time
+	alerts_count: u32,
+	explorer: Explorer,
+}
+
+impl DisputeParticipationTracker {
+	/// Returns a new tracker
+	///
+	/// # Arguments
+	///
+	/// * `validator` - The validator's account
+	/// * `max_delay` - The number of blocks after that a missing statement is reported
+	pub fn new(validator: AccountId32, max_delay: u32) -> Self {
+		Self {
+			validator,
+			max_delay,
+			validator_indices: Default::default(),
+			disputes: Default::default(),
+			disputes_count: 0,
+			voted_count: 0,
+			alerts_count: 0,
+			explorer: Default::default(),
+		}
+	}
+
+	/// Links the alerts to the block explorer
+	pub fn with_explorer(mut self, explorer: Explorer) -> Self {
+		self.explorer = explorer;
+		self
+	}
+
+	/// Returns sessions of the disputes, for which session keys were not provided yet
+	pub fn unknown_sessions(&self, disputes: &[DisputeStatementSet]) -> Vec<u32> {
+		let mut sessions: Vec<u32> = disputes
+			.iter()
+			.map(|dispute| dispute.session)
+			.filter(|session| !self.validator_indices.contains_key(session))
+			.collect();
+		sessions.sort_unstable();
+		sessions.dedup();
+
+		sessions
+	}
+
+	/// Saves the validator's index in a session
+	pub fn set_session_keys(&mut self, session_index: u32, keys: &[AccountId32]) {
+		let index = keys.iter().position(|account| account == &self.validator).map(|v| v as u32);
+		self.validator_indices.insert(session_index, index);
+	}
+
+	/// Returns the tracked disputes that have not concluded yet
+	pub fn unconcluded(&self) -> Vec<H256> {
+		self.disputes
+			.iter()
+			.filter(|(_, dispute)| !dispute.concluded)
+			.map(|(candidate_hash, _)| *candidate_hash)
+			.collect()
+	}
+
+	/// Marks a dispute as concluded, it is forgotten once the validator's participation in it is known
+	pub fn on_concluded(&mut self, candidate_hash: H256) {
+		if let Some(dispute) = self.disputes.get_mut(&candidate_hash) {
+			dispute.concluded = true;
+		}
+	}
+
+	/// Processes disputes from a relay chain block inherent and checks all tracked disputes,
+	/// returns disputes without the validator's statement in time
+	pub fn on_block(
+		&mut self,
+		block_number: BlockNumber,
+		disputes: &[DisputeStatementSet],
+	) -> Vec<DisputeParticipationAlert> {
+		for dispute in disputes {
+			let validator_index = match self.validator_indices.get(&dispute.session) {
+				Some(Some(v)) => *v,
+				_ => continue,
+			};
+			let pending = self.disputes.entry(dispute.candidate_hash.0).or_insert_with(|| {
+				self.disputes_count += 1;
+				PendingDispute {
+					initiated: block_number,
+					validator_index,
+					voted: false,
+					alerted: false,
+					concluded: false,
+				}
+			});
+			if !pending.voted && dispute.statements.iter().any(|(_, index, _)| index.0 == validator_index) {
+				pending.voted = true;
+				self.voted_count += 1;
+			}
+		}
+
+		// Disputes are not necessarily included again, so all of them are checked on every block
+		let mut alerts = vec![];
+		for (candidate_hash, pending) in self.disputes.iter_mut() {
+			if !pending.voted && !pending.alerted && block_number.saturating_sub(pending.initiated) >= self.max_delay {
+				pending.alerted = true;
+				self.alerts_count += 1;
+				alerts.push(DisputeParticipationAlert {
+					block_number,
+					candidate_hash: *candidate_hash,
+					initiated: pending.initiated,
+					validator_index: pending.validator_index,
+					explorer: self.explorer.clone(),
+				});
+			}
+		}
+		let max_delay = self.max_delay;
+		self.disputes.retain(|_, dispute| !dispute.is_finished(block_number, max_delay));
+		alerts.sort_by_key(|alert| (alert.initiated, alert.candidate_hash));
+
+		alerts
+	}
+
+	/// Returns the number of tracked disputes without the validator's statement
+	pub fn pending_count(&self) -> u32 {
+		self.disputes.values().filter(|dispute| !dispute.voted).count() as u32
+	}
+}
+
+impl Display for DisputeParticipationAlert {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(
+			f,
+			"[#{}] {} validator {} has not cast a statement for candidate {} disputed at #{} ({} blocks ago)",
+			self.block_number,
+			"DISPUTE NOT PARTICIPATED".to_string().bold().red(),
+			self.validator_index,
+			format!("{:?}", self.candidate_hash).magenta(),
+			self.initiated,
+			self.block_number.saturating_sub(self.initiated).to_string().bright_red()
+		)?;
+		self.explorer.write_block_link(f, self.initiated)
+	}
+}
+
+impl Display for DisputeParticipationTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- Dispute participation ---".to_string().bold().blue())?;
+		writeln!(
+			f,
+			"Validator {}: {} disputes seen, voted in {}, not voted within {} blocks: {}",
+			self.validator,
+			self.disputes_count,
+			self.voted_count.to_string().bright_green(),
+			self.max_delay,
+			self.alerts_count.to_string().bright_red()
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::create_dispute_statement_set;
+	use polkadot_introspector_essentials::metadata::polkadot::runtime_types::polkadot_core_primitives::CandidateHash;
+
+	const CANDIDATE_HASH: H256 = H256([7; 32]);
+
+	fn create_tracker() -> DisputeParticipationTracker {
+		let validator = AccountId32([1; 32]);
+		let mut tracker = DisputeParticipationTracker::new(validator.clone(), 3);
+		// The validator has index 2 in the session 0
+		tracker.set_session_keys(0, &[AccountId32([0; 32]), AccountId32([0; 32]), validator]);
+		tracker
+	}
+
+	fn create_dispute(session: u32, has_statement: bool) -> DisputeStatementSet {
+		let mut dispute = create_dispute_statement_set();
+		dispute.candidate_hash = CandidateHash(CANDIDATE_HASH);
+		dispute.session = session;
+		if !has_statement {
+			dispute.statements.retain(|(_, index, _)| index.0 != 2);
+		}
+		dispute
+	}
+
+	#[test]
+	fn test_returns_unknown_sessions() {
+		let tracker = create_tracker();
+
+		assert!(tracker.unknown_sessions(&[create_dispute(0, true)]).is_empty());
+		assert_eq!(tracker.unknown_sessions(&[create_dispute(1, true), create_dispute(1, false)]), vec![1]);
+	}
+
+	#[test]
+	fn test_tracks_statements() {
+		let mut tracker = create_tracker();
+
+		assert!(tracker.on_block(10, &[create_dispute(0, true)]).is_empty());
+		assert!(tracker.on_block(20, &[create_dispute(0, true)]).is_empty());
+		assert_eq!(tracker.pending_count(), 0);
+		assert_eq!(tracker.voted_count, 1);
+	}
+
+	#[test]
+	fn test_alerts_on_missing_statements() {
+		let mut tracker = create_tracker();
+
+		assert!(tracker.on_block(10, &[create_dispute(0, false)]).is_empty());
+		assert!(tracker.on_block(12, &[create_dispute(0, false)]).is_empty());
+		assert_eq!(tracker.pending_count(), 1);
+
+		let alerts = tracker.on_block(13, &[create_dispute(0, false)]);
+		assert_eq!(
+			alerts,
+			vec![DisputeParticipationAlert {
+				block_number: 13,
+				candidate_hash: CANDIDATE_HASH,
+				initiated: 10,
+				validator_index: 2,
+				explorer: Default::default(),
+			}]
+		);
+		// Alerted only once
+		assert!(tracker.on_block(14, &[create_dispute(0, false)]).is_empty());
+	}
+
+	#[test]
+	fn test_alerts_on_disputes_included_once() {
+		let mut tracker = create_tracker();
+
+		assert!(tracker.on_block(10, &[create_dispute(0, false)]).is_empty());
+		assert!(tracker.on_block(11, &[]).is_empty());
+		assert_eq!(tracker.unconcluded(), vec![CANDIDATE_HASH]);
+
+		let alerts = tracker.on_block(13, &[]);
+		assert_eq!(alerts.len(), 1);
+		assert_eq!(alerts[0].initiated, 10);
+		assert_eq!(alerts[0].validator_index, 2);
+		assert!(tracker.on_block(14, &[]).is_empty());
+	}
+
+	#[test]
+	fn test_expires_concluded_disputes() {
+		let mut tracker = create_tracker();
+
+		assert!(tracker.on_block(10, &[create_dispute(0, false)]).is_empty());
+		tracker.on_concluded(CANDIDATE_HASH);
+		assert!(tracker.unconcluded().is_empty());
+		// A late statement is still counted
+		assert!(tracker.on_block(11, &[create_dispute(0, true)]).is_empty());
+		assert_eq!(tracker.voted_count, 1);
+		assert_eq!(tracker.pending_count(), 0);
+		assert!(tracker.disputes.is_empty());
+
+		// A concluded dispute without a statement is reported once the delay is over and then forgotten
+		let mut tracker = create_tracker();
+		tracker.on_block(10, &[create_dispute(0, false)]);
+		tracker.on_concluded(CANDIDATE_HASH);
+		assert!(tracker.on_block(12, &[]).is_empty());
+		assert_eq!(tracker.on_block(13, &[]).len(), 1);
+		assert!(tracker.disputes.is_empty());
+	}
+
+	#[test]
+	fn test_expires_timed_out_disputes() {
+		let mut tracker = create_tracker();
+
+		tracker.on_block(10, &[create_dispute(0, true)]);
+		assert!(tracker.on_block(10 + DISPUTE_TRACKING_BLOCKS - 1, &[]).is_empty());
+		assert_eq!(tracker.disputes.len(), 1);
+		tracker.on_block(10 + DISPUTE_TRACKING_BLOCKS, &[]);
+		assert!(tracker.disputes.is_empty());
+	}
+
+	#[test]
+	fn test_ignores_sessions_without_validator() {
+		let mut tracker = create_tracker();
+		tracker.set_session_keys(1, &[AccountId32([0; 32])]);
+
+		assert!(tracker.on_block(10, &[create_dispute(1, false)]).is_empty());
+		assert!(tracker.on_block(20, &[create_dispute(1, false)]).is_empty());
+		assert_eq!(tracker.disputes_count, 0);
+	}
+}
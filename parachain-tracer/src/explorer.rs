@@ -0,0 +1,113 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Block explorer links for the blocks referenced in alerts.
+//!
+//! Links are built from a URL template where `{block}` is replaced by a block number or hash, so any explorer
+//! (Subscan, Statescan, Polkassembly, a local one) can be used. Candidates and disputes have no pages of their
+//! own, so they are linked by the relay chain block they were observed in.
+
+use clap::ValueEnum;
+use std::{
+	fmt::{self, Display, Write},
+	sync::Arc,
+};
+
+/// Relay chains with a default block explorer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum KnownRelayChain {
+	Polkadot,
+	Kusama,
+}
+
+impl KnownRelayChain {
+	/// Guesses the relay chain from the node URL, e.g. `wss://rpc.polkadot.io:443`
+	pub(crate) fn from_url(url: &str) -> Option<Self> {
+		let url = url.to_lowercase();
+		if url.contains("kusama") {
+			Some(Self::Kusama)
+		} else if url.contains("polkadot") {
+			Some(Self::Polkadot)
+		} else {
+			None
+		}
+	}
+}
+
+/// Replaced by the block number or hash in URL templates
+pub(crate) const BLOCK_PLACEHOLDER: &str = "{block}";
+
+/// Subscan block page of the relay chain
+pub(crate) fn default_url_template(relay_chain: KnownRelayChain) -> String {
+	let subdomain = match relay_chain {
+		KnownRelayChain::Polkadot => "polkadot",
+		KnownRelayChain::Kusama => "kusama",
+	};
+	format!("https://{}.subscan.io/block/{}", subdomain, BLOCK_PLACEHOLDER)
+}
+
+fn block_url(url_template: &str, block: impl Display) -> String {
+	url_template.replace(BLOCK_PLACEHOLDER, &block.to_string())
+}
+
+/// Links to a block explorer, cheap to clone into every output that shows them. The default one has no explorer
+/// configured and builds no links.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Explorer(Option<Arc<str>>);
+
+impl Explorer {
+	pub(crate) fn new(url_template: String) -> Self {
+		Self(Some(url_template.into()))
+	}
+
+	/// Returns the link to the block, `None` if no explorer is configured
+	pub(crate) fn block_link(&self, block: impl Display) -> Option<String> {
+		self.0.as_deref().map(|url_template| block_url(url_template, block))
+	}
+
+	/// Writes a line with the link to the block, nothing if no explorer is configured
+	pub(crate) fn write_block_link(&self, f: &mut impl Write, block: impl Display) -> fmt::Result {
+		match self.block_link(block) {
+			Some(url) => writeln!(f, "\t🔎 Explorer: {}", url),
+			None => Ok(()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_introspector_essentials::types::H256;
+
+	#[test]
+	fn test_builds_block_urls() {
+		let template = default_url_template(KnownRelayChain::Kusama);
+		assert_eq!(block_url(&template, 42), "https://kusama.subscan.io/block/42");
+		assert_eq!(
+			block_url("https://polkadot.statescan.io/#/blocks/{block}", format!("{:?}", H256::zero())),
+			format!("https://polkadot.statescan.io/#/blocks/0x{}", "0".repeat(64))
+		);
+		assert_eq!(Explorer::new(template).block_link(42).as_deref(), Some("https://kusama.subscan.io/block/42"));
+		assert_eq!(Explorer::default().block_link(42), None);
+	}
+
+	#[test]
+	fn test_guesses_relay_chain_from_url() {
+		assert_eq!(KnownRelayChain::from_url("wss://rpc.polkadot.io:443"), Some(KnownRelayChain::Polkadot));
+		assert_eq!(KnownRelayChain::from_url("wss://kusama-rpc.polkadot.io:443"), Some(KnownRelayChain::Kusama));
+		assert_eq!(KnownRelayChain::from_url("ws://localhost:9944"), None);
+	}
+}
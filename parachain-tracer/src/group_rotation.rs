@@ -0,0 +1,272 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Follows the validator groups assigned to the availability core of a parachain.
+//!
+//! The groups rotate through the cores every `group_rotation_frequency` relay chain blocks since the start of the
+//! session. The group assigned at each block is calculated the same way the runtime does it, credited with the
+//! candidates backed in the block and blamed for the skipped slots, so consistently underperforming groups stand out.
+
+use crate::types::{ParachainConsensusEvent, ParachainProgressUpdate, SkippedSlotReason};
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::types::BlockNumber;
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Display, Formatter},
+};
+
+/// The validator group assigned to the core of the parachain at a relay chain block
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupAssignment {
+	/// Relay chain block number
+	pub block_number: BlockNumber,
+	/// Availability core of the parachain
+	pub core: u32,
+	/// Index of the assigned validator group
+	pub group_idx: u32,
+	/// The group assigned at the previous block, if the groups have rotated since then
+	pub rotated_from: Option<u32>,
+	/// The slot was skipped while the group was assigned
+	pub missed_backing: bool,
+}
+
+/// Slots of the parachain a validator group was assigned to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupStats {
+	/// Number of relay chain blocks the group was assigned to the core of the parachain
+	pub assigned: u32,
+	/// Number of blocks with a candidate backed by the group
+	pub backed: u32,
+	/// Number of skipped slots while the group was assigned
+	pub missed: u32,
+}
+
+impl GroupStats {
+	/// Share of the slots with a backed candidate, in percent, `None` if the group had no slots
+	pub fn success_rate(&self) -> Option<f64> {
+		let slots = self.backed + self.missed;
+		(slots > 0).then(|| self.backed as f64 * 100.0 / slots as f64)
+	}
+}
+
+/// Backing success rate by group index in a session
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GroupBackingSummary {
+	/// Session the group indices are valid in
+	pub session_index: Option<u32>,
+	/// Slots per group index
+	pub groups: BTreeMap<u32, GroupStats>,
+}
+
+/// Calculates the validator groups assigned to the core of a parachain and aggregates their backing per session
+pub struct GroupRotationTracker {
+	/// Number of relay chain blocks between the group rotations
+	frequency: u32,
+	/// Current session
+	session_index: Option<u32>,
+	/// Block number the current session started at, groups rotate relative to it
+	session_start_block: Option<BlockNumber>,
+	/// The start of the current session was requested, successfully or not
+	is_session_start_block_requested: bool,
+	/// The group assigned at the previous block
+	last_group_idx: Option<u32>,
+	/// Slots per group index in the current session
+	groups: BTreeMap<u32, GroupStats>,
+}
+
+impl GroupRotationTracker {
+	/// Returns a new tracker for groups rotating every `frequency` relay chain blocks
+	pub fn new(frequency: u32) -> Self {
+		Self {
+			frequency: frequency.max(1),
+			session_index: None,
+			session_start_block: None,
+			is_session_start_block_requested: false,
+			last_group_idx: None,
+			groups: Default::default(),
+		}
+	}
+
+	/// The start of the session is read from the chain once per session
+	pub fn needs_session_start_block(&self) -> bool {
+		!self.is_session_start_block_requested
+	}
+
+	/// Sets the start of the session, if it couldn't be read the groups are not followed until the next session
+	pub fn set_session_start_block(&mut self, block_number: Option<BlockNumber>) {
+		self.is_session_start_block_requested = true;
+		self.session_start_block = block_number;
+	}
+
+	/// Group indices are valid within a session, returns the summary of the previous one
+	pub fn on_new_session(&mut self, session_index: u32) -> Option<GroupBackingSummary> {
+		let summary = (!self.groups.is_empty()).then(|| self.summary());
+		self.session_index = Some(session_index);
+		self.session_start_block = None;
+		self.is_session_start_block_requested = false;
+		self.last_group_idx = None;
+		self.groups.clear();
+		summary
+	}
+
+	/// Returns the group assigned to the core at a relay chain block. The runtime checks the backing of a candidate
+	/// against the group assigned at the block after its relay parent, that is the block it is backed in when built
+	/// on the most recent relay parent
+	pub fn group_for_core(&self, core: u32, block_number: BlockNumber, groups_count: usize) -> Option<u32> {
+		let session_start_block = self.session_start_block?;
+		if groups_count == 0 {
+			return None
+		}
+		let rotations = block_number.saturating_sub(session_start_block) / self.frequency;
+		Some(((core as usize + rotations as usize) % groups_count) as u32)
+	}
+
+	/// Processes the progress of the parachain on the given core, returns the assigned group
+	pub fn on_progress(
+		&mut self,
+		progress: &ParachainProgressUpdate,
+		core: Option<u32>,
+		groups_count: usize,
+	) -> Option<GroupAssignment> {
+		let core = core?;
+		let group_idx = self.group_for_core(core, progress.block_number, groups_count)?;
+		let backed = progress
+			.events
+			.iter()
+			.any(|event| matches!(event, ParachainConsensusEvent::Backed(..)));
+		// A group can't back anything on an occupied core, a candidate dropped for insufficient votes is missed
+		let missed_backing = progress
+			.skipped_slot_reason()
+			.is_some_and(|reason| reason != SkippedSlotReason::CoreOccupied);
+
+		let stats = self.groups.entry(group_idx).or_default();
+		stats.assigned += 1;
+		if missed_backing {
+			stats.missed += 1;
+		} else if backed {
+			stats.backed += 1;
+		}
+		let rotated_from = self.last_group_idx.replace(group_idx).filter(|last| *last != group_idx);
+
+		Some(GroupAssignment { block_number: progress.block_number, core, group_idx, rotated_from, missed_backing })
+	}
+
+	/// Returns the backing success rate by group index in the current session
+	pub fn summary(&self) -> GroupBackingSummary {
+		GroupBackingSummary { session_index: self.session_index, groups: self.groups.clone() }
+	}
+}
+
+impl Display for GroupAssignment {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "[#{}] Core {}: group {}", self.block_number, self.core, self.group_idx.to_string().bold())?;
+		if let Some(rotated_from) = self.rotated_from {
+			write!(f, " {}", format!("(rotated from group {})", rotated_from).bright_yellow())?;
+		}
+		if self.missed_backing {
+			write!(f, " {}", "missed backing".to_string().bright_red())?;
+		}
+		writeln!(f)
+	}
+}
+
+impl Display for GroupBackingSummary {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "{}", "--- Backing success rate by group index ---".to_string().bold().blue())?;
+		if let Some(session_index) = self.session_index {
+			write!(f, " in session {}", session_index)?;
+		}
+		writeln!(f)?;
+		writeln!(f, "\t{:>6} {:>9} {:>7} {:>7} {:>8}", "Group", "Assigned", "Backed", "Missed", "Success")?;
+		for (group_idx, stats) in &self.groups {
+			let success_rate = format!(
+				"{:>8}",
+				stats
+					.success_rate()
+					.map_or_else(|| "NA".to_owned(), |rate| format!("{:.1}%", rate))
+			);
+			writeln!(
+				f,
+				"\t{:>6} {:>9} {:>7} {:>7} {}",
+				group_idx,
+				stats.assigned,
+				stats.backed,
+				stats.missed,
+				if stats.missed > 0 { success_rate.bright_red().to_string() } else { success_rate }
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::create_progress;
+
+	#[test]
+	fn test_rotates_groups() {
+		let mut tracker = GroupRotationTracker::new(10);
+		assert!(tracker.on_progress(&create_progress(100, vec![]), Some(1), 5).is_none());
+
+		// A failed lookup is not repeated in the same session
+		tracker.set_session_start_block(None);
+		assert!(!tracker.needs_session_start_block());
+		assert!(tracker.on_progress(&create_progress(100, vec![]), Some(1), 5).is_none());
+
+		tracker.set_session_start_block(Some(95));
+		assert!(!tracker.needs_session_start_block());
+		assert_eq!(tracker.group_for_core(1, 104, 5), Some(1));
+		assert_eq!(tracker.group_for_core(1, 105, 5), Some(2));
+		assert_eq!(tracker.group_for_core(4, 105, 5), Some(0));
+
+		let assignment = tracker.on_progress(&create_progress(104, vec![]), Some(1), 5).unwrap();
+		assert_eq!(assignment.group_idx, 1);
+		assert_eq!(assignment.rotated_from, None);
+		let assignment = tracker.on_progress(&create_progress(105, vec![]), Some(1), 5).unwrap();
+		assert_eq!(assignment.group_idx, 2);
+		assert_eq!(assignment.rotated_from, Some(1));
+	}
+
+	#[test]
+	fn test_summarizes_backing_by_group() {
+		let mut tracker = GroupRotationTracker::new(2);
+		tracker.on_new_session(7);
+		tracker.set_session_start_block(Some(100));
+
+		let backed = ParachainConsensusEvent::Backed(Default::default(), 5, 5);
+		tracker.on_progress(&create_progress(100, vec![backed.clone()]), Some(0), 3);
+		tracker.on_progress(&create_progress(101, vec![backed.clone()]), Some(0), 3);
+		let assignment = tracker
+			.on_progress(
+				&create_progress(102, vec![ParachainConsensusEvent::SkippedSlot(SkippedSlotReason::NoCandidate)]),
+				Some(0),
+				3,
+			)
+			.unwrap();
+		assert!(assignment.missed_backing);
+		tracker.on_progress(&create_progress(103, vec![backed]), Some(0), 3);
+
+		let summary = tracker.on_new_session(8).unwrap();
+		assert_eq!(summary.session_index, Some(7));
+		assert_eq!(summary.groups[&0], GroupStats { assigned: 2, backed: 2, missed: 0 });
+		assert_eq!(summary.groups[&1], GroupStats { assigned: 2, backed: 1, missed: 1 });
+		assert_eq!(summary.groups[&1].success_rate(), Some(50.0));
+		assert!(tracker.needs_session_start_block());
+		assert!(tracker.on_new_session(9).is_none());
+	}
+}
@@ -0,0 +1,255 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compares the heads of a parachain observed on several relay chain nodes.
+//!
+//! The other nodes are polled in a task of their own, at most once per relay chain block time, with the last block of
+//! the traced node. The head of the parachain is read only on the nodes whose block at the same height differs from
+//! the traced one. Forks make them differ for a block or two, a divergence that persists points to a node serving
+//! stale or forked state. A node that has no block at that height yet, or fails to answer, is checked again later.
+
+use crate::types::ParachainProgressUpdate;
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use log::warn;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::{ExecutorContext, RequestExecutor},
+	constants::STANDARD_BLOCK_TIME,
+	types::{BlockNumber, H256},
+	utils::RetryOptions,
+};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+};
+use tokio::{
+	sync::{
+		mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+		watch,
+	},
+	time::{interval, Duration, MissedTickBehavior},
+};
+
+/// The head of a parachain on a node has differed from the traced node for a number of blocks
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct HeadDivergence {
+	pub para_id: u32,
+	/// The diverging node
+	pub node: String,
+	/// Relay chain block the divergence was reported at
+	pub block_number: BlockNumber,
+	/// Relay chain block the heads started to differ at
+	pub since: BlockNumber,
+	/// Hash of the head data on the traced node
+	pub expected: Option<H256>,
+	/// Hash of the head data on the diverging node, not set if it has no head
+	pub observed: Option<H256>,
+}
+
+impl Display for HeadDivergence {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(
+			f,
+			"[#{}] {} of parachain {} on {} since #{}: {:?} on the traced node, {}",
+			self.block_number,
+			"HEAD DIVERGENCE".to_string().bold().red(),
+			self.para_id,
+			self.node,
+			self.since,
+			self.expected,
+			format!("{:?}", self.observed).bright_red()
+		)
+	}
+}
+
+/// Reports the relay chain nodes whose head of a parachain differs from the traced node
+pub(crate) struct HeadDivergenceDetector {
+	/// The last block of the traced node, the poller skips the blocks it cannot keep up with
+	blocks: watch::Sender<Option<(BlockNumber, H256)>>,
+	divergences: UnboundedReceiver<HeadDivergence>,
+}
+
+impl HeadDivergenceDetector {
+	/// Spawns the poller of the other nodes, if there are any. It stops when the detector is dropped
+	pub fn spawn(
+		para_id: u32,
+		node: &str,
+		others: &[String],
+		threshold: u32,
+		retry: &RetryOptions,
+		context: &ExecutorContext,
+	) -> Option<Self> {
+		if others.is_empty() {
+			return None
+		}
+		let (blocks, blocks_rx) = watch::channel(None);
+		let (divergences_tx, divergences) = unbounded_channel();
+		let poller = HeadPoller {
+			para_id,
+			node: node.to_string(),
+			others: others.to_vec(),
+			threshold,
+			executor: RequestExecutor::new(retry.clone(), context.clone()),
+			diverging: Default::default(),
+		};
+		tokio::spawn(poller.run(blocks_rx, divergences_tx));
+
+		Some(Self { blocks, divergences })
+	}
+
+	/// Passes the block of the progress to the poller, returns the divergences it has found since the last call
+	pub fn on_progress(&mut self, progress: &ParachainProgressUpdate) -> Vec<HeadDivergence> {
+		self.blocks.send_replace(Some((progress.block_number, progress.block_hash)));
+		std::iter::from_fn(|| self.divergences.try_recv().ok()).collect()
+	}
+}
+
+/// Polls the other nodes off the parachain task
+struct HeadPoller {
+	para_id: u32,
+	/// The traced node
+	node: String,
+	/// The nodes compared with the traced one
+	others: Vec<String>,
+	/// Number of relay chain blocks a divergence is reported after
+	threshold: u32,
+	executor: RequestExecutor,
+	/// Relay chain block the heads started to differ at and if it was reported, by node
+	diverging: HashMap<String, (BlockNumber, bool)>,
+}
+
+impl HeadPoller {
+	async fn run(
+		mut self,
+		mut blocks: watch::Receiver<Option<(BlockNumber, H256)>>,
+		divergences: UnboundedSender<HeadDivergence>,
+	) {
+		let mut rate_limit = interval(Duration::from_secs_f64(STANDARD_BLOCK_TIME));
+		rate_limit.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		while blocks.changed().await.is_ok() {
+			rate_limit.tick().await;
+			let Some((block_number, block_hash)) = *blocks.borrow_and_update() else { continue };
+			for divergence in self.poll(block_number, block_hash).await {
+				if divergences.send(divergence).is_err() {
+					return
+				}
+			}
+		}
+	}
+
+	/// Compares the heads of the parachain on the other nodes with the traced block
+	async fn poll(&mut self, block_number: BlockNumber, block_hash: H256) -> Vec<HeadDivergence> {
+		let expected = match self.executor.get_para_head(&self.node, block_hash, self.para_id).await {
+			Ok(v) => v,
+			Err(e) => {
+				warn!("Cannot read the head of parachain {} on {}: {:?}", self.para_id, self.node, e);
+				return vec![]
+			},
+		};
+		let mut heads = vec![];
+		for node in self.others.clone() {
+			match self.observe(&node, block_number, block_hash, expected).await {
+				Ok(Some(observed)) => heads.push((node, observed)),
+				// The node is behind, it's checked at a later block
+				Ok(None) => (),
+				Err(e) => warn!("Cannot read the head of parachain {} on {}: {:?}", self.para_id, node, e),
+			}
+		}
+
+		self.on_heads(block_number, expected, heads)
+	}
+
+	/// Returns the head of the parachain on a node at the height of the traced block, `None` if the node has no block
+	/// at that height yet
+	async fn observe(
+		&mut self,
+		node: &str,
+		block_number: BlockNumber,
+		block_hash: H256,
+		expected: Option<H256>,
+	) -> color_eyre::Result<Option<Option<H256>>> {
+		match self.executor.get_block_hash(node, Some(block_number)).await? {
+			// The same block has the same head
+			Some(node_block_hash) if node_block_hash == block_hash => Ok(Some(expected)),
+			Some(node_block_hash) => Ok(Some(self.executor.get_para_head(node, node_block_hash, self.para_id).await?)),
+			None => Ok(None),
+		}
+	}
+
+	/// Compares the heads of the other nodes with the head of the traced node
+	fn on_heads(
+		&mut self,
+		block_number: BlockNumber,
+		expected: Option<H256>,
+		heads: Vec<(String, Option<H256>)>,
+	) -> Vec<HeadDivergence> {
+		let mut divergences = vec![];
+		for (node, observed) in heads {
+			if observed == expected {
+				self.diverging.remove(&node);
+				continue
+			}
+			let (since, reported) = self.diverging.entry(node.clone()).or_insert((block_number, false));
+			if block_number.saturating_sub(*since) >= self.threshold && !*reported {
+				*reported = true;
+				divergences.push(HeadDivergence {
+					para_id: self.para_id,
+					node,
+					block_number,
+					since: *since,
+					expected,
+					observed,
+				});
+			}
+		}
+
+		divergences
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_reports_persistent_divergence() {
+		let others = ["ws://b".to_string(), "ws://c".to_string()];
+		let mut detector = HeadPoller {
+			para_id: 100,
+			node: "ws://a".to_string(),
+			others: others.to_vec(),
+			threshold: 3,
+			executor: RequestExecutor::new(Default::default(), Default::default()),
+			diverging: Default::default(),
+		};
+		let (head, stale) = (Some(H256::random()), Some(H256::random()));
+		let heads = |b, c| vec![("ws://b".to_string(), b), ("ws://c".to_string(), c)];
+
+		// A fork resolved within the threshold
+		assert!(detector.on_heads(10, head, heads(head, stale)).is_empty());
+		assert!(detector.on_heads(11, head, heads(head, head)).is_empty());
+
+		assert!(detector.on_heads(12, head, heads(stale, None)).is_empty());
+		assert!(detector.on_heads(14, head, heads(stale, None)).is_empty());
+		// A node lagging behind is not compared
+		assert!(detector.on_heads(15, head, vec![("ws://c".to_string(), head)]).is_empty());
+		let divergences = detector.on_heads(16, head, heads(stale, head));
+		assert_eq!(divergences.len(), 1);
+		assert_eq!((divergences[0].node.as_str(), divergences[0].since), ("ws://b", 12));
+		// Reported once
+		assert!(detector.on_heads(17, head, heads(stale, head)).is_empty());
+	}
+}
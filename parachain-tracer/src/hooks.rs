@@ -0,0 +1,305 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Scriptable hooks run on the parachain progress and anomalies.
+//!
+//! Hooks are [Rhai](https://rhai.rs) scripts defining `fn on_progress(event)`, `fn on_anomaly(anomaly)` or both.
+//! A progress event is an object map with the fields of the `progress` schema, optional fields are left out when not
+//! set. An anomaly has `detector`, `severity`, `block_number`, `timestamp`, `para_id` and `description` fields.
+//!
+//! Scripts have no access to the file system or the network, they can only call:
+//! - `emit_alert(message)` to report an alert, it's printed and added to the incidents timeline
+//! - `set_metric(name, value)` to set the `pc_hook_metric` gauge labeled with the script and metric names
+//!
+//! The number of operations of a call is limited, so a runaway script can't stall the tracer. `print` and `debug`
+//! of scripts write to the log. Every parachain task runs the hooks with its own engine.
+
+use crate::progress_record::ProgressRecord;
+use color_eyre::eyre::eyre;
+use log::{debug, error, info};
+use polkadot_introspector_essentials::anomaly::Anomaly;
+use rhai::{Dynamic, Engine, ImmutableString, Map, Scope, AST};
+use std::{
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+
+/// Maximum number of operations in a call of a hook
+const MAX_OPERATIONS: u64 = 100_000;
+/// Maximum depth of function calls in a hook
+const MAX_CALL_LEVELS: usize = 32;
+/// Maximum size of strings, arrays and maps created by a hook
+const MAX_VALUE_SIZE: usize = 64 * 1024;
+
+/// An action requested by a hook
+#[derive(Clone, Debug, PartialEq)]
+pub enum HookAction {
+	/// An alert message
+	Alert(String),
+	/// A metric name and value
+	Metric(String, f64),
+}
+
+/// An action requested by a hook with the name of the hook
+#[derive(Clone, Debug, PartialEq)]
+pub struct HookOutput {
+	pub script: String,
+	pub action: HookAction,
+}
+
+/// Returns an engine with the limits of a script, no modules and `print`, `debug` routed to the log, as the
+/// standard output is the output of the JSON mode
+pub(crate) fn sandboxed_engine(max_operations: u64, max_call_levels: usize) -> Engine {
+	let mut engine = Engine::new();
+	engine
+		.set_max_operations(max_operations)
+		.set_max_call_levels(max_call_levels)
+		.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new())
+		.on_print(|message| info!("{}", message))
+		.on_debug(|message, source, position| debug!("{} at {}: {}", source.unwrap_or("script"), position, message));
+	engine
+}
+
+#[derive(Clone)]
+struct Script {
+	/// Name of the script, the file name without extension
+	name: String,
+	ast: AST,
+	/// Defines `on_progress(event)`
+	on_progress: bool,
+	/// Defines `on_anomaly(anomaly)`
+	on_anomaly: bool,
+}
+
+/// Compiled hooks and the engine to run them
+pub struct ScriptHooks {
+	engine: Engine,
+	scripts: Vec<Script>,
+	/// Actions requested by the running hook
+	pending: Arc<Mutex<Vec<HookAction>>>,
+}
+
+impl ScriptHooks {
+	pub fn new() -> Self {
+		let pending: Arc<Mutex<Vec<HookAction>>> = Default::default();
+		let mut engine = sandboxed_engine(MAX_OPERATIONS, MAX_CALL_LEVELS);
+		engine
+			.set_max_string_size(MAX_VALUE_SIZE)
+			.set_max_array_size(MAX_VALUE_SIZE)
+			.set_max_map_size(MAX_VALUE_SIZE);
+
+		let alerts = pending.clone();
+		engine.register_fn("emit_alert", move |message: ImmutableString| {
+			alerts
+				.lock()
+				.expect("hook actions lock is poisoned")
+				.push(HookAction::Alert(message.to_string()))
+		});
+		let metrics = pending.clone();
+		engine.register_fn("set_metric", move |name: ImmutableString, value: f64| {
+			metrics
+				.lock()
+				.expect("hook actions lock is poisoned")
+				.push(HookAction::Metric(name.to_string(), value))
+		});
+		let metrics = pending.clone();
+		engine.register_fn("set_metric", move |name: ImmutableString, value: i64| {
+			metrics
+				.lock()
+				.expect("hook actions lock is poisoned")
+				.push(HookAction::Metric(name.to_string(), value as f64))
+		});
+
+		Self { engine, scripts: vec![], pending }
+	}
+
+	/// Compiles the scripts from the files
+	pub fn load(paths: &[PathBuf]) -> color_eyre::Result<Self> {
+		let mut hooks = Self::new();
+		for path in paths {
+			let source =
+				std::fs::read_to_string(path).map_err(|e| eyre!("Cannot read hook {}: {:?}", path.display(), e))?;
+			let name = path
+				.file_stem()
+				.map_or_else(|| path.display().to_string(), |v| v.to_string_lossy().into_owned());
+			hooks.add(name, &source)?;
+		}
+
+		Ok(hooks)
+	}
+
+	/// Compiles a script
+	pub fn add(&mut self, name: impl Into<String>, source: &str) -> color_eyre::Result<()> {
+		let name = name.into();
+		let ast = self
+			.engine
+			.compile(source)
+			.map_err(|e| eyre!("Cannot compile hook {}: {}", name, e))?;
+		let defines = |fn_name: &str| ast.iter_functions().any(|f| f.name == fn_name && f.params.len() == 1);
+		let on_progress = defines("on_progress");
+		let on_anomaly = defines("on_anomaly");
+		if !on_progress && !on_anomaly {
+			return Err(eyre!("Hook {} defines neither `on_progress(event)` nor `on_anomaly(anomaly)`", name))
+		}
+
+		self.scripts.push(Script { name, ast, on_progress, on_anomaly });
+		Ok(())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.scripts.is_empty()
+	}
+
+	/// Runs `on_progress` of the hooks
+	pub fn on_progress(&self, record: &ProgressRecord) -> Vec<HookOutput> {
+		match rhai::serde::to_dynamic(record) {
+			Ok(event) => self.call(|script| script.on_progress, "on_progress", event),
+			Err(e) => {
+				error!("Cannot pass the progress to hooks: {}", e);
+				vec![]
+			},
+		}
+	}
+
+	/// Runs `on_anomaly` of the hooks
+	pub fn on_anomaly(&self, anomaly: &Anomaly) -> Vec<HookOutput> {
+		let mut map = Map::new();
+		map.insert("detector".into(), Dynamic::from(anomaly.detector.clone()));
+		map.insert("severity".into(), Dynamic::from(anomaly.severity.to_string()));
+		map.insert("block_number".into(), Dynamic::from(anomaly.block_number as i64));
+		map.insert("timestamp".into(), Dynamic::from(anomaly.timestamp as i64));
+		map.insert("para_id".into(), anomaly.para_id.map_or(Dynamic::UNIT, |v| Dynamic::from(v as i64)));
+		map.insert("description".into(), Dynamic::from(anomaly.description.clone()));
+		self.call(|script| script.on_anomaly, "on_anomaly", Dynamic::from_map(map))
+	}
+
+	fn call(&self, is_defined: impl Fn(&Script) -> bool, fn_name: &str, arg: Dynamic) -> Vec<HookOutput> {
+		let mut outputs = vec![];
+		for script in self.scripts.iter().filter(|script| is_defined(script)) {
+			let result = self
+				.engine
+				.call_fn::<Dynamic>(&mut Scope::new(), &script.ast, fn_name, (arg.clone(),));
+			// Actions requested before a failure are kept
+			let actions = std::mem::take(&mut *self.pending.lock().expect("hook actions lock is poisoned"));
+			if let Err(e) = result {
+				error!("Hook {} failed in {}: {}", script.name, fn_name, e);
+			}
+			outputs.extend(
+				actions
+					.into_iter()
+					.map(|action| HookOutput { script: script.name.clone(), action }),
+			);
+		}
+
+		outputs
+	}
+}
+
+/// A clone has its own engine, so the tasks running the same hooks don't wait for each other
+impl Clone for ScriptHooks {
+	fn clone(&self) -> Self {
+		Self { scripts: self.scripts.clone(), ..Self::new() }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::types::ParachainProgressUpdate;
+	use polkadot_introspector_essentials::incidents::Severity;
+
+	#[test]
+	fn test_runs_progress_hooks() {
+		let mut hooks = ScriptHooks::new();
+		hooks
+			.add(
+				"slow",
+				r#"
+				fn on_progress(event) {
+					if event.block_time_ms > 12000 {
+						emit_alert("slow block " + event.block_number);
+					}
+					set_metric("available", event.availability.available_count);
+				}
+				"#,
+			)
+			.unwrap();
+		let progress = ParachainProgressUpdate {
+			para_id: 100,
+			block_number: 42,
+			timestamp: 20000,
+			prev_timestamp: 1000,
+			..Default::default()
+		};
+
+		let outputs = hooks.on_progress(&ProgressRecord::from(&progress));
+		assert_eq!(
+			outputs,
+			vec![
+				HookOutput { script: "slow".to_owned(), action: HookAction::Alert("slow block 42".to_owned()) },
+				HookOutput { script: "slow".to_owned(), action: HookAction::Metric("available".to_owned(), 0.0) },
+			]
+		);
+	}
+
+	#[test]
+	fn test_runs_anomaly_hooks() {
+		let mut hooks = ScriptHooks::new();
+		hooks
+			.add("progress", "fn on_progress(event) { emit_alert(\"progress\"); }")
+			.unwrap();
+		hooks
+			.add(
+				"anomaly",
+				"fn on_anomaly(anomaly) { print(anomaly.description); emit_alert(anomaly.detector + \" \" + \
+				 anomaly.severity); }",
+			)
+			.unwrap();
+		// Every task runs its own copy of the hooks
+		let hooks = hooks.clone();
+		let anomaly = Anomaly {
+			detector: "slow-block".to_string(),
+			severity: Severity::Warning,
+			block_number: 42,
+			timestamp: 0,
+			para_id: None,
+			description: "slow".to_owned(),
+		};
+
+		assert_eq!(
+			hooks.on_anomaly(&anomaly),
+			vec![HookOutput {
+				script: "anomaly".to_owned(),
+				action: HookAction::Alert("slow-block warning".to_owned())
+			}]
+		);
+	}
+
+	#[test]
+	fn test_rejects_invalid_hooks() {
+		let mut hooks = ScriptHooks::new();
+		assert!(hooks.add("syntax", "fn on_progress(event) {").is_err());
+		assert!(hooks.add("no-entry-points", "fn on_block(event) {}").is_err());
+		assert!(hooks.is_empty());
+
+		// Runaway scripts are stopped
+		hooks
+			.add("loop", "fn on_progress(event) { emit_alert(\"started\"); loop {} }")
+			.unwrap();
+		let outputs = hooks.on_progress(&ProgressRecord::from(&ParachainProgressUpdate::default()));
+		assert_eq!(outputs.len(), 1);
+	}
+}
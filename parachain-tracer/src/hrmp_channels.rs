@@ -0,0 +1,250 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Monitors the state of the HRMP channels of a parachain.
+//!
+//! A channel accepts messages until either its message count or their total size reaches the limits set when the
+//! channel was opened. Channels close to full are reported, so queues backing up are spotted before the senders
+//! can't send messages anymore.
+
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::{api::subxt_wrapper::SubxtHrmpChannel, types::BlockNumber};
+use std::{
+	collections::{BTreeMap, HashSet},
+	fmt::{self, Display, Formatter},
+};
+
+/// An open HRMP channel between two parachains
+#[derive(Clone, Debug, PartialEq)]
+pub struct HrmpChannelState {
+	pub sender: u32,
+	pub recipient: u32,
+	/// Number of messages in the queue
+	pub msg_count: u32,
+	/// Maximum number of messages in the queue
+	pub max_capacity: u32,
+	/// Total size of the messages in the queue
+	pub total_size: u32,
+	/// Maximum total size of the messages in the queue
+	pub max_total_size: u32,
+	/// Deposits reserved for the channel by the sender and the recipient
+	pub sender_deposit: u128,
+	pub recipient_deposit: u128,
+}
+
+impl HrmpChannelState {
+	fn new(sender: u32, recipient: u32, channel: &SubxtHrmpChannel) -> Self {
+		Self {
+			sender,
+			recipient,
+			msg_count: channel.msg_count,
+			max_capacity: channel.max_capacity,
+			total_size: channel.total_size,
+			max_total_size: channel.max_total_size,
+			sender_deposit: channel.sender_deposit,
+			recipient_deposit: channel.recipient_deposit,
+		}
+	}
+
+	/// Share of the capacity of the channel in use, by the message count or the total size, whichever is closer to
+	/// its limit
+	pub fn fill_ratio(&self) -> f64 {
+		let ratio = |used: u32, max: u32| if max == 0 { 0.0 } else { used as f64 / max as f64 };
+		ratio(self.msg_count, self.max_capacity).max(ratio(self.total_size, self.max_total_size))
+	}
+}
+
+impl Display for HrmpChannelState {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		let fill = format!("{:.0}%", self.fill_ratio() * 100.0);
+		write!(
+			f,
+			"{} → {}: {}/{} messages, {}/{} bytes, {} full, deposits {} / {}",
+			self.sender,
+			self.recipient,
+			self.msg_count,
+			self.max_capacity,
+			self.total_size,
+			self.max_total_size,
+			fill.bold(),
+			self.sender_deposit,
+			self.recipient_deposit
+		)
+	}
+}
+
+/// A channel that has just become close to full
+#[derive(Clone, Debug, PartialEq)]
+pub struct HrmpChannelWarning {
+	/// Relay chain block the channel was seen at
+	pub block_number: BlockNumber,
+	pub channel: HrmpChannelState,
+	/// Share of the capacity after that a channel is close to full
+	pub threshold: f64,
+}
+
+impl Display for HrmpChannelWarning {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(
+			f,
+			"[#{}] {}: {}, above {:.0}%",
+			self.block_number,
+			"HRMP CHANNEL CLOSE TO FULL".to_string().bold().yellow(),
+			self.channel,
+			self.threshold * 100.0
+		)
+	}
+}
+
+/// Follows the inbound and outbound HRMP channels of a parachain
+pub struct HrmpChannelMonitor {
+	para_id: u32,
+	/// Share of the capacity after that a channel is close to full
+	threshold: f64,
+	/// Open channels in the last relay chain block
+	channels: Vec<HrmpChannelState>,
+	/// Senders and recipients of the channels closed in the last relay chain block
+	closed: Vec<(u32, u32)>,
+	/// Senders and recipients of the channels above the threshold, warned once until they drain
+	near_full: HashSet<(u32, u32)>,
+}
+
+impl HrmpChannelMonitor {
+	pub fn new(para_id: u32, threshold: f64) -> Self {
+		Self { para_id, threshold, channels: vec![], closed: vec![], near_full: Default::default() }
+	}
+
+	/// Updates the channels with the ones open in a relay chain block, inbound ones indexed by the sender and
+	/// outbound ones by the recipient, returns the channels that have just become close to full
+	pub fn on_channels(
+		&mut self,
+		block_number: BlockNumber,
+		inbound: &BTreeMap<u32, SubxtHrmpChannel>,
+		outbound: &BTreeMap<u32, SubxtHrmpChannel>,
+	) -> Vec<HrmpChannelWarning> {
+		let channels: Vec<HrmpChannelState> = inbound
+			.iter()
+			.map(|(sender, channel)| HrmpChannelState::new(*sender, self.para_id, channel))
+			.chain(
+				outbound
+					.iter()
+					.map(|(recipient, channel)| HrmpChannelState::new(self.para_id, *recipient, channel)),
+			)
+			.collect();
+		self.closed = self
+			.channels
+			.iter()
+			.map(|channel| (channel.sender, channel.recipient))
+			.filter(|key| !channels.iter().any(|channel| (channel.sender, channel.recipient) == *key))
+			.collect();
+		self.channels = channels;
+
+		let mut warnings = vec![];
+		let mut near_full = HashSet::new();
+		for channel in self.channels.iter().filter(|channel| channel.fill_ratio() >= self.threshold) {
+			let key = (channel.sender, channel.recipient);
+			if !self.near_full.contains(&key) {
+				warnings.push(HrmpChannelWarning { block_number, channel: channel.clone(), threshold: self.threshold });
+			}
+			near_full.insert(key);
+		}
+		self.near_full = near_full;
+
+		warnings
+	}
+
+	/// Open channels in the last relay chain block
+	pub fn channels(&self) -> &[HrmpChannelState] {
+		&self.channels
+	}
+
+	/// Senders and recipients of the channels closed in the last relay chain block
+	pub fn closed_channels(&self) -> &[(u32, u32)] {
+		&self.closed
+	}
+}
+
+impl Display for HrmpChannelMonitor {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- HRMP channels ---".to_string().bold().blue())?;
+		writeln!(
+			f,
+			"Open channels: {}, close to full: {}",
+			self.channels.len(),
+			self.near_full.len().to_string().bright_red()
+		)?;
+		for channel in &self.channels {
+			writeln!(f, "\t{}", channel)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn channel(msg_count: u32, total_size: u32) -> SubxtHrmpChannel {
+		SubxtHrmpChannel { max_capacity: 10, max_total_size: 1000, msg_count, total_size, ..Default::default() }
+	}
+
+	#[test]
+	fn test_calculates_fill_ratio() {
+		assert_eq!(HrmpChannelState::new(100, 200, &channel(5, 100)).fill_ratio(), 0.5);
+		assert_eq!(HrmpChannelState::new(100, 200, &channel(1, 900)).fill_ratio(), 0.9);
+		assert_eq!(HrmpChannelState::new(100, 200, &SubxtHrmpChannel::default()).fill_ratio(), 0.0);
+	}
+
+	#[test]
+	fn test_warns_once_about_near_full_channels() {
+		let mut monitor = HrmpChannelMonitor::new(100, 0.8);
+		let inbound = BTreeMap::from([(200, channel(9, 0)), (300, channel(1, 0))]);
+		let outbound = BTreeMap::from([(400, channel(0, 800))]);
+
+		let warnings = monitor.on_channels(10, &inbound, &outbound);
+		assert_eq!(monitor.channels().len(), 3);
+		assert_eq!(
+			warnings
+				.iter()
+				.map(|w| (w.channel.sender, w.channel.recipient))
+				.collect::<Vec<_>>(),
+			vec![(200, 100), (100, 400)]
+		);
+		assert!(monitor.on_channels(11, &inbound, &outbound).is_empty());
+
+		// Warned again after draining
+		monitor.on_channels(12, &Default::default(), &outbound);
+		let warnings = monitor.on_channels(13, &inbound, &outbound);
+		assert_eq!(warnings.len(), 1);
+		assert_eq!(warnings[0].channel.sender, 200);
+	}
+
+	#[test]
+	fn test_reports_closed_channels() {
+		let mut monitor = HrmpChannelMonitor::new(100, 0.8);
+		let inbound = BTreeMap::from([(200, channel(1, 0)), (300, channel(1, 0))]);
+		let outbound = BTreeMap::from([(400, channel(0, 100))]);
+
+		monitor.on_channels(10, &inbound, &outbound);
+		assert!(monitor.closed_channels().is_empty());
+
+		monitor.on_channels(11, &BTreeMap::from([(300, channel(1, 0))]), &Default::default());
+		assert_eq!(monitor.closed_channels(), &[(200, 100), (100, 400)]);
+		monitor.on_channels(12, &BTreeMap::from([(300, channel(1, 0))]), &Default::default());
+		assert!(monitor.closed_channels().is_empty());
+	}
+}
@@ -0,0 +1,106 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Incidents observed by the tracer: skipped slots and disputes of the traced parachains, finality stalls of the
+//! relay chain and stale nodes of the chain in telemetry. RPC disconnects are recorded by the chain subscription.
+
+use crate::types::{ParachainConsensusEvent, ParachainProgressUpdate};
+use log::error;
+use polkadot_introspector_essentials::{
+	chain_events::SubxtDisputeResult,
+	incidents::{Incident, IncidentSource, Incidents, Severity},
+	telemetry_feed::{FeedNodeId, TelemetryFeed},
+	telemetry_subscription::TelemetryEvent,
+};
+use polkadot_introspector_priority_channel::Receiver;
+use std::{collections::HashMap, path::Path};
+
+/// Records skipped slots and disputes of a parachain block
+pub(crate) fn record_progress(incidents: &Incidents, progress: &ParachainProgressUpdate) {
+	for event in &progress.events {
+		let incident = match event {
+			ParachainConsensusEvent::SkippedSlot(reason) => Incident::new(
+				progress.timestamp,
+				Severity::Info,
+				IncidentSource::SkippedSlot,
+				Some(progress.block_number),
+				format!("Parachain {} skipped a slot: {}", progress.para_names.display(progress.para_id), reason),
+			),
+			ParachainConsensusEvent::Disputed(dispute) => Incident::new(
+				progress.timestamp,
+				match dispute.outcome {
+					SubxtDisputeResult::Invalid => Severity::Critical,
+					SubxtDisputeResult::Valid | SubxtDisputeResult::TimedOut => Severity::Warning,
+				},
+				IncidentSource::Dispute,
+				Some(progress.block_number),
+				format!(
+					"Dispute of candidate {:?} of parachain {} concluded {:?}, {} voted valid, {} voted invalid",
+					dispute.candidate,
+					progress.para_names.display(progress.para_id),
+					dispute.outcome,
+					dispute.voted_for,
+					dispute.voted_against
+				),
+			),
+			_ => continue,
+		};
+		incidents.record(incident);
+	}
+}
+
+/// Records stale nodes and lost connections of a telemetry feed
+pub(crate) async fn watch_telemetry(update: Receiver<TelemetryEvent>, incidents: Incidents) {
+	// Feed ids are assigned again after reconnection
+	let mut names: HashMap<FeedNodeId, String> = HashMap::new();
+
+	while let Ok(event) = update.recv().await {
+		match event {
+			TelemetryEvent::NewMessage(TelemetryFeed::AddedNode(node)) => {
+				names.insert(node.node_id, node.details.name);
+			},
+			TelemetryEvent::NewMessage(TelemetryFeed::RemovedNode(node)) => {
+				names.remove(&node.node_id);
+			},
+			TelemetryEvent::NewMessage(TelemetryFeed::StaleNode(node)) => incidents.record(Incident::now(
+				Severity::Warning,
+				IncidentSource::StaleNode,
+				format!(
+					"Node {} stopped reporting to telemetry",
+					names.get(&node.node_id).map_or("unknown", |v| v.as_str())
+				),
+			)),
+			TelemetryEvent::Gap { from, to } => {
+				names.clear();
+				incidents.record(Incident::new(
+					from,
+					Severity::Warning,
+					IncidentSource::TelemetryDisconnect,
+					None,
+					format!("Telemetry connection was lost for {}s", to.saturating_sub(from) / 1000),
+				))
+			},
+			TelemetryEvent::NewMessage(_) => {},
+		}
+	}
+}
+
+/// Writes the timeline recorded so far as Markdown
+pub(crate) fn write_markdown(incidents: &Incidents, path: &Path, min_severity: Severity) {
+	if let Err(e) = std::fs::write(path, incidents.timeline().filter(min_severity).to_markdown()) {
+		error!("Cannot write incidents to {}: {:?}", path.display(), e);
+	}
+}
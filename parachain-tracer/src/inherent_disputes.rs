@@ -0,0 +1,155 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! This module tracks the dispute statement sets included in the paras inherent of relay chain blocks.
+//!
+//! Unlike dispute events, which are emitted once per dispute, the statement sets are carried by every block that
+//! imports new votes, so they show the load disputes place on the block space. Sets of sessions older than
+//! the current one are counted as stale: such votes arrive late and take the space of backed candidates.
+
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use parity_scale_codec::Encode;
+use polkadot_introspector_essentials::{api::subxt_wrapper::InherentData, types::BlockNumber};
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Display, Formatter},
+};
+
+/// Dispute statement sets included in the paras inherent of a relay chain block
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InherentDisputesUpdate {
+	/// Relay chain block number
+	pub block_number: BlockNumber,
+	/// Number of dispute statement sets
+	pub sets: u32,
+	/// Number of statements in all sets
+	pub statements: u32,
+	/// Encoded size of the sets in bytes
+	pub size: usize,
+	/// Number of sets referring to sessions older than the current one
+	pub stale_sets: u32,
+}
+
+/// Aggregates the dispute statement sets over the processed relay chain blocks
+#[derive(Default)]
+pub struct InherentDisputesTracker {
+	/// Number of blocks processed
+	blocks_count: u32,
+	/// Number of blocks with at least one dispute statement set
+	blocks_with_disputes: u32,
+	/// Total number of dispute statement sets
+	sets_count: u32,
+	/// Total number of statements
+	statements_count: u32,
+	/// Total number of sets referring to past sessions
+	stale_sets_count: u32,
+	/// The largest number of sets in a single block
+	max_sets: u32,
+	/// Number of sets by the session they refer to
+	sets_by_session: BTreeMap<u32, u32>,
+}
+
+impl InherentDisputesTracker {
+	/// Processes the paras inherent of a relay chain block, sessions before `current_session` are counted as stale
+	pub fn on_block(
+		&mut self,
+		block_number: BlockNumber,
+		inherent: &InherentData,
+		current_session: Option<u32>,
+	) -> InherentDisputesUpdate {
+		let mut update =
+			InherentDisputesUpdate { block_number, size: inherent.disputes.encoded_size(), ..Default::default() };
+		for set in inherent.disputes.iter() {
+			update.sets += 1;
+			update.statements += set.statements.len() as u32;
+			if current_session.is_some_and(|current| set.session < current) {
+				update.stale_sets += 1;
+			}
+			*self.sets_by_session.entry(set.session).or_default() += 1;
+		}
+
+		self.blocks_count += 1;
+		if update.sets > 0 {
+			self.blocks_with_disputes += 1;
+		}
+		self.sets_count += update.sets;
+		self.statements_count += update.statements;
+		self.stale_sets_count += update.stale_sets;
+		self.max_sets = self.max_sets.max(update.sets);
+
+		update
+	}
+}
+
+impl Display for InherentDisputesTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- Disputes in the paras inherent ---".to_string().bold().blue())?;
+		if self.blocks_with_disputes == 0 {
+			return writeln!(f, "No dispute statement sets in {} blocks", self.blocks_count)
+		}
+		writeln!(
+			f,
+			"Blocks with disputes: {} of {}, {} sets on average, {} at most",
+			self.blocks_with_disputes.to_string().bold(),
+			self.blocks_count,
+			format!("{:.2}", self.sets_count as f64 / self.blocks_with_disputes as f64).bold(),
+			self.max_sets
+		)?;
+		writeln!(f, "Statements: {}", self.statements_count.to_string().bold())?;
+		writeln!(f, "Stale sets: {} of {}", self.stale_sets_count.to_string().bright_red(), self.sets_count)?;
+		writeln!(
+			f,
+			"Sets by session: {}",
+			self.sets_by_session
+				.iter()
+				.map(|(session, count)| format!("{}: {}", session, count))
+				.collect::<Vec<_>>()
+				.join(", ")
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::{create_dispute_statement_set, create_inherent_data};
+
+	#[test]
+	fn test_counts_dispute_statement_sets() {
+		let mut tracker = InherentDisputesTracker::default();
+		let mut inherent = create_inherent_data(100);
+		let mut stale = create_dispute_statement_set();
+		stale.session = 1;
+		let mut current = create_dispute_statement_set();
+		current.session = 2;
+		inherent.disputes = vec![stale, current];
+
+		let update = tracker.on_block(42, &inherent, Some(2));
+		assert_eq!(update.sets, 2);
+		assert_eq!(update.statements, 6);
+		assert_eq!(update.stale_sets, 1);
+		assert!(update.size > 0);
+
+		inherent.disputes.clear();
+		let update = tracker.on_block(43, &inherent, Some(2));
+		assert_eq!(update.sets, 0);
+		assert_eq!(update.stale_sets, 0);
+		assert_eq!(tracker.blocks_count, 2);
+		assert_eq!(tracker.blocks_with_disputes, 1);
+		assert_eq!(tracker.max_sets, 2);
+		assert_eq!(tracker.sets_by_session, BTreeMap::from([(1, 1), (2, 1)]));
+	}
+}
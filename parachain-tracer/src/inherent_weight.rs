@@ -0,0 +1,247 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module tracks how the paras inherent of relay chain blocks is shared between its components.
+//!
+//! The runtime reports only the total weight of the inherent, so the components are compared by their
+//! encoded size, which the weight of each of them grows with.
+
+use crate::explorer::Explorer;
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use parity_scale_codec::Encode;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::InherentData,
+	types::{BlockNumber, ExtrinsicWeight},
+};
+use std::fmt::{self, Display, Formatter};
+
+/// Components of the paras inherent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InherentComponent {
+	Bitfields,
+	BackedCandidates,
+	Disputes,
+}
+
+impl InherentComponent {
+	pub const ALL: [InherentComponent; 3] =
+		[InherentComponent::Bitfields, InherentComponent::BackedCandidates, InherentComponent::Disputes];
+
+	/// Used as a metric label
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			InherentComponent::Bitfields => "bitfields",
+			InherentComponent::BackedCandidates => "backed_candidates",
+			InherentComponent::Disputes => "disputes",
+		}
+	}
+}
+
+impl Display for InherentComponent {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			InherentComponent::Bitfields => write!(f, "bitfields"),
+			InherentComponent::BackedCandidates => write!(f, "backed candidates"),
+			InherentComponent::Disputes => write!(f, "disputes"),
+		}
+	}
+}
+
+/// Composition of the paras inherent observed in a relay chain block
+#[derive(Clone, Debug, PartialEq)]
+pub struct InherentWeightUpdate {
+	/// Relay chain block number
+	pub block_number: BlockNumber,
+	/// Actual weight of the inherent, if reported by the runtime
+	pub weight: Option<ExtrinsicWeight>,
+	/// Encoded size of the components in bytes, in the order of `InherentComponent::ALL`
+	pub sizes: [usize; 3],
+	/// Number of free cores with scheduled parachains left without a backed candidate
+	pub unused_cores: u32,
+	/// Disputes took the largest share of the inherent while scheduled cores were left unused
+	pub is_crowded_out: bool,
+	pub explorer: Explorer,
+}
+
+impl InherentWeightUpdate {
+	/// Returns the percentage of the inherent taken by the component
+	pub fn share(&self, component: InherentComponent) -> f64 {
+		let total: usize = self.sizes.iter().sum();
+		if total == 0 {
+			return 0.0
+		}
+
+		self.size(component) as f64 * 100.0 / total as f64
+	}
+
+	/// Returns the component with the largest share, the first one of equal
+	pub fn dominant(&self) -> InherentComponent {
+		InherentComponent::ALL
+			.into_iter()
+			.reduce(|a, b| if self.size(b) > self.size(a) { b } else { a })
+			.expect("not empty; qed")
+	}
+
+	fn size(&self, component: InherentComponent) -> usize {
+		self.sizes[InherentComponent::ALL
+			.iter()
+			.position(|v| *v == component)
+			.expect("all components; qed")]
+	}
+}
+
+/// Tracks the composition of the paras inherent and detects disputes crowding out backed candidates
+#[derive(Default)]
+pub struct InherentWeightTracker {
+	/// Number of blocks processed
+	blocks_count: u32,
+	/// Number of blocks by the component with the largest share, in the order of `InherentComponent::ALL`
+	dominant_counts: [u32; 3],
+	/// Average share of the components (calculated using `CMA`: cumulative moving average)
+	avg_shares: [f64; 3],
+	/// Number of blocks where disputes crowded out backed candidates
+	crowded_out_count: u32,
+	explorer: Explorer,
+}
+
+impl InherentWeightTracker {
+	/// Links the crowded out blocks to the block explorer
+	pub fn with_explorer(mut self, explorer: Explorer) -> Self {
+		self.explorer = explorer;
+		self
+	}
+
+	/// Processes the paras inherent of a relay chain block
+	pub fn on_block(
+		&mut self,
+		block_number: BlockNumber,
+		inherent: &InherentData,
+		weight: Option<ExtrinsicWeight>,
+		unused_cores: u32,
+	) -> InherentWeightUpdate {
+		let sizes = [
+			inherent.bitfields.encoded_size(),
+			inherent.backed_candidates.encoded_size(),
+			inherent.disputes.encoded_size(),
+		];
+		let mut update = InherentWeightUpdate {
+			block_number,
+			weight,
+			sizes,
+			unused_cores,
+			is_crowded_out: false,
+			explorer: self.explorer.clone(),
+		};
+		let dominant = update.dominant();
+		update.is_crowded_out =
+			!inherent.disputes.is_empty() && dominant == InherentComponent::Disputes && unused_cores > 0;
+
+		self.blocks_count += 1;
+		for (idx, component) in InherentComponent::ALL.into_iter().enumerate() {
+			if component == dominant {
+				self.dominant_counts[idx] += 1;
+			}
+			self.avg_shares[idx] += (update.share(component) - self.avg_shares[idx]) / self.blocks_count as f64;
+		}
+		if update.is_crowded_out {
+			self.crowded_out_count += 1;
+		}
+
+		update
+	}
+}
+
+impl Display for InherentWeightUpdate {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(
+			f,
+			"[#{}] {} disputes take {}% of the paras inherent, {} scheduled cores left without backed candidates",
+			self.block_number,
+			"DISPUTES CROWDING OUT CANDIDATES".to_string().bold().red(),
+			format!("{:.1}", self.share(InherentComponent::Disputes)).bright_red(),
+			self.unused_cores
+		)?;
+		if let Some(weight) = self.weight {
+			writeln!(f, "\t⚖️ Inherent weight: ref time {}, proof size {}", weight.ref_time, weight.proof_size)?;
+		}
+		self.explorer.write_block_link(f, self.block_number)
+	}
+}
+
+impl Display for InherentWeightTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- Relay chain paras inherent composition ---".to_string().bold().blue())?;
+		if self.blocks_count == 0 {
+			return writeln!(f, "No blocks processed")
+		}
+		for (idx, component) in InherentComponent::ALL.into_iter().enumerate() {
+			writeln!(
+				f,
+				"{}: {}% of the inherent on average, the largest share in {} blocks",
+				component,
+				format!("{:.2}", self.avg_shares[idx]).bold(),
+				self.dominant_counts[idx]
+			)?;
+		}
+		writeln!(
+			f,
+			"Disputes crowding out candidates: {} of {} blocks",
+			self.crowded_out_count.to_string().bright_red(),
+			self.blocks_count
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::create_inherent_data;
+
+	#[test]
+	fn test_detects_disputes_crowding_out_candidates() {
+		let mut tracker = InherentWeightTracker::default();
+		let mut inherent = create_inherent_data(100);
+		inherent.backed_candidates.clear();
+		inherent.bitfields.clear();
+
+		let update = tracker.on_block(42, &inherent, None, 0);
+		assert_eq!(update.dominant(), InherentComponent::Disputes);
+		assert!(!update.is_crowded_out);
+
+		let update = tracker.on_block(43, &inherent, None, 2);
+		assert!(update.is_crowded_out);
+		assert_eq!(tracker.crowded_out_count, 1);
+		assert_eq!(tracker.dominant_counts, [0, 0, 2]);
+	}
+
+	#[test]
+	fn test_calculates_shares() {
+		let update = InherentWeightUpdate {
+			block_number: 42,
+			weight: None,
+			sizes: [100, 300, 0],
+			unused_cores: 0,
+			is_crowded_out: false,
+			explorer: Default::default(),
+		};
+
+		assert_eq!(update.share(InherentComponent::Bitfields), 25.0);
+		assert_eq!(update.share(InherentComponent::BackedCandidates), 75.0);
+		assert_eq!(update.share(InherentComponent::Disputes), 0.0);
+		assert_eq!(update.dominant(), InherentComponent::BackedCandidates);
+	}
+}
@@ -21,52 +21,144 @@
 //! - backing and availability health metrics for all parachains
 //! - TODO: backing group information - validator addresses
 //! - TODO: parachain block times measured in relay chain blocks
-//! - TODO: parachain XCM throughput
+//! - parachain XCM throughput
 //! - TODO: parachain code size
 //!
 //! The CLI interface is useful for debugging/diagnosing issues with the parachain block pipeline.
 //! Soon: CI integration also supported via Prometheus metrics exporting.
 
+use alert_manager::{AlertKey, AlertManager};
+use alerts::{AlertWebhook, StallAlert};
+use availability_culprits::{AvailabilityCulpritsReport, AvailabilityCulpritsTracker};
+use baseline::BaselineOptions;
+use bitfield_participation::BitfieldParticipationTracker;
+use candidate_timeline::CandidateTimelineFile;
 use clap::{error::ErrorKind, CommandFactory, Parser};
+use color_eyre::eyre::eyre;
 use colored::Colorize;
+use core_occupancy::CoreOccupancyTracker;
 use crossterm::style::Stylize;
+use dispute_participation::DisputeParticipationTracker;
+use explorer::{Explorer, KnownRelayChain};
 use futures::{future, stream::FuturesUnordered, StreamExt};
+use hooks::{HookAction, HookOutput, ScriptHooks};
+use inherent_disputes::InherentDisputesTracker;
+use inherent_weight::InherentWeightTracker;
 use itertools::Itertools;
 use log::{error, info, warn};
+use maintenance::MaintenanceOptions;
+use notify::{Notifier, NotifyOptions};
+use on_demand::{OnDemandOrderEvent, OnDemandOrdersTracker};
+use para_config::ParaConfig;
+use para_names::ParaNames;
+use para_nodes::{ParaNode, ParaNodeOptions};
+use parachain_watcher::ParachainWatcher;
 use polkadot_introspector_essentials::{
-	api::subxt_wrapper::RequestExecutor,
+	anomaly::{self, AnomalyDetectors},
+	api::{
+		api_client::RpcClientFactory,
+		subxt_wrapper::{DynamicHostConfiguration, ExecutorContext, RequestExecutor},
+	},
 	chain_head_subscription::ChainHeadSubscription,
 	chain_subscription::ChainSubscriptionEvent,
+	chaos::{ChaosInjector, Fault},
 	collector,
-	collector::{Collector, CollectorOptions, CollectorStorageApi, CollectorUpdateEvent, TerminationReason},
+	collector::{
+		Collector, CollectorOptions, CollectorStorageApi, CollectorSubscribeMode, CollectorUpdateEvent,
+		TerminationReason,
+	},
+	constants::MINIMUM_BACKING_VOTES,
 	consumer::{EventConsumerInit, EventStream},
+	decode_diagnostics::DecodeFailuresCounter,
+	event_decoding::EventDecodingCounter,
 	historical_subscription::HistoricalSubscription,
+	incidents::{FinalityStallDetector, Incident, IncidentSource, Incidents, Severity},
 	init,
-	types::BlockNumber,
+	output::{OutputFormat, OutputOptions, OutputWriter},
+	rpc_budget::RpcBudgetCounter,
+	runtime_support::{check_runtime_support, RuntimeSupportOptions},
+	sequence::SequenceGenerator,
+	telemetry_subscription::{TelemetryEvent, TelemetrySubscription},
+	types::{AccountId32, BlockNumber, BlockRef, Timestamp, H256},
 	utils::RetryOptions,
 };
 use polkadot_introspector_priority_channel::{channel_with_capacities, Receiver, Sender};
-use prometheus::{Metrics, ParachainTracerPrometheusOptions};
-use stats::ParachainStats;
-use std::{collections::HashMap, default::Default, ops::DerefMut};
-use tokio::sync::broadcast::Sender as BroadcastSender;
-use tracker::SubxtTracker;
-use tracker_rpc::ParachainTrackerRpc;
+use prometheus::{Metrics, ParachainTracerPrometheusOptions, PrometheusMetrics};
+use reorgs::ReorgDetector;
+use replay::ReplayOptions;
+use runtime_upgrades::RuntimeUpgradeTracker;
+use sinks::{clickhouse::ClickHouseSink, parquet::ParquetSink, protobuf::ProtobufSink, wal::WalSink, OutputSink};
+use stats::StatsWindow;
+use std::{
+	collections::HashMap, default::Default, fmt::Display, ops::DerefMut, path::PathBuf, str::FromStr, sync::Arc,
+};
+use summary_file::{SummaryFile, SummaryFormat};
+use system_parachains::SystemParachainNode;
+use tokio::sync::{broadcast::Sender as BroadcastSender, watch};
 use tracker_storage::TrackerStorage;
+use tui::{LogBuffer, TuiHandle};
+use utils::assigned_cores;
+use validator_history::{fetch_session_keys, ValidatorHistory, ValidatorHistoryOptions};
+use wasm_plugins::WasmPlugins;
+use watch_expressions::WatchExpressions;
 
+mod alert_manager;
+mod alerts;
+mod approval_noshows;
+mod availability_culprits;
+mod baseline;
+mod bitfield_participation;
+mod candidate_timeline;
+mod collators;
+mod core_occupancy;
+mod dashboard;
+mod dispute_participation;
+mod explorer;
+mod group_rotation;
+mod head_divergence;
+mod hooks;
+mod hrmp_channels;
+mod incident_sources;
+mod inherent_disputes;
+mod inherent_weight;
+mod maintenance;
 mod message_queues_tracker;
+mod notify;
+mod observation;
+mod on_demand;
+mod para_config;
+mod para_heads;
+mod para_names;
+mod para_nodes;
 mod parachain_block_info;
+mod parachain_watcher;
+mod pov;
+mod progress_record;
 mod prometheus;
+mod reorgs;
+mod replay;
+mod runtime_upgrades;
+mod schema;
+mod sinks;
 mod stats;
+mod summary_file;
+mod system_parachains;
 mod tracker;
 mod tracker_rpc;
 mod tracker_storage;
+mod tui;
 mod types;
 mod utils;
+mod validator_history;
+mod wasm_plugins;
+mod watch_expressions;
 
 #[cfg(test)]
 mod test_utils;
 
+/// Exit code of a run whose RPC error budget was exceeded, other failures exit with 1
+const RPC_ERROR_BUDGET_EXIT_CODE: i32 = 3;
+
 #[derive(Clone, Debug, Parser, Default)]
 #[clap(rename_all = "kebab-case")]
 pub(crate) enum ParachainTracerMode {
@@ -75,6 +167,23 @@ pub(crate) enum ParachainTracerMode {
 	Cli,
 	/// Prometheus endpoint mode.
 	Prometheus(ParachainTracerPrometheusOptions),
+	/// JSON mode, one object per tracked parachain block on stdout (the `progress` schema), everything else is logged.
+	/// Records are JSON lines unless another `--output` format is set, which also implies this mode.
+	Json,
+	/// Print the Prometheus metrics exported in Prometheus mode with the given options and exit.
+	ListMetrics(ParachainTracerPrometheusOptions),
+	/// Print a Grafana dashboard for the metrics exported in Prometheus mode with the given options and exit.
+	/// The series of the parachain metrics are filtered by `--para-id`, if set.
+	GenerateDashboard(ParachainTracerPrometheusOptions),
+	/// Trace blocks between `--from` and `--to` offline from a relay chain node database instead of RPC, printing
+	/// the results as in CLI mode.
+	Replay(ReplayOptions),
+	/// Interactive terminal interface with a live table of the traced parachains, best used with `--all`, and a
+	/// detail pane of the selected one. Everything else is logged and written to stderr when the interface is closed.
+	Tui,
+	/// Replay blocks between `--from` and `--to` and print the bitfields, backing votes and dispute statements of a
+	/// validator per session as a table or CSV, for due diligence on nominee validators.
+	ValidatorHistory(ValidatorHistoryOptions),
 }
 
 #[derive(Clone, Debug, Parser)]
@@ -84,56 +193,380 @@ pub(crate) struct ParachainTracerOptions {
 	#[clap(name = "ws", long, value_delimiter = ',', default_value = "wss://rpc.polkadot.io:443")]
 	pub node: String,
 	/// Parachain id.
-	#[clap(long, conflicts_with = "all")]
+	#[clap(long, conflicts_with = "all", global = true)]
 	para_id: Vec<u32>,
 	#[clap(long, conflicts_with = "para_id", default_value = "false")]
 	all: bool,
+	/// Trace whichever parachains are assigned to these cores over time, e.g. `--core 0,3`
+	#[clap(long = "core", value_delimiter = ',', conflicts_with_all = ["all", "para_id"])]
+	cores: Vec<u32>,
+	/// TOML file listing the parachains to trace with their names and alert thresholds, reloaded when changed
+	/// to add and remove trackers without a restart
+	#[clap(long, conflicts_with_all = ["all", "para_id", "cores"])]
+	para_config: Option<PathBuf>,
+	/// Relay chain to take the default block explorer from, guessed from the node URL by default
+	#[clap(long, value_enum)]
+	relay_chain: Option<KnownRelayChain>,
+	/// JSON file with parachain names, either `{"2004": "Moonbeam"}` or chain registry entries
+	/// (`[{"paraId": 2004, "text": "Moonbeam"}]`), overrides the names read from the parachain nodes
+	#[clap(long)]
+	para_names: Option<PathBuf>,
+	/// Block explorer URL template for the links in alerts, where `{block}` is replaced by a block number or hash,
+	/// e.g. `https://polkadot.statescan.io/#/blocks/{block}`. Subscan is used for the known relay chains by default
+	#[clap(long)]
+	explorer_url: Option<String>,
+	/// Do not print block explorer links in alerts
+	#[clap(long, conflicts_with = "explorer_url")]
+	no_explorer_links: bool,
 	/// Run for a number of blocks then stop.
 	#[clap(name = "blocks", long)]
 	block_count: Option<u32>,
+	/// Write the statistics of the traced parachains to this file when the tracer stops, for regression
+	/// comparison in CI. With `--stats-window` the statistics of the last window are written
+	#[clap(long)]
+	summary_file: Option<PathBuf>,
+	/// Format of the summary file, JSON is described by the `summary` schema
+	#[clap(long, value_enum, default_value = "json", requires = "summary_file")]
+	summary_format: SummaryFormat,
+	/// Write the timeline of every candidate of the traced parachains to this file as JSON lines (the
+	/// `candidate-timeline` schema): the relay chain blocks and times it was backed, available, included, disputed
+	/// and finalized at
+	#[clap(long)]
+	candidate_timeline_file: Option<PathBuf>,
+	/// Alert when a parachain has not backed a candidate for this number of relay chain blocks
+	#[clap(long)]
+	alert_no_backed: Option<u32>,
+	/// Alert when a parachain has not included a candidate for this number of relay chain blocks
+	#[clap(long)]
+	alert_no_included: Option<u32>,
+	/// POST alerts as JSON to this URL, e.g. a PagerDuty or Alertmanager webhook
+	#[clap(long)]
+	alert_webhook: Option<String>,
+	/// Number of relay chain blocks a condition has to stay clear before its alert is resolved. Identical alerts
+	/// within this period are posted once, and conditions clearing and firing again are collapsed into one alert
+	#[clap(long, default_value = "10")]
+	alert_cooldown: u32,
+	#[clap(flatten)]
+	notify: NotifyOptions,
+	#[clap(flatten)]
+	maintenance: MaintenanceOptions,
+	#[clap(flatten)]
+	para_nodes: ParaNodeOptions,
+	/// Web-Socket URLs of other relay chain nodes to compare the heads of the parachains with the traced node
+	#[clap(long, value_delimiter = ',')]
+	compare_ws: Vec<String>,
+	/// Number of relay chain blocks the head of a parachain on a `--compare-ws` node may differ from the traced node
+	/// before it's reported
+	#[clap(long, default_value = "5")]
+	head_divergence_blocks: u32,
+	/// Attribute candidates to the collators that produced them and list the worst-performing collators in the
+	/// summary: the fewest backed candidates included, then the slowest inclusion
+	#[clap(long)]
+	show_collators: bool,
+	/// The number of worst-performing collators to list
+	#[clap(long, default_value = "5", requires = "show_collators")]
+	collators_top: usize,
+	/// Show the validator group assigned to the core of the parachain at each block in the CLI mode, log its rotations
+	/// and missed backings otherwise, and the backing success rate by group index in the summary
+	#[clap(long)]
+	show_group_rotations: bool,
+	/// Follow the inbound and outbound HRMP channels of the parachain: their capacity, queued messages and deposits,
+	/// warning when a channel is close to full
+	#[clap(long)]
+	track_hrmp: bool,
+	/// Share of the capacity of an HRMP channel, by message count or total size, after that it is close to full
+	#[clap(long, default_value = "0.8", requires = "track_hrmp")]
+	hrmp_fill_warning: f64,
+	/// Exit with code 3 when more than this share (0..1) of the RPC calls failed, so CI can tell an unhealthy
+	/// monitoring run from an unhealthy chain
+	#[clap(long)]
+	max_rpc_error_ratio: Option<f64>,
+	/// Exit with code 3 when data could not be fetched for more than this number of blocks
+	#[clap(long)]
+	max_data_gaps: Option<u64>,
 	/// The number of last blocks with missing slots to display
 	#[clap(long = "last-skipped-slot-blocks", default_value = "10")]
 	pub last_skipped_slot_blocks: usize,
+	/// Reset the parachain statistics after this number of relay chain blocks (`600`)
+	/// or period of time (`30m`, `6h`, `1d`), printing the ones of the elapsed window
+	#[clap(long)]
+	stats_window: Option<StatsWindow>,
+	/// Print a compact summary of every parachain (skipped slots, disputes, average inclusion time) when a session
+	/// changes, exported as the `pc_session_summary` gauges labeled with the session index in Prometheus mode
+	#[clap(long)]
+	per_session_summary: bool,
+	/// Flag candidates backed on relay parents at least this number of blocks old, defaults to the oldest relay
+	/// parent allowed by the host configuration
+	#[clap(long)]
+	max_relay_parent_age: Option<u32>,
+	/// Learn per-parachain baselines of availability time and signed bitfields and report only significant
+	/// deviations from them instead of the fixed thresholds
+	#[clap(long, default_value_t = false)]
+	adaptive_thresholds: bool,
+	/// Number of samples to learn the baselines from before they replace the fixed thresholds
+	#[clap(long, default_value = "100", requires = "adaptive_thresholds")]
+	baseline_warmup: u32,
+	/// Weight of a new sample in the moving averages of the baselines
+	#[clap(long, default_value = "0.05", requires = "adaptive_thresholds")]
+	baseline_alpha: f64,
+	/// Number of standard deviations from the baseline after which a sample is reported
+	#[clap(long, default_value = "3", requires = "adaptive_thresholds")]
+	baseline_sigma: f64,
 	/// Evict a stalled parachain after this amount of skipped blocks
 	#[clap(long, default_value = "256")]
 	max_parachain_stall: u32,
+	/// The number of relay chain blocks to calculate the rolling average of bitfield participation
+	#[clap(long, default_value = "20")]
+	bitfield_participation_window: usize,
+	/// Alert when bitfield participation drops by this amount of percentage points below the rolling average
+	#[clap(long, default_value = "10.0")]
+	bitfield_participation_drop: f64,
+	/// Report validators which consistently fail to set their availability bits
+	#[clap(long)]
+	show_availability_culprits: bool,
+	/// The number of relay chain blocks to attribute missing availability bits over
+	#[clap(long, default_value = "100")]
+	availability_culprits_window: u32,
+	/// The number of top offenders to report
+	#[clap(long, default_value = "10")]
+	availability_culprits_top: usize,
+	/// Report validators missing availability bits in at least this percentage of blocks
+	#[clap(long, default_value = "50.0")]
+	availability_culprits_threshold: f64,
+	/// Follow on-demand orders of all parachains from placing to inclusion of a candidate
+	#[clap(long)]
+	track_on_demand_orders: bool,
+	/// Monitor participation of the validator with this account (SS58) in disputes
+	#[clap(long)]
+	dispute_validator: Option<String>,
+	/// Alert if the monitored validator has not cast a dispute statement within this number of blocks
+	#[clap(long, default_value = "10")]
+	dispute_participation_blocks: u32,
+	/// Record anomalies from all sources (skipped slots, disputes, finality stalls, stale telemetry nodes, RPC
+	/// disconnects) and print their chronological timeline in the summaries
+	#[clap(long)]
+	incidents: bool,
+	/// Write the incidents timeline as Markdown to this file on exit and on `SIGUSR1`, enables recording of incidents
+	#[clap(long)]
+	incidents_markdown: Option<PathBuf>,
+	/// Only include incidents of at least this severity in the timeline
+	#[clap(long, value_enum, default_value = "info")]
+	incidents_min_severity: Severity,
+	/// Record a finality stall incident when finality lags this number of blocks behind the best block
+	#[clap(long, default_value = "10")]
+	finality_stall_blocks: u32,
+	/// Web-Socket URL of a telemetry backend to record stale nodes of the chain as incidents,
+	/// used with `--incidents` or `--incidents-markdown`
+	#[clap(long, requires = "telemetry_chain")]
+	telemetry_feed: Option<String>,
+	/// Name of the chain in the telemetry feed
+	#[clap(long)]
+	telemetry_chain: Option<String>,
+	/// Run these built-in anomaly detectors on the per-block observations, e.g. `--anomaly-detectors slow-block,idle-core`,
+	/// custom detectors are loaded with `--plugin-dir`
+	#[clap(long, value_delimiter = ',', value_parser = anomaly::BUILTIN_DETECTORS)]
+	anomaly_detectors: Vec<String>,
+	/// Rhai script run on every parachain progress and anomaly, can be repeated
+	#[clap(long = "hook")]
+	hooks: Vec<PathBuf>,
+	/// Export the value of an expression on every parachain progress as a gauge, e.g.
+	/// `--watch 'bitfield_ratio = current_availability_bits / max_availability_bits'`, can be repeated
+	#[clap(long = "watch", value_name = "NAME=EXPRESSION")]
+	watch_expressions: Vec<String>,
+	/// Read watch expressions from this file, one `name = expression` per line
+	#[clap(long)]
+	watch_file: Option<PathBuf>,
+	/// Load WASM plugins implementing output sinks and anomaly detectors from `*.wasm` files of this directory
+	#[clap(long)]
+	plugin_dir: Option<PathBuf>,
+	/// Write per-block observations (paras, cores, bitfields, candidates, votes) as Parquet files to this directory
+	#[clap(long)]
+	research_dump: Option<PathBuf>,
+	/// Insert per-block observations into ClickHouse using its HTTP interface at this URL
+	#[clap(long)]
+	clickhouse_url: Option<String>,
+	/// ClickHouse database to insert observations into
+	#[clap(long, default_value = "introspector")]
+	clickhouse_database: String,
+	/// The number of relay chain blocks to buffer before inserting them into ClickHouse
+	#[clap(long, default_value = "100")]
+	clickhouse_batch_size: usize,
+	/// Keep a write-ahead log of observations in this directory to retry delivery to ClickHouse after crashes
+	#[clap(long)]
+	wal_dir: Option<PathBuf>,
+	/// Append per-block observations to this file as length-delimited protobuf records (`proto/introspector.proto`)
+	#[clap(long)]
+	protobuf_output: Option<PathBuf>,
+	/// Print the JSON schema of an output (Write-ahead log observations, ClickHouse rows or JSON mode progress) and exit
+	#[clap(long, value_parser = schema::SCHEMAS.map(|schema| schema.name))]
+	schema: Option<String>,
 	/// Defines subscription mode
 	#[clap(flatten)]
 	collector_opts: CollectorOptions,
+	/// Drive all tracking by finalized blocks only, trading latency for numbers unaffected by forks
+	#[clap(long, conflicts_with = "subscribe_mode", conflicts_with = "historical")]
+	finalized_only: bool,
 	/// Run in historical mode to trace parachains between specific blocks instead of following live chain progress
 	#[clap(name = "historical", long, requires = "from", requires = "to", conflicts_with = "subscribe_mode")]
 	is_historical: bool,
-	/// First block in historical mode, should be less then `--to` and the chain's tip
-	#[clap(name = "from", long)]
-	from_block_number: Option<BlockNumber>,
+	/// First block in historical mode, should be less then `--to` and the chain's tip.
+	/// In live modes, blocks are backfilled from this one before following the chain, e.g. after a restart.
+	/// Either a block number or a block hash
+	#[clap(name = "from", long, global = true)]
+	from_block: Option<BlockRef>,
 	/// Last block in historical mode, should be greater then `--from` and less then the chain's tip
-	#[clap(name = "to", long)]
+	#[clap(name = "to", long, global = true)]
 	to_block_number: Option<BlockNumber>,
 	/// Mode of running - CLI/Prometheus. Default or no subcommand means `CLI` mode.
 	#[clap(subcommand)]
 	mode: Option<ParachainTracerMode>,
 	#[clap(flatten)]
+	output: OutputOptions,
+	#[clap(flatten)]
 	pub verbose: init::VerbosityOptions,
 	#[clap(flatten)]
 	pub retry: RetryOptions,
+	#[clap(flatten)]
+	pub runtime_support: RuntimeSupportOptions,
+	#[cfg(feature = "chaos")]
+	#[clap(flatten)]
+	pub chaos: polkadot_introspector_essentials::chaos::ChaosOptions,
 }
 
 #[derive(Clone)]
 pub(crate) struct ParachainTracer {
 	opts: ParachainTracerOptions,
 	retry: RetryOptions,
+	/// Shared by the executors of the tracer
+	context: ExecutorContext,
 	node: String,
 	metrics: Metrics,
+	/// Notified when the current summaries are requested by `SIGUSR1`
+	summary_tx: BroadcastSender<()>,
+	/// Collects statistics of the stopped trackers if requested
+	summary_file: Option<SummaryFile>,
+	/// Receives the timelines of the candidates if requested
+	candidate_timeline_file: Option<CandidateTimelineFile>,
+	/// Scriptable hooks if any
+	hooks: Option<ScriptHooks>,
+	/// Watch expressions if any
+	watch_expressions: Option<Arc<WatchExpressions>>,
+	/// Receives alerts if requested
+	alert_webhook: Option<AlertWebhook>,
+	/// Posts notifications to chat channels if requested
+	notifier: Option<Notifier>,
+	/// Prints the progress records in the JSON mode
+	output: Arc<OutputWriter>,
+	/// Receives the progress of the parachains in the TUI mode
+	tui: Option<TuiHandle>,
+	/// Keeps the log while the TUI takes the terminal
+	log_buffer: LogBuffer,
+	/// Receives the changes of the parachain config file if given
+	para_config: Option<watch::Receiver<ParaConfig>>,
+	/// Number of relay chain blocks between the validator group rotations, from the host configuration
+	group_rotation_frequency: Option<u32>,
+	/// Number of relay chain blocks after that an approval checker is a no-show, from the host configuration
+	no_show_slots: Option<u32>,
+	/// Minimum number of backing votes of a candidate, from the host configuration
+	minimum_backing_votes: u32,
+	/// Counts the chain data the collector failed to decode
+	decode_failures: DecodeFailuresCounter,
+	/// Counts the chain events the collector decoded and skipped
+	event_decoding: EventDecodingCounter,
+	/// Nodes of the parachains, the ones serving another parachain are left out
+	para_nodes: Vec<ParaNode>,
+	/// System parachains among the parachains with a node
+	system_parachains: Vec<SystemParachainNode>,
+	/// Timeline of the incidents, disabled unless requested
+	incidents: Incidents,
+	/// Links the blocks referenced in alerts to a block explorer if configured
+	explorer: Explorer,
+	/// Names of the parachains shown next to their ids
+	para_names: ParaNames,
 }
 
 impl ParachainTracer {
-	pub(crate) fn new(mut opts: ParachainTracerOptions) -> color_eyre::Result<Self> {
+	pub(crate) fn new(
+		mut opts: ParachainTracerOptions,
+		context: ExecutorContext,
+		incidents: Incidents,
+	) -> color_eyre::Result<Self> {
 		// This starts the both the storage and subxt APIs.
 		let node = opts.node.clone();
 		let retry = opts.retry.clone();
 		opts.mode = opts.mode.or(Some(ParachainTracerMode::Cli));
+		if opts.finalized_only {
+			opts.collector_opts.subscribe_mode = CollectorSubscribeMode::Finalized;
+		}
+		let relay_chain = opts.relay_chain.or_else(|| KnownRelayChain::from_url(&node));
+		let para_names = ParaNames::new(opts.para_names.as_deref())?;
+		let explorer_url = opts
+			.explorer_url
+			.clone()
+			.or_else(|| relay_chain.map(explorer::default_url_template));
+		let explorer = match explorer_url.filter(|_| !opts.no_explorer_links) {
+			Some(url_template) => {
+				if !url_template.contains(explorer::BLOCK_PLACEHOLDER) {
+					ParachainTracerOptions::command()
+						.error(ErrorKind::InvalidValue, "`--explorer-url` should contain `{block}`")
+						.exit()
+				}
+				Explorer::new(url_template)
+			},
+			None => Explorer::default(),
+		};
 
-		Ok(ParachainTracer { opts, node, metrics: Default::default(), retry })
+		let summary_file = opts
+			.summary_file
+			.clone()
+			.map(|path| SummaryFile::new(path, opts.summary_format, context.rpc_budget().clone()));
+		let candidate_timeline_file = opts
+			.candidate_timeline_file
+			.clone()
+			.map(CandidateTimelineFile::create)
+			.transpose()?;
+		let hooks = ScriptHooks::load(&opts.hooks)?;
+		let hooks = (!hooks.is_empty()).then_some(hooks);
+		let watch_expressions = WatchExpressions::load(&opts.watch_expressions, opts.watch_file.as_deref())?;
+		let watch_expressions = (!watch_expressions.is_empty()).then(|| Arc::new(watch_expressions));
+		let alert_webhook = opts.alert_webhook.as_deref().map(AlertWebhook::new).transpose()?;
+		let notifier = Notifier::new(&opts.notify, explorer.clone())?;
+		let output = Arc::new(OutputWriter::new(opts.output.format));
+
+		Ok(ParachainTracer {
+			opts,
+			node,
+			metrics: Default::default(),
+			retry,
+			context,
+			summary_tx: init::init_summary_signal(),
+			summary_file,
+			candidate_timeline_file,
+			hooks,
+			watch_expressions,
+			alert_webhook,
+			notifier,
+			output,
+			tui: None,
+			log_buffer: Default::default(),
+			para_config: None,
+			group_rotation_frequency: None,
+			no_show_slots: None,
+			minimum_backing_votes: MINIMUM_BACKING_VOTES,
+			decode_failures: Default::default(),
+			event_decoding: Default::default(),
+			para_nodes: vec![],
+			system_parachains: vec![],
+			incidents,
+			explorer,
+			para_names,
+		})
+	}
+
+	/// Keeps the log in the given buffer while the TUI takes the terminal
+	pub(crate) fn with_log_buffer(mut self, log_buffer: LogBuffer) -> Self {
+		self.log_buffer = log_buffer;
+		self
 	}
 
 	/// Spawn the UI and subxt tasks and return their futures.
@@ -148,29 +581,88 @@ impl ParachainTracer {
 			self.metrics = prometheus::run_prometheus_endpoint(prometheus_opts).await?;
 		}
 
-		let mut collector =
-			Collector::new(self.opts.node.as_str(), self.opts.collector_opts.clone(), self.retry.clone());
+		let mut collector = Collector::new(
+			self.opts.node.as_str(),
+			self.opts.collector_opts.clone(),
+			self.retry.clone(),
+			self.context.clone(),
+		)?
+		.with_incidents(self.incidents.clone());
 		collector.spawn(shutdown_tx).await?;
-		if let Err(e) = print_host_configuration(self.opts.node.as_str(), &mut collector.executor()).await {
-			warn!("Cannot get host configuration");
-			return Err(e)
+		self.decode_failures = collector.decode_failures();
+		self.event_decoding = collector.event_decoding();
+		check_runtime_support(self.opts.node.as_str(), &mut collector.executor(), &self.opts.runtime_support).await?;
+		let is_json = self.opts.logs_info();
+		let host_configuration =
+			match print_host_configuration(self.opts.node.as_str(), &mut collector.executor(), is_json).await {
+				Ok(v) => v,
+				Err(e) => {
+					warn!("Cannot get host configuration");
+					return Err(e)
+				},
+			};
+		self.group_rotation_frequency = host_configuration.group_rotation_frequency();
+		if self.opts.show_group_rotations && self.group_rotation_frequency.is_none() {
+			warn!("The host configuration has no group_rotation_frequency, group rotations are not shown");
 		}
+		self.no_show_slots = host_configuration.no_show_slots();
+		self.minimum_backing_votes = host_configuration.minimum_backing_votes().unwrap_or(MINIMUM_BACKING_VOTES);
+		self.metrics.on_async_backing_params(
+			host_configuration.max_candidate_depth(),
+			host_configuration.allowed_ancestry_len(),
+		);
+		// The most recent relay parent is one block old, the allowed ancestry goes back from it
+		self.opts.max_relay_parent_age = self
+			.opts
+			.max_relay_parent_age
+			.or_else(|| host_configuration.allowed_ancestry_len().map(|len| len + 1));
+		self.para_nodes = self.opts.para_nodes.resolve(&self.retry, &self.context).await;
+		self.para_names
+			.read_from_nodes(&self.para_nodes, &self.retry, &self.context)
+			.await;
+		self.system_parachains = system_parachains::resolve(&self.para_nodes, &self.retry, &self.context).await;
 
-		println!(
+		if let Some(ref path) = self.opts.para_config {
+			let (para_config, watcher_fut) =
+				para_config::watch(path.clone(), ParaConfig::load(path)?, self.para_names.clone(), shutdown_tx);
+			self.para_config = Some(para_config);
+			output_futures.push(watcher_fut);
+		}
+
+		let header = format!(
 			"{} will trace {} on {}\n{}",
 			"Parachain Tracer".to_string().purple(),
 			if self.opts.all {
 				"all parachain(s)".to_string()
+			} else if let Some(ParachainTracerMode::ValidatorHistory(ref opts)) = self.opts.mode {
+				opts.validator.to_string()
+			} else if let Some(ref path) = self.opts.para_config {
+				format!("parachain(s) listed in {}", path.display())
+			} else if !self.opts.cores.is_empty() {
+				format!("parachain(s) on core(s) {}", self.opts.cores.iter().join(","))
 			} else {
-				format!("parachain(s) {}", self.opts.para_id.iter().join(","))
+				format!(
+					"parachain(s) {}",
+					self.opts
+						.para_id
+						.iter()
+						.map(|para_id| self.para_names.display(*para_id))
+						.join(",")
+				)
 			},
 			&self.node,
 			"-----------------------------------------------------------------------"
 				.to_string()
 				.bold()
 		);
+		print_info(header, is_json);
+		if let Some(ParachainTracerMode::Tui) = self.opts.mode {
+			let (handle, tui_fut) = tui::spawn(shutdown_tx, self.log_buffer.clone())?;
+			self.tui = Some(handle);
+			output_futures.push(tui_fut);
+		}
 
-		if self.opts.all {
+		if self.opts.all || !self.opts.cores.is_empty() || self.para_config.is_some() {
 			let from_collector = collector.subscribe_broadcast_updates().await?;
 			output_futures.push(tokio::spawn(ParachainTracer::watch_node_broadcast(
 				self.clone(),
@@ -189,6 +681,75 @@ impl ParachainTracer {
 			}
 		}
 
+		let mut sinks: Vec<Box<dyn OutputSink>> = vec![];
+		if let Some(ref dir) = self.opts.research_dump {
+			sinks.push(Box::new(ParquetSink::new(dir.clone())?));
+		}
+		if let Some(ref url) = self.opts.clickhouse_url {
+			let sink = ClickHouseSink::new(url, &self.opts.clickhouse_database, self.opts.clickhouse_batch_size)?;
+			sink.create_tables().await?;
+			match self.opts.wal_dir {
+				Some(ref dir) =>
+					sinks.push(Box::new(WalSink::open(Box::new(sink), dir, self.opts.clickhouse_batch_size).await?)),
+				None => sinks.push(Box::new(sink)),
+			}
+		}
+		if let Some(ref path) = self.opts.protobuf_output {
+			sinks.push(Box::new(ProtobufSink::new(path)?));
+		}
+		let plugins = match self.opts.plugin_dir {
+			Some(ref dir) => WasmPlugins::load(dir)?,
+			None => WasmPlugins::default(),
+		};
+		for sink in plugins.sinks {
+			sinks.push(Box::new(sink));
+		}
+
+		let dispute_tracker = match self.opts.dispute_validator {
+			Some(ref account) => {
+				let validator = AccountId32::from_str(account).unwrap_or_else(|_| {
+					ParachainTracerOptions::command()
+						.error(ErrorKind::InvalidValue, "`--dispute-validator` should be a SS58 account")
+						.exit()
+				});
+				Some(
+					DisputeParticipationTracker::new(validator, self.opts.dispute_participation_blocks)
+						.with_explorer(self.explorer.clone()),
+				)
+			},
+			None => None,
+		};
+
+		let culprits_tracker = self.opts.show_availability_culprits.then(|| {
+			AvailabilityCulpritsTracker::new(
+				self.opts.availability_culprits_window,
+				self.opts.availability_culprits_top,
+				self.opts.availability_culprits_threshold,
+			)
+		});
+
+		let on_demand_tracker = self.opts.track_on_demand_orders.then(OnDemandOrdersTracker::new);
+
+		let mut anomaly_detectors = AnomalyDetectors::new();
+		for name in self.opts.anomaly_detectors.iter() {
+			anomaly_detectors.register(anomaly::builtin_detector(name).expect("checked by the parser; qed"));
+		}
+		for detector in plugins.detectors {
+			anomaly_detectors.register(Box::new(detector));
+		}
+
+		let from_collector = collector.subscribe_relay_updates().await?;
+		output_futures.push(ParachainTracer::watch_node_for_relay_chain(
+			self.clone(),
+			from_collector,
+			collector.api(),
+			sinks,
+			dispute_tracker,
+			culprits_tracker,
+			on_demand_tracker,
+			anomaly_detectors,
+		));
+
 		let consumer_channels: Vec<Receiver<ChainSubscriptionEvent>> = consumer_config.into();
 		let collector_fut = collector
 			.run_with_consumer_channel(consumer_channels.into_iter().next().unwrap())
@@ -206,38 +767,29 @@ impl ParachainTracer {
 		from_collector: Receiver<CollectorUpdateEvent>,
 		para_id: u32,
 		api_service: CollectorStorageApi,
-	) -> tokio::task::JoinHandle<()> {
-		let mut rpc = ParachainTrackerRpc::new(para_id, self.node.as_str(), api_service.subxt());
-		let mut tracker = SubxtTracker::new(para_id);
-		let storage = TrackerStorage::new(para_id, api_service.storage());
-
-		let metrics = self.metrics.clone();
-		let mut stats = ParachainStats::new(para_id, self.opts.last_skipped_slot_blocks);
-		let is_cli = matches!(&self.opts.mode, Some(ParachainTracerMode::Cli));
+	) -> tokio::task::JoinHandle<u32> {
+		let mut watcher = ParachainWatcher::new(&self, para_id, api_service);
+		let mut summary_rx = self.summary_tx.subscribe();
+		let chaos = self.context.chaos().clone();
 
 		tokio::spawn(async move {
 			loop {
-				match from_collector.recv().await {
+				let update_event = tokio::select! {
+					update_event = from_collector.recv() => update_event,
+					Ok(()) = summary_rx.recv() => {
+						watcher.print_summaries();
+						continue
+					},
+				};
+				// Stalls the tracker, so its channel fills up and holds the collector back
+				if let Some(delay) = chaos.delay(Fault::SlowTracker) {
+					tokio::time::sleep(delay).await;
+				}
+				match update_event {
 					Ok(update_event) => match update_event {
-						CollectorUpdateEvent::NewHead(new_head) =>
-							for relay_fork in &new_head.relay_parent_hashes {
-								let parent_number = new_head.relay_parent_number;
-								if let Err(e) =
-									tracker.inject_block(*relay_fork, parent_number, &mut rpc, &storage).await
-								{
-									error!("error occurred when processing block {}: {:?}", relay_fork, e);
-									std::process::exit(1);
-								}
-								if let Some(progress) = tracker.progress(&mut stats, &metrics, &storage).await {
-									if is_cli {
-										println!("{}", progress)
-									}
-								}
-								tracker.maybe_reset_state();
-							},
-						CollectorUpdateEvent::NewSession(idx) => {
-							tracker.inject_new_session(idx);
-						},
+						CollectorUpdateEvent::NewHead(new_head) => watcher.on_new_head(new_head).await,
+						CollectorUpdateEvent::NewSession(idx) => watcher.on_new_session(idx),
+						CollectorUpdateEvent::NewRelayHead(_) => {},
 						CollectorUpdateEvent::Termination(reason) => {
 							info!("collector is terminating");
 							match reason {
@@ -256,10 +808,380 @@ impl ParachainTracer {
 				}
 			}
 
-			if is_cli {
-				print!("{}", stats);
-			} else {
-				info!("{}", stats);
+			watcher.finish()
+		})
+	}
+
+	// Follows relay chain updates, tracks bitfield participation of the validators as a whole and per validator,
+	// composition of the paras inherent, dispute participation of a single validator, on-demand orders, occupancy of
+	// the availability cores, runs anomaly detectors and writes observations to the output sinks.
+	fn watch_node_for_relay_chain(
+		self,
+		from_collector: Receiver<CollectorUpdateEvent>,
+		api_service: CollectorStorageApi,
+		mut sinks: Vec<Box<dyn OutputSink>>,
+		mut dispute_tracker: Option<DisputeParticipationTracker>,
+		mut culprits_tracker: Option<AvailabilityCulpritsTracker>,
+		mut on_demand_tracker: Option<OnDemandOrdersTracker>,
+		mut anomaly_detectors: AnomalyDetectors,
+	) -> tokio::task::JoinHandle<()> {
+		// Parachain id is not used for the relay chain wide reads
+		let mut storage = TrackerStorage::new(0, api_service.storage());
+		let mut tracker = BitfieldParticipationTracker::new(
+			self.opts.bitfield_participation_window,
+			self.opts.bitfield_participation_drop,
+		)
+		.with_explorer(self.explorer.clone());
+		let mut inherent_weight_tracker = InherentWeightTracker::default().with_explorer(self.explorer.clone());
+		let mut inherent_disputes_tracker = InherentDisputesTracker::default();
+		let mut runtime_upgrade_tracker = RuntimeUpgradeTracker::default().with_explorer(self.explorer.clone());
+		let mut finality_stall_detector = FinalityStallDetector::new(self.opts.finality_stall_blocks);
+		let mut reorg_detector = ReorgDetector::default();
+		let mut core_occupancy_tracker = self.opts.all.then(CoreOccupancyTracker::default);
+		let (mut validator_history, history_format) = match self.opts.mode {
+			Some(ParachainTracerMode::ValidatorHistory(ref opts)) =>
+				(Some(ValidatorHistory::new(opts.validator.clone())), opts.format),
+			_ => (None, Default::default()),
+		};
+		let mut last_session: Option<u32> = None;
+		let mut executor = api_service.subxt();
+		let node = self.node.clone();
+		let mut sequence = SequenceGenerator::new();
+		let metrics = self.metrics.clone();
+		let incidents = self.incidents.clone();
+		let rpc_budget = self.context.rpc_budget().clone();
+		let is_cli = matches!(&self.opts.mode, Some(ParachainTracerMode::Cli));
+		let mut summary_rx = self.summary_tx.subscribe();
+		let hooks = self.hooks.clone();
+		let notifier = self.notifier.clone();
+		let maintenance_windows = self.opts.maintenance.windows();
+		let mut anomaly_alerts = AlertManager::new(self.opts.alert_cooldown).with_explorer(self.explorer.clone());
+
+		tokio::spawn(async move {
+			loop {
+				let update_event = tokio::select! {
+					update_event = from_collector.recv() => update_event,
+					Ok(()) = summary_rx.recv() => {
+						print_summary(&tracker, is_cli);
+						print_summary(&inherent_weight_tracker, is_cli);
+						print_summary(&inherent_disputes_tracker, is_cli);
+						print_summary(&runtime_upgrade_tracker, is_cli);
+						if let Some(ref dispute_tracker) = dispute_tracker {
+							print_summary(dispute_tracker, is_cli);
+						}
+						if let Some(ref culprits_tracker) = culprits_tracker {
+							print_summary(culprits_tracker, is_cli);
+						}
+						if let Some(ref on_demand_tracker) = on_demand_tracker {
+							print_summary(on_demand_tracker, is_cli);
+						}
+						if let Some(ref core_occupancy_tracker) = core_occupancy_tracker {
+							print_summary(core_occupancy_tracker, is_cli);
+						}
+						report_incidents(&self.opts, &incidents, is_cli);
+						print_summary(&rpc_budget.snapshot(), is_cli);
+						print_diagnostics_summary(&self.event_decoding, &self.decode_failures, self.context.chaos(), is_cli);
+						continue
+					},
+				};
+				match update_event {
+					Ok(CollectorUpdateEvent::NewRelayHead(new_head)) => {
+						storage.set_block_contexts(new_head.block_contexts.clone());
+						let is_forked = new_head.relay_parent_hashes.len() > 1;
+						for relay_fork in &new_head.relay_parent_hashes {
+							let parent_hash = storage.parent_hash(*relay_fork).await;
+							if let Some(reorg) = reorg_detector
+								.on_block(*relay_fork, new_head.relay_parent_number, parent_hash, is_forked)
+								.filter(|resolution| resolution.is_reorg)
+							{
+								metrics.on_relay_reorg();
+								if is_cli {
+									println!("{}", reorg)
+								} else {
+									warn!("{}", reorg)
+								}
+							}
+
+							let mut observation =
+								match observation::collect(*relay_fork, new_head.relay_parent_number, &storage).await {
+									Some(v) => v,
+									None => continue,
+								};
+							observation.position = sequence.next_position();
+							let in_maintenance = maintenance_windows
+								.as_ref()
+								.is_some_and(|v| v.contains(observation.block_number, observation.timestamp));
+
+							let finalized = storage.relevant_finalized_block_number(*relay_fork).await;
+							metrics.on_relay_head(observation.block_number, finalized, observation.timestamp);
+							metrics.on_event_decoding(&self.event_decoding.snapshot());
+							if let Some(finalized) = finalized {
+								if let Some(incident) = finality_stall_detector.on_block(
+									observation.block_number,
+									observation.timestamp,
+									observation.block_number.saturating_sub(finalized),
+								) {
+									incidents.record(incident);
+								}
+							}
+
+							for anomaly in anomaly_detectors.on_observation(&observation) {
+								incidents.record((&anomaly).into());
+								if is_cli {
+									println!("{}", anomaly)
+								} else {
+									warn!("{}", anomaly)
+								}
+								if let Some(ref hooks) = hooks {
+									let outputs = hooks.on_anomaly(&anomaly);
+									process_hook_outputs(
+										outputs,
+										anomaly.block_number,
+										anomaly.timestamp,
+										&metrics,
+										&incidents,
+										is_cli,
+									);
+								}
+								let key = AlertKey::new(anomaly.detector.clone(), anomaly.para_id);
+								if anomaly_alerts.on_event(key, anomaly.block_number, in_maintenance) {
+									if let Some(notifier) = notifier.as_ref() {
+										notifier.notify(&(&anomaly).into());
+									}
+								}
+							}
+							for resolution in anomaly_alerts.on_block(observation.block_number) {
+								if is_cli {
+									println!("{}", resolution)
+								} else {
+									info!("{}", resolution)
+								}
+								// The notifier has received the alert being resolved, so resolutions are never muted
+								if let Some(notifier) = notifier.as_ref() {
+									notifier.notify(&(&resolution).into());
+								}
+							}
+
+							for sink in sinks.iter_mut() {
+								if let Err(e) = sink.write(&observation).await {
+									error!("{} sink failed to write block {}: {:?}", sink.name(), relay_fork, e);
+								}
+							}
+
+							if let Some(session) = storage.session_index(*relay_fork).await {
+								// Forks of the boundary block don't change the session again
+								let is_change = last_session.is_some_and(|prev| prev < session);
+								metrics.on_session(
+									session,
+									is_change.then_some((observation.block_number, observation.timestamp)),
+								);
+								last_session = Some(last_session.map_or(session, |prev| prev.max(session)));
+							}
+
+							if let Some(spec_version) = storage.spec_version(*relay_fork).await {
+								metrics.on_spec_version(spec_version);
+								if let Some(upgrade) =
+									runtime_upgrade_tracker.on_block(observation.block_number, spec_version)
+								{
+									if is_cli {
+										println!("{}", upgrade)
+									} else {
+										info!("{}", upgrade)
+									}
+									if let Some(notifier) = notifier.as_ref().filter(|_| !in_maintenance) {
+										notifier.notify(&(&upgrade).into());
+									}
+								}
+							}
+
+							if let Some(on_demand_tracker) = on_demand_tracker.as_mut() {
+								let orders = storage.on_demand_orders(*relay_fork).await;
+								let (included, timed_out) = inclusion_outcomes(
+									on_demand_tracker.awaiting_inclusion(),
+									observation.block_number,
+									&storage,
+								)
+								.await;
+								for event in on_demand_tracker.on_block(&observation, &orders, &included, &timed_out) {
+									metrics.on_on_demand_order(&event);
+									if is_cli {
+										println!("{}", event)
+									} else if matches!(event, OnDemandOrderEvent::Expired { .. }) {
+										warn!("{}", event)
+									} else {
+										info!("{}", event)
+									}
+								}
+							}
+
+							if let Some(core_occupancy_tracker) = core_occupancy_tracker.as_mut() {
+								let (included, timed_out) = inclusion_outcomes(
+									core_occupancy_tracker.awaiting_inclusion(),
+									observation.block_number,
+									&storage,
+								)
+								.await;
+								if let Some(occupancy) =
+									core_occupancy_tracker.on_block(&observation, &included, &timed_out)
+								{
+									metrics.on_core_occupancy(&occupancy);
+									if is_cli {
+										println!("{}", occupancy)
+									} else {
+										info!("{}", occupancy)
+									}
+								}
+							}
+
+							if let Some(update) = tracker.on_block(
+								observation.block_number,
+								observation.bitfields_count,
+								observation.validators_count,
+							) {
+								metrics.on_bitfield_participation(&update);
+								if update.is_drop {
+									if is_cli {
+										println!("{}", update)
+									} else {
+										warn!("{}", update)
+									}
+								}
+							}
+
+							let inherent = match storage.inherent_data(*relay_fork).await {
+								Some(v) => v,
+								None => continue,
+							};
+							if let Some(validator_history) = validator_history.as_mut() {
+								if let Some(session_index) = storage.session_index(*relay_fork).await {
+									for session_index in validator_history.unknown_sessions(session_index, &inherent) {
+										match fetch_session_keys(&mut executor, &node, *relay_fork, session_index).await
+										{
+											Some((keys, active_indices)) => validator_history.set_session_keys(
+												session_index,
+												&keys,
+												&active_indices,
+											),
+											None => validator_history.set_unresolved_session(session_index),
+										}
+									}
+									validator_history.on_block(session_index, &observation, &inherent);
+								}
+							}
+							if let Some(culprits_tracker) = culprits_tracker.as_mut() {
+								let bitfields: Vec<_> = inherent
+									.bitfields
+									.iter()
+									.map(|bitfield| (bitfield.validator_index.0, bitfield.payload.clone()))
+									.collect();
+								if let Some(report) = culprits_tracker.on_block(
+									observation.block_number,
+									&bitfields,
+									observation.validators_count,
+								) {
+									report_availability_culprits(report, &storage, &metrics, is_cli).await;
+								}
+							}
+							let unused_cores = observation
+								.cores
+								.iter()
+								.filter(|core| !core.occupied && !core.scheduled_paras.is_empty())
+								.count() as u32;
+							let update = inherent_weight_tracker.on_block(
+								observation.block_number,
+								&inherent,
+								storage.para_inherent_weight(*relay_fork).await,
+								unused_cores,
+							);
+							metrics.on_inherent_weight(&update);
+							metrics.on_inherent_disputes(&inherent_disputes_tracker.on_block(
+								observation.block_number,
+								&inherent,
+								last_session,
+							));
+							if update.is_crowded_out {
+								if is_cli {
+									println!("{}", update)
+								} else {
+									warn!("{}", update)
+								}
+							}
+
+							if let Some(dispute_tracker) = dispute_tracker.as_mut() {
+								let disputes = inherent.disputes;
+								for session_index in dispute_tracker.unknown_sessions(&disputes) {
+									if let Some(keys) = storage.session_keys(session_index).await {
+										dispute_tracker.set_session_keys(session_index, &keys);
+									}
+								}
+								for candidate_hash in dispute_tracker.unconcluded() {
+									let is_concluded = storage
+										.any_candidate(candidate_hash)
+										.await
+										.and_then(|candidate| candidate.candidate_disputed)
+										.is_some_and(|disputed| disputed.concluded.is_some());
+									if is_concluded {
+										dispute_tracker.on_concluded(candidate_hash);
+									}
+								}
+								let alerts = dispute_tracker.on_block(observation.block_number, &disputes);
+								metrics.on_dispute_participation(dispute_tracker.pending_count(), alerts.len() as u32);
+								for alert in alerts {
+									if is_cli {
+										println!("{}", alert)
+									} else {
+										warn!("{}", alert)
+									}
+								}
+							}
+						}
+					},
+					Ok(CollectorUpdateEvent::NewSession(session_index)) => {
+						if let Some(report) = culprits_tracker
+							.as_mut()
+							.and_then(|tracker| tracker.on_new_session(session_index))
+						{
+							report_availability_culprits(report, &storage, &metrics, is_cli).await;
+						}
+					},
+					Ok(CollectorUpdateEvent::Termination(_)) | Err(_) => break,
+					Ok(_) => continue,
+				}
+			}
+
+			for sink in sinks.iter_mut() {
+				if let Err(e) = sink.flush().await {
+					error!("{} sink failed to flush: {:?}", sink.name(), e);
+				}
+			}
+
+			print_summary(&tracker, is_cli);
+			print_summary(&inherent_weight_tracker, is_cli);
+			print_summary(&inherent_disputes_tracker, is_cli);
+			print_summary(&runtime_upgrade_tracker, is_cli);
+			if let Some(ref dispute_tracker) = dispute_tracker {
+				print_summary(dispute_tracker, is_cli);
+			}
+			if let Some(ref culprits_tracker) = culprits_tracker {
+				print_summary(culprits_tracker, is_cli);
+			}
+			if let Some(ref on_demand_tracker) = on_demand_tracker {
+				print_summary(on_demand_tracker, is_cli);
+			}
+			if let Some(ref core_occupancy_tracker) = core_occupancy_tracker {
+				print_summary(core_occupancy_tracker, is_cli);
+			}
+			report_incidents(&self.opts, &incidents, is_cli);
+			print_summary(&rpc_budget.snapshot(), is_cli);
+			print_diagnostics_summary(&self.event_decoding, &self.decode_failures, self.context.chaos(), is_cli);
+			if let Some(ref validator_history) = validator_history {
+				print!("{}", validator_history.render(history_format));
+				if !validator_history.unresolved_sessions().is_empty() {
+					error!(
+						"Cannot resolve the validator in sessions {:?}, their keys are not available on chain",
+						validator_history.unresolved_sessions()
+					);
+				}
 			}
 		})
 	}
@@ -277,6 +1199,7 @@ impl ParachainTracer {
 		let mut best_known_block: u32 = 0;
 		let max_stall = self.opts.max_parachain_stall;
 		let mut futures = FuturesUnordered::new();
+		let mut para_config = self.para_config.clone();
 
 		loop {
 			tokio::select! {
@@ -286,15 +1209,59 @@ impl ParachainTracer {
 							CollectorUpdateEvent::NewHead(new_head) => {
 								let para_id = new_head.para_id;
 								let last_known_block = new_head.relay_parent_number;
+								if para_config.as_ref().is_some_and(|config| !config.borrow().contains(para_id)) {
+									continue
+								}
+								// Parachains come and go on the selected cores, only the ones currently assigned are traced
+								let cores = if self.opts.cores.is_empty() {
+									vec![]
+								} else {
+									let storage = TrackerStorage::new(para_id, api_service.storage());
+									let assignments = match new_head.relay_parent_hashes.first() {
+										Some(hash) => storage.core_assignments(*hash).await.unwrap_or_default(),
+										None => Default::default(),
+									};
+									let cores = assigned_cores(&assignments, &self.opts.cores, para_id);
+									if cores.is_empty() {
+										if trackers.remove(&para_id).is_some() {
+											info!(
+												"Removed tracker for parachain {}, moved off the selected cores",
+												self.para_names.display(para_id)
+											);
+											last_blocks.remove(&para_id);
+										}
+										continue
+									}
+									cores
+								};
 
 								let to_tracker = trackers.entry(para_id).or_insert_with(|| {
 									let (tx, rx) = channel_with_capacities(collector::COLLECTOR_NORMAL_CHANNEL_CAPACITY, 1);
 									futures.push(ParachainTracer::watch_node_for_parachain(self.clone(), rx, para_id, api_service.clone()));
-									info!("Added tracker for parachain {}", para_id);
+									if cores.is_empty() {
+										info!("Added tracker for parachain {}", self.para_names.display(para_id));
+									} else {
+										info!(
+											"Added tracker for parachain {} on core(s) {}",
+											self.para_names.display(para_id),
+											cores.iter().join(",")
+										);
+									}
 
 									tx
 								});
-								to_tracker.send(CollectorUpdateEvent::NewHead(new_head.clone())).await.unwrap();
+								if to_tracker.send(CollectorUpdateEvent::NewHead(new_head.clone())).await.is_err() ||
+									self.context.chaos().inject(Fault::DroppedChannel)
+								{
+									// The tracker is added again on the next block of the parachain
+									warn!(
+										"Channel to the tracker of parachain {} is closed, dropping the tracker",
+										self.para_names.display(para_id)
+									);
+									trackers.remove(&para_id);
+									last_blocks.remove(&para_id);
+									continue
+								}
 								// Update last block number
 								let _ = std::mem::replace(
 									last_blocks.entry(para_id).or_insert(last_known_block).deref_mut(),
@@ -307,9 +1274,15 @@ impl ParachainTracer {
 								}
 							},
 							CollectorUpdateEvent::NewSession(idx) =>
-								for to_tracker in trackers.values_mut() {
-									to_tracker.send(CollectorUpdateEvent::NewSession(idx)).await.unwrap();
+								for (para_id, to_tracker) in trackers.iter_mut() {
+									if to_tracker.send(CollectorUpdateEvent::NewSession(idx)).await.is_err() {
+										warn!(
+											"Channel to the tracker of parachain {} is closed",
+											self.para_names.display(*para_id)
+										);
+									}
 								},
+							CollectorUpdateEvent::NewRelayHead(_) => {},
 							CollectorUpdateEvent::Termination(reason) => {
 								info!("Received termination event, {} trackers will be terminated, {} futures are pending",
 									trackers.len(), futures.len());
@@ -328,7 +1301,29 @@ impl ParachainTracer {
 						},
 					};
 				},
-				Some(_) = futures.next() => {},
+				Ok(()) = async { para_config.as_mut().expect("checked by the precondition; qed").changed().await },
+					if para_config.is_some() => {
+					let config = para_config.as_mut().expect("checked by the precondition; qed").borrow_and_update().clone();
+					trackers.retain(|para_id, _| {
+						let is_listed = config.contains(*para_id);
+						if !is_listed {
+							info!(
+								"Removed tracker for parachain {}, not listed in the parachain config",
+								self.para_names.display(*para_id)
+							);
+						}
+						is_listed
+					});
+					last_blocks.retain(|para_id, _| config.contains(*para_id));
+				},
+				Some(stopped) = futures.next() => {
+					// The series of a parachain that is not traced anymore would stay frozen otherwise
+					if let Ok(para_id) = stopped {
+						if !trackers.contains_key(&para_id) {
+							self.metrics.on_para_removed(para_id);
+						}
+					}
+				},
 				else => break,
 			}
 		}
@@ -357,15 +1352,199 @@ fn evict_stalled(
 	}
 }
 
-async fn print_host_configuration(url: &str, executor: &mut RequestExecutor) -> color_eyre::Result<()> {
+impl ParachainTracerOptions {
+	fn is_json(&self) -> bool {
+		matches!(self.mode, Some(ParachainTracerMode::Json))
+	}
+
+	/// Stdout is taken by the progress records in the JSON mode and by the interface in the TUI mode
+	fn logs_info(&self) -> bool {
+		matches!(self.mode, Some(ParachainTracerMode::Json) | Some(ParachainTracerMode::Tui))
+	}
+
+	fn records_incidents(&self) -> bool {
+		self.incidents || self.incidents_markdown.is_some()
+	}
+
+	fn baseline_options(&self) -> Option<BaselineOptions> {
+		self.adaptive_thresholds.then_some(BaselineOptions {
+			warmup: self.baseline_warmup,
+			alpha: self.baseline_alpha,
+			sigma: self.baseline_sigma,
+		})
+	}
+}
+
+/// Splits the candidates into the ones included and the ones timed out by the given relay chain block
+async fn inclusion_outcomes(
+	candidates: Vec<(u32, H256)>,
+	block_number: BlockNumber,
+	storage: &TrackerStorage,
+) -> (Vec<H256>, Vec<H256>) {
+	let mut included = vec![];
+	let mut timed_out = vec![];
+	for (para_id, candidate_hash) in candidates {
+		let inclusion = match storage.para_candidate(para_id, candidate_hash).await {
+			Some(candidate) => candidate.candidate_inclusion,
+			None => continue,
+		};
+		if inclusion.included.is_some_and(|at| at <= block_number) {
+			included.push(candidate_hash);
+		} else if inclusion.timedout.is_some_and(|at| at <= block_number) {
+			timed_out.push(candidate_hash);
+		}
+	}
+
+	(included, timed_out)
+}
+
+/// Prints the incidents timeline and writes it as Markdown if requested
+fn report_incidents(opts: &ParachainTracerOptions, incidents: &Incidents, is_cli: bool) {
+	if opts.incidents {
+		print_summary(&incidents.timeline().filter(opts.incidents_min_severity), is_cli);
+	}
+	if let Some(ref path) = opts.incidents_markdown {
+		incident_sources::write_markdown(incidents, path, opts.incidents_min_severity);
+	}
+}
+
+/// Posts a stall alert to the sinks, unless it is a duplicate of an open one
+fn post_stall_alert(
+	alert: &StallAlert,
+	alert_manager: &mut AlertManager,
+	alert_webhook: Option<&AlertWebhook>,
+	notifier: Option<&Notifier>,
+) {
+	let key = AlertKey::new(alert.kind.as_str(), Some(alert.para_id));
+	if !alert_manager.on_fire(key, alert.block_number, false) {
+		return
+	}
+	if let Some(webhook) = alert_webhook {
+		webhook.post(alert);
+	}
+	if let Some(notifier) = notifier {
+		notifier.notify(&alert.into());
+	}
+}
+
+/// Prints alerts of the hooks, adds them to the incidents timeline and updates the metrics set by them
+fn process_hook_outputs(
+	outputs: Vec<HookOutput>,
+	block_number: BlockNumber,
+	timestamp: Timestamp,
+	metrics: &Metrics,
+	incidents: &Incidents,
+	is_cli: bool,
+) {
+	for HookOutput { script, action } in outputs {
+		match action {
+			HookAction::Alert(message) => {
+				let alert = format!("[#{}] {} hook: {}", block_number, script, message);
+				if is_cli {
+					println!("{}", alert)
+				} else {
+					warn!("{}", alert)
+				}
+				incidents.record(Incident::new(
+					timestamp,
+					Severity::Warning,
+					IncidentSource::Hook,
+					Some(block_number),
+					format!("{}: {}", script, message),
+				));
+			},
+			HookAction::Metric(name, value) => metrics.on_hook_metric(&script, &name, value),
+		}
+	}
+}
+
+/// Informational messages are logged in the JSON mode to keep stdout parseable
+fn print_info(message: impl Display, is_json: bool) {
+	if is_json {
+		info!("{}", message);
+	} else {
+		println!("{}", message);
+	}
+}
+
+fn print_summary(summary: &impl Display, is_cli: bool) {
+	if is_cli {
+		print!("{}", summary);
+	} else {
+		info!("{}", summary);
+	}
+}
+
+/// Prints the diagnostics of the run: the cost of the chain events decoding if some events were skipped, the decode
+/// failures if any and the faults injected in the chaos mode
+fn print_diagnostics_summary(
+	event_decoding: &EventDecodingCounter,
+	decode_failures: &DecodeFailuresCounter,
+	chaos: &ChaosInjector,
+	is_cli: bool,
+) {
+	let stats = event_decoding.snapshot();
+	if stats.skipped > 0 {
+		print_summary(&stats, is_cli);
+	}
+	let failures = decode_failures.snapshot();
+	if failures.failures > 0 {
+		print_summary(&failures, is_cli);
+	}
+	if let Some(report) = chaos.snapshot() {
+		print_summary(&report, is_cli);
+	}
+}
+
+async fn report_availability_culprits(
+	mut report: AvailabilityCulpritsReport,
+	storage: &TrackerStorage,
+	metrics: &Metrics,
+	is_cli: bool,
+) {
+	if let Some(session_index) = report.session_index {
+		report.set_addresses(storage.session_keys(session_index).await.as_ref());
+	}
+	metrics.on_availability_culprits(&report);
+	if is_cli {
+		println!("{}", report)
+	} else if !report.culprits.is_empty() {
+		warn!("{}", report)
+	}
+}
+
+async fn print_host_configuration(
+	url: &str,
+	executor: &mut RequestExecutor,
+	is_json: bool,
+) -> color_eyre::Result<DynamicHostConfiguration> {
 	let conf = executor.get_host_configuration(url).await?;
-	println!("Host configuration for {}:", url.to_owned().bold());
-	println!("{}", conf);
-	Ok(())
+	print_info(format!("Host configuration for {}:\n{}", url.to_owned().bold(), conf), is_json);
+	Ok(conf)
 }
 
-fn historical_bounds(opts: &ParachainTracerOptions) -> color_eyre::Result<(u32, u32)> {
-	let from_block_number = opts.from_block_number.expect("`--from` must exist in historical mode");
+/// Resolves the number of the `--from` block
+async fn from_block_number(
+	opts: &ParachainTracerOptions,
+	context: &ExecutorContext,
+) -> color_eyre::Result<Option<BlockNumber>> {
+	match opts.from_block {
+		Some(BlockRef::Number(block_number)) => Ok(Some(block_number)),
+		Some(BlockRef::Hash(block_hash)) => RequestExecutor::new(opts.retry.clone(), context.clone())
+			.get_block_head(&opts.node, Some(block_hash))
+			.await
+			.map_err(|e| eyre!("Cannot get block {:?}: {:?}", block_hash, e))?
+			.map(|header| Some(header.number))
+			.ok_or_else(|| eyre!("Block {:?} not found", block_hash)),
+		None => Ok(None),
+	}
+}
+
+fn historical_bounds(
+	opts: &ParachainTracerOptions,
+	from_block_number: Option<BlockNumber>,
+) -> color_eyre::Result<(u32, u32)> {
+	let from_block_number = from_block_number.expect("`--from` must exist in historical mode");
 	let to_block_number = opts.to_block_number.expect("`--to` must exist in historical mode");
 	if from_block_number >= to_block_number {
 		let mut cmd = ParachainTracerOptions::command();
@@ -373,36 +1552,137 @@ fn historical_bounds(opts: &ParachainTracerOptions) -> color_eyre::Result<(u32,
 			.error(ErrorKind::ArgumentConflict, "`--from` block number should be less then `--to`")
 			.exit();
 	}
-	println!("Historical mode: from {} to {}", from_block_number, to_block_number);
+	print_info(format!("Historical mode: from {} to {}", from_block_number, to_block_number), opts.logs_info());
 
 	Ok((from_block_number, to_block_number))
 }
 
-#[tokio::main]
-async fn main() -> color_eyre::Result<()> {
-	let opts = ParachainTracerOptions::parse();
-	init::init_cli(&opts.verbose)?;
-
-	let tracer = ParachainTracer::new(opts.clone())?;
+/// Runs the tracer with the subscriptions feeding it until it is shut down
+async fn run_tracer(
+	tracer: ParachainTracer,
+	opts: &ParachainTracerOptions,
+	context: &ExecutorContext,
+	incidents: Incidents,
+) -> color_eyre::Result<()> {
 	let shutdown_tx = init::init_shutdown();
 	let mut futures = vec![];
 
+	let from_block_number = from_block_number(opts, context).await?;
 	if opts.is_historical {
-		let (from, to) = historical_bounds(&opts)?;
-		let mut historical_sub = HistoricalSubscription::new(vec![opts.node.clone()], from, to, opts.retry.clone());
+		let (from, to) = historical_bounds(opts, from_block_number)?;
+		let mut historical_sub = HistoricalSubscription::new(vec![opts.node.clone()], from, to, opts.retry.clone())
+			.with_executor_context(context.clone());
 		let consumer_init = historical_sub.create_consumer();
 
 		futures.extend(tracer.run(&shutdown_tx, consumer_init).await?);
 		futures.extend(historical_sub.run(&shutdown_tx).await?);
 	} else {
-		let mut head_sub = ChainHeadSubscription::new(vec![opts.node.clone()], opts.retry.clone());
+		let mut head_sub = ChainHeadSubscription::new(vec![opts.node.clone()], opts.retry.clone())
+			.with_executor_context(context.clone())
+			.with_incidents(incidents.clone());
+		if opts.finalized_only {
+			head_sub = head_sub.finalized_only();
+		}
+		if let Some(from) = from_block_number {
+			print_info(format!("Backfilling from #{} before following the chain", from), opts.logs_info());
+			head_sub = head_sub.backfill_from(from);
+		}
 		let consumer_init = head_sub.create_consumer();
 
 		futures.extend(tracer.run(&shutdown_tx, consumer_init).await?);
 		futures.extend(head_sub.run(&shutdown_tx).await?);
 	};
 
-	init::run(futures, &shutdown_tx).await?;
+	if let Some(feed) = opts.telemetry_feed.clone().filter(|_| opts.records_incidents()) {
+		let mut telemetry_sub = TelemetrySubscription::new(feed, opts.telemetry_chain.clone());
+		let consumer_channels: Vec<Receiver<TelemetryEvent>> = telemetry_sub.create_consumer().into();
+		futures.extend(
+			consumer_channels
+				.into_iter()
+				.map(|c| tokio::spawn(incident_sources::watch_telemetry(c, incidents.clone()))),
+		);
+		futures.extend(telemetry_sub.run(&shutdown_tx).await?);
+	}
+
+	init::run(futures, &shutdown_tx).await
+}
+
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
+	let mut opts = ParachainTracerOptions::parse();
+	if let Some(schema) = opts.schema.as_deref().and_then(schema::find) {
+		print!("{}", schema.json);
+		return Ok(())
+	}
+	if let Some(ParachainTracerMode::ListMetrics(ref prometheus_opts)) = opts.mode {
+		return prometheus::list_metrics(prometheus_opts)
+	}
+	if let Some(ParachainTracerMode::GenerateDashboard(ref prometheus_opts)) = opts.mode {
+		return prometheus::generate_dashboard(prometheus_opts, &opts.para_id)
+	}
+	// The log is kept while the TUI takes the terminal and shown when it is closed
+	let log_buffer = LogBuffer::default();
+	if let Some(ParachainTracerMode::Tui) = opts.mode {
+		init::init_cli_with_log_writer(&opts.verbose, Box::new(log_buffer.clone()))?;
+	} else {
+		init::init_cli(&opts.verbose)?;
+	}
+	let mut client_factory = RpcClientFactory::default();
+	if let Some(ParachainTracerMode::Replay(ref replay_opts)) = opts.mode {
+		if opts.from_block.is_none() || opts.to_block_number.is_none() {
+			ParachainTracerOptions::command()
+				.error(ErrorKind::MissingRequiredArgument, "`replay` requires `--from` and `--to`")
+				.exit();
+		}
+		opts.node = replay::serve(replay_opts, &mut client_factory)?;
+		opts.is_historical = true;
+		opts.mode = Some(ParachainTracerMode::Cli);
+	}
+	if let Some(ParachainTracerMode::ValidatorHistory(_)) = opts.mode {
+		if opts.from_block.is_none() || opts.to_block_number.is_none() {
+			ParachainTracerOptions::command()
+				.error(ErrorKind::MissingRequiredArgument, "`validator-history` requires `--from` and `--to`")
+				.exit();
+		}
+		opts.is_historical = true;
+	}
+	// Progress records are printed in the JSON mode, as JSON objects per line unless another format is requested
+	match opts.mode {
+		Some(ParachainTracerMode::Json) if opts.output.format.is_pretty() => opts.output.format = OutputFormat::Json,
+		None | Some(ParachainTracerMode::Cli) if !opts.output.format.is_pretty() =>
+			opts.mode = Some(ParachainTracerMode::Json),
+		_ => {},
+	}
+
+	let rpc_budget = RpcBudgetCounter::default();
+	let context = ExecutorContext::default()
+		.with_rpc_budget(rpc_budget.clone())
+		.with_client_factory(client_factory);
+	#[cfg(feature = "chaos")]
+	let context = context.with_chaos(ChaosInjector::new(&opts.chaos));
+	let incidents = if opts.records_incidents() { Incidents::enabled() } else { Default::default() };
+	let tracer =
+		ParachainTracer::new(opts.clone(), context.clone(), incidents.clone())?.with_log_buffer(log_buffer.clone());
+	let result = run_tracer(tracer, &opts, &context, incidents).await;
+	log_buffer.close();
+	result?;
+
+	let budget = rpc_budget.snapshot();
+	if let Some(max_ratio) = opts.max_rpc_error_ratio.filter(|max_ratio| budget.is_exceeded(*max_ratio)) {
+		error!(
+			"RPC error budget exceeded: {:.2}% of the calls failed, the limit is {:.2}%",
+			budget.failure_ratio() * 100.0,
+			max_ratio * 100.0
+		);
+		std::process::exit(RPC_ERROR_BUDGET_EXIT_CODE)
+	}
+	if let Some(max_data_gaps) = opts.max_data_gaps.filter(|max_data_gaps| budget.data_gaps > *max_data_gaps) {
+		error!(
+			"RPC error budget exceeded: data of {} blocks could not be fetched, the limit is {}",
+			budget.data_gaps, max_data_gaps
+		);
+		std::process::exit(RPC_ERROR_BUDGET_EXIT_CODE)
+	}
 
 	Ok(())
 }
@@ -0,0 +1,317 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Maintenance windows muting the alert sinks, e.g. during planned runtime upgrades.
+//!
+//! A window is a range of relay chain blocks, a range of UTC times or a daily or weekly recurring range of UTC
+//! times. Blocks are matched by their number and timestamp, so a window applies to the time the block was produced
+//! rather than to the time it was received. Stall alerts, notifications and the alert webhook are muted in a window,
+//! alerts are still printed and counted in the summary. Stall alerts raised in a window are deferred: they are posted
+//! when the window ends if the parachain is still stalled. Blocks in a window are marked in the progress records.
+
+use crate::{
+	alerts::{StallAlert, StallKind},
+	types::ParachainProgressUpdate,
+};
+use clap::Parser;
+use polkadot_introspector_essentials::types::{BlockNumber, Timestamp};
+use std::{
+	fmt::{self, Display, Formatter},
+	str::FromStr,
+};
+use time::{Date, Month, PrimitiveDateTime, Time};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+#[derive(Clone, Debug, Parser, Default)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct MaintenanceOptions {
+	/// Maintenance window muting the alert webhook and notifications, can be repeated. Either relay chain blocks
+	/// `FROM..TO`, UTC times `2024-01-15T14:00..2024-01-15T16:00` or recurring UTC times `daily 02:00..03:00` and
+	/// `tue 14:00..16:00`
+	#[clap(long = "maintenance-window", value_name = "WINDOW")]
+	maintenance_windows: Vec<MaintenanceWindow>,
+}
+
+impl MaintenanceOptions {
+	/// Returns the maintenance windows if any is configured
+	pub(crate) fn windows(&self) -> Option<MaintenanceWindows> {
+		(!self.maintenance_windows.is_empty()).then(|| MaintenanceWindows(self.maintenance_windows.clone()))
+	}
+}
+
+/// A period during which the alert sinks are muted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum MaintenanceWindow {
+	/// Relay chain blocks, both ends included
+	Blocks { from: BlockNumber, to: BlockNumber },
+	/// Timestamps in milliseconds, the end is excluded
+	Time { from: Timestamp, to: Timestamp },
+	/// Minutes of a day, every day or on a weekday (0 is Monday), the end is excluded and can be on the next day
+	Recurring { weekday: Option<u8>, from: u32, to: u32 },
+}
+
+impl MaintenanceWindow {
+	pub fn contains(&self, block_number: BlockNumber, timestamp: Timestamp) -> bool {
+		match *self {
+			MaintenanceWindow::Blocks { from, to } => (from..=to).contains(&block_number),
+			MaintenanceWindow::Time { from, to } => (from..to).contains(&timestamp),
+			MaintenanceWindow::Recurring { weekday, from, to } => {
+				let minutes = timestamp / 60_000;
+				let minute_of_day = (minutes % MINUTES_PER_DAY as u64) as u32;
+				// 1970-01-01 was a Thursday
+				let today = ((minutes / MINUTES_PER_DAY as u64 + 3) % 7) as u8;
+				let yesterday = (today + 6) % 7;
+				let is_day = |day: u8| weekday.map_or(true, |weekday| weekday == day);
+				if from < to {
+					is_day(today) && (from..to).contains(&minute_of_day)
+				} else {
+					(is_day(today) && minute_of_day >= from) || (is_day(yesterday) && minute_of_day < to)
+				}
+			},
+		}
+	}
+}
+
+fn parse_minute_of_day(s: &str) -> Result<u32, String> {
+	let (hours, minutes) = s.split_once(':').ok_or_else(|| format!("expected HH:MM, got {}", s))?;
+	match (hours.parse::<u32>(), minutes.parse::<u32>()) {
+		(Ok(hours), Ok(minutes)) if hours < 24 && minutes < 60 => Ok(hours * 60 + minutes),
+		_ => Err(format!("invalid time of day: {}", s)),
+	}
+}
+
+/// Parses a UTC time as `YYYY-MM-DDTHH:MM`, returns its timestamp in milliseconds
+fn parse_utc_time(s: &str) -> Result<Timestamp, String> {
+	let invalid = || format!("expected YYYY-MM-DDTHH:MM, got {}", s);
+	let (date, time) = s.trim_end_matches('Z').split_once('T').ok_or_else(invalid)?;
+	let mut parts = date.splitn(3, '-').map(|v| v.parse::<u32>().map_err(|_| invalid()));
+	let (year, month, day) = match (parts.next(), parts.next(), parts.next()) {
+		(Some(year), Some(month), Some(day)) => (year?, month?, day?),
+		_ => return Err(invalid()),
+	};
+	let minute_of_day = parse_minute_of_day(time)?;
+	let date = match (i32::try_from(year), u8::try_from(month), u8::try_from(day)) {
+		(Ok(year), Ok(month), Ok(day)) => Month::try_from(month)
+			.and_then(|month| Date::from_calendar_date(year, month, day))
+			.map_err(|_| invalid())?,
+		_ => return Err(invalid()),
+	};
+	let time = Time::from_hms((minute_of_day / 60) as u8, (minute_of_day % 60) as u8, 0).map_err(|_| invalid())?;
+	let seconds = PrimitiveDateTime::new(date, time).assume_utc().unix_timestamp();
+
+	Timestamp::try_from(seconds).map(|v| v * 1000).map_err(|_| invalid())
+}
+
+impl FromStr for MaintenanceWindow {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		if let Some((schedule, range)) = s.split_once(' ') {
+			let schedule = schedule.to_lowercase();
+			let weekday = match schedule.as_str() {
+				"daily" => None,
+				day => Some(
+					WEEKDAYS
+						.iter()
+						.position(|v| *v == day)
+						.ok_or_else(|| format!("expected `daily` or a weekday, got {}", schedule))? as u8,
+				),
+			};
+			let (from, to) = range
+				.trim()
+				.split_once("..")
+				.ok_or_else(|| format!("expected HH:MM..HH:MM, got {}", range))?;
+			let (from, to) = (parse_minute_of_day(from)?, parse_minute_of_day(to)?);
+			if from == to {
+				return Err(format!("empty maintenance window: {}", s))
+			}
+			return Ok(MaintenanceWindow::Recurring { weekday, from, to })
+		}
+
+		let (from, to) = s.split_once("..").ok_or_else(|| format!("expected FROM..TO, got {}", s))?;
+		let window = match (from.parse::<BlockNumber>(), to.parse::<BlockNumber>()) {
+			(Ok(from), Ok(to)) => MaintenanceWindow::Blocks { from, to },
+			_ => MaintenanceWindow::Time { from: parse_utc_time(from)?, to: parse_utc_time(to)? },
+		};
+		match window {
+			MaintenanceWindow::Blocks { from, to } if from > to => Err(format!("empty maintenance window: {}", s)),
+			MaintenanceWindow::Time { from, to } if from >= to => Err(format!("empty maintenance window: {}", s)),
+			window => Ok(window),
+		}
+	}
+}
+
+/// The configured maintenance windows
+#[derive(Clone, Debug)]
+pub(crate) struct MaintenanceWindows(Vec<MaintenanceWindow>);
+
+impl MaintenanceWindows {
+	/// Returns if a block is in any of the windows
+	pub fn contains(&self, block_number: BlockNumber, timestamp: Timestamp) -> bool {
+		self.0.iter().any(|window| window.contains(block_number, timestamp))
+	}
+}
+
+/// Follows the maintenance windows of a parachain and counts what happened in them for the summary
+pub(crate) struct MaintenanceTracker {
+	windows: MaintenanceWindows,
+	/// Relay chain blocks in the windows
+	blocks: u32,
+	/// Alerts not posted to the sinks
+	suppressed_alerts: u32,
+	/// Stall alerts posted after the end of a window
+	deferred_alerts: u32,
+	/// Stall alerts raised in the current window, the last one of each kind
+	deferred: Vec<StallAlert>,
+	is_active: bool,
+}
+
+impl MaintenanceTracker {
+	pub fn new(windows: MaintenanceWindows) -> Self {
+		Self { windows, blocks: 0, suppressed_alerts: 0, deferred_alerts: 0, deferred: vec![], is_active: false }
+	}
+
+	/// Processes a progress of the parachain, returns if its block is in a maintenance window
+	pub fn on_progress(&mut self, progress: &ParachainProgressUpdate) -> bool {
+		let is_active = self.windows.contains(progress.block_number, progress.timestamp);
+		if !progress.is_fork {
+			self.is_active = is_active;
+			if is_active {
+				self.blocks += 1;
+			}
+		}
+
+		is_active
+	}
+
+	/// Counts an alert that wasn't posted because of a maintenance window
+	pub fn on_suppressed_alert(&mut self) {
+		self.suppressed_alerts += 1;
+	}
+
+	/// Keeps a stall alert raised in a window to re-evaluate it when the window ends
+	pub fn defer(&mut self, alert: StallAlert) {
+		self.deferred.retain(|deferred| deferred.kind != alert.kind);
+		self.deferred.push(alert);
+	}
+
+	/// Returns the deferred alerts once the window has ended, those of the conditions still present are to be posted
+	pub fn take_deferred(&mut self, is_stalled: impl Fn(StallKind) -> bool) -> Vec<StallAlert> {
+		if self.is_active {
+			return vec![]
+		}
+		let (still_stalled, cleared): (Vec<_>, Vec<_>) =
+			self.deferred.drain(..).partition(|alert| is_stalled(alert.kind));
+		self.suppressed_alerts += cleared.len() as u32;
+		self.deferred_alerts += still_stalled.len() as u32;
+
+		still_stalled
+	}
+}
+
+impl Display for MaintenanceTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		if self.blocks == 0 {
+			return Ok(())
+		}
+		writeln!(
+			f,
+			"🔧 Maintenance windows: {} blocks, {} alerts suppressed, {} posted after a window{}",
+			self.blocks,
+			self.suppressed_alerts,
+			self.deferred_alerts,
+			if self.is_active { ", in progress" } else { "" }
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parses_windows() {
+		assert_eq!("100..200".parse(), Ok(MaintenanceWindow::Blocks { from: 100, to: 200 }));
+		assert_eq!(
+			"2024-01-15T14:00..2024-01-15T16:30Z".parse(),
+			Ok(MaintenanceWindow::Time { from: 1_705_327_200_000, to: 1_705_336_200_000 })
+		);
+		assert_eq!(
+			"daily 23:30..00:30".parse(),
+			Ok(MaintenanceWindow::Recurring { weekday: None, from: 1410, to: 30 })
+		);
+		assert_eq!(
+			"Tue 14:00..16:00".parse(),
+			Ok(MaintenanceWindow::Recurring { weekday: Some(1), from: 840, to: 960 })
+		);
+		assert!("200..100".parse::<MaintenanceWindow>().is_err());
+		assert!("daily 25:00..26:00".parse::<MaintenanceWindow>().is_err());
+		assert!("someday 14:00..16:00".parse::<MaintenanceWindow>().is_err());
+	}
+
+	#[test]
+	fn test_matches_recurring_windows() {
+		// Monday 2024-01-15
+		let monday = 1_705_276_800_000;
+		let at = |day: u64, hours: u64, minutes: u64| monday + ((day * 24 + hours) * 60 + minutes) * 60_000;
+		let nightly: MaintenanceWindow = "daily 23:30..00:30".parse().unwrap();
+		assert!(nightly.contains(0, at(0, 23, 45)));
+		assert!(nightly.contains(0, at(1, 0, 15)));
+		assert!(!nightly.contains(0, at(1, 0, 30)));
+
+		let weekly: MaintenanceWindow = "tue 14:00..16:00".parse().unwrap();
+		assert!(!weekly.contains(0, at(0, 15, 0)));
+		assert!(weekly.contains(0, at(1, 15, 0)));
+		assert!(!weekly.contains(0, at(1, 16, 0)));
+
+		let sunday_night: MaintenanceWindow = "sun 23:00..01:00".parse().unwrap();
+		assert!(sunday_night.contains(0, at(0, 0, 30)));
+		assert!(!sunday_night.contains(0, at(1, 0, 30)));
+	}
+
+	#[test]
+	fn test_defers_alerts_to_the_end_of_window() {
+		let mut tracker = MaintenanceTracker::new(MaintenanceWindows(vec!["10..20".parse().unwrap()]));
+		let progress = |block_number| ParachainProgressUpdate { para_id: 100, block_number, ..Default::default() };
+		let alert = |kind, block_number| StallAlert {
+			para_id: 100,
+			para_name: None,
+			kind,
+			block_number,
+			block_hash: Default::default(),
+			timestamp: 0,
+			blocks: 5,
+			last_seen: None,
+			explorer: Default::default(),
+		};
+
+		assert!(tracker.on_progress(&progress(15)));
+		tracker.defer(alert(StallKind::NoBacked, 15));
+		tracker.defer(alert(StallKind::NoIncluded, 16));
+		tracker.defer(alert(StallKind::NoBacked, 17));
+		assert!(tracker.take_deferred(|_| true).is_empty());
+
+		// Only the parachain still not backing candidates is alerted once the window ends
+		assert!(!tracker.on_progress(&progress(21)));
+		let alerts = tracker.take_deferred(|kind| kind == StallKind::NoBacked);
+		assert_eq!(alerts, vec![alert(StallKind::NoBacked, 17)]);
+		assert_eq!((tracker.suppressed_alerts, tracker.deferred_alerts), (1, 1));
+		assert!(tracker.take_deferred(|_| true).is_empty());
+	}
+}
@@ -14,17 +14,20 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::types::{MessageQueueDepths, QueueDepth};
 use log::debug;
 use polkadot_introspector_essentials::api::subxt_wrapper::SubxtHrmpChannel;
 use std::collections::BTreeMap;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 /// A structure that tracks messages (UMP, HRMP, DMP etc)
 pub struct MessageQueuesTracker {
 	/// Known inbound HRMP channels, indexed by source parachain id
 	pub inbound_hrmp_channels: BTreeMap<u32, SubxtHrmpChannel>,
 	/// Known outbound HRMP channels, indexed by source parachain id
 	pub outbound_hrmp_channels: BTreeMap<u32, SubxtHrmpChannel>,
+	/// Lengths of the downward and upward message queues
+	pub queue_depths: Option<MessageQueueDepths>,
 }
 
 impl MessageQueuesTracker {
@@ -39,6 +42,15 @@ impl MessageQueuesTracker {
 		self.outbound_hrmp_channels = outbound_channels;
 	}
 
+	/// Update the lengths of the downward and upward message queues, keeping the previous ones to show the trend
+	pub fn set_queue_lens(&mut self, downward: u32, upward: u32) {
+		let prev = self.queue_depths;
+		self.queue_depths = Some(MessageQueueDepths {
+			downward: QueueDepth { len: downward, prev_len: prev.map(|v| v.downward.len) },
+			upward: QueueDepth { len: upward, prev_len: prev.map(|v| v.upward.len) },
+		});
+	}
+
 	/// Returns if there are HRMP messages in any direction
 	pub fn has_hrmp_messages(&self) -> bool {
 		self.inbound_hrmp_channels.values().any(|channel| channel.total_size > 0) ||
@@ -83,6 +95,18 @@ mod tests {
 		assert!(tracker.has_hrmp_messages());
 	}
 
+	#[test]
+	fn test_set_queue_lens() {
+		let mut tracker = MessageQueuesTracker::default();
+		assert!(tracker.queue_depths.is_none());
+
+		tracker.set_queue_lens(3, 0);
+		tracker.set_queue_lens(5, 0);
+		let depths = tracker.queue_depths.unwrap();
+		assert_eq!(depths.downward, QueueDepth { len: 5, prev_len: Some(3) });
+		assert_eq!(depths.upward, QueueDepth { len: 0, prev_len: Some(0) });
+	}
+
 	#[test]
 	fn test_active_inbound_channels() {
 		let mut tracker = MessageQueuesTracker::default();
@@ -0,0 +1,385 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Notifications of disputes, runtime upgrades, stall alerts, anomalies and their resolutions posted to chat channels.
+//!
+//! Slack channels receive messages through an incoming webhook, formatted with `mrkdwn`. Matrix rooms receive
+//! `m.room.message` events sent with the access token of a bot account, with a plain text body and HTML formatting.
+
+use crate::{
+	alert_manager::AlertResolution,
+	alerts::{StallAlert, StallKind},
+	explorer::Explorer,
+	head_divergence::HeadDivergence,
+	para_heads::ParaHeadMismatch,
+	para_names::DisplayPara,
+	runtime_upgrades::RuntimeUpgrade,
+	types::{ParachainConsensusEvent, ParachainProgressUpdate},
+};
+use clap::Parser;
+use log::warn;
+use polkadot_introspector_essentials::{anomaly::Anomaly, chain_events::SubxtDisputeResult, types::BlockNumber};
+use serde_json::{json, Value};
+use std::{
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const HTTP_UA: &str = "polkadot-introspector";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Parser, Default)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct NotifyOptions {
+	/// Post disputes, runtime upgrades and stall alerts to a Slack channel using this incoming webhook URL
+	#[clap(long)]
+	notify_slack_url: Option<String>,
+	/// Post disputes, runtime upgrades and stall alerts to this Matrix room, e.g. `!abcdef:matrix.org`
+	#[clap(long, requires = "notify_matrix_homeserver", requires = "notify_matrix_token")]
+	notify_matrix_room: Option<String>,
+	/// URL of the Matrix homeserver, e.g. `https://matrix-client.matrix.org`
+	#[clap(long)]
+	notify_matrix_homeserver: Option<String>,
+	/// Access token of the Matrix account posting to the room
+	#[clap(long)]
+	notify_matrix_token: Option<String>,
+}
+
+/// A message to post to the channels
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Notification {
+	pub title: String,
+	pub details: Vec<String>,
+	/// Relay chain block the notification is about
+	pub block_number: BlockNumber,
+}
+
+impl Notification {
+	/// Returns notifications of the disputes concluded in a parachain block
+	pub fn from_progress(progress: &ParachainProgressUpdate) -> Vec<Notification> {
+		progress
+			.events
+			.iter()
+			.filter_map(|event| match event {
+				ParachainConsensusEvent::Disputed(dispute) => Some(Notification {
+					title: format!(
+						"Dispute of parachain {} concluded {}",
+						progress.para_names.display(progress.para_id),
+						match dispute.outcome {
+							SubxtDisputeResult::Valid => "valid",
+							SubxtDisputeResult::Invalid => "invalid",
+							SubxtDisputeResult::TimedOut => "with a time out",
+						}
+					),
+					details: vec![
+						format!("Candidate: {:?}", dispute.candidate),
+						format!("Session: {}", dispute.session_index),
+						format!("Voted valid: {}, voted invalid: {}", dispute.voted_for, dispute.voted_against),
+					],
+					block_number: progress.block_number,
+				}),
+				_ => None,
+			})
+			.collect()
+	}
+}
+
+impl From<&RuntimeUpgrade> for Notification {
+	fn from(upgrade: &RuntimeUpgrade) -> Self {
+		Notification {
+			title: format!("Relay chain runtime upgraded at #{}", upgrade.block_number),
+			details: vec![format!("Spec version: {} → {}", upgrade.previous, upgrade.current)],
+			block_number: upgrade.block_number,
+		}
+	}
+}
+
+impl From<&StallAlert> for Notification {
+	fn from(alert: &StallAlert) -> Self {
+		let action = match alert.kind {
+			StallKind::NoBacked => "backed",
+			StallKind::NoIncluded => "included",
+		};
+		Notification {
+			title: format!(
+				"Parachain {} has not {} a candidate for {} blocks",
+				DisplayPara(alert.para_id, alert.para_name.as_deref().map(Into::into)),
+				action,
+				alert.blocks
+			),
+			details: vec![match alert.last_seen {
+				Some(block_number) => format!("Last one at #{}", block_number),
+				None => "None since the start".to_string(),
+			}],
+			block_number: alert.block_number,
+		}
+	}
+}
+
+impl From<&ParaHeadMismatch> for Notification {
+	fn from(mismatch: &ParaHeadMismatch) -> Self {
+		Notification {
+			title: format!("Node of parachain {} follows another fork than the relay chain", mismatch.para_id),
+			details: vec![
+				format!("Parachain block: #{}", mismatch.para_block_number),
+				format!("Included on the relay chain: {:?}", mismatch.relay_chain_head),
+				format!("On the parachain node: {:?}", mismatch.para_node_head),
+			],
+			block_number: mismatch.block_number,
+		}
+	}
+}
+
+impl From<&Anomaly> for Notification {
+	fn from(anomaly: &Anomaly) -> Self {
+		let title = match anomaly.para_id {
+			Some(para_id) => format!("{} anomaly of parachain {}", anomaly.detector, para_id),
+			None => format!("{} anomaly", anomaly.detector),
+		};
+		Notification {
+			title,
+			details: vec![format!("Severity: {}", anomaly.severity), anomaly.description.clone()],
+			block_number: anomaly.block_number,
+		}
+	}
+}
+
+impl From<&AlertResolution> for Notification {
+	fn from(resolution: &AlertResolution) -> Self {
+		let mut details =
+			vec![format!("Fired at #{}", resolution.fired_at), format!("Occurrences: {}", resolution.occurrences)];
+		if resolution.flaps > 0 {
+			details.push(format!("Flapped {} times", resolution.flaps));
+		}
+		Notification { title: format!("Resolved: {}", resolution.key), details, block_number: resolution.block_number }
+	}
+}
+
+impl From<&HeadDivergence> for Notification {
+	fn from(divergence: &HeadDivergence) -> Self {
+		Notification {
+			title: format!(
+				"Relay chain node {} diverges on the head of parachain {}",
+				divergence.node, divergence.para_id
+			),
+			details: vec![
+				format!("Since: #{}", divergence.since),
+				format!("On the traced node: {:?}", divergence.expected),
+				format!("On the diverging node: {:?}", divergence.observed),
+			],
+			block_number: divergence.block_number,
+		}
+	}
+}
+
+/// Slack `mrkdwn` escaping, only the control characters are escaped
+fn escape_slack(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn escape_html(text: &str) -> String {
+	escape_slack(text).replace('"', "&quot;")
+}
+
+/// Payload of a Slack incoming webhook
+fn slack_message(notification: &Notification, link: Option<&str>) -> Value {
+	let mut text = format!("*{}*", escape_slack(&notification.title));
+	for detail in &notification.details {
+		text.push_str(&format!("\n• {}", escape_slack(detail)));
+	}
+	if let Some(link) = link {
+		text.push_str(&format!("\n<{}|Block #{}>", link, notification.block_number));
+	}
+
+	json!({ "text": text })
+}
+
+/// Content of a Matrix `m.room.message` event
+fn matrix_message(notification: &Notification, link: Option<&str>) -> Value {
+	let mut body = format!("**{}**", notification.title);
+	let mut formatted_body = format!("<strong>{}</strong><ul>", escape_html(&notification.title));
+	for detail in &notification.details {
+		body.push_str(&format!("\n- {}", detail));
+		formatted_body.push_str(&format!("<li>{}</li>", escape_html(detail)));
+	}
+	formatted_body.push_str("</ul>");
+	if let Some(link) = link {
+		body.push_str(&format!("\n{}", link));
+		formatted_body.push_str(&format!("<a href=\"{}\">Block #{}</a>", escape_html(link), notification.block_number));
+	}
+
+	json!({
+		"msgtype": "m.text",
+		"body": body,
+		"format": "org.matrix.custom.html",
+		"formatted_body": formatted_body,
+	})
+}
+
+#[derive(Clone)]
+struct MatrixRoom {
+	homeserver: reqwest::Url,
+	room: String,
+	token: String,
+	/// Makes transaction ids of the messages unique within a run, shared by the clones of the notifier
+	txn_counter: Arc<AtomicU64>,
+}
+
+/// Posts notifications to the configured channels
+#[derive(Clone)]
+pub(crate) struct Notifier {
+	http_client: reqwest::Client,
+	slack_url: Option<reqwest::Url>,
+	matrix: Option<MatrixRoom>,
+	explorer: Explorer,
+}
+
+impl Notifier {
+	/// Returns a notifier if any channel is configured
+	pub fn new(opts: &NotifyOptions, explorer: Explorer) -> color_eyre::Result<Option<Self>> {
+		let slack_url = opts.notify_slack_url.as_deref().map(reqwest::Url::parse).transpose()?;
+		let matrix = match (&opts.notify_matrix_room, &opts.notify_matrix_homeserver, &opts.notify_matrix_token) {
+			(Some(room), Some(homeserver), Some(token)) => Some(MatrixRoom {
+				homeserver: reqwest::Url::parse(homeserver)?,
+				room: room.clone(),
+				token: token.clone(),
+				txn_counter: Default::default(),
+			}),
+			_ => None,
+		};
+		if slack_url.is_none() && matrix.is_none() {
+			return Ok(None)
+		}
+		let http_client = reqwest::Client::builder().timeout(HTTP_TIMEOUT).user_agent(HTTP_UA).build()?;
+
+		Ok(Some(Self { http_client, slack_url, matrix, explorer }))
+	}
+
+	/// Posts a notification to every channel in the background, failures are logged
+	pub fn notify(&self, notification: &Notification) {
+		let link = self.explorer.block_link(notification.block_number);
+		let mut requests = vec![];
+		if let Some(ref url) = self.slack_url {
+			let request = self
+				.http_client
+				.post(url.clone())
+				.header(reqwest::header::CONTENT_TYPE, "application/json")
+				.body(slack_message(notification, link.as_deref()).to_string());
+			requests.push(("Slack", request));
+		}
+		if let Some(ref matrix) = self.matrix {
+			let mut url = matrix.homeserver.clone();
+			let txn_id = format!(
+				"introspector-{}-{}",
+				SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |v| v.as_millis()),
+				matrix.txn_counter.fetch_add(1, Ordering::Relaxed)
+			);
+			url.path_segments_mut()
+				.expect("homeserver URL is a base; qed")
+				.pop_if_empty()
+				.extend([
+					"_matrix",
+					"client",
+					"v3",
+					"rooms",
+					matrix.room.as_str(),
+					"send",
+					"m.room.message",
+					txn_id.as_str(),
+				]);
+			let request = self
+				.http_client
+				.put(url)
+				.bearer_auth(&matrix.token)
+				.header(reqwest::header::CONTENT_TYPE, "application/json")
+				.body(matrix_message(notification, link.as_deref()).to_string());
+			requests.push(("Matrix", request));
+		}
+
+		for (channel, request) in requests {
+			tokio::spawn(async move {
+				match request.send().await {
+					Ok(response) if !response.status().is_success() =>
+						warn!("{} notification failed: {}", channel, response.status()),
+					Ok(_) => {},
+					Err(e) => warn!("Cannot post a {} notification: {:?}", channel, e),
+				}
+			});
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::types::{DisputesTracker, SkippedSlotReason};
+	use polkadot_introspector_essentials::types::H256;
+
+	fn notification() -> Notification {
+		Notification {
+			title: "Relay chain runtime upgraded at #42".to_string(),
+			details: vec!["Spec version: 1 → 2 <new>".to_string()],
+			block_number: 42,
+		}
+	}
+
+	#[test]
+	fn test_formats_slack_message() {
+		assert_eq!(
+			slack_message(&notification(), Some("https://example.com/block/42")),
+			json!({
+				"text": "*Relay chain runtime upgraded at #42*\n• Spec version: 1 → 2 &lt;new&gt;\n<https://example.com/block/42|Block #42>"
+			})
+		);
+	}
+
+	#[test]
+	fn test_formats_matrix_message() {
+		let message = matrix_message(&notification(), None);
+		assert_eq!(message["body"], "**Relay chain runtime upgraded at #42**\n- Spec version: 1 → 2 <new>");
+		assert_eq!(
+			message["formatted_body"],
+			"<strong>Relay chain runtime upgraded at #42</strong><ul><li>Spec version: 1 → 2 &lt;new&gt;</li></ul>"
+		);
+	}
+
+	#[test]
+	fn test_notifies_concluded_disputes() {
+		let progress = ParachainProgressUpdate {
+			para_id: 100,
+			block_number: 42,
+			events: vec![
+				ParachainConsensusEvent::SkippedSlot(SkippedSlotReason::NoCandidate),
+				ParachainConsensusEvent::Disputed(DisputesTracker {
+					candidate: H256::zero(),
+					outcome: SubxtDisputeResult::Invalid,
+					voted_for: 1,
+					voted_against: 9,
+					..Default::default()
+				}),
+			],
+			..Default::default()
+		};
+
+		let notifications = Notification::from_progress(&progress);
+		assert_eq!(notifications.len(), 1);
+		assert_eq!(notifications[0].title, "Dispute of parachain 100 concluded invalid");
+		assert_eq!(notifications[0].details[2], "Voted valid: 1, voted invalid: 9");
+	}
+}
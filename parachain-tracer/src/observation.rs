@@ -0,0 +1,191 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Collects normalized per-block observations of the relay chain from the data saved in the collector.
+
+use crate::{
+	tracker_storage::TrackerStorage,
+	utils::{candidate_hash, extract_backing_votes, extract_inherent_fields},
+};
+pub use polkadot_introspector_essentials::observation::{BlockObservation, CandidateObservation, CoreObservation};
+use polkadot_introspector_essentials::types::{BlockNumber, CoreOccupied, H256};
+
+/// Builds an observation from the data saved in the collector, returns `None` if the block is unknown
+pub async fn collect(
+	block_hash: H256,
+	block_number: BlockNumber,
+	storage: &TrackerStorage,
+) -> Option<BlockObservation> {
+	let inherent = storage.inherent_data(block_hash).await?;
+	let (bitfields, backed_candidates, _) = extract_inherent_fields(&inherent);
+	let timestamp = storage.block_timestamp(block_hash).await.unwrap_or_default();
+	let spec_version = storage.spec_version(block_hash).await.unwrap_or_default();
+	let groups = storage.backing_groups(block_hash).await.unwrap_or_default();
+	let validators_count = groups.iter().map(|group| group.len() as u32).sum();
+	let assignments = storage.core_assignments(block_hash).await.unwrap_or_default();
+	let cores = storage
+		.occupied_cores(block_hash)
+		.await
+		.unwrap_or_default()
+		.iter()
+		.enumerate()
+		.map(|(core_index, occupied)| CoreObservation {
+			core_index: core_index as u32,
+			occupied: matches!(occupied, CoreOccupied::Paras),
+			scheduled_paras: assignments.get(&(core_index as u32)).cloned().unwrap_or_default(),
+		})
+		.collect();
+	let mut candidates = Vec::with_capacity(backed_candidates.len());
+	for candidate in backed_candidates.iter() {
+		let para_id = candidate.candidate.descriptor.para_id.0;
+		let candidate_hash = candidate_hash(candidate);
+		let (validity_votes, group_size) = extract_backing_votes(candidate);
+		// The votes are resolved by the collector when the candidate is backed
+		let votes = storage
+			.para_candidate(para_id, candidate_hash)
+			.await
+			.map(|v| v.validity_votes)
+			.unwrap_or_default();
+		candidates.push(CandidateObservation {
+			para_id,
+			candidate_hash,
+			relay_parent: candidate.candidate.descriptor.relay_parent,
+			validity_votes,
+			group_size,
+			votes,
+		});
+	}
+
+	Some(BlockObservation {
+		block_number,
+		block_hash,
+		timestamp,
+		spec_version,
+		bitfields_count: bitfields.len() as u32,
+		validators_count,
+		cores,
+		candidates,
+		position: Default::default(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		schema,
+		test_utils::{create_candidate_record, create_inherent_data, create_storage, storage_write},
+	};
+	use polkadot_introspector_essentials::{
+		collector::CollectorPrefixType, metadata::polkadot_primitives::ValidatorIndex, observation::ValidityVote,
+		sequence::StreamPosition, types::AccountId32,
+	};
+	use std::collections::BTreeMap;
+
+	#[tokio::test]
+	async fn test_returns_none_for_unknown_block() {
+		let storage = TrackerStorage::new(0, create_storage());
+
+		assert!(collect(H256::random(), 42, &storage).await.is_none());
+	}
+
+	#[tokio::test]
+	async fn test_collects_observation() {
+		let hash = H256::random();
+		let storage = create_storage();
+		let tracker_storage = TrackerStorage::new(0, storage.clone());
+		let inherent = create_inherent_data(100);
+		let backed_hash = candidate_hash(&inherent.backed_candidates[0]);
+		storage_write(CollectorPrefixType::InherentData, hash, inherent, &storage)
+			.await
+			.unwrap();
+		let mut record = create_candidate_record(100, 42, H256::random(), 41);
+		record.validity_votes =
+			vec![ValidityVote { validator_index: 2, address: Some(AccountId32([2; 32]).to_string()), voted: true }];
+		storage_write(CollectorPrefixType::Candidate(100), backed_hash, record, &storage)
+			.await
+			.unwrap();
+		storage_write(CollectorPrefixType::Timestamp, hash, 1694095332000_u64, &storage)
+			.await
+			.unwrap();
+		storage_write(
+			CollectorPrefixType::BackingGroups,
+			hash,
+			vec![vec![ValidatorIndex(0), ValidatorIndex(1)], vec![ValidatorIndex(2)]],
+			&storage,
+		)
+		.await
+		.unwrap();
+		storage_write(
+			CollectorPrefixType::OccupiedCores,
+			hash,
+			vec![CoreOccupied::Paras, CoreOccupied::Free],
+			&storage,
+		)
+		.await
+		.unwrap();
+		storage_write(CollectorPrefixType::CoreAssignments, hash, BTreeMap::from([(1_u32, vec![100_u32])]), &storage)
+			.await
+			.unwrap();
+
+		let observation = collect(hash, 42, &tracker_storage).await.unwrap();
+
+		assert_eq!(observation.block_number, 42);
+		assert_eq!(observation.timestamp, 1694095332000);
+		assert_eq!(observation.bitfields_count, 1);
+		assert_eq!(observation.validators_count, 3);
+		assert_eq!(
+			observation.cores,
+			vec![
+				CoreObservation { core_index: 0, occupied: true, scheduled_paras: vec![] },
+				CoreObservation { core_index: 1, occupied: false, scheduled_paras: vec![100] },
+			]
+		);
+		assert_eq!(observation.candidates.len(), 1);
+		assert_eq!(observation.candidates[0].para_id, 100);
+		assert_eq!(observation.candidates[0].group_size, 1);
+		assert_eq!(
+			observation.candidates[0].votes,
+			vec![ValidityVote { validator_index: 2, address: Some(AccountId32([2; 32]).to_string()), voted: true }]
+		);
+	}
+
+	#[test]
+	fn test_serialized_observation_conforms_to_schema() {
+		let observation = BlockObservation {
+			block_number: 42,
+			block_hash: H256::random(),
+			timestamp: 1694095332000,
+			spec_version: 1_003_000,
+			bitfields_count: 2,
+			validators_count: 3,
+			cores: vec![CoreObservation { core_index: 1, occupied: true, scheduled_paras: vec![100] }],
+			candidates: vec![CandidateObservation {
+				para_id: 100,
+				validity_votes: 2,
+				group_size: 3,
+				votes: vec![
+					ValidityVote { validator_index: 0, address: Some(AccountId32([0; 32]).to_string()), voted: true },
+					ValidityVote { validator_index: 1, address: None, voted: false },
+				],
+				..Default::default()
+			}],
+			position: StreamPosition { epoch: 1694095332000, sequence: 1 },
+		};
+
+		schema::tests::assert_conforms("observation", &serde_json::to_value(&observation).unwrap());
+	}
+}
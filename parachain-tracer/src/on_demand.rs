@@ -0,0 +1,365 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module follows on-demand coretime orders of all parachains.
+//!
+//! An order placed with the on-demand assignment provider goes through scheduling of the parachain on a core,
+//! backing of a candidate and its inclusion. Latencies of the stages are measured from the block the order was
+//! placed in, orders not fulfilled within `MAX_ORDER_AGE` blocks are reported as expired.
+
+use crate::types::{BlockWithoutHash, Latency};
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::{
+	observation::BlockObservation,
+	types::{BlockNumber, OnDemandOrder, H256},
+};
+use std::{
+	collections::{BTreeMap, VecDeque},
+	fmt::{self, Display, Formatter},
+	time::Duration,
+};
+
+/// Orders not fulfilled within this number of relay chain blocks are expired
+pub const MAX_ORDER_AGE: u32 = 100;
+
+#[derive(Clone, Debug)]
+struct PendingOrder {
+	spot_price: u128,
+	placed_at: BlockWithoutHash,
+	scheduled_at: Option<BlockWithoutHash>,
+	backed: Option<(H256, BlockWithoutHash)>,
+}
+
+/// Stages of an on-demand order
+#[derive(Clone, Debug, PartialEq)]
+pub enum OnDemandOrderEvent {
+	/// An order was placed
+	Placed { para_id: u32, spot_price: u128, block_number: BlockNumber },
+	/// A candidate of the parachain was included after the order
+	Fulfilled(OnDemandOrderReport),
+	/// No candidate was included within `MAX_ORDER_AGE` blocks after the order
+	Expired { para_id: u32, spot_price: u128, placed_at: BlockNumber },
+}
+
+/// Latencies of a fulfilled on-demand order
+#[derive(Clone, Debug, PartialEq)]
+pub struct OnDemandOrderReport {
+	pub para_id: u32,
+	pub spot_price: u128,
+	/// Relay chain block the order was placed in
+	pub placed_at: BlockNumber,
+	/// From the order to scheduling the parachain on a core, not known if it happened in a skipped block
+	pub scheduled_after: Option<Latency>,
+	/// From the order to backing a candidate
+	pub backed_after: Latency,
+	/// From the order to inclusion of the candidate
+	pub included_after: Latency,
+}
+
+fn latency(from: BlockWithoutHash, to: BlockWithoutHash) -> Latency {
+	Latency {
+		blocks: to.num.saturating_sub(from.num),
+		duration: Some(Duration::from_millis(to.ts.saturating_sub(from.ts))),
+	}
+}
+
+/// Follows on-demand orders of all parachains from placing to inclusion
+#[derive(Default)]
+pub struct OnDemandOrdersTracker {
+	/// Orders by parachain, the oldest first
+	pending: BTreeMap<u32, VecDeque<PendingOrder>>,
+	/// The last processed block, forks are skipped
+	last_block: Option<BlockNumber>,
+	placed_count: u32,
+	fulfilled_count: u32,
+	expired_count: u32,
+	/// Sum of the spot prices of the placed orders
+	spot_price_total: u128,
+	/// Sum of the latencies from the order to inclusion of the fulfilled orders, in blocks and milliseconds
+	included_after_total: (u64, u64),
+}
+
+impl OnDemandOrdersTracker {
+	pub fn new() -> Self {
+		Default::default()
+	}
+
+	/// Candidates backed for the orders and waiting to be included, with their parachain ids
+	pub fn awaiting_inclusion(&self) -> Vec<(u32, H256)> {
+		self.pending
+			.iter()
+			.flat_map(|(para_id, orders)| {
+				orders
+					.iter()
+					.filter_map(|order| order.backed.map(|(candidate_hash, _)| (*para_id, candidate_hash)))
+			})
+			.collect()
+	}
+
+	/// Processes a relay chain block with the orders placed in it and the candidates included or timed out in it
+	pub fn on_block(
+		&mut self,
+		observation: &BlockObservation,
+		orders: &[OnDemandOrder],
+		included: &[H256],
+		timed_out: &[H256],
+	) -> Vec<OnDemandOrderEvent> {
+		if self
+			.last_block
+			.is_some_and(|block_number| observation.block_number <= block_number)
+		{
+			return vec![]
+		}
+		self.last_block = Some(observation.block_number);
+
+		let block = BlockWithoutHash { num: observation.block_number, ts: observation.timestamp };
+		let mut events = vec![];
+
+		// Stages of the pending orders are processed before the new ones are added
+		for (para_id, para_orders) in self.pending.iter_mut() {
+			// The order is fulfilled only by an included candidate, so it waits for the next one
+			for order in para_orders.iter_mut() {
+				if order
+					.backed
+					.is_some_and(|(candidate_hash, _)| timed_out.contains(&candidate_hash))
+				{
+					order.backed = None;
+				}
+			}
+			let is_scheduled = observation.cores.iter().any(|core| core.scheduled_paras.contains(para_id));
+			if let Some(order) = para_orders
+				.iter_mut()
+				.find(|order| order.scheduled_at.is_none() && order.backed.is_none())
+			{
+				if is_scheduled {
+					order.scheduled_at = Some(block);
+				}
+			}
+			if let Some(candidate) = observation.candidates.iter().find(|candidate| candidate.para_id == *para_id) {
+				if let Some(order) = para_orders.iter_mut().find(|order| order.backed.is_none()) {
+					order.backed = Some((candidate.candidate_hash, block));
+				}
+			}
+
+			while let Some(order) = para_orders.front() {
+				match order.backed {
+					Some((candidate_hash, backed_at)) if included.contains(&candidate_hash) => {
+						events.push(OnDemandOrderEvent::Fulfilled(OnDemandOrderReport {
+							para_id: *para_id,
+							spot_price: order.spot_price,
+							placed_at: order.placed_at.num,
+							scheduled_after: order.scheduled_at.map(|at| latency(order.placed_at, at)),
+							backed_after: latency(order.placed_at, backed_at),
+							included_after: latency(order.placed_at, block),
+						}));
+					},
+					_ if block.num.saturating_sub(order.placed_at.num) >= MAX_ORDER_AGE => {
+						events.push(OnDemandOrderEvent::Expired {
+							para_id: *para_id,
+							spot_price: order.spot_price,
+							placed_at: order.placed_at.num,
+						});
+					},
+					_ => break,
+				}
+				para_orders.pop_front();
+			}
+		}
+		self.pending.retain(|_, orders| !orders.is_empty());
+
+		for order in orders {
+			self.pending.entry(order.para_id).or_default().push_back(PendingOrder {
+				spot_price: order.spot_price,
+				placed_at: block,
+				scheduled_at: None,
+				backed: None,
+			});
+			events.push(OnDemandOrderEvent::Placed {
+				para_id: order.para_id,
+				spot_price: order.spot_price,
+				block_number: block.num,
+			});
+		}
+
+		for event in &events {
+			match event {
+				OnDemandOrderEvent::Placed { spot_price, .. } => {
+					self.placed_count += 1;
+					self.spot_price_total = self.spot_price_total.saturating_add(*spot_price);
+				},
+				OnDemandOrderEvent::Fulfilled(report) => {
+					self.fulfilled_count += 1;
+					self.included_after_total.0 += report.included_after.blocks as u64;
+					self.included_after_total.1 +=
+						report.included_after.duration.map_or(0, |duration| duration.as_millis() as u64);
+				},
+				OnDemandOrderEvent::Expired { .. } => self.expired_count += 1,
+			}
+		}
+
+		events
+	}
+}
+
+impl Display for OnDemandOrderEvent {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			OnDemandOrderEvent::Placed { para_id, spot_price, block_number } => writeln!(
+				f,
+				"[#{}] {} by {}, spot price {}",
+				block_number,
+				"ON-DEMAND ORDER PLACED".to_string().bold().blue(),
+				para_id,
+				spot_price
+			),
+			OnDemandOrderEvent::Fulfilled(report) => {
+				writeln!(
+					f,
+					"[#{}] {} of {}, spot price {}",
+					report.placed_at,
+					"ON-DEMAND ORDER FULFILLED".to_string().bold().green(),
+					report.para_id,
+					report.spot_price
+				)?;
+				if let Some(scheduled_after) = report.scheduled_after {
+					writeln!(f, "\t⏱️ Scheduled after: {}", scheduled_after)?;
+				}
+				writeln!(f, "\t⏱️ Backed after: {}", report.backed_after)?;
+				writeln!(f, "\t⏱️ Included after: {}", report.included_after)
+			},
+			OnDemandOrderEvent::Expired { para_id, spot_price, placed_at } => writeln!(
+				f,
+				"[#{}] {} of {}, spot price {}, no candidate included within {} blocks",
+				placed_at,
+				"ON-DEMAND ORDER EXPIRED".to_string().bold().red(),
+				para_id,
+				spot_price,
+				MAX_ORDER_AGE
+			),
+		}
+	}
+}
+
+impl Display for OnDemandOrdersTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- On-demand orders ---".to_string().bold().blue())?;
+		writeln!(
+			f,
+			"Placed: {}, fulfilled: {}, expired: {}, pending: {}",
+			self.placed_count,
+			self.fulfilled_count.to_string().green(),
+			self.expired_count.to_string().bright_red(),
+			self.pending.values().map(|orders| orders.len()).sum::<usize>()
+		)?;
+		if self.placed_count > 0 {
+			writeln!(f, "Average spot price: {}", self.spot_price_total / self.placed_count as u128)?;
+		}
+		if self.fulfilled_count > 0 {
+			writeln!(
+				f,
+				"Average time from order to inclusion: {:.2} blocks, {:.2} seconds",
+				self.included_after_total.0 as f64 / self.fulfilled_count as f64,
+				self.included_after_total.1 as f64 / 1000.0 / self.fulfilled_count as f64
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_introspector_essentials::observation::{CandidateObservation, CoreObservation};
+
+	fn observation(
+		block_number: BlockNumber,
+		scheduled_paras: Vec<u32>,
+		backed: Option<(u32, H256)>,
+	) -> BlockObservation {
+		BlockObservation {
+			block_number,
+			timestamp: block_number as u64 * 6000,
+			cores: vec![CoreObservation { core_index: 0, occupied: false, scheduled_paras }],
+			candidates: backed
+				.map(|(para_id, candidate_hash)| CandidateObservation { para_id, candidate_hash, ..Default::default() })
+				.into_iter()
+				.collect(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_measures_order_latencies() {
+		let mut tracker = OnDemandOrdersTracker::new();
+		let candidate_hash = H256::random();
+
+		let events = tracker.on_block(
+			&observation(10, vec![], None),
+			&[OnDemandOrder { para_id: 2000, spot_price: 5 }],
+			&[],
+			&[],
+		);
+		assert_eq!(events, vec![OnDemandOrderEvent::Placed { para_id: 2000, spot_price: 5, block_number: 10 }]);
+		assert!(tracker.on_block(&observation(11, vec![2000], None), &[], &[], &[]).is_empty());
+		// A timed out candidate doesn't fulfill the order
+		let timed_out_hash = H256::random();
+		assert!(tracker
+			.on_block(&observation(12, vec![], Some((2000, timed_out_hash))), &[], &[], &[])
+			.is_empty());
+		assert!(tracker
+			.on_block(&observation(13, vec![], None), &[], &[], &[timed_out_hash])
+			.is_empty());
+		assert!(tracker.awaiting_inclusion().is_empty());
+		assert!(tracker
+			.on_block(&observation(14, vec![], Some((2000, candidate_hash))), &[], &[], &[])
+			.is_empty());
+		assert_eq!(tracker.awaiting_inclusion(), vec![(2000, candidate_hash)]);
+		// Forks are skipped
+		assert!(tracker
+			.on_block(&observation(14, vec![], None), &[], &[candidate_hash], &[])
+			.is_empty());
+
+		let events = tracker.on_block(&observation(15, vec![], None), &[], &[candidate_hash], &[]);
+		assert_eq!(
+			events,
+			vec![OnDemandOrderEvent::Fulfilled(OnDemandOrderReport {
+				para_id: 2000,
+				spot_price: 5,
+				placed_at: 10,
+				scheduled_after: Some(Latency { blocks: 1, duration: Some(Duration::from_secs(6)) }),
+				backed_after: Latency { blocks: 4, duration: Some(Duration::from_secs(24)) },
+				included_after: Latency { blocks: 5, duration: Some(Duration::from_secs(30)) },
+			})]
+		);
+		assert!(tracker.awaiting_inclusion().is_empty());
+	}
+
+	#[test]
+	fn test_expires_unfulfilled_orders() {
+		let mut tracker = OnDemandOrdersTracker::new();
+		tracker.on_block(&observation(10, vec![], None), &[OnDemandOrder { para_id: 2000, spot_price: 5 }], &[], &[]);
+
+		assert!(tracker
+			.on_block(&observation(10 + MAX_ORDER_AGE - 1, vec![], None), &[], &[], &[])
+			.is_empty());
+		assert_eq!(
+			tracker.on_block(&observation(10 + MAX_ORDER_AGE, vec![], None), &[], &[], &[]),
+			vec![OnDemandOrderEvent::Expired { para_id: 2000, spot_price: 5, placed_at: 10 }]
+		);
+		assert_eq!((tracker.placed_count, tracker.expired_count), (1, 1));
+	}
+}
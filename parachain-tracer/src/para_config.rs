@@ -0,0 +1,196 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! Registry file of the traced parachains.
+//!
+//! `--para-config` lists the parachains to trace with their names and alert thresholds, e.g.
+//!
+//! ```toml
+//! [[para]]
+//! id = 2004
+//! name = "Moonbeam"
+//! alert_no_backed = 10
+//! alert_no_included = 20
+//! ```
+//!
+//! The file is polled for changes: trackers of the added parachains start with their next block, trackers of
+//! the removed ones are stopped, and the others keep their statistics with the new names and thresholds.
+
+use crate::para_names::ParaNames;
+use color_eyre::{eyre::eyre, Result};
+use log::{info, warn};
+use serde::Deserialize;
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
+};
+use tokio::sync::{broadcast::Sender as BroadcastSender, watch};
+
+/// How often the file is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Settings of a traced parachain
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ParaSettings {
+	id: u32,
+	/// Overrides the name read from the parachain node and the one of `--para-names`
+	name: Option<String>,
+	/// Overrides `--alert-no-backed`
+	alert_no_backed: Option<u32>,
+	/// Overrides `--alert-no-included`
+	alert_no_included: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+	#[serde(default)]
+	para: Vec<ParaSettings>,
+}
+
+/// Traced parachains by their ids
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ParaConfig(BTreeMap<u32, ParaSettings>);
+
+impl ParaConfig {
+	pub(crate) fn load(path: &Path) -> Result<Self> {
+		let content = fs::read_to_string(path).map_err(|e| eyre!("Cannot read {}: {:?}", path.display(), e))?;
+		Self::parse(&content).map_err(|e| eyre!("Cannot parse {}: {}", path.display(), e))
+	}
+
+	fn parse(content: &str) -> Result<Self> {
+		let file: ConfigFile = toml::from_str(content)?;
+		let mut paras = BTreeMap::new();
+		for settings in file.para {
+			if let Some(prev) = paras.insert(settings.id, settings) {
+				return Err(eyre!("parachain {} is listed more than once", prev.id))
+			}
+		}
+
+		Ok(Self(paras))
+	}
+
+	pub(crate) fn contains(&self, para_id: u32) -> bool {
+		self.0.contains_key(&para_id)
+	}
+
+	pub(crate) fn para_ids(&self) -> impl Iterator<Item = u32> + '_ {
+		self.0.keys().copied()
+	}
+
+	/// Returns the stall alert thresholds of the parachain, falling back to the given defaults
+	pub(crate) fn alert_thresholds(
+		&self,
+		para_id: u32,
+		no_backed: Option<u32>,
+		no_included: Option<u32>,
+	) -> (Option<u32>, Option<u32>) {
+		match self.0.get(&para_id) {
+			Some(settings) => (settings.alert_no_backed.or(no_backed), settings.alert_no_included.or(no_included)),
+			None => (no_backed, no_included),
+		}
+	}
+
+	fn names(&self) -> impl Iterator<Item = (u32, String)> + '_ {
+		self.0
+			.values()
+			.filter_map(|settings| settings.name.clone().map(|name| (settings.id, name)))
+	}
+}
+
+/// Publishes the contents of the file and its changes until the shutdown, names are applied to all outputs
+pub(crate) fn watch(
+	path: PathBuf,
+	config: ParaConfig,
+	para_names: ParaNames,
+	shutdown_tx: &BroadcastSender<()>,
+) -> (watch::Receiver<ParaConfig>, tokio::task::JoinHandle<()>) {
+	para_names.set_configured(config.names());
+	let (tx, rx) = watch::channel(config);
+	let mut shutdown_rx = shutdown_tx.subscribe();
+
+	let fut = tokio::spawn(async move {
+		let mut modified = modified_at(&path);
+		let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+		loop {
+			tokio::select! {
+				_ = interval.tick() => {},
+				_ = shutdown_rx.recv() => break,
+			}
+			let last_modified = modified_at(&path);
+			if last_modified == modified {
+				continue
+			}
+			modified = last_modified;
+
+			let config = match ParaConfig::load(&path) {
+				Ok(v) => v,
+				Err(e) => {
+					warn!("Keeping the previous parachain config: {}", e);
+					continue
+				},
+			};
+			if config == *tx.borrow() {
+				continue
+			}
+			info!(
+				"Reloaded parachain config, tracing parachain(s) {}",
+				config.para_ids().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+			);
+			para_names.set_configured(config.names());
+			if tx.send(config).is_err() {
+				break
+			}
+		}
+	});
+
+	(rx, fut)
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parses_config() {
+		let config = ParaConfig::parse(
+			r#"
+			[[para]]
+			id = 2004
+			name = "Moonbeam"
+			alert_no_backed = 10
+
+			[[para]]
+			id = 3000
+			"#,
+		)
+		.unwrap();
+		assert_eq!(config.para_ids().collect::<Vec<_>>(), vec![2004, 3000]);
+		assert_eq!(config.names().collect::<Vec<_>>(), vec![(2004, "Moonbeam".to_string())]);
+		assert_eq!(config.alert_thresholds(2004, Some(5), Some(7)), (Some(10), Some(7)));
+		assert_eq!(config.alert_thresholds(3000, Some(5), None), (Some(5), None));
+
+		assert!(ParaConfig::parse("[[para]]\nid = 1\n[[para]]\nid = 1").is_err());
+		assert!(ParaConfig::parse("[[para]]\nid = 1\nthreshold = 2").is_err());
+		assert_eq!(ParaConfig::parse("").unwrap(), ParaConfig::default());
+	}
+}
@@ -0,0 +1,249 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Verifies the heads of a parachain committed on the relay chain against a node of the parachain.
+//!
+//! The head data of an included candidate is the encoded header of a parachain block and becomes the head of the
+//! parachain on the relay chain. A node of the parachain that finalized another block at the same height follows a
+//! different fork than the relay chain, e.g. because its collator keeps building on a wrong fork. The heads are
+//! compared once the node has finalized their height, forks of its best chain are not reported.
+
+use crate::{
+	para_nodes::{para_node, ParaNode},
+	pov::CandidateSize,
+	types::{ParachainConsensusEvent, ParachainProgressUpdate},
+	utils::candidate_hash,
+};
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::{ExecutorContext, RequestExecutor},
+	metadata::polkadot_primitives::BackedCandidate,
+	types::{BlockNumber, H256},
+	utils::RetryOptions,
+};
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt::{self, Display, Formatter},
+};
+
+/// Number of relay chain blocks a backed candidate is followed for, candidates backed on forks are never included
+const MAX_PENDING_BLOCKS: BlockNumber = 64;
+/// Number of included heads waiting for the node of the parachain to finalize them, the oldest ones are dropped
+const MAX_UNFINALIZED_HEADS: usize = 1024;
+
+/// A head of the parachain on the relay chain that the node of the parachain doesn't follow
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ParaHeadMismatch {
+	pub para_id: u32,
+	/// Relay chain block the head was included in
+	pub block_number: BlockNumber,
+	pub para_block_number: BlockNumber,
+	/// Hash of the parachain block committed on the relay chain
+	pub relay_chain_head: H256,
+	/// Hash of the finalized block at the same height on the node of the parachain
+	pub para_node_head: H256,
+}
+
+impl Display for ParaHeadMismatch {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"[#{}] {} of parachain {}: block #{} is {:?} on the relay chain, {} on the parachain node",
+			self.block_number,
+			"HEAD MISMATCH".to_string().bold().red(),
+			self.para_id,
+			self.para_block_number,
+			self.relay_chain_head,
+			format!("{:?}", self.para_node_head).bright_red()
+		)
+	}
+}
+
+/// Verifies the included heads of a parachain with the blocks fetched from a node of the parachain
+pub(crate) struct ParaHeadVerifier {
+	para_id: u32,
+	/// RPC node of the parachain
+	node: String,
+	executor: RequestExecutor,
+	/// Number and hash of the parachain blocks of the backed candidates with the relay chain block they were backed
+	/// at, by candidate hash
+	pending: HashMap<H256, (BlockNumber, BlockNumber, H256)>,
+	/// Number and hash of the included heads not finalized on the node yet, with the relay chain block they were
+	/// included in
+	unfinalized: VecDeque<(BlockNumber, BlockNumber, H256)>,
+}
+
+impl ParaHeadVerifier {
+	/// Returns a head verifier if the parachain has a node
+	pub(crate) fn new(
+		para_id: u32,
+		nodes: &[ParaNode],
+		retry: &RetryOptions,
+		context: &ExecutorContext,
+	) -> Option<Self> {
+		para_node(nodes, para_id).map(|node| Self {
+			para_id,
+			node: node.url.clone(),
+			executor: RequestExecutor::new(retry.clone(), context.clone()),
+			pending: Default::default(),
+			unfinalized: Default::default(),
+		})
+	}
+
+	/// Processes the progress of the parachain with the candidates backed in the relay chain block,
+	/// returns the included heads that differ from the blocks finalized by the node of the parachain at their height
+	pub(crate) async fn on_progress(
+		&mut self,
+		progress: &ParachainProgressUpdate,
+		backed_candidates: &[BackedCandidate<H256>],
+	) -> color_eyre::Result<Vec<ParaHeadMismatch>> {
+		for (para_block_number, relay_chain_head) in self.included_heads(progress, backed_candidates) {
+			self.unfinalized
+				.push_back((progress.block_number, para_block_number, relay_chain_head));
+		}
+		while self.unfinalized.len() > MAX_UNFINALIZED_HEADS {
+			self.unfinalized.pop_front();
+		}
+		if self.unfinalized.is_empty() {
+			return Ok(vec![])
+		}
+
+		let Some(finalized_hash) = self.executor.get_finalized_block_hash(&self.node).await? else { return Ok(vec![]) };
+		let Some(finalized) = self.executor.get_block_head(&self.node, Some(finalized_hash)).await? else {
+			return Ok(vec![])
+		};
+		let mut mismatches = vec![];
+		while let Some(&(block_number, para_block_number, relay_chain_head)) = self.unfinalized.front() {
+			// The heads are included in the order of their height
+			if para_block_number > finalized.number {
+				break
+			}
+			// The blocks up to the finalized one are on the finalized chain of the node
+			let para_node_head = self.executor.get_block_hash(&self.node, Some(para_block_number)).await?;
+			self.unfinalized.pop_front();
+			match para_node_head {
+				Some(para_node_head) if para_node_head != relay_chain_head => mismatches.push(ParaHeadMismatch {
+					para_id: self.para_id,
+					block_number,
+					para_block_number,
+					relay_chain_head,
+					para_node_head,
+				}),
+				_ => (),
+			}
+		}
+
+		Ok(mismatches)
+	}
+
+	/// Follows the heads of the backed candidates, returns the ones included in the relay chain block
+	fn included_heads(
+		&mut self,
+		progress: &ParachainProgressUpdate,
+		backed_candidates: &[BackedCandidate<H256>],
+	) -> Vec<(BlockNumber, H256)> {
+		for candidate in backed_candidates {
+			// The head data is the encoded header, the number is unknown if it can't be decoded
+			let size = CandidateSize::new(candidate);
+			if let Some(para_block_number) = size.para_block_number {
+				self.pending.insert(
+					candidate_hash(candidate),
+					(progress.block_number, para_block_number, size.para_block_hash),
+				);
+			}
+		}
+
+		let mut heads = vec![];
+		for event in &progress.events {
+			match event {
+				ParachainConsensusEvent::Included(candidate_hash, _, _) => heads.extend(
+					self.pending
+						.remove(candidate_hash)
+						.map(|(_, para_block_number, para_block_hash)| (para_block_number, para_block_hash)),
+				),
+				ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash) => {
+					self.pending.remove(candidate_hash);
+				},
+				_ => {},
+			}
+		}
+		self.pending
+			.retain(|_, (backed_at, _, _)| progress.block_number.saturating_sub(*backed_at) <= MAX_PENDING_BLOCKS);
+		heads
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::{create_backed_candidate, create_progress};
+	use parity_scale_codec::Encode;
+	use polkadot_introspector_essentials::{
+		metadata::polkadot::runtime_types::polkadot_parachain::primitives::HeadData, types::Header,
+	};
+	use subxt::config::{substrate::BlakeTwo256, Hasher};
+
+	#[test]
+	fn test_follows_included_heads() {
+		let mut verifier = ParaHeadVerifier::new(
+			100,
+			&["100=ws://localhost:9944".parse().unwrap()],
+			&Default::default(),
+			&Default::default(),
+		)
+		.unwrap();
+		let header = Header {
+			parent_hash: H256::random(),
+			number: 42,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		let mut included = create_backed_candidate(100);
+		included.candidate.commitments.head_data = HeadData(header.encode());
+		let mut timed_out = create_backed_candidate(100);
+		timed_out.candidate.commitments.head_data = HeadData(header.encode());
+		timed_out.candidate.commitments.processed_downward_messages = 1;
+		let mut forked = create_backed_candidate(100);
+		forked.candidate.commitments.head_data = HeadData(header.encode());
+		forked.candidate.commitments.processed_downward_messages = 2;
+
+		assert!(verifier
+			.included_heads(&create_progress(10, vec![]), &[included.clone(), timed_out.clone(), forked])
+			.is_empty());
+		assert_eq!(verifier.pending.len(), 3);
+
+		let heads = verifier.included_heads(
+			&create_progress(
+				11,
+				vec![
+					ParachainConsensusEvent::Included(candidate_hash(&included), 5, 5),
+					ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash(&timed_out)),
+				],
+			),
+			&[],
+		);
+		assert_eq!(heads, vec![(42, BlakeTwo256::hash(&header.encode()))]);
+		assert_eq!(verifier.pending.len(), 1);
+
+		// The candidate backed on a fork is never included
+		assert!(verifier
+			.included_heads(&create_progress(10 + MAX_PENDING_BLOCKS + 1, vec![]), &[])
+			.is_empty());
+		assert!(verifier.pending.is_empty());
+	}
+}
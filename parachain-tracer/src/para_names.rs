@@ -0,0 +1,171 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Human-readable names of parachains.
+//!
+//! The on-chain registrar only stores para ids, so a parachain with a node is named after the runtime of the node,
+//! e.g. `moonbeam`, and other names come from an optional user-supplied mapping file. Names are resolved at startup
+//! and shared by all outputs, the parachain config file can update them at runtime.
+
+use crate::para_nodes::ParaNode;
+use color_eyre::{eyre::eyre, Result};
+use log::warn;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::{ExecutorContext, RequestExecutor},
+	utils::RetryOptions,
+};
+use serde::Deserialize;
+use std::{
+	collections::HashMap,
+	fmt::{self, Display},
+	fs,
+	path::Path,
+	sync::{Arc, RwLock},
+};
+
+/// Formats of the user-supplied mapping file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MappingFile {
+	/// `{"2004": "Moonbeam"}`
+	Map(HashMap<u32, String>),
+	/// Chain registry entries, e.g. `[{"paraId": 2004, "text": "Moonbeam", ...}]`
+	Registry(Vec<RegistryEntry>),
+}
+
+#[derive(Deserialize)]
+struct RegistryEntry {
+	#[serde(rename = "paraId")]
+	para_id: u32,
+	text: String,
+}
+
+/// Names of parachains by their ids, cheap to clone into every output that shows them
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ParaNames(Arc<RwLock<Names>>);
+
+#[derive(Debug, Default)]
+struct Names {
+	/// Names read from the mapping file and the parachain nodes
+	resolved: HashMap<u32, Arc<str>>,
+	/// Names of the parachain config, take precedence over the resolved ones
+	configured: HashMap<u32, Arc<str>>,
+}
+
+impl ParaNames {
+	/// Reads the names from the mapping file if given
+	pub(crate) fn new(file: Option<&Path>) -> Result<Self> {
+		let mut names = Names::default();
+		if let Some(file) = file {
+			let content = fs::read_to_string(file).map_err(|e| eyre!("Cannot read {}: {:?}", file.display(), e))?;
+			let mapping = parse_mapping(&content).map_err(|e| eyre!("Cannot parse {}: {:?}", file.display(), e))?;
+			names.resolved = mapping.into_iter().map(|(para_id, name)| (para_id, name.into())).collect();
+		}
+
+		Ok(Self(Arc::new(RwLock::new(names))))
+	}
+
+	/// Names the parachains with a node after the spec names of their runtimes, the names of the mapping file take
+	/// precedence
+	pub(crate) async fn read_from_nodes(&self, nodes: &[ParaNode], retry: &RetryOptions, context: &ExecutorContext) {
+		let mut executor = RequestExecutor::new(retry.clone(), context.clone());
+		for node in nodes {
+			match executor.get_runtime_version(&node.url).await {
+				Ok(version) => {
+					self.0
+						.write()
+						.expect("parachain names lock is poisoned")
+						.resolved
+						.entry(node.para_id)
+						.or_insert_with(|| version.spec_name.into());
+				},
+				Err(e) => warn!("Cannot read the runtime version of {}: {:?}", node.url, e),
+			}
+		}
+	}
+
+	/// Returns the name of the parachain if it is known
+	pub(crate) fn get(&self, para_id: u32) -> Option<Arc<str>> {
+		let names = self.0.read().expect("parachain names lock is poisoned");
+		names.configured.get(&para_id).or_else(|| names.resolved.get(&para_id)).cloned()
+	}
+
+	/// Displays the parachain id followed by its name if it is known
+	pub(crate) fn display(&self, para_id: u32) -> DisplayPara {
+		DisplayPara(para_id, self.get(para_id))
+	}
+
+	/// Replaces the names of the parachain config, the parachains it doesn't name anymore get their resolved names
+	/// back
+	pub(crate) fn set_configured(&self, names: impl IntoIterator<Item = (u32, String)>) {
+		self.0.write().expect("parachain names lock is poisoned").configured =
+			names.into_iter().map(|(para_id, name)| (para_id, name.into())).collect();
+	}
+}
+
+fn parse_mapping(content: &str) -> serde_json::Result<HashMap<u32, String>> {
+	Ok(match serde_json::from_str(content)? {
+		MappingFile::Map(names) => names,
+		MappingFile::Registry(entries) => entries.into_iter().map(|entry| (entry.para_id, entry.text)).collect(),
+	})
+}
+
+/// Displays the parachain id followed by its name if it is known, e.g. `2004 (Moonbeam)`
+pub(crate) struct DisplayPara(pub u32, pub Option<Arc<str>>);
+
+impl Display for DisplayPara {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.1 {
+			Some(ref name) => write!(f, "{} ({})", self.0, name),
+			None => write!(f, "{}", self.0),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parses_mapping_formats() {
+		let names = parse_mapping(r#"{"2004": "Moonbeam", "3000": "Local"}"#).unwrap();
+		assert_eq!(names.get(&2004).map(|v| v.as_str()), Some("Moonbeam"));
+		assert_eq!(names.get(&3000).map(|v| v.as_str()), Some("Local"));
+
+		let names =
+			parse_mapping(r#"[{"info": "moonbeam", "paraId": 2004, "text": "Moonbeam", "providers": {}}]"#).unwrap();
+		assert_eq!(names.get(&2004).map(|v| v.as_str()), Some("Moonbeam"));
+
+		assert!(parse_mapping(r#"{"moonbeam": 2004}"#).is_err());
+	}
+
+	#[test]
+	fn test_configured_names_take_precedence() {
+		let names = ParaNames::default();
+		names.0.write().unwrap().resolved.insert(2004, "moonbeam".into());
+		assert_eq!(names.display(2004).to_string(), "2004 (moonbeam)");
+
+		names.set_configured([(2004, "Moonbeam".to_string()), (3000, "Local".to_string())]);
+		assert_eq!(names.clone().get(2004).as_deref(), Some("Moonbeam"));
+		assert_eq!(names.get(3000).as_deref(), Some("Local"));
+		assert_eq!(names.display(1000).to_string(), "1000");
+
+		// Names removed from the config fall back to the resolved ones
+		names.set_configured([]);
+		assert_eq!(names.get(2004).as_deref(), Some("moonbeam"));
+		assert_eq!(names.get(3000), None);
+	}
+}
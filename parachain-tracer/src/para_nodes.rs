@@ -0,0 +1,102 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Nodes of the traced parachains.
+//!
+//! A parachain with a node given as `--para-node PARA_ID=URL` is traced with the data of its own blocks: the heads
+//! included on the relay chain are verified against the node, the sizes of the blocks of the backed candidates are
+//! reported and the activity of the system parachains is counted. The id each node serves is checked at startup.
+
+use clap::Parser;
+use log::warn;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::{ExecutorContext, RequestExecutor},
+	utils::RetryOptions,
+};
+use std::str::FromStr;
+
+/// A node of a parachain given as `PARA_ID=URL`
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ParaNode {
+	pub para_id: u32,
+	pub url: String,
+}
+
+impl FromStr for ParaNode {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (para_id, url) = s.split_once('=').ok_or_else(|| format!("expected PARA_ID=URL, got {}", s))?;
+		let para_id = para_id
+			.trim()
+			.parse()
+			.map_err(|_| format!("invalid parachain id: {}", para_id))?;
+		Ok(Self { para_id, url: url.trim().to_string() })
+	}
+}
+
+#[derive(Clone, Debug, Parser, Default)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct ParaNodeOptions {
+	/// RPC node of a parachain as `PARA_ID=URL`, its blocks are fetched to verify the included heads, measure the
+	/// backed candidates and count the activity of the system parachains, can be repeated
+	#[clap(long = "para-node", value_name = "PARA_ID=URL")]
+	para_nodes: Vec<ParaNode>,
+}
+
+impl ParaNodeOptions {
+	/// Returns the nodes serving the parachain they are given for. A node that cannot be asked is kept, it's
+	/// retried when its blocks are fetched
+	pub(crate) async fn resolve(&self, retry: &RetryOptions, context: &ExecutorContext) -> Vec<ParaNode> {
+		let mut executor = RequestExecutor::new(retry.clone(), context.clone());
+		let mut nodes = vec![];
+		for node in &self.para_nodes {
+			match executor.get_parachain_id(&node.url).await {
+				Ok(Some(para_id)) if para_id != node.para_id => {
+					warn!(
+						"{} serves parachain {}, not {}, its blocks are not fetched",
+						node.url, para_id, node.para_id
+					);
+					continue
+				},
+				Ok(_) => (),
+				Err(e) => warn!("Cannot read the parachain id of {}: {:?}", node.url, e),
+			}
+			nodes.push(node.clone());
+		}
+		nodes
+	}
+}
+
+/// Returns the node of a parachain if it has one
+pub(crate) fn para_node(nodes: &[ParaNode], para_id: u32) -> Option<&ParaNode> {
+	nodes.iter().find(|node| node.para_id == para_id)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parses_para_node() {
+		assert_eq!(
+			"1000=wss://asset-hub.example:443".parse::<ParaNode>(),
+			Ok(ParaNode { para_id: 1000, url: "wss://asset-hub.example:443".to_string() })
+		);
+		assert!("wss://asset-hub.example:443".parse::<ParaNode>().is_err());
+		assert!("asset-hub=wss://asset-hub.example:443".parse::<ParaNode>().is_err());
+	}
+}
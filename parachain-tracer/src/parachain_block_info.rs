@@ -14,13 +14,13 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::utils::candidate_hash;
 use parity_scale_codec::{Decode, Encode};
 use polkadot_introspector_essentials::{metadata::polkadot_primitives::BackedCandidate, types::H256};
-use subxt::config::{substrate::BlakeTwo256, Hasher};
 
 /// The parachain block tracking information.
 /// This is used for displaying CLI updates and also goes to Storage.
-#[derive(Encode, Decode, Debug, Default)]
+#[derive(Clone, Encode, Decode, Debug, Default)]
 pub struct ParachainBlockInfo {
 	/// The candidate information as observed during backing
 	pub candidate: Option<BackedCandidate<H256>>,
@@ -36,13 +36,15 @@ pub struct ParachainBlockInfo {
 	pub current_availability_bits: u32,
 	/// Parachain availability core assignment information.
 	pub assigned_core: Option<u32>,
+	/// All availability cores assigned to the parachain, more than one with elastic scaling.
+	pub assigned_cores: Vec<u32>,
 	/// Core occupation status.
 	pub core_occupied: bool,
 }
 
 impl ParachainBlockInfo {
 	pub fn maybe_reset(&mut self) {
-		if self.is_included() {
+		if self.is_included() || self.is_timed_out() {
 			self.state = ParachainBlockState::Idle;
 			self.candidate = None;
 			self.candidate_hash = None;
@@ -65,10 +67,12 @@ impl ParachainBlockInfo {
 		self.state = ParachainBlockState::Included
 	}
 
+	pub fn set_timed_out(&mut self) {
+		self.state = ParachainBlockState::TimedOut
+	}
+
 	pub fn set_candidate(&mut self, candidate: BackedCandidate<H256>) {
-		let commitments_hash = BlakeTwo256::hash_of(&candidate.candidate.commitments);
-		let candidate_hash = BlakeTwo256::hash_of(&(&candidate.candidate.descriptor, commitments_hash));
-		self.candidate_hash = Some(candidate_hash);
+		self.candidate_hash = Some(candidate_hash(&candidate));
 		self.candidate = Some(candidate);
 	}
 
@@ -88,6 +92,21 @@ impl ParachainBlockInfo {
 		self.state == ParachainBlockState::Included
 	}
 
+	pub fn is_timed_out(&self) -> bool {
+		self.state == ParachainBlockState::TimedOut
+	}
+
+	/// Name of the current state, used in the progress output.
+	pub fn state_name(&self) -> &'static str {
+		match self.state {
+			ParachainBlockState::Idle => "idle",
+			ParachainBlockState::Backed => "backed",
+			ParachainBlockState::PendingAvailability => "pending",
+			ParachainBlockState::Included => "included",
+			ParachainBlockState::TimedOut => "timed_out",
+		}
+	}
+
 	pub fn is_data_available(&self) -> bool {
 		self.current_availability_bits > (self.max_availability_bits / 3) * 2
 	}
@@ -109,6 +128,8 @@ enum ParachainBlockState {
 	PendingAvailability,
 	// A candidate has been included.
 	Included,
+	// A candidate has been evicted from the availability core without inclusion.
+	TimedOut,
 }
 
 #[cfg(test)]
@@ -147,6 +168,32 @@ mod tests {
 		assert!(info.candidate_hash.is_none());
 	}
 
+	#[test]
+	fn test_resets_state_if_timed_out() {
+		let mut info = create_para_block_info();
+		info.set_timed_out();
+
+		assert!(info.is_timed_out());
+
+		info.maybe_reset();
+
+		assert!(info.is_idle());
+		assert!(info.candidate.is_none());
+		assert!(info.candidate_hash.is_none());
+	}
+
+	#[test]
+	fn test_state_name() {
+		let mut info = create_para_block_info();
+		assert_eq!(info.state_name(), "idle");
+
+		info.set_pending();
+		assert_eq!(info.state_name(), "pending");
+
+		info.set_timed_out();
+		assert_eq!(info.state_name(), "timed_out");
+	}
+
 	#[test]
 	fn test_is_data_available() {
 		let mut info = create_para_block_info();
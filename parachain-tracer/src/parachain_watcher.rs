@@ -0,0 +1,542 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Follows a parachain in the updates of the collector.
+//!
+//! The watcher keeps the state of the features enabled for the parachain. Every kind of collector update has a
+//! handler, and so has every feature checking the progress of the parachain in a relay chain block.
+
+use crate::{
+	alert_manager::{AlertKey, AlertManager},
+	alerts::{AlertWebhook, StallDetector, StallKind},
+	approval_noshows::NoShowDetector,
+	candidate_timeline::{CandidateTimelineFile, CandidateTimelines},
+	collators::CollatorTracker,
+	group_rotation::GroupRotationTracker,
+	head_divergence::HeadDivergenceDetector,
+	hooks::ScriptHooks,
+	hrmp_channels::HrmpChannelMonitor,
+	incident_sources,
+	maintenance::MaintenanceTracker,
+	notify::{Notification, Notifier},
+	para_config::ParaConfig,
+	para_heads::ParaHeadVerifier,
+	para_names::ParaNames,
+	post_stall_alert,
+	pov::BlockSizeFetcher,
+	print_summary, process_hook_outputs,
+	progress_record::ProgressRecord,
+	prometheus::{Metrics, PrometheusMetrics},
+	stats::ParachainStats,
+	summary_file::SummaryFile,
+	system_parachains::ActivityCounter,
+	tracker::SubxtTracker,
+	tracker_rpc::ParachainTrackerRpc,
+	tracker_storage::TrackerStorage,
+	tui::TuiHandle,
+	types::ParachainProgressUpdate,
+	watch_expressions::WatchExpressions,
+	ParachainTracer, ParachainTracerMode,
+};
+use log::{error, info, warn};
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::RequestExecutor,
+	collector::{CollectorStorageApi, NewHeadEvent},
+	incidents::Incidents,
+	output::OutputWriter,
+};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+pub(crate) struct ParachainWatcher {
+	para_id: u32,
+	tracker: SubxtTracker,
+	rpc: ParachainTrackerRpc,
+	storage: TrackerStorage,
+	executor: RequestExecutor,
+	node: String,
+	metrics: Metrics,
+	stats: ParachainStats,
+	is_cli: bool,
+	is_json: bool,
+	per_session_summary: bool,
+	output: Arc<OutputWriter>,
+	tui: Option<TuiHandle>,
+	incidents: Incidents,
+	para_names: ParaNames,
+	summary_file: Option<SummaryFile>,
+	candidate_timeline_file: Option<CandidateTimelineFile>,
+	candidate_timelines: Option<CandidateTimelines>,
+	hooks: Option<ScriptHooks>,
+	watch_expressions: Option<Arc<WatchExpressions>>,
+	/// Alert thresholds of the command line, overridden by the parachain config file if any
+	alert_no_backed: Option<u32>,
+	alert_no_included: Option<u32>,
+	para_config: Option<watch::Receiver<ParaConfig>>,
+	stall_detector: StallDetector,
+	alert_webhook: Option<AlertWebhook>,
+	alert_manager: AlertManager,
+	notifier: Option<Notifier>,
+	maintenance_tracker: Option<MaintenanceTracker>,
+	activity_counter: Option<ActivityCounter>,
+	block_size_fetcher: Option<BlockSizeFetcher>,
+	head_verifier: Option<ParaHeadVerifier>,
+	divergence_detector: Option<HeadDivergenceDetector>,
+	hrmp_monitor: Option<HrmpChannelMonitor>,
+	collator_tracker: Option<CollatorTracker>,
+	group_rotation_tracker: Option<GroupRotationTracker>,
+	no_show_detector: Option<NoShowDetector>,
+}
+
+impl ParachainWatcher {
+	pub(crate) fn new(tracer: &ParachainTracer, para_id: u32, api_service: CollectorStorageApi) -> Self {
+		let opts = &tracer.opts;
+		let tracker = SubxtTracker::new(para_id, opts.max_relay_parent_age, opts.baseline_options())
+			.with_explorer(tracer.explorer.clone())
+			.with_para_names(tracer.para_names.clone())
+			.with_minimum_backing_votes(tracer.minimum_backing_votes);
+		let stats = ParachainStats::new(para_id, opts.last_skipped_slot_blocks, opts.stats_window)
+			.with_explorer(tracer.explorer.clone())
+			.with_para_names(tracer.para_names.clone());
+		let (alert_no_backed, alert_no_included) = (opts.alert_no_backed, opts.alert_no_included);
+		let mut para_config = tracer.para_config.clone();
+		let stall_detector = match para_config {
+			Some(ref mut config) => {
+				let (no_backed, no_included) =
+					config
+						.borrow_and_update()
+						.alert_thresholds(para_id, alert_no_backed, alert_no_included);
+				StallDetector::new(no_backed, no_included)
+			},
+			None => StallDetector::new(alert_no_backed, alert_no_included),
+		};
+		let candidate_timelines = tracer
+			.candidate_timeline_file
+			.as_ref()
+			.map(|_| CandidateTimelines::new(para_id));
+		let metrics = tracer.metrics.clone();
+		metrics.on_para_name(para_id, tracer.para_names.get(para_id).as_deref().unwrap_or_default());
+
+		Self {
+			para_id,
+			tracker,
+			rpc: ParachainTrackerRpc::new(para_id, tracer.node.as_str(), api_service.subxt()),
+			storage: TrackerStorage::new(para_id, api_service.storage()),
+			executor: api_service.subxt(),
+			node: tracer.node.clone(),
+			metrics,
+			stats,
+			is_cli: matches!(&opts.mode, Some(ParachainTracerMode::Cli)),
+			is_json: opts.is_json(),
+			per_session_summary: opts.per_session_summary,
+			output: tracer.output.clone(),
+			tui: tracer.tui.clone(),
+			incidents: tracer.incidents.clone(),
+			para_names: tracer.para_names.clone(),
+			summary_file: tracer.summary_file.clone(),
+			candidate_timeline_file: tracer.candidate_timeline_file.clone(),
+			candidate_timelines,
+			hooks: tracer.hooks.clone(),
+			watch_expressions: tracer.watch_expressions.clone(),
+			alert_no_backed,
+			alert_no_included,
+			para_config,
+			stall_detector,
+			alert_webhook: tracer.alert_webhook.clone(),
+			alert_manager: AlertManager::new(opts.alert_cooldown).with_explorer(tracer.explorer.clone()),
+			notifier: tracer.notifier.clone(),
+			maintenance_tracker: opts.maintenance.windows().map(MaintenanceTracker::new),
+			activity_counter: ActivityCounter::spawn(
+				para_id,
+				&tracer.system_parachains,
+				&tracer.retry,
+				&tracer.context,
+			),
+			block_size_fetcher: BlockSizeFetcher::spawn(para_id, &tracer.para_nodes, &tracer.retry, &tracer.context),
+			head_verifier: ParaHeadVerifier::new(para_id, &tracer.para_nodes, &tracer.retry, &tracer.context),
+			divergence_detector: HeadDivergenceDetector::spawn(
+				para_id,
+				&opts.node,
+				&opts.compare_ws,
+				opts.head_divergence_blocks,
+				&tracer.retry,
+				&tracer.context,
+			),
+			hrmp_monitor: opts
+				.track_hrmp
+				.then(|| HrmpChannelMonitor::new(para_id, opts.hrmp_fill_warning)),
+			collator_tracker: opts.show_collators.then(|| CollatorTracker::new(opts.collators_top)),
+			group_rotation_tracker: tracer
+				.group_rotation_frequency
+				.filter(|_| opts.show_group_rotations)
+				.map(GroupRotationTracker::new),
+			no_show_detector: tracer.no_show_slots.map(NoShowDetector::new),
+		}
+	}
+
+	/// Processes the relay parent of a new head and its forks
+	pub(crate) async fn on_new_head(&mut self, new_head: NewHeadEvent) {
+		self.update_para_config();
+		self.storage.set_block_contexts(new_head.block_contexts.clone());
+		let is_forked = new_head.relay_parent_hashes.len() > 1;
+		for relay_fork in &new_head.relay_parent_hashes {
+			let parent_number = new_head.relay_parent_number;
+			if let Err(e) = self
+				.tracker
+				.inject_block(*relay_fork, parent_number, is_forked, &mut self.rpc, &self.storage)
+				.await
+			{
+				error!("error occurred when processing block {}: {:?}", relay_fork, e);
+				std::process::exit(1);
+			}
+			// Alerts were raised on the forks, the replayed blocks only correct the outputs
+			for progress in self.tracker.take_replayed_progress() {
+				self.print_progress(&progress);
+			}
+			if let Some(progress) = self.tracker.progress(&mut self.stats, &self.metrics, &self.storage).await {
+				self.on_progress(progress).await;
+			}
+			self.tracker.maybe_reset_state();
+		}
+	}
+
+	pub(crate) fn on_new_session(&mut self, idx: u32) {
+		self.tracker.inject_new_session(idx);
+		if let Some(summary) = self
+			.group_rotation_tracker
+			.as_mut()
+			.and_then(|tracker| tracker.on_new_session(idx))
+		{
+			print_summary(&summary, self.is_cli);
+		}
+		if let Some(summary) = self.stats.on_new_session(idx).filter(|_| self.per_session_summary) {
+			self.metrics.on_session_summary(&summary);
+			print_summary(&summary, self.is_cli);
+		}
+	}
+
+	/// Prints the summaries of the parachain
+	pub(crate) fn print_summaries(&self) {
+		print_summary(&self.stats, self.is_cli);
+		if let Some(ref collator_tracker) = self.collator_tracker {
+			print_summary(collator_tracker, self.is_cli);
+		}
+		if let Some(ref group_rotation_tracker) = self.group_rotation_tracker {
+			print_summary(&group_rotation_tracker.summary(), self.is_cli);
+		}
+		if let Some(ref hrmp_monitor) = self.hrmp_monitor {
+			print_summary(hrmp_monitor, self.is_cli);
+		}
+		if let Some(ref maintenance_tracker) = self.maintenance_tracker {
+			print_summary(maintenance_tracker, self.is_cli);
+		}
+	}
+
+	/// Prints the summaries and writes the files of the parachain once it's not followed anymore
+	pub(crate) fn finish(self) -> u32 {
+		self.print_summaries();
+		if let Some(summary_file) = self.summary_file {
+			summary_file.record(self.stats.summary());
+		}
+		if let (Some(timelines), Some(file)) = (self.candidate_timelines, self.candidate_timeline_file) {
+			file.write(&timelines.finish());
+		}
+
+		self.para_id
+	}
+
+	/// Takes the alert thresholds and the name of the parachain from the config file if it has changed
+	fn update_para_config(&mut self) {
+		if let Some(config) = self.para_config.as_mut().filter(|v| v.has_changed().unwrap_or_default()) {
+			let (no_backed, no_included) =
+				config
+					.borrow_and_update()
+					.alert_thresholds(self.para_id, self.alert_no_backed, self.alert_no_included);
+			self.stall_detector.set_thresholds(no_backed, no_included);
+			self.metrics
+				.on_para_name(self.para_id, self.para_names.get(self.para_id).as_deref().unwrap_or_default());
+		}
+	}
+
+	fn print_progress(&self, progress: &ParachainProgressUpdate) {
+		if self.is_cli {
+			println!("{}", progress)
+		} else if self.is_json {
+			if let Err(e) = self.output.print(&ProgressRecord::from(progress)) {
+				error!("Cannot print a progress record: {:?}", e);
+			}
+		} else if let Some(ref tui) = self.tui {
+			tui.on_progress(progress, self.stats.summary());
+		}
+	}
+
+	/// Runs the features on the progress of the parachain, the ones following the chain skip the forks
+	async fn on_progress(&mut self, mut progress: ParachainProgressUpdate) {
+		// Alerts are still printed in a maintenance window, only the sinks are muted
+		let in_maintenance = self.maintenance_tracker.as_mut().is_some_and(|v| v.on_progress(&progress));
+		progress.in_maintenance = in_maintenance;
+		self.print_progress(&progress);
+		self.on_hooks(&progress);
+		self.on_watch_expressions(&progress);
+		self.on_stall_alerts(&progress, in_maintenance);
+		if progress.is_fork {
+			return
+		}
+
+		self.on_resolved_alerts(&progress, in_maintenance);
+		self.on_notifications(&progress, in_maintenance);
+		self.on_candidate_activity(&progress);
+		self.on_candidate_sizes();
+		self.on_collators(&progress);
+		self.on_candidate_timelines(&progress);
+		self.on_message_queues(&progress);
+		self.on_para_heads(&progress, in_maintenance).await;
+		self.on_head_divergence(&progress, in_maintenance);
+		self.on_approval_noshows(&progress);
+		self.on_group_rotation(&progress).await;
+		incident_sources::record_progress(&self.incidents, &progress);
+		if let Some(elapsed) = self.stats.maybe_rotate_window(progress.timestamp) {
+			print_summary(&elapsed, self.is_cli);
+		}
+	}
+
+	fn on_hooks(&self, progress: &ParachainProgressUpdate) {
+		if let Some(ref hooks) = self.hooks {
+			let outputs = hooks.on_progress(&ProgressRecord::from(progress));
+			process_hook_outputs(
+				outputs,
+				progress.block_number,
+				progress.timestamp,
+				&self.metrics,
+				&self.incidents,
+				self.is_cli,
+			);
+		}
+	}
+
+	fn on_watch_expressions(&self, progress: &ParachainProgressUpdate) {
+		if let Some(ref watch_expressions) = self.watch_expressions {
+			for (name, value) in watch_expressions.evaluate(&ProgressRecord::from(progress)) {
+				self.metrics.on_watch_expression(name, value, self.para_id);
+			}
+		}
+	}
+
+	fn on_stall_alerts(&mut self, progress: &ParachainProgressUpdate, in_maintenance: bool) {
+		let (alert_webhook, notifier) =
+			if in_maintenance { (None, None) } else { (self.alert_webhook.as_ref(), self.notifier.as_ref()) };
+		for alert in self.stall_detector.on_progress(progress) {
+			self.metrics.on_stall_alert(&alert);
+			if self.is_cli {
+				println!("{}", alert)
+			} else {
+				warn!("{}", alert)
+			}
+			// Re-evaluated when the window ends
+			if let Some(tracker) = self.maintenance_tracker.as_mut().filter(|_| in_maintenance) {
+				tracker.defer(alert);
+				continue
+			}
+			post_stall_alert(&alert, &mut self.alert_manager, alert_webhook, notifier);
+		}
+	}
+
+	fn on_resolved_alerts(&mut self, progress: &ParachainProgressUpdate, in_maintenance: bool) {
+		if let Some(tracker) = self.maintenance_tracker.as_mut().filter(|_| !in_maintenance) {
+			for alert in tracker.take_deferred(|kind| self.stall_detector.is_stalled(kind)) {
+				post_stall_alert(&alert, &mut self.alert_manager, self.alert_webhook.as_ref(), self.notifier.as_ref());
+			}
+		}
+		for kind in [StallKind::NoBacked, StallKind::NoIncluded] {
+			if !self.stall_detector.is_stalled(kind) {
+				let key = AlertKey::new(kind.as_str(), Some(self.para_id));
+				self.alert_manager.on_clear(&key, progress.block_number);
+			}
+		}
+		// The sinks have received the alerts being resolved, so resolutions are never muted
+		for resolution in self.alert_manager.on_block(progress.block_number) {
+			if self.is_cli {
+				println!("{}", resolution)
+			} else {
+				info!("{}", resolution)
+			}
+			if let Some(ref webhook) = self.alert_webhook {
+				webhook.post(&resolution);
+			}
+			if let Some(ref notifier) = self.notifier {
+				notifier.notify(&(&resolution).into());
+			}
+		}
+	}
+
+	fn on_notifications(&self, progress: &ParachainProgressUpdate, in_maintenance: bool) {
+		if let Some(notifier) = self.notifier.as_ref().filter(|_| !in_maintenance) {
+			for notification in Notification::from_progress(progress) {
+				notifier.notify(&notification);
+			}
+		}
+	}
+
+	fn on_candidate_activity(&mut self, progress: &ParachainProgressUpdate) {
+		let Some(ref mut activity_counter) = self.activity_counter else { return };
+		for activity in activity_counter.on_progress(progress, self.tracker.backed_candidates()) {
+			self.metrics.on_candidate_activity(&activity, self.para_id);
+			if self.is_cli {
+				println!("{}", activity)
+			}
+		}
+	}
+
+	fn on_candidate_sizes(&mut self) {
+		let Some(ref mut block_size_fetcher) = self.block_size_fetcher else { return };
+		let (sizes, skipped) = block_size_fetcher.on_candidates(self.tracker.backed_candidates());
+		if skipped > 0 {
+			self.metrics.on_pov_candidates_skipped(self.para_id, skipped);
+		}
+		for size in sizes {
+			self.metrics.on_candidate_size(&size, self.para_id);
+			if let Some(pov_size) = size.pov_size {
+				self.stats.on_pov_size(pov_size);
+			}
+			if self.is_cli {
+				println!("{}", size)
+			}
+		}
+	}
+
+	fn on_collators(&mut self, progress: &ParachainProgressUpdate) {
+		if let Some(ref mut collator_tracker) = self.collator_tracker {
+			collator_tracker.on_progress(progress, self.tracker.backed_candidates());
+		}
+	}
+
+	fn on_candidate_timelines(&mut self, progress: &ParachainProgressUpdate) {
+		if let (Some(timelines), Some(file)) =
+			(self.candidate_timelines.as_mut(), self.candidate_timeline_file.as_ref())
+		{
+			file.write(&timelines.on_progress(progress, self.tracker.backed_candidates()));
+		}
+	}
+
+	fn on_message_queues(&mut self, progress: &ParachainProgressUpdate) {
+		if let Some(ref depths) = progress.message_queues {
+			self.metrics.on_message_queue_depths(depths, self.para_id);
+		}
+		let Some(ref mut hrmp_monitor) = self.hrmp_monitor else { return };
+		let message_queues = self.tracker.message_queues();
+		for warning in hrmp_monitor.on_channels(
+			progress.block_number,
+			&message_queues.inbound_hrmp_channels,
+			&message_queues.outbound_hrmp_channels,
+		) {
+			if self.is_cli {
+				print!("{}", warning)
+			} else {
+				warn!("{}", warning)
+			}
+		}
+		for channel in hrmp_monitor.channels() {
+			self.metrics.on_hrmp_channel(channel, self.para_id);
+		}
+		for (sender, recipient) in hrmp_monitor.closed_channels() {
+			self.metrics.on_hrmp_channel_closed(*sender, *recipient, self.para_id);
+		}
+	}
+
+	async fn on_para_heads(&mut self, progress: &ParachainProgressUpdate, in_maintenance: bool) {
+		let Some(ref mut head_verifier) = self.head_verifier else { return };
+		match head_verifier.on_progress(progress, self.tracker.backed_candidates()).await {
+			Ok(mismatches) =>
+				for mismatch in mismatches {
+					self.metrics.on_para_head_mismatch(self.para_id);
+					if self.is_cli {
+						println!("{}", mismatch)
+					} else {
+						warn!("{}", mismatch)
+					}
+					if let Some(tracker) = self.maintenance_tracker.as_mut().filter(|_| in_maintenance) {
+						tracker.on_suppressed_alert();
+					}
+					if let Some(notifier) = self.notifier.as_ref().filter(|_| !in_maintenance) {
+						notifier.notify(&(&mismatch).into());
+					}
+				},
+			Err(e) => warn!("Cannot verify the heads of the parachain: {:?}", e),
+		}
+	}
+
+	fn on_head_divergence(&mut self, progress: &ParachainProgressUpdate, in_maintenance: bool) {
+		let Some(ref mut divergence_detector) = self.divergence_detector else { return };
+		for divergence in divergence_detector.on_progress(progress) {
+			self.metrics.on_para_head_divergence(&divergence.node, self.para_id);
+			if self.is_cli {
+				print!("{}", divergence)
+			} else {
+				warn!("{}", divergence)
+			}
+			if let Some(tracker) = self.maintenance_tracker.as_mut().filter(|_| in_maintenance) {
+				tracker.on_suppressed_alert();
+			}
+			if let Some(notifier) = self.notifier.as_ref().filter(|_| !in_maintenance) {
+				notifier.notify(&(&divergence).into());
+			}
+		}
+	}
+
+	fn on_approval_noshows(&mut self, progress: &ParachainProgressUpdate) {
+		let Some(ref mut no_show_detector) = self.no_show_detector else { return };
+		for no_show in no_show_detector.on_progress(progress) {
+			self.metrics.on_approval_noshow_suspected(self.para_id);
+			if self.is_cli {
+				print!("{}", no_show)
+			} else {
+				warn!("{}", no_show)
+			}
+		}
+	}
+
+	async fn on_group_rotation(&mut self, progress: &ParachainProgressUpdate) {
+		let Some(ref mut group_rotation_tracker) = self.group_rotation_tracker else { return };
+		if group_rotation_tracker.needs_session_start_block() {
+			let block_number = self
+				.executor
+				.get_session_start_block(&self.node, progress.block_hash)
+				.await
+				.map_err(|e| {
+					warn!(
+						"Cannot get the session start block, group rotations are not shown until the next session: {:?}",
+						e
+					)
+				})
+				.ok();
+			group_rotation_tracker.set_session_start_block(block_number);
+		}
+		let groups_count = self
+			.storage
+			.backing_groups(progress.block_hash)
+			.await
+			.map_or(0, |groups| groups.len());
+		if let Some(assignment) =
+			group_rotation_tracker.on_progress(progress, self.tracker.assigned_core(), groups_count)
+		{
+			if self.is_cli {
+				print!("{}", assignment)
+			} else if assignment.rotated_from.is_some() || assignment.missed_backing {
+				info!("{}", assignment)
+			}
+		}
+	}
+}
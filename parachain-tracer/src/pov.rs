@@ -0,0 +1,235 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sizes of the backed candidates of a parachain.
+//!
+//! The proof of validity of a candidate is not part of the relay chain, the candidate descriptor only commits to
+//! its hash and the receipt carries no size. The block data of the PoV is the parachain block though, and the head
+//! data of a backed candidate is the encoded header of that block, so with a node of the parachain the encoded size
+//! of the block is reported. It doesn't include the storage proof, so it's a lower bound of the PoV size. The weight
+//! consumed by the block is read from the node too. The blocks are fetched in a task of their own, off the parachain
+//! task; if it falls behind, the candidates beyond its queue are skipped. The commitments of the candidate are on the
+//! relay chain and reported along with it.
+
+use crate::para_nodes::{para_node, ParaNode};
+use log::{debug, warn};
+use parity_scale_codec::{Compact, Decode, Encode};
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::{ExecutorContext, RequestExecutor},
+	metadata::polkadot_primitives::BackedCandidate,
+	types::{BlockNumber, BlockWeight, Header, H256},
+	utils::RetryOptions,
+};
+use std::fmt::{self, Display, Formatter};
+use subxt::config::{substrate::BlakeTwo256, Hasher};
+use tokio::sync::mpsc::{channel, error::TrySendError, Receiver, Sender};
+
+/// Number of candidates waiting for the fetcher, the following ones are skipped
+const FETCHER_QUEUE_SIZE: usize = 64;
+
+/// Sizes of a backed candidate
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CandidateSize {
+	/// Number of the parachain block, if its header can be decoded
+	pub para_block_number: Option<BlockNumber>,
+	pub para_block_hash: H256,
+	/// Encoded size of the parachain block, a lower bound of the PoV size as the storage proof is not included,
+	/// if the block was found
+	pub pov_size: Option<u32>,
+	/// Weight consumed by the parachain block, if the block was found
+	pub block_weight: Option<BlockWeight>,
+	/// Encoded size of the head data
+	pub head_data_size: u32,
+	/// Encoded size of the commitments: head data, upward and horizontal messages and new validation code
+	pub commitments_size: u32,
+	/// Number of downward messages processed by the candidate
+	pub processed_downward_messages: u32,
+}
+
+impl CandidateSize {
+	/// Returns the sizes of a candidate known from the relay chain
+	pub(crate) fn new(candidate: &BackedCandidate<H256>) -> Self {
+		let commitments = &candidate.candidate.commitments;
+		let head_data = &commitments.head_data.0;
+		Self {
+			para_block_number: Header::decode(&mut &head_data[..]).ok().map(|header| header.number),
+			// The head data is the encoded header, so its hash is the hash of the parachain block
+			para_block_hash: BlakeTwo256::hash(head_data),
+			pov_size: None,
+			block_weight: None,
+			head_data_size: head_data.len() as u32,
+			commitments_size: commitments.encoded_size() as u32,
+			processed_downward_messages: commitments.processed_downward_messages,
+		}
+	}
+}
+
+impl Display for CandidateSize {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "parachain block ")?;
+		match self.para_block_number {
+			Some(number) => write!(f, "#{}", number)?,
+			None => write!(f, "{:?}", self.para_block_hash)?,
+		}
+		match self.pov_size {
+			Some(size) => write!(f, ", PoV size at least {:.1} KiB", size as f64 / 1024.0)?,
+			None => write!(f, ", PoV size unknown")?,
+		}
+		if let Some(weight) = self.block_weight {
+			write!(
+				f,
+				", weight {:.1} ms, {:.1} KiB proof",
+				weight.ref_time as f64 / 1_000_000_000.0,
+				weight.proof_size as f64 / 1024.0
+			)?;
+		}
+		write!(
+			f,
+			", commitments {} bytes, {} downward messages processed",
+			self.commitments_size, self.processed_downward_messages
+		)
+	}
+}
+
+/// Measures the parachain blocks of the backed candidates with a node of the parachain
+pub(crate) struct BlockSizeFetcher {
+	/// Sizes of the backed candidates known from the relay chain, passed to the fetcher
+	candidates: Sender<CandidateSize>,
+	sizes: Receiver<CandidateSize>,
+	/// Number of candidates skipped as the fetcher fell behind
+	skipped: u64,
+}
+
+impl BlockSizeFetcher {
+	/// Spawns a fetcher of the parachain blocks if the parachain has a node. It stops when the fetcher is dropped
+	pub(crate) fn spawn(
+		para_id: u32,
+		nodes: &[ParaNode],
+		retry: &RetryOptions,
+		context: &ExecutorContext,
+	) -> Option<Self> {
+		let node = para_node(nodes, para_id)?;
+		let (candidates, candidates_rx) = channel(FETCHER_QUEUE_SIZE);
+		let (sizes_tx, sizes) = channel(FETCHER_QUEUE_SIZE);
+		let fetcher = BlockFetcher {
+			para_id,
+			node: node.url.clone(),
+			executor: RequestExecutor::new(retry.clone(), context.clone()),
+		};
+		tokio::spawn(fetcher.run(candidates_rx, sizes_tx));
+
+		Some(Self { candidates, sizes, skipped: 0 })
+	}
+
+	/// Passes the backed candidates to the fetcher, returns the sizes it has measured since the last call and the
+	/// number of candidates skipped as its queue is full
+	pub(crate) fn on_candidates(&mut self, backed_candidates: &[BackedCandidate<H256>]) -> (Vec<CandidateSize>, u32) {
+		let sizes = std::iter::from_fn(|| self.sizes.try_recv().ok()).collect();
+		let mut skipped = 0;
+		for candidate in backed_candidates {
+			// The fetcher stops only when this is dropped, so the queue can only be full
+			if let Err(TrySendError::Full(size)) = self.candidates.try_send(CandidateSize::new(candidate)) {
+				debug!("Block fetcher is behind, skipping parachain block {:?}", size.para_block_hash);
+				skipped += 1;
+			}
+		}
+		if skipped > 0 {
+			self.skipped += skipped as u64;
+			warn!("Block fetcher is behind, {} candidates skipped since the start", self.skipped);
+		}
+		(sizes, skipped)
+	}
+}
+
+/// Fetches the parachain blocks off the parachain task
+struct BlockFetcher {
+	para_id: u32,
+	/// RPC node of the parachain
+	node: String,
+	executor: RequestExecutor,
+}
+
+impl BlockFetcher {
+	async fn run(mut self, mut candidates: Receiver<CandidateSize>, sizes: Sender<CandidateSize>) {
+		while let Some(mut size) = candidates.recv().await {
+			match self.block_size(&size).await {
+				Ok(block_size) => size.pov_size = Some(block_size),
+				Err(e) => warn!("Cannot fetch a block of parachain {}: {:?}", self.para_id, e),
+			}
+			match self.executor.get_block_weight(&self.node, size.para_block_hash).await {
+				Ok(block_weight) => size.block_weight = block_weight,
+				Err(e) => warn!("Cannot fetch the block weight of parachain {}: {:?}", self.para_id, e),
+			}
+			if sizes.send(size).await.is_err() {
+				return
+			}
+		}
+	}
+
+	/// Returns the encoded size of the parachain block of a candidate
+	async fn block_size(&mut self, size: &CandidateSize) -> color_eyre::Result<u32> {
+		let block = self.executor.get_block(&self.node, Some(size.para_block_hash)).await?;
+		let extrinsics = block.extrinsics().await?;
+		let mut extrinsics_size = 0;
+		for extrinsic in extrinsics.iter() {
+			extrinsics_size += extrinsic?.bytes().len();
+		}
+
+		Ok(block_size(size.head_data_size as usize, extrinsics.len(), extrinsics_size) as u32)
+	}
+}
+
+/// Encoded size of a block: its header and the compact length prefixed extrinsics
+fn block_size(header_size: usize, extrinsics_count: usize, extrinsics_size: usize) -> usize {
+	header_size + Compact(extrinsics_count as u32).encoded_size() + extrinsics_size
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::create_backed_candidate;
+
+	#[test]
+	fn test_measures_candidate() {
+		let mut candidate = create_backed_candidate(100);
+		candidate.candidate.commitments.processed_downward_messages = 3;
+		let size = CandidateSize::new(&candidate);
+
+		assert_eq!(size.pov_size, None);
+		assert_eq!(size.block_weight, None);
+		assert_eq!(size.processed_downward_messages, 3);
+		assert_eq!(size.commitments_size, candidate.candidate.commitments.encoded_size() as u32);
+		assert_eq!(block_size(100, 2, 300), 401);
+	}
+
+	#[test]
+	fn test_skips_candidates_when_behind() {
+		let (candidates, _candidates_rx) = channel(2);
+		let (sizes_tx, sizes) = channel(2);
+		let mut fetcher = BlockSizeFetcher { candidates, sizes, skipped: 0 };
+		let backed_candidates: Vec<_> = (0..3).map(|_| create_backed_candidate(100)).collect();
+
+		let (measured, skipped) = fetcher.on_candidates(&backed_candidates);
+		assert!(measured.is_empty());
+		assert_eq!(skipped, 1);
+
+		sizes_tx.try_send(CandidateSize::new(&backed_candidates[0])).unwrap();
+		let (measured, skipped) = fetcher.on_candidates(&backed_candidates);
+		assert_eq!(measured.len(), 1);
+		assert_eq!(skipped, 3);
+		assert_eq!(fetcher.skipped, 4);
+	}
+}
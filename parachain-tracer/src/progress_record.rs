@@ -0,0 +1,375 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Machine-readable records of the parachain progress, printed in the `--output` format in the JSON mode
+//! instead of the colored CLI output. The format is described by the `progress` schema.
+
+use crate::types::{CoreProgress, DisputesTracker, Latency, ParachainConsensusEvent, ParachainProgressUpdate};
+use polkadot_introspector_essentials::{
+	chain_events::SubxtDisputeResult,
+	output::OutputRecord,
+	types::{BlockNumber, Timestamp, H256},
+};
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+
+/// Progress of a parachain at a relay chain block
+#[derive(Clone, Debug, Serialize)]
+pub struct ProgressRecord {
+	/// Parachain id
+	pub para_id: u32,
+	/// Parachain name, if known
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub para_name: Option<String>,
+	/// Relay chain block number
+	pub block_number: BlockNumber,
+	/// Relay chain block hash
+	pub block_hash: H256,
+	/// Relay chain block timestamp, in milliseconds
+	pub timestamp: Timestamp,
+	/// Time since the previous relay chain block, in milliseconds
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub block_time_ms: Option<u64>,
+	/// If the block is on a fork
+	pub is_fork: bool,
+	/// Best block number minus the last finalized block number
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub finality_lag: Option<u32>,
+	/// Core assigned to the parachain
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub core_index: Option<u32>,
+	/// If the core is occupied by a candidate pending availability
+	pub core_occupied: bool,
+	/// Availability bits of the parachain in the block
+	pub availability: AvailabilityRecord,
+	/// Candidate backed in the block
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub backed: Option<BackedRecord>,
+	/// Candidate included in the block
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub included: Option<IncludedRecord>,
+	/// No candidate was backed though the core was free
+	pub skipped_slot: bool,
+	/// Why the slot was skipped: `not_scheduled`, `no_candidate`, `insufficient_backing` or `core_occupied`
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub skipped_slot_reason: Option<&'static str>,
+	/// Backing latency of the included candidate, from its relay parent
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub backed_in: Option<LatencyRecord>,
+	/// Latency since the previous candidate inclusion
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub included_after: Option<LatencyRecord>,
+	/// Age of the relay parent of the backed candidate, in relay chain blocks
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub relay_parent_age: Option<u32>,
+	/// Number of candidates backed and not yet included on chain
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub unincluded_depth: Option<u32>,
+	/// Number of candidates included in the block
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub included_count: Option<u32>,
+	/// Candidates per availability core, only set if the parachain uses several cores
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub cores: Vec<CoreRecord>,
+	/// Disputes concluded in the block
+	pub disputes: Vec<DisputeRecord>,
+	/// Other events worth looking into
+	pub alerts: Vec<&'static str>,
+	/// The block is in a maintenance window
+	pub in_maintenance: bool,
+	/// The block is replayed on the chosen fork after the relay chain forks were resolved
+	pub is_replayed: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AvailabilityRecord {
+	/// Number of signed bitfields in the block
+	pub bitfield_count: u32,
+	/// Expected number of bitfields, equal to the number of validators
+	pub max_bitfield_count: u32,
+	/// Number of bits set for the parachain
+	pub available_count: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BackedRecord {
+	pub candidate_hash: H256,
+	pub validity_votes: u32,
+	pub group_size: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct IncludedRecord {
+	pub candidate_hash: H256,
+	pub bits_available: u32,
+	pub max_bits: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CoreRecord {
+	pub core_index: u32,
+	pub candidate_hash: H256,
+	/// `backed`, `pending`, `included` or `timed_out`
+	pub state: &'static str,
+	pub bits_available: u32,
+	pub max_bits: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LatencyRecord {
+	/// Number of relay chain blocks
+	pub blocks: u32,
+	/// Time between the relay chain blocks, in milliseconds
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DisputeRecord {
+	pub candidate_hash: H256,
+	pub session_index: u32,
+	/// `valid`, `invalid` or `timed_out`
+	pub outcome: &'static str,
+	pub voted_for: u32,
+	pub voted_against: u32,
+	/// Number of relay chain blocks from initiation to conclusion
+	pub resolve_time: u32,
+}
+
+impl From<&Latency> for LatencyRecord {
+	fn from(v: &Latency) -> Self {
+		Self { blocks: v.blocks, ms: v.duration.map(|duration| duration.as_millis() as u64) }
+	}
+}
+
+impl From<&CoreProgress> for CoreRecord {
+	fn from(v: &CoreProgress) -> Self {
+		Self {
+			core_index: v.core_index,
+			candidate_hash: v.candidate_hash,
+			state: v.state,
+			bits_available: v.available_count,
+			max_bits: v.max_availability_bits,
+		}
+	}
+}
+
+impl From<&DisputesTracker> for DisputeRecord {
+	fn from(v: &DisputesTracker) -> Self {
+		Self {
+			candidate_hash: v.candidate,
+			session_index: v.session_index,
+			outcome: match v.outcome {
+				SubxtDisputeResult::Valid => "valid",
+				SubxtDisputeResult::Invalid => "invalid",
+				SubxtDisputeResult::TimedOut => "timed_out",
+			},
+			voted_for: v.voted_for,
+			voted_against: v.voted_against,
+			resolve_time: v.resolve_time,
+		}
+	}
+}
+
+/// A short summary, the colored CLI output is printed in the pretty format instead
+impl OutputRecord for ProgressRecord {
+	const CSV_COLUMNS: &'static [&'static str] = &[
+		"para_id",
+		"para_name",
+		"block_number",
+		"block_hash",
+		"timestamp",
+		"block_time_ms",
+		"is_fork",
+		"finality_lag",
+		"core_index",
+		"core_occupied",
+		"availability.bitfield_count",
+		"availability.max_bitfield_count",
+		"availability.available_count",
+		"backed.candidate_hash",
+		"backed.validity_votes",
+		"backed.group_size",
+		"included.candidate_hash",
+		"included.bits_available",
+		"included.max_bits",
+		"skipped_slot",
+		"skipped_slot_reason",
+		"backed_in.blocks",
+		"backed_in.ms",
+		"included_after.blocks",
+		"included_after.ms",
+		"relay_parent_age",
+		"unincluded_depth",
+		"included_count",
+		"cores",
+		"disputes",
+		"alerts",
+		"in_maintenance",
+		"is_replayed",
+	];
+}
+
+impl Display for ProgressRecord {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"[#{}] parachain {}: backed: {}, included: {}, skipped slot: {}",
+			self.block_number,
+			self.para_id,
+			self.backed.is_some(),
+			self.included.is_some(),
+			self.skipped_slot
+		)
+	}
+}
+
+impl From<&ParachainProgressUpdate> for ProgressRecord {
+	fn from(v: &ParachainProgressUpdate) -> Self {
+		let mut record = Self {
+			para_id: v.para_id,
+			para_name: v.para_names.get(v.para_id).map(|name| name.to_string()),
+			block_number: v.block_number,
+			block_hash: v.block_hash,
+			timestamp: v.timestamp,
+			block_time_ms: (v.prev_timestamp > 0).then(|| v.timestamp.saturating_sub(v.prev_timestamp)),
+			is_fork: v.is_fork,
+			finality_lag: v.finality_lag,
+			core_index: None,
+			core_occupied: v.core_occupied,
+			availability: AvailabilityRecord {
+				bitfield_count: v.bitfield_health.bitfield_count,
+				max_bitfield_count: v.bitfield_health.max_bitfield_count,
+				available_count: v.bitfield_health.available_count,
+			},
+			backed: None,
+			included: None,
+			skipped_slot: false,
+			skipped_slot_reason: None,
+			backed_in: v.backed_in.as_ref().map(LatencyRecord::from),
+			included_after: v.included_after.as_ref().map(LatencyRecord::from),
+			relay_parent_age: v.relay_parent_age,
+			unincluded_depth: v.unincluded_depth,
+			included_count: v.included_count,
+			cores: v.cores.iter().map(CoreRecord::from).collect(),
+			disputes: vec![],
+			alerts: vec![],
+			in_maintenance: v.in_maintenance,
+			is_replayed: v.is_replayed,
+		};
+
+		for event in &v.events {
+			match event {
+				ParachainConsensusEvent::CoreAssigned(core_index) => record.core_index = Some(*core_index),
+				ParachainConsensusEvent::Backed(candidate_hash, validity_votes, group_size) =>
+					record.backed = Some(BackedRecord {
+						candidate_hash: *candidate_hash,
+						validity_votes: *validity_votes,
+						group_size: *group_size,
+					}),
+				ParachainConsensusEvent::Included(candidate_hash, bits_available, max_bits) =>
+					record.included = Some(IncludedRecord {
+						candidate_hash: *candidate_hash,
+						bits_available: *bits_available,
+						max_bits: *max_bits,
+					}),
+				ParachainConsensusEvent::Disputed(dispute) => record.disputes.push(dispute.into()),
+				ParachainConsensusEvent::SkippedSlot(reason) => {
+					record.skipped_slot = true;
+					record.skipped_slot_reason = Some(reason.as_str());
+				},
+				ParachainConsensusEvent::SlowAvailability(_, _) => record.alerts.push("slow_availability"),
+				ParachainConsensusEvent::AvailabilityTimedOut(_) => record.alerts.push("availability_timed_out"),
+				ParachainConsensusEvent::SlowBitfieldPropagation(_, _) =>
+					record.alerts.push("slow_bitfield_propagation"),
+				ParachainConsensusEvent::InclusionMismatch(_, mismatch) => record.alerts.push(mismatch.as_str()),
+				ParachainConsensusEvent::OldRelayParent(_, _, _) => record.alerts.push("old_relay_parent"),
+				ParachainConsensusEvent::NewSession(_) | ParachainConsensusEvent::MessageQueues(_, _) => {},
+			}
+		}
+
+		record
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{schema, types::InclusionMismatch};
+	use std::time::Duration;
+
+	#[test]
+	fn test_serialized_progress_conforms_to_schema() {
+		let candidate_hash = H256::random();
+		let progress = ParachainProgressUpdate {
+			para_id: 100,
+			timestamp: 1694095332000,
+			prev_timestamp: 1694095326000,
+			block_number: 42,
+			block_hash: H256::random(),
+			core_occupied: true,
+			events: vec![
+				ParachainConsensusEvent::CoreAssigned(3),
+				ParachainConsensusEvent::Backed(candidate_hash, 2, 3),
+				ParachainConsensusEvent::Included(candidate_hash, 200, 300),
+				ParachainConsensusEvent::Disputed(DisputesTracker {
+					candidate: candidate_hash,
+					outcome: SubxtDisputeResult::Invalid,
+					..Default::default()
+				}),
+				ParachainConsensusEvent::InclusionMismatch(candidate_hash, InclusionMismatch::BackedWithoutEvent),
+			],
+			finality_lag: Some(2),
+			backed_in: Some(Latency { blocks: 2, duration: Some(Duration::from_secs(12)) }),
+			included_after: Some(Latency { blocks: 3, duration: None }),
+			relay_parent_age: Some(2),
+			unincluded_depth: Some(1),
+			included_count: Some(2),
+			cores: vec![
+				CoreProgress {
+					core_index: 3,
+					candidate_hash,
+					state: "included",
+					available_count: 200,
+					max_availability_bits: 300,
+				},
+				CoreProgress {
+					core_index: 4,
+					candidate_hash: H256::random(),
+					state: "backed",
+					available_count: 0,
+					max_availability_bits: 300,
+				},
+			],
+			..Default::default()
+		};
+
+		let record = ProgressRecord::from(&progress);
+		assert_eq!(record.block_time_ms, Some(6000));
+		assert_eq!(record.core_index, Some(3));
+		assert_eq!(record.disputes[0].outcome, "invalid");
+		assert_eq!(record.alerts, vec!["backed_without_event"]);
+
+		let value = serde_json::to_value(&record).unwrap();
+		assert_eq!(value["backed_in"]["ms"], 12000);
+		schema::tests::assert_conforms("progress", &value);
+		// Optional fields are left out
+		schema::tests::assert_conforms(
+			"progress",
+			&serde_json::to_value(ProgressRecord::from(&ParachainProgressUpdate::default())).unwrap(),
+		);
+	}
+}
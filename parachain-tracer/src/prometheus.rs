@@ -14,16 +14,41 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::types::{DisputesTracker, ParachainProgressUpdate};
-use clap::Parser;
+use crate::{
+	alerts::StallAlert,
+	availability_culprits::AvailabilityCulpritsReport,
+	bitfield_participation::BitfieldParticipationUpdate,
+	core_occupancy::CoreOccupancy,
+	dashboard,
+	hrmp_channels::HrmpChannelState,
+	inherent_disputes::InherentDisputesUpdate,
+	inherent_weight::{InherentComponent, InherentWeightUpdate},
+	on_demand::OnDemandOrderEvent,
+	pov::CandidateSize,
+	stats::SessionSummary,
+	system_parachains::CandidateActivity,
+	types::{DisputesTracker, InclusionMismatch, MessageQueueDepths, ParachainProgressUpdate, XcmThroughput},
+};
+use clap::{Args, Parser};
 use color_eyre::Result;
 use mockall::automock;
-use polkadot_introspector_essentials::{constants::STANDARD_BLOCK_TIME, types::OnDemandOrder};
-use prometheus_endpoint::{
-	prometheus::{Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts},
-	Registry,
+use polkadot_introspector_essentials::{
+	constants::STANDARD_BLOCK_TIME,
+	event_decoding::EventDecodingStats,
+	metrics::{self, MetricsRegistry},
+	types::{BlockNumber, OnDemandOrder, Timestamp},
+};
+use prometheus_endpoint::prometheus::{
+	core::{Collector, MetricVec, MetricVecBuilder},
+	Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+};
+use std::{
+	collections::{HashMap, HashSet},
+	net::ToSocketAddrs,
+	str::FromStr,
+	sync::{Arc, Mutex},
+	time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use std::{net::ToSocketAddrs, time::Duration};
 
 #[derive(Clone, Debug, Parser, Default)]
 #[clap(rename_all = "kebab-case")]
@@ -34,6 +59,120 @@ pub struct ParachainTracerPrometheusOptions {
 	/// Port to bind Prometheus listener
 	#[clap(short = 'p', long = "port", default_value = "65432")]
 	port: u16,
+	/// Observe relay chain block times for 1 in N blocks of each parachain
+	#[clap(long = "block-time-sample-rate", default_value = "1")]
+	block_time_sample_rate: u64,
+	/// Export histograms only for these parachains, comma separated (all parachains by default)
+	#[clap(long = "histogram-paras", value_delimiter = ',')]
+	histogram_paras: Vec<u32>,
+	#[clap(flatten)]
+	buckets: HistogramBucketsOptions,
+}
+
+/// Strictly increasing bucket boundaries of a histogram
+#[derive(Clone, Debug, PartialEq)]
+struct HistogramBuckets(Vec<f64>);
+
+impl FromStr for HistogramBuckets {
+	type Err = String;
+
+	/// Parses comma separated upper bounds, e.g. `1,2,4,8`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let buckets = s
+			.split(',')
+			.map(|bound| {
+				bound
+					.trim()
+					.parse::<f64>()
+					.ok()
+					.filter(|bound| bound.is_finite())
+					.ok_or_else(|| format!("invalid histogram bucket: {}", bound))
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		if buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+			return Err(format!("histogram buckets must be strictly increasing: {}", s))
+		}
+
+		Ok(Self(buckets))
+	}
+}
+
+/// Bucket boundaries of the time histograms, the defaults saturate on fast testnets and slow parachains
+#[derive(Clone, Debug, Args, Default)]
+struct HistogramBucketsOptions {
+	/// Buckets of the relay chain block time histogram in seconds, e.g. `1,2,3,6,12`
+	#[clap(long, value_name = "BUCKETS")]
+	relay_block_time_buckets: Option<HistogramBuckets>,
+	/// Buckets of the parachain block, backing, availability and core dispatch time and relay parent age histograms
+	/// in relay chain blocks
+	#[clap(long, value_name = "BUCKETS")]
+	para_time_buckets_blocks: Option<HistogramBuckets>,
+	/// Buckets of the parachain block, backing, core dispatch and on-demand order time histograms in seconds
+	#[clap(long, value_name = "BUCKETS")]
+	para_time_buckets_seconds: Option<HistogramBuckets>,
+	/// Buckets of the dispute resolution time histogram in relay chain blocks
+	#[clap(long, value_name = "BUCKETS")]
+	dispute_resolve_time_buckets: Option<HistogramBuckets>,
+}
+
+impl HistogramBucketsOptions {
+	fn relay_block_time(&self) -> Vec<f64> {
+		Self::or_default(&self.relay_block_time_buckets, HISTOGRAM_TIME_BUCKETS_SECONDS)
+	}
+
+	fn para_time_blocks(&self) -> Vec<f64> {
+		Self::or_default(&self.para_time_buckets_blocks, HISTOGRAM_TIME_BUCKETS_BLOCKS)
+	}
+
+	fn para_time_seconds(&self) -> Vec<f64> {
+		Self::or_default(&self.para_time_buckets_seconds, HISTOGRAM_TIME_BUCKETS_SECONDS)
+	}
+
+	fn dispute_resolve_time(&self) -> Vec<f64> {
+		Self::or_default(&self.dispute_resolve_time_buckets, HISTOGRAM_TIME_BUCKETS_BLOCKS)
+	}
+
+	fn or_default(buckets: &Option<HistogramBuckets>, default: &[f64]) -> Vec<f64> {
+		buckets.as_ref().map_or_else(|| default.into(), |buckets| buckets.0.clone())
+	}
+}
+
+/// Controls which observations are exported to reduce the exporter overhead on busy networks
+#[derive(Clone, Default)]
+struct MetricsSampling {
+	/// Observe relay chain block times for 1 in `block_time_rate` blocks of each parachain
+	block_time_rate: u64,
+	/// Export histograms only for these parachains, all parachains if empty
+	histogram_paras: HashSet<u32>,
+	/// Number of blocks seen per parachain
+	blocks_seen: Arc<Mutex<HashMap<u32, u64>>>,
+}
+
+impl MetricsSampling {
+	fn new(opts: &ParachainTracerPrometheusOptions) -> Self {
+		Self {
+			block_time_rate: opts.block_time_sample_rate.max(1),
+			histogram_paras: opts.histogram_paras.iter().copied().collect(),
+			blocks_seen: Default::default(),
+		}
+	}
+
+	fn has_histograms(&self, para_id: u32) -> bool {
+		self.histogram_paras.is_empty() || self.histogram_paras.contains(&para_id)
+	}
+
+	fn sample_block_time(&self, para_id: u32) -> bool {
+		if !self.has_histograms(para_id) {
+			return false
+		}
+
+		let mut blocks_seen = self.blocks_seen.lock().expect("poisoned lock");
+		let seen = blocks_seen.entry(para_id).or_default();
+		let is_sampled = *seen % self.block_time_rate == 0;
+		*seen += 1;
+
+		is_sampled
+	}
 }
 
 #[derive(Clone)]
@@ -45,12 +184,34 @@ struct DisputesMetrics {
 	/// Average count of validators that voted against supermajority
 	/// Average resolution time in blocks
 	resolution_time: HistogramVec,
+	/// Participation of validators in disputes by role: initiated, voted valid or invalid, voted against the outcome
+	validator_participation: IntCounterVec,
+}
+
+#[derive(Clone)]
+struct XcmMetrics {
+	/// Number of upward messages sent
+	ump_messages: IntCounterVec,
+	/// Size of upward messages sent
+	ump_bytes: IntCounterVec,
+	/// Number of downward messages processed
+	dmp_processed: IntCounterVec,
+	/// Number of horizontal messages sent
+	hrmp_messages_sent: IntCounterVec,
+	/// Size of horizontal messages sent
+	hrmp_bytes_sent: IntCounterVec,
+	/// Number of horizontal messages received from other parachains
+	hrmp_messages_received: IntCounterVec,
+	/// Size of horizontal messages received from other parachains
+	hrmp_bytes_received: IntCounterVec,
 }
 
 #[derive(Clone)]
 struct MetricsInner {
 	/// Number of backed candidates.
 	backed_count: IntCounterVec,
+	/// Number of validity votes per backed candidate, labeled by backing group size.
+	backing_votes: HistogramVec,
 	/// Number of skipped slots, where no candidate was backed and availability core
 	/// was free.
 	skipped_slots: IntCounterVec,
@@ -64,6 +225,10 @@ struct MetricsInner {
 	relay_skipped_slots: IntCounterVec,
 	/// Number of slow availability events.
 	slow_avail_count: IntCounterVec,
+	/// Number of candidates evicted from availability cores without inclusion.
+	availability_timeouts: IntCounterVec,
+	/// Number of cross-checks of the inferred candidate state against the runtime events, by result.
+	inclusion_checks: IntCounterVec,
 	/// Number of low bitfield propagation events.
 	low_bitfields_count: IntCounterVec,
 	/// Number of bitfields being set
@@ -74,25 +239,253 @@ struct MetricsInner {
 	para_backing_times: HistogramVec,
 	/// Average candidate inclusion time measured in seconds.
 	para_block_times_sec: HistogramVec,
+	/// Average candidate backing time measured in seconds, using the timestamps of the relay chain blocks.
+	para_backing_times_sec: HistogramVec,
+	/// Number of relay chain blocks from backing a candidate to 2/3 of its availability bits set or to its timeout
+	availability_times: HistogramVec,
+	/// Latency between scheduling a parachain on a core and backing a candidate, measured in relay chain blocks
+	para_core_dispatch_times: HistogramVec,
+	/// Latency between scheduling a parachain on a core and backing a candidate, measured in seconds
+	para_core_dispatch_times_sec: HistogramVec,
+	/// Age of relay parents of backed candidates measured in relay chain blocks
+	relay_parent_ages: HistogramVec,
+	/// Number of candidates backed on relay parents at the edge of the allowed ancestry
+	old_relay_parents: IntCounterVec,
+	/// Number of candidates backed on relay parents older than the parent of the block
+	non_latest_relay_parents: IntCounterVec,
+	/// Number of candidates backed and not yet included on chain
+	unincluded_depth: IntGaugeVec,
+	/// Number of candidates of the parachain included per relay chain block
+	included_per_block: HistogramVec,
+	/// Number of availability cores occupied by candidates of the parachain
+	cores_in_use: IntGaugeVec,
+	/// Maximum depth of candidates built on top of unincluded ones from the host configuration
+	max_candidate_depth: IntGauge,
+	/// Allowed relay parent ancestry from the host configuration
+	allowed_ancestry_len: IntGauge,
+	/// Metrics set by the scriptable hooks
+	hook_metrics: GaugeVec,
+	/// Values of the watch expressions per parachain
+	watch_expressions: GaugeVec,
+	/// XCM messages in the commitments of backed candidates
+	xcm: XcmMetrics,
 	/// Parachain's on-demand orders
 	para_on_demand_orders: GaugeVec,
 	/// Latency between ordering a slot by a parachain and its last backed candidate in relay blocks
 	para_on_demand_delay: GaugeVec,
 	/// Latency between ordering a slot by a parachain and its last backed candidate in seconds
 	para_on_demand_delay_sec: GaugeVec,
+	/// Latency from an on-demand order to scheduling, backing and inclusion in seconds
+	ondemand_order_latency: HistogramVec,
+	/// Spot price of the last on-demand order
+	ondemand_spot_price: GaugeVec,
 	/// Finality lag
 	finality_lag: Gauge,
+	/// Percentage of active validators that contributed bitfields in the last relay chain block
+	bitfield_participation: Gauge,
+	/// Rolling average of bitfield participation
+	bitfield_participation_avg: Gauge,
+	/// Number of bitfield participation drops
+	bitfield_participation_drops: IntCounter,
+	/// Blocks where the top offenders missed availability bits in the last window
+	availability_culprits: IntGaugeVec,
+	/// Share of the paras inherent taken by its components
+	inherent_shares: GaugeVec,
+	/// Actual weight of the paras inherent
+	inherent_weight: GaugeVec,
+	/// Number of blocks where disputes crowded out backed candidates
+	inherent_crowded_out: IntCounter,
+	/// Number of dispute statement sets in the paras inherent of the last relay chain block
+	inherent_dispute_sets: IntGauge,
+	/// Number of dispute statements in the paras inherent of the last relay chain block
+	inherent_dispute_statements: IntGauge,
+	/// Encoded size of the dispute statement sets in the paras inherent of the last relay chain block
+	inherent_dispute_bytes: IntGauge,
+	/// Number of dispute statement sets referring to past sessions
+	inherent_dispute_stale_sets: IntCounter,
+	/// Runtime spec version of the last relay chain block
+	spec_version: IntGauge,
+	/// Number of relay chain reorgs abandoning the blocks followed as the main chain
+	relay_reorgs: IntCounter,
+	/// Number of the last relay chain block processed by the tracer
+	relay_best_block: IntGauge,
+	/// Number of the last finalized relay chain block known when the last block was processed
+	relay_finalized_block: IntGauge,
+	/// Timestamp of the last relay chain block processed by the tracer
+	relay_block_timestamp: Gauge,
+	/// Wall-clock time when the last relay chain block was processed by the tracer
+	relay_processed_timestamp: Gauge,
+	/// Number of chain events decoded and skipped by the collector
+	collector_events: IntGaugeVec,
+	/// Time spent by the collector decoding chain events
+	event_decode_time: Gauge,
+	/// Estimated time saved by skipping the chain events not configured for decoding
+	event_decode_saved_time: Gauge,
+	/// Number of availability cores occupied in the last relay chain block
+	cores_occupied: IntGauge,
+	/// Number of availability cores free in the last relay chain block, scheduled or not
+	cores_free: IntGauge,
+	/// Session index of the last relay chain block
+	session_index: IntGauge,
+	/// Timestamp of the first relay chain block of the last session change
+	session_change_timestamp: Gauge,
+	/// Number of the first relay chain block of the last session change
+	session_change_block: IntGauge,
+	/// Number of stall alerts raised
+	alerts: IntCounterVec,
+	/// Activity of system parachain blocks, e.g. bridge messages or asset transactions
+	system_parachain_activity: IntCounterVec,
+	/// Estimated PoV sizes of the backed candidates
+	pov_size: HistogramVec,
+	/// Weight consumed by the parachain block of the last backed candidate
+	para_block_weight: GaugeVec,
+	/// Number of backed candidates skipped as the PoV size fetcher fell behind
+	pov_candidates_skipped: IntCounterVec,
+	/// Statistics of the parachains over the last finished session
+	session_summary: GaugeVec,
+	/// Number of candidates not finalized within `no_show_slots` beyond the usual finality lag after inclusion
+	approval_noshow_suspected: IntCounterVec,
+	/// Number of included heads not followed by the node of the parachain
+	para_head_mismatches: IntCounterVec,
+	/// Number of persistent divergences of the parachain heads between relay chain nodes
+	para_head_divergences: IntCounterVec,
+	/// Share of the capacity of the HRMP channels in use
+	hrmp_channel_fill_ratio: GaugeVec,
+	/// Number of messages in the downward queue of a parachain
+	dmp_queue_len: IntGaugeVec,
+	/// Number of messages in the upward queue of a parachain
+	ump_queue_len: IntGaugeVec,
+	/// Number of disputes without a statement of the monitored validator
+	dispute_participation_pending: IntGauge,
+	/// Number of disputes where the monitored validator hasn't cast a statement in time
+	dispute_participation_missed: IntCounter,
+	/// Name of a parachain, always 1
+	para_info: IntGaugeVec,
+	/// Sampling of high-frequency metrics
+	sampling: MetricsSampling,
+}
+
+impl MetricsInner {
+	/// Returns the metric of the parachain with the rest of the label values
+	fn para_metric<B: MetricVecBuilder>(&self, metric: &MetricVec<B>, para_id: u32, values: &[&str]) -> B::M {
+		let para_str = para_id.to_string();
+		let mut label_values = vec![&para_str[..]];
+		label_values.extend_from_slice(values);
+		metric.with_label_values(&label_values)
+	}
+
+	/// Metrics labeled by the parachain id
+	fn para_metrics(&self) -> Vec<&dyn ParaSeries> {
+		vec![
+			&self.disputes_stats.disputed_count as &dyn ParaSeries,
+			&self.disputes_stats.concluded_valid,
+			&self.disputes_stats.concluded_invalid,
+			&self.disputes_stats.resolution_time,
+			&self.xcm.ump_messages,
+			&self.xcm.ump_bytes,
+			&self.xcm.dmp_processed,
+			&self.xcm.hrmp_messages_sent,
+			&self.xcm.hrmp_bytes_sent,
+			&self.xcm.hrmp_messages_received,
+			&self.xcm.hrmp_bytes_received,
+			&self.backed_count,
+			&self.backing_votes,
+			&self.skipped_slots,
+			&self.included_count,
+			&self.relay_block_times,
+			&self.relay_skipped_slots,
+			&self.slow_avail_count,
+			&self.availability_timeouts,
+			&self.inclusion_checks,
+			&self.low_bitfields_count,
+			&self.bitfields,
+			&self.para_block_times,
+			&self.para_block_times_sec,
+			&self.para_backing_times,
+			&self.para_backing_times_sec,
+			&self.availability_times,
+			&self.para_core_dispatch_times,
+			&self.para_core_dispatch_times_sec,
+			&self.relay_parent_ages,
+			&self.old_relay_parents,
+			&self.non_latest_relay_parents,
+			&self.unincluded_depth,
+			&self.included_per_block,
+			&self.cores_in_use,
+			&self.watch_expressions,
+			&self.para_on_demand_orders,
+			&self.para_on_demand_delay,
+			&self.para_on_demand_delay_sec,
+			&self.ondemand_order_latency,
+			&self.ondemand_spot_price,
+			&self.alerts,
+			&self.system_parachain_activity,
+			&self.pov_size,
+			&self.para_block_weight,
+			&self.pov_candidates_skipped,
+			&self.session_summary,
+			&self.approval_noshow_suspected,
+			&self.para_head_mismatches,
+			&self.para_head_divergences,
+			&self.hrmp_channel_fill_ratio,
+			&self.dmp_queue_len,
+			&self.ump_queue_len,
+			&self.para_info,
+		]
+	}
+}
+
+/// A metric labeled by the parachain id
+trait ParaSeries {
+	/// Removes the series of the parachain
+	fn remove_para(&self, para_id: u32);
+}
+
+impl<B: MetricVecBuilder> ParaSeries for MetricVec<B> {
+	fn remove_para(&self, para_id: u32) {
+		for values in para_series(self, para_id) {
+			let _ = self.remove_label_values(&values.iter().map(String::as_str).collect::<Vec<_>>());
+		}
+	}
+}
+
+/// Returns the label values of the series of a parachain, in the order of the labels of the metric
+fn para_series<B: MetricVecBuilder>(metric: &MetricVec<B>, para_id: u32) -> Vec<Vec<String>> {
+	let para_str = para_id.to_string();
+	let Some(label_names) = metric.desc().first().map(|desc| desc.variable_labels.clone()) else { return vec![] };
+	metric
+		.collect()
+		.iter()
+		.flat_map(|family| family.get_metric())
+		.filter_map(|series| {
+			let labels: HashMap<&str, &str> = series
+				.get_label()
+				.iter()
+				.map(|label| (label.get_name(), label.get_value()))
+				.collect();
+			(labels.get("parachain_id") == Some(&para_str.as_str())).then(|| {
+				label_names
+					.iter()
+					.map(|name| labels.get(name.as_str()).copied().unwrap_or_default().to_string())
+					.collect()
+			})
+		})
+		.collect()
 }
 
 #[automock]
 /// Common methods for parachain metrics tracker
 pub trait PrometheusMetrics {
 	/// Update metrics on candidate backing
-	fn on_backed(&self, para_id: u32);
+	fn on_backed(&self, votes: u32, group_size: u32, para_id: u32);
 	/// Update metrics on new block
 	fn on_block(&self, time: f64, para_id: u32);
 	/// Update metrics on slow availability
 	fn on_slow_availability(&self, para_id: u32);
+	/// Update metrics on availability core timeout
+	fn on_availability_timeout(&self, para_id: u32);
+	/// Update metrics on cross-check of the candidate state against the runtime events
+	fn on_inclusion_check(&self, mismatch: Option<InclusionMismatch>, para_id: u32);
 	/// Update metrics on bitfields propogation
 	fn on_bitfields(&self, nbitfields: u32, is_low: bool, para_id: u32);
 	/// Update metrics on skipped slot
@@ -106,16 +499,84 @@ pub trait PrometheusMetrics {
 		previous_included: Option<u32>,
 		backed_in: Option<u32>,
 		para_block_time_sec: Option<Duration>,
+		backed_in_sec: Option<Duration>,
 		para_id: u32,
 	);
+	/// Update availability time of a candidate, from backing to 2/3 of availability bits set or to the timeout
+	fn on_availability_time(&self, blocks: u32, para_id: u32);
+	/// Update core dispatch latency (from scheduling on a core to backing)
+	fn on_core_dispatch(&self, delay_blocks: u32, delay_sec: Option<Duration>, para_id: u32);
+	/// Update relay parent age of a backed candidate
+	fn on_relay_parent_age(&self, age: u32, is_old: bool, para_id: u32);
+	/// Update number of candidates backed and not yet included on chain
+	fn on_unincluded_depth(&self, depth: u32, para_id: u32);
+	/// Update candidates included in a relay chain block and cores in use, more than one with elastic scaling
+	fn on_core_candidates(&self, included_count: u32, cores_in_use: u32, para_id: u32);
+	/// Update async backing parameters of the host configuration
+	fn on_async_backing_params(&self, max_candidate_depth: Option<u32>, allowed_ancestry_len: Option<u32>);
+	/// Update a metric set by a hook
+	fn on_hook_metric(&self, script: &str, name: &str, value: f64);
+	/// Update a value of a watch expression
+	fn on_watch_expression(&self, name: &str, value: f64, para_id: u32);
+	/// Update XCM messages of backed candidates
+	fn on_xcm_throughput(&self, xcm: &XcmThroughput, para_id: u32);
 	/// Update on-demand orders
 	fn handle_on_demand_order(&self, order: &OnDemandOrder);
 	/// Update on-demand latency in blocks
 	fn handle_on_demand_delay(&self, delay_blocks: u32, para_id: u32, until: &str);
 	/// Update on-demand latency in seconds
 	fn handle_on_demand_delay_sec(&self, delay_sec: Duration, para_id: u32, until: &str);
+	/// Update on-demand orders followed relay chain wide
+	fn on_on_demand_order(&self, event: &OnDemandOrderEvent);
 	/// Update finality lag
 	fn on_finality_lag(&self, lag: u32);
+	/// Update relay chain bitfield participation
+	fn on_bitfield_participation(&self, update: &BitfieldParticipationUpdate);
+	/// Update the top offenders missing availability bits
+	fn on_availability_culprits(&self, report: &AvailabilityCulpritsReport);
+	/// Update dispute participation of the monitored validator
+	fn on_dispute_participation(&self, pending: u32, missed: u32);
+	/// Update composition of the paras inherent
+	fn on_inherent_weight(&self, update: &InherentWeightUpdate);
+	fn on_inherent_disputes(&self, update: &InherentDisputesUpdate);
+	/// Update runtime spec version
+	fn on_spec_version(&self, spec_version: u32);
+	/// Update relay chain reorgs
+	fn on_relay_reorg(&self);
+	/// Update the last processed relay chain block with the finalized block number known at that time
+	fn on_relay_head(&self, block_number: BlockNumber, finalized_block_number: Option<BlockNumber>, ts: Timestamp);
+	/// Update the counters of the chain events decoding
+	fn on_event_decoding(&self, stats: &EventDecodingStats);
+	/// Update utilisation of the availability cores
+	fn on_core_occupancy(&self, occupancy: &CoreOccupancy);
+	/// Update session index, with the number and timestamp of the first block if the session has just changed
+	fn on_session(&self, session_index: u32, changed_at: Option<(BlockNumber, Timestamp)>);
+	/// Update stall alerts
+	fn on_stall_alert(&self, alert: &StallAlert);
+	/// Update activity of a system parachain block
+	fn on_candidate_activity(&self, activity: &CandidateActivity, para_id: u32);
+	/// Update estimated PoV size and consumed weight of a backed candidate
+	fn on_candidate_size(&self, size: &CandidateSize, para_id: u32);
+	/// Update backed candidates skipped as the PoV size fetcher fell behind
+	fn on_pov_candidates_skipped(&self, para_id: u32, skipped: u32);
+	/// Update statistics of a parachain over a finished session
+	fn on_session_summary(&self, summary: &SessionSummary);
+	/// Update suspected approval no-shows
+	fn on_approval_noshow_suspected(&self, para_id: u32);
+	/// Update included heads not followed by the node of the parachain
+	fn on_para_head_mismatch(&self, para_id: u32);
+	/// Update persistent divergences of the parachain head on another relay chain node
+	fn on_para_head_divergence(&self, node: &str, para_id: u32);
+	/// Update the fill ratio of an HRMP channel of a parachain
+	fn on_hrmp_channel(&self, channel: &HrmpChannelState, para_id: u32);
+	/// Remove the series of a closed HRMP channel of a parachain
+	fn on_hrmp_channel_closed(&self, sender: u32, recipient: u32, para_id: u32);
+	/// Update the lengths of the downward and upward message queues of a parachain
+	fn on_message_queue_depths(&self, depths: &MessageQueueDepths, para_id: u32);
+	/// Update the name of a parachain, empty if it is not known
+	fn on_para_name(&self, para_id: u32, name: &str);
+	/// Remove the series of a parachain that is not traced anymore
+	fn on_para_removed(&self, para_id: u32);
 }
 
 /// Parachain tracer prometheus metrics
@@ -125,25 +586,32 @@ pub struct Metrics(Option<MetricsInner>);
 const HISTOGRAM_TIME_BUCKETS_BLOCKS: &[f64] =
 	&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 12.0, 15.0, 25.0, 35.0, 50.0];
 const HISTOGRAM_TIME_BUCKETS_SECONDS: &[f64] = &[3.0, 6.0, 12.0, 18.0, 24.0, 30.0, 36.0, 48.0, 60.0, 90.0, 120.0];
+const HISTOGRAM_CANDIDATES_PER_BLOCK: &[f64] = &[0.0, 1.0, 2.0, 3.0, 4.0, 6.0, 8.0];
+const HISTOGRAM_VOTES_BUCKETS: &[f64] = &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 10.0];
+const HISTOGRAM_POV_SIZE_BUCKETS: &[f64] =
+	&[16384.0, 65536.0, 131072.0, 262144.0, 524288.0, 1048576.0, 2097152.0, 3145728.0, 4194304.0, 5242880.0];
 
 impl PrometheusMetrics for Metrics {
-	fn on_backed(&self, para_id: u32) {
+	fn on_backed(&self, votes: u32, group_size: u32, para_id: u32) {
 		if let Some(metrics) = &self.0 {
-			metrics.backed_count.with_label_values(&[&para_id.to_string()[..]]).inc();
+			metrics.para_metric(&metrics.backed_count, para_id, &[]).inc();
+			if group_size > 0 && metrics.sampling.has_histograms(para_id) {
+				metrics
+					.para_metric(&metrics.backing_votes, para_id, &[&group_size.to_string()[..]])
+					.observe(votes as f64);
+			}
 		}
 	}
 
 	fn on_block(&self, time: f64, para_id: u32) {
 		if let Some(metrics) = &self.0 {
-			metrics
-				.relay_block_times
-				.with_label_values(&[&para_id.to_string()[..]])
-				.observe(time);
+			if metrics.sampling.sample_block_time(para_id) {
+				metrics.para_metric(&metrics.relay_block_times, para_id, &[]).observe(time);
+			}
 			let skipped_slots = ((time / STANDARD_BLOCK_TIME).round() as u64).saturating_sub(1);
 			if skipped_slots > 0 {
 				metrics
-					.relay_skipped_slots
-					.with_label_values(&[&para_id.to_string()[..]])
+					.para_metric(&metrics.relay_skipped_slots, para_id, &[])
 					.inc_by(skipped_slots);
 			}
 		}
@@ -151,51 +619,68 @@ impl PrometheusMetrics for Metrics {
 
 	fn on_slow_availability(&self, para_id: u32) {
 		if let Some(metrics) = &self.0 {
-			metrics.slow_avail_count.with_label_values(&[&para_id.to_string()[..]]).inc();
+			metrics.para_metric(&metrics.slow_avail_count, para_id, &[]).inc();
+		}
+	}
+
+	fn on_availability_timeout(&self, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics.para_metric(&metrics.availability_timeouts, para_id, &[]).inc();
+		}
+	}
+
+	fn on_inclusion_check(&self, mismatch: Option<InclusionMismatch>, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			let result = mismatch.map_or("consistent", |v| v.as_str());
+			metrics.para_metric(&metrics.inclusion_checks, para_id, &[result]).inc();
 		}
 	}
 
 	fn on_bitfields(&self, nbitfields: u32, is_low: bool, para_id: u32) {
 		if let Some(metrics) = &self.0 {
-			metrics
-				.bitfields
-				.with_label_values(&[&para_id.to_string()[..]])
-				.set(nbitfields as i64);
+			metrics.para_metric(&metrics.bitfields, para_id, &[]).set(nbitfields as i64);
 
 			if is_low {
-				metrics.low_bitfields_count.with_label_values(&[&para_id.to_string()[..]]).inc();
+				metrics.para_metric(&metrics.low_bitfields_count, para_id, &[]).inc();
 			}
 		}
 	}
 
 	fn on_skipped_slot(&self, update: &ParachainProgressUpdate) {
 		if let Some(metrics) = &self.0 {
-			metrics
-				.skipped_slots
-				.with_label_values(&[&update.para_id.to_string()[..]])
-				.inc();
+			let reason = update.skipped_slot_reason().map_or("unknown", |v| v.as_str());
+			metrics.para_metric(&metrics.skipped_slots, update.para_id, &[reason]).inc();
 		}
 	}
 
 	fn on_disputed(&self, dispute_outcome: &DisputesTracker, para_id: u32) {
 		if let Some(metrics) = &self.0 {
-			let para_str: String = para_id.to_string();
-			metrics.disputes_stats.disputed_count.with_label_values(&[&para_str[..]]).inc();
+			metrics.para_metric(&metrics.disputes_stats.disputed_count, para_id, &[]).inc();
 
 			if dispute_outcome.voted_for > dispute_outcome.voted_against {
-				metrics.disputes_stats.concluded_valid.with_label_values(&[&para_str[..]]).inc();
+				metrics.para_metric(&metrics.disputes_stats.concluded_valid, para_id, &[]).inc();
 			} else {
 				metrics
-					.disputes_stats
-					.concluded_invalid
-					.with_label_values(&[&para_str[..]])
+					.para_metric(&metrics.disputes_stats.concluded_invalid, para_id, &[])
 					.inc();
 			}
-			metrics
-				.disputes_stats
-				.resolution_time
-				.with_label_values(&[&para_str[..]])
-				.observe(dispute_outcome.resolve_time as f64);
+			if metrics.sampling.has_histograms(para_id) {
+				metrics
+					.para_metric(&metrics.disputes_stats.resolution_time, para_id, &[])
+					.observe(dispute_outcome.resolve_time as f64);
+			}
+
+			let participation = &metrics.disputes_stats.validator_participation;
+			for (role, validators) in [
+				("initiated", &dispute_outcome.initiators),
+				("voted_valid", &dispute_outcome.valid_voters),
+				("voted_invalid", &dispute_outcome.invalid_voters),
+				("lost", &dispute_outcome.misbehaving_validators),
+			] {
+				for (_, address) in validators {
+					participation.with_label_values(&[address.as_str(), role]).inc();
+				}
+			}
 		}
 	}
 
@@ -205,212 +690,1161 @@ impl PrometheusMetrics for Metrics {
 		previous_included: Option<u32>,
 		backed_in: Option<u32>,
 		para_block_time_sec: Option<Duration>,
+		backed_in_sec: Option<Duration>,
 		para_id: u32,
 	) {
 		if let Some(metrics) = &self.0 {
-			let para_str: String = para_id.to_string();
-			metrics.included_count.with_label_values(&[&para_str[..]]).inc();
+			metrics.para_metric(&metrics.included_count, para_id, &[]).inc();
+			if !metrics.sampling.has_histograms(para_id) {
+				return
+			}
 
 			if let Some(previous_block_number) = previous_included {
 				metrics
-					.para_block_times
-					.with_label_values(&[&para_str[..]])
+					.para_metric(&metrics.para_block_times, para_id, &[])
 					.observe(relay_parent_number.saturating_sub(previous_block_number) as f64);
 			}
 			if let Some(time) = para_block_time_sec {
 				metrics
-					.para_block_times_sec
-					.with_label_values(&[&para_str[..]])
+					.para_metric(&metrics.para_block_times_sec, para_id, &[])
 					.observe(time.as_secs_f64());
 			}
 			if let Some(backed_in) = backed_in {
 				metrics
-					.para_backing_times
-					.with_label_values(&[&para_str[..]])
+					.para_metric(&metrics.para_backing_times, para_id, &[])
 					.observe(backed_in as f64);
 			}
+			if let Some(time) = backed_in_sec {
+				metrics
+					.para_metric(&metrics.para_backing_times_sec, para_id, &[])
+					.observe(time.as_secs_f64());
+			}
+		}
+	}
+
+	fn on_availability_time(&self, blocks: u32, para_id: u32) {
+		if let Some(metrics) = self.0.as_ref().filter(|metrics| metrics.sampling.has_histograms(para_id)) {
+			metrics
+				.para_metric(&metrics.availability_times, para_id, &[])
+				.observe(blocks as f64);
+		}
+	}
+
+	fn on_core_dispatch(&self, delay_blocks: u32, delay_sec: Option<Duration>, para_id: u32) {
+		if let Some(metrics) = self.0.as_ref().filter(|metrics| metrics.sampling.has_histograms(para_id)) {
+			metrics
+				.para_metric(&metrics.para_core_dispatch_times, para_id, &[])
+				.observe(delay_blocks as f64);
+			if let Some(time) = delay_sec {
+				metrics
+					.para_metric(&metrics.para_core_dispatch_times_sec, para_id, &[])
+					.observe(time.as_secs_f64());
+			}
+		}
+	}
+
+	fn on_relay_parent_age(&self, age: u32, is_old: bool, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			if metrics.sampling.has_histograms(para_id) {
+				metrics
+					.para_metric(&metrics.relay_parent_ages, para_id, &[])
+					.observe(age as f64);
+			}
+			if is_old {
+				metrics.para_metric(&metrics.old_relay_parents, para_id, &[]).inc();
+			}
+			if age > 1 {
+				metrics.para_metric(&metrics.non_latest_relay_parents, para_id, &[]).inc();
+			}
+		}
+	}
+
+	fn on_unincluded_depth(&self, depth: u32, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics.para_metric(&metrics.unincluded_depth, para_id, &[]).set(depth as i64);
+		}
+	}
+
+	fn on_core_candidates(&self, included_count: u32, cores_in_use: u32, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics
+				.para_metric(&metrics.cores_in_use, para_id, &[])
+				.set(cores_in_use as i64);
+			if metrics.sampling.has_histograms(para_id) {
+				metrics
+					.para_metric(&metrics.included_per_block, para_id, &[])
+					.observe(included_count as f64);
+			}
+		}
+	}
+
+	fn on_async_backing_params(&self, max_candidate_depth: Option<u32>, allowed_ancestry_len: Option<u32>) {
+		if let Some(metrics) = &self.0 {
+			// Zeros before async backing
+			metrics.max_candidate_depth.set(max_candidate_depth.unwrap_or_default() as i64);
+			metrics
+				.allowed_ancestry_len
+				.set(allowed_ancestry_len.unwrap_or_default() as i64);
+		}
+	}
+
+	fn on_hook_metric(&self, script: &str, name: &str, value: f64) {
+		if let Some(metrics) = &self.0 {
+			metrics.hook_metrics.with_label_values(&[script, name]).set(value);
+		}
+	}
+
+	fn on_watch_expression(&self, name: &str, value: f64, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics.para_metric(&metrics.watch_expressions, para_id, &[name]).set(value);
+		}
+	}
+
+	fn on_xcm_throughput(&self, xcm: &XcmThroughput, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			for (metric, value) in [
+				(&metrics.xcm.ump_messages, xcm.ump_messages),
+				(&metrics.xcm.ump_bytes, xcm.ump_bytes),
+				(&metrics.xcm.dmp_processed, xcm.dmp_processed),
+				(&metrics.xcm.hrmp_messages_sent, xcm.hrmp_sent),
+				(&metrics.xcm.hrmp_bytes_sent, xcm.hrmp_sent_bytes),
+				(&metrics.xcm.hrmp_messages_received, xcm.hrmp_received),
+				(&metrics.xcm.hrmp_bytes_received, xcm.hrmp_received_bytes),
+			] {
+				metrics.para_metric(metric, para_id, &[]).inc_by(value.into());
+			}
 		}
 	}
 
 	fn handle_on_demand_order(&self, order: &OnDemandOrder) {
 		if let Some(metrics) = &self.0 {
-			let para_str: String = order.para_id.to_string();
 			metrics
-				.para_on_demand_orders
-				.with_label_values(&[&para_str[..]])
+				.para_metric(&metrics.para_on_demand_orders, order.para_id, &[])
 				.set(order.spot_price as f64);
 		}
 	}
 
 	fn handle_on_demand_delay(&self, delay_blocks: u32, para_id: u32, until: &str) {
 		if let Some(metrics) = &self.0 {
-			let para_str: String = para_id.to_string();
 			metrics
-				.para_on_demand_delay
-				.with_label_values(&[&para_str[..], until])
+				.para_metric(&metrics.para_on_demand_delay, para_id, &[until])
 				.set(delay_blocks as f64);
 		}
 	}
 
 	fn handle_on_demand_delay_sec(&self, delay_sec: Duration, para_id: u32, until: &str) {
 		if let Some(metrics) = &self.0 {
-			let para_str: String = para_id.to_string();
 			metrics
-				.para_on_demand_delay_sec
-				.with_label_values(&[&para_str[..], until])
+				.para_metric(&metrics.para_on_demand_delay_sec, para_id, &[until])
 				.set(delay_sec.as_secs_f64());
 		}
 	}
 
+	fn on_on_demand_order(&self, event: &OnDemandOrderEvent) {
+		if let Some(metrics) = &self.0 {
+			match event {
+				OnDemandOrderEvent::Placed { para_id, spot_price, .. } => {
+					metrics
+						.para_metric(&metrics.ondemand_spot_price, *para_id, &[])
+						.set(*spot_price as f64);
+				},
+				OnDemandOrderEvent::Fulfilled(report) if metrics.sampling.has_histograms(report.para_id) => {
+					for (latency, until) in [
+						(report.scheduled_after, "scheduled"),
+						(Some(report.backed_after), "backed"),
+						(Some(report.included_after), "included"),
+					] {
+						if let Some(duration) = latency.and_then(|latency| latency.duration) {
+							metrics
+								.para_metric(&metrics.ondemand_order_latency, report.para_id, &[until])
+								.observe(duration.as_secs_f64());
+						}
+					}
+				},
+				_ => {},
+			}
+		}
+	}
+
 	fn on_finality_lag(&self, lag: u32) {
 		if let Some(metrics) = &self.0 {
 			metrics.finality_lag.set(lag.into());
 		}
 	}
+
+	fn on_bitfield_participation(&self, update: &BitfieldParticipationUpdate) {
+		if let Some(metrics) = &self.0 {
+			metrics.bitfield_participation.set(update.participation);
+			metrics.bitfield_participation_avg.set(update.rolling_average);
+			if update.is_drop {
+				metrics.bitfield_participation_drops.inc();
+			}
+		}
+	}
+
+	fn on_availability_culprits(&self, report: &AvailabilityCulpritsReport) {
+		if let Some(metrics) = &self.0 {
+			// Offenders of the previous window are not reported anymore
+			metrics.availability_culprits.reset();
+			for culprit in &report.culprits {
+				metrics
+					.availability_culprits
+					.with_label_values(&[
+						&culprit.validator_index.to_string()[..],
+						culprit.address.as_deref().unwrap_or_default(),
+					])
+					.set(culprit.missed_blocks.into());
+			}
+		}
+	}
+
+	fn on_dispute_participation(&self, pending: u32, missed: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics.dispute_participation_pending.set(pending.into());
+			metrics.dispute_participation_missed.inc_by(missed.into());
+		}
+	}
+
+	fn on_inherent_weight(&self, update: &InherentWeightUpdate) {
+		if let Some(metrics) = &self.0 {
+			for component in InherentComponent::ALL {
+				metrics
+					.inherent_shares
+					.with_label_values(&[component.as_str()])
+					.set(update.share(component));
+			}
+			if let Some(weight) = update.weight {
+				metrics
+					.inherent_weight
+					.with_label_values(&["ref_time"])
+					.set(weight.ref_time as f64);
+				metrics
+					.inherent_weight
+					.with_label_values(&["proof_size"])
+					.set(weight.proof_size as f64);
+			}
+			if update.is_crowded_out {
+				metrics.inherent_crowded_out.inc();
+			}
+		}
+	}
+
+	fn on_inherent_disputes(&self, update: &InherentDisputesUpdate) {
+		if let Some(metrics) = &self.0 {
+			metrics.inherent_dispute_sets.set(update.sets.into());
+			metrics.inherent_dispute_statements.set(update.statements.into());
+			metrics.inherent_dispute_bytes.set(update.size as i64);
+			metrics.inherent_dispute_stale_sets.inc_by(update.stale_sets.into());
+		}
+	}
+
+	fn on_spec_version(&self, spec_version: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics.spec_version.set(spec_version as i64);
+		}
+	}
+
+	fn on_relay_reorg(&self) {
+		if let Some(metrics) = &self.0 {
+			metrics.relay_reorgs.inc();
+		}
+	}
+
+	fn on_relay_head(&self, block_number: BlockNumber, finalized_block_number: Option<BlockNumber>, ts: Timestamp) {
+		if let Some(metrics) = &self.0 {
+			metrics.relay_best_block.set(block_number as i64);
+			if let Some(finalized_block_number) = finalized_block_number {
+				metrics.relay_finalized_block.set(finalized_block_number as i64);
+			}
+			metrics.relay_block_timestamp.set(ts as f64 / 1000.0);
+			metrics
+				.relay_processed_timestamp
+				.set(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |v| v.as_secs_f64()));
+		}
+	}
+
+	fn on_event_decoding(&self, stats: &EventDecodingStats) {
+		if let Some(metrics) = &self.0 {
+			metrics
+				.collector_events
+				.with_label_values(&["decoded"])
+				.set(stats.decoded as i64);
+			metrics
+				.collector_events
+				.with_label_values(&["skipped"])
+				.set(stats.skipped as i64);
+			metrics.event_decode_time.set(stats.decode_time.as_secs_f64());
+			metrics.event_decode_saved_time.set(stats.saved_time().as_secs_f64());
+		}
+	}
+
+	fn on_core_occupancy(&self, occupancy: &CoreOccupancy) {
+		if let Some(metrics) = &self.0 {
+			metrics.cores_occupied.set(occupancy.occupied as i64);
+			metrics.cores_free.set((occupancy.scheduled + occupancy.free) as i64);
+		}
+	}
+
+	fn on_session(&self, session_index: u32, changed_at: Option<(BlockNumber, Timestamp)>) {
+		if let Some(metrics) = &self.0 {
+			metrics.session_index.set(session_index as i64);
+			if let Some((block_number, timestamp)) = changed_at {
+				metrics.session_change_block.set(block_number as i64);
+				metrics.session_change_timestamp.set(timestamp as f64 / 1000.0);
+			}
+		}
+	}
+
+	fn on_stall_alert(&self, alert: &StallAlert) {
+		if let Some(metrics) = &self.0 {
+			metrics
+				.para_metric(&metrics.alerts, alert.para_id, &[alert.kind.as_str()])
+				.inc();
+		}
+	}
+
+	fn on_candidate_activity(&self, activity: &CandidateActivity, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			for (counter, value) in activity.counters() {
+				metrics
+					.para_metric(&metrics.system_parachain_activity, para_id, &[counter])
+					.inc_by(value);
+			}
+		}
+	}
+
+	fn on_session_summary(&self, summary: &SessionSummary) {
+		if let Some(metrics) = &self.0 {
+			let Some(session_index) = summary.session_index else { return };
+			// Only the last finished session is kept, the averages are NaN if nothing was included in it
+			let values = [
+				("session_index", session_index as f64),
+				("blocks", summary.blocks as f64),
+				("backed", summary.backed_count as f64),
+				("included", summary.included_count as f64),
+				("skipped_slots", summary.skipped_slots as f64),
+				("disputes", summary.disputes as f64),
+				("avg_inclusion_time_blocks", summary.avg_inclusion_time_blocks.unwrap_or(f64::NAN)),
+				("avg_inclusion_time_sec", summary.avg_inclusion_time_sec.unwrap_or(f64::NAN)),
+			];
+			for (stat, value) in values {
+				metrics
+					.para_metric(&metrics.session_summary, summary.para_id, &[stat])
+					.set(value);
+			}
+		}
+	}
+
+	fn on_approval_noshow_suspected(&self, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics.para_metric(&metrics.approval_noshow_suspected, para_id, &[]).inc();
+		}
+	}
+
+	fn on_para_head_mismatch(&self, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics.para_metric(&metrics.para_head_mismatches, para_id, &[]).inc();
+		}
+	}
+
+	fn on_para_head_divergence(&self, node: &str, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics.para_metric(&metrics.para_head_divergences, para_id, &[node]).inc();
+		}
+	}
+
+	fn on_hrmp_channel(&self, channel: &HrmpChannelState, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics
+				.para_metric(
+					&metrics.hrmp_channel_fill_ratio,
+					para_id,
+					&[&channel.sender.to_string(), &channel.recipient.to_string()],
+				)
+				.set(channel.fill_ratio());
+		}
+	}
+
+	fn on_hrmp_channel_closed(&self, sender: u32, recipient: u32, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			let _ = metrics.hrmp_channel_fill_ratio.remove_label_values(&[
+				&para_id.to_string(),
+				&sender.to_string(),
+				&recipient.to_string(),
+			]);
+		}
+	}
+
+	fn on_message_queue_depths(&self, depths: &MessageQueueDepths, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics
+				.para_metric(&metrics.dmp_queue_len, para_id, &[])
+				.set(depths.downward.len as i64);
+			metrics
+				.para_metric(&metrics.ump_queue_len, para_id, &[])
+				.set(depths.upward.len as i64);
+		}
+	}
+
+	fn on_para_name(&self, para_id: u32, name: &str) {
+		if let Some(metrics) = &self.0 {
+			// A renamed parachain doesn't keep the series of its previous name
+			for values in para_series(&metrics.para_info, para_id)
+				.into_iter()
+				.filter(|values| values[1] != name)
+			{
+				let _ = metrics
+					.para_info
+					.remove_label_values(&values.iter().map(String::as_str).collect::<Vec<_>>());
+			}
+			metrics.para_metric(&metrics.para_info, para_id, &[name]).set(1);
+		}
+	}
+
+	fn on_para_removed(&self, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			for metric in metrics.para_metrics() {
+				metric.remove_para(para_id);
+			}
+		}
+	}
+
+	fn on_candidate_size(&self, size: &CandidateSize, para_id: u32) {
+		if let Some(metrics) = &self.0 {
+			if let Some(pov_size) = size.pov_size {
+				metrics.para_metric(&metrics.pov_size, para_id, &[]).observe(pov_size as f64);
+			}
+			if let Some(weight) = size.block_weight {
+				metrics
+					.para_metric(&metrics.para_block_weight, para_id, &["ref_time"])
+					.set(weight.ref_time as f64);
+				metrics
+					.para_metric(&metrics.para_block_weight, para_id, &["proof_size"])
+					.set(weight.proof_size as f64);
+			}
+		}
+	}
+
+	fn on_pov_candidates_skipped(&self, para_id: u32, skipped: u32) {
+		if let Some(metrics) = &self.0 {
+			metrics
+				.para_metric(&metrics.pov_candidates_skipped, para_id, &[])
+				.inc_by(skipped as u64);
+		}
+	}
 }
 
 pub async fn run_prometheus_endpoint(prometheus_opts: &ParachainTracerPrometheusOptions) -> Result<Metrics> {
-	let prometheus_registry = Registry::new_custom(Some("introspector".into()), None)?;
-	let metrics = register_metrics(&prometheus_registry)?;
+	let prometheus_registry = MetricsRegistry::new("introspector")?;
+	let metrics = register_metrics(&prometheus_registry, prometheus_opts)?;
 	let socket_addr_str = format!("{}:{}", prometheus_opts.address, prometheus_opts.port);
 	for addr in socket_addr_str.to_socket_addrs()? {
-		let prometheus_registry = prometheus_registry.clone();
+		let prometheus_registry = prometheus_registry.registry().clone();
 		tokio::spawn(prometheus_endpoint::init_prometheus(addr, prometheus_registry));
 	}
 
 	Ok(metrics)
 }
 
-fn register_metrics(registry: &Registry) -> Result<Metrics> {
-	let disputes_stats = DisputesMetrics {
-		disputed_count: prometheus_endpoint::register(
-			IntCounterVec::new(Opts::new("pc_disputed_count", "Number of disputed candidates"), &["parachain_id"])?,
+/// Prints the metrics exported with the given options without starting the endpoint
+pub fn list_metrics(prometheus_opts: &ParachainTracerPrometheusOptions) -> Result<()> {
+	let prometheus_registry = MetricsRegistry::new("introspector")?;
+	register_metrics(&prometheus_registry, prometheus_opts)?;
+	prometheus_registry.print_catalog();
+
+	Ok(())
+}
+
+/// Prints a Grafana dashboard for the metrics exported with the given options without starting the endpoint
+pub fn generate_dashboard(prometheus_opts: &ParachainTracerPrometheusOptions, para_ids: &[u32]) -> Result<()> {
+	let prometheus_registry = MetricsRegistry::new("introspector")?;
+	register_metrics(&prometheus_registry, prometheus_opts)?;
+	let dashboard = dashboard::generate(&prometheus_registry.catalog(), "introspector", para_ids);
+	println!("{}", serde_json::to_string_pretty(&dashboard)?);
+
+	Ok(())
+}
+
+fn register_metrics(registry: &MetricsRegistry, opts: &ParachainTracerPrometheusOptions) -> Result<Metrics> {
+	let para_labels: &[&str] = &["parachain_id"];
+	let disputes_stats = DisputesMetrics {
+		disputed_count: metrics::register(
+			IntCounterVec::new(Opts::new("pc_disputed_count", "Number of disputed candidates"), para_labels)?,
 			registry,
 		)?,
-		concluded_valid: prometheus_endpoint::register(
+		concluded_valid: metrics::register(
 			IntCounterVec::new(
 				Opts::new("pc_disputed_valid_count", "Number of disputed candidates concluded valid"),
-				&["parachain_id"],
+				para_labels,
 			)?,
 			registry,
 		)?,
-		concluded_invalid: prometheus_endpoint::register(
+		concluded_invalid: metrics::register(
 			IntCounterVec::new(
 				Opts::new("pc_disputed_invalid_count", "Number of disputed candidates concluded invalid"),
-				&["parachain_id"],
+				para_labels,
 			)?,
 			registry,
 		)?,
-		resolution_time: prometheus_endpoint::register(
+		resolution_time: metrics::register(
 			HistogramVec::new(
 				HistogramOpts::new("pc_disputed_resolve_time", "Dispute resolution time in relay parent blocks")
-					.buckets(HISTOGRAM_TIME_BUCKETS_BLOCKS.into()),
-				&["parachain_id"],
+					.buckets(opts.buckets.dispute_resolve_time()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		validator_participation: metrics::register(
+			IntCounterVec::new(
+				Opts::new(
+					"pc_dispute_validator_participation",
+					"Number of disputes a validator initiated, voted valid or invalid in, or lost by voting against the outcome",
+				),
+				&["validator", "role"],
+			)?,
+			registry,
+		)?,
+	};
+	let xcm = XcmMetrics {
+		ump_messages: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_ump_messages", "Number of upward messages sent by backed candidates"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		ump_bytes: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_ump_bytes", "Size of upward messages sent by backed candidates in bytes"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		dmp_processed: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_dmp_messages_processed", "Number of downward messages processed by backed candidates"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		hrmp_messages_sent: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_hrmp_messages_sent", "Number of horizontal messages sent by backed candidates"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		hrmp_bytes_sent: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_hrmp_bytes_sent", "Size of horizontal messages sent by backed candidates in bytes"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		hrmp_messages_received: metrics::register(
+			IntCounterVec::new(
+				Opts::new(
+					"pc_hrmp_messages_received",
+					"Number of horizontal messages sent to the parachain by backed candidates of other parachains",
+				),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		hrmp_bytes_received: metrics::register(
+			IntCounterVec::new(
+				Opts::new(
+					"pc_hrmp_bytes_received",
+					"Size of horizontal messages sent to the parachain by backed candidates of other parachains in bytes",
+				),
+				para_labels,
 			)?,
 			registry,
 		)?,
 	};
 	Ok(Metrics(Some(MetricsInner {
-		backed_count: prometheus_endpoint::register(
-			IntCounterVec::new(Opts::new("pc_backed_count", "Number of backed candidates"), &["parachain_id"])?,
+		backed_count: metrics::register(
+			IntCounterVec::new(Opts::new("pc_backed_count", "Number of backed candidates"), para_labels)?,
+			registry,
+		)?,
+		backing_votes: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new("pc_backing_votes", "Number of validity votes received by backed candidates")
+					.buckets(HISTOGRAM_VOTES_BUCKETS.into()),
+				&[para_labels, &["group_size"]].concat(),
+			)?,
 			registry,
 		)?,
-		skipped_slots: prometheus_endpoint::register(
+		skipped_slots: metrics::register(
 			IntCounterVec::new(
 				Opts::new(
 					"pc_skipped_slots",
-					"Number of skipped slots, where no candidate was backed and availability core was free",
+					"Number of skipped slots, where no candidate was backed, by reason",
 				),
-				&["parachain_id"],
+				&[para_labels, &["reason"]].concat(),
 			)?,
 			registry,
 		)?,
-		included_count: prometheus_endpoint::register(
-			IntCounterVec::new(Opts::new("pc_included_count", "Number of candidates included"), &["parachain_id"])?,
+		included_count: metrics::register(
+			IntCounterVec::new(Opts::new("pc_included_count", "Number of candidates included"), para_labels)?,
 			registry,
 		)?,
 		disputes_stats,
-		relay_block_times: prometheus_endpoint::register(
+		relay_block_times: metrics::register(
 			HistogramVec::new(
 				HistogramOpts::new("pc_relay_block_time", "Relay chain block time measured in seconds")
-					.buckets(HISTOGRAM_TIME_BUCKETS_SECONDS.into()),
-				&["parachain_id"],
+					.buckets(opts.buckets.relay_block_time()),
+				para_labels,
 			)?,
 			registry,
 		)?,
-		relay_skipped_slots: prometheus_endpoint::register(
+		relay_skipped_slots: metrics::register(
 			IntCounterVec::new(
 				Opts::new("pc_relay_skipped_slots", "Relay chain block time measured in standard blocks") ,
-				&["parachain_id"],
+				para_labels,
 			)?,
 			registry,
 		)?,
-		slow_avail_count: prometheus_endpoint::register(
+		slow_avail_count: metrics::register(
 			IntCounterVec::new(
 				Opts::new("pc_slow_available_count", "Number of slow availability events. We consider it slow when the relay chain block bitfield entries amounts to less than 2/3 one bits for the availability core to which the parachain is assigned"),
-				&["parachain_id"],
+				para_labels,
+			)?,
+			registry,
+		)?,
+		availability_timeouts: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_availability_timeout_total", "Number of candidates evicted from the availability core without being included"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		inclusion_checks: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_inclusion_checks_total", "Number of cross-checks of backing and inclusion inferred by the tracker against the runtime events, by result"),
+				&[para_labels, &["result"]].concat(),
 			)?,
 			registry,
 		)?,
-		low_bitfields_count: prometheus_endpoint::register(
+		low_bitfields_count: metrics::register(
 			IntCounterVec::new(
 				Opts::new("pc_low_bitfields_count", "Number of low bitfields count events. This happens when a block author received the signed bitfields from less than 2/3 of the para validators"),
-				&["parachain_id"],
+				para_labels,
 			)?,
 			registry,
 		)?,
-		bitfields: prometheus_endpoint::register(
-			IntGaugeVec::new(Opts::new("pc_bitfields_count", "Number of bitfields"), &["parachain_id"]).unwrap(),
+		bitfields: metrics::register(
+			IntGaugeVec::new(Opts::new("pc_bitfields_count", "Number of bitfields"), para_labels).unwrap(),
 			registry,
 		)?,
-		para_block_times: prometheus_endpoint::register(
+		para_block_times: metrics::register(
 			HistogramVec::new(
 				HistogramOpts::new("pc_para_block_time", "Parachain block time measured in relay chain blocks.")
-					.buckets(HISTOGRAM_TIME_BUCKETS_BLOCKS.into()),
-				&["parachain_id"],
+					.buckets(opts.buckets.para_time_blocks()),
+				para_labels,
 			)?,
 			registry,
 		)?,
-		para_block_times_sec: prometheus_endpoint::register(
+		para_block_times_sec: metrics::register(
 			HistogramVec::new(
 				HistogramOpts::new("pc_para_block_time_sec", "Parachain block time measured in seconds.")
-					.buckets(HISTOGRAM_TIME_BUCKETS_SECONDS.into()),
-				&["parachain_id"],
+					.buckets(opts.buckets.para_time_seconds()),
+				para_labels,
 			)?,
 			registry,
 		)?,
-		para_backing_times: prometheus_endpoint::register(
+		para_backing_times: metrics::register(
 			HistogramVec::new(
 				HistogramOpts::new("pc_para_backing_time", "Parachain backing time measured in relay chain blocks.")
-					.buckets(HISTOGRAM_TIME_BUCKETS_BLOCKS.into()),
-				&["parachain_id"],
+					.buckets(opts.buckets.para_time_blocks()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		para_backing_times_sec: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new("pc_para_backing_time_sec", "Parachain backing time measured in seconds.")
+					.buckets(opts.buckets.para_time_seconds()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		availability_times: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new(
+					"pc_availability_time_blocks",
+					"Number of relay chain blocks from backing a candidate to 2/3 of its availability bits set or to its availability timeout",
+				)
+				.buckets(opts.buckets.para_time_blocks()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		para_core_dispatch_times: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new(
+					"pc_para_core_dispatch_time",
+					"Latency between scheduling a parachain on a core and backing a candidate, measured in relay chain blocks.",
+				)
+				.buckets(opts.buckets.para_time_blocks()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		para_core_dispatch_times_sec: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new(
+					"pc_para_core_dispatch_time_sec",
+					"Latency between scheduling a parachain on a core and backing a candidate, measured in seconds.",
+				)
+				.buckets(opts.buckets.para_time_seconds()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		relay_parent_ages: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new(
+					"pc_relay_parent_age",
+					"Age of relay parents of backed candidates measured in relay chain blocks.",
+				)
+				.buckets(opts.buckets.para_time_blocks()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		old_relay_parents: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_old_relay_parents_total", "Number of candidates backed on relay parents at the edge of the allowed ancestry"),
+				para_labels,
 			)?,
 			registry,
 		)?,
-		para_on_demand_orders: prometheus_endpoint::register(
+		non_latest_relay_parents: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_non_latest_relay_parents_total", "Number of candidates backed on relay parents older than the parent of the relay chain block, only possible with async backing"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		unincluded_depth: metrics::register(
+			IntGaugeVec::new(
+				Opts::new("pc_para_unincluded_depth", "Number of para blocks backed on chain on top of the para head"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		included_per_block: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new(
+					"pc_para_included_per_block",
+					"Number of candidates of the parachain included per relay chain block, more than one with elastic scaling",
+				)
+				.buckets(HISTOGRAM_CANDIDATES_PER_BLOCK.into()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		cores_in_use: metrics::register(
+			IntGaugeVec::new(
+				Opts::new("pc_para_cores_in_use", "Number of availability cores occupied by candidates of the parachain"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		max_candidate_depth: metrics::register(
+			IntGauge::new("pc_max_candidate_depth", "Maximum number of candidates built on top of an unincluded one, from the host configuration, 0 before async backing")?,
+			registry,
+		)?,
+		allowed_ancestry_len: metrics::register(
+			IntGauge::new("pc_allowed_ancestry_len", "Number of relay chain blocks before the most recent one allowed as relay parents, from the host configuration, 0 before async backing")?,
+			registry,
+		)?,
+		hook_metrics: metrics::register(
+			GaugeVec::new(
+				Opts::new("pc_hook_metric", "Metrics set by the scriptable hooks with `set_metric`"),
+				&["script", "name"],
+			)?,
+			registry,
+		)?,
+		watch_expressions: metrics::register(
+			GaugeVec::new(
+				Opts::new("pc_watch_expression", "Values of the watch expressions evaluated on every parachain progress"),
+				&[para_labels, &["name"]].concat(),
+			)?,
+			registry,
+		)?,
+		xcm,
+		para_on_demand_orders: metrics::register(
 			GaugeVec::new(
 				Opts::new("pc_para_on_demand_orders", "Parachain's on demand orders"),
-				&["parachain_id"],
+				para_labels,
 			)?,
 			registry,
 		)?,
-		para_on_demand_delay: prometheus_endpoint::register(
+		para_on_demand_delay: metrics::register(
 			GaugeVec::new(
 				Opts::new("pc_para_on_demand_delay", "Latency (in relay chain blocks) between when the parachain orders a core and when first candidate is scheduled or backed on that core."),
-				&["parachain_id", "until"],
+				&[para_labels, &["until"]].concat(),
 			)?,
 			registry,
 		)?,
-		para_on_demand_delay_sec: prometheus_endpoint::register(
+		para_on_demand_delay_sec: metrics::register(
 			GaugeVec::new(
 				Opts::new("pc_para_on_demand_delay_sec", "Latency (in seconds) between when the parachain orders a core and when first candidate is scheduled or backed on that core."),
-				&["parachain_id"],
+				para_labels,
+			)?,
+			registry,
+		)?,
+		ondemand_order_latency: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new(
+					"pc_ondemand_order_latency",
+					"Latency between placing an on-demand order and scheduling the parachain, backing and including its candidate, measured in seconds.",
+				)
+				.buckets(opts.buckets.para_time_seconds()),
+				&[para_labels, &["until"]].concat(),
+			)?,
+			registry,
+		)?,
+		ondemand_spot_price: metrics::register(
+			GaugeVec::new(
+				Opts::new("pc_ondemand_spot_price", "Spot price paid for the last on-demand order of the parachain"),
+				para_labels,
 			)?,
 			registry,
 		)?,
-		finality_lag: prometheus_endpoint::register(
+		finality_lag: metrics::register(
 			Gauge::new("pc_finality_lag", "Finality lag")?,
 			registry,
 		)?,
+		bitfield_participation: metrics::register(
+			Gauge::new("pc_relay_bitfield_participation", "Percentage of active validators that contributed bitfields to the relay chain block")?,
+			registry,
+		)?,
+		bitfield_participation_avg: metrics::register(
+			Gauge::new("pc_relay_bitfield_participation_avg", "Rolling average of the relay chain bitfield participation")?,
+			registry,
+		)?,
+		bitfield_participation_drops: metrics::register(
+			IntCounter::new("pc_relay_bitfield_participation_drops", "Number of relay chain bitfield participation drops below the rolling average")?,
+			registry,
+		)?,
+		availability_culprits: metrics::register(
+			IntGaugeVec::new(
+				Opts::new("pc_availability_culprit_missed_blocks", "Number of blocks in the last window where the top offenders missed their availability bits"),
+				&["validator_index", "address"],
+			)?,
+			registry,
+		)?,
+		inherent_shares: metrics::register(
+			GaugeVec::new(
+				Opts::new("pc_relay_inherent_share", "Percentage of the encoded paras inherent taken by its components"),
+				&["component"],
+			)?,
+			registry,
+		)?,
+		inherent_weight: metrics::register(
+			GaugeVec::new(
+				Opts::new("pc_relay_inherent_weight", "Actual weight of the paras inherent reported by the runtime"),
+				&["resource"],
+			)?,
+			registry,
+		)?,
+		inherent_crowded_out: metrics::register(
+			IntCounter::new("pc_relay_inherent_crowded_out", "Number of relay chain blocks where disputes took the largest share of the paras inherent while scheduled cores were left without backed candidates")?,
+			registry,
+		)?,
+		inherent_dispute_sets: metrics::register(
+			IntGauge::new("pc_relay_inherent_dispute_sets", "Number of dispute statement sets in the paras inherent of the last relay chain block")?,
+			registry,
+		)?,
+		inherent_dispute_statements: metrics::register(
+			IntGauge::new("pc_relay_inherent_dispute_statements", "Number of dispute statements in the paras inherent of the last relay chain block")?,
+			registry,
+		)?,
+		inherent_dispute_bytes: metrics::register(
+			IntGauge::new("pc_relay_inherent_dispute_bytes", "Encoded size of the dispute statement sets in the paras inherent of the last relay chain block")?,
+			registry,
+		)?,
+		inherent_dispute_stale_sets: metrics::register(
+			IntCounter::new("pc_relay_inherent_dispute_stale_sets_total", "Number of dispute statement sets in the paras inherent referring to sessions older than the current one")?,
+			registry,
+		)?,
+		spec_version: metrics::register(
+			IntGauge::new("pc_relay_spec_version", "Runtime spec version of the last relay chain block, changes mark runtime upgrades")?,
+			registry,
+		)?,
+		relay_reorgs: metrics::register(
+			IntCounter::new("pc_relay_reorgs_total", "Number of relay chain reorgs abandoning the blocks followed as the main chain")?,
+			registry,
+		)?,
+		relay_best_block: metrics::register(
+			IntGauge::new("pc_relay_best_block_number", "Number of the last relay chain block processed by the tracer")?,
+			registry,
+		)?,
+		relay_finalized_block: metrics::register(
+			IntGauge::new("pc_relay_finalized_block_number", "Number of the last finalized relay chain block known when the last block was processed")?,
+			registry,
+		)?,
+		relay_block_timestamp: metrics::register(
+			Gauge::new("pc_relay_block_timestamp", "Unix timestamp of the last relay chain block processed by the tracer, in seconds")?,
+			registry,
+		)?,
+		relay_processed_timestamp: metrics::register(
+			Gauge::new("pc_relay_block_processed_timestamp", "Unix time when the last relay chain block was processed by the tracer, in seconds")?,
+			registry,
+		)?,
+		collector_events: metrics::register(
+			IntGaugeVec::new(Opts::new("pc_collector_events", "Number of chain events decoded and skipped by the collector since the start"), &["outcome"])?,
+			registry,
+		)?,
+		event_decode_time: metrics::register(
+			Gauge::new("pc_collector_event_decode_seconds", "Time spent by the collector decoding chain events since the start")?,
+			registry,
+		)?,
+		event_decode_saved_time: metrics::register(
+			Gauge::new("pc_collector_event_decode_saved_seconds", "Estimated time saved by skipping the chain events not configured for decoding")?,
+			registry,
+		)?,
+		cores_occupied: metrics::register(
+			IntGauge::new("pc_cores_occupied", "Number of availability cores occupied by candidates pending availability in the last relay chain block")?,
+			registry,
+		)?,
+		cores_free: metrics::register(
+			IntGauge::new("pc_cores_free", "Number of availability cores not occupied in the last relay chain block")?,
+			registry,
+		)?,
+		session_index: metrics::register(
+			IntGauge::new("pc_session_index", "Session index of the last relay chain block")?,
+			registry,
+		)?,
+		session_change_timestamp: metrics::register(
+			Gauge::new("pc_session_change_timestamp", "Unix timestamp of the first relay chain block of the last session change, in seconds, for dashboard annotations")?,
+			registry,
+		)?,
+		session_change_block: metrics::register(
+			IntGauge::new("pc_session_change_block", "Number of the first relay chain block of the last session change")?,
+			registry,
+		)?,
+		alerts: metrics::register(
+			IntCounterVec::new(
+				Opts::new("pc_alerts_total", "Number of alerts raised on parachains not backing or including candidates"),
+				&[para_labels, &["kind"]].concat(),
+			)?,
+			registry,
+		)?,
+		system_parachain_activity: metrics::register(
+			IntCounterVec::new(
+				Opts::new(
+					"pc_system_parachain_activity_total",
+					"Activity of system parachain blocks, e.g. bridge messages or asset transactions",
+				),
+				&[para_labels, &["counter"]].concat(),
+			)?,
+			registry,
+		)?,
+		pov_size: metrics::register(
+			HistogramVec::new(
+				HistogramOpts::new(
+					"pc_pov_size_bytes",
+					"Estimated PoV sizes of the backed candidates, the encoded size of their parachain blocks without the storage proof",
+				)
+				.buckets(HISTOGRAM_POV_SIZE_BUCKETS.into()),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		para_block_weight: metrics::register(
+			GaugeVec::new(
+				Opts::new(
+					"pc_para_block_weight",
+					"Weight consumed by the parachain block of the last backed candidate, by the `ref_time` and `proof_size` resources",
+				),
+				&[para_labels, &["resource"]].concat(),
+			)?,
+			registry,
+		)?,
+		pov_candidates_skipped: metrics::register(
+			IntCounterVec::new(
+				Opts::new(
+					"pc_pov_candidates_skipped_total",
+					"Number of backed candidates whose PoV size is not estimated as the fetcher of the parachain blocks fell behind",
+				),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		session_summary: metrics::register(
+			GaugeVec::new(
+				Opts::new(
+					"pc_session_summary",
+					"Statistics of the parachain over the last finished session: session_index, blocks, backed, included, skipped_slots, disputes and average inclusion time",
+				),
+				&[para_labels, &["stat"]].concat(),
+			)?,
+			registry,
+		)?,
+		approval_noshow_suspected: metrics::register(
+			IntCounterVec::new(
+				Opts::new(
+					"pc_approval_noshow_suspected",
+					"Number of candidates not finalized within the no-show slots beyond the median finality lag after inclusion, suggesting approval checkers missing their votes",
+				),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		para_head_mismatches: metrics::register(
+			IntCounterVec::new(
+				Opts::new(
+					"pc_para_head_mismatch_total",
+					"Number of parachain heads included on the relay chain with another block at the same height on the parachain node",
+				),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		para_head_divergences: metrics::register(
+			IntCounterVec::new(
+				Opts::new(
+					"pc_para_head_divergence_total",
+					"Number of times the parachain head on a relay chain node differed from the traced node for longer than the threshold",
+				),
+				&[para_labels, &["node"]].concat(),
+			)?,
+			registry,
+		)?,
+		hrmp_channel_fill_ratio: metrics::register(
+			GaugeVec::new(
+				Opts::new(
+					"pc_hrmp_channel_fill_ratio",
+					"Share of the capacity of an HRMP channel in use, by message count or total size, whichever is higher",
+				),
+				&[para_labels, &["sender", "recipient"]].concat(),
+			)?,
+			registry,
+		)?,
+		dmp_queue_len: metrics::register(
+			IntGaugeVec::new(
+				Opts::new("pc_dmp_queue_len", "Number of messages in the downward queue of a parachain"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		ump_queue_len: metrics::register(
+			IntGaugeVec::new(
+				Opts::new("pc_ump_queue_len", "Number of messages in the upward queue of a parachain"),
+				para_labels,
+			)?,
+			registry,
+		)?,
+		dispute_participation_pending: metrics::register(
+			IntGauge::new("pc_dispute_participation_pending", "Number of disputes without a statement of the monitored validator")?,
+			registry,
+		)?,
+		dispute_participation_missed: metrics::register(
+			IntCounter::new("pc_dispute_participation_missed", "Number of disputes where the monitored validator has not cast a statement in time")?,
+			registry,
+		)?,
+		para_info: metrics::register(
+			IntGaugeVec::new(
+				Opts::new("pc_para_info", "Name of a parachain, always 1"),
+				&[para_labels, &["para_name"]].concat(),
+			)?,
+			registry,
+		)?,
+		sampling: MetricsSampling::new(opts),
 	})))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn create_sampling(block_time_sample_rate: u64, histogram_paras: Vec<u32>) -> MetricsSampling {
+		MetricsSampling::new(&ParachainTracerPrometheusOptions {
+			block_time_sample_rate,
+			histogram_paras,
+			..Default::default()
+		})
+	}
+
+	#[test]
+	fn test_samples_block_times_per_para() {
+		let sampling = create_sampling(3, vec![]);
+
+		let sampled: Vec<bool> = (0..6).map(|_| sampling.sample_block_time(100)).collect();
+		assert_eq!(sampled, vec![true, false, false, true, false, false]);
+		assert!(sampling.sample_block_time(200));
+	}
+
+	#[test]
+	fn test_exports_histograms_for_listed_paras() {
+		let sampling = create_sampling(0, vec![100]);
+
+		assert!(sampling.has_histograms(100));
+		assert!(!sampling.has_histograms(200));
+		assert!(sampling.sample_block_time(100));
+		assert!(!sampling.sample_block_time(200));
+	}
+
+	#[test]
+	fn test_parses_histogram_buckets() {
+		assert_eq!("0.5, 1,2,4".parse(), Ok(HistogramBuckets(vec![0.5, 1.0, 2.0, 4.0])));
+		assert!("1,2,x".parse::<HistogramBuckets>().is_err());
+		assert!("1,inf".parse::<HistogramBuckets>().is_err());
+		assert!("1,3,2".parse::<HistogramBuckets>().is_err());
+
+		let opts = HistogramBucketsOptions { para_time_buckets_blocks: "1,2".parse().ok(), ..Default::default() };
+		assert_eq!(opts.para_time_blocks(), vec![1.0, 2.0]);
+		assert_eq!(opts.dispute_resolve_time(), HISTOGRAM_TIME_BUCKETS_BLOCKS.to_vec());
+	}
+
+	#[test]
+	fn test_updates_and_removes_para_series() {
+		let registry = MetricsRegistry::new("introspector").unwrap();
+		let metrics = register_metrics(&registry, &Default::default()).unwrap();
+		metrics.on_para_name(2004, "");
+		metrics.on_para_name(2004, "Moonbeam");
+		metrics.on_para_name(2000, "Acala");
+
+		let para_info = &metrics.0.as_ref().unwrap().para_info;
+		assert_eq!(para_series(para_info, 2004), vec![vec!["2004".to_string(), "Moonbeam".to_string()]]);
+		assert_eq!(para_series(para_info, 2000), vec![vec!["2000".to_string(), "Acala".to_string()]]);
+
+		metrics.on_backed(3, 5, 2004);
+		metrics.on_block(6.0, 2004);
+		metrics.on_para_removed(2004);
+		let inner = metrics.0.as_ref().unwrap();
+		assert!(para_series(&inner.para_info, 2004).is_empty());
+		assert!(para_series(&inner.backed_count, 2004).is_empty());
+		assert!(para_series(&inner.backing_votes, 2004).is_empty());
+		assert_eq!(para_series(&inner.para_info, 2000).len(), 1);
+	}
+
+	#[test]
+	fn test_removes_closed_hrmp_channels() {
+		let registry = MetricsRegistry::new("introspector").unwrap();
+		let metrics = register_metrics(&registry, &Default::default()).unwrap();
+		let channel = |recipient| HrmpChannelState {
+			sender: 2004,
+			recipient,
+			msg_count: 1,
+			max_capacity: 10,
+			total_size: 0,
+			max_total_size: 1000,
+			sender_deposit: 0,
+			recipient_deposit: 0,
+		};
+		metrics.on_hrmp_channel(&channel(2000), 2004);
+		metrics.on_hrmp_channel(&channel(2006), 2004);
+
+		metrics.on_hrmp_channel_closed(2004, 2000, 2004);
+		let inner = metrics.0.as_ref().unwrap();
+		assert_eq!(
+			para_series(&inner.hrmp_channel_fill_ratio, 2004),
+			vec![vec!["2004".to_string(), "2004".to_string(), "2006".to_string()]]
+		);
+	}
+}
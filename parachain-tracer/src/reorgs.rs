@@ -0,0 +1,220 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Resolution of relay chain forks. The collector sends all blocks of a height before the next one, so forks are
+//! resolved by the next height with a single block: the chain continues from its ancestors, the other blocks since
+//! the last resolved height are abandoned. When a block processed first at its height, the one followed as the main
+//! chain, is abandoned, the relay chain has been reorganized.
+
+use crossterm::style::Stylize;
+use itertools::Itertools;
+use polkadot_introspector_essentials::types::{BlockNumber, H256};
+use std::fmt::{self, Display, Formatter};
+
+/// Number of forked heights after which the forks are resolved along the followed chain
+pub const MAX_FORKED_HEIGHTS: usize = 16;
+
+/// Blocks of the relay chain kept and abandoned since the last resolved height
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForkResolution {
+	/// Number of the first forked height
+	pub block_number: BlockNumber,
+	/// The blocks the chain continues from, in the ascending order of their numbers
+	pub chosen: Vec<(BlockNumber, H256)>,
+	/// The other blocks since the last resolved height
+	pub abandoned: Vec<H256>,
+	/// Whether a block followed as the main chain was abandoned
+	pub is_reorg: bool,
+}
+
+impl Display for ForkResolution {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"[#{}] {} relay chain switched to {}, abandoned {}",
+			self.block_number,
+			"REORG".to_string().bold().yellow(),
+			self.chosen.iter().map(|(_, hash)| format!("{:?}", hash)).join(", "),
+			self.abandoned.iter().map(|hash| format!("{:?}", hash)).join(", ")
+		)
+	}
+}
+
+/// A relay chain block at a forked height
+#[derive(Clone, Debug)]
+struct ForkedBlock {
+	hash: H256,
+	number: BlockNumber,
+	parent_hash: Option<H256>,
+	/// Processed first at its height
+	is_followed: bool,
+}
+
+/// Follows the blocks of the forked relay chain heights to tell which of them were abandoned
+#[derive(Clone, Default)]
+pub struct ReorgDetector {
+	/// Blocks since the last resolved height in the order they were processed
+	forked: Vec<ForkedBlock>,
+}
+
+impl ReorgDetector {
+	/// Processes a relay chain block, `is_forked` tells if there are other blocks at its height.
+	/// Returns the resolution of the forks since the last resolved height once they are resolved.
+	pub fn on_block(
+		&mut self,
+		block_hash: H256,
+		block_number: BlockNumber,
+		parent_hash: Option<H256>,
+		is_forked: bool,
+	) -> Option<ForkResolution> {
+		let is_new_height = self.forked.last().map_or(true, |block| block.number < block_number);
+		if is_new_height && !is_forked {
+			return (!self.forked.is_empty()).then(|| self.resolve(parent_hash))
+		}
+
+		// Bounds the blocks kept while the relay chain doesn't converge
+		let resolution = (is_new_height &&
+			self.forked.iter().map(|block| block.number).dedup().count() >= MAX_FORKED_HEIGHTS)
+			.then(|| self.resolve(None));
+		if !self.forked.iter().any(|block| block.hash == block_hash) {
+			self.forked.push(ForkedBlock {
+				hash: block_hash,
+				number: block_number,
+				parent_hash,
+				is_followed: is_new_height,
+			});
+		}
+
+		resolution
+	}
+
+	/// Whether blocks are kept until the forks are resolved
+	pub fn is_forked(&self) -> bool {
+		!self.forked.is_empty()
+	}
+
+	/// Chooses the ancestors of the `tip` since the last resolved height, the followed chain if they are unknown
+	fn resolve(&mut self, tip: Option<H256>) -> ForkResolution {
+		let forked = std::mem::take(&mut self.forked);
+		let mut chosen = vec![];
+		let mut next = tip;
+		while let Some(block) = next.and_then(|hash| forked.iter().find(|block| block.hash == hash)) {
+			chosen.push((block.number, block.hash));
+			next = block.parent_hash;
+		}
+		chosen.reverse();
+		if chosen.is_empty() {
+			chosen = forked
+				.iter()
+				.filter(|block| block.is_followed)
+				.map(|block| (block.number, block.hash))
+				.collect();
+		}
+
+		let abandoned: Vec<_> = forked
+			.iter()
+			.filter(|block| !chosen.iter().any(|(_, hash)| *hash == block.hash))
+			.collect();
+		ForkResolution {
+			block_number: forked[0].number,
+			is_reorg: abandoned.iter().any(|block| block.is_followed),
+			abandoned: abandoned.into_iter().map(|block| block.hash).collect(),
+			chosen,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_detects_reorgs() {
+		let mut detector = ReorgDetector::default();
+		let (first, second) = (H256::random(), H256::random());
+
+		assert!(detector.on_block(first, 10, Some(H256::random()), true).is_none());
+		assert!(detector.on_block(second, 10, Some(H256::random()), true).is_none());
+		assert!(detector.is_forked());
+		let resolution = detector.on_block(H256::random(), 11, Some(second), false).unwrap();
+		assert_eq!(
+			resolution,
+			ForkResolution { block_number: 10, chosen: vec![(10, second)], abandoned: vec![first], is_reorg: true }
+		);
+		assert!(!detector.is_forked());
+	}
+
+	#[test]
+	fn test_resolves_forks_without_reorgs() {
+		let mut detector = ReorgDetector::default();
+		let (first, second, next) = (H256::random(), H256::random(), H256::random());
+
+		assert!(detector.on_block(H256::random(), 9, None, false).is_none());
+		assert!(!detector.is_forked());
+		detector.on_block(first, 10, None, true);
+		detector.on_block(second, 10, None, true);
+		let resolution = detector.on_block(next, 11, Some(first), false).unwrap();
+		assert!(!resolution.is_reorg);
+		assert_eq!(resolution.abandoned, vec![second]);
+
+		// No forks at the previous height
+		assert!(detector.on_block(H256::random(), 12, Some(next), false).is_none());
+		// Unknown parent, the followed chain is chosen
+		let (first, second) = (H256::random(), H256::random());
+		detector.on_block(first, 13, None, true);
+		detector.on_block(second, 13, None, true);
+		let resolution = detector.on_block(H256::random(), 14, Some(H256::random()), false).unwrap();
+		assert_eq!(resolution.chosen, vec![(13, first)]);
+		assert!(!resolution.is_reorg);
+	}
+
+	#[test]
+	fn test_resolves_deep_reorgs() {
+		let mut detector = ReorgDetector::default();
+		let (a1, a2, b1, b2) = (H256::random(), H256::random(), H256::random(), H256::random());
+
+		detector.on_block(a1, 10, None, true);
+		detector.on_block(b1, 10, None, true);
+		detector.on_block(a2, 11, Some(a1), true);
+		detector.on_block(b2, 11, Some(b1), true);
+		let resolution = detector.on_block(H256::random(), 12, Some(b2), false).unwrap();
+		assert_eq!(
+			resolution,
+			ForkResolution {
+				block_number: 10,
+				chosen: vec![(10, b1), (11, b2)],
+				abandoned: vec![a1, a2],
+				is_reorg: true
+			}
+		);
+	}
+
+	#[test]
+	fn test_bounds_forked_heights() {
+		let mut detector = ReorgDetector::default();
+		let followed: Vec<_> = (0..MAX_FORKED_HEIGHTS as u32).map(|_| H256::random()).collect();
+		for (number, hash) in followed.iter().enumerate() {
+			assert!(detector.on_block(*hash, number as u32, None, true).is_none());
+			assert!(detector.on_block(H256::random(), number as u32, None, true).is_none());
+		}
+
+		let next = H256::random();
+		let resolution = detector.on_block(next, MAX_FORKED_HEIGHTS as u32, None, true).unwrap();
+		assert_eq!(resolution.chosen.into_iter().map(|(_, hash)| hash).collect::<Vec<_>>(), followed);
+		assert!(!resolution.is_reorg);
+		assert!(detector.is_forked());
+	}
+}
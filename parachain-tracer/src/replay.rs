@@ -0,0 +1,260 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Replay of the tracer analysis from a relay chain node database instead of RPC
+//!
+//! The database is served within the process as a node RPC with the methods the tracer calls. Blocks and storage are
+//! read with the `polkadot-kvdb` tool, storage of pruned states cannot be read, so old blocks need an archive node
+//! database. The runtime is not executed, the metadata is bundled or read from a file, runtime API calls fail.
+
+use clap::Parser;
+use color_eyre::{eyre::eyre, Result};
+use parity_scale_codec::{Compact, Decode, Encode};
+use polkadot_introspector_essentials::{
+	api::api_client::RpcClientFactory,
+	types::{BlockNumber, H256},
+};
+#[cfg(feature = "rocksdb")]
+use polkadot_kvdb::rocksdb::IntrospectorRocksDB;
+use polkadot_kvdb::{
+	autodetect_db_type, node::NodeDb, paritydb::IntrospectorParityDB, sled::IntrospectorSled, IntrospectorKvdb,
+	KvdbType,
+};
+use serde_json::{json, value::RawValue, Value};
+use std::{future, path::PathBuf, sync::Arc};
+use subxt::{
+	backend::rpc::{RawRpcFuture, RawRpcSubscription, RpcClient, RpcClientT},
+	error::RpcError,
+};
+
+/// The metadata the tracer is built with
+const BUNDLED_METADATA: &[u8] = include_bytes!("../../essentials/assets/polkadot_metadata.scale");
+/// Storage key of `System::LastRuntimeUpgrade`
+const LAST_RUNTIME_UPGRADE_KEY: &str = "26aa394eea5630e07c48ae0c9558cef7f9cce9c888469bb1a0dceaa129672ef8";
+
+#[derive(Clone, Debug, Parser)]
+#[clap(rename_all = "kebab-case")]
+pub(crate) struct ReplayOptions {
+	/// Path to the relay chain database of a node, e.g. `~/.local/share/polkadot/chains/polkadot/db/full`
+	#[clap(long)]
+	db: String,
+	/// Database type
+	#[clap(long, default_value_t)]
+	db_type: KvdbType,
+	/// SCALE-encoded metadata of the runtime of the replayed blocks, the bundled Polkadot metadata by default
+	#[clap(long)]
+	metadata: Option<PathBuf>,
+}
+
+/// Serves a node database as RPC to the clients built by the factory, returns the URL to trace it with
+pub(crate) fn serve(opts: &ReplayOptions, client_factory: &mut RpcClientFactory) -> Result<String> {
+	let metadata = match opts.metadata {
+		Some(ref path) =>
+			std::fs::read(path).map_err(|e| eyre!("Cannot read metadata from {}: {}", path.display(), e))?,
+		None => BUNDLED_METADATA.to_vec(),
+	};
+	let path = std::path::Path::new(opts.db.as_str());
+	let db_type = if opts.db_type == KvdbType::Auto { autodetect_db_type(opts.db.as_str())? } else { opts.db_type };
+	let rpc_client = match db_type {
+		KvdbType::Auto => unreachable!(),
+		#[cfg(feature = "rocksdb")]
+		KvdbType::RocksDB => DatabaseRpc::new_client(IntrospectorRocksDB::new(path)?, metadata),
+		#[cfg(not(feature = "rocksdb"))]
+		KvdbType::RocksDB =>
+			return Err(eyre!(
+				"RocksDB support is disabled in this build, dump the database with `polkadot-kvdb dump --format sled`"
+			)),
+		KvdbType::ParityDB => DatabaseRpc::new_client(IntrospectorParityDB::new(path)?, metadata),
+		KvdbType::Sled => DatabaseRpc::new_client(IntrospectorSled::new(path)?, metadata),
+	};
+
+	let url = format!("db://{}", opts.db);
+	client_factory.register(&url, rpc_client);
+	Ok(url)
+}
+
+/// Node RPC served from a database
+struct DatabaseRpc<D> {
+	db: Arc<NodeDb<D>>,
+	metadata: Arc<Vec<u8>>,
+}
+
+impl<D: IntrospectorKvdb + Send + Sync + 'static> DatabaseRpc<D> {
+	fn new_client(db: D, metadata: Vec<u8>) -> RpcClient {
+		RpcClient::new(Self { db: Arc::new(NodeDb::new(db)), metadata: Arc::new(metadata) })
+	}
+
+	fn request(&self, method: &str, params: Option<Box<RawValue>>) -> Result<Value> {
+		let params: Vec<Value> = match params {
+			Some(params) => serde_json::from_str(params.get())?,
+			None => vec![],
+		};
+
+		match method {
+			"chain_getBlockHash" => {
+				let hash = match block_number_param(&params, 0)? {
+					Some(number) => self.db.block_hash(number)?,
+					None => Some(self.db.best_hash()?),
+				};
+				Ok(serde_json::to_value(hash)?)
+			},
+			"chain_getFinalizedHead" => Ok(serde_json::to_value(self.db.finalized_hash()?)?),
+			"chain_getHeader" => Ok(serde_json::to_value(self.db.header(self.block_param(&params, 0)?)?)?),
+			"chain_getBlock" => {
+				let hash = self.block_param(&params, 0)?;
+				match (self.db.header(hash)?, self.db.body(hash)?) {
+					(Some(header), Some(extrinsics)) => {
+						// Opaque extrinsics are returned with their length prefixes
+						let extrinsics: Vec<String> =
+							extrinsics.iter().map(|extrinsic| hex_bytes(&extrinsic.encode())).collect();
+						Ok(json!({ "block": { "header": header, "extrinsics": extrinsics }, "justifications": null }))
+					},
+					_ => Ok(Value::Null),
+				}
+			},
+			"state_getStorage" => {
+				let key = bytes_param(&params, 0)?;
+				let value = self.db.storage(self.state_root(&params, 1)?, &key)?;
+				Ok(serde_json::to_value(value.map(|value| hex_bytes(&value)))?)
+			},
+			"state_getRuntimeVersion" => self.runtime_version(self.state_root(&params, 0)?),
+			"state_getMetadata" => Ok(Value::String(hex_bytes(&self.metadata))),
+			"state_call" => self.runtime_call(&params),
+			_ => Err(eyre!("{} is not supported when replaying from a database", method)),
+		}
+	}
+
+	/// Returns the block of a hash parameter, the best one if it is missing
+	fn block_param(&self, params: &[Value], index: usize) -> Result<H256> {
+		match params.get(index) {
+			None | Some(Value::Null) => self.db.best_hash(),
+			Some(param) => Ok(serde_json::from_value(param.clone())?),
+		}
+	}
+
+	fn state_root(&self, params: &[Value], index: usize) -> Result<H256> {
+		let hash = self.block_param(params, index)?;
+		let header = self.db.header(hash)?.ok_or_else(|| eyre!("Block {:?} not found", hash))?;
+		Ok(header.state_root)
+	}
+
+	/// The runtime version is not stored, but the spec of the last upgrade is
+	fn runtime_version(&self, state_root: H256) -> Result<Value> {
+		let value = self
+			.db
+			.storage(state_root, &hex::decode(LAST_RUNTIME_UPGRADE_KEY)?)?
+			.ok_or_else(|| eyre!("Missing the last runtime upgrade in the state"))?;
+		let (spec_version, spec_name) = <(Compact<u32>, String)>::decode(&mut value.as_slice())?;
+
+		Ok(json!({
+			"specName": spec_name,
+			"implName": spec_name,
+			"authoringVersion": 0,
+			"specVersion": spec_version.0,
+			"implVersion": 0,
+			"apis": [],
+			"transactionVersion": 0,
+			"stateVersion": 0,
+		}))
+	}
+
+	/// Only the metadata API is available without executing the runtime
+	fn runtime_call(&self, params: &[Value]) -> Result<Value> {
+		let method = params.first().and_then(Value::as_str).unwrap_or_default();
+		// The metadata starts with the magic number `meta` followed by its version
+		let version = self.metadata.get(4).copied().unwrap_or_default() as u32;
+		let response = match method {
+			"Metadata_metadata" => self.metadata.as_slice().encode(),
+			"Metadata_metadata_versions" => vec![version].encode(),
+			"Metadata_metadata_at_version" => {
+				let requested = u32::decode(&mut bytes_param(params, 1)?.as_slice())?;
+				(requested == version).then_some(self.metadata.as_slice()).encode()
+			},
+			_ => return Err(eyre!("Runtime API {} is not supported when replaying from a database", method)),
+		};
+
+		Ok(Value::String(hex_bytes(&response)))
+	}
+}
+
+impl<D: IntrospectorKvdb + Send + Sync + 'static> RpcClientT for DatabaseRpc<D> {
+	fn request_raw<'a>(&'a self, method: &'a str, params: Option<Box<RawValue>>) -> RawRpcFuture<'a, Box<RawValue>> {
+		let response = self
+			.request(method, params)
+			.and_then(|value| serde_json::value::to_raw_value(&value).map_err(Into::into))
+			.map_err(|e| RpcError::ClientError(e.into()));
+		Box::pin(future::ready(response))
+	}
+
+	fn subscribe_raw<'a>(
+		&'a self,
+		sub: &'a str,
+		_params: Option<Box<RawValue>>,
+		_unsub: &'a str,
+	) -> RawRpcFuture<'a, RawRpcSubscription> {
+		let error = eyre!("Subscription {} is not supported when replaying from a database", sub);
+		Box::pin(future::ready(Err(RpcError::ClientError(error.into()))))
+	}
+}
+
+/// Block numbers are passed either as numbers or as hex strings
+fn block_number_param(params: &[Value], index: usize) -> Result<Option<BlockNumber>> {
+	match params.get(index) {
+		None | Some(Value::Null) => Ok(None),
+		Some(Value::Number(number)) => number
+			.as_u64()
+			.and_then(|number| BlockNumber::try_from(number).ok())
+			.map(Some)
+			.ok_or_else(|| eyre!("Invalid block number: {}", number)),
+		Some(Value::String(number)) => Ok(Some(BlockNumber::from_str_radix(number.trim_start_matches("0x"), 16)?)),
+		Some(param) => Err(eyre!("Invalid block number: {}", param)),
+	}
+}
+
+fn bytes_param(params: &[Value], index: usize) -> Result<Vec<u8>> {
+	let param = params
+		.get(index)
+		.and_then(Value::as_str)
+		.ok_or_else(|| eyre!("Missing bytes parameter {}", index))?;
+	Ok(hex::decode(param.trim_start_matches("0x"))?)
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+	format!("0x{}", hex::encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parses_params() {
+		let params = [json!(15000000), json!("0xe4e1c0"), Value::Null, json!(-1)];
+		assert_eq!(block_number_param(&params, 0).unwrap(), Some(15000000));
+		assert_eq!(block_number_param(&params, 1).unwrap(), Some(15000000));
+		assert_eq!(block_number_param(&params, 2).unwrap(), None);
+		assert_eq!(block_number_param(&params, 4).unwrap(), None);
+		assert!(block_number_param(&params, 3).is_err());
+
+		assert_eq!(bytes_param(&[json!("0x0f000000")], 0).unwrap(), vec![15, 0, 0, 0]);
+		assert!(bytes_param(&[], 0).is_err());
+	}
+
+	#[test]
+	fn test_bundles_metadata() {
+		assert_eq!(&BUNDLED_METADATA[..4], b"meta");
+	}
+}
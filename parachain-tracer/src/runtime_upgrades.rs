@@ -0,0 +1,131 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! This module tracks the runtime spec version of relay chain blocks, so changes in the metrics can be
+//! attributed to runtime upgrades.
+
+use crate::explorer::Explorer;
+use color_eyre::owo_colors::OwoColorize;
+use crossterm::style::Stylize;
+use polkadot_introspector_essentials::types::BlockNumber;
+use std::fmt::{self, Display, Formatter};
+
+/// A change of the runtime spec version between relay chain blocks
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeUpgrade {
+	/// Relay chain block number of the first block with the new runtime
+	pub block_number: BlockNumber,
+	/// Spec version before the upgrade
+	pub previous: u32,
+	/// Spec version after the upgrade
+	pub current: u32,
+	pub explorer: Explorer,
+}
+
+/// Tracks the runtime spec versions and the blocks they were upgraded at
+#[derive(Default)]
+pub struct RuntimeUpgradeTracker {
+	/// Spec versions with the first blocks they were observed at, in the order of observation
+	versions: Vec<(BlockNumber, u32)>,
+	explorer: Explorer,
+}
+
+impl RuntimeUpgradeTracker {
+	/// Links the upgrades to the block explorer
+	pub fn with_explorer(mut self, explorer: Explorer) -> Self {
+		self.explorer = explorer;
+		self
+	}
+
+	/// Processes the spec version of a relay chain block, returns an upgrade if the version has changed
+	pub fn on_block(&mut self, block_number: BlockNumber, spec_version: u32) -> Option<RuntimeUpgrade> {
+		let previous = self.current();
+		if previous == Some(spec_version) {
+			return None
+		}
+
+		self.versions.push((block_number, spec_version));
+		previous.map(|previous| RuntimeUpgrade {
+			block_number,
+			previous,
+			current: spec_version,
+			explorer: self.explorer.clone(),
+		})
+	}
+
+	/// Returns the spec version of the last processed block
+	pub fn current(&self) -> Option<u32> {
+		self.versions.last().map(|(_, spec_version)| *spec_version)
+	}
+}
+
+impl Display for RuntimeUpgrade {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(
+			f,
+			"[#{}] {} spec version {} -> {}",
+			self.block_number,
+			"RUNTIME UPGRADE".to_string().bold().blue(),
+			self.previous,
+			self.current.to_string().bold()
+		)?;
+		self.explorer.write_block_link(f, self.block_number)
+	}
+}
+
+impl Display for RuntimeUpgradeTracker {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		writeln!(f, "{}", "--- Relay chain runtime versions ---".to_string().bold().blue())?;
+		if self.versions.is_empty() {
+			return writeln!(f, "No blocks processed")
+		}
+		for (block_number, spec_version) in &self.versions {
+			writeln!(f, "Spec version {} from #{}", spec_version.to_string().bold(), block_number)?;
+		}
+		if self.versions.len() > 1 {
+			writeln!(
+				f,
+				"{} runtime upgrade(s) observed, compare the metrics across these boundaries",
+				(self.versions.len() - 1).to_string().bright_yellow()
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_detects_runtime_upgrades() {
+		let mut tracker = RuntimeUpgradeTracker::default();
+		assert!(tracker.on_block(40, 1_002_000).is_none());
+		assert!(tracker.on_block(41, 1_002_000).is_none());
+		assert_eq!(
+			tracker.on_block(42, 1_003_000),
+			Some(RuntimeUpgrade {
+				block_number: 42,
+				previous: 1_002_000,
+				current: 1_003_000,
+				explorer: Default::default()
+			})
+		);
+		assert!(tracker.on_block(43, 1_003_000).is_none());
+		assert_eq!(tracker.current(), Some(1_003_000));
+		assert_eq!(tracker.versions, vec![(40, 1_002_000), (42, 1_003_000)]);
+	}
+}
@@ -0,0 +1,139 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Versioned JSON schemas of the machine-readable outputs.
+//!
+//! Schemas are kept in `schemas/<name>.v<major>.json` and embedded into the binary, `--schema <name>` prints them.
+//! Within a major version the outputs stay compatible:
+//! - a minor version may only add fields which are not required, consumers should ignore unknown fields
+//! - removing or renaming a field, changing its type or making it required is a new major version,
+//!   which gets a new file and `$id`
+//!
+//! Tests check that the serialized outputs conform to their schemas, so a change of the output structs
+//! fails them until the schema is updated.
+
+/// A JSON schema of an output
+#[derive(Clone, Copy, Debug)]
+pub struct Schema {
+	/// Name of the output
+	pub name: &'static str,
+	/// Version of the schema, `major.minor`
+	pub version: &'static str,
+	/// The schema itself
+	pub json: &'static str,
+}
+
+/// Schemas of all outputs
+pub const SCHEMAS: [Schema; 7] = [
+	Schema { name: "observation", version: "1.1", json: include_str!("../schemas/observation.v1.json") },
+	Schema { name: "clickhouse-block", version: "1.0", json: include_str!("../schemas/clickhouse-block.v1.json") },
+	Schema { name: "clickhouse-core", version: "1.0", json: include_str!("../schemas/clickhouse-core.v1.json") },
+	Schema {
+		name: "clickhouse-candidate",
+		version: "1.0",
+		json: include_str!("../schemas/clickhouse-candidate.v1.json"),
+	},
+	Schema { name: "progress", version: "1.6", json: include_str!("../schemas/progress.v1.json") },
+	Schema { name: "summary", version: "1.5", json: include_str!("../schemas/summary.v1.json") },
+	Schema { name: "candidate-timeline", version: "1.0", json: include_str!("../schemas/candidate-timeline.v1.json") },
+];
+
+/// Returns the schema of the output with this name
+pub fn find(name: &str) -> Option<&'static Schema> {
+	SCHEMAS.iter().find(|schema| schema.name == name)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use serde_json::Value;
+
+	/// Checks types, required and undeclared fields of the value, other keywords are not supported.
+	/// Unlike the JSON schema validation, undeclared fields are errors to keep schemas complete.
+	fn check(value: &Value, schema: &Value, root: &Value, path: &str) -> Result<(), String> {
+		if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+			let name = reference
+				.strip_prefix("#/$defs/")
+				.ok_or(format!("{}: unsupported $ref", path))?;
+			return check(value, &root["$defs"][name], root, path)
+		}
+
+		let expected = schema["type"].as_str().ok_or(format!("{}: no type", path))?;
+		let matches = match expected {
+			"object" => value.is_object(),
+			"array" => value.is_array(),
+			"string" => value.is_string(),
+			"integer" => value.is_u64() || value.is_i64(),
+			"number" => value.is_number(),
+			"boolean" => value.is_boolean(),
+			_ => return Err(format!("{}: unsupported type {}", path, expected)),
+		};
+		if !matches {
+			return Err(format!("{}: expected {}, got {}", path, expected, value))
+		}
+		if let Some(minimum) = schema.get("minimum").and_then(Value::as_i64) {
+			if value.as_i64().is_some_and(|value| value < minimum) {
+				return Err(format!("{}: less than {}", path, minimum))
+			}
+		}
+
+		if let Some(object) = value.as_object() {
+			for required in schema["required"].as_array().into_iter().flatten() {
+				let required = required.as_str().unwrap_or_default();
+				if !object.contains_key(required) {
+					return Err(format!("{}: missing {}", path, required))
+				}
+			}
+			for (key, field) in object {
+				let field_schema = schema["properties"]
+					.get(key)
+					.ok_or(format!("{}: undeclared field {}", path, key))?;
+				check(field, field_schema, root, &format!("{}.{}", path, key))?;
+			}
+		}
+		if let Some(items) = value.as_array() {
+			for (idx, item) in items.iter().enumerate() {
+				check(item, &schema["items"], root, &format!("{}[{}]", path, idx))?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Panics if the serialized value does not conform to the named schema
+	pub fn assert_conforms(name: &str, value: &Value) {
+		let schema: Value = serde_json::from_str(find(name).unwrap().json).unwrap();
+		if let Err(e) = check(value, &schema, &schema, "$") {
+			panic!("{} does not conform to its schema: {}", name, e);
+		}
+	}
+
+	#[test]
+	fn test_schemas_are_versioned() {
+		for schema in SCHEMAS {
+			let json: Value = serde_json::from_str(schema.json).unwrap();
+			let major = schema.version.split('.').next().unwrap();
+			assert_eq!(json["x-version"], schema.version, "{}", schema.name);
+			assert_eq!(json["$id"], format!("urn:polkadot-introspector:{}:v{}", schema.name, major), "{}", schema.name);
+		}
+	}
+
+	#[test]
+	fn test_finds_schemas() {
+		assert_eq!(find("observation").unwrap().name, "observation");
+		assert!(find("unknown").is_none());
+	}
+}
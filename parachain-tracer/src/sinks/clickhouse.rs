@@ -0,0 +1,337 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Inserts observations into ClickHouse via its HTTP interface.
+//!
+//! Rows are buffered and sent in batches using the `JSONEachRow` format into the same tables
+//! as the Parquet dump: `blocks`, `cores` and `candidates`. Tables use `ReplacingMergeTree`
+//! keyed by the block hash, so observations replayed from the write-ahead log are deduplicated.
+//! While ClickHouse is unreachable, inserts are retried with a backoff and up to `MAX_BUFFERED_BATCHES` batches
+//! are kept, the blocks observed once the buffer is full are dropped.
+
+use super::OutputSink;
+use crate::observation::BlockObservation;
+use async_trait::async_trait;
+use color_eyre::eyre::eyre;
+use log::{debug, warn};
+use polkadot_introspector_essentials::types::{BlockNumber, Timestamp, H256};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Used to distinguish our user-agent
+const HTTP_UA: &str = "polkadot-introspector";
+/// Timeout for a single insert request
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Number of batches kept while the inserts fail
+const MAX_BUFFERED_BATCHES: usize = 10;
+/// Time to wait before retrying a failed insert
+const INSERT_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+const BLOCKS_TABLE: &str = "blocks";
+const CORES_TABLE: &str = "cores";
+const CANDIDATES_TABLE: &str = "candidates";
+
+/// Tables schema, `{db}` is replaced with the database name
+const CREATE_TABLES: [&str; 3] = [
+	"CREATE TABLE IF NOT EXISTS {db}.blocks (block_number UInt32, block_hash String, timestamp UInt64, \
+	 spec_version UInt32, bitfields_count UInt32, validators_count UInt32, cores_count UInt32, candidates_count UInt32, epoch UInt64, sequence UInt64) \
+	 ENGINE = ReplacingMergeTree ORDER BY (block_number, block_hash)",
+	"CREATE TABLE IF NOT EXISTS {db}.cores (block_number UInt32, block_hash String, core_index UInt32, \
+	 occupied Bool, scheduled_paras Array(UInt32)) \
+	 ENGINE = ReplacingMergeTree ORDER BY (block_number, block_hash, core_index)",
+	"CREATE TABLE IF NOT EXISTS {db}.candidates (block_number UInt32, block_hash String, para_id UInt32, \
+	 candidate_hash String, relay_parent String, validity_votes UInt32, group_size UInt32) \
+	 ENGINE = ReplacingMergeTree ORDER BY (block_number, block_hash, para_id, candidate_hash)",
+];
+
+/// Columns added after the tables were created by the previous versions
+const MIGRATIONS: [&str; 3] = [
+	"ALTER TABLE {db}.blocks ADD COLUMN IF NOT EXISTS spec_version UInt32 AFTER timestamp",
+	"ALTER TABLE {db}.blocks ADD COLUMN IF NOT EXISTS epoch UInt64 AFTER candidates_count",
+	"ALTER TABLE {db}.blocks ADD COLUMN IF NOT EXISTS sequence UInt64 AFTER epoch",
+];
+
+/// A row of any table, prefixed with the relay chain block it belongs to
+#[derive(Serialize)]
+struct Row<'a, T: Serialize> {
+	block_number: BlockNumber,
+	block_hash: &'a H256,
+	#[serde(flatten)]
+	data: T,
+}
+
+#[derive(Serialize)]
+struct BlockRow {
+	timestamp: Timestamp,
+	spec_version: u32,
+	bitfields_count: u32,
+	validators_count: u32,
+	cores_count: u32,
+	candidates_count: u32,
+	epoch: u64,
+	sequence: u64,
+}
+
+/// Rows waiting to be inserted
+#[derive(Default)]
+struct Batch {
+	blocks: Vec<String>,
+	cores: Vec<String>,
+	candidates: Vec<String>,
+}
+
+/// Inserts observations to ClickHouse in batches
+pub struct ClickHouseSink {
+	/// ClickHouse HTTP interface URL
+	url: reqwest::Url,
+	/// Database name
+	database: String,
+	/// Number of blocks to buffer before sending an insert
+	batch_size: usize,
+	/// Rows waiting to be inserted
+	batch: Batch,
+	/// Time after that a failed insert is retried
+	retry_at: Option<Instant>,
+	/// Async HTTP client
+	http_client: reqwest::Client,
+}
+
+impl ClickHouseSink {
+	pub fn new(url: &str, database: &str, batch_size: usize) -> color_eyre::Result<Self> {
+		// The name is put into the queries as is
+		if !is_valid_identifier(database) {
+			return Err(eyre!(
+				"Invalid ClickHouse database name `{}`, expected ASCII letters, digits and underscores",
+				database
+			))
+		}
+		let http_client = reqwest::Client::builder().timeout(HTTP_TIMEOUT).user_agent(HTTP_UA).build()?;
+
+		Ok(Self {
+			url: reqwest::Url::parse(url)?,
+			database: database.to_string(),
+			batch_size: batch_size.max(1),
+			batch: Default::default(),
+			retry_at: None,
+			http_client,
+		})
+	}
+
+	/// Creates the database and tables if they don't exist
+	pub async fn create_tables(&self) -> color_eyre::Result<()> {
+		self.execute(format!("CREATE DATABASE IF NOT EXISTS {}", self.database), String::new())
+			.await?;
+		for query in CREATE_TABLES.iter().chain(MIGRATIONS.iter()) {
+			self.execute(query.replace("{db}", &self.database), String::new()).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Buffers rows of an observation, returns `true` if the batch is full
+	fn push(&mut self, observation: &BlockObservation) -> color_eyre::Result<bool> {
+		let block_hash = &observation.block_hash;
+		let block_number = observation.block_number;
+
+		self.batch.blocks.push(serde_json::to_string(&Row {
+			block_number,
+			block_hash,
+			data: BlockRow {
+				timestamp: observation.timestamp,
+				spec_version: observation.spec_version,
+				bitfields_count: observation.bitfields_count,
+				validators_count: observation.validators_count,
+				cores_count: observation.cores.len() as u32,
+				candidates_count: observation.candidates.len() as u32,
+				epoch: observation.position.epoch,
+				sequence: observation.position.sequence,
+			},
+		})?);
+		for core in &observation.cores {
+			self.batch
+				.cores
+				.push(serde_json::to_string(&Row { block_number, block_hash, data: core })?);
+		}
+		for candidate in &observation.candidates {
+			self.batch
+				.candidates
+				.push(serde_json::to_string(&Row { block_number, block_hash, data: candidate })?);
+		}
+
+		Ok(self.batch.blocks.len() >= self.batch_size)
+	}
+
+	/// Inserts buffered rows of a table, keeps them for the next attempt on failure
+	async fn insert(&self, table: &str, rows: &mut Vec<String>) -> color_eyre::Result<()> {
+		if rows.is_empty() {
+			return Ok(())
+		}
+
+		let query = format!("INSERT INTO {}.{} FORMAT JSONEachRow", self.database, table);
+		self.execute(query, rows.join("\n")).await?;
+		debug!("inserted {} rows into {}.{}", rows.len(), self.database, table);
+		rows.clear();
+
+		Ok(())
+	}
+
+	async fn execute(&self, query: String, body: String) -> color_eyre::Result<()> {
+		let mut url = self.url.clone();
+		url.query_pairs_mut().append_pair("query", &query);
+		let response = self.http_client.post(url).body(body).send().await?;
+		let status = response.status();
+		if !status.is_success() {
+			return Err(eyre!("ClickHouse returned {}: {}", status, response.text().await.unwrap_or_default()))
+		}
+
+		Ok(())
+	}
+}
+
+#[async_trait]
+impl OutputSink for ClickHouseSink {
+	fn name(&self) -> &str {
+		"clickhouse"
+	}
+
+	async fn write(&mut self, observation: &BlockObservation) -> color_eyre::Result<()> {
+		let is_full = if self.batch.blocks.len() >= self.batch_size * MAX_BUFFERED_BATCHES {
+			warn!("ClickHouse insert buffer is full, dropping block {}", observation.block_number);
+			true
+		} else {
+			self.push(observation)?
+		};
+		if is_full && self.retry_at.map_or(true, |retry_at| Instant::now() >= retry_at) {
+			self.flush().await?;
+		}
+
+		Ok(())
+	}
+
+	async fn flush(&mut self) -> color_eyre::Result<()> {
+		let mut batch = std::mem::take(&mut self.batch);
+		let result = async {
+			self.insert(BLOCKS_TABLE, &mut batch.blocks).await?;
+			self.insert(CORES_TABLE, &mut batch.cores).await?;
+			self.insert(CANDIDATES_TABLE, &mut batch.candidates).await
+		}
+		.await;
+		self.batch = batch;
+		self.retry_at = result.is_err().then(|| Instant::now() + INSERT_RETRY_INTERVAL);
+
+		result
+	}
+}
+
+fn is_valid_identifier(name: &str) -> bool {
+	name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') &&
+		name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		observation::{CandidateObservation, CoreObservation},
+		schema,
+	};
+
+	fn create_observation(block_number: BlockNumber) -> BlockObservation {
+		BlockObservation {
+			block_number,
+			block_hash: H256::zero(),
+			timestamp: 1694095332000,
+			spec_version: 1_003_000,
+			bitfields_count: 2,
+			validators_count: 3,
+			cores: vec![CoreObservation { core_index: 1, occupied: false, scheduled_paras: vec![100] }],
+			candidates: vec![CandidateObservation {
+				para_id: 100,
+				validity_votes: 2,
+				group_size: 3,
+				..Default::default()
+			}],
+			position: Default::default(),
+		}
+	}
+
+	#[test]
+	fn test_buffers_rows_until_batch_is_full() {
+		let mut sink = ClickHouseSink::new("http://localhost:8123", "introspector", 2).unwrap();
+
+		assert!(!sink.push(&create_observation(42)).unwrap());
+		assert!(sink.push(&create_observation(43)).unwrap());
+		assert_eq!(sink.batch.blocks.len(), 2);
+		assert_eq!(sink.batch.cores.len(), 2);
+		assert_eq!(sink.batch.candidates.len(), 2);
+	}
+
+	#[tokio::test]
+	async fn test_drops_blocks_when_buffer_is_full() {
+		let mut sink = ClickHouseSink::new("http://localhost:8123", "introspector", 2).unwrap();
+		sink.retry_at = Some(Instant::now() + Duration::from_secs(3600));
+
+		for block_number in 0..(2 * MAX_BUFFERED_BATCHES as u32 + 5) {
+			sink.write(&create_observation(block_number)).await.unwrap();
+		}
+		assert_eq!(sink.batch.blocks.len(), 2 * MAX_BUFFERED_BATCHES);
+	}
+
+	#[test]
+	fn test_validates_database_name() {
+		assert!(ClickHouseSink::new("http://localhost:8123", "introspector_1", 2).is_ok());
+		assert!(ClickHouseSink::new("http://localhost:8123", "db; DROP TABLE blocks", 2).is_err());
+		assert!(ClickHouseSink::new("http://localhost:8123", "", 2).is_err());
+	}
+
+	#[test]
+	fn test_serializes_rows() {
+		let mut sink = ClickHouseSink::new("http://localhost:8123", "introspector", 10).unwrap();
+		sink.push(&create_observation(42)).unwrap();
+
+		let hash = format!("{:?}", H256::zero());
+		assert_eq!(
+			sink.batch.blocks[0],
+			format!(
+				r#"{{"block_number":42,"block_hash":"{}","timestamp":1694095332000,"spec_version":1003000,"bitfields_count":2,"validators_count":3,"cores_count":1,"candidates_count":1,"epoch":0,"sequence":0}}"#,
+				hash
+			)
+		);
+		assert_eq!(
+			sink.batch.cores[0],
+			format!(
+				r#"{{"block_number":42,"block_hash":"{}","core_index":1,"occupied":false,"scheduled_paras":[100]}}"#,
+				hash
+			)
+		);
+	}
+
+	#[test]
+	fn test_rows_conform_to_schemas() {
+		let mut sink = ClickHouseSink::new("http://localhost:8123", "introspector", 10).unwrap();
+		sink.push(&create_observation(42)).unwrap();
+
+		for (name, rows) in [
+			("clickhouse-block", &sink.batch.blocks),
+			("clickhouse-core", &sink.batch.cores),
+			("clickhouse-candidate", &sink.batch.candidates),
+		] {
+			for row in rows {
+				schema::tests::assert_conforms(name, &serde_json::from_str(row).unwrap());
+			}
+		}
+	}
+}
@@ -0,0 +1,39 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Output sinks for the normalized relay chain observations.
+
+use crate::observation::BlockObservation;
+use async_trait::async_trait;
+
+pub mod clickhouse;
+pub mod parquet;
+pub mod proto;
+pub mod protobuf;
+pub mod wal;
+
+/// A destination for the relay chain observations
+#[async_trait]
+pub trait OutputSink: Send {
+	/// Sink name, used for logging
+	fn name(&self) -> &str;
+	/// Writes an observation of a relay chain block
+	async fn write(&mut self, observation: &BlockObservation) -> color_eyre::Result<()>;
+	/// Flushes buffered observations, called before the termination
+	async fn flush(&mut self) -> color_eyre::Result<()> {
+		Ok(())
+	}
+}
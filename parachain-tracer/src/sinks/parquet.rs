@@ -0,0 +1,312 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Writes observations as Parquet files, one table per directory:
+//!
+//! - `blocks/<first>_<last>.parquet` - relay chain block summary
+//! - `cores/<first>_<last>.parquet` - availability cores state
+//! - `candidates/<first>_<last>.parquet` - backed candidates with validity votes
+//! - `votes/<first>_<last>.parquet` - backing group members of the candidates and their validity votes
+//!
+//! Observations are buffered in memory and written to a new set of files, named after the first and the last
+//! block in them, once the buffer reaches 64 MiB or holds an hour of blocks, and on termination. The files are
+//! written on a blocking thread. Every row has `block_number` and `block_hash` columns, so tables can be joined
+//! after loading a directory with `pandas.read_parquet` or `polars.scan_parquet`.
+
+use super::OutputSink;
+use crate::observation::BlockObservation;
+use arrow_array::{
+	types::UInt32Type, ArrayRef, BooleanArray, ListArray, RecordBatch, StringArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+use async_trait::async_trait;
+use parquet::arrow::ArrowWriter;
+use polkadot_introspector_essentials::types::BlockNumber;
+use std::{
+	fs::{self, File},
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+const BLOCKS_TABLE: &str = "blocks";
+const CORES_TABLE: &str = "cores";
+const CANDIDATES_TABLE: &str = "candidates";
+const VOTES_TABLE: &str = "votes";
+const TABLES: [&str; 4] = [BLOCKS_TABLE, CORES_TABLE, CANDIDATES_TABLE, VOTES_TABLE];
+/// Size of the buffered batches after that they are written to new files
+const MAX_BUFFERED_BYTES: usize = 64 * 1024 * 1024;
+/// Time after that the buffered batches are written to new files
+const MAX_BUFFERED_DURATION: Duration = Duration::from_secs(3600);
+
+/// Observations not written yet
+struct Buffer {
+	/// Batches of every table, in the order of `TABLES`
+	batches: [Vec<RecordBatch>; 4],
+	first_block: BlockNumber,
+	last_block: BlockNumber,
+	bytes: usize,
+	started: Instant,
+}
+
+/// Dumps observations to a directory in Parquet format
+pub struct ParquetSink {
+	/// Output directory
+	dir: PathBuf,
+	buffer: Option<Buffer>,
+}
+
+impl ParquetSink {
+	pub fn new(dir: PathBuf) -> color_eyre::Result<Self> {
+		for table in TABLES {
+			fs::create_dir_all(dir.join(table))?;
+		}
+
+		Ok(Self { dir, buffer: None })
+	}
+
+	/// Writes the buffered batches to a new file per table
+	async fn roll(&mut self) -> color_eyre::Result<()> {
+		let Some(buffer) = self.buffer.take() else { return Ok(()) };
+		let dir = self.dir.clone();
+		tokio::task::spawn_blocking(move || {
+			for (table, batches) in TABLES.into_iter().zip(buffer.batches) {
+				write_batches(&file_path(&dir, table, buffer.first_block, buffer.last_block), batches)?;
+			}
+			Ok::<_, color_eyre::Report>(())
+		})
+		.await?
+	}
+}
+
+#[async_trait]
+impl OutputSink for ParquetSink {
+	fn name(&self) -> &str {
+		"parquet"
+	}
+
+	async fn write(&mut self, observation: &BlockObservation) -> color_eyre::Result<()> {
+		let batches = [
+			blocks_batch(observation)?,
+			cores_batch(observation)?,
+			candidates_batch(observation)?,
+			votes_batch(observation)?,
+		];
+		let buffer = self.buffer.get_or_insert_with(|| Buffer {
+			batches: Default::default(),
+			first_block: observation.block_number,
+			last_block: observation.block_number,
+			bytes: 0,
+			started: Instant::now(),
+		});
+		for (table_batches, batch) in buffer.batches.iter_mut().zip(batches) {
+			buffer.bytes += batch.get_array_memory_size();
+			table_batches.push(batch);
+		}
+		buffer.first_block = buffer.first_block.min(observation.block_number);
+		buffer.last_block = buffer.last_block.max(observation.block_number);
+
+		if buffer.bytes >= MAX_BUFFERED_BYTES || buffer.started.elapsed() >= MAX_BUFFERED_DURATION {
+			self.roll().await?;
+		}
+
+		Ok(())
+	}
+
+	async fn flush(&mut self) -> color_eyre::Result<()> {
+		self.roll().await
+	}
+}
+
+fn file_path(dir: &Path, table: &str, first_block: BlockNumber, last_block: BlockNumber) -> PathBuf {
+	dir.join(table).join(format!("{}_{}.parquet", first_block, last_block))
+}
+
+/// Writes the batches of a table to a file, the writer groups them in row groups
+fn write_batches(path: &Path, batches: Vec<RecordBatch>) -> color_eyre::Result<()> {
+	let Some(schema) = batches.first().map(|batch| batch.schema()) else { return Ok(()) };
+	let mut writer = ArrowWriter::try_new(File::create(path)?, schema, None)?;
+	for batch in batches {
+		writer.write(&batch)?;
+	}
+	writer.close()?;
+
+	Ok(())
+}
+
+fn block_columns(observation: &BlockObservation, rows: usize) -> Vec<ArrayRef> {
+	vec![
+		Arc::new(UInt32Array::from(vec![observation.block_number; rows])),
+		Arc::new(StringArray::from(vec![format!("{:?}", observation.block_hash); rows])),
+	]
+}
+
+fn block_fields() -> Vec<Field> {
+	vec![Field::new("block_number", DataType::UInt32, false), Field::new("block_hash", DataType::Utf8, false)]
+}
+
+fn blocks_batch(observation: &BlockObservation) -> color_eyre::Result<RecordBatch> {
+	let mut fields = block_fields();
+	fields.extend([
+		Field::new("timestamp", DataType::UInt64, false),
+		Field::new("spec_version", DataType::UInt32, false),
+		Field::new("bitfields_count", DataType::UInt32, false),
+		Field::new("validators_count", DataType::UInt32, false),
+		Field::new("cores_count", DataType::UInt32, false),
+		Field::new("candidates_count", DataType::UInt32, false),
+		Field::new("epoch", DataType::UInt64, false),
+		Field::new("sequence", DataType::UInt64, false),
+	]);
+	let mut columns = block_columns(observation, 1);
+	columns.push(Arc::new(UInt64Array::from(vec![observation.timestamp])));
+	columns.push(Arc::new(UInt32Array::from(vec![observation.spec_version])));
+	columns.push(Arc::new(UInt32Array::from(vec![observation.bitfields_count])));
+	columns.push(Arc::new(UInt32Array::from(vec![observation.validators_count])));
+	columns.push(Arc::new(UInt32Array::from(vec![observation.cores.len() as u32])));
+	columns.push(Arc::new(UInt32Array::from(vec![observation.candidates.len() as u32])));
+	columns.push(Arc::new(UInt64Array::from(vec![observation.position.epoch])));
+	columns.push(Arc::new(UInt64Array::from(vec![observation.position.sequence])));
+
+	Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+}
+
+fn cores_batch(observation: &BlockObservation) -> color_eyre::Result<RecordBatch> {
+	let mut fields = block_fields();
+	fields.extend([
+		Field::new("core_index", DataType::UInt32, false),
+		Field::new("occupied", DataType::Boolean, false),
+		Field::new("scheduled_paras", DataType::List(Arc::new(Field::new("item", DataType::UInt32, true))), false),
+	]);
+	let cores = &observation.cores;
+	let mut columns = block_columns(observation, cores.len());
+	columns.push(Arc::new(UInt32Array::from_iter_values(cores.iter().map(|v| v.core_index))));
+	columns.push(Arc::new(BooleanArray::from(cores.iter().map(|v| v.occupied).collect::<Vec<_>>())));
+	columns.push(Arc::new(ListArray::from_iter_primitive::<UInt32Type, _, _>(
+		cores
+			.iter()
+			.map(|v| Some(v.scheduled_paras.iter().map(|id| Some(*id)).collect::<Vec<_>>())),
+	)));
+
+	Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+}
+
+fn candidates_batch(observation: &BlockObservation) -> color_eyre::Result<RecordBatch> {
+	let mut fields = block_fields();
+	fields.extend([
+		Field::new("para_id", DataType::UInt32, false),
+		Field::new("candidate_hash", DataType::Utf8, false),
+		Field::new("relay_parent", DataType::Utf8, false),
+		Field::new("validity_votes", DataType::UInt32, false),
+		Field::new("group_size", DataType::UInt32, false),
+	]);
+	let candidates = &observation.candidates;
+	let mut columns = block_columns(observation, candidates.len());
+	columns.push(Arc::new(UInt32Array::from_iter_values(candidates.iter().map(|v| v.para_id))));
+	columns.push(Arc::new(StringArray::from_iter_values(candidates.iter().map(|v| format!("{:?}", v.candidate_hash)))));
+	columns.push(Arc::new(StringArray::from_iter_values(candidates.iter().map(|v| format!("{:?}", v.relay_parent)))));
+	columns.push(Arc::new(UInt32Array::from_iter_values(candidates.iter().map(|v| v.validity_votes))));
+	columns.push(Arc::new(UInt32Array::from_iter_values(candidates.iter().map(|v| v.group_size))));
+
+	Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+}
+
+fn votes_batch(observation: &BlockObservation) -> color_eyre::Result<RecordBatch> {
+	let mut fields = block_fields();
+	fields.extend([
+		Field::new("para_id", DataType::UInt32, false),
+		Field::new("candidate_hash", DataType::Utf8, false),
+		Field::new("validator_index", DataType::UInt32, false),
+		Field::new("address", DataType::Utf8, true),
+		Field::new("voted", DataType::Boolean, false),
+	]);
+	let votes: Vec<_> = observation
+		.candidates
+		.iter()
+		.flat_map(|candidate| candidate.votes.iter().map(move |vote| (candidate, vote)))
+		.collect();
+	let mut columns = block_columns(observation, votes.len());
+	columns.push(Arc::new(UInt32Array::from_iter_values(votes.iter().map(|(candidate, _)| candidate.para_id))));
+	columns.push(Arc::new(StringArray::from_iter_values(
+		votes.iter().map(|(candidate, _)| format!("{:?}", candidate.candidate_hash)),
+	)));
+	columns.push(Arc::new(UInt32Array::from_iter_values(votes.iter().map(|(_, vote)| vote.validator_index))));
+	columns.push(Arc::new(StringArray::from(votes.iter().map(|(_, vote)| vote.address.clone()).collect::<Vec<_>>())));
+	columns.push(Arc::new(BooleanArray::from(votes.iter().map(|(_, vote)| vote.voted).collect::<Vec<_>>())));
+
+	Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::observation::{CandidateObservation, CoreObservation};
+	use parquet::file::reader::{FileReader, SerializedFileReader};
+	use polkadot_introspector_essentials::{observation::ValidityVote, types::H256};
+
+	fn create_observation(block_number: BlockNumber) -> BlockObservation {
+		BlockObservation {
+			block_number,
+			block_hash: H256::random(),
+			timestamp: 1694095332000,
+			spec_version: 1_003_000,
+			bitfields_count: 2,
+			validators_count: 3,
+			cores: vec![
+				CoreObservation { core_index: 0, occupied: true, scheduled_paras: vec![] },
+				CoreObservation { core_index: 1, occupied: false, scheduled_paras: vec![100, 200] },
+			],
+			candidates: vec![CandidateObservation {
+				para_id: 100,
+				validity_votes: 2,
+				group_size: 3,
+				votes: vec![
+					ValidityVote { validator_index: 0, address: Some("validator".to_string()), voted: true },
+					ValidityVote { validator_index: 1, address: None, voted: true },
+					ValidityVote { validator_index: 2, address: None, voted: false },
+				],
+				..Default::default()
+			}],
+			position: Default::default(),
+		}
+	}
+
+	#[test]
+	fn test_builds_batches() {
+		let observation = create_observation(42);
+
+		assert_eq!(blocks_batch(&observation).unwrap().num_rows(), 1);
+		assert_eq!(cores_batch(&observation).unwrap().num_rows(), 2);
+		assert_eq!(candidates_batch(&observation).unwrap().num_rows(), 1);
+		assert_eq!(votes_batch(&observation).unwrap().num_rows(), 3);
+	}
+
+	#[tokio::test]
+	async fn test_writes_files() {
+		let dir = std::env::temp_dir().join(format!("introspector-parquet-{}", rand::random::<u64>()));
+		let mut sink = ParquetSink::new(dir.clone()).unwrap();
+
+		sink.write(&create_observation(42)).await.unwrap();
+		sink.write(&create_observation(43)).await.unwrap();
+		assert!(!file_path(&dir, BLOCKS_TABLE, 42, 43).exists());
+		sink.flush().await.unwrap();
+
+		for (table, rows) in TABLES.into_iter().zip([2, 4, 2, 6]) {
+			let reader = SerializedFileReader::new(File::open(file_path(&dir, table, 42, 43)).unwrap()).unwrap();
+			assert_eq!(reader.metadata().file_metadata().num_rows(), rows);
+		}
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
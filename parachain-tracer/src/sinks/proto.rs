@@ -0,0 +1,529 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Protobuf messages of the exported records.
+//!
+//! The messages mirror `proto/introspector.proto` and must be kept in sync with it,
+//! they are written by hand to avoid a dependency on `protoc` at build time.
+
+use crate::{
+	observation,
+	types::{self, DisputesTracker, ParachainConsensusEvent, ParachainProgressUpdate},
+};
+use color_eyre::eyre::eyre;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::SubxtHrmpChannel, chain_events::SubxtDisputeResult, types::H256,
+};
+use prost::Message;
+
+/// Version of the schema, bumped only on incompatible changes
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Record {
+	#[prost(uint32, tag = "1")]
+	pub schema_version: u32,
+	#[prost(oneof = "record::Payload", tags = "2, 3")]
+	pub payload: Option<record::Payload>,
+}
+
+pub mod record {
+	#[derive(Clone, PartialEq, prost::Oneof)]
+	pub enum Payload {
+		#[prost(message, tag = "2")]
+		BlockObservation(super::BlockObservation),
+		#[prost(message, tag = "3")]
+		ParachainProgress(super::ParachainProgress),
+	}
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BlockObservation {
+	#[prost(uint32, tag = "1")]
+	pub block_number: u32,
+	#[prost(bytes = "vec", tag = "2")]
+	pub block_hash: Vec<u8>,
+	#[prost(uint64, tag = "3")]
+	pub timestamp: u64,
+	#[prost(uint32, tag = "4")]
+	pub bitfields_count: u32,
+	#[prost(uint32, tag = "5")]
+	pub validators_count: u32,
+	#[prost(message, repeated, tag = "6")]
+	pub cores: Vec<CoreObservation>,
+	#[prost(message, repeated, tag = "7")]
+	pub candidates: Vec<CandidateObservation>,
+	#[prost(uint64, tag = "8")]
+	pub epoch: u64,
+	#[prost(uint64, tag = "9")]
+	pub sequence: u64,
+	#[prost(uint32, tag = "10")]
+	pub spec_version: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CoreObservation {
+	#[prost(uint32, tag = "1")]
+	pub core_index: u32,
+	#[prost(bool, tag = "2")]
+	pub occupied: bool,
+	#[prost(uint32, repeated, tag = "3")]
+	pub scheduled_paras: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CandidateObservation {
+	#[prost(uint32, tag = "1")]
+	pub para_id: u32,
+	#[prost(bytes = "vec", tag = "2")]
+	pub candidate_hash: Vec<u8>,
+	#[prost(bytes = "vec", tag = "3")]
+	pub relay_parent: Vec<u8>,
+	#[prost(uint32, tag = "4")]
+	pub validity_votes: u32,
+	#[prost(uint32, tag = "5")]
+	pub group_size: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ParachainProgress {
+	#[prost(uint32, tag = "1")]
+	pub para_id: u32,
+	#[prost(uint32, tag = "2")]
+	pub block_number: u32,
+	#[prost(bytes = "vec", tag = "3")]
+	pub block_hash: Vec<u8>,
+	#[prost(uint64, tag = "4")]
+	pub timestamp: u64,
+	#[prost(bool, tag = "5")]
+	pub core_occupied: bool,
+	#[prost(bool, tag = "6")]
+	pub is_fork: bool,
+	#[prost(uint32, optional, tag = "7")]
+	pub finality_lag: Option<u32>,
+	#[prost(message, repeated, tag = "8")]
+	pub events: Vec<ConsensusEvent>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ConsensusEvent {
+	#[prost(oneof = "consensus_event::Event", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11")]
+	pub event: Option<consensus_event::Event>,
+}
+
+pub mod consensus_event {
+	#[derive(Clone, PartialEq, prost::Oneof)]
+	pub enum Event {
+		#[prost(uint32, tag = "1")]
+		CoreAssigned(u32),
+		#[prost(message, tag = "2")]
+		Backed(super::CandidateBacked),
+		#[prost(message, tag = "3")]
+		Included(super::CandidateIncluded),
+		#[prost(message, tag = "4")]
+		Disputed(super::Dispute),
+		#[prost(message, tag = "5")]
+		SkippedSlot(super::SkippedSlot),
+		#[prost(message, tag = "6")]
+		SlowAvailability(super::Availability),
+		#[prost(bytes = "vec", tag = "7")]
+		AvailabilityTimedOut(Vec<u8>),
+		#[prost(message, tag = "8")]
+		SlowBitfieldPropagation(super::Bitfields),
+		#[prost(uint32, tag = "9")]
+		NewSession(u32),
+		#[prost(message, tag = "10")]
+		MessageQueues(super::MessageQueues),
+		#[prost(message, tag = "11")]
+		InclusionMismatch(super::InclusionMismatch),
+		#[prost(message, tag = "12")]
+		OldRelayParent(super::OldRelayParent),
+	}
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CandidateBacked {
+	#[prost(bytes = "vec", tag = "1")]
+	pub candidate_hash: Vec<u8>,
+	#[prost(uint32, tag = "2")]
+	pub validity_votes: u32,
+	#[prost(uint32, tag = "3")]
+	pub group_size: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CandidateIncluded {
+	#[prost(bytes = "vec", tag = "1")]
+	pub candidate_hash: Vec<u8>,
+	#[prost(uint32, tag = "2")]
+	pub bits_available: u32,
+	#[prost(uint32, tag = "3")]
+	pub max_bits: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Dispute {
+	#[prost(bytes = "vec", tag = "1")]
+	pub candidate_hash: Vec<u8>,
+	#[prost(enumeration = "dispute::Outcome", tag = "2")]
+	pub outcome: i32,
+	#[prost(uint32, tag = "3")]
+	pub voted_for: u32,
+	#[prost(uint32, tag = "4")]
+	pub voted_against: u32,
+	#[prost(uint32, tag = "5")]
+	pub resolve_time: u32,
+}
+
+pub mod dispute {
+	#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+	#[repr(i32)]
+	pub enum Outcome {
+		Valid = 0,
+		Invalid = 1,
+		TimedOut = 2,
+	}
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OldRelayParent {
+	#[prost(bytes = "vec", tag = "1")]
+	pub candidate_hash: Vec<u8>,
+	#[prost(uint32, tag = "2")]
+	pub age: u32,
+	#[prost(uint32, tag = "3")]
+	pub max_age: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct InclusionMismatch {
+	#[prost(bytes = "vec", tag = "1")]
+	pub candidate_hash: Vec<u8>,
+	#[prost(enumeration = "inclusion_mismatch::Kind", tag = "2")]
+	pub kind: i32,
+}
+
+pub mod inclusion_mismatch {
+	#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+	#[repr(i32)]
+	pub enum Kind {
+		BackedWithoutEvent = 0,
+		IncludedWithoutEvent = 1,
+		EventWithoutInclusion = 2,
+	}
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SkippedSlot {
+	#[prost(enumeration = "skipped_slot::Reason", tag = "1")]
+	pub reason: i32,
+}
+
+pub mod skipped_slot {
+	#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+	#[repr(i32)]
+	pub enum Reason {
+		NoCandidate = 0,
+		NotScheduled = 1,
+		InsufficientBacking = 2,
+		CoreOccupied = 3,
+	}
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Availability {
+	#[prost(uint32, tag = "1")]
+	pub bits_available: u32,
+	#[prost(uint32, tag = "2")]
+	pub max_bits: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Bitfields {
+	#[prost(uint32, tag = "1")]
+	pub bitfields_count: u32,
+	#[prost(uint32, tag = "2")]
+	pub max_bits: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MessageQueues {
+	#[prost(message, repeated, tag = "1")]
+	pub inbound: Vec<HrmpChannel>,
+	#[prost(message, repeated, tag = "2")]
+	pub outbound: Vec<HrmpChannel>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct HrmpChannel {
+	#[prost(uint32, tag = "1")]
+	pub peer_para_id: u32,
+	#[prost(uint32, tag = "2")]
+	pub msg_count: u32,
+	#[prost(uint32, tag = "3")]
+	pub total_size: u32,
+	#[prost(uint32, tag = "4")]
+	pub max_message_size: u32,
+}
+
+fn hash_bytes(hash: &H256) -> Vec<u8> {
+	hash.as_bytes().to_vec()
+}
+
+impl From<record::Payload> for Record {
+	fn from(payload: record::Payload) -> Self {
+		Self { schema_version: SCHEMA_VERSION, payload: Some(payload) }
+	}
+}
+
+impl TryFrom<&[u8]> for Record {
+	type Error = color_eyre::Report;
+
+	/// Decodes a length-delimited record, rejects records of unsupported schema versions
+	fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+		let record = Record::decode_length_delimited(bytes)?;
+		if record.schema_version > SCHEMA_VERSION {
+			return Err(eyre!("unsupported schema version {}, expected {}", record.schema_version, SCHEMA_VERSION))
+		}
+
+		Ok(record)
+	}
+}
+
+impl From<&observation::BlockObservation> for BlockObservation {
+	fn from(v: &observation::BlockObservation) -> Self {
+		Self {
+			block_number: v.block_number,
+			block_hash: hash_bytes(&v.block_hash),
+			timestamp: v.timestamp,
+			bitfields_count: v.bitfields_count,
+			validators_count: v.validators_count,
+			cores: v
+				.cores
+				.iter()
+				.map(|core| CoreObservation {
+					core_index: core.core_index,
+					occupied: core.occupied,
+					scheduled_paras: core.scheduled_paras.clone(),
+				})
+				.collect(),
+			candidates: v
+				.candidates
+				.iter()
+				.map(|candidate| CandidateObservation {
+					para_id: candidate.para_id,
+					candidate_hash: hash_bytes(&candidate.candidate_hash),
+					relay_parent: hash_bytes(&candidate.relay_parent),
+					validity_votes: candidate.validity_votes,
+					group_size: candidate.group_size,
+				})
+				.collect(),
+			epoch: v.position.epoch,
+			sequence: v.position.sequence,
+			spec_version: v.spec_version,
+		}
+	}
+}
+
+impl From<&ParachainProgressUpdate> for ParachainProgress {
+	fn from(v: &ParachainProgressUpdate) -> Self {
+		Self {
+			para_id: v.para_id,
+			block_number: v.block_number,
+			block_hash: hash_bytes(&v.block_hash),
+			timestamp: v.timestamp,
+			core_occupied: v.core_occupied,
+			is_fork: v.is_fork,
+			finality_lag: v.finality_lag,
+			events: v.events.iter().map(ConsensusEvent::from).collect(),
+		}
+	}
+}
+
+impl From<&ParachainConsensusEvent> for ConsensusEvent {
+	fn from(v: &ParachainConsensusEvent) -> Self {
+		use consensus_event::Event;
+
+		let event = match v {
+			ParachainConsensusEvent::CoreAssigned(core_index) => Event::CoreAssigned(*core_index),
+			ParachainConsensusEvent::Backed(candidate_hash, validity_votes, group_size) =>
+				Event::Backed(CandidateBacked {
+					candidate_hash: hash_bytes(candidate_hash),
+					validity_votes: *validity_votes,
+					group_size: *group_size,
+				}),
+			ParachainConsensusEvent::Included(candidate_hash, bits_available, max_bits) =>
+				Event::Included(CandidateIncluded {
+					candidate_hash: hash_bytes(candidate_hash),
+					bits_available: *bits_available,
+					max_bits: *max_bits,
+				}),
+			ParachainConsensusEvent::Disputed(dispute) => Event::Disputed(dispute.into()),
+			ParachainConsensusEvent::SkippedSlot(reason) =>
+				Event::SkippedSlot(SkippedSlot { reason: skipped_slot::Reason::from(*reason) as i32 }),
+			ParachainConsensusEvent::SlowAvailability(bits_available, max_bits) =>
+				Event::SlowAvailability(Availability { bits_available: *bits_available, max_bits: *max_bits }),
+			ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash) =>
+				Event::AvailabilityTimedOut(hash_bytes(candidate_hash)),
+			ParachainConsensusEvent::SlowBitfieldPropagation(bitfields_count, max_bits) =>
+				Event::SlowBitfieldPropagation(Bitfields { bitfields_count: *bitfields_count, max_bits: *max_bits }),
+			ParachainConsensusEvent::NewSession(session_index) => Event::NewSession(*session_index),
+			ParachainConsensusEvent::MessageQueues(inbound, outbound) => Event::MessageQueues(MessageQueues {
+				inbound: inbound.iter().map(HrmpChannel::from).collect(),
+				outbound: outbound.iter().map(HrmpChannel::from).collect(),
+			}),
+			ParachainConsensusEvent::InclusionMismatch(candidate_hash, mismatch) =>
+				Event::InclusionMismatch(InclusionMismatch {
+					candidate_hash: hash_bytes(candidate_hash),
+					kind: inclusion_mismatch::Kind::from(*mismatch) as i32,
+				}),
+			ParachainConsensusEvent::OldRelayParent(candidate_hash, age, max_age) =>
+				Event::OldRelayParent(OldRelayParent {
+					candidate_hash: hash_bytes(candidate_hash),
+					age: *age,
+					max_age: *max_age,
+				}),
+		};
+
+		Self { event: Some(event) }
+	}
+}
+
+impl From<&DisputesTracker> for Dispute {
+	fn from(v: &DisputesTracker) -> Self {
+		let outcome = match v.outcome {
+			SubxtDisputeResult::Valid => dispute::Outcome::Valid,
+			SubxtDisputeResult::Invalid => dispute::Outcome::Invalid,
+			SubxtDisputeResult::TimedOut => dispute::Outcome::TimedOut,
+		};
+
+		Self {
+			candidate_hash: hash_bytes(&v.candidate),
+			outcome: outcome as i32,
+			voted_for: v.voted_for,
+			voted_against: v.voted_against,
+			resolve_time: v.resolve_time,
+		}
+	}
+}
+
+impl From<types::InclusionMismatch> for inclusion_mismatch::Kind {
+	fn from(v: types::InclusionMismatch) -> Self {
+		match v {
+			types::InclusionMismatch::BackedWithoutEvent => Self::BackedWithoutEvent,
+			types::InclusionMismatch::IncludedWithoutEvent => Self::IncludedWithoutEvent,
+			types::InclusionMismatch::EventWithoutInclusion => Self::EventWithoutInclusion,
+		}
+	}
+}
+
+impl From<types::SkippedSlotReason> for skipped_slot::Reason {
+	fn from(v: types::SkippedSlotReason) -> Self {
+		match v {
+			types::SkippedSlotReason::NoCandidate => Self::NoCandidate,
+			types::SkippedSlotReason::NotScheduled => Self::NotScheduled,
+			types::SkippedSlotReason::InsufficientBacking => Self::InsufficientBacking,
+			types::SkippedSlotReason::CoreOccupied => Self::CoreOccupied,
+		}
+	}
+}
+
+impl From<&(u32, SubxtHrmpChannel)> for HrmpChannel {
+	fn from((peer_para_id, channel): &(u32, SubxtHrmpChannel)) -> Self {
+		Self {
+			peer_para_id: *peer_para_id,
+			msg_count: channel.msg_count,
+			total_size: channel.total_size,
+			max_message_size: channel.max_message_size,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::observation::CoreObservation as CoreObservationData;
+
+	#[test]
+	fn test_encodes_observation() {
+		let observation = observation::BlockObservation {
+			block_number: 42,
+			block_hash: H256::random(),
+			cores: vec![CoreObservationData { core_index: 1, occupied: true, scheduled_paras: vec![100] }],
+			..Default::default()
+		};
+		let record = Record::from(record::Payload::BlockObservation((&observation).into()));
+
+		let decoded = Record::try_from(record.encode_length_delimited_to_vec().as_slice()).unwrap();
+
+		assert_eq!(decoded, record);
+		match decoded.payload {
+			Some(record::Payload::BlockObservation(v)) => {
+				assert_eq!(v.block_number, 42);
+				assert_eq!(v.block_hash, observation.block_hash.as_bytes());
+				assert_eq!(v.cores[0].scheduled_paras, vec![100]);
+			},
+			_ => panic!("unexpected payload"),
+		}
+	}
+
+	#[test]
+	fn test_encodes_progress() {
+		let candidate_hash = H256::random();
+		let progress = ParachainProgressUpdate {
+			para_id: 100,
+			block_number: 42,
+			events: vec![
+				ParachainConsensusEvent::Backed(candidate_hash, 2, 3),
+				ParachainConsensusEvent::SkippedSlot(types::SkippedSlotReason::CoreOccupied),
+			],
+			finality_lag: Some(2),
+			..Default::default()
+		};
+		let record = Record::from(record::Payload::ParachainProgress((&progress).into()));
+
+		let decoded = Record::try_from(record.encode_length_delimited_to_vec().as_slice()).unwrap();
+
+		match decoded.payload {
+			Some(record::Payload::ParachainProgress(v)) => {
+				assert_eq!(v.para_id, 100);
+				assert_eq!(v.finality_lag, Some(2));
+				assert_eq!(
+					v.events[0].event,
+					Some(consensus_event::Event::Backed(CandidateBacked {
+						candidate_hash: candidate_hash.as_bytes().to_vec(),
+						validity_votes: 2,
+						group_size: 3
+					}))
+				);
+				assert_eq!(
+					v.events[1].event,
+					Some(consensus_event::Event::SkippedSlot(SkippedSlot {
+						reason: skipped_slot::Reason::CoreOccupied as i32
+					}))
+				);
+			},
+			_ => panic!("unexpected payload"),
+		}
+	}
+
+	#[test]
+	fn test_rejects_unsupported_schema_version() {
+		let record = Record { schema_version: SCHEMA_VERSION + 1, payload: None };
+
+		assert!(Record::try_from(record.encode_length_delimited_to_vec().as_slice()).is_err());
+	}
+}
@@ -0,0 +1,94 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Appends observations to a file as length-delimited protobuf records.
+
+use super::{
+	proto::{record::Payload, Record},
+	OutputSink,
+};
+use crate::observation::BlockObservation;
+use async_trait::async_trait;
+use prost::Message;
+use std::{
+	fs::{File, OpenOptions},
+	io::{BufWriter, Write},
+	path::Path,
+};
+
+/// Writes observations to a file in the protobuf wire format
+pub struct ProtobufSink {
+	writer: BufWriter<File>,
+}
+
+impl ProtobufSink {
+	pub fn new(path: &Path) -> color_eyre::Result<Self> {
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+		Ok(Self { writer: BufWriter::new(file) })
+	}
+}
+
+#[async_trait]
+impl OutputSink for ProtobufSink {
+	fn name(&self) -> &str {
+		"protobuf"
+	}
+
+	async fn write(&mut self, observation: &BlockObservation) -> color_eyre::Result<()> {
+		let record = Record::from(Payload::BlockObservation(observation.into()));
+		self.writer.write_all(&record.encode_length_delimited_to_vec())?;
+
+		Ok(())
+	}
+
+	async fn flush(&mut self) -> color_eyre::Result<()> {
+		self.writer.flush()?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use prost::bytes::Buf;
+
+	#[tokio::test]
+	async fn test_appends_records() {
+		let path = std::env::temp_dir().join(format!("introspector-protobuf-{}", rand::random::<u64>()));
+		let mut sink = ProtobufSink::new(&path).unwrap();
+
+		for block_number in [42, 43] {
+			sink.write(&BlockObservation { block_number, ..Default::default() })
+				.await
+				.unwrap();
+		}
+		sink.flush().await.unwrap();
+
+		let bytes = std::fs::read(&path).unwrap();
+		let mut buf = bytes.as_slice();
+		let mut block_numbers = vec![];
+		while buf.has_remaining() {
+			match Record::decode_length_delimited(&mut buf).unwrap().payload {
+				Some(Payload::BlockObservation(v)) => block_numbers.push(v.block_number),
+				_ => panic!("unexpected payload"),
+			}
+		}
+		assert_eq!(block_numbers, vec![42, 43]);
+		std::fs::remove_file(path).unwrap();
+	}
+}
@@ -0,0 +1,309 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Local write-ahead log for the sinks that deliver observations to external systems.
+//!
+//! Every observation is appended to `<dir>/<sink>.wal` before it is passed to the wrapped sink,
+//! the log is truncated only after the sink has acknowledged delivery by a successful flush.
+//! The log is synced to disk once per commit rather than per observation, so a crash of the host
+//! (not of the process) may lose the observations written since the last commit.
+//!
+//! Delivery is at-least-once: a crash after the sink has acknowledged a batch and before the log is truncated
+//! delivers the batch again. The entries are replayed with their original stream positions, so the destination
+//! can drop duplicates using them as idempotency keys, e.g. the ClickHouse tables are `ReplacingMergeTree` ones.
+//! Entries left after a crash are replayed by a background task retrying until the destination is reachable,
+//! so the tracer starts even if the destination is down. The replay checks the stream positions of the entries,
+//! duplicated entries are skipped and missed ones, e.g. corrupted by the crash, are reported.
+
+use super::OutputSink;
+use crate::observation::BlockObservation;
+use async_trait::async_trait;
+use log::{info, warn};
+use polkadot_introspector_essentials::sequence::{GapDetector, SequenceCheck};
+use std::{
+	fs::{self, File, OpenOptions},
+	io::{BufRead, BufReader, Write},
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+use tokio::sync::Mutex;
+
+/// Interval between the attempts to replay the log after a crash
+const REPLAY_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Wraps a sink with a write-ahead log
+pub struct WalSink {
+	/// Sink name, cached to not lock the state
+	name: String,
+	/// State shared with the replay task
+	state: Arc<Mutex<WalState>>,
+	/// Number of entries to write before asking the sink to flush
+	commit_interval: usize,
+}
+
+struct WalState {
+	/// The wrapped sink
+	inner: Box<dyn OutputSink>,
+	/// Path to the log file
+	path: PathBuf,
+	/// The log file opened for appending
+	file: File,
+	/// Number of entries written since the last commit
+	pending: usize,
+	/// The log has entries from a previous run that have not been replayed yet, it is not truncated until they are
+	replaying: bool,
+}
+
+impl WalSink {
+	/// Opens the log and starts replaying the entries that were not delivered before the last shutdown
+	pub async fn open(inner: Box<dyn OutputSink>, dir: &Path, commit_interval: usize) -> color_eyre::Result<Self> {
+		fs::create_dir_all(dir)?;
+		let name = inner.name().to_string();
+		let path = dir.join(format!("{}.wal", name));
+		let file = OpenOptions::new().create(true).append(true).open(&path)?;
+		let replaying = !read_entries(&path)?.is_empty();
+		let state = Arc::new(Mutex::new(WalState { inner, path, file, pending: 0, replaying }));
+		if replaying {
+			tokio::spawn(replay(state.clone()));
+		}
+
+		Ok(Self { name, state, commit_interval: commit_interval.max(1) })
+	}
+}
+
+/// Delivers the entries of the log until it succeeds
+async fn replay(state: Arc<Mutex<WalState>>) {
+	loop {
+		match state.lock().await.replay().await {
+			Ok(()) => break,
+			Err(e) => warn!("cannot replay the write-ahead log, retrying in {:?}: {:?}", REPLAY_RETRY_INTERVAL, e),
+		}
+		tokio::time::sleep(REPLAY_RETRY_INTERVAL).await;
+	}
+}
+
+impl WalState {
+	/// Delivers all entries of the log, including those written since the start, and truncates it on success
+	async fn replay(&mut self) -> color_eyre::Result<()> {
+		let entries = read_entries(&self.path)?;
+		info!("{} sink: replaying {} undelivered observations", self.inner.name(), entries.len());
+		let mut gap_detector = GapDetector::new();
+		for observation in &entries {
+			match gap_detector.observe(observation.position) {
+				SequenceCheck::Duplicate => continue,
+				SequenceCheck::Gap { from, to } => warn!(
+					"{} sink: observations {}-{} of epoch {} are missing in the write-ahead log",
+					self.inner.name(),
+					from,
+					to,
+					observation.position.epoch
+				),
+				_ => {},
+			}
+			self.inner.write(observation).await?;
+		}
+		self.inner.flush().await?;
+		self.file.set_len(0)?;
+		sync_data(&self.file).await?;
+		self.replaying = false;
+		self.pending = 0;
+
+		Ok(())
+	}
+
+	fn append(&mut self, observation: &BlockObservation) -> color_eyre::Result<()> {
+		let mut line = serde_json::to_vec(observation)?;
+		line.push(b'\n');
+		self.file.write_all(&line)?;
+		self.pending += 1;
+
+		Ok(())
+	}
+
+	/// Syncs the log, flushes the wrapped sink and truncates the log on success
+	async fn commit(&mut self) -> color_eyre::Result<()> {
+		if self.pending == 0 {
+			return Ok(())
+		}
+
+		sync_data(&self.file).await?;
+		self.inner.flush().await?;
+		// Entries of a previous run are still to be delivered by the replay
+		if !self.replaying {
+			self.file.set_len(0)?;
+			sync_data(&self.file).await?;
+		}
+		self.pending = 0;
+
+		Ok(())
+	}
+}
+
+/// Syncs the file to disk without blocking the runtime
+async fn sync_data(file: &File) -> color_eyre::Result<()> {
+	let file = file.try_clone()?;
+	tokio::task::spawn_blocking(move || file.sync_data()).await??;
+
+	Ok(())
+}
+
+fn read_entries(path: &Path) -> color_eyre::Result<Vec<BlockObservation>> {
+	let mut entries = vec![];
+	for line in BufReader::new(File::open(path)?).lines() {
+		match serde_json::from_str(&line?) {
+			Ok(observation) => entries.push(observation),
+			// Partially written entry, the process has crashed before it was passed to the sink
+			Err(e) => warn!("skipping corrupted entry in {}: {:?}", path.display(), e),
+		}
+	}
+
+	Ok(entries)
+}
+
+#[async_trait]
+impl OutputSink for WalSink {
+	fn name(&self) -> &str {
+		&self.name
+	}
+
+	async fn write(&mut self, observation: &BlockObservation) -> color_eyre::Result<()> {
+		let mut state = self.state.lock().await;
+		state.append(observation)?;
+		state.inner.write(observation).await?;
+		if state.pending >= self.commit_interval {
+			state.commit().await?;
+		}
+
+		Ok(())
+	}
+
+	async fn flush(&mut self) -> color_eyre::Result<()> {
+		self.state.lock().await.commit().await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_introspector_essentials::sequence::StreamPosition;
+	use std::sync::{Arc, Mutex};
+
+	/// Records delivered observations, fails to flush when asked to
+	#[derive(Clone, Default)]
+	struct TestSink {
+		written: Arc<Mutex<Vec<StreamPosition>>>,
+		delivered: Arc<Mutex<Vec<StreamPosition>>>,
+		fail_flush: Arc<Mutex<bool>>,
+	}
+
+	#[async_trait]
+	impl OutputSink for TestSink {
+		fn name(&self) -> &str {
+			"test"
+		}
+
+		async fn write(&mut self, observation: &BlockObservation) -> color_eyre::Result<()> {
+			self.written.lock().unwrap().push(observation.position);
+			Ok(())
+		}
+
+		async fn flush(&mut self) -> color_eyre::Result<()> {
+			if *self.fail_flush.lock().unwrap() {
+				return Err(color_eyre::eyre::eyre!("destination is down"))
+			}
+			let mut written = self.written.lock().unwrap();
+			self.delivered.lock().unwrap().append(&mut written);
+			Ok(())
+		}
+	}
+
+	fn create_observation(sequence: u64) -> BlockObservation {
+		BlockObservation { position: StreamPosition { epoch: 100, sequence }, ..Default::default() }
+	}
+
+	fn temp_dir() -> PathBuf {
+		std::env::temp_dir().join(format!("introspector-wal-{}", rand::random::<u64>()))
+	}
+
+	#[tokio::test]
+	async fn test_truncates_log_on_commit() {
+		let dir = temp_dir();
+		let inner = TestSink::default();
+		let mut sink = WalSink::open(Box::new(inner.clone()), &dir, 2).await.unwrap();
+
+		sink.write(&create_observation(1)).await.unwrap();
+		assert_eq!(read_entries(&dir.join("test.wal")).unwrap().len(), 1);
+		sink.write(&create_observation(2)).await.unwrap();
+		assert!(read_entries(&dir.join("test.wal")).unwrap().is_empty());
+		assert_eq!(inner.delivered.lock().unwrap().len(), 2);
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_replays_undelivered_entries() {
+		let dir = temp_dir();
+		let inner = TestSink::default();
+		*inner.fail_flush.lock().unwrap() = true;
+		let mut sink = WalSink::open(Box::new(inner.clone()), &dir, 1).await.unwrap();
+
+		assert!(sink.write(&create_observation(1)).await.is_err());
+		assert!(sink.write(&create_observation(2)).await.is_err());
+		// The same entry appended twice is delivered once
+		sink.state.lock().await.append(&create_observation(2)).unwrap();
+		drop(sink);
+
+		// Restart with the destination back online, the log is replayed in the background
+		let inner = TestSink::default();
+		let sink = WalSink::open(Box::new(inner.clone()), &dir, 1).await.unwrap();
+		while sink.state.lock().await.replaying {
+			tokio::task::yield_now().await;
+		}
+
+		assert_eq!(
+			*inner.delivered.lock().unwrap(),
+			vec![StreamPosition { epoch: 100, sequence: 1 }, StreamPosition { epoch: 100, sequence: 2 }]
+		);
+		assert!(read_entries(&dir.join("test.wal")).unwrap().is_empty());
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_starts_with_destination_down() {
+		let dir = temp_dir();
+		let inner = TestSink::default();
+		*inner.fail_flush.lock().unwrap() = true;
+		let mut sink = WalSink::open(Box::new(inner.clone()), &dir, 1).await.unwrap();
+		assert!(sink.write(&create_observation(1)).await.is_err());
+		drop(sink);
+
+		// The destination is still down, but the sink is opened and keeps the undelivered entries
+		let mut sink = WalSink::open(Box::new(inner.clone()), &dir, 1).await.unwrap();
+		assert!(sink.write(&create_observation(2)).await.is_err());
+		assert_eq!(read_entries(&dir.join("test.wal")).unwrap().len(), 2);
+
+		// Once the destination is back, new entries are delivered but the log is kept until it is replayed
+		*inner.fail_flush.lock().unwrap() = false;
+		sink.write(&create_observation(3)).await.unwrap();
+		assert_eq!(read_entries(&dir.join("test.wal")).unwrap().len(), 3);
+		sink.state.lock().await.replay().await.unwrap();
+		assert!(read_entries(&dir.join("test.wal")).unwrap().is_empty());
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -16,15 +16,23 @@
 
 //! This module keep tracks of the statistics for the parachain events
 
-use crate::types::{DisputesTracker, ParachainProgressUpdate};
+use crate::{
+	explorer::Explorer,
+	para_names::{DisplayPara, ParaNames},
+	types::{DisputesTracker, ParachainProgressUpdate, SkippedSlotReason},
+	utils::{backing_quorum, BackingQuorum},
+};
 use color_eyre::owo_colors::OwoColorize;
 use crossterm::style::Stylize;
+use itertools::Itertools;
 use mockall::automock;
-use polkadot_introspector_essentials::types::H256;
+use polkadot_introspector_essentials::types::{Timestamp, H256};
+use serde::Serialize;
 use std::{
-	collections::VecDeque,
+	collections::{BTreeMap, VecDeque},
 	default::Default,
 	fmt::{self, Display, Formatter},
+	str::FromStr,
 	time::Duration,
 };
 
@@ -97,6 +105,61 @@ impl<T: UsableNumber> AvgBucket<T> {
 	pub fn count(&self) -> usize {
 		self.num_samples
 	}
+
+	/// Returns counter value if there are samples
+	pub fn avg(&self) -> Option<f64> {
+		(self.num_samples > 0).then_some(self.avg)
+	}
+}
+
+/// Number of histogram buckets per doubling of the value, the percentiles are within 2.2% of the samples
+const BUCKETS_PER_DOUBLING: f64 = 16.0;
+
+/// Histogram of a distribution to compute its percentiles. The buckets grow exponentially, so their number is
+/// bounded by the range of the values rather than the number of samples
+#[derive(Clone, Default)]
+struct Samples {
+	/// Number of samples by bucket
+	buckets: BTreeMap<u32, u64>,
+	count: usize,
+}
+
+impl Samples {
+	pub fn update(&mut self, value: u32) {
+		*self.buckets.entry(Self::bucket(value)).or_default() += 1;
+		self.count += 1;
+	}
+
+	pub fn count(&self) -> usize {
+		self.count
+	}
+
+	/// Returns the nearest-rank percentile if there are samples, approximated by the middle of its bucket
+	pub fn percentile(&self, percentile: f64) -> Option<u32> {
+		let rank = ((percentile / 100.0 * self.count as f64).ceil() as u64).max(1);
+		let mut samples = 0;
+		self.buckets.iter().find_map(|(bucket, count)| {
+			samples += count;
+			(samples >= rank).then(|| Self::value(*bucket))
+		})
+	}
+
+	/// Bucket `n` holds the values from `2^((n - 1) / BUCKETS_PER_DOUBLING)` up to the next bucket, zero has a bucket
+	/// of its own
+	fn bucket(value: u32) -> u32 {
+		match value {
+			0 => 0,
+			_ => ((value as f64).log2() * BUCKETS_PER_DOUBLING) as u32 + 1,
+		}
+	}
+
+	/// The geometric middle of a bucket
+	fn value(bucket: u32) -> u32 {
+		match bucket {
+			0 => 0,
+			_ => 2f64.powf((bucket as f64 - 0.5) / BUCKETS_PER_DOUBLING).round() as u32,
+		}
+	}
 }
 
 /// Tracker of the disputes
@@ -116,17 +179,49 @@ struct DisputesStats {
 struct SkippedSlotBlock {
 	block_number: u32,
 	block_hash: H256,
+	reason: Option<SkippedSlotReason>,
 }
 
 impl SkippedSlotBlock {
 	pub fn new(update: &ParachainProgressUpdate) -> Self {
-		Self { block_number: update.block_number, block_hash: update.block_hash }
+		Self { block_number: update.block_number, block_hash: update.block_hash, reason: update.skipped_slot_reason() }
 	}
 }
 
 impl Display for SkippedSlotBlock {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		write!(f, "\n    {} {:?}", self.block_number, self.block_hash)
+		write!(f, "\n    {} {:?}", self.block_number, self.block_hash)?;
+		match self.reason {
+			Some(reason) => write!(f, " ({})", reason),
+			None => Ok(()),
+		}
+	}
+}
+
+/// A candidate evicted from the availability core without being included
+#[derive(Clone)]
+struct TimedOutCandidate {
+	block_number: u32,
+	candidate_hash: H256,
+}
+
+impl Display for TimedOutCandidate {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "\n    {} {:?}", self.block_number, self.candidate_hash)
+	}
+}
+
+fn join_timed_out_candidates_to_string(candidates: &VecDeque<TimedOutCandidate>, explorer: &Explorer) -> String {
+	if candidates.is_empty() {
+		String::from("none")
+	} else {
+		candidates
+			.iter()
+			.map(|c| match explorer.block_link(c.block_number) {
+				Some(url) => format!("{} {}", c, url),
+				None => c.to_string(),
+			})
+			.collect()
 	}
 }
 
@@ -138,14 +233,168 @@ fn join_skipped_slot_blocks_to_string(blocks: &VecDeque<SkippedSlotBlock>) -> St
 	}
 }
 
+fn join_relay_parent_ages_to_string(ages: &BTreeMap<u32, u32>) -> String {
+	if ages.is_empty() {
+		String::from("none")
+	} else {
+		ages.iter().map(|(age, count)| format!("{} ({})", age, count)).join(", ")
+	}
+}
+
+/// Statistics of the current session, summarized and reset when the session changes
+#[derive(Clone, Default)]
+struct SessionStats {
+	/// Session index, not known before the first session change
+	session_index: Option<u32>,
+	blocks: u32,
+	backed_count: u32,
+	included_count: u32,
+	skipped_slots: u32,
+	disputes: u32,
+	/// Average included time in relay parent blocks
+	included_times: AvgBucket<u16>,
+	/// Average included time in seconds
+	included_times_sec: AvgBucket<f32>,
+}
+
+impl SessionStats {
+	fn new(session_index: u32) -> Self {
+		Self { session_index: Some(session_index), ..Default::default() }
+	}
+
+	fn summary(&self, para_id: u32, para_name: Option<String>) -> SessionSummary {
+		SessionSummary {
+			para_id,
+			para_name,
+			session_index: self.session_index,
+			blocks: self.blocks,
+			backed_count: self.backed_count,
+			included_count: self.included_count,
+			skipped_slots: self.skipped_slots,
+			disputes: self.disputes,
+			avg_inclusion_time_blocks: self.included_times.avg(),
+			avg_inclusion_time_sec: self.included_times_sec.avg(),
+		}
+	}
+}
+
+/// Compact statistics of a parachain over a session
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SessionSummary {
+	pub para_id: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub para_name: Option<String>,
+	/// Session index, not set for the session the tracer was started in if no session change was seen before
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub session_index: Option<u32>,
+	/// Number of relay chain blocks processed in the session
+	pub blocks: u32,
+	pub backed_count: u32,
+	pub included_count: u32,
+	pub skipped_slots: u32,
+	pub disputes: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_inclusion_time_blocks: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_inclusion_time_sec: Option<f64>,
+}
+
+impl Display for SessionSummary {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		let session = self.session_index.map_or_else(|| "?".to_owned(), |v| v.to_string());
+		write!(
+			f,
+			"{} {}: {} blocks, {} backed, {} included",
+			format!("[Session {}] Parachain", session).bold().blue(),
+			DisplayPara(self.para_id, self.para_name.as_deref().map(Into::into)),
+			self.blocks,
+			self.backed_count,
+			self.included_count.to_string().green()
+		)?;
+		if let (Some(blocks), Some(sec)) = (self.avg_inclusion_time_blocks, self.avg_inclusion_time_sec) {
+			write!(f, " (in {:.2} blocks / {:.2}s on average)", blocks, sec)?;
+		}
+		writeln!(
+			f,
+			", {} skipped slots, {} disputes",
+			self.skipped_slots.to_string().bright_purple(),
+			self.disputes.to_string().bright_red()
+		)
+	}
+}
+
+/// A window the statistics are computed over, the statistics are reset when it elapses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsWindow {
+	/// A number of relay chain blocks
+	Blocks(u32),
+	/// A period of time measured by the relay chain block timestamps
+	Duration(Duration),
+}
+
+impl StatsWindow {
+	fn is_elapsed(&self, blocks: u32, time: Duration) -> bool {
+		match self {
+			StatsWindow::Blocks(v) => blocks >= *v,
+			StatsWindow::Duration(v) => time >= *v,
+		}
+	}
+}
+
+impl FromStr for StatsWindow {
+	type Err = String;
+
+	/// Parses a number of blocks (`600`) or a duration with `s`, `m`, `h` or `d` suffix (`1h`)
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		let (value, multiplier) = match s.char_indices().last() {
+			Some((idx, 's')) => (&s[..idx], Some(1)),
+			Some((idx, 'm')) => (&s[..idx], Some(60)),
+			Some((idx, 'h')) => (&s[..idx], Some(60 * 60)),
+			Some((idx, 'd')) => (&s[..idx], Some(24 * 60 * 60)),
+			_ => (s, None),
+		};
+		let value: u32 = value.parse().map_err(|_| format!("invalid stats window: {}", s))?;
+		if value == 0 {
+			return Err("stats window must not be empty".to_owned())
+		}
+
+		Ok(match multiplier {
+			Some(multiplier) => StatsWindow::Duration(Duration::from_secs(value as u64 * multiplier)),
+			None => StatsWindow::Blocks(value),
+		})
+	}
+}
+
+impl Display for StatsWindow {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			StatsWindow::Blocks(v) => write!(f, "{} blocks", v),
+			StatsWindow::Duration(v) => write!(f, "{} seconds", v.as_secs()),
+		}
+	}
+}
+
 #[automock]
 pub trait Stats {
-	fn on_backed(&mut self);
-	fn on_included(&mut self, relay_parent_number: u32, previous_included: Option<u32>, backed_in: Option<u32>);
+	fn on_backed(&mut self, votes: u32, group_size: u32, minimum_backing_votes: u32);
+	fn on_core_dispatch(&mut self, delay_blocks: u32);
+	fn on_relay_parent_age(&mut self, age: u32, is_old: bool);
+	fn on_unincluded_depth(&mut self, depth: u32);
+	fn on_included_candidates(&mut self, count: u32);
+	fn on_included(
+		&mut self,
+		relay_parent_number: u32,
+		previous_included: Option<u32>,
+		backed_in: Option<u32>,
+		para_block_time_sec: Option<Duration>,
+		backed_in_sec: Option<Duration>,
+	);
 	fn on_disputed(&mut self, dispute_outcome: &DisputesTracker);
 	fn on_block(&mut self, time: Duration);
 	fn on_bitfields(&mut self, nbits: u32, is_low: bool);
 	fn on_slow_availability(&mut self);
+	fn on_availability_timeout(&mut self, block_number: u32, candidate_hash: H256);
 	fn on_skipped_slot(&mut self, update: &ParachainProgressUpdate);
 }
 
@@ -156,6 +405,10 @@ pub struct ParachainStats {
 	para_id: u32,
 	/// Number of backed candidates.
 	backed_count: u32,
+	/// Number of candidates backed with the minimum number of validity votes.
+	min_quorum_backed_count: u32,
+	/// Average number of validity votes per backed candidate
+	backing_votes: AvgBucket<u32>,
 	/// Number of skipped slots, where no candidate was backed and availability core
 	/// was free.
 	skipped_slots: u32,
@@ -169,14 +422,48 @@ pub struct ParachainStats {
 	block_times: AvgBucket<f32>,
 	/// Number of slow availability events.
 	slow_avail_count: u32,
+	/// Number of candidates evicted from availability cores without inclusion.
+	availability_timeouts: u32,
+	/// Last candidates evicted from availability cores without inclusion
+	last_timed_out_candidates: VecDeque<TimedOutCandidate>,
 	/// Number of low bitfield propagation events.
 	low_bitfields_count: u32,
 	/// Number of bitfields being set
 	bitfields: AvgBucket<u32>,
 	/// Average included time in relay parent blocks
 	included_times: AvgBucket<u16>,
+	/// Average included time in seconds
+	included_times_sec: AvgBucket<f32>,
 	/// Average backing time in relay parent blocks
 	backed_times: AvgBucket<u16>,
+	/// Average backing time in seconds
+	backed_times_sec: AvgBucket<f32>,
+	/// Average time between scheduling on a core and backing in relay parent blocks
+	core_dispatch_times: AvgBucket<u16>,
+	/// Number of backed candidates by the age of their relay parents in relay chain blocks
+	relay_parent_ages: BTreeMap<u32, u32>,
+	/// Number of candidates backed on relay parents at the edge of the allowed ancestry
+	old_relay_parent_count: u32,
+	/// Number of candidates backed on relay parents older than the parent of the block
+	non_latest_relay_parent_count: u32,
+	/// Number of candidates backed and not yet included on chain per relay chain block
+	unincluded_depths: AvgBucket<u32>,
+	/// Number of candidates included per relay chain block, more than one with elastic scaling
+	included_per_block: AvgBucket<u32>,
+	/// Estimated PoV sizes of the backed candidates in bytes
+	pov_sizes: Samples,
+	/// The window the statistics are computed over, cumulative since start if not set
+	window: Option<StatsWindow>,
+	/// Timestamp of the first relay chain block in the current window
+	window_started_at: Option<Timestamp>,
+	/// Number of relay chain blocks in the current window
+	window_blocks: u32,
+	/// Statistics of the current session, kept across the windows
+	session: SessionStats,
+	/// Links the timed out candidates to the block explorer
+	explorer: Explorer,
+	/// Names of the parachains shown in the statistics
+	para_names: ParaNames,
 }
 
 impl ParachainStats {
@@ -185,38 +472,226 @@ impl ParachainStats {
 	/// # Arguments
 	///
 	/// * `para_id` - Parachain id
-	/// * `last_skipped_slot_blocks` - The number of last blocks with missing slots
-	pub fn new(para_id: u32, last_skipped_slot_blocks: usize) -> Self {
+	/// * `last_skipped_slot_blocks` - The number of last blocks with missing slots, also limits the number of
+	///   last timed out candidates
+	/// * `window` - The window the statistics are reset after
+	pub fn new(para_id: u32, last_skipped_slot_blocks: usize, window: Option<StatsWindow>) -> Self {
 		Self {
 			para_id,
 			last_skipped_slot_blocks: VecDeque::with_capacity(last_skipped_slot_blocks),
+			last_timed_out_candidates: VecDeque::with_capacity(last_skipped_slot_blocks),
+			window,
 			..Default::default()
 		}
 	}
+
+	/// Links the timed out candidates to the block explorer
+	pub fn with_explorer(mut self, explorer: Explorer) -> Self {
+		self.explorer = explorer;
+		self
+	}
+
+	/// Shows the name of the parachain in the statistics
+	pub fn with_para_names(mut self, para_names: ParaNames) -> Self {
+		self.para_names = para_names;
+		self
+	}
+
+	/// Counts a relay chain block in the current window, if the window has elapsed,
+	/// resets the statistics and returns the ones collected over the elapsed window
+	pub fn maybe_rotate_window(&mut self, ts: Timestamp) -> Option<ParachainStats> {
+		let window = self.window?;
+		let started_at = *self.window_started_at.get_or_insert(ts);
+		self.window_blocks += 1;
+		if !window.is_elapsed(self.window_blocks, Duration::from_millis(ts.saturating_sub(started_at))) {
+			return None
+		}
+
+		let mut next = Self::new(self.para_id, self.last_skipped_slot_blocks.capacity(), Some(window))
+			.with_explorer(self.explorer.clone())
+			.with_para_names(self.para_names.clone());
+		next.session = std::mem::take(&mut self.session);
+		Some(std::mem::replace(self, next))
+	}
+
+	/// Starts the statistics of a new session, returns the summary of the previous one if any blocks were processed
+	/// in it
+	pub fn on_new_session(&mut self, session_index: u32) -> Option<SessionSummary> {
+		if self.session.session_index == Some(session_index) {
+			return None
+		}
+		let previous = std::mem::replace(&mut self.session, SessionStats::new(session_index));
+		(previous.blocks > 0).then(|| previous.summary(self.para_id, self.para_name()))
+	}
+
+	/// Update estimated PoV size of a backed candidate
+	pub fn on_pov_size(&mut self, size: u32) {
+		self.pov_sizes.update(size);
+	}
+
+	fn para_name(&self) -> Option<String> {
+		self.para_names.get(self.para_id).map(|name| name.to_string())
+	}
+
+	/// Returns the statistics in a machine-readable form
+	pub fn summary(&self) -> StatsSummary {
+		StatsSummary {
+			para_id: self.para_id,
+			para_name: self.para_name(),
+			blocks: self.block_times.count() as u32,
+			avg_block_time_sec: self.block_times.avg(),
+			backed_count: self.backed_count,
+			included_count: self.included_count,
+			avg_backing_time_blocks: self.backed_times.avg(),
+			avg_backing_time_sec: self.backed_times_sec.avg(),
+			avg_inclusion_time_blocks: self.included_times.avg(),
+			avg_inclusion_time_sec: self.included_times_sec.avg(),
+			avg_core_dispatch_time_blocks: self.core_dispatch_times.avg(),
+			avg_backing_votes: self.backing_votes.avg(),
+			min_quorum_backed_count: self.min_quorum_backed_count,
+			skipped_slots: self.skipped_slots,
+			slow_availability: self.slow_avail_count,
+			availability_timeouts: self.availability_timeouts,
+			slow_bitfields_propagation: self.low_bitfields_count,
+			old_relay_parents: self.old_relay_parent_count,
+			disputes: self.disputes_stats.disputed_count,
+			disputes_concluded_valid: self.disputes_stats.concluded_valid,
+			disputes_concluded_invalid: self.disputes_stats.concluded_invalid,
+			avg_dispute_resolution_blocks: self.disputes_stats.resolution_time.avg(),
+			non_latest_relay_parents: self.non_latest_relay_parent_count,
+			avg_unincluded_depth: self.unincluded_depths.avg(),
+			max_unincluded_depth: self.unincluded_depths.max,
+			avg_included_per_block: self.included_per_block.avg(),
+			max_included_per_block: self.included_per_block.max,
+			p50_pov_size_bytes: self.pov_sizes.percentile(50.0),
+			p95_pov_size_bytes: self.pov_sizes.percentile(95.0),
+		}
+	}
+}
+
+/// Statistics of a parachain, averages are not set without samples
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StatsSummary {
+	pub para_id: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub para_name: Option<String>,
+	/// Number of relay chain blocks processed
+	pub blocks: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_block_time_sec: Option<f64>,
+	pub backed_count: u32,
+	pub included_count: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_backing_time_blocks: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_backing_time_sec: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_inclusion_time_blocks: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_inclusion_time_sec: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_core_dispatch_time_blocks: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_backing_votes: Option<f64>,
+	pub min_quorum_backed_count: u32,
+	pub skipped_slots: u32,
+	pub slow_availability: u32,
+	pub availability_timeouts: u32,
+	pub slow_bitfields_propagation: u32,
+	pub old_relay_parents: u32,
+	pub disputes: u32,
+	pub disputes_concluded_valid: u32,
+	pub disputes_concluded_invalid: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_dispute_resolution_blocks: Option<f64>,
+	pub non_latest_relay_parents: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_unincluded_depth: Option<f64>,
+	pub max_unincluded_depth: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avg_included_per_block: Option<f64>,
+	pub max_included_per_block: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub p50_pov_size_bytes: Option<u32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub p95_pov_size_bytes: Option<u32>,
 }
 impl Stats for ParachainStats {
-	/// Update backed counter
-	fn on_backed(&mut self) {
+	/// Update backed counter and backing votes
+	fn on_backed(&mut self, votes: u32, group_size: u32, minimum_backing_votes: u32) {
 		self.backed_count += 1;
+		self.session.backed_count += 1;
+
+		if group_size > 0 {
+			self.backing_votes.update(votes);
+			if backing_quorum(votes, group_size, minimum_backing_votes) == BackingQuorum::Minimum {
+				self.min_quorum_backed_count += 1;
+			}
+		}
+	}
+
+	/// Update core dispatch latency
+	fn on_core_dispatch(&mut self, delay_blocks: u32) {
+		self.core_dispatch_times.update(delay_blocks as u16);
+	}
+
+	/// Update relay parent age distribution
+	fn on_relay_parent_age(&mut self, age: u32, is_old: bool) {
+		*self.relay_parent_ages.entry(age).or_default() += 1;
+		if is_old {
+			self.old_relay_parent_count += 1;
+		}
+		if age > 1 {
+			self.non_latest_relay_parent_count += 1;
+		}
+	}
+
+	/// Update unincluded segment depth
+	fn on_unincluded_depth(&mut self, depth: u32) {
+		self.unincluded_depths.update(depth);
+	}
+
+	/// Update candidates included per relay chain block
+	fn on_included_candidates(&mut self, count: u32) {
+		self.included_per_block.update(count);
 	}
 
 	/// Update included counter
-	fn on_included(&mut self, relay_parent_number: u32, previous_included: Option<u32>, backed_in: Option<u32>) {
+	fn on_included(
+		&mut self,
+		relay_parent_number: u32,
+		previous_included: Option<u32>,
+		backed_in: Option<u32>,
+		para_block_time_sec: Option<Duration>,
+		backed_in_sec: Option<Duration>,
+	) {
 		self.included_count += 1;
+		self.session.included_count += 1;
 
 		if let Some(previous_block_number) = previous_included {
-			self.included_times
-				.update(relay_parent_number.saturating_sub(previous_block_number) as u16);
+			let included_time = relay_parent_number.saturating_sub(previous_block_number) as u16;
+			self.included_times.update(included_time);
+			self.session.included_times.update(included_time);
+		}
+
+		if let Some(time) = para_block_time_sec {
+			self.included_times_sec.update(time.as_secs_f32());
+			self.session.included_times_sec.update(time.as_secs_f32());
 		}
 
 		if let Some(backed_in) = backed_in {
 			self.backed_times.update(backed_in as u16);
 		}
+
+		if let Some(time) = backed_in_sec {
+			self.backed_times_sec.update(time.as_secs_f32());
+		}
 	}
 
 	/// Update disputed counter
 	fn on_disputed(&mut self, dispute_outcome: &DisputesTracker) {
 		self.disputes_stats.disputed_count += 1;
+		self.session.disputes += 1;
 
 		if dispute_outcome.voted_for > dispute_outcome.voted_against {
 			self.disputes_stats.concluded_valid += 1;
@@ -233,6 +708,7 @@ impl Stats for ParachainStats {
 	/// Track block
 	fn on_block(&mut self, time: Duration) {
 		self.block_times.update(time.as_secs_f32());
+		self.session.blocks += 1;
 	}
 
 	/// Track bitfields
@@ -248,9 +724,21 @@ impl Stats for ParachainStats {
 		self.slow_avail_count += 1;
 	}
 
+	/// Update count and last candidates details for availability timeouts
+	fn on_availability_timeout(&mut self, block_number: u32, candidate_hash: H256) {
+		self.availability_timeouts += 1;
+
+		if self.last_timed_out_candidates.len() >= self.last_timed_out_candidates.capacity() {
+			self.last_timed_out_candidates.pop_front();
+		}
+		self.last_timed_out_candidates
+			.push_back(TimedOutCandidate { block_number, candidate_hash })
+	}
+
 	/// Update count and last blocks details for skipped slots
 	fn on_skipped_slot(&mut self, update: &ParachainProgressUpdate) {
 		self.skipped_slots += 1;
+		self.session.skipped_slots += 1;
 
 		if self.last_skipped_slot_blocks.len() >= self.last_skipped_slot_blocks.capacity() {
 			self.last_skipped_slot_blocks.pop_front();
@@ -261,7 +749,26 @@ impl Stats for ParachainStats {
 
 impl Display for ParachainStats {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		writeln!(f, "{}", format!("--- Parachain {} trace statistics ---", self.para_id).bold().blue())?;
+		match self.window {
+			Some(window) => writeln!(
+				f,
+				"{}",
+				format!(
+					"--- Parachain {} trace statistics, window of {} ---",
+					self.para_names.display(self.para_id),
+					window
+				)
+				.bold()
+				.blue()
+			)?,
+			None => writeln!(
+				f,
+				"{}",
+				format!("--- Parachain {} trace statistics ---", self.para_names.display(self.para_id))
+					.bold()
+					.blue()
+			)?,
+		}
 		writeln!(
 			f,
 			"Average relay chain block time: {} seconds ({} blocks processed)",
@@ -270,21 +777,58 @@ impl Display for ParachainStats {
 		)?;
 		writeln!(
 			f,
-			"Average parachain block inclusion time: {} relay parent blocks ({} parachain blocks processed)",
+			"Average parachain block inclusion time: {} relay parent blocks, {} seconds ({} parachain blocks processed)",
 			format!("{:.2}", self.included_times.value()).bold(),
+			format!("{:.2}", self.included_times_sec.value()).bold(),
 			self.included_times.count()
 		)?;
 		writeln!(
 			f,
-			"Average parachain block backing time: {} relay parent blocks ({} parachain blocks processed)",
+			"Average parachain block backing time: {} relay parent blocks, {} seconds ({} parachain blocks processed)",
 			format!("{:.2}", self.backed_times.value()).bold(),
+			format!("{:.2}", self.backed_times_sec.value()).bold(),
 			self.backed_times.count()
 		)?;
 		writeln!(
 			f,
-			"Skipped slots: {}, slow availability: {}, slow bitfields propagation: {}",
+			"Average core dispatch time: {} relay parent blocks ({} parachain blocks processed)",
+			format!("{:.2}", self.core_dispatch_times.value()).bold(),
+			self.core_dispatch_times.count()
+		)?;
+		writeln!(
+			f,
+			"Relay parent age in blocks (candidates): {}, {} candidates backed on old relay parents",
+			join_relay_parent_ages_to_string(&self.relay_parent_ages),
+			self.old_relay_parent_count.to_string().bright_yellow()
+		)?;
+		writeln!(
+			f,
+			"Async backing: {} candidates backed on non-latest relay parents, unincluded candidates: {} on average, {} at most",
+			self.non_latest_relay_parent_count.to_string().bold(),
+			format!("{:.2}", self.unincluded_depths.value()).bold(),
+			self.unincluded_depths.max
+		)?;
+		writeln!(
+			f,
+			"Throughput: {} candidates included per relay chain block on average, {} at most",
+			format!("{:.2}", self.included_per_block.value()).bold(),
+			self.included_per_block.max
+		)?;
+		if let (Some(p50), Some(p95)) = (self.pov_sizes.percentile(50.0), self.pov_sizes.percentile(95.0)) {
+			writeln!(
+				f,
+				"PoV size: {} KiB median, {} KiB p95 of {} candidates",
+				format!("{:.1}", p50 as f64 / 1024.0).bold(),
+				format!("{:.1}", p95 as f64 / 1024.0).bright_yellow(),
+				self.pov_sizes.count()
+			)?;
+		}
+		writeln!(
+			f,
+			"Skipped slots: {}, slow availability: {}, availability timeouts: {}, slow bitfields propagation: {}",
 			self.skipped_slots.to_string().bright_purple(),
 			self.slow_avail_count.to_string().bright_cyan(),
+			self.availability_timeouts.to_string().bright_red(),
 			self.low_bitfields_count.to_string().bright_magenta()
 		)?;
 		writeln!(
@@ -292,6 +836,11 @@ impl Display for ParachainStats {
 			"Last blocks with skipped slots: {}",
 			join_skipped_slot_blocks_to_string(&self.last_skipped_slot_blocks).bright_purple()
 		)?;
+		writeln!(
+			f,
+			"Last availability timed out candidates: {}",
+			join_timed_out_candidates_to_string(&self.last_timed_out_candidates, &self.explorer).bright_red()
+		)?;
 		writeln!(f, "Average bitfileds: {:.3}", self.bitfields.value())?;
 		writeln!(
 			f,
@@ -299,6 +848,12 @@ impl Display for ParachainStats {
 			self.backed_count.to_string().blue(),
 			self.included_count.to_string().green()
 		)?;
+		writeln!(
+			f,
+			"Backing votes: {} on average, {} blocks backed with minimum quorum",
+			format!("{:.2}", self.backing_votes.value()).bold(),
+			self.min_quorum_backed_count.to_string().bright_yellow()
+		)?;
 		writeln!(f, "Disputes stats: {}", self.disputes_stats)
 	}
 }
@@ -316,3 +871,119 @@ impl Display for DisputesStats {
 		)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use polkadot_introspector_essentials::constants::MINIMUM_BACKING_VOTES;
+
+	#[test]
+	fn test_parses_window() {
+		assert_eq!("600".parse::<StatsWindow>(), Ok(StatsWindow::Blocks(600)));
+		assert_eq!("90s".parse::<StatsWindow>(), Ok(StatsWindow::Duration(Duration::from_secs(90))));
+		assert_eq!("1h".parse::<StatsWindow>(), Ok(StatsWindow::Duration(Duration::from_secs(3600))));
+		assert!("0".parse::<StatsWindow>().is_err());
+		assert!("1w".parse::<StatsWindow>().is_err());
+	}
+
+	#[test]
+	fn test_rotates_block_window() {
+		let mut stats = ParachainStats::new(100, 10, Some(StatsWindow::Blocks(2)));
+
+		stats.on_slow_availability();
+		assert!(stats.maybe_rotate_window(1694095320000).is_none());
+		let elapsed = stats.maybe_rotate_window(1694095326000).unwrap();
+
+		assert_eq!(elapsed.slow_avail_count, 1);
+		assert_eq!(stats.slow_avail_count, 0);
+		assert_eq!(stats.last_skipped_slot_blocks.capacity(), elapsed.last_skipped_slot_blocks.capacity());
+	}
+
+	#[test]
+	fn test_rotates_time_window() {
+		let mut stats = ParachainStats::new(100, 10, Some(StatsWindow::Duration(Duration::from_secs(12))));
+
+		assert!(stats.maybe_rotate_window(1694095320000).is_none());
+		assert!(stats.maybe_rotate_window(1694095326000).is_none());
+		assert!(stats.maybe_rotate_window(1694095332000).is_some());
+		// The next window starts with the next block
+		assert!(stats.maybe_rotate_window(1694095338000).is_none());
+	}
+
+	#[test]
+	fn test_keeps_last_timed_out_candidates() {
+		let mut stats = ParachainStats::new(100, 2, None);
+		let hashes = [H256::random(), H256::random(), H256::random()];
+		for (block_number, candidate_hash) in hashes.iter().enumerate() {
+			stats.on_availability_timeout(block_number as u32, *candidate_hash);
+		}
+
+		assert_eq!(stats.summary().availability_timeouts, 3);
+		assert_eq!(
+			stats
+				.last_timed_out_candidates
+				.iter()
+				.map(|c| (c.block_number, c.candidate_hash))
+				.collect::<Vec<_>>(),
+			vec![(1, hashes[1]), (2, hashes[2])]
+		);
+	}
+
+	#[test]
+	fn test_summarizes_pov_sizes() {
+		let mut stats = ParachainStats::new(100, 10, None);
+		assert_eq!(stats.summary().p50_pov_size_bytes, None);
+
+		for size in (1..=20).rev() {
+			stats.on_pov_size(size * 1000);
+		}
+		let summary = stats.summary();
+		// The percentiles are approximated by the middle of their buckets
+		assert!(summary.p50_pov_size_bytes.unwrap().abs_diff(10000) <= 10000 * 22 / 1000);
+		assert!(summary.p95_pov_size_bytes.unwrap().abs_diff(19000) <= 19000 * 22 / 1000);
+		assert_eq!(stats.pov_sizes.count(), 20);
+		assert!(stats.pov_sizes.buckets.len() <= 20);
+	}
+
+	#[test]
+	fn test_summarizes_sessions() {
+		let mut stats = ParachainStats::new(100, 10, Some(StatsWindow::Blocks(1)));
+		assert!(stats.on_new_session(5).is_none());
+
+		stats.on_block(Duration::from_secs(6));
+		stats.on_backed(5, 5, MINIMUM_BACKING_VOTES);
+		stats.on_included(12, Some(10), Some(1), Some(Duration::from_secs(12)), None);
+		// The session is kept across the windows
+		assert!(stats.maybe_rotate_window(1694095320000).is_some());
+		stats.on_block(Duration::from_secs(6));
+		stats.on_skipped_slot(&ParachainProgressUpdate::default());
+
+		assert!(stats.on_new_session(5).is_none());
+		let summary = stats.on_new_session(6).unwrap();
+		assert_eq!(
+			summary,
+			SessionSummary {
+				para_id: 100,
+				para_name: None,
+				session_index: Some(5),
+				blocks: 2,
+				backed_count: 1,
+				included_count: 1,
+				skipped_slots: 1,
+				disputes: 0,
+				avg_inclusion_time_blocks: Some(2.0),
+				avg_inclusion_time_sec: Some(12.0),
+			}
+		);
+		assert_eq!(stats.session.session_index, Some(6));
+		assert_eq!(stats.session.blocks, 0);
+	}
+
+	#[test]
+	fn test_keeps_cumulative_stats_without_window() {
+		let mut stats = ParachainStats::new(100, 10, None);
+
+		assert!(stats.maybe_rotate_window(1694095320000).is_none());
+		assert!(stats.maybe_rotate_window(1694095920000).is_none());
+	}
+}
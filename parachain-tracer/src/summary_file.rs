@@ -0,0 +1,192 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Statistics of the traced parachains written to a file when the tracer stops, to compare releases in CI.
+//!
+//! Every parachain tracker records its statistics when it stops and the file is rewritten with all statistics
+//! recorded so far, so it is complete however the tracer was stopped. The JSON format is described by the
+//! `summary` schema and also includes the RPC error budget of the run, CSV has a row per parachain with the same
+//! columns.
+
+use crate::stats::StatsSummary;
+use clap::ValueEnum;
+use log::error;
+use polkadot_introspector_essentials::rpc_budget::{RpcBudgetCounter, RpcErrorBudget};
+use serde::Serialize;
+use std::{
+	collections::BTreeMap,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+
+/// Columns of the CSV format, the fields of `StatsSummary` in order
+const CSV_COLUMNS: [&str; 28] = [
+	"para_id",
+	"para_name",
+	"blocks",
+	"avg_block_time_sec",
+	"backed_count",
+	"included_count",
+	"avg_backing_time_blocks",
+	"avg_backing_time_sec",
+	"avg_inclusion_time_blocks",
+	"avg_inclusion_time_sec",
+	"avg_core_dispatch_time_blocks",
+	"avg_backing_votes",
+	"min_quorum_backed_count",
+	"skipped_slots",
+	"slow_availability",
+	"availability_timeouts",
+	"slow_bitfields_propagation",
+	"old_relay_parents",
+	"disputes",
+	"disputes_concluded_valid",
+	"disputes_concluded_invalid",
+	"avg_dispute_resolution_blocks",
+	"non_latest_relay_parents",
+	"avg_unincluded_depth",
+	"max_unincluded_depth",
+	"avg_included_per_block",
+	"max_included_per_block",
+	"p50_pov_size_bytes",
+	"p95_pov_size_bytes",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum SummaryFormat {
+	Csv,
+	#[default]
+	Json,
+}
+
+#[derive(Serialize)]
+struct SummaryDocument<'a> {
+	parachains: Vec<&'a StatsSummary>,
+	rpc: RpcErrorBudget,
+}
+
+/// Collects statistics of the parachain trackers into a file
+#[derive(Clone)]
+pub(crate) struct SummaryFile {
+	path: PathBuf,
+	format: SummaryFormat,
+	summaries: Arc<Mutex<BTreeMap<u32, StatsSummary>>>,
+	rpc_budget: RpcBudgetCounter,
+}
+
+impl SummaryFile {
+	pub fn new(path: PathBuf, format: SummaryFormat, rpc_budget: RpcBudgetCounter) -> Self {
+		Self { path, format, summaries: Default::default(), rpc_budget }
+	}
+
+	/// Records the statistics of a stopped tracker and rewrites the file
+	pub fn record(&self, summary: StatsSummary) {
+		let mut summaries = self.summaries.lock().expect("summaries lock is poisoned");
+		summaries.insert(summary.para_id, summary);
+		let content = render(summaries.values(), self.format, self.rpc_budget.snapshot());
+		if let Err(e) = std::fs::write(&self.path, content) {
+			error!("Cannot write the summary to {}: {:?}", self.path.display(), e);
+		}
+	}
+}
+
+fn render<'a>(summaries: impl Iterator<Item = &'a StatsSummary>, format: SummaryFormat, rpc: RpcErrorBudget) -> String {
+	match format {
+		SummaryFormat::Json => {
+			let document = SummaryDocument { parachains: summaries.collect(), rpc };
+			serde_json::to_string_pretty(&document).expect("summaries are serializable; qed")
+		},
+		SummaryFormat::Csv => {
+			let mut csv = CSV_COLUMNS.join(",");
+			csv.push('\n');
+			for summary in summaries {
+				let value = serde_json::to_value(summary).expect("summaries are serializable; qed");
+				let row: Vec<String> = CSV_COLUMNS
+					.iter()
+					.map(|column| match &value[column] {
+						serde_json::Value::Null => String::new(),
+						serde_json::Value::Number(number) => match number.as_f64() {
+							Some(v) if number.is_f64() => format!("{:.3}", v),
+							_ => number.to_string(),
+						},
+						other => other.to_string(),
+					})
+					.collect();
+				csv.push_str(&row.join(","));
+				csv.push('\n');
+			}
+			csv
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::schema;
+
+	fn summary() -> StatsSummary {
+		StatsSummary {
+			para_id: 100,
+			blocks: 10,
+			avg_block_time_sec: Some(6.0123),
+			backed_count: 9,
+			included_count: 8,
+			skipped_slots: 1,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_renders_csv() {
+		let csv = render([summary()].iter(), SummaryFormat::Csv, Default::default());
+		let mut lines = csv.lines();
+		assert_eq!(lines.next().unwrap(), CSV_COLUMNS.join(","));
+		assert_eq!(lines.next().unwrap(), "100,,10,6.012,9,8,,,,,,,0,1,0,0,0,0,0,0,0,,0,,0,,0,,");
+		assert!(lines.next().is_none());
+	}
+
+	#[test]
+	fn test_csv_columns_cover_all_fields() {
+		let full = StatsSummary {
+			para_name: Some("Moonbeam".to_string()),
+			avg_backing_time_blocks: Some(1.0),
+			avg_backing_time_sec: Some(1.0),
+			avg_inclusion_time_blocks: Some(1.0),
+			avg_inclusion_time_sec: Some(1.0),
+			avg_core_dispatch_time_blocks: Some(1.0),
+			avg_backing_votes: Some(1.0),
+			avg_dispute_resolution_blocks: Some(1.0),
+			avg_unincluded_depth: Some(1.0),
+			avg_included_per_block: Some(1.0),
+			p50_pov_size_bytes: Some(1),
+			p95_pov_size_bytes: Some(1),
+			..summary()
+		};
+		let value = serde_json::to_value(&full).unwrap();
+		let mut fields: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+		fields.sort();
+		let mut columns = CSV_COLUMNS.to_vec();
+		columns.sort();
+		assert_eq!(fields, columns);
+	}
+
+	#[test]
+	fn test_serialized_summary_conforms_to_schema() {
+		let json = render([summary()].iter(), SummaryFormat::Json, Default::default());
+		schema::tests::assert_conforms("summary", &serde_json::from_str(&json).unwrap());
+	}
+}
@@ -0,0 +1,439 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Enriched tracking of the system parachains.
+//!
+//! Parachain blocks are not part of the relay chain, but the head data of a candidate is the encoded header of its
+//! parachain block, so the block can be looked up on a node of the parachain. The system parachains among the
+//! parachains with a node are recognized by the runtime of the node at startup. Once a candidate is included its
+//! block is passed to a task of its own, which fetches the events of the block off the parachain task and counts its
+//! activity:
+//! - BridgeHub: messages accepted, received and delivered by the bridge messages pallets
+//! - AssetHub: asset transactions, i.e. extrinsics emitting events of the assets pallets, and their transfers,
+//!   issues and burns
+
+use crate::{
+	para_nodes::ParaNode,
+	types::{ParachainConsensusEvent, ParachainProgressUpdate},
+	utils::candidate_hash,
+};
+use color_eyre::eyre::eyre;
+use log::warn;
+use parity_scale_codec::Decode;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::{ExecutorContext, RequestExecutor},
+	metadata::polkadot_primitives::BackedCandidate,
+	types::{BlockNumber, Header, H256},
+	utils::RetryOptions,
+};
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::{self, Display, Formatter},
+};
+use subxt::{
+	config::{substrate::BlakeTwo256, Hasher},
+	events::Phase,
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Pallets of BridgeHub relaying messages to the bridged chains, e.g. `BridgeKusamaMessages`
+fn is_bridge_messages_pallet(pallet: &str) -> bool {
+	pallet.starts_with("Bridge") && pallet.ends_with("Messages")
+}
+
+/// Pallets of AssetHub managing fungible assets
+const ASSETS_PALLETS: &[&str] = &["Assets", "ForeignAssets", "PoolAssets"];
+
+/// Number of relay chain blocks a backed candidate is followed for, candidates backed on forks are never included
+const MAX_PENDING_BLOCKS: BlockNumber = 64;
+
+/// Returns the system parachains among the parachains with a node, recognized by the runtimes of their nodes
+pub(crate) async fn resolve(
+	nodes: &[ParaNode],
+	retry: &RetryOptions,
+	context: &ExecutorContext,
+) -> Vec<SystemParachainNode> {
+	let mut executor = RequestExecutor::new(retry.clone(), context.clone());
+	let mut system_parachains = vec![];
+	for node in nodes {
+		match executor.get_runtime_version(&node.url).await {
+			Ok(version) =>
+				if let Some(chain) = SystemParachain::from_spec_name(&version.spec_name) {
+					system_parachains.push(SystemParachainNode {
+						chain,
+						para_id: node.para_id,
+						node: node.url.clone(),
+					});
+				},
+			Err(e) => warn!("Cannot read the runtime version of {}: {:?}", node.url, e),
+		}
+	}
+	system_parachains
+}
+
+/// A system parachain with a node
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SystemParachainNode {
+	chain: SystemParachain,
+	para_id: u32,
+	/// RPC node of the system parachain
+	node: String,
+}
+
+/// A system parachain with enriched tracking
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SystemParachain {
+	AssetHub,
+	BridgeHub,
+}
+
+impl SystemParachain {
+	/// Recognizes a system parachain by its runtime, e.g. `asset-hub-polkadot` or `bridge-hub-kusama`
+	fn from_spec_name(spec_name: &str) -> Option<Self> {
+		match spec_name {
+			// The former names of AssetHub
+			"statemint" | "statemine" | "westmint" => Some(Self::AssetHub),
+			_ if spec_name.starts_with("asset-hub") => Some(Self::AssetHub),
+			_ if spec_name.starts_with("bridge-hub") => Some(Self::BridgeHub),
+			_ => None,
+		}
+	}
+
+	/// Names of the counters of the parachain's activity, in the order they are counted
+	pub(crate) fn counters(&self) -> &'static [&'static str] {
+		match self {
+			Self::AssetHub => &["asset_transactions", "asset_transfers", "assets_issued", "assets_burned"],
+			Self::BridgeHub => &["bridge_messages_accepted", "bridge_messages_received", "bridge_messages_delivered"],
+		}
+	}
+
+	/// Counts the activity of a parachain block by its events
+	fn count(&self, events: &[ParaEvent]) -> Vec<u64> {
+		let count = |pallet: fn(&str) -> bool, variants: &[&str]| {
+			events
+				.iter()
+				.filter(|event| pallet(&event.pallet) && variants.contains(&event.variant.as_str()))
+				.count() as u64
+		};
+		match self {
+			Self::AssetHub => {
+				let is_assets_pallet = |pallet: &str| ASSETS_PALLETS.contains(&pallet);
+				let transactions: HashSet<u32> = events
+					.iter()
+					.filter(|event| is_assets_pallet(&event.pallet))
+					.filter_map(|event| event.extrinsic_index)
+					.collect();
+				vec![
+					transactions.len() as u64,
+					count(is_assets_pallet, &["Transferred", "TransferredApproved"]),
+					count(is_assets_pallet, &["Issued"]),
+					count(is_assets_pallet, &["Burned"]),
+				]
+			},
+			Self::BridgeHub => vec![
+				count(is_bridge_messages_pallet, &["MessageAccepted"]),
+				count(is_bridge_messages_pallet, &["MessagesReceived"]),
+				count(is_bridge_messages_pallet, &["MessagesDelivered"]),
+			],
+		}
+	}
+}
+
+impl Display for SystemParachain {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			Self::AssetHub => write!(f, "AssetHub"),
+			Self::BridgeHub => write!(f, "BridgeHub"),
+		}
+	}
+}
+
+/// An event of a parachain block
+#[derive(Clone, Debug)]
+struct ParaEvent {
+	pallet: String,
+	variant: String,
+	/// Index of the extrinsic emitting the event, if any
+	extrinsic_index: Option<u32>,
+}
+
+/// Activity of the parachain block of an included candidate
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CandidateActivity {
+	pub chain: SystemParachain,
+	/// Number of the parachain block, if its header can be decoded
+	pub para_block_number: Option<BlockNumber>,
+	pub para_block_hash: H256,
+	/// Counter values, in the order of `SystemParachain::counters`
+	pub counts: Vec<u64>,
+}
+
+impl CandidateActivity {
+	/// Pairs of counter names and values
+	pub(crate) fn counters(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+		self.chain.counters().iter().copied().zip(self.counts.iter().copied())
+	}
+}
+
+impl Display for CandidateActivity {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(f, "{} block ", self.chain)?;
+		match self.para_block_number {
+			Some(number) => write!(f, "#{}", number)?,
+			None => write!(f, "{:?}", self.para_block_hash)?,
+		}
+		let counters = self
+			.counters()
+			.map(|(name, value)| format!("{}: {}", name.replace('_', " "), value))
+			.collect::<Vec<_>>();
+		write!(f, ", {}", counters.join(", "))
+	}
+}
+
+/// Counts the activity of the included candidates of a system parachain
+pub(crate) struct ActivityCounter {
+	/// Number and hash of the parachain blocks of the backed candidates with the relay chain block they were backed
+	/// at, by candidate hash
+	pending: HashMap<H256, (BlockNumber, Option<BlockNumber>, H256)>,
+	/// Parachain blocks of the included candidates, passed to the fetcher
+	included: UnboundedSender<(Option<BlockNumber>, H256)>,
+	activities: UnboundedReceiver<CandidateActivity>,
+}
+
+impl ActivityCounter {
+	/// Spawns the fetcher of the events if the parachain is a system parachain with a node. It stops when the counter
+	/// is dropped
+	pub fn spawn(
+		para_id: u32,
+		nodes: &[SystemParachainNode],
+		retry: &RetryOptions,
+		context: &ExecutorContext,
+	) -> Option<Self> {
+		let node = nodes.iter().find(|node| node.para_id == para_id)?;
+		let (included, included_rx) = unbounded_channel();
+		let (activities_tx, activities) = unbounded_channel();
+		let fetcher = EventFetcher {
+			chain: node.chain,
+			para_id,
+			node: node.node.clone(),
+			executor: RequestExecutor::new(retry.clone(), context.clone()),
+		};
+		tokio::spawn(fetcher.run(included_rx, activities_tx));
+
+		Some(Self { pending: Default::default(), included, activities })
+	}
+
+	/// Passes the blocks of the candidates included in the relay chain block to the fetcher, returns the activity
+	/// it has counted since the last call
+	pub fn on_progress(
+		&mut self,
+		progress: &ParachainProgressUpdate,
+		backed_candidates: &[BackedCandidate<H256>],
+	) -> Vec<CandidateActivity> {
+		for block in self.included_blocks(progress, backed_candidates) {
+			// The fetcher stops only when the counter is dropped
+			let _ = self.included.send(block);
+		}
+		std::iter::from_fn(|| self.activities.try_recv().ok()).collect()
+	}
+
+	/// Follows the parachain blocks of the backed candidates, returns the ones included in the relay chain block
+	fn included_blocks(
+		&mut self,
+		progress: &ParachainProgressUpdate,
+		backed_candidates: &[BackedCandidate<H256>],
+	) -> Vec<(Option<BlockNumber>, H256)> {
+		for candidate in backed_candidates {
+			let head_data = &candidate.candidate.commitments.head_data.0;
+			// The head data is the encoded header, so its hash is the hash of the parachain block
+			let para_block_hash = BlakeTwo256::hash(head_data);
+			let para_block_number = Header::decode(&mut &head_data[..]).ok().map(|header| header.number);
+			self.pending
+				.insert(candidate_hash(candidate), (progress.block_number, para_block_number, para_block_hash));
+		}
+
+		let mut blocks = vec![];
+		for event in &progress.events {
+			match event {
+				ParachainConsensusEvent::Included(candidate_hash, _, _) => blocks.extend(
+					self.pending
+						.remove(candidate_hash)
+						.map(|(_, para_block_number, para_block_hash)| (para_block_number, para_block_hash)),
+				),
+				ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash) => {
+					self.pending.remove(candidate_hash);
+				},
+				_ => {},
+			}
+		}
+		self.pending
+			.retain(|_, (backed_at, _, _)| progress.block_number.saturating_sub(*backed_at) <= MAX_PENDING_BLOCKS);
+		blocks
+	}
+}
+
+/// Fetches the events of the included parachain blocks off the parachain task
+struct EventFetcher {
+	chain: SystemParachain,
+	para_id: u32,
+	/// RPC node of the system parachain
+	node: String,
+	executor: RequestExecutor,
+}
+
+impl EventFetcher {
+	async fn run(
+		mut self,
+		mut included: UnboundedReceiver<(Option<BlockNumber>, H256)>,
+		activities: UnboundedSender<CandidateActivity>,
+	) {
+		while let Some((para_block_number, para_block_hash)) = included.recv().await {
+			match self.count(para_block_number, para_block_hash).await {
+				Ok(activity) =>
+					if activities.send(activity).is_err() {
+						return
+					},
+				Err(e) => warn!("Cannot count activity of a block of parachain {}: {:?}", self.para_id, e),
+			}
+		}
+	}
+
+	/// Counts the activity of a parachain block by the events fetched from the node
+	async fn count(
+		&mut self,
+		para_block_number: Option<BlockNumber>,
+		para_block_hash: H256,
+	) -> color_eyre::Result<CandidateActivity> {
+		let events = self
+			.executor
+			.get_events(&self.node, para_block_hash)
+			.await?
+			.ok_or_else(|| eyre!("No events of {} block {:?}", self.chain, para_block_hash))?;
+		let events = events
+			.iter()
+			.map(|event| {
+				event.map(|event| ParaEvent {
+					pallet: event.pallet_name().to_string(),
+					variant: event.variant_name().to_string(),
+					extrinsic_index: match event.phase() {
+						Phase::ApplyExtrinsic(index) => Some(index),
+						_ => None,
+					},
+				})
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(CandidateActivity {
+			chain: self.chain,
+			para_block_number,
+			para_block_hash,
+			counts: self.chain.count(&events),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::{create_backed_candidate, create_progress};
+
+	#[test]
+	fn test_counts_included_candidates() {
+		let (included, _) = unbounded_channel();
+		let (_, activities) = unbounded_channel();
+		let mut counter = ActivityCounter { pending: Default::default(), included, activities };
+		let backed = create_backed_candidate(1000);
+		let mut timed_out = create_backed_candidate(1000);
+		timed_out.candidate.commitments.processed_downward_messages = 1;
+		let mut forked = create_backed_candidate(1000);
+		forked.candidate.commitments.processed_downward_messages = 2;
+
+		assert!(counter
+			.included_blocks(&create_progress(10, vec![]), &[backed.clone(), timed_out.clone()])
+			.is_empty());
+		assert!(counter.included_blocks(&create_progress(11, vec![]), &[forked]).is_empty());
+		assert_eq!(counter.pending.len(), 3);
+
+		let blocks = counter.included_blocks(
+			&create_progress(
+				12,
+				vec![
+					ParachainConsensusEvent::Included(candidate_hash(&backed), 200, 200),
+					ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash(&timed_out)),
+				],
+			),
+			&[],
+		);
+		let head_data = &backed.candidate.commitments.head_data.0;
+		assert_eq!(blocks, vec![(None, BlakeTwo256::hash(head_data))]);
+		assert_eq!(counter.pending.len(), 1);
+
+		// The candidate backed on a fork is never included
+		assert!(counter
+			.included_blocks(&create_progress(11 + MAX_PENDING_BLOCKS + 1, vec![]), &[])
+			.is_empty());
+		assert!(counter.pending.is_empty());
+	}
+
+	fn event(pallet: &str, variant: &str, extrinsic_index: Option<u32>) -> ParaEvent {
+		ParaEvent { pallet: pallet.to_string(), variant: variant.to_string(), extrinsic_index }
+	}
+
+	#[test]
+	fn test_recognizes_system_parachains() {
+		assert_eq!(SystemParachain::from_spec_name("asset-hub-kusama"), Some(SystemParachain::AssetHub));
+		assert_eq!(SystemParachain::from_spec_name("statemint"), Some(SystemParachain::AssetHub));
+		assert_eq!(SystemParachain::from_spec_name("bridge-hub-polkadot"), Some(SystemParachain::BridgeHub));
+		assert_eq!(SystemParachain::from_spec_name("moonbeam"), None);
+	}
+
+	#[test]
+	fn test_counts_bridge_messages() {
+		let events = vec![
+			event("BridgePolkadotMessages", "MessageAccepted", Some(2)),
+			event("BridgePolkadotMessages", "MessageAccepted", Some(2)),
+			event("BridgeKusamaMessages", "MessagesReceived", Some(3)),
+			event("BridgeKusamaGrandpa", "UpdatedBestFinalizedHeader", Some(3)),
+			event("XcmpQueue", "XcmpMessageSent", Some(2)),
+		];
+		assert_eq!(SystemParachain::BridgeHub.count(&events), vec![2, 1, 0]);
+	}
+
+	#[test]
+	fn test_counts_asset_transactions() {
+		let events = vec![
+			event("Assets", "Transferred", Some(1)),
+			event("Assets", "Burned", Some(1)),
+			event("ForeignAssets", "Issued", Some(2)),
+			event("Balances", "Transfer", Some(3)),
+			event("Assets", "Issued", None),
+		];
+		let activity = CandidateActivity {
+			chain: SystemParachain::AssetHub,
+			para_block_number: Some(42),
+			para_block_hash: H256::zero(),
+			counts: SystemParachain::AssetHub.count(&events),
+		};
+		assert_eq!(
+			activity.counters().collect::<Vec<_>>(),
+			vec![("asset_transactions", 2), ("asset_transfers", 1), ("assets_issued", 2), ("assets_burned", 1)]
+		);
+		assert_eq!(
+			activity.to_string(),
+			"AssetHub block #42, asset transactions: 2, asset transfers: 1, assets issued: 2, assets burned: 1"
+		);
+	}
+}
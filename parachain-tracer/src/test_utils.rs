@@ -13,7 +13,10 @@
 
 #![cfg(test)]
 
-use crate::parachain_block_info::ParachainBlockInfo;
+use crate::{
+	parachain_block_info::ParachainBlockInfo,
+	types::{ParachainConsensusEvent, ParachainProgressUpdate},
+};
 use parity_scale_codec::Encode;
 use polkadot_introspector_essentials::{
 	api::{storage::RequestExecutor, subxt_wrapper::SubxtHrmpChannel, ApiService},
@@ -39,7 +42,7 @@ use polkadot_introspector_essentials::{
 		},
 	},
 	storage::{RecordTime, RecordsStorageConfig, StorageEntry},
-	types::H256,
+	types::{BlockNumber, Timestamp, H256},
 };
 use std::{collections::BTreeMap, time::Duration};
 use subxt::utils::bits::DecodedBits;
@@ -82,6 +85,17 @@ pub fn create_backed_candidate(para_id: u32) -> BackedCandidate<H256> {
 	}
 }
 
+pub fn create_para_head(number: u32) -> HeadData {
+	let header = polkadot_introspector_essentials::types::Header {
+		parent_hash: H256::random(),
+		number,
+		state_root: Default::default(),
+		extrinsics_root: Default::default(),
+		digest: Default::default(),
+	};
+	HeadData(header.encode())
+}
+
 pub fn create_dispute_statement_set() -> DisputeStatementSet {
 	DisputeStatementSet {
 		candidate_hash: CandidateHash(H256::random()),
@@ -128,11 +142,16 @@ pub fn create_inherent_data(para_id: u32) -> InherentData<Header<u32, BlakeTwo25
 }
 
 pub fn create_api() -> ApiService<H256> {
-	ApiService::new_with_storage(RecordsStorageConfig { max_blocks: 4 }, Default::default())
+	ApiService::new_with_storage(RecordsStorageConfig { max_blocks: 4 }, Default::default(), Default::default())
 }
 
 pub fn create_storage() -> RequestExecutor<H256, CollectorPrefixType> {
-	ApiService::new_with_prefixed_storage(RecordsStorageConfig { max_blocks: 4 }, Default::default()).storage()
+	ApiService::new_with_prefixed_storage(
+		RecordsStorageConfig { max_blocks: 4 },
+		Default::default(),
+		Default::default(),
+	)
+	.storage()
 }
 
 pub fn create_hrmp_channels() -> BTreeMap<u32, SubxtHrmpChannel> {
@@ -156,14 +175,34 @@ pub fn create_candidate_record(
 			included: None,
 			timedout: None,
 			core_idx: None,
+			group_idx: None,
 			relay_parent,
 			relay_parent_number,
 		},
 		candidate_first_seen: Duration::from_secs(0),
 		candidate_disputed: None,
+		validity_votes: vec![],
 	}
 }
 
+pub fn create_progress(block_number: BlockNumber, events: Vec<ParachainConsensusEvent>) -> ParachainProgressUpdate {
+	ParachainProgressUpdate {
+		para_id: 100,
+		block_number,
+		timestamp: block_number as Timestamp * 6000,
+		events,
+		..Default::default()
+	}
+}
+
+pub fn create_progress_with_finality_lag(
+	block_number: BlockNumber,
+	finality_lag: Option<u32>,
+	events: Vec<ParachainConsensusEvent>,
+) -> ParachainProgressUpdate {
+	ParachainProgressUpdate { finality_lag, ..create_progress(block_number, events) }
+}
+
 pub fn create_para_block_info() -> ParachainBlockInfo {
 	let mut info = ParachainBlockInfo::default();
 	info.set_candidate(BackedCandidate {
@@ -16,27 +16,46 @@
 
 //! This module tracks parachain blocks.
 use crate::{
+	baseline::{BaselineOptions, ParaBaselines},
+	explorer::Explorer,
 	message_queues_tracker::MessageQueuesTracker,
+	para_names::ParaNames,
 	parachain_block_info::ParachainBlockInfo,
 	prometheus::PrometheusMetrics,
+	reorgs::ReorgDetector,
 	stats::Stats,
 	tracker_rpc::TrackerRpc,
 	tracker_storage::TrackerStorage,
-	types::{Block, BlockWithoutHash, DisputesTracker, ForkTracker, ParachainConsensusEvent, ParachainProgressUpdate},
-	utils::{backed_candidate, extract_availability_bits_count, extract_inherent_fields, time_diff},
+	types::{
+		Block, BlockWithoutHash, CoreProgress, DisputesTracker, ForkTracker, InclusionMismatch, Latency,
+		ParachainConsensusEvent, ParachainProgressUpdate, SkippedSlotReason, XcmThroughput,
+	},
+	utils::{
+		backed_candidate, backing_quorum, candidate_hash, extract_availability_bits_count, extract_backing_votes,
+		extract_inherent_fields, extract_xcm_throughput, time_diff, BackingQuorum,
+	},
 };
-use log::{error, info};
+use log::{error, info, warn};
+use parity_scale_codec::Decode;
 use polkadot_introspector_essentials::{
+	api::subxt_wrapper::SubxtHrmpChannel,
 	collector::DisputeInfo,
+	constants::MINIMUM_BACKING_VOTES,
 	metadata::polkadot_primitives::{AvailabilityBitfield, BackedCandidate, DisputeStatementSet, ValidatorIndex},
-	types::{BlockNumber, CoreOccupied, OnDemandOrder, Timestamp, H256},
+	types::{BlockNumber, CoreOccupied, Header, OnDemandOrder, Timestamp, H256},
 };
-use std::{default::Default, time::Duration};
+use std::{collections::BTreeMap, default::Default, time::Duration};
+
+/// Number of recent relay chain blocks to keep timestamps for, enough to cover the backing and availability periods.
+const MAX_RECENT_RELAY_BLOCKS: usize = 64;
 
 /// A subxt based parachain candidate tracker.
+#[derive(Clone)]
 pub struct SubxtTracker {
 	/// Parachain ID to track.
 	para_id: u32,
+	/// Relay parents at least this number of blocks old are flagged, not flagged if unknown.
+	max_relay_parent_age: Option<u32>,
 
 	/// A new session index.
 	new_session: Option<u32>,
@@ -49,12 +68,18 @@ pub struct SubxtTracker {
 
 	/// A timestamp of a last relay chain block which is not a fork.
 	last_non_fork_relay_block_ts: Option<Timestamp>,
+	/// Timestamps of recent relay chain blocks by their numbers.
+	recent_relay_block_ts: BTreeMap<BlockNumber, Timestamp>,
 	/// The relay chain block number at which the last candidate was backed.
 	last_backed_at_block_number: Option<BlockNumber>,
 	/// The relay chain block at which last candidate was included.
 	last_included_at: Option<BlockWithoutHash>,
 	/// The relay chain block at which previous candidate was included.
 	previous_included_at: Option<BlockWithoutHash>,
+	/// The relay chain blocks at which the parachain was scheduled on its free availability cores, by core index.
+	scheduled_at: BTreeMap<u32, BlockWithoutHash>,
+	/// Core dispatch delays in blocks and time of the candidates backed in current relay block.
+	core_dispatch: Vec<(u32, Option<Duration>)>,
 
 	/// Last observed finality lag.
 	finality_lag: Option<u32>,
@@ -65,6 +90,8 @@ pub struct SubxtTracker {
 	on_demand_order_at: Option<BlockWithoutHash>,
 	/// On-demand parachain was scheduled in current relay block.
 	is_on_demand_scheduled_in_current_block: bool,
+	/// Availability core of the parachain was freed in current relay block.
+	is_core_freed_in_current_block: bool,
 
 	/// Disputes information.
 	disputes: Vec<DisputesTracker>,
@@ -72,12 +99,234 @@ pub struct SubxtTracker {
 	message_queues: MessageQueuesTracker,
 	/// Current forks
 	relay_forks: Vec<ForkTracker>,
+	/// Results of cross-checking the candidate state against the runtime events in current relay block.
+	inclusion_checks: Vec<(H256, Option<InclusionMismatch>)>,
+	/// Candidate backed in current relay block and the age of its relay parent in relay chain blocks.
+	relay_parent_age: Option<(H256, u32)>,
+	/// Messages of the parachain in the candidates backed in current relay block.
+	xcm_throughput: XcmThroughput,
+	/// Learned baselines, replace the fixed thresholds of the availability and bitfield checks after the warm-up.
+	baselines: Option<ParaBaselines>,
+	/// Availability of the pending candidate checked against the baseline in current relay block.
+	is_slow_availability_by_baseline: Option<bool>,
+	/// Bitfields checked against the baseline in current relay block.
+	is_bitfield_propagation_slow_by_baseline: Option<bool>,
+	/// Number of the para block in the head data of the last candidate backed on chain, until it's included.
+	last_backed_para_block: Option<BlockNumber>,
+	/// Depth of the unincluded segment seen on chain in current relay block, not measured on forks.
+	unincluded_depth: Option<u32>,
+	/// Candidates of the parachain by availability core, with elastic scaling several cores are occupied at once.
+	core_candidates: BTreeMap<u32, ParachainBlockInfo>,
+	/// Number of candidates of the parachain included on chain in current relay block, not measured on forks.
+	included_count: Option<u32>,
+	/// Candidates of the parachain backed in current relay block.
+	backed_candidates: Vec<BackedCandidate<H256>>,
+	/// Resolves the forks of the relay chain.
+	reorg_detector: ReorgDetector,
+	/// State before the first block of the forked relay chain heights, the chosen fork is replayed on it once
+	/// the forks are resolved.
+	fork_checkpoint: Option<Box<SubxtTracker>>,
+	/// RPC results of the blocks of the forked relay chain heights, the chosen fork is replayed with them.
+	fork_rpc_results: BTreeMap<H256, BlockRpcResults>,
+	/// Updates of the statistics and metrics by the replayed blocks of the chosen fork.
+	replayed_updates: Vec<BlockUpdate>,
+	/// Progress of the replayed blocks of the chosen fork.
+	replayed_progress: Vec<ParachainProgressUpdate>,
+	/// Links the progress with alerts to the block explorer.
+	explorer: Explorer,
+	/// Names of the parachains shown in the progress.
+	para_names: ParaNames,
+	/// Minimum number of backing votes of a candidate, from the host configuration.
+	minimum_backing_votes: u32,
+}
+
+/// Results of the RPC calls made for a relay chain block
+#[derive(Clone, Debug, Default)]
+struct BlockRpcResults {
+	inbound_hrmp_channels: BTreeMap<u32, SubxtHrmpChannel>,
+	outbound_hrmp_channels: BTreeMap<u32, SubxtHrmpChannel>,
+	/// Lengths of the downward and upward message queues, if they could be read
+	queue_lens: Option<(u32, u32)>,
+	/// Block number in the para head, `None` if it couldn't be read
+	para_head_number: Option<Option<BlockNumber>>,
+}
+
+impl BlockRpcResults {
+	async fn fetch(para_id: u32, block_hash: H256, rpc: &mut impl TrackerRpc) -> color_eyre::Result<Self> {
+		let inbound_hrmp_channels = rpc.inbound_hrmp_channels(block_hash).await?;
+		let outbound_hrmp_channels = rpc.outbound_hrmp_channels(block_hash).await?;
+		// Queue lengths are informational, the block is processed without them
+		let queue_lens = match (rpc.downward_queue_len(block_hash).await, rpc.upward_queue_len(block_hash).await) {
+			(Ok(downward), Ok(upward)) => Some((downward, upward)),
+			(Err(e), _) | (_, Err(e)) => {
+				warn!("Cannot read the message queues at {:?}: {:?}", block_hash, e);
+				None
+			},
+		};
+		let para_head_number = match rpc.para_head_number(block_hash).await {
+			Ok(number) => Some(number),
+			Err(e) => {
+				warn!("Failed to get the head of parachain {} at {:?}: {:?}", para_id, block_hash, e);
+				None
+			},
+		};
+
+		Ok(Self { inbound_hrmp_channels, outbound_hrmp_channels, queue_lens, para_head_number })
+	}
+}
+
+/// An update of the statistics and metrics by a relay chain block
+#[derive(Clone, Debug)]
+enum BlockUpdate {
+	Bitfields {
+		count: u32,
+		is_low: bool,
+	},
+	SkippedSlot {
+		block_number: BlockNumber,
+		block_hash: H256,
+		reason: SkippedSlotReason,
+	},
+	Backed {
+		votes: u32,
+		group_size: u32,
+		minimum_backing_votes: u32,
+	},
+	CoreDispatch {
+		delay: u32,
+		delay_sec: Option<Duration>,
+	},
+	Included {
+		relay_parent_number: BlockNumber,
+		previous_included: Option<BlockNumber>,
+		backed_in: Option<u32>,
+		para_block_time_sec: Option<Duration>,
+		backed_in_sec: Option<Duration>,
+	},
+	AvailabilityTime(u32),
+	SlowAvailability,
+	AvailabilityTimeout {
+		block_number: BlockNumber,
+		candidate_hash: H256,
+	},
+	Disputed(DisputesTracker),
+	XcmThroughput(XcmThroughput),
+	Block(Duration),
+	FinalityLag(u32),
+	OnDemandOrder(OnDemandOrder),
+	OnDemandDelay {
+		delay: u32,
+		state: &'static str,
+	},
+	OnDemandDelaySec {
+		delay: Duration,
+		state: &'static str,
+	},
+	InclusionCheck(Option<InclusionMismatch>),
+	RelayParentAge {
+		age: u32,
+		is_old: bool,
+	},
+	UnincludedDepth(u32),
+	CoreCandidates {
+		included_count: u32,
+		cores_in_use: u32,
+	},
+}
+
+impl BlockUpdate {
+	fn apply(self, para_id: u32, stats: &mut impl Stats, metrics: &impl PrometheusMetrics) {
+		match self {
+			BlockUpdate::Bitfields { count, is_low } => {
+				stats.on_bitfields(count, is_low);
+				metrics.on_bitfields(count, is_low, para_id);
+			},
+			BlockUpdate::SkippedSlot { block_number, block_hash, reason } => {
+				let update = ParachainProgressUpdate {
+					block_number,
+					block_hash,
+					para_id,
+					events: vec![ParachainConsensusEvent::SkippedSlot(reason)],
+					..Default::default()
+				};
+				stats.on_skipped_slot(&update);
+				metrics.on_skipped_slot(&update);
+			},
+			BlockUpdate::Backed { votes, group_size, minimum_backing_votes } => {
+				stats.on_backed(votes, group_size, minimum_backing_votes);
+				metrics.on_backed(votes, group_size, para_id);
+			},
+			BlockUpdate::CoreDispatch { delay, delay_sec } => {
+				stats.on_core_dispatch(delay);
+				metrics.on_core_dispatch(delay, delay_sec, para_id);
+			},
+			BlockUpdate::Included {
+				relay_parent_number,
+				previous_included,
+				backed_in,
+				para_block_time_sec,
+				backed_in_sec,
+			} => {
+				stats.on_included(
+					relay_parent_number,
+					previous_included,
+					backed_in,
+					para_block_time_sec,
+					backed_in_sec,
+				);
+				metrics.on_included(
+					relay_parent_number,
+					previous_included,
+					backed_in,
+					para_block_time_sec,
+					backed_in_sec,
+					para_id,
+				);
+			},
+			BlockUpdate::AvailabilityTime(blocks) => metrics.on_availability_time(blocks, para_id),
+			BlockUpdate::SlowAvailability => {
+				stats.on_slow_availability();
+				metrics.on_slow_availability(para_id);
+			},
+			BlockUpdate::AvailabilityTimeout { block_number, candidate_hash } => {
+				stats.on_availability_timeout(block_number, candidate_hash);
+				metrics.on_availability_timeout(para_id);
+			},
+			BlockUpdate::Disputed(outcome) => {
+				stats.on_disputed(&outcome);
+				metrics.on_disputed(&outcome, para_id);
+			},
+			BlockUpdate::XcmThroughput(xcm) => metrics.on_xcm_throughput(&xcm, para_id),
+			BlockUpdate::Block(time) => {
+				stats.on_block(time);
+				metrics.on_block(time.as_secs_f64(), para_id);
+			},
+			BlockUpdate::FinalityLag(lag) => metrics.on_finality_lag(lag),
+			BlockUpdate::OnDemandOrder(order) => metrics.handle_on_demand_order(&order),
+			BlockUpdate::OnDemandDelay { delay, state } => metrics.handle_on_demand_delay(delay, para_id, state),
+			BlockUpdate::OnDemandDelaySec { delay, state } => metrics.handle_on_demand_delay_sec(delay, para_id, state),
+			BlockUpdate::InclusionCheck(mismatch) => metrics.on_inclusion_check(mismatch, para_id),
+			BlockUpdate::RelayParentAge { age, is_old } => {
+				stats.on_relay_parent_age(age, is_old);
+				metrics.on_relay_parent_age(age, is_old, para_id);
+			},
+			BlockUpdate::UnincludedDepth(depth) => {
+				stats.on_unincluded_depth(depth);
+				metrics.on_unincluded_depth(depth, para_id);
+			},
+			BlockUpdate::CoreCandidates { included_count, cores_in_use } => {
+				stats.on_included_candidates(included_count);
+				metrics.on_core_candidates(included_count, cores_in_use, para_id);
+			},
+		}
+	}
 }
 
 impl SubxtTracker {
-	pub fn new(para_id: u32) -> Self {
+	pub fn new(para_id: u32, max_relay_parent_age: Option<u32>, baselines: Option<BaselineOptions>) -> Self {
 		Self {
 			para_id,
+			max_relay_parent_age,
 			current_candidate: Default::default(),
 			new_session: None,
 			current_relay_block: None,
@@ -85,24 +334,141 @@ impl SubxtTracker {
 			on_demand_order: None,
 			on_demand_order_at: None,
 			is_on_demand_scheduled_in_current_block: false,
+			is_core_freed_in_current_block: false,
 			finality_lag: None,
 			disputes: Vec::new(),
 			last_backed_at_block_number: None,
 			last_non_fork_relay_block_ts: None,
+			recent_relay_block_ts: BTreeMap::new(),
 			last_included_at: None,
 			previous_included_at: None,
+			scheduled_at: Default::default(),
+			core_dispatch: Default::default(),
 			message_queues: Default::default(),
 			relay_forks: vec![],
+			inclusion_checks: vec![],
+			relay_parent_age: None,
+			xcm_throughput: Default::default(),
+			baselines: baselines.map(ParaBaselines::new),
+			is_slow_availability_by_baseline: None,
+			is_bitfield_propagation_slow_by_baseline: None,
+			last_backed_para_block: None,
+			unincluded_depth: None,
+			core_candidates: BTreeMap::new(),
+			included_count: None,
+			backed_candidates: vec![],
+			reorg_detector: Default::default(),
+			fork_checkpoint: None,
+			fork_rpc_results: BTreeMap::new(),
+			replayed_updates: vec![],
+			replayed_progress: vec![],
+			explorer: Default::default(),
+			para_names: Default::default(),
+			minimum_backing_votes: MINIMUM_BACKING_VOTES,
 		}
 	}
 
+	/// Links the progress with alerts to the block explorer
+	pub fn with_explorer(mut self, explorer: Explorer) -> Self {
+		self.explorer = explorer;
+		self
+	}
+
+	/// Shows the names of the parachains in the progress
+	pub fn with_para_names(mut self, para_names: ParaNames) -> Self {
+		self.para_names = para_names;
+		self
+	}
+
+	/// Sets the minimum number of backing votes read from the host configuration
+	pub fn with_minimum_backing_votes(mut self, minimum_backing_votes: u32) -> Self {
+		self.minimum_backing_votes = minimum_backing_votes;
+		self
+	}
+
 	/// Saves new session to tracker's state
 	pub fn inject_new_session(&mut self, session_index: u32) {
 		self.new_session = Some(session_index)
 	}
 
-	/// Injects a new relay chain block into the tracker. Blocks must be injected in order.
+	/// Injects a new relay chain block into the tracker. Blocks must be injected in order, `is_forked` tells if
+	/// there are other blocks at the same height.
+	///
+	/// Blocks of the forked heights don't update the statistics and metrics. Once the forks are resolved,
+	/// the state is restored to the last resolved height and the chosen fork is replayed with the RPC results
+	/// recorded when its blocks were injected, its progress is taken with `take_replayed_progress`.
 	pub async fn inject_block(
+		&mut self,
+		block_hash: H256,
+		block_number: BlockNumber,
+		is_forked: bool,
+		rpc: &mut impl TrackerRpc,
+		storage: &TrackerStorage,
+	) -> color_eyre::Result<()> {
+		let parent_hash = storage.parent_hash(block_hash).await;
+		let resolution = self.reorg_detector.on_block(block_hash, block_number, parent_hash, is_forked);
+		if let (Some(resolution), Some(checkpoint)) = (resolution, self.fork_checkpoint.take()) {
+			// Candidate state derived from the abandoned forks is dropped by replaying the chosen one
+			self.restore(*checkpoint);
+			for (number, hash) in resolution.chosen {
+				self.apply_block(hash, number, rpc, storage).await?;
+				if let Some((mut progress, updates)) = self.block_progress(storage).await {
+					progress.is_replayed = true;
+					self.replayed_progress.push(progress);
+					self.replayed_updates.extend(updates);
+				}
+				self.maybe_reset_state();
+			}
+			self.fork_rpc_results.clear();
+		}
+		if self.reorg_detector.is_forked() && self.fork_checkpoint.is_none() {
+			self.fork_checkpoint = Some(self.checkpoint());
+		}
+
+		self.apply_block(block_hash, block_number, rpc, storage).await
+	}
+
+	/// Saves the whole state of the tracker
+	fn checkpoint(&self) -> Box<SubxtTracker> {
+		Box::new(Self {
+			fork_rpc_results: BTreeMap::new(),
+			replayed_updates: vec![],
+			replayed_progress: vec![],
+			..self.clone()
+		})
+	}
+
+	/// Restores the whole state of the tracker, except the resolution of the forks in progress
+	fn restore(&mut self, checkpoint: SubxtTracker) {
+		*self = Self {
+			reorg_detector: std::mem::take(&mut self.reorg_detector),
+			fork_checkpoint: None,
+			fork_rpc_results: std::mem::take(&mut self.fork_rpc_results),
+			replayed_updates: std::mem::take(&mut self.replayed_updates),
+			replayed_progress: std::mem::take(&mut self.replayed_progress),
+			..checkpoint
+		};
+	}
+
+	/// Returns the RPC results of a relay chain block, they are recorded while the relay chain is forked so the
+	/// chosen fork is replayed without calling the RPC again
+	async fn rpc_results(
+		&mut self,
+		block_hash: H256,
+		rpc: &mut impl TrackerRpc,
+	) -> color_eyre::Result<BlockRpcResults> {
+		if let Some(results) = self.fork_rpc_results.get(&block_hash) {
+			return Ok(results.clone())
+		}
+		let results = BlockRpcResults::fetch(self.para_id, block_hash, rpc).await?;
+		if self.reorg_detector.is_forked() {
+			self.fork_rpc_results.insert(block_hash, results.clone());
+		}
+
+		Ok(results)
+	}
+
+	async fn apply_block(
 		&mut self,
 		block_hash: H256,
 		block_number: BlockNumber,
@@ -110,22 +476,38 @@ impl SubxtTracker {
 		storage: &TrackerStorage,
 	) -> color_eyre::Result<()> {
 		if let Some(inherent) = storage.inherent_data(block_hash).await {
-			let (bitfields, backed_candidates, disputes) = extract_inherent_fields(inherent);
+			let (bitfields, backed_candidates, disputes) = extract_inherent_fields(&inherent);
+			let rpc_results = self.rpc_results(block_hash, rpc).await?;
 
 			self.set_relay_block(block_hash, block_number, storage).await?;
 			self.set_forks(block_hash, block_number);
 
+			self.xcm_throughput = extract_xcm_throughput(&backed_candidates, self.para_id);
+			let para_candidates: Vec<_> = backed_candidates
+				.iter()
+				.filter(|candidate| candidate.candidate.descriptor.para_id.0 == self.para_id)
+				.cloned()
+				.collect();
 			self.set_current_candidate(backed_candidates, bitfields.len(), block_number);
+			// The scheduling of this block is recorded before the candidates backed in it are dispatched
 			self.set_core_assignment(block_hash, storage).await?;
-			self.set_disputes(&disputes[..], storage).await;
+			self.backed_candidates = para_candidates.clone();
+			self.set_core_candidates(block_hash, para_candidates, &bitfields, storage).await;
+			self.set_core_dispatch();
+			self.set_disputes(disputes, storage).await;
 
-			self.set_hrmp_channels(block_hash, rpc).await?;
+			self.set_message_queues(&rpc_results);
 			self.set_on_demand_order(block_hash, storage).await;
 
 			// If a candidate was backed in this relay block, we don't need to process availability now.
 			if self.has_backed_candidate() && !self.is_just_backed() {
-				self.set_availability(block_hash, bitfields, storage).await?;
+				self.set_availability(block_hash, &bitfields, storage).await?;
+				self.set_availability_timeout(storage).await;
 			}
+			self.set_inclusion_checks(storage).await;
+			self.set_relay_parent_age(storage).await;
+			self.set_unincluded_depth(&rpc_results);
+			self.set_baselines();
 		} else {
 			error!("Failed to get inherent data for {:?}", block_hash);
 		}
@@ -133,13 +515,35 @@ impl SubxtTracker {
 		Ok(())
 	}
 
-	/// Creates a parachain progress.
+	/// Creates a parachain progress, the statistics and metrics are updated unless the relay chain is forked.
 	pub async fn progress(
-		&self,
+		&mut self,
 		stats: &mut impl Stats,
 		metrics: &impl PrometheusMetrics,
 		storage: &TrackerStorage,
 	) -> Option<ParachainProgressUpdate> {
+		for update in std::mem::take(&mut self.replayed_updates) {
+			update.apply(self.para_id, stats, metrics);
+		}
+
+		let (progress, updates) = self.block_progress(storage).await?;
+		// Updated by the chosen fork when it's replayed
+		if self.fork_checkpoint.is_none() {
+			for update in updates {
+				update.apply(self.para_id, stats, metrics);
+			}
+		}
+
+		Some(progress)
+	}
+
+	/// Progress of the blocks of the chosen fork replayed after the forks were resolved
+	pub fn take_replayed_progress(&mut self) -> Vec<ParachainProgressUpdate> {
+		std::mem::take(&mut self.replayed_progress)
+	}
+
+	/// Creates a parachain progress in current relay block with the updates of the statistics and metrics
+	async fn block_progress(&self, storage: &TrackerStorage) -> Option<(ParachainProgressUpdate, Vec<BlockUpdate>)> {
 		if let Some(block) = self.current_relay_block {
 			let prev_timestamp = self.last_non_fork_relay_block_ts.unwrap_or(block.ts);
 			let mut progress = ParachainProgressUpdate {
@@ -151,43 +555,82 @@ impl SubxtTracker {
 				is_fork: self.is_fork(),
 				finality_lag: self.finality_lag,
 				core_occupied: self.current_candidate.core_occupied,
+				explorer: self.explorer.clone(),
+				para_names: self.para_names.clone(),
 				..Default::default()
 			};
 
+			let mut updates = vec![];
 			self.notify_new_session(&mut progress);
 			self.notify_core_assignment(&mut progress);
-			self.notify_bitfield_propagation(&mut progress, stats, metrics);
-			self.notify_candidate_state(&mut progress, stats, metrics, storage).await;
-			self.notify_disputes(&mut progress, stats, metrics);
+			self.notify_bitfield_propagation(&mut progress, &mut updates);
+			self.notify_candidate_state(&mut progress, &mut updates, storage).await;
+			self.notify_core_dispatch(&mut updates);
+			self.notify_disputes(&mut progress, &mut updates);
 			self.notify_active_message_queues(&mut progress);
-			self.notify_current_block_time(stats, metrics);
-			self.notify_finality_lag(metrics);
-			self.notify_on_demand_order(metrics);
-
-			Some(progress)
+			progress.message_queues = self.message_queues.queue_depths;
+			self.notify_xcm_throughput(&mut progress, &mut updates);
+			self.notify_current_block_time(&mut updates);
+			self.notify_finality_lag(&mut updates);
+			self.notify_on_demand_order(&mut updates);
+			self.notify_inclusion_checks(&mut progress, &mut updates);
+			self.notify_relay_parent_age(&mut progress, &mut updates);
+			self.notify_unincluded_depth(&mut progress, &mut updates);
+			self.notify_core_candidates(&mut progress, &mut updates);
+
+			Some((progress, updates))
 		} else {
 			None
 		}
 	}
 
+	/// HRMP channels of the parachain in current relay block
+	pub fn message_queues(&self) -> &MessageQueuesTracker {
+		&self.message_queues
+	}
+
+	/// Availability core the parachain is assigned to in current relay block
+	pub fn assigned_core(&self) -> Option<u32> {
+		self.current_candidate.assigned_core
+	}
+
+	/// Candidates of the parachain backed in current relay block
+	pub fn backed_candidates(&self) -> &[BackedCandidate<H256>] {
+		&self.backed_candidates
+	}
+
 	/// Resets state
 	pub fn maybe_reset_state(&mut self) {
 		if self.current_candidate.is_backed() {
 			self.on_demand_order_at = None;
 		}
+		self.core_dispatch.clear();
 		self.new_session = None;
 		self.on_demand_order = None;
 		self.is_on_demand_scheduled_in_current_block = false;
+		self.is_core_freed_in_current_block = false;
 		self.disputes.clear();
+		self.inclusion_checks.clear();
+		self.relay_parent_age = None;
+		self.xcm_throughput = Default::default();
+		self.backed_candidates.clear();
+		self.is_slow_availability_by_baseline = None;
+		self.is_bitfield_propagation_slow_by_baseline = None;
+		self.unincluded_depth = None;
+		self.included_count = None;
+		self.core_candidates.retain(|_, info| {
+			info.maybe_reset();
+			!info.is_idle()
+		});
 		self.current_candidate.maybe_reset();
 	}
 
-	async fn set_hrmp_channels(&mut self, block_hash: H256, rpc: &mut impl TrackerRpc) -> color_eyre::Result<()> {
-		let inbound = rpc.inbound_hrmp_channels(block_hash).await?;
-		let outbound = rpc.outbound_hrmp_channels(block_hash).await?;
-		self.message_queues.set_hrmp_channels(inbound, outbound);
-
-		Ok(())
+	fn set_message_queues(&mut self, rpc_results: &BlockRpcResults) {
+		self.message_queues
+			.set_hrmp_channels(rpc_results.inbound_hrmp_channels.clone(), rpc_results.outbound_hrmp_channels.clone());
+		if let Some((downward, upward)) = rpc_results.queue_lens {
+			self.message_queues.set_queue_lens(downward, upward);
+		}
 	}
 
 	async fn set_relay_block(
@@ -203,6 +646,10 @@ impl SubxtTracker {
 		if !self.is_fork() {
 			self.last_non_fork_relay_block_ts = Some(ts);
 		}
+		self.recent_relay_block_ts.insert(block_number, ts);
+		while self.recent_relay_block_ts.len() > MAX_RECENT_RELAY_BLOCKS {
+			self.recent_relay_block_ts.pop_first();
+		}
 
 		self.finality_lag = storage
 			.relevant_finalized_block_number(block_hash)
@@ -233,14 +680,14 @@ impl SubxtTracker {
 
 	fn set_current_candidate(
 		&mut self,
-		backed_candidates: Vec<BackedCandidate<H256>>,
+		backed_candidates: &[BackedCandidate<H256>],
 		bitfields_count: usize,
 		block_number: BlockNumber,
 	) {
 		self.current_candidate.bitfield_count = bitfields_count as u32;
 		if let Some(candidate) = backed_candidate(backed_candidates, self.para_id) {
 			self.current_candidate.set_backed();
-			self.current_candidate.set_candidate(candidate);
+			self.current_candidate.set_candidate(candidate.clone());
 			self.last_backed_at_block_number = Some(block_number);
 
 			if let Some(current_fork) = self.relay_forks.last_mut() {
@@ -253,18 +700,52 @@ impl SubxtTracker {
 
 	async fn set_core_assignment(&mut self, block_hash: H256, storage: &TrackerStorage) -> color_eyre::Result<()> {
 		let assignments = storage.core_assignments(block_hash).await.expect("saved in the collector");
+		self.current_candidate.assigned_cores = assignments
+			.iter()
+			.filter(|(_, ids)| ids.contains(&self.para_id))
+			.map(|(core, _)| *core)
+			.collect();
 		if let Some((&core, scheduled_ids)) = assignments.iter().find(|(_, ids)| ids.contains(&self.para_id)) {
+			let occupied_cores = storage.occupied_cores(block_hash).await.expect("saved in the collector");
+			let is_occupied = |core: u32| matches!(occupied_cores.get(core as usize), Some(CoreOccupied::Paras));
+			let was_core_occupied = self.current_candidate.core_occupied;
 			self.current_candidate.assigned_core = Some(core);
-			self.current_candidate.core_occupied = matches!(
-				storage.occupied_cores(block_hash).await.expect("saved in the collector")[core as usize],
-				CoreOccupied::Paras
-			);
+			self.current_candidate.core_occupied = is_occupied(core);
+			self.is_core_freed_in_current_block = was_core_occupied && !self.current_candidate.core_occupied;
 			self.is_on_demand_scheduled_in_current_block =
 				self.on_demand_order.is_some() && scheduled_ids[0] == self.para_id;
+			// The cores free after this block can take a candidate from the next block on, whatever was backed in it
+			if let Some(block) = self.current_relay_block {
+				for &core in self.current_candidate.assigned_cores.iter().filter(|&&core| !is_occupied(core)) {
+					self.scheduled_at.entry(core).or_insert(block.into());
+				}
+			}
 		}
 		Ok(())
 	}
 
+	/// Measures the core dispatch delays of the candidates backed in current relay block on the cores the parachain
+	/// was scheduled on, needs the scheduling and the candidates of the block set
+	fn set_core_dispatch(&mut self) {
+		self.core_dispatch.clear();
+		let relay_block = match self.current_relay_block {
+			Some(block) if !self.is_fork() => block,
+			_ => return,
+		};
+
+		for (core, _) in self.core_candidates.iter().filter(|(_, info)| info.is_backed()) {
+			if let Some(scheduled) = self.scheduled_at.remove(core) {
+				self.core_dispatch.push((
+					relay_block.num.saturating_sub(scheduled.num),
+					time_diff(Some(relay_block.ts), Some(scheduled.ts)),
+				));
+			}
+		}
+		// The parachain is not scheduled anymore on the cores taken away from it
+		let assigned_cores = &self.current_candidate.assigned_cores;
+		self.scheduled_at.retain(|core, _| assigned_cores.contains(core));
+	}
+
 	async fn set_disputes(&mut self, disputes: &[DisputeStatementSet], storage: &TrackerStorage) {
 		self.disputes = Vec::with_capacity(disputes.len());
 		for dispute_info in disputes {
@@ -295,10 +776,10 @@ impl SubxtTracker {
 	async fn set_availability(
 		&mut self,
 		block_hash: H256,
-		bitfields: Vec<AvailabilityBitfield>,
+		bitfields: &[&AvailabilityBitfield],
 		storage: &TrackerStorage,
 	) -> color_eyre::Result<()> {
-		if self.current_candidate.is_backed() {
+		if self.current_candidate.is_backed() || self.current_candidate.is_pending() {
 			// We only process availability if our parachain is assigned to an availability core.
 			if let Some(core) = self.current_candidate.assigned_core {
 				self.current_candidate.current_availability_bits = extract_availability_bits_count(bitfields, core);
@@ -320,19 +801,278 @@ impl SubxtTracker {
 		Ok(())
 	}
 
-	fn notify_disputes(
-		&self,
-		progress: &mut ParachainProgressUpdate,
-		stats: &mut impl Stats,
-		metrics: &impl PrometheusMetrics,
+	/// Marks a pending candidate as timed out if its availability core was freed without inclusion
+	async fn set_availability_timeout(&mut self, storage: &TrackerStorage) {
+		if !self.current_candidate.is_pending() {
+			return
+		}
+
+		let is_timed_out_on_chain = match self.current_candidate.candidate_hash {
+			Some(candidate_hash) => storage
+				.candidate(candidate_hash)
+				.await
+				.map_or(false, |v| v.candidate_inclusion.timedout.is_some()),
+			None => false,
+		};
+		if self.is_core_freed_in_current_block || is_timed_out_on_chain {
+			self.current_candidate.set_timed_out();
+		}
+	}
+
+	/// Measures the unincluded segment visible to the relay chain, the para blocks of candidates backed on chain
+	/// on top of the para head of the relay block
+	fn set_unincluded_depth(&mut self, rpc_results: &BlockRpcResults) {
+		self.unincluded_depth = None;
+		if self.is_fork() {
+			return
+		}
+
+		// Candidates built on top of a timed out one are dropped with it
+		if self.current_candidate.is_timed_out() {
+			self.last_backed_para_block = None;
+		}
+		let backed_para_block = self
+			.backed_candidates
+			.iter()
+			.filter_map(|candidate| Header::decode(&mut &candidate.candidate.commitments.head_data.0[..]).ok())
+			.map(|header| header.number)
+			.max();
+		if backed_para_block.is_some() {
+			self.last_backed_para_block = backed_para_block;
+		}
+
+		let Some(head_para_block) = rpc_results.para_head_number else { return };
+		let depth = match (self.last_backed_para_block, head_para_block) {
+			(Some(backed), Some(head)) => backed.saturating_sub(head),
+			_ => 0,
+		};
+		if depth == 0 {
+			self.last_backed_para_block = None;
+		}
+		self.unincluded_depth = Some(depth);
+	}
+
+	/// Follows candidates of the parachain on each of its availability cores using the runtime events,
+	/// with elastic scaling several candidates are backed and included in the same relay block
+	async fn set_core_candidates(
+		&mut self,
+		block_hash: H256,
+		backed_candidates: Vec<BackedCandidate<H256>>,
+		bitfields: &[&AvailabilityBitfield],
+		storage: &TrackerStorage,
 	) {
+		self.included_count = None;
+		let block_number = match self.current_relay_block {
+			Some(block) if !self.is_fork() => block.num,
+			_ => return,
+		};
+
+		let max_availability_bits = storage
+			.backing_groups(block_hash)
+			.await
+			.map_or(0, |groups| groups.into_iter().flatten().count() as u32);
+		let mut included_count = 0;
+		for (core, info) in self.core_candidates.iter_mut() {
+			info.bitfield_count = bitfields.len() as u32;
+			info.max_availability_bits = max_availability_bits;
+			info.current_availability_bits = bitfields
+				.iter()
+				.filter(|bitfield| bitfield.0.as_bits().get(*core as usize).unwrap_or_default())
+				.count() as u32;
+
+			let inclusion = match info.candidate_hash {
+				Some(candidate_hash) => storage.candidate(candidate_hash).await.map(|v| v.candidate_inclusion),
+				None => None,
+			};
+			match inclusion {
+				Some(v) if v.included.is_some_and(|at| at <= block_number) => {
+					info.set_included();
+					included_count += 1;
+				},
+				Some(v) if v.timedout.is_some_and(|at| at <= block_number) => info.set_timed_out(),
+				// Candidates with missed events would be tracked forever
+				Some(v) if block_number.saturating_sub(v.backed) <= MAX_RECENT_RELAY_BLOCKS as u32 =>
+					info.set_pending(),
+				_ => info.set_idle(),
+			}
+		}
+
+		for candidate in backed_candidates {
+			let candidate_hash = candidate_hash(&candidate);
+			let core = match storage
+				.candidate(candidate_hash)
+				.await
+				.and_then(|v| v.candidate_inclusion.core_idx)
+			{
+				Some(core) => Some(core),
+				// Not known without the `CandidateBacked` event, the first free assigned core is the best guess
+				None => self
+					.current_candidate
+					.assigned_cores
+					.iter()
+					.copied()
+					.find(|core| self.core_candidates.get(core).map_or(true, |info| info.is_idle())),
+			};
+			let Some(core) = core else { continue };
+
+			let mut info = ParachainBlockInfo::default();
+			info.set_candidate(candidate);
+			info.set_backed();
+			info.assigned_core = Some(core);
+			info.core_occupied = true;
+			info.bitfield_count = bitfields.len() as u32;
+			info.max_availability_bits = max_availability_bits;
+			self.core_candidates.insert(core, info);
+		}
+
+		self.included_count = Some(included_count);
+	}
+
+	/// Learns the baselines and checks the current relay block against them
+	fn set_baselines(&mut self) {
+		let is_fork = self.is_fork();
+		let pending_blocks = match (self.current_relay_block, self.last_backed_at_block_number) {
+			(Some(relay), Some(backed_at)) => Some(relay.num.saturating_sub(backed_at)),
+			_ => None,
+		};
+		let is_just_backed = self.is_just_backed();
+		let Some(baselines) = self.baselines.as_mut() else { return };
+
+		// Forks are checked against the baselines too, but not learned so the relay block is learned once
+		let candidate = &self.current_candidate;
+		if candidate.max_availability_bits > 0 && !candidate.is_idle() {
+			self.is_bitfield_propagation_slow_by_baseline =
+				baselines.is_low_bitfields(candidate.bitfield_count, candidate.max_availability_bits);
+			if !is_fork {
+				baselines.on_bitfields(candidate.bitfield_count, candidate.max_availability_bits);
+			}
+		}
+		if let Some(pending_blocks) = pending_blocks.filter(|_| !is_just_backed) {
+			if candidate.is_included() {
+				if !is_fork {
+					baselines.on_available(pending_blocks);
+				}
+			} else if candidate.is_pending() {
+				self.is_slow_availability_by_baseline = baselines.is_slow_availability(pending_blocks);
+			}
+		}
+	}
+
+	/// Cross-checks backing and inclusion inferred from the inherent data against the runtime events
+	async fn set_inclusion_checks(&mut self, storage: &TrackerStorage) {
+		self.inclusion_checks.clear();
+		// Events of the forks at the same height are merged in the collector's storage
+		if self.is_fork() {
+			return
+		}
+		let (candidate_hash, block_number) = match (self.current_candidate.candidate_hash, self.current_relay_block) {
+			(Some(hash), Some(block)) => (hash, block.num),
+			_ => return,
+		};
+
+		let record = storage.candidate(candidate_hash).await.map(|v| v.candidate_inclusion);
+		if self.is_just_backed() {
+			let is_backed_on_chain = record.as_ref().map_or(false, |v| v.backed == block_number);
+			self.inclusion_checks
+				.push((candidate_hash, (!is_backed_on_chain).then_some(InclusionMismatch::BackedWithoutEvent)));
+		}
+
+		let is_included_on_chain = record.as_ref().map_or(false, |v| v.included == Some(block_number));
+		if self.current_candidate.is_included() {
+			self.inclusion_checks
+				.push((candidate_hash, (!is_included_on_chain).then_some(InclusionMismatch::IncludedWithoutEvent)));
+		} else if is_included_on_chain {
+			self.inclusion_checks
+				.push((candidate_hash, Some(InclusionMismatch::EventWithoutInclusion)));
+		}
+	}
+
+	/// Saves the age of the relay parent of a candidate backed in current relay block
+	async fn set_relay_parent_age(&mut self, storage: &TrackerStorage) {
+		self.relay_parent_age = None;
+		if !self.is_just_backed() {
+			return
+		}
+
+		if let (Some(candidate_hash), Some(block)) = (self.current_candidate.candidate_hash, self.current_relay_block) {
+			self.relay_parent_age = storage
+				.candidate(candidate_hash)
+				.await
+				.map(|v| (candidate_hash, block.num.saturating_sub(v.candidate_inclusion.relay_parent_number)));
+		}
+	}
+
+	fn notify_disputes(&self, progress: &mut ParachainProgressUpdate, updates: &mut Vec<BlockUpdate>) {
 		self.disputes.iter().for_each(|outcome| {
 			progress.events.push(ParachainConsensusEvent::Disputed(outcome.clone()));
-			stats.on_disputed(outcome);
-			metrics.on_disputed(outcome, self.para_id);
+			updates.push(BlockUpdate::Disputed(outcome.clone()));
 		});
 	}
 
+	fn notify_inclusion_checks(&self, progress: &mut ParachainProgressUpdate, updates: &mut Vec<BlockUpdate>) {
+		for (candidate_hash, mismatch) in &self.inclusion_checks {
+			updates.push(BlockUpdate::InclusionCheck(*mismatch));
+			if let Some(mismatch) = mismatch {
+				progress
+					.events
+					.push(ParachainConsensusEvent::InclusionMismatch(*candidate_hash, *mismatch));
+			}
+		}
+	}
+
+	fn notify_relay_parent_age(&self, progress: &mut ParachainProgressUpdate, updates: &mut Vec<BlockUpdate>) {
+		if let Some((candidate_hash, age)) = self.relay_parent_age {
+			progress.relay_parent_age = Some(age);
+			let max_age = self.max_relay_parent_age.filter(|max_age| age >= *max_age);
+			updates.push(BlockUpdate::RelayParentAge { age, is_old: max_age.is_some() });
+			if let Some(max_age) = max_age {
+				progress
+					.events
+					.push(ParachainConsensusEvent::OldRelayParent(candidate_hash, age, max_age));
+			}
+		}
+	}
+
+	fn notify_unincluded_depth(&self, progress: &mut ParachainProgressUpdate, updates: &mut Vec<BlockUpdate>) {
+		if let Some(depth) = self.unincluded_depth {
+			progress.unincluded_depth = Some(depth);
+			updates.push(BlockUpdate::UnincludedDepth(depth));
+		}
+	}
+
+	fn notify_core_dispatch(&self, updates: &mut Vec<BlockUpdate>) {
+		updates.extend(
+			self.core_dispatch
+				.iter()
+				.map(|&(delay, delay_sec)| BlockUpdate::CoreDispatch { delay, delay_sec }),
+		);
+	}
+
+	fn notify_core_candidates(&self, progress: &mut ParachainProgressUpdate, updates: &mut Vec<BlockUpdate>) {
+		let Some(included_count) = self.included_count else { return };
+		let cores_in_use = self.core_candidates.values().filter(|info| !info.is_idle()).count() as u32;
+		progress.included_count = Some(included_count);
+		updates.push(BlockUpdate::CoreCandidates { included_count, cores_in_use });
+
+		// Per core states only add noise to the output of parachains using a single core
+		if cores_in_use > 1 || self.current_candidate.assigned_cores.len() > 1 {
+			progress.cores = self
+				.core_candidates
+				.iter()
+				.filter(|(_, info)| !info.is_idle())
+				.filter_map(|(core, info)| {
+					Some(CoreProgress {
+						core_index: *core,
+						candidate_hash: info.candidate_hash?,
+						state: info.state_name(),
+						available_count: info.current_availability_bits,
+						max_availability_bits: info.max_availability_bits,
+					})
+				})
+				.collect();
+		}
+	}
+
 	fn notify_active_message_queues(&self, progress: &mut ParachainProgressUpdate) {
 		if self.message_queues.has_hrmp_messages() {
 			progress.events.push(ParachainConsensusEvent::MessageQueues(
@@ -342,38 +1082,45 @@ impl SubxtTracker {
 		}
 	}
 
-	fn notify_current_block_time(&self, stats: &mut impl Stats, metrics: &impl PrometheusMetrics) {
+	fn notify_xcm_throughput(&self, progress: &mut ParachainProgressUpdate, updates: &mut Vec<BlockUpdate>) {
+		if self.xcm_throughput.is_empty() {
+			return
+		}
+
+		progress.xcm = Some(self.xcm_throughput);
+		// Candidates of the forks at the same height carry the same messages
+		if !self.is_fork() {
+			updates.push(BlockUpdate::XcmThroughput(self.xcm_throughput));
+		}
+	}
+
+	fn notify_current_block_time(&self, updates: &mut Vec<BlockUpdate>) {
 		if !self.is_fork() {
-			let ts = self.current_block_time();
-			stats.on_block(ts);
-			metrics.on_block(ts.as_secs_f64(), self.para_id);
+			updates.push(BlockUpdate::Block(self.current_block_time()));
 		}
 	}
 
-	fn notify_finality_lag(&self, metrics: &impl PrometheusMetrics) {
+	fn notify_finality_lag(&self, updates: &mut Vec<BlockUpdate>) {
 		if let Some(finality_lag) = self.finality_lag {
-			metrics.on_finality_lag(finality_lag);
+			updates.push(BlockUpdate::FinalityLag(finality_lag));
 		}
 	}
 
-	fn notify_on_demand_order(&self, metrics: &impl PrometheusMetrics) {
+	fn notify_on_demand_order(&self, updates: &mut Vec<BlockUpdate>) {
 		if let Some(ref order) = self.on_demand_order {
-			metrics.handle_on_demand_order(order);
-		}
-		if let Some(delay) = self.on_demand_delay() {
-			if self.is_on_demand_scheduled_in_current_block {
-				metrics.handle_on_demand_delay(delay, self.para_id, "scheduled");
-			}
-			if self.current_candidate.is_backed() {
-				metrics.handle_on_demand_delay(delay, self.para_id, "backed");
-			}
+			updates.push(BlockUpdate::OnDemandOrder(order.clone()));
 		}
-		if let Some(delay_sec) = self.on_demand_delay_sec() {
-			if self.is_on_demand_scheduled_in_current_block {
-				metrics.handle_on_demand_delay_sec(delay_sec, self.para_id, "scheduled");
+		let delay = self.on_demand_delay();
+		let delay_sec = self.on_demand_delay_sec();
+		for (state, is_reached) in [
+			("scheduled", self.is_on_demand_scheduled_in_current_block),
+			("backed", self.current_candidate.is_backed()),
+		] {
+			if let Some(delay) = delay.filter(|_| is_reached) {
+				updates.push(BlockUpdate::OnDemandDelay { delay, state });
 			}
-			if self.current_candidate.is_backed() {
-				metrics.handle_on_demand_delay_sec(delay_sec, self.para_id, "backed");
+			if let Some(delay) = delay_sec.filter(|_| is_reached) {
+				updates.push(BlockUpdate::OnDemandDelaySec { delay, state });
 			}
 		}
 	}
@@ -390,45 +1137,50 @@ impl SubxtTracker {
 		}
 	}
 
-	fn notify_bitfield_propagation(
-		&self,
-		progress: &mut ParachainProgressUpdate,
-		stats: &mut impl Stats,
-		metrics: &impl PrometheusMetrics,
-	) {
-		if self.current_candidate.is_bitfield_propagation_slow() {
+	fn notify_bitfield_propagation(&self, progress: &mut ParachainProgressUpdate, updates: &mut Vec<BlockUpdate>) {
+		if self.is_bitfield_propagation_slow() {
 			progress.events.push(ParachainConsensusEvent::SlowBitfieldPropagation(
 				self.current_candidate.bitfield_count,
 				self.current_candidate.max_availability_bits,
 			))
 		}
-		stats
-			.on_bitfields(self.current_candidate.bitfield_count, self.current_candidate.is_bitfield_propagation_slow());
-		metrics.on_bitfields(
-			self.current_candidate.bitfield_count,
-			self.current_candidate.is_bitfield_propagation_slow(),
-			self.para_id,
-		);
+		updates.push(BlockUpdate::Bitfields {
+			count: self.current_candidate.bitfield_count,
+			is_low: self.is_bitfield_propagation_slow(),
+		});
 	}
 
 	async fn notify_candidate_state(
 		&self,
 		progress: &mut ParachainProgressUpdate,
-		stats: &mut impl Stats,
-		metrics: &impl PrometheusMetrics,
+		updates: &mut Vec<BlockUpdate>,
 		storage: &TrackerStorage,
 	) {
-		if self.current_candidate.is_idle() {
-			progress.events.push(ParachainConsensusEvent::SkippedSlot);
-			stats.on_skipped_slot(progress);
-			metrics.on_skipped_slot(progress);
+		if let Some(reason) = self.skipped_slot_reason() {
+			progress.events.push(ParachainConsensusEvent::SkippedSlot(reason));
+			updates.push(BlockUpdate::SkippedSlot {
+				block_number: progress.block_number,
+				block_hash: progress.block_hash,
+				reason,
+			});
 		}
 
 		if self.current_candidate.is_backed() {
 			if let Some(candidate_hash) = self.current_candidate.candidate_hash {
-				progress.events.push(ParachainConsensusEvent::Backed(candidate_hash));
-				stats.on_backed();
-				metrics.on_backed(self.para_id);
+				let (votes, group_size) = self
+					.current_candidate
+					.candidate
+					.as_ref()
+					.map(extract_backing_votes)
+					.unwrap_or_default();
+				progress
+					.events
+					.push(ParachainConsensusEvent::Backed(candidate_hash, votes, group_size));
+				updates.push(BlockUpdate::Backed {
+					votes,
+					group_size,
+					minimum_backing_votes: self.minimum_backing_votes,
+				});
 			}
 		}
 
@@ -447,22 +1199,43 @@ impl SubxtTracker {
 
 					let backed_in = self.candidate_backed_in(candidate_hash, storage).await;
 					let relay_block = self.current_relay_block.expect("Checked by caller; qed");
-					stats.on_included(relay_block.num, self.previous_included_at.map(|v| v.num), backed_in);
-					metrics.on_included(
-						relay_block.num,
-						self.previous_included_at.map(|v| v.num),
-						backed_in,
-						time_diff(Some(relay_block.ts), self.previous_included_at.map(|v| v.ts)),
-						self.para_id,
-					);
+					let para_block_time_sec = time_diff(Some(relay_block.ts), self.previous_included_at.map(|v| v.ts));
+					let backed_in_sec = backed_in.and_then(|v| v.duration);
+					updates.push(BlockUpdate::Included {
+						relay_parent_number: relay_block.num,
+						previous_included: self.previous_included_at.map(|v| v.num),
+						backed_in: backed_in.map(|v| v.blocks),
+						para_block_time_sec,
+						backed_in_sec,
+					});
+					if let Some(blocks) = self.candidate_available_in(candidate_hash, storage).await {
+						updates.push(BlockUpdate::AvailabilityTime(blocks));
+					}
+					progress.backed_in = backed_in;
+					progress.included_after = self.previous_included_at.map(|v| Latency {
+						blocks: relay_block.num.saturating_sub(v.num),
+						duration: para_block_time_sec,
+					});
 				}
 			} else if self.is_slow_availability() {
 				progress.events.push(ParachainConsensusEvent::SlowAvailability(
 					self.current_candidate.current_availability_bits,
 					self.current_candidate.max_availability_bits,
 				));
-				stats.on_slow_availability();
-				metrics.on_slow_availability(self.para_id);
+				updates.push(BlockUpdate::SlowAvailability);
+			}
+		}
+
+		if self.current_candidate.is_timed_out() {
+			if let Some(candidate_hash) = self.current_candidate.candidate_hash {
+				progress
+					.events
+					.push(ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash));
+				updates.push(BlockUpdate::AvailabilityTimeout { block_number: progress.block_number, candidate_hash });
+				// Stuck candidates are the tail of the availability time, they are recorded with the time they waited
+				if let Some(blocks) = self.candidate_available_in(candidate_hash, storage).await {
+					updates.push(BlockUpdate::AvailabilityTime(blocks));
+				}
 			}
 		}
 	}
@@ -499,6 +1272,30 @@ impl SubxtTracker {
 				.any(|fork| fork.backed_candidate.is_some() || fork.included_candidate.is_some())
 	}
 
+	/// Returns why no candidate was backed in current relay block, if none was
+	fn skipped_slot_reason(&self) -> Option<SkippedSlotReason> {
+		if self.current_candidate.is_idle() {
+			return Some(if self.current_candidate.assigned_cores.is_empty() {
+				SkippedSlotReason::NotScheduled
+			} else if self.current_candidate.core_occupied {
+				SkippedSlotReason::CoreOccupied
+			} else {
+				SkippedSlotReason::NoCandidate
+			})
+		}
+
+		// The runtime drops candidates without enough votes, the event tells if it did
+		let is_dropped = self
+			.inclusion_checks
+			.iter()
+			.any(|(_, mismatch)| *mismatch == Some(InclusionMismatch::BackedWithoutEvent));
+		let has_few_votes = self.current_candidate.candidate.as_ref().is_some_and(|candidate| {
+			let (votes, group_size) = extract_backing_votes(candidate);
+			backing_quorum(votes, group_size, self.minimum_backing_votes) == BackingQuorum::Below
+		});
+		(self.is_just_backed() && is_dropped && has_few_votes).then_some(SkippedSlotReason::InsufficientBacking)
+	}
+
 	fn is_just_backed(&self) -> bool {
 		self.last_backed_at_block_number.is_some() &&
 			self.last_backed_at_block_number == self.current_relay_block.map(|v| v.num)
@@ -506,7 +1303,14 @@ impl SubxtTracker {
 
 	fn is_slow_availability(&self) -> bool {
 		self.current_candidate.core_occupied &&
-			self.last_backed_at_block_number != self.current_relay_block.map(|v| v.num)
+			self.last_backed_at_block_number != self.current_relay_block.map(|v| v.num) &&
+			self.is_slow_availability_by_baseline.unwrap_or(true)
+	}
+
+	/// Checked against the baseline after its warm-up, against the supermajority before
+	fn is_bitfield_propagation_slow(&self) -> bool {
+		self.is_bitfield_propagation_slow_by_baseline
+			.unwrap_or_else(|| self.current_candidate.is_bitfield_propagation_slow())
 	}
 
 	async fn validators_indices(
@@ -523,13 +1327,28 @@ impl SubxtTracker {
 			.collect())
 	}
 
-	async fn candidate_backed_in(&self, candidate_hash: H256, storage: &TrackerStorage) -> Option<u32> {
-		storage.candidate(candidate_hash).await.map(|v| {
-			v.candidate_inclusion
-				.backed
-				.saturating_sub(v.candidate_inclusion.relay_parent_number)
+	/// Returns the latency between the relay parent of the candidate and the relay chain block it was backed in.
+	/// The time is measured using the actual block timestamps if they are known.
+	async fn candidate_backed_in(&self, candidate_hash: H256, storage: &TrackerStorage) -> Option<Latency> {
+		let inclusion = storage.candidate(candidate_hash).await?.candidate_inclusion;
+		let relay_parent_ts = match self.recent_relay_block_ts.get(&inclusion.relay_parent_number) {
+			Some(ts) => Some(*ts),
+			None => storage.block_timestamp(inclusion.relay_parent).await,
+		};
+
+		Some(Latency {
+			blocks: inclusion.backed.saturating_sub(inclusion.relay_parent_number),
+			duration: time_diff(self.recent_relay_block_ts.get(&inclusion.backed).copied(), relay_parent_ts),
 		})
 	}
+
+	/// Returns the number of relay chain blocks between the block the candidate was backed in and current one,
+	/// where 2/3 of its availability bits are set or the candidate timed out
+	async fn candidate_available_in(&self, candidate_hash: H256, storage: &TrackerStorage) -> Option<u32> {
+		let backed = storage.candidate(candidate_hash).await?.candidate_inclusion.backed;
+		let relay_block = self.current_relay_block?;
+		Some(relay_block.num.saturating_sub(backed))
+	}
 }
 
 #[cfg(test)]
@@ -538,7 +1357,7 @@ mod test_inject_new_session {
 
 	#[tokio::test]
 	async fn test_sets_new_session() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		assert!(tracker.new_session.is_none());
 
 		tracker.inject_new_session(42);
@@ -553,11 +1372,12 @@ mod test_maybe_reset_state {
 
 	#[tokio::test]
 	async fn test_resets_state_if_not_backed() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		tracker.current_candidate.set_included();
 		tracker.new_session = Some(42);
 		tracker.on_demand_order = Some(OnDemandOrder::default());
 		tracker.is_on_demand_scheduled_in_current_block = true;
+		tracker.is_core_freed_in_current_block = true;
 		tracker.disputes = vec![DisputesTracker::default()];
 
 		tracker.maybe_reset_state();
@@ -565,23 +1385,29 @@ mod test_maybe_reset_state {
 		assert!(tracker.new_session.is_none());
 		assert!(tracker.on_demand_order.is_none());
 		assert!(!tracker.is_on_demand_scheduled_in_current_block);
+		assert!(!tracker.is_core_freed_in_current_block);
 		assert!(tracker.disputes.is_empty());
 		assert!(tracker.current_candidate.is_idle());
 	}
 
 	#[tokio::test]
 	async fn test_resets_state_if_backed() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		tracker.current_candidate.set_backed();
 		tracker.new_session = Some(42);
 		tracker.on_demand_order = Some(OnDemandOrder::default());
 		tracker.on_demand_order_at = Some(BlockWithoutHash::default());
+		tracker.scheduled_at.insert(0, BlockWithoutHash::default());
+		tracker.core_dispatch = vec![(2, None)];
 		tracker.is_on_demand_scheduled_in_current_block = true;
 		tracker.disputes = vec![DisputesTracker::default()];
 
 		tracker.maybe_reset_state();
 
 		assert!(tracker.on_demand_order_at.is_none());
+		// The parachain stays scheduled on its cores until a candidate is backed on them
+		assert_eq!(tracker.scheduled_at.len(), 1);
+		assert!(tracker.core_dispatch.is_empty());
 		assert!(tracker.new_session.is_none());
 		assert!(tracker.on_demand_order.is_none());
 		assert!(!tracker.is_on_demand_scheduled_in_current_block);
@@ -589,25 +1415,94 @@ mod test_maybe_reset_state {
 	}
 }
 
+#[cfg(test)]
+mod test_set_baselines {
+	use super::*;
+
+	fn set_relay_block(tracker: &mut SubxtTracker, num: u32, is_fork: bool) {
+		tracker.previous_relay_block = tracker.current_relay_block.filter(|_| is_fork);
+		tracker.current_relay_block = Some(Block { num, ts: 0, hash: H256::random() });
+	}
+
+	#[test]
+	fn test_replaces_fixed_thresholds_after_warmup() {
+		let mut tracker = SubxtTracker::new(100, None, Some(BaselineOptions { warmup: 3, alpha: 0.1, sigma: 3.0 }));
+		tracker.current_candidate.max_availability_bits = 100;
+		tracker.current_candidate.core_occupied = true;
+
+		// Candidates of this parachain are available in 3 blocks with bitfields of 60% of the validators
+		for num in 0..3 {
+			tracker.last_backed_at_block_number = Some(num * 10);
+			set_relay_block(&mut tracker, num * 10 + 3, false);
+			tracker.current_candidate.set_included();
+			tracker.current_candidate.bitfield_count = 60;
+			tracker.set_baselines();
+			// The fixed thresholds are used during the warm-up
+			assert!(tracker.is_bitfield_propagation_slow());
+		}
+
+		tracker.last_backed_at_block_number = Some(100);
+		tracker.current_candidate.set_pending();
+		set_relay_block(&mut tracker, 104, false);
+		tracker.set_baselines();
+		assert!(!tracker.is_bitfield_propagation_slow());
+		assert!(!tracker.is_slow_availability());
+
+		set_relay_block(&mut tracker, 106, false);
+		tracker.current_candidate.bitfield_count = 30;
+		tracker.set_baselines();
+		assert!(tracker.is_bitfield_propagation_slow());
+		assert!(tracker.is_slow_availability());
+	}
+
+	#[test]
+	fn test_checks_forks_without_learning() {
+		let mut tracker = SubxtTracker::new(100, None, Some(BaselineOptions { warmup: 1, alpha: 0.1, sigma: 3.0 }));
+		tracker.current_candidate.max_availability_bits = 100;
+		tracker.current_candidate.core_occupied = true;
+		tracker.last_backed_at_block_number = Some(10);
+		set_relay_block(&mut tracker, 13, false);
+		tracker.current_candidate.set_included();
+		tracker.current_candidate.bitfield_count = 60;
+		tracker.set_baselines();
+
+		let learned = tracker.baselines.clone();
+		set_relay_block(&mut tracker, 13, true);
+		tracker.set_baselines();
+		assert!(tracker.is_fork());
+		// Checked against the baseline rather than the fixed threshold
+		assert!(!tracker.is_bitfield_propagation_slow());
+		assert_eq!(tracker.baselines, learned);
+	}
+}
+
 #[cfg(test)]
 mod test_inject_block {
 	use std::collections::BTreeMap;
 
 	use super::*;
 	use crate::{
-		test_utils::{create_inherent_data, create_storage, storage_write},
+		prometheus::Metrics,
+		stats::ParachainStats,
+		test_utils::{create_inherent_data, create_para_head, create_storage, storage_write},
 		tracker_rpc::MockTrackerRpc,
+		types::QueueDepth,
+	};
+	use polkadot_introspector_essentials::{
+		api::subxt_wrapper::SubxtWrapperError, collector::CollectorPrefixType, types::Header,
 	};
-	use polkadot_introspector_essentials::collector::CollectorPrefixType;
 
 	#[tokio::test]
 	async fn test_changes_nothing_if_there_is_no_inherent_data() {
 		let hash = H256::random();
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut mock_rpc = MockTrackerRpc::new();
 
-		tracker.inject_block(hash, 0, &mut mock_rpc, &tracker_storage).await.unwrap();
+		tracker
+			.inject_block(hash, 0, false, &mut mock_rpc, &tracker_storage)
+			.await
+			.unwrap();
 
 		assert!(tracker.new_session.is_none());
 		assert!(tracker.current_candidate.candidate.is_none());
@@ -617,6 +1512,7 @@ mod test_inject_block {
 		assert!(tracker.last_backed_at_block_number.is_none());
 		assert!(tracker.last_included_at.is_none());
 		assert!(tracker.previous_included_at.is_none());
+		assert!(tracker.scheduled_at.is_empty());
 		assert!(tracker.finality_lag.is_none());
 		assert!(tracker.on_demand_order.is_none());
 		assert!(tracker.on_demand_order_at.is_none());
@@ -631,11 +1527,14 @@ mod test_inject_block {
 		let first_hash = H256::random();
 		let second_hash = H256::random();
 		let storage = create_storage();
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, storage.clone());
 		let mut mock_rpc = MockTrackerRpc::new();
 		mock_rpc.expect_inbound_hrmp_channels().returning(|_| Ok(Default::default()));
 		mock_rpc.expect_outbound_hrmp_channels().returning(|_| Ok(Default::default()));
+		mock_rpc.expect_downward_queue_len().returning(|_| Ok(0));
+		mock_rpc.expect_upward_queue_len().returning(|_| Ok(0));
+		mock_rpc.expect_para_head_number().returning(|_| Ok(None));
 
 		// Inject a block
 		storage_write(CollectorPrefixType::CoreAssignments, first_hash, BTreeMap::<u32, Vec<u32>>::default(), &storage)
@@ -648,7 +1547,7 @@ mod test_inject_block {
 			.await
 			.unwrap();
 		tracker
-			.inject_block(first_hash, 42, &mut mock_rpc, &tracker_storage)
+			.inject_block(first_hash, 42, true, &mut mock_rpc, &tracker_storage)
 			.await
 			.unwrap();
 
@@ -677,7 +1576,7 @@ mod test_inject_block {
 			.await
 			.unwrap();
 		tracker
-			.inject_block(second_hash, 42, &mut mock_rpc, &tracker_storage)
+			.inject_block(second_hash, 42, true, &mut mock_rpc, &tracker_storage)
 			.await
 			.unwrap();
 
@@ -688,39 +1587,314 @@ mod test_inject_block {
 		assert_eq!(tracker.last_non_fork_relay_block_ts, Some(1));
 		assert_eq!(tracker.finality_lag, Some(2));
 	}
-}
-
-#[cfg(test)]
-mod test_progress {
-	use super::*;
-	use crate::{
-		prometheus::{Metrics, MockPrometheusMetrics},
-		stats::{MockStats, ParachainStats},
-		test_utils::{create_candidate_record, create_hrmp_channels, create_storage, storage_write},
-	};
-	use mockall::predicate::eq;
-	use polkadot_introspector_essentials::collector::CollectorPrefixType;
 
 	#[tokio::test]
-	async fn test_returns_none_if_no_current_block() {
-		let tracker = SubxtTracker::new(100);
-		let tracker_storage = TrackerStorage::new(100, create_storage());
-		let mut stats = MockStats::default();
-		let metrics = Metrics::default();
+	async fn test_processes_block_without_message_queues() {
+		let hash = H256::random();
+		let storage = create_storage();
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		let mut mock_rpc = MockTrackerRpc::new();
+		mock_rpc.expect_inbound_hrmp_channels().returning(|_| Ok(Default::default()));
+		mock_rpc.expect_outbound_hrmp_channels().returning(|_| Ok(Default::default()));
+		mock_rpc.expect_downward_queue_len().returning(|_| Ok(1));
+		mock_rpc
+			.expect_upward_queue_len()
+			.returning(|_| Err(SubxtWrapperError::DecodeExtrinsicError));
+		mock_rpc.expect_para_head_number().returning(|_| Ok(None));
+		storage_write(CollectorPrefixType::CoreAssignments, hash, BTreeMap::<u32, Vec<u32>>::default(), &storage)
+			.await
+			.unwrap();
+		storage_write(CollectorPrefixType::InherentData, hash, create_inherent_data(100), &storage)
+			.await
+			.unwrap();
+		storage_write(CollectorPrefixType::Timestamp, hash, 1_u64, &storage)
+			.await
+			.unwrap();
 
-		let progress = tracker.progress(&mut stats, &metrics, &tracker_storage).await;
+		tracker
+			.inject_block(hash, 42, true, &mut mock_rpc, &tracker_storage)
+			.await
+			.unwrap();
 
-		assert!(progress.is_none());
+		assert_eq!(tracker.current_relay_block.unwrap().hash, hash);
 	}
 
 	#[tokio::test]
-	async fn test_returns_progress_on_current_block() {
-		let hash = H256::random();
-		let mut tracker = SubxtTracker::new(100);
-		let tracker_storage = TrackerStorage::new(100, create_storage());
-		let mut stats = ParachainStats::default();
+	async fn test_rolls_back_abandoned_forks() {
+		let (first_hash, second_hash, next_hash) = (H256::random(), H256::random(), H256::random());
+		let storage = create_storage();
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		let mut stats = ParachainStats::new(100, 10, None);
 		let metrics = Metrics::default();
-
+		let mut mock_rpc = MockTrackerRpc::new();
+		mock_rpc.expect_inbound_hrmp_channels().returning(|_| Ok(Default::default()));
+		mock_rpc.expect_outbound_hrmp_channels().returning(|_| Ok(Default::default()));
+		mock_rpc.expect_downward_queue_len().returning(|_| Ok(0));
+		mock_rpc.expect_upward_queue_len().returning(|_| Ok(0));
+		mock_rpc.expect_para_head_number().returning(|_| Ok(None));
+
+		// Only the first fork backs a candidate of the parachain
+		for (hash, para_id) in [(first_hash, 100), (second_hash, 200), (next_hash, 200)] {
+			storage_write(CollectorPrefixType::CoreAssignments, hash, BTreeMap::<u32, Vec<u32>>::default(), &storage)
+				.await
+				.unwrap();
+			storage_write(CollectorPrefixType::InherentData, hash, create_inherent_data(para_id), &storage)
+				.await
+				.unwrap();
+			storage_write(CollectorPrefixType::Timestamp, hash, 1_u64, &storage)
+				.await
+				.unwrap();
+		}
+		let next_header = Header {
+			parent_hash: second_hash,
+			number: 43,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		storage_write(CollectorPrefixType::RelayBlockHeader, next_hash, next_header, &storage)
+			.await
+			.unwrap();
+
+		tracker
+			.inject_block(first_hash, 42, true, &mut mock_rpc, &tracker_storage)
+			.await
+			.unwrap();
+		tracker
+			.inject_block(second_hash, 42, true, &mut mock_rpc, &tracker_storage)
+			.await
+			.unwrap();
+		assert!(tracker.current_candidate.candidate.is_some());
+		// Forks don't update the statistics
+		tracker.progress(&mut stats, &metrics, &tracker_storage).await.unwrap();
+		assert_eq!(stats.summary().backed_count, 0);
+
+		// The chain continues from the second fork, so the candidate backed in the first one is forgotten
+		tracker.maybe_reset_state();
+		tracker
+			.inject_block(next_hash, 43, false, &mut mock_rpc, &tracker_storage)
+			.await
+			.unwrap();
+		assert!(tracker.current_candidate.candidate.is_none());
+		assert_eq!(tracker.previous_relay_block.unwrap().hash, second_hash);
+		assert_eq!(tracker.current_relay_block.unwrap().hash, next_hash);
+
+		let replayed = tracker.take_replayed_progress();
+		assert_eq!(replayed.len(), 1);
+		assert_eq!(replayed[0].block_hash, second_hash);
+		assert!(replayed[0].is_replayed && !replayed[0].is_fork);
+		tracker.progress(&mut stats, &metrics, &tracker_storage).await.unwrap();
+		assert_eq!(stats.summary().backed_count, 0);
+		assert_eq!(stats.summary().blocks, 2);
+	}
+
+	#[tokio::test]
+	async fn test_switching_forks_leaves_counters_and_queues_of_chosen_chain() {
+		let (prev_hash, first_hash, second_hash, next_hash) =
+			(H256::random(), H256::random(), H256::random(), H256::random());
+		let storage = create_storage();
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		// Only the abandoned fork backs a candidate of the parachain and has a long downward queue
+		let blocks = [(prev_hash, 200, 1), (first_hash, 100, 7), (second_hash, 200, 2), (next_hash, 200, 3)];
+		let queue_lens: BTreeMap<H256, u32> = blocks.iter().map(|(hash, _, len)| (*hash, *len)).collect();
+		for (idx, (hash, para_id, _)) in blocks.into_iter().enumerate() {
+			storage_write(CollectorPrefixType::CoreAssignments, hash, BTreeMap::<u32, Vec<u32>>::default(), &storage)
+				.await
+				.unwrap();
+			storage_write(CollectorPrefixType::InherentData, hash, create_inherent_data(para_id), &storage)
+				.await
+				.unwrap();
+			storage_write(CollectorPrefixType::Timestamp, hash, idx as u64 * 6000, &storage)
+				.await
+				.unwrap();
+		}
+		let next_header = Header {
+			parent_hash: second_hash,
+			number: 43,
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		storage_write(CollectorPrefixType::RelayBlockHeader, next_hash, next_header, &storage)
+			.await
+			.unwrap();
+		// Every block is read from the RPC once, the chosen fork is replayed with the recorded results
+		let mock_rpc = |calls: usize| {
+			let queue_lens = queue_lens.clone();
+			let mut mock_rpc = MockTrackerRpc::new();
+			mock_rpc
+				.expect_inbound_hrmp_channels()
+				.times(calls)
+				.returning(|_| Ok(Default::default()));
+			mock_rpc
+				.expect_outbound_hrmp_channels()
+				.times(calls)
+				.returning(|_| Ok(Default::default()));
+			mock_rpc
+				.expect_downward_queue_len()
+				.times(calls)
+				.returning(move |hash| Ok(queue_lens[&hash]));
+			mock_rpc.expect_upward_queue_len().times(calls).returning(|_| Ok(0));
+			mock_rpc.expect_para_head_number().times(calls).returning(|_| Ok(None));
+			mock_rpc
+		};
+		let metrics = Metrics::default();
+
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let mut stats = ParachainStats::new(100, 10, None);
+		let mut rpc = mock_rpc(4);
+		for (hash, number, is_forked) in
+			[(prev_hash, 41, false), (first_hash, 42, true), (second_hash, 42, true), (next_hash, 43, false)]
+		{
+			tracker
+				.inject_block(hash, number, is_forked, &mut rpc, &tracker_storage)
+				.await
+				.unwrap();
+			tracker.progress(&mut stats, &metrics, &tracker_storage).await.unwrap();
+			tracker.maybe_reset_state();
+		}
+
+		// The same chain without the abandoned fork
+		let mut expected_tracker = SubxtTracker::new(100, None, None);
+		let mut expected_stats = ParachainStats::new(100, 10, None);
+		let mut rpc = mock_rpc(3);
+		for (hash, number) in [(prev_hash, 41), (second_hash, 42), (next_hash, 43)] {
+			expected_tracker
+				.inject_block(hash, number, false, &mut rpc, &tracker_storage)
+				.await
+				.unwrap();
+			expected_tracker
+				.progress(&mut expected_stats, &metrics, &tracker_storage)
+				.await
+				.unwrap();
+			expected_tracker.maybe_reset_state();
+		}
+
+		assert_eq!(stats.summary(), expected_stats.summary());
+		assert_eq!(stats.summary().backed_count, 0);
+		assert_eq!(tracker.message_queues.queue_depths, expected_tracker.message_queues.queue_depths);
+		assert_eq!(tracker.message_queues.queue_depths.unwrap().downward, QueueDepth { len: 3, prev_len: Some(2) });
+		assert!(tracker.fork_rpc_results.is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_measures_unincluded_depth_from_para_heads() {
+		let hashes = [H256::random(), H256::random(), H256::random()];
+		let storage = create_storage();
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		// The para head on chain stays at 1000 while two candidates are backed on top of each other,
+		// then both are included
+		let heads = BTreeMap::from([(hashes[0], 1000), (hashes[1], 1000), (hashes[2], 1002)]);
+		let mut mock_rpc = MockTrackerRpc::new();
+		mock_rpc.expect_inbound_hrmp_channels().returning(|_| Ok(Default::default()));
+		mock_rpc.expect_outbound_hrmp_channels().returning(|_| Ok(Default::default()));
+		mock_rpc.expect_downward_queue_len().returning(|_| Ok(0));
+		mock_rpc.expect_upward_queue_len().returning(|_| Ok(0));
+		mock_rpc
+			.expect_para_head_number()
+			.returning(move |hash| Ok(heads.get(&hash).copied()));
+
+		let backed = [Some(1001), Some(1002), None];
+		for (idx, (hash, para_block)) in hashes.into_iter().zip(backed).enumerate() {
+			let mut inherent = create_inherent_data(100);
+			match para_block {
+				Some(number) =>
+					inherent.backed_candidates[0].candidate.commitments.head_data = create_para_head(number),
+				None => inherent.backed_candidates.clear(),
+			}
+			storage_write(CollectorPrefixType::CoreAssignments, hash, BTreeMap::<u32, Vec<u32>>::default(), &storage)
+				.await
+				.unwrap();
+			storage_write(CollectorPrefixType::InherentData, hash, inherent, &storage)
+				.await
+				.unwrap();
+			storage_write(CollectorPrefixType::Timestamp, hash, idx as u64, &storage)
+				.await
+				.unwrap();
+		}
+
+		let mut depths = vec![];
+		for (idx, hash) in hashes.into_iter().enumerate() {
+			tracker
+				.inject_block(hash, 42 + idx as u32, false, &mut mock_rpc, &tracker_storage)
+				.await
+				.unwrap();
+			depths.push(tracker.unincluded_depth);
+			tracker.maybe_reset_state();
+		}
+		assert_eq!(depths, vec![Some(1), Some(2), Some(0)]);
+	}
+}
+
+#[cfg(test)]
+mod test_progress {
+	use super::*;
+	use crate::{
+		prometheus::{Metrics, MockPrometheusMetrics},
+		stats::{MockStats, ParachainStats},
+		test_utils::{
+			create_backed_candidate, create_candidate_record, create_hrmp_channels, create_storage, storage_write,
+		},
+	};
+	use mockall::predicate::{always, eq};
+	use polkadot_introspector_essentials::collector::CollectorPrefixType;
+	use subxt::utils::bits::DecodedBits;
+
+	#[tokio::test]
+	async fn test_returns_none_if_no_current_block() {
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, create_storage());
+		let mut stats = MockStats::default();
+		let metrics = Metrics::default();
+
+		let progress = tracker.progress(&mut stats, &metrics, &tracker_storage).await;
+
+		assert!(progress.is_none());
+	}
+
+	#[tokio::test]
+	async fn test_measures_backing_time_with_block_timestamps() {
+		let candidate_hash = H256::random();
+		let storage = create_storage();
+		let mut tracker = SubxtTracker::new(100, None, None);
+		storage_write(
+			CollectorPrefixType::Candidate(100),
+			candidate_hash,
+			create_candidate_record(100, 42, H256::random(), 40),
+			&storage,
+		)
+		.await
+		.unwrap();
+		let tracker_storage = TrackerStorage::new(100, storage);
+
+		// A slow block between the relay parent and the backing block
+		tracker.recent_relay_block_ts.insert(40, 1694095320000);
+		tracker.recent_relay_block_ts.insert(42, 1694095338000);
+
+		assert_eq!(
+			tracker.candidate_backed_in(candidate_hash, &tracker_storage).await,
+			Some(Latency { blocks: 2, duration: Some(Duration::from_secs(18)) })
+		);
+
+		// Timestamps are unknown
+		tracker.recent_relay_block_ts.clear();
+		assert_eq!(
+			tracker.candidate_backed_in(candidate_hash, &tracker_storage).await,
+			Some(Latency { blocks: 2, duration: None })
+		);
+	}
+
+	#[tokio::test]
+	async fn test_returns_progress_on_current_block() {
+		let hash = H256::random();
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, create_storage());
+		let mut stats = ParachainStats::default();
+		let metrics = Metrics::default();
+
 		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash });
 		let progress = tracker.progress(&mut stats, &metrics, &tracker_storage).await.unwrap();
 
@@ -736,7 +1910,7 @@ mod test_progress {
 
 	#[tokio::test]
 	async fn test_includes_new_session_if_exist() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut stats = ParachainStats::default();
 		let metrics = Metrics::default();
@@ -762,7 +1936,7 @@ mod test_progress {
 
 	#[tokio::test]
 	async fn test_includes_core_assignment() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut stats = ParachainStats::default();
 		let metrics = Metrics::default();
@@ -780,14 +1954,14 @@ mod test_progress {
 
 	#[tokio::test]
 	async fn test_includes_slow_propogation() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut mock_stats = MockStats::default();
-		mock_stats.expect_on_backed().returning(|| ());
+		mock_stats.expect_on_backed().returning(|_, _, _| ());
 		mock_stats.expect_on_block().returning(|_| ());
 		mock_stats.expect_on_skipped_slot().returning(|_| ());
 		let mut mock_metrics = MockPrometheusMetrics::default();
-		mock_metrics.expect_on_backed().returning(|_| ());
+		mock_metrics.expect_on_backed().returning(|_, _, _| ());
 		mock_metrics.expect_on_block().returning(|_, _| ());
 		mock_metrics.expect_on_skipped_slot().returning(|_| ());
 
@@ -830,7 +2004,7 @@ mod test_progress {
 
 	#[tokio::test]
 	async fn test_includes_message_queues() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut stats = ParachainStats::default();
 		let metrics = Metrics::default();
@@ -858,7 +2032,7 @@ mod test_progress {
 
 	#[tokio::test]
 	async fn test_includes_current_block_time() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut mock_stats = MockStats::default();
 		mock_stats.expect_on_bitfields().returning(|_, _| ());
@@ -895,9 +2069,54 @@ mod test_progress {
 			.unwrap();
 	}
 
+	#[tokio::test]
+	async fn test_includes_xcm_throughput() {
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, create_storage());
+		let mut mock_stats = MockStats::default();
+		mock_stats.expect_on_bitfields().returning(|_, _| ());
+		mock_stats.expect_on_skipped_slot().returning(|_| ());
+		mock_stats.expect_on_block().returning(|_| ());
+		let mut mock_metrics = MockPrometheusMetrics::default();
+		mock_metrics.expect_on_bitfields().returning(|_, _, _| ());
+		mock_metrics.expect_on_skipped_slot().returning(|_| ());
+		mock_metrics.expect_on_block().returning(|_, _| ());
+
+		// No messages
+		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash: H256::random() });
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+		assert!(progress.xcm.is_none());
+
+		// With messages
+		let xcm = XcmThroughput { ump_messages: 2, ump_bytes: 30, hrmp_received: 1, ..Default::default() };
+		tracker.xcm_throughput = xcm;
+		mock_metrics
+			.expect_on_xcm_throughput()
+			.with(eq(xcm), eq(100))
+			.once()
+			.returning(|_, _| ());
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+		assert_eq!(progress.xcm, Some(xcm));
+
+		// On a fork messages are shown but not counted again
+		tracker.previous_relay_block = tracker.current_relay_block;
+		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash: H256::random() });
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+		assert_eq!(progress.xcm, Some(xcm));
+	}
+
 	#[tokio::test]
 	async fn test_includes_finality_lag() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut stats = ParachainStats::default();
 		let mut mock_metrics = MockPrometheusMetrics::default();
@@ -919,7 +2138,7 @@ mod test_progress {
 
 	#[tokio::test]
 	async fn test_includes_disputes() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut mock_stats = MockStats::default();
 		mock_stats.expect_on_bitfields().returning(|_, _| ());
@@ -971,7 +2190,7 @@ mod test_progress {
 
 	#[tokio::test]
 	async fn test_includes_on_demand_order() {
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut stats = ParachainStats::default();
 		let mut mock_metrics = MockPrometheusMetrics::default();
@@ -1022,11 +2241,317 @@ mod test_progress {
 		let _progress = tracker.progress(&mut stats, &mock_metrics, &tracker_storage).await.unwrap();
 	}
 
+	#[tokio::test]
+	async fn test_includes_availability_timeout() {
+		let candidate_hash = H256::random();
+		let storage = create_storage();
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		let mut mock_stats = MockStats::default();
+		mock_stats.expect_on_bitfields().returning(|_, _| ());
+		mock_stats.expect_on_block().returning(|_| ());
+		let mut mock_metrics = MockPrometheusMetrics::default();
+		mock_metrics.expect_on_bitfields().returning(|_, _, _| ());
+		mock_metrics.expect_on_block().returning(|_, _| ());
+
+		storage_write(
+			CollectorPrefixType::Candidate(100),
+			candidate_hash,
+			create_candidate_record(100, 40, H256::random(), 38),
+			&storage,
+		)
+		.await
+		.unwrap();
+
+		// Backed at #40, timed out at #42
+		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash: H256::random() });
+		tracker.current_candidate.candidate_hash = Some(candidate_hash);
+		tracker.current_candidate.set_pending();
+		tracker.is_core_freed_in_current_block = true;
+		tracker.set_availability_timeout(&tracker_storage).await;
+		mock_stats
+			.expect_on_availability_timeout()
+			.with(eq(42), always())
+			.once()
+			.returning(|_, _| ());
+		mock_metrics
+			.expect_on_availability_timeout()
+			.with(eq(100))
+			.once()
+			.returning(|_| ());
+		mock_metrics
+			.expect_on_availability_time()
+			.with(eq(2), eq(100))
+			.once()
+			.returning(|_, _| ());
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+
+		assert!(tracker.current_candidate.is_timed_out());
+		assert!(progress
+			.events
+			.iter()
+			.any(|e| matches!(e, ParachainConsensusEvent::AvailabilityTimedOut(_))));
+	}
+
+	#[tokio::test]
+	async fn test_includes_inclusion_mismatches() {
+		let candidate_hash = H256::random();
+		let storage = create_storage();
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		let mut mock_stats = MockStats::default();
+		mock_stats.expect_on_bitfields().returning(|_, _| ());
+		mock_stats.expect_on_block().returning(|_| ());
+		mock_stats.expect_on_backed().returning(|_, _, _| ());
+		let mut mock_metrics = MockPrometheusMetrics::default();
+		mock_metrics.expect_on_bitfields().returning(|_, _, _| ());
+		mock_metrics.expect_on_block().returning(|_, _| ());
+		mock_metrics.expect_on_backed().returning(|_, _, _| ());
+		storage_write(
+			CollectorPrefixType::Candidate(100),
+			candidate_hash,
+			create_candidate_record(100, 42, H256::random(), 40),
+			&storage,
+		)
+		.await
+		.unwrap();
+
+		// Backed in the inherent and by the event
+		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash: H256::random() });
+		tracker.last_backed_at_block_number = Some(42);
+		tracker.current_candidate.set_backed();
+		tracker.current_candidate.candidate_hash = Some(candidate_hash);
+		tracker.set_inclusion_checks(&tracker_storage).await;
+		mock_metrics
+			.expect_on_inclusion_check()
+			.with(eq(None), eq(100))
+			.once()
+			.returning(|_, _| ());
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+
+		assert!(!progress
+			.events
+			.iter()
+			.any(|e| matches!(e, ParachainConsensusEvent::InclusionMismatch(_, _))));
+
+		// Available by the bitfields, but no inclusion event
+		tracker.current_relay_block = Some(Block { num: 43, ts: 1694095338000, hash: H256::random() });
+		tracker.current_candidate.set_included();
+		tracker.set_inclusion_checks(&tracker_storage).await;
+		mock_stats.expect_on_included().returning(|_, _, _, _, _| ());
+		mock_metrics.expect_on_included().returning(|_, _, _, _, _, _| ());
+		mock_metrics
+			.expect_on_inclusion_check()
+			.with(eq(Some(InclusionMismatch::IncludedWithoutEvent)), eq(100))
+			.once()
+			.returning(|_, _| ());
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+
+		assert!(progress.events.iter().any(|e| matches!(
+			e,
+			ParachainConsensusEvent::InclusionMismatch(_, InclusionMismatch::IncludedWithoutEvent)
+		)));
+	}
+
+	#[tokio::test]
+	async fn test_includes_old_relay_parents() {
+		let candidate_hash = H256::random();
+		let storage = create_storage();
+		let mut tracker = SubxtTracker::new(100, Some(3), None);
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		let mut mock_stats = MockStats::default();
+		mock_stats.expect_on_bitfields().returning(|_, _| ());
+		mock_stats.expect_on_block().returning(|_| ());
+		mock_stats.expect_on_backed().returning(|_, _, _| ());
+		let mut mock_metrics = MockPrometheusMetrics::default();
+		mock_metrics.expect_on_bitfields().returning(|_, _, _| ());
+		mock_metrics.expect_on_block().returning(|_, _| ());
+		mock_metrics.expect_on_backed().returning(|_, _, _| ());
+		storage_write(
+			CollectorPrefixType::Candidate(100),
+			candidate_hash,
+			create_candidate_record(100, 42, H256::random(), 40),
+			&storage,
+		)
+		.await
+		.unwrap();
+		tracker.current_candidate.set_backed();
+		tracker.current_candidate.candidate_hash = Some(candidate_hash);
+
+		// Backed within the allowed ancestry
+		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash: H256::random() });
+		tracker.last_backed_at_block_number = Some(42);
+		tracker.set_relay_parent_age(&tracker_storage).await;
+		mock_stats
+			.expect_on_relay_parent_age()
+			.with(eq(2), eq(false))
+			.once()
+			.returning(|_, _| ());
+		mock_metrics
+			.expect_on_relay_parent_age()
+			.with(eq(2), eq(false), eq(100))
+			.once()
+			.returning(|_, _, _| ());
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+
+		assert!(!progress
+			.events
+			.iter()
+			.any(|e| matches!(e, ParachainConsensusEvent::OldRelayParent(_, _, _))));
+
+		// Backed at the edge of the allowed ancestry
+		tracker.current_relay_block = Some(Block { num: 43, ts: 1694095338000, hash: H256::random() });
+		tracker.last_backed_at_block_number = Some(43);
+		tracker.set_relay_parent_age(&tracker_storage).await;
+		mock_stats
+			.expect_on_relay_parent_age()
+			.with(eq(3), eq(true))
+			.once()
+			.returning(|_, _| ());
+		mock_metrics
+			.expect_on_relay_parent_age()
+			.with(eq(3), eq(true), eq(100))
+			.once()
+			.returning(|_, _, _| ());
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+
+		assert!(progress
+			.events
+			.iter()
+			.any(|e| matches!(e, ParachainConsensusEvent::OldRelayParent(_, 3, 3))));
+	}
+
+	#[tokio::test]
+	async fn test_tracks_candidates_per_core() {
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let storage = create_storage();
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		let first = create_backed_candidate(100);
+		let second = create_backed_candidate(100);
+		let mut records = vec![];
+		for (candidate, core) in [(&first, 3), (&second, 5)] {
+			let mut record = create_candidate_record(100, 42, H256::random(), 41);
+			record.candidate_inclusion.core_idx = Some(core);
+			storage_write(CollectorPrefixType::Candidate(100), candidate_hash(candidate), record.clone(), &storage)
+				.await
+				.unwrap();
+			records.push(record);
+		}
+		let bitfield = AvailabilityBitfield(DecodedBits::from_iter([false, false, false, true, false, true]));
+		let bitfields = vec![&bitfield];
+
+		// Both candidates are backed in the same relay block
+		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash: H256::random() });
+		tracker
+			.set_core_candidates(H256::random(), vec![first.clone(), second.clone()], &bitfields, &tracker_storage)
+			.await;
+		assert_eq!(tracker.included_count, Some(0));
+		assert_eq!(tracker.core_candidates.keys().copied().collect::<Vec<_>>(), vec![3, 5]);
+		assert!(tracker.core_candidates.values().all(|info| info.is_backed()));
+		tracker.maybe_reset_state();
+
+		// Only the first one is included in the next block
+		records[0].candidate_inclusion.included = Some(43);
+		storage_write(CollectorPrefixType::Candidate(100), candidate_hash(&first), records[0].clone(), &storage)
+			.await
+			.unwrap();
+		tracker.previous_relay_block = tracker.current_relay_block;
+		tracker.current_relay_block = Some(Block { num: 43, ts: 1694095338000, hash: H256::random() });
+		tracker
+			.set_core_candidates(H256::random(), vec![], &bitfields, &tracker_storage)
+			.await;
+		assert_eq!(tracker.included_count, Some(1));
+		assert!(tracker.core_candidates[&3].is_included());
+		assert!(tracker.core_candidates[&5].is_pending());
+		assert_eq!(tracker.core_candidates[&5].current_availability_bits, 1);
+
+		let mut mock_stats = MockStats::default();
+		mock_stats.expect_on_included_candidates().with(eq(1)).once().returning(|_| ());
+		let mut mock_metrics = MockPrometheusMetrics::default();
+		mock_metrics
+			.expect_on_core_candidates()
+			.with(eq(1), eq(2), eq(100))
+			.once()
+			.returning(|_, _, _| ());
+		let mut progress = ParachainProgressUpdate::default();
+		let mut updates = vec![];
+		tracker.notify_core_candidates(&mut progress, &mut updates);
+		for update in updates {
+			update.apply(100, &mut mock_stats, &mock_metrics);
+		}
+		assert_eq!(progress.included_count, Some(1));
+		assert_eq!(
+			progress
+				.cores
+				.iter()
+				.map(|core| (core.core_index, core.state))
+				.collect::<Vec<_>>(),
+			vec![(3, "included"), (5, "pending")]
+		);
+
+		// The included candidate is not tracked anymore
+		tracker.maybe_reset_state();
+		assert_eq!(tracker.core_candidates.keys().copied().collect::<Vec<_>>(), vec![5]);
+	}
+
+	#[tokio::test]
+	async fn test_includes_core_dispatch_latency() {
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, create_storage());
+		let mut mock_stats = MockStats::default();
+		mock_stats.expect_on_bitfields().returning(|_, _| ());
+		mock_stats.expect_on_block().returning(|_| ());
+		mock_stats.expect_on_backed().returning(|_, _, _| ());
+		let mut mock_metrics = MockPrometheusMetrics::default();
+		mock_metrics.expect_on_bitfields().returning(|_, _, _| ());
+		mock_metrics.expect_on_block().returning(|_, _| ());
+		mock_metrics.expect_on_backed().returning(|_, _, _| ());
+
+		// Backed without being scheduled before
+		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash: H256::random() });
+		tracker.current_candidate.set_backed();
+		tracker.current_candidate.candidate_hash = Some(H256::random());
+		mock_stats.expect_on_core_dispatch().times(0).returning(|_| ());
+		mock_metrics.expect_on_core_dispatch().times(0).returning(|_, _, _| ());
+		let _progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+
+		// Backed two blocks after being scheduled
+		tracker.core_dispatch = vec![(2, Some(Duration::from_secs(12)))];
+		mock_stats.expect_on_core_dispatch().with(eq(2)).once().returning(|_| ());
+		mock_metrics
+			.expect_on_core_dispatch()
+			.with(eq(2), eq(Some(Duration::from_secs(12))), eq(100))
+			.once()
+			.returning(|_, _, _| ());
+		let _progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+	}
+
 	#[tokio::test]
 	async fn test_includes_candidate_state() {
 		let candidate_hash = H256::random();
 		let storage = create_storage();
-		let mut tracker = SubxtTracker::new(100);
+		let mut tracker = SubxtTracker::new(100, None, None);
 		let tracker_storage = TrackerStorage::new(100, create_storage());
 		let mut mock_stats = MockStats::default();
 		mock_stats.expect_on_bitfields().returning(|_, _| ());
@@ -1056,19 +2581,31 @@ mod test_progress {
 		assert!(progress
 			.events
 			.iter()
-			.any(|e| matches!(e, ParachainConsensusEvent::SkippedSlot)));
+			.any(|e| matches!(e, ParachainConsensusEvent::SkippedSlot(SkippedSlotReason::NotScheduled))));
 
 		// When candidate is backed
 		tracker.current_candidate.set_backed();
+		tracker.current_candidate.set_candidate(create_backed_candidate(100));
 		tracker.current_candidate.candidate_hash = Some(candidate_hash);
-		mock_stats.expect_on_backed().once().returning(|| ());
-		mock_metrics.expect_on_backed().with(eq(100)).once().returning(|_| ());
+		mock_stats
+			.expect_on_backed()
+			.with(eq(0), eq(1), eq(MINIMUM_BACKING_VOTES))
+			.once()
+			.returning(|_, _, _| ());
+		mock_metrics
+			.expect_on_backed()
+			.with(eq(0), eq(1), eq(100))
+			.once()
+			.returning(|_, _, _| ());
 		let progress = tracker
 			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
 			.await
 			.unwrap();
 
-		assert!(progress.events.iter().any(|e| matches!(e, ParachainConsensusEvent::Backed(_))));
+		assert!(progress
+			.events
+			.iter()
+			.any(|e| matches!(e, ParachainConsensusEvent::Backed(_, 0, 1))));
 
 		// When candidate is pending
 		// And data is available
@@ -1079,14 +2616,14 @@ mod test_progress {
 		tracker.current_candidate.bitfield_count = 150;
 		mock_stats
 			.expect_on_included()
-			.with(eq(42), eq(Some(41)), eq(None))
+			.with(eq(42), eq(Some(41)), eq(None), eq(Some(Duration::from_secs(6))), eq(None))
 			.once()
-			.returning(|_, _, _| ());
+			.returning(|_, _, _, _, _| ());
 		mock_metrics
 			.expect_on_included()
-			.with(eq(42), eq(Some(41)), eq(None), eq(Some(Duration::from_secs(6))), eq(100))
+			.with(eq(42), eq(Some(41)), eq(None), eq(Some(Duration::from_secs(6))), eq(None), eq(100))
 			.once()
-			.returning(|_, _, _, _, _| ());
+			.returning(|_, _, _, _, _, _| ());
 		let progress = tracker
 			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
 			.await
@@ -1134,14 +2671,14 @@ mod test_progress {
 		tracker.current_candidate.bitfield_count = 150;
 		mock_stats
 			.expect_on_included()
-			.with(eq(42), eq(Some(41)), eq(None))
+			.with(eq(42), eq(Some(41)), eq(None), eq(Some(Duration::from_secs(6))), eq(None))
 			.once()
-			.returning(|_, _, _| ());
+			.returning(|_, _, _, _, _| ());
 		mock_metrics
 			.expect_on_included()
-			.with(eq(42), eq(Some(41)), eq(None), eq(Some(Duration::from_secs(6))), eq(100))
+			.with(eq(42), eq(Some(41)), eq(None), eq(Some(Duration::from_secs(6))), eq(None), eq(100))
 			.once()
-			.returning(|_, _, _, _, _| ());
+			.returning(|_, _, _, _, _, _| ());
 		let progress = tracker
 			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
 			.await
@@ -1180,4 +2717,215 @@ mod test_progress {
 			.iter()
 			.any(|e| matches!(e, ParachainConsensusEvent::SlowAvailability(_, _))));
 	}
+
+	#[test]
+	fn test_classifies_skipped_slots() {
+		let mut tracker = SubxtTracker::new(100, None, None);
+		tracker.current_relay_block = Some(Block { num: 42, ts: 1694095332000, hash: H256::random() });
+		tracker.current_candidate.set_idle();
+		assert_eq!(tracker.skipped_slot_reason(), Some(SkippedSlotReason::NotScheduled));
+
+		tracker.current_candidate.assigned_cores = vec![3];
+		tracker.current_candidate.core_occupied = true;
+		assert_eq!(tracker.skipped_slot_reason(), Some(SkippedSlotReason::CoreOccupied));
+		tracker.current_candidate.core_occupied = false;
+		assert_eq!(tracker.skipped_slot_reason(), Some(SkippedSlotReason::NoCandidate));
+
+		// Backed in the inherent without votes of a group of three, no event
+		let mut candidate = create_backed_candidate(100);
+		candidate.validator_indices = DecodedBits::from_iter([true, true, false]);
+		tracker.current_candidate.set_backed();
+		tracker.current_candidate.set_candidate(candidate);
+		tracker.last_backed_at_block_number = Some(42);
+		tracker.inclusion_checks = vec![(H256::random(), Some(InclusionMismatch::BackedWithoutEvent))];
+		assert_eq!(tracker.skipped_slot_reason(), Some(SkippedSlotReason::InsufficientBacking));
+		tracker.inclusion_checks = vec![(H256::random(), None)];
+		assert_eq!(tracker.skipped_slot_reason(), None);
+	}
+
+	#[tokio::test]
+	async fn test_includes_availability_time() {
+		let candidate_hash = H256::random();
+		let storage = create_storage();
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		let mut mock_stats = MockStats::default();
+		mock_stats.expect_on_bitfields().returning(|_, _| ());
+		mock_stats.expect_on_block().returning(|_| ());
+		mock_stats.expect_on_included().returning(|_, _, _, _, _| ());
+		let mut mock_metrics = MockPrometheusMetrics::default();
+		mock_metrics.expect_on_bitfields().returning(|_, _, _| ());
+		mock_metrics.expect_on_block().returning(|_, _| ());
+		mock_metrics.expect_on_included().returning(|_, _, _, _, _, _| ());
+		storage_write(
+			CollectorPrefixType::Candidate(100),
+			candidate_hash,
+			create_candidate_record(100, 42, H256::random(), 40),
+			&storage,
+		)
+		.await
+		.unwrap();
+
+		// Backed at #42, 2/3 of availability bits set at #45
+		tracker.current_relay_block = Some(Block { num: 45, ts: 1694095350000, hash: H256::random() });
+		tracker.current_candidate.set_pending();
+		tracker.current_candidate.candidate_hash = Some(candidate_hash);
+		tracker.current_candidate.max_availability_bits = 200;
+		tracker.current_candidate.current_availability_bits = 140;
+		mock_metrics
+			.expect_on_availability_time()
+			.with(eq(3), eq(100))
+			.once()
+			.returning(|_, _| ());
+		let progress = tracker
+			.progress(&mut mock_stats, &mock_metrics, &tracker_storage)
+			.await
+			.unwrap();
+
+		assert!(progress
+			.events
+			.iter()
+			.any(|e| matches!(e, ParachainConsensusEvent::Included(_, _, _))));
+	}
+}
+
+#[cfg(test)]
+mod test_core_dispatch {
+	use super::*;
+	use crate::{
+		prometheus::MockPrometheusMetrics,
+		stats::MockStats,
+		test_utils::{create_backed_candidate, create_candidate_record, create_storage, storage_write},
+	};
+	use mockall::predicate::eq;
+	use polkadot_introspector_essentials::{api::storage::RequestExecutor, collector::CollectorPrefixType};
+
+	/// Processes the availability cores of a relay block, returns the updates of the core dispatch delays
+	async fn inject_cores(
+		tracker: &mut SubxtTracker,
+		storage: &RequestExecutor<H256, CollectorPrefixType>,
+		num: u32,
+		assigned: &[u32],
+		occupied: &[u32],
+		backed: &[(&BackedCandidate<H256>, u32)],
+	) -> Vec<BlockUpdate> {
+		let hash = H256::random();
+		let assignments: BTreeMap<u32, Vec<u32>> = assigned.iter().map(|core| (*core, vec![100])).collect();
+		let occupied_cores: Vec<CoreOccupied> = (0..8)
+			.map(|core| if occupied.contains(&core) { CoreOccupied::Paras } else { CoreOccupied::Free })
+			.collect();
+		storage_write(CollectorPrefixType::CoreAssignments, hash, assignments, storage)
+			.await
+			.unwrap();
+		storage_write(CollectorPrefixType::OccupiedCores, hash, occupied_cores, storage)
+			.await
+			.unwrap();
+		for (candidate, core) in backed {
+			let mut record = create_candidate_record(100, num, H256::random(), num - 1);
+			record.candidate_inclusion.core_idx = Some(*core);
+			storage_write(CollectorPrefixType::Candidate(100), candidate_hash(candidate), record, storage)
+				.await
+				.unwrap();
+		}
+
+		let tracker_storage = TrackerStorage::new(100, storage.clone());
+		tracker.previous_relay_block = tracker.current_relay_block;
+		tracker.current_relay_block = Some(Block { num, ts: num as u64 * 6000, hash });
+		tracker.set_core_assignment(hash, &tracker_storage).await.unwrap();
+		let candidates = backed.iter().map(|(candidate, _)| (*candidate).clone()).collect();
+		tracker.set_core_candidates(hash, candidates, &[], &tracker_storage).await;
+		tracker.set_core_dispatch();
+		let mut updates = vec![];
+		tracker.notify_core_dispatch(&mut updates);
+		tracker.maybe_reset_state();
+
+		updates
+	}
+
+	/// Checks that exactly the given delays in blocks and seconds are recorded
+	fn assert_dispatched(updates: Vec<BlockUpdate>, delays: &[(u32, u64)]) {
+		let mut mock_stats = MockStats::default();
+		let mut mock_metrics = MockPrometheusMetrics::default();
+		for &(blocks, secs) in delays {
+			mock_stats.expect_on_core_dispatch().with(eq(blocks)).once().returning(|_| ());
+			mock_metrics
+				.expect_on_core_dispatch()
+				.with(eq(blocks), eq(Some(Duration::from_secs(secs))), eq(100))
+				.once()
+				.returning(|_, _, _| ());
+		}
+		for update in updates {
+			update.apply(100, &mut mock_stats, &mock_metrics);
+		}
+	}
+
+	#[tokio::test]
+	async fn test_measures_from_scheduling_to_backing() {
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let storage = create_storage();
+		let candidate = create_backed_candidate(100);
+
+		// Scheduled on a free core, nothing backed for two blocks
+		assert_dispatched(inject_cores(&mut tracker, &storage, 40, &[3], &[], &[]).await, &[]);
+		assert_dispatched(inject_cores(&mut tracker, &storage, 41, &[3], &[], &[]).await, &[]);
+		assert_eq!(tracker.scheduled_at[&3].num, 40);
+
+		// Backed in the second block after the scheduling
+		assert_dispatched(inject_cores(&mut tracker, &storage, 42, &[3], &[3], &[(&candidate, 3)]).await, &[(2, 12)]);
+		assert!(tracker.scheduled_at.is_empty());
+
+		// The occupied core is not scheduled again
+		assert_dispatched(inject_cores(&mut tracker, &storage, 43, &[3], &[3], &[]).await, &[]);
+		assert!(tracker.scheduled_at.is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_measures_from_timeout_to_next_backing() {
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let storage = create_storage();
+		let first = create_backed_candidate(100);
+		let second = create_backed_candidate(100);
+
+		assert_dispatched(inject_cores(&mut tracker, &storage, 40, &[3], &[], &[]).await, &[]);
+		assert_dispatched(inject_cores(&mut tracker, &storage, 41, &[3], &[3], &[(&first, 3)]).await, &[(1, 6)]);
+
+		// The candidate times out, the freed core is scheduled again
+		let mut record = create_candidate_record(100, 41, H256::random(), 40);
+		record.candidate_inclusion.core_idx = Some(3);
+		record.candidate_inclusion.timedout = Some(43);
+		storage_write(CollectorPrefixType::Candidate(100), candidate_hash(&first), record, &storage)
+			.await
+			.unwrap();
+		assert_dispatched(inject_cores(&mut tracker, &storage, 42, &[3], &[3], &[]).await, &[]);
+		assert_dispatched(inject_cores(&mut tracker, &storage, 43, &[3], &[], &[]).await, &[]);
+		assert_eq!(tracker.scheduled_at[&3].num, 43);
+		assert!(tracker.core_candidates.is_empty());
+
+		// The next candidate is measured from the timeout
+		assert_dispatched(inject_cores(&mut tracker, &storage, 45, &[3], &[3], &[(&second, 3)]).await, &[(2, 12)]);
+	}
+
+	#[tokio::test]
+	async fn test_measures_each_core() {
+		let mut tracker = SubxtTracker::new(100, None, None);
+		let storage = create_storage();
+		let first = create_backed_candidate(100);
+		let second = create_backed_candidate(100);
+
+		// Scheduled on two free cores at once, a candidate is backed on each of them in turn
+		assert_dispatched(inject_cores(&mut tracker, &storage, 40, &[3, 5], &[], &[]).await, &[]);
+		assert_dispatched(inject_cores(&mut tracker, &storage, 41, &[3, 5], &[3], &[(&first, 3)]).await, &[(1, 6)]);
+		assert_eq!(tracker.scheduled_at.keys().copied().collect::<Vec<_>>(), vec![5]);
+		assert_dispatched(
+			inject_cores(&mut tracker, &storage, 43, &[3, 5], &[3, 5], &[(&second, 5)]).await,
+			&[(3, 18)],
+		);
+		assert!(tracker.scheduled_at.is_empty());
+
+		// Scheduling on a core taken away from the parachain lapses without a delay
+		assert_dispatched(inject_cores(&mut tracker, &storage, 44, &[3, 5, 7], &[3, 5], &[]).await, &[]);
+		assert_eq!(tracker.scheduled_at.keys().copied().collect::<Vec<_>>(), vec![7]);
+		assert_dispatched(inject_cores(&mut tracker, &storage, 45, &[3, 5], &[3, 5], &[]).await, &[]);
+		assert!(tracker.scheduled_at.is_empty());
+	}
 }
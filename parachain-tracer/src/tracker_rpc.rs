@@ -18,7 +18,7 @@ use async_trait::async_trait;
 use mockall::automock;
 use polkadot_introspector_essentials::{
 	api::subxt_wrapper::{RequestExecutor, SubxtHrmpChannel, SubxtWrapperError},
-	types::H256,
+	types::{BlockNumber, H256},
 };
 use std::collections::BTreeMap;
 
@@ -33,6 +33,12 @@ pub trait TrackerRpc {
 		&mut self,
 		block_hash: H256,
 	) -> color_eyre::Result<BTreeMap<u32, SubxtHrmpChannel>, SubxtWrapperError>;
+	async fn downward_queue_len(&mut self, block_hash: H256) -> color_eyre::Result<u32, SubxtWrapperError>;
+	async fn upward_queue_len(&mut self, block_hash: H256) -> color_eyre::Result<u32, SubxtWrapperError>;
+	async fn para_head_number(
+		&mut self,
+		block_hash: H256,
+	) -> color_eyre::Result<Option<BlockNumber>, SubxtWrapperError>;
 }
 
 pub struct ParachainTrackerRpc {
@@ -69,6 +75,27 @@ impl TrackerRpc for ParachainTrackerRpc {
 			.get_outbound_hrmp_channels(self.node.as_str(), block_hash, self.para_id)
 			.await
 	}
+
+	async fn downward_queue_len(&mut self, block_hash: H256) -> color_eyre::Result<u32, SubxtWrapperError> {
+		self.executor
+			.get_downward_queue_len(self.node.as_str(), block_hash, self.para_id)
+			.await
+	}
+
+	async fn upward_queue_len(&mut self, block_hash: H256) -> color_eyre::Result<u32, SubxtWrapperError> {
+		self.executor
+			.get_upward_queue_len(self.node.as_str(), block_hash, self.para_id)
+			.await
+	}
+
+	async fn para_head_number(
+		&mut self,
+		block_hash: H256,
+	) -> color_eyre::Result<Option<BlockNumber>, SubxtWrapperError> {
+		self.executor
+			.get_para_head_number(self.node.as_str(), block_hash, self.para_id)
+			.await
+	}
 }
 
 #[cfg(test)]
@@ -101,4 +128,19 @@ mod tests {
 
 		assert!(response.is_ok());
 	}
+
+	#[tokio::test]
+	async fn test_fetches_message_queue_lengths() {
+		let (mut rpc, block_hash) = setup_client().await;
+
+		assert!(rpc.downward_queue_len(block_hash).await.is_ok());
+		assert!(rpc.upward_queue_len(block_hash).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn test_fetches_para_head_number() {
+		let (mut rpc, block_hash) = setup_client().await;
+
+		assert!(rpc.para_head_number(block_hash).await.is_ok());
+	}
 }
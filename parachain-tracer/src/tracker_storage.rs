@@ -16,11 +16,11 @@
 
 use polkadot_introspector_essentials::{
 	api::{storage::RequestExecutor, subxt_wrapper::InherentData},
-	collector::{candidate_record::CandidateRecord, CollectorPrefixType, DisputeInfo},
+	collector::{block_context::BlockContext, candidate_record::CandidateRecord, CollectorPrefixType, DisputeInfo},
 	metadata::polkadot_primitives::ValidatorIndex,
-	types::{AccountId32, CoreOccupied, OnDemandOrder, Timestamp, H256},
+	types::{AccountId32, CoreOccupied, ExtrinsicWeight, Header, OnDemandOrder, Timestamp, H256},
 };
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::Arc};
 use subxt::config::{substrate::BlakeTwo256, Hasher};
 
 pub struct TrackerStorage {
@@ -28,11 +28,26 @@ pub struct TrackerStorage {
 	para_id: u32,
 	/// API to access collector's storage
 	storage: RequestExecutor<H256, CollectorPrefixType>,
+	/// Contexts of the relay chain blocks of the last new head event, shared by all trackers
+	block_contexts: Vec<Arc<BlockContext>>,
 }
 
 impl TrackerStorage {
 	pub fn new(para_id: u32, storage: RequestExecutor<H256, CollectorPrefixType>) -> Self {
-		Self { para_id, storage }
+		Self { para_id, storage, block_contexts: vec![] }
+	}
+
+	/// Sets the contexts of the relay chain blocks being processed, the block data is taken from them
+	/// instead of being read and decoded from the collector's storage
+	pub fn set_block_contexts(&mut self, block_contexts: Vec<Arc<BlockContext>>) {
+		self.block_contexts = block_contexts;
+	}
+
+	fn block_context(&self, block_hash: H256) -> Option<&BlockContext> {
+		self.block_contexts
+			.iter()
+			.find(|context| context.block_hash == block_hash)
+			.map(Arc::as_ref)
 	}
 
 	/// Reads validators account keys for the given session index
@@ -47,10 +62,43 @@ impl TrackerStorage {
 	}
 
 	/// Reads inherent data of a relay block by its block hash
-	pub async fn inherent_data(&self, block_hash: H256) -> Option<InherentData> {
+	pub async fn inherent_data(&self, block_hash: H256) -> Option<Arc<InherentData>> {
+		if let Some(context) = self.block_context(block_hash) {
+			return context.inherent_data.clone()
+		}
 		self.storage
 			.storage_read_prefixed(CollectorPrefixType::InherentData, block_hash)
 			.await
+			.map(|v| Arc::new(v.into_inner().unwrap()))
+	}
+
+	/// Reads the actual weight of the paras inherent of the given block
+	pub async fn para_inherent_weight(&self, block_hash: H256) -> Option<ExtrinsicWeight> {
+		self.storage
+			.storage_read_prefixed(CollectorPrefixType::ParaInherentWeight, block_hash)
+			.await
+			.map(|v| v.into_inner().unwrap())
+	}
+
+	/// Reads the runtime spec version active at the given block
+	pub async fn spec_version(&self, block_hash: H256) -> Option<u32> {
+		if let Some(context) = self.block_context(block_hash) {
+			return Some(context.spec_version)
+		}
+		self.storage
+			.storage_read_prefixed(CollectorPrefixType::SpecVersion, block_hash)
+			.await
+			.map(|v| v.into_inner().unwrap())
+	}
+
+	/// Reads the session index of the given block
+	pub async fn session_index(&self, block_hash: H256) -> Option<u32> {
+		if let Some(context) = self.block_context(block_hash) {
+			return Some(context.session_index)
+		}
+		self.storage
+			.storage_read_prefixed(CollectorPrefixType::SessionIndex, block_hash)
+			.await
 			.map(|v| v.into_inner().unwrap())
 	}
 
@@ -62,6 +110,15 @@ impl TrackerStorage {
 			.map(|v| v.into_inner().unwrap())
 	}
 
+	/// Reads all on-demand orders placed in the given block
+	pub async fn on_demand_orders(&self, block_hash: H256) -> Vec<OnDemandOrder> {
+		self.storage
+			.storage_read_prefixed(CollectorPrefixType::OnDemandOrders, block_hash)
+			.await
+			.map(|v| v.into_inner().unwrap())
+			.unwrap_or_default()
+	}
+
 	/// Reads the last finalized block number at the moment, when the given block has appeared
 	pub async fn relevant_finalized_block_number(&self, block_hash: H256) -> Option<u32> {
 		self.storage
@@ -86,16 +143,52 @@ impl TrackerStorage {
 			.map(|v| v.into_inner().unwrap())
 	}
 
+	/// Reads a candidate of any parachain by its hash, used by the relay chain wide trackers
+	pub async fn any_candidate(&self, candidate_hash: H256) -> Option<CandidateRecord> {
+		let para_id: u32 = self
+			.storage
+			.storage_read_prefixed(CollectorPrefixType::CandidatesParachains, candidate_hash)
+			.await?
+			.into_inner()
+			.ok()?;
+		self.para_candidate(para_id, candidate_hash).await
+	}
+
+	/// Reads a candidate of another parachain, used by the relay chain wide trackers
+	pub async fn para_candidate(&self, para_id: u32, candidate_hash: H256) -> Option<CandidateRecord> {
+		self.storage
+			.storage_read_prefixed(CollectorPrefixType::Candidate(para_id), candidate_hash)
+			.await
+			.map(|v| v.into_inner().unwrap())
+	}
+
 	/// Read the timestamp for the given relay block
 	pub async fn block_timestamp(&self, block_hash: H256) -> Option<Timestamp> {
+		if let Some(context) = self.block_context(block_hash) {
+			return Some(context.timestamp)
+		}
 		self.storage
 			.storage_read_prefixed(CollectorPrefixType::Timestamp, block_hash)
 			.await
 			.map(|v| v.into_inner().unwrap())
 	}
 
+	/// Read the parent hash of the given relay block
+	pub async fn parent_hash(&self, block_hash: H256) -> Option<H256> {
+		if let Some(context) = self.block_context(block_hash) {
+			return Some(context.header.parent_hash)
+		}
+		self.storage
+			.storage_read_prefixed(CollectorPrefixType::RelayBlockHeader, block_hash)
+			.await
+			.map(|v| v.into_inner::<Header>().unwrap().parent_hash)
+	}
+
 	/// Read the occupied cores for the given relay block
 	pub async fn occupied_cores(&self, block_hash: H256) -> Option<Vec<CoreOccupied>> {
+		if let Some(context) = self.block_context(block_hash) {
+			return Some(context.occupied_cores.clone())
+		}
 		self.storage
 			.storage_read_prefixed(CollectorPrefixType::OccupiedCores, block_hash)
 			.await
@@ -104,6 +197,9 @@ impl TrackerStorage {
 
 	/// Read the backing groups for the given relay block
 	pub async fn backing_groups(&self, block_hash: H256) -> Option<Vec<Vec<ValidatorIndex>>> {
+		if let Some(context) = self.block_context(block_hash) {
+			return Some(context.backing_groups.clone())
+		}
 		self.storage
 			.storage_read_prefixed(CollectorPrefixType::BackingGroups, block_hash)
 			.await
@@ -112,6 +208,9 @@ impl TrackerStorage {
 
 	/// Read the core assignments for the given relay block
 	pub async fn core_assignments(&self, block_hash: H256) -> Option<BTreeMap<u32, Vec<u32>>> {
+		if let Some(context) = self.block_context(block_hash) {
+			return Some(context.core_assignments.clone())
+		}
 		self.storage
 			.storage_read_prefixed(CollectorPrefixType::CoreAssignments, block_hash)
 			.await
@@ -134,8 +233,11 @@ mod tests {
 	use subxt::utils::AccountId32;
 
 	fn setup_client() -> (TrackerStorage, CollectorStorageApi) {
-		let api: CollectorStorageApi =
-			ApiService::new_with_prefixed_storage(RecordsStorageConfig { max_blocks: 4 }, Default::default());
+		let api: CollectorStorageApi = ApiService::new_with_prefixed_storage(
+			RecordsStorageConfig { max_blocks: 4 },
+			Default::default(),
+			Default::default(),
+		);
 		let storage = TrackerStorage::new(100, api.storage());
 
 		(storage, api)
@@ -182,6 +284,42 @@ mod tests {
 		assert_eq!(storage_data.parent_header.parent_hash, parent_hash);
 	}
 
+	#[tokio::test]
+	async fn test_reads_block_contexts() {
+		let (mut storage, _api) = setup_client();
+		let hash = H256::random();
+		let parent_hash = H256::random();
+		let context = Arc::new(BlockContext {
+			block_hash: hash,
+			block_number: 42,
+			header: Header {
+				parent_hash,
+				number: 42,
+				state_root: Default::default(),
+				extrinsics_root: Default::default(),
+				digest: Default::default(),
+			},
+			timestamp: 1694095332000,
+			session_index: 5,
+			spec_version: 1000,
+			inherent_data: Some(Arc::new(create_inherent_data(100))),
+			occupied_cores: vec![CoreOccupied::Paras],
+			backing_groups: vec![vec![ValidatorIndex(1)]],
+			core_assignments: BTreeMap::from([(0, vec![100])]),
+		});
+		storage.set_block_contexts(vec![context.clone()]);
+
+		// The inherent data is shared, not decoded again
+		let inherent_data = storage.inherent_data(hash).await.unwrap();
+		assert!(Arc::ptr_eq(&inherent_data, context.inherent_data.as_ref().unwrap()));
+		assert_eq!(storage.block_timestamp(hash).await, Some(1694095332000));
+		assert_eq!(storage.parent_hash(hash).await, Some(parent_hash));
+		assert_eq!(storage.session_index(hash).await, Some(5));
+		assert_eq!(storage.core_assignments(hash).await, Some(BTreeMap::from([(0, vec![100])])));
+		// Other blocks are read from the collector's storage
+		assert!(storage.block_timestamp(H256::random()).await.is_none());
+	}
+
 	#[tokio::test]
 	async fn test_reads_on_demand_order() {
 		let (storage, api) = setup_client();
@@ -0,0 +1,547 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Interactive terminal interface showing a live table of the traced parachains and a detail pane of the selected
+//! one. The table is sorted by skipped slots, disputes or block time with the keyboard, the recent block times of every
+//! parachain are drawn as a sparkline.
+
+use crate::{
+	para_names::DisplayPara, progress_record::ProgressRecord, stats::StatsSummary, types::ParachainProgressUpdate,
+};
+use crossterm::{
+	event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+	execute,
+	terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use log::warn;
+use polkadot_introspector_essentials::{
+	charts::{self, TimeSeries},
+	types::BlockNumber,
+};
+use ratatui::{
+	backend::{Backend, CrosstermBackend},
+	layout::{Constraint, Direction, Layout},
+	style::{Color, Modifier, Style},
+	widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+	Frame, Terminal,
+};
+use std::{
+	collections::{HashMap, VecDeque},
+	io::{self, Stdout, Write},
+	sync::{Arc, Mutex, PoisonError},
+	time::Duration,
+};
+use tokio::sync::{
+	broadcast::Sender as BroadcastSender,
+	mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+
+/// Interval between redraws of the interface
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+/// Number of recent events kept in the detail pane of a parachain
+const RECENT_EVENTS: usize = 20;
+/// Number of recent block times kept for the sparklines of a parachain
+const RECENT_BLOCK_TIMES: usize = 64;
+/// Width of the block times sparkline in the parachains table, in characters of two blocks each
+const TABLE_SPARKLINE_WIDTH: usize = 8;
+/// Size of the log kept while the interface takes the terminal, the oldest lines are dropped beyond it
+const MAX_LOG_BUFFER: usize = 1 << 20;
+
+/// Log writer keeping the log while the interface takes the terminal, the log is written to stderr otherwise
+#[derive(Clone, Default)]
+pub(crate) struct LogBuffer(Arc<Mutex<LogBufferState>>);
+
+#[derive(Default)]
+struct LogBufferState {
+	buffer: Vec<u8>,
+	is_held: bool,
+}
+
+impl LogBuffer {
+	/// Keeps the following records until released
+	fn hold(&self) {
+		self.0.lock().unwrap_or_else(PoisonError::into_inner).is_held = true;
+	}
+
+	/// Writes the kept log to stderr, the following records go to stderr directly
+	fn release(&self) {
+		// Called by the panic hook as well, where the lock may be poisoned
+		let mut state = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+		state.is_held = false;
+		let _ = io::stderr().write_all(&std::mem::take(&mut state.buffer));
+	}
+
+	/// Restores the terminal if the interface still holds it and writes the kept log to stderr,
+	/// used when the tracer stops with an error before the interface is closed
+	pub(crate) fn close(&self) {
+		let is_held = self.0.lock().unwrap_or_else(PoisonError::into_inner).is_held;
+		if is_held {
+			restore_terminal(&mut io::stdout());
+			self.release();
+		}
+	}
+}
+
+impl Write for LogBuffer {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let mut state = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+		if !state.is_held {
+			return io::stderr().write(buf)
+		}
+
+		state.buffer.extend_from_slice(buf);
+		if state.buffer.len() > MAX_LOG_BUFFER {
+			let excess = state.buffer.len() - MAX_LOG_BUFFER / 2;
+			let cut = state.buffer[excess..]
+				.iter()
+				.position(|byte| *byte == b'\n')
+				.map_or(state.buffer.len(), |pos| excess + pos + 1);
+			state.buffer.drain(..cut);
+		}
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Column the parachains table is sorted by
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum SortKey {
+	#[default]
+	ParaId,
+	/// Most skipped slots first
+	SkippedSlots,
+	/// Most disputes first
+	Disputes,
+	/// Slowest average block time first
+	BlockTime,
+}
+
+enum TuiEvent {
+	Progress {
+		para_id: u32,
+		block_number: BlockNumber,
+		block_time_sec: Option<f64>,
+		line: String,
+		summary: StatsSummary,
+	},
+	Key(KeyEvent),
+}
+
+/// Sends the updates of the parachain trackers to the interface
+#[derive(Clone)]
+pub(crate) struct TuiHandle(UnboundedSender<TuiEvent>);
+
+impl TuiHandle {
+	/// Updates the row and the detail pane of a parachain
+	pub(crate) fn on_progress(&self, progress: &ParachainProgressUpdate, summary: StatsSummary) {
+		let _ = self.0.send(TuiEvent::Progress {
+			para_id: progress.para_id,
+			block_number: progress.block_number,
+			block_time_sec: (!progress.is_fork && progress.prev_timestamp > 0)
+				.then(|| progress.timestamp.saturating_sub(progress.prev_timestamp) as f64 / 1000.0),
+			line: ProgressRecord::from(progress).to_string(),
+			summary,
+		});
+	}
+}
+
+struct ParaPane {
+	summary: StatsSummary,
+	last_block: BlockNumber,
+	block_times: TimeSeries,
+	recent: VecDeque<String>,
+}
+
+impl Default for ParaPane {
+	fn default() -> Self {
+		Self {
+			summary: Default::default(),
+			last_block: Default::default(),
+			block_times: TimeSeries::new(RECENT_BLOCK_TIMES),
+			recent: Default::default(),
+		}
+	}
+}
+
+impl ParaPane {
+	fn push_recent(&mut self, line: String) {
+		if self.recent.len() == RECENT_EVENTS {
+			self.recent.pop_front();
+		}
+		self.recent.push_back(line);
+	}
+}
+
+/// State of the interface, independent of the terminal
+#[derive(Default)]
+pub(crate) struct TuiState {
+	paras: HashMap<u32, ParaPane>,
+	sort_by: SortKey,
+	/// Selected parachain, kept when the table is resorted
+	selected: Option<u32>,
+}
+
+impl TuiState {
+	fn on_progress(
+		&mut self,
+		para_id: u32,
+		block_number: BlockNumber,
+		block_time_sec: Option<f64>,
+		line: String,
+		summary: StatsSummary,
+	) {
+		let pane = self.paras.entry(para_id).or_default();
+		pane.summary = summary;
+		pane.last_block = pane.last_block.max(block_number);
+		if let Some(block_time_sec) = block_time_sec {
+			pane.block_times.push(block_time_sec);
+		}
+		pane.push_recent(line);
+		self.selected.get_or_insert(para_id);
+	}
+
+	/// Handles a key press, returns `false` if the interface should be closed
+	fn on_key(&mut self, key: KeyEvent) -> bool {
+		if key.kind == KeyEventKind::Release {
+			return true
+		}
+		match key.code {
+			KeyCode::Char('q') | KeyCode::Esc => return false,
+			KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+			KeyCode::Char('p') => self.sort_by = SortKey::ParaId,
+			KeyCode::Char('s') => self.sort_by = SortKey::SkippedSlots,
+			KeyCode::Char('d') => self.sort_by = SortKey::Disputes,
+			KeyCode::Char('t') => self.sort_by = SortKey::BlockTime,
+			KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+			KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+			_ => {},
+		}
+		true
+	}
+
+	fn move_selection(&mut self, offset: isize) {
+		let sorted = self.sorted();
+		if sorted.is_empty() {
+			return
+		}
+		let position = self.selected_position(&sorted).unwrap_or_default();
+		let position = position.saturating_add_signed(offset).min(sorted.len() - 1);
+		self.selected = Some(sorted[position]);
+	}
+
+	fn selected_position(&self, sorted: &[u32]) -> Option<usize> {
+		sorted.iter().position(|para_id| Some(*para_id) == self.selected)
+	}
+
+	/// Returns parachain ids in the order of the table
+	pub(crate) fn sorted(&self) -> Vec<u32> {
+		let mut para_ids: Vec<u32> = self.paras.keys().copied().collect();
+		para_ids.sort_by(|a, b| {
+			let (pane_a, pane_b) = (&self.paras[a].summary, &self.paras[b].summary);
+			let ordering = match self.sort_by {
+				SortKey::ParaId => std::cmp::Ordering::Equal,
+				SortKey::SkippedSlots => pane_b.skipped_slots.cmp(&pane_a.skipped_slots),
+				SortKey::Disputes => pane_b.disputes.cmp(&pane_a.disputes),
+				SortKey::BlockTime => pane_b
+					.avg_block_time_sec
+					.unwrap_or_default()
+					.total_cmp(&pane_a.avg_block_time_sec.unwrap_or_default()),
+			};
+			ordering.then(a.cmp(b))
+		});
+		para_ids
+	}
+
+	fn render<B: Backend>(&self, frame: &mut Frame<B>) {
+		let chunks = Layout::default()
+			.direction(Direction::Vertical)
+			.constraints([Constraint::Percentage(55), Constraint::Min(8), Constraint::Length(1)])
+			.split(frame.size());
+
+		let sorted = self.sorted();
+		let header_style = |key: SortKey| {
+			let style = Style::default().add_modifier(Modifier::BOLD);
+			if key == self.sort_by {
+				style.fg(Color::Yellow)
+			} else {
+				style
+			}
+		};
+		let header = Row::new(vec![
+			Cell::from("Parachain").style(header_style(SortKey::ParaId)),
+			Cell::from("Relay block"),
+			Cell::from("Backed"),
+			Cell::from("Included"),
+			Cell::from("Skipped slots").style(header_style(SortKey::SkippedSlots)),
+			Cell::from("Disputes").style(header_style(SortKey::Disputes)),
+			Cell::from("Block time").style(header_style(SortKey::BlockTime)),
+			Cell::from("Recent"),
+			Cell::from("Inclusion"),
+		]);
+		let rows = sorted.iter().map(|para_id| {
+			let pane = &self.paras[para_id];
+			let summary = &pane.summary;
+			Row::new(vec![
+				Cell::from(DisplayPara(*para_id, summary.para_name.as_deref().map(Into::into)).to_string()),
+				Cell::from(format!("#{}", pane.last_block)),
+				Cell::from(summary.backed_count.to_string()),
+				Cell::from(summary.included_count.to_string()),
+				Cell::from(summary.skipped_slots.to_string())
+					.style(Style::default().fg(if summary.skipped_slots > 0 { Color::Red } else { Color::Reset })),
+				Cell::from(summary.disputes.to_string()).style(Style::default().fg(if summary.disputes > 0 {
+					Color::Red
+				} else {
+					Color::Reset
+				})),
+				Cell::from(format_avg(summary.avg_block_time_sec, "s")),
+				Cell::from(charts::sparkline(&pane.block_times, TABLE_SPARKLINE_WIDTH)),
+				Cell::from(format_avg(summary.avg_inclusion_time_blocks, " blocks")),
+			])
+		});
+		let widths = [
+			Constraint::Percentage(20),
+			Constraint::Percentage(11),
+			Constraint::Percentage(7),
+			Constraint::Percentage(8),
+			Constraint::Percentage(11),
+			Constraint::Percentage(8),
+			Constraint::Percentage(10),
+			Constraint::Length(TABLE_SPARKLINE_WIDTH as u16 + 1),
+			Constraint::Percentage(13),
+		];
+		let table = Table::new(rows)
+			.header(header)
+			.block(Block::default().borders(Borders::ALL).title("Parachains"))
+			.widths(&widths)
+			.highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+		let mut table_state = TableState::default();
+		table_state.select(self.selected_position(&sorted));
+		frame.render_stateful_widget(table, chunks[0], &mut table_state);
+
+		let (title, details) = match self.selected.and_then(|para_id| Some((para_id, self.paras.get(&para_id)?))) {
+			Some((para_id, pane)) => (
+				format!("Parachain {}", DisplayPara(para_id, pane.summary.para_name.as_deref().map(Into::into))),
+				pane_details(pane),
+			),
+			None => ("Parachain".to_string(), "Waiting for the first parachain blocks".to_string()),
+		};
+		frame.render_widget(
+			Paragraph::new(details).block(Block::default().borders(Borders::ALL).title(title)),
+			chunks[1],
+		);
+		frame.render_widget(
+			Paragraph::new("sort: [p]ara id, [s]kipped slots, [d]isputes, block [t]ime | select: up/down | [q]uit"),
+			chunks[2],
+		);
+	}
+}
+
+fn format_avg(value: Option<f64>, unit: &str) -> String {
+	value.map_or_else(|| "-".to_string(), |value| format!("{:.2}{}", value, unit))
+}
+
+/// Statistics of the parachain followed by its recent events, most recent last
+fn pane_details(pane: &ParaPane) -> String {
+	let summary = &pane.summary;
+	let mut lines = vec![
+		format!(
+			"Relay blocks: {}, backed: {} ({} with minimum quorum), included: {}",
+			summary.blocks, summary.backed_count, summary.min_quorum_backed_count, summary.included_count
+		),
+		format!(
+			"Backing time: {}, inclusion time: {}, core dispatch time: {}",
+			format_avg(summary.avg_backing_time_blocks, " blocks"),
+			format_avg(summary.avg_inclusion_time_blocks, " blocks"),
+			format_avg(summary.avg_core_dispatch_time_blocks, " blocks")
+		),
+		format!(
+			"Skipped slots: {}, slow availability: {}, availability timeouts: {}, slow bitfields: {}",
+			summary.skipped_slots,
+			summary.slow_availability,
+			summary.availability_timeouts,
+			summary.slow_bitfields_propagation
+		),
+		format!(
+			"Disputes: {} (valid: {}, invalid: {}), old relay parents: {}, unincluded depth: {} (max {})",
+			summary.disputes,
+			summary.disputes_concluded_valid,
+			summary.disputes_concluded_invalid,
+			summary.old_relay_parents,
+			format_avg(summary.avg_unincluded_depth, ""),
+			summary.max_unincluded_depth
+		),
+		format!(
+			"Recent block times: {} (last: {}, min: {}, max: {})",
+			charts::sparkline(&pane.block_times, RECENT_BLOCK_TIMES / 2),
+			format_avg(pane.block_times.last(), "s"),
+			format_avg(pane.block_times.min(), "s"),
+			format_avg(pane.block_times.max(), "s")
+		),
+		String::new(),
+	];
+	lines.extend(pane.recent.iter().cloned());
+	lines.join("\n")
+}
+
+/// Takes over the terminal and spawns the task drawing the interface, closing the interface shuts the tracer down.
+/// The log is kept in the given buffer until the terminal is restored.
+pub(crate) fn spawn(
+	shutdown_tx: &BroadcastSender<()>,
+	log_buffer: LogBuffer,
+) -> io::Result<(TuiHandle, tokio::task::JoinHandle<()>)> {
+	let terminal = enter_terminal()?;
+	log_buffer.hold();
+	// A panic report would be lost in the alternate screen and leave the terminal in raw mode otherwise
+	let default_hook = std::panic::take_hook();
+	let panic_log_buffer = log_buffer.clone();
+	std::panic::set_hook(Box::new(move |info| {
+		restore_terminal(&mut io::stdout());
+		panic_log_buffer.release();
+		default_hook(info);
+	}));
+
+	let (tx, rx) = unbounded_channel();
+	let keys_tx = tx.clone();
+	// Reading the terminal events blocks, the thread exits with the process
+	std::thread::spawn(move || {
+		while let Ok(event) = event::read() {
+			if let Event::Key(key) = event {
+				if keys_tx.send(TuiEvent::Key(key)).is_err() {
+					break
+				}
+			}
+		}
+	});
+
+	Ok((TuiHandle(tx), tokio::spawn(run(terminal, rx, shutdown_tx.clone(), log_buffer))))
+}
+
+fn enter_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
+	enable_raw_mode()?;
+	let mut stdout = io::stdout();
+	execute!(stdout, EnterAlternateScreen)?;
+	Terminal::new(CrosstermBackend::new(stdout))
+}
+
+fn restore_terminal(out: &mut impl Write) {
+	if let Err(e) = disable_raw_mode().and_then(|_| execute!(out, LeaveAlternateScreen)) {
+		warn!("Cannot restore the terminal: {:?}", e);
+	}
+}
+
+async fn run(
+	mut terminal: Terminal<CrosstermBackend<Stdout>>,
+	mut rx: UnboundedReceiver<TuiEvent>,
+	shutdown_tx: BroadcastSender<()>,
+	log_buffer: LogBuffer,
+) {
+	let mut shutdown_rx = shutdown_tx.subscribe();
+	let mut state = TuiState::default();
+	let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+
+	loop {
+		tokio::select! {
+			Some(event) = rx.recv() => match event {
+				TuiEvent::Progress { para_id, block_number, block_time_sec, line, summary } =>
+					state.on_progress(para_id, block_number, block_time_sec, line, summary),
+				TuiEvent::Key(key) => {
+					if !state.on_key(key) {
+						let _ = shutdown_tx.send(());
+						break
+					}
+					// Redraw at once for the keyboard to feel responsive
+					interval.reset_immediately();
+				},
+			},
+			_ = interval.tick() =>
+				if let Err(e) = terminal.draw(|frame| state.render(frame)) {
+					warn!("Cannot draw the interface: {:?}", e);
+				},
+			_ = shutdown_rx.recv() => break,
+		}
+	}
+
+	restore_terminal(terminal.backend_mut());
+	let _ = terminal.show_cursor();
+	log_buffer.release();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn summary(para_id: u32, skipped_slots: u32, disputes: u32, avg_block_time_sec: f64) -> StatsSummary {
+		StatsSummary {
+			para_id,
+			skipped_slots,
+			disputes,
+			avg_block_time_sec: Some(avg_block_time_sec),
+			..Default::default()
+		}
+	}
+
+	fn key(code: KeyCode) -> KeyEvent {
+		KeyEvent::new(code, KeyModifiers::NONE)
+	}
+
+	#[test]
+	fn test_sorts_parachains() {
+		let mut state = TuiState::default();
+		state.on_progress(1000, 10, None, String::new(), summary(1000, 1, 0, 6.0));
+		state.on_progress(2000, 10, None, String::new(), summary(2000, 5, 0, 12.0));
+		state.on_progress(3000, 10, None, String::new(), summary(3000, 1, 2, 6.5));
+		assert_eq!(state.sorted(), vec![1000, 2000, 3000]);
+
+		state.on_key(key(KeyCode::Char('s')));
+		assert_eq!(state.sorted(), vec![2000, 1000, 3000]);
+		state.on_key(key(KeyCode::Char('d')));
+		assert_eq!(state.sorted(), vec![3000, 1000, 2000]);
+		state.on_key(key(KeyCode::Char('t')));
+		assert_eq!(state.sorted(), vec![2000, 3000, 1000]);
+	}
+
+	#[test]
+	fn test_keeps_selection_when_resorted() {
+		let mut state = TuiState::default();
+		state.on_progress(1000, 10, None, String::new(), summary(1000, 0, 0, 6.0));
+		state.on_progress(2000, 10, None, String::new(), summary(2000, 5, 0, 6.0));
+		assert_eq!(state.selected, Some(1000));
+
+		state.on_key(key(KeyCode::Down));
+		state.on_key(key(KeyCode::Down));
+		assert_eq!(state.selected, Some(2000));
+		state.on_key(key(KeyCode::Char('s')));
+		assert_eq!(state.selected, Some(2000));
+		state.on_key(key(KeyCode::Down));
+		assert_eq!(state.selected, Some(1000));
+		assert!(!state.on_key(key(KeyCode::Char('q'))));
+	}
+
+	#[test]
+	fn test_keeps_recent_block_times() {
+		let mut state = TuiState::default();
+		state.on_progress(1000, 10, None, String::new(), summary(1000, 0, 0, 6.0));
+		state.on_progress(1000, 11, Some(6.0), String::new(), summary(1000, 0, 0, 6.0));
+		state.on_progress(1000, 12, Some(12.0), String::new(), summary(1000, 0, 0, 9.0));
+		let pane = &state.paras[&1000];
+		assert_eq!(pane.block_times.values().collect::<Vec<_>>(), vec![6.0, 12.0]);
+		assert_eq!(charts::sparkline(&pane.block_times, TABLE_SPARKLINE_WIDTH).chars().count(), 1);
+		assert!(pane_details(pane).contains("last: 12.00s, min: 6.00s, max: 12.00s"));
+	}
+}
@@ -14,9 +14,14 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::utils::{extract_misbehaving_validators, extract_validator_addresses, extract_votes, format_ts};
+use crate::{
+	explorer::Explorer,
+	para_names::ParaNames,
+	utils::{extract_misbehaving_validators, extract_validator_addresses, extract_voters, extract_votes, format_ts},
+};
 use color_eyre::owo_colors::OwoColorize;
 use crossterm::style::Stylize;
+use itertools::Itertools;
 use parity_scale_codec::{Decode, Encode};
 use polkadot_introspector_essentials::{
 	api::subxt_wrapper::SubxtHrmpChannel,
@@ -45,18 +50,26 @@ pub struct ForkTracker {
 pub struct DisputesTracker {
 	/// Disputed candidate
 	pub candidate: H256,
+	/// Session the disputed candidate belongs to
+	pub session_index: u32,
 	/// The real outcome
 	pub outcome: SubxtDisputeResult,
 	/// Number of validators voted that a candidate is valid
 	pub voted_for: u32,
 	/// Number of validators voted that a candidate is invalid
 	pub voted_against: u32,
+	/// A vector of validators voted that a candidate is valid (index + identify)
+	pub valid_voters: Vec<(u32, String)>,
+	/// A vector of validators voted that a candidate is invalid (index + identify)
+	pub invalid_voters: Vec<(u32, String)>,
 	/// A vector of validators initiateds the dispute (index + identify)
 	pub initiators: Vec<(u32, String)>,
 	/// A vector of validators voted against supermajority (index + identify)
 	pub misbehaving_validators: Vec<(u32, String)>,
 	/// Dispute conclusion time: how many blocks have passed since DisputeInitiated event
 	pub resolve_time: u32,
+	/// Relay chain block where the dispute was concluded
+	pub concluded: u32,
 }
 
 impl DisputesTracker {
@@ -71,12 +84,25 @@ impl DisputesTracker {
 	) -> Self {
 		let candidate = dispute_info.candidate_hash.0;
 		let (voted_for, voted_against) = extract_votes(dispute_info);
+		let (valid_voters, invalid_voters) = extract_voters(session_info, dispute_info);
 		let initiators = extract_validator_addresses(initiators_session_info, initiator_indices);
 		let misbehaving_validators =
 			extract_misbehaving_validators(session_info, dispute_info, outcome == SubxtDisputeResult::Valid);
 		let resolve_time = concluded.saturating_sub(initiated);
 
-		Self { outcome, candidate, voted_for, voted_against, initiators, misbehaving_validators, resolve_time }
+		Self {
+			outcome,
+			candidate,
+			session_index: dispute_info.session,
+			voted_for,
+			voted_against,
+			valid_voters,
+			invalid_voters,
+			initiators,
+			misbehaving_validators,
+			resolve_time,
+			concluded,
+		}
 	}
 }
 
@@ -110,27 +136,122 @@ pub struct BitfieldsHealth {
 	pub available_count: u32,
 }
 
+/// Disagreement between the candidate state inferred from the inherent data and the runtime events,
+/// means either a bug in the tracker or inconsistent data returned by the node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InclusionMismatch {
+	/// A candidate was backed in the inherent, but no `CandidateBacked` event was seen in the block
+	BackedWithoutEvent,
+	/// A candidate became available by the bitfields, but no `CandidateIncluded` event was seen in the block
+	IncludedWithoutEvent,
+	/// `CandidateIncluded` event was seen in the block, but the candidate didn't become available by the bitfields
+	EventWithoutInclusion,
+}
+
+impl InclusionMismatch {
+	/// Returns a label used in metrics and exported records
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			InclusionMismatch::BackedWithoutEvent => "backed_without_event",
+			InclusionMismatch::IncludedWithoutEvent => "included_without_event",
+			InclusionMismatch::EventWithoutInclusion => "event_without_inclusion",
+		}
+	}
+}
+
+impl Display for InclusionMismatch {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			InclusionMismatch::BackedWithoutEvent => write!(f, "backed in the inherent, but no CandidateBacked event"),
+			InclusionMismatch::IncludedWithoutEvent =>
+				write!(f, "available by the bitfields, but no CandidateIncluded event"),
+			InclusionMismatch::EventWithoutInclusion =>
+				write!(f, "CandidateIncluded event, but not available by the bitfields"),
+		}
+	}
+}
+
+/// Why no candidate of a parachain was backed in a relay chain block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkippedSlotReason {
+	/// No availability core was assigned to the parachain
+	NotScheduled,
+	/// The core was free, but no candidate was supplied by the collators
+	NoCandidate,
+	/// A candidate was in the inherent, but with fewer validity votes than required and without a `CandidateBacked`
+	/// event, so the runtime has dropped it
+	InsufficientBacking,
+	/// The core was occupied by a previous candidate awaiting availability
+	CoreOccupied,
+}
+
+impl SkippedSlotReason {
+	/// Returns a label used in metrics and exported records
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			SkippedSlotReason::NotScheduled => "not_scheduled",
+			SkippedSlotReason::NoCandidate => "no_candidate",
+			SkippedSlotReason::InsufficientBacking => "insufficient_backing",
+			SkippedSlotReason::CoreOccupied => "core_occupied",
+		}
+	}
+}
+
+impl Display for SkippedSlotReason {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			SkippedSlotReason::NotScheduled => write!(f, "core not scheduled"),
+			SkippedSlotReason::NoCandidate => write!(f, "no candidate supplied by the collators"),
+			SkippedSlotReason::InsufficientBacking => write!(f, "insufficient backing votes"),
+			SkippedSlotReason::CoreOccupied => write!(f, "core occupied by a candidate awaiting availability"),
+		}
+	}
+}
+
 #[derive(Clone)]
 /// Events related to parachain blocks from consensus perspective.
 pub enum ParachainConsensusEvent {
 	/// A core has been assigned to a parachain.
 	CoreAssigned(u32),
-	/// A candidate was backed.
-	Backed(H256),
+	/// A candidate was backed, including validity votes and backing group size
+	Backed(H256, u32, u32),
 	/// A candidate was included, including availability bits
 	Included(H256, u32, u32),
 	/// A dispute has concluded.
 	Disputed(DisputesTracker),
-	/// No candidate backed.
-	SkippedSlot,
+	/// No candidate backed, with the reason
+	SkippedSlot(SkippedSlotReason),
 	/// Candidate not available yet, including availability bits
 	SlowAvailability(u32, u32),
+	/// Candidate was evicted from the availability core without being included
+	AvailabilityTimedOut(H256),
 	/// Inherent bitfield count is lower than 2/3 of expect.
 	SlowBitfieldPropagation(u32, u32),
 	/// New session occurred
 	NewSession(u32),
 	/// HRMP messages queues for inbound and outbound transfers
 	MessageQueues(Vec<(u32, SubxtHrmpChannel)>, Vec<(u32, SubxtHrmpChannel)>),
+	/// Candidate state inferred by the tracker doesn't match the runtime events
+	InclusionMismatch(H256, InclusionMismatch),
+	/// A candidate was backed on a relay parent at the edge of the allowed ancestry, including its age and the
+	/// maximum allowed age in relay chain blocks
+	OldRelayParent(H256, u32, u32),
+}
+
+impl ParachainConsensusEvent {
+	/// Whether the event is a warning worth looking into
+	pub fn is_alert(&self) -> bool {
+		matches!(
+			self,
+			ParachainConsensusEvent::Disputed(_) |
+				ParachainConsensusEvent::SkippedSlot(_) |
+				ParachainConsensusEvent::SlowAvailability(_, _) |
+				ParachainConsensusEvent::AvailabilityTimedOut(_) |
+				ParachainConsensusEvent::SlowBitfieldPropagation(_, _) |
+				ParachainConsensusEvent::InclusionMismatch(_, _) |
+				ParachainConsensusEvent::OldRelayParent(_, _, _)
+		)
+	}
 }
 
 #[derive(Clone, Default)]
@@ -157,6 +278,143 @@ pub struct ParachainProgressUpdate {
 	pub is_fork: bool,
 	/// Finality lag (best block number - last finalized block number)
 	pub finality_lag: Option<u32>,
+	/// Backing latency of the candidate included in this block, from its relay parent
+	pub backed_in: Option<Latency>,
+	/// Latency since the previous candidate inclusion
+	pub included_after: Option<Latency>,
+	/// XCM messages in the commitments of candidates backed in this block
+	pub xcm: Option<XcmThroughput>,
+	/// Lengths of the downward and upward message queues
+	pub message_queues: Option<MessageQueueDepths>,
+	/// Age of the relay parent of the candidate backed in this block, in relay chain blocks
+	pub relay_parent_age: Option<u32>,
+	/// Number of para blocks backed on chain on top of the para head
+	pub unincluded_depth: Option<u32>,
+	/// Number of candidates of the parachain included in this block, more than one with elastic scaling
+	pub included_count: Option<u32>,
+	/// Candidates of the parachain per availability core, only set if the parachain uses several cores
+	pub cores: Vec<CoreProgress>,
+	/// The block is in a maintenance window, alerts raised in it are not posted to the sinks
+	pub in_maintenance: bool,
+	/// The block is replayed on the chosen fork after the relay chain forks were resolved
+	pub is_replayed: bool,
+	/// Links the block to the explorer if it has alerts
+	pub explorer: Explorer,
+	/// Names of the parachains shown next to their ids
+	pub para_names: ParaNames,
+}
+
+/// State of a candidate of the parachain on one of its availability cores
+#[derive(Clone, Debug)]
+pub struct CoreProgress {
+	/// Availability core index
+	pub core_index: u32,
+	pub candidate_hash: H256,
+	/// `backed`, `pending`, `included` or `timed_out`
+	pub state: &'static str,
+	/// Number of availability bits set for the core
+	pub available_count: u32,
+	/// Number of validators
+	pub max_availability_bits: u32,
+}
+
+/// Messages of a parachain, taken from the commitments of candidates backed in a relay chain block
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct XcmThroughput {
+	/// Number of upward messages sent by the parachain
+	pub ump_messages: u32,
+	/// Size of upward messages sent by the parachain
+	pub ump_bytes: u32,
+	/// Number of downward messages processed by the parachain
+	pub dmp_processed: u32,
+	/// Number of horizontal messages sent by the parachain
+	pub hrmp_sent: u32,
+	/// Size of horizontal messages sent by the parachain
+	pub hrmp_sent_bytes: u32,
+	/// Number of horizontal messages sent to the parachain by other parachains
+	pub hrmp_received: u32,
+	/// Size of horizontal messages sent to the parachain by other parachains
+	pub hrmp_received_bytes: u32,
+}
+
+impl XcmThroughput {
+	pub fn is_empty(&self) -> bool {
+		*self == Self::default()
+	}
+}
+
+impl Display for XcmThroughput {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"UMP: {} ({} bytes), DMP processed: {}, HRMP sent: {} ({} bytes), HRMP received: {} ({} bytes)",
+			self.ump_messages,
+			self.ump_bytes,
+			self.dmp_processed,
+			self.hrmp_sent,
+			self.hrmp_sent_bytes,
+			self.hrmp_received,
+			self.hrmp_received_bytes
+		)
+	}
+}
+
+/// Length of a message queue in a relay chain block, compared to the previous one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueDepth {
+	/// Number of messages in the queue
+	pub len: u32,
+	/// Number of messages in the previous relay chain block, if known
+	pub prev_len: Option<u32>,
+}
+
+impl Display for QueueDepth {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.len)?;
+		match self.prev_len {
+			Some(prev_len) if self.len > prev_len => write!(f, " {}", "↑".bright_red()),
+			Some(prev_len) if self.len < prev_len => write!(f, " {}", "↓".bright_green()),
+			Some(_) => write!(f, " →"),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Downward and upward message queues of a parachain
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MessageQueueDepths {
+	/// Downward messages waiting to be processed by the parachain
+	pub downward: QueueDepth,
+	/// Upward messages of the parachain waiting to be processed on the relay chain
+	pub upward: QueueDepth,
+}
+
+/// Latency measured in relay chain blocks and, if timestamps are known, in seconds
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Latency {
+	/// Number of relay chain blocks
+	pub blocks: u32,
+	/// Time between the relay chain blocks
+	pub duration: Option<Duration>,
+}
+
+impl Display for Latency {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.duration {
+			Some(duration) => write!(f, "{} blocks ({:.1}s)", self.blocks, duration.as_secs_f32()),
+			None => write!(f, "{} blocks", self.blocks),
+		}
+	}
+}
+
+impl ParachainProgressUpdate {
+	/// Returns why the slot was skipped, if it was
+	pub fn skipped_slot_reason(&self) -> Option<SkippedSlotReason> {
+		self.events.iter().find_map(|event| match event {
+			ParachainConsensusEvent::SkippedSlot(reason) => Some(*reason),
+			_ => None,
+		})
+	}
 }
 
 impl Display for ParachainProgressUpdate {
@@ -168,7 +426,7 @@ impl Display for ParachainProgressUpdate {
 				"{} [#{}, fork]; parachain: {}",
 				format_ts(Duration::from_millis(self.timestamp.saturating_sub(self.prev_timestamp)), self.timestamp),
 				self.block_number,
-				format!("{}", self.para_id).bold(),
+				format!("{}", self.para_names.display(self.para_id)).bold(),
 			)?;
 		} else {
 			writeln!(
@@ -176,13 +434,64 @@ impl Display for ParachainProgressUpdate {
 				"{} [#{}]; parachain: {}",
 				format_ts(Duration::from_millis(self.timestamp.saturating_sub(self.prev_timestamp)), self.timestamp),
 				self.block_number,
-				format!("{}", self.para_id).bold(),
+				format!("{}", self.para_names.display(self.para_id)).bold(),
 			)?;
 		}
+		if self.in_maintenance {
+			writeln!(buf, "\t🔧 In a maintenance window, alerts are not posted")?;
+		}
+		if self.is_replayed {
+			writeln!(buf, "\t🔁 Replayed on the chosen fork")?;
+		}
 		for event in &self.events {
 			write!(buf, "{}", event)?;
 		}
 		writeln!(buf, "\t🔗 Relay block hash: {} ", format!("{:?}", self.block_hash).bold())?;
+		if self.events.iter().any(|event| event.is_alert()) {
+			// Numbers are ambiguous on forks
+			if self.is_fork {
+				self.explorer.write_block_link(&mut buf, format!("{:?}", self.block_hash))?;
+			} else {
+				self.explorer.write_block_link(&mut buf, self.block_number)?;
+			}
+		}
+		if let Some(backed_in) = self.backed_in {
+			writeln!(buf, "\t⏱️ Backed in: {}", backed_in)?;
+		}
+		if let Some(included_after) = self.included_after {
+			writeln!(buf, "\t⏱️ Included after: {}", included_after)?;
+		}
+		if let Some(xcm) = self.xcm {
+			writeln!(buf, "\t✉️ XCM messages: {}", xcm)?;
+		}
+		if let Some(queues) = self.message_queues {
+			writeln!(buf, "\t📬 Message queues: DMP {}, UMP {}", queues.downward, queues.upward)?;
+		}
+		if let Some(age) = self.relay_parent_age {
+			writeln!(buf, "\t⚓ Relay parent age: {} blocks{}", age, if age > 1 { "" } else { " (latest)" })?;
+		}
+		if let Some(depth) = self.unincluded_depth {
+			writeln!(buf, "\t🪜 Unincluded para blocks: {}", depth)?;
+		}
+		if !self.cores.is_empty() {
+			writeln!(
+				buf,
+				"\t🧩 Elastic scaling: {} cores in use, {} candidates included",
+				self.cores.len(),
+				self.included_count.unwrap_or_default()
+			)?;
+			for core in &self.cores {
+				writeln!(
+					buf,
+					"\t\t- core {}: {} {} ({}/{} bits)",
+					core.core_index,
+					core.state,
+					format!("{:?}", core.candidate_hash).magenta(),
+					core.available_count,
+					core.max_availability_bits
+				)?;
+			}
+		}
 		writeln!(buf, "\t🥝 Availability core {}", if !self.core_occupied { "FREE" } else { "OCCUPIED" })?;
 		writeln!(
 			buf,
@@ -200,9 +509,10 @@ impl Display for ParachainConsensusEvent {
 			ParachainConsensusEvent::CoreAssigned(core_id) => {
 				writeln!(f, "\t- Parachain assigned to core index {}", core_id)
 			},
-			ParachainConsensusEvent::Backed(candidate_hash) => {
+			ParachainConsensusEvent::Backed(candidate_hash, votes, group_size) => {
 				writeln!(f, "\t{}", "CANDIDATE BACKED".to_string().bold().green())?;
-				writeln!(f, "\t💜 Candidate hash: {} ", format!("{:?}", candidate_hash).magenta())
+				writeln!(f, "\t💜 Candidate hash: {} ", format!("{:?}", candidate_hash).magenta())?;
+				writeln!(f, "\t🗳️ Backing votes: {}/{}", votes, group_size)
 			},
 			ParachainConsensusEvent::Included(candidate_hash, bits_available, max_bits) => {
 				writeln!(f, "\t{}", "CANDIDATE INCLUDED".to_string().bold().green())?;
@@ -213,13 +523,17 @@ impl Display for ParachainConsensusEvent {
 				writeln!(f, "\t{}", "💔 Dispute tracked:".to_string().bold())?;
 				write!(f, "{}", outcome)
 			},
-			ParachainConsensusEvent::SkippedSlot => {
-				writeln!(f, "\t{}, no candidate backed", "SLOW BACKING".to_string().bold().red(),)
+			ParachainConsensusEvent::SkippedSlot(reason) => {
+				writeln!(f, "\t{}, no candidate backed: {}", "SLOW BACKING".to_string().bold().red(), reason)
 			},
 			ParachainConsensusEvent::SlowAvailability(bits_available, max_bits) => {
 				writeln!(f, "\t{}", "SLOW AVAILABILITY".to_string().bold().yellow())?;
 				writeln!(f, "\t🟢 Availability bits: {}/{}", bits_available, max_bits)
 			},
+			ParachainConsensusEvent::AvailabilityTimedOut(candidate_hash) => {
+				writeln!(f, "\t{}", "AVAILABILITY TIMED OUT".to_string().bold().red())?;
+				writeln!(f, "\t💜 Candidate hash: {} ", format!("{:?}", candidate_hash).magenta())
+			},
 			ParachainConsensusEvent::SlowBitfieldPropagation(bitfields_count, max_bits) => {
 				writeln!(
 					f,
@@ -263,6 +577,20 @@ impl Display for ParachainConsensusEvent {
 				}
 				Ok(())
 			},
+			ParachainConsensusEvent::InclusionMismatch(candidate_hash, mismatch) => {
+				writeln!(f, "\t{} {}", "INCLUSION MISMATCH".to_string().bold().red(), mismatch)?;
+				writeln!(f, "\t💜 Candidate hash: {} ", format!("{:?}", candidate_hash).magenta())
+			},
+			ParachainConsensusEvent::OldRelayParent(candidate_hash, age, max_age) => {
+				writeln!(
+					f,
+					"\t{} {} blocks old, {} blocks allowed",
+					"OLD RELAY PARENT".to_string().bold().yellow(),
+					age,
+					max_age
+				)?;
+				writeln!(f, "\t💜 Candidate hash: {} ", format!("{:?}", candidate_hash).magenta())
+			},
 		}
 	}
 }
@@ -302,6 +630,18 @@ impl Display for DisputesTracker {
 			},
 		}
 
+		writeln!(f, "\t\t\tSession: {}, concluded at block #{}", self.session_index, self.concluded)?;
+		writeln!(
+			f,
+			"\t\t\t✅ Voted valid: {}",
+			format!("[{}]", self.valid_voters.iter().map(|(idx, _)| idx).join(", ")).bright_green()
+		)?;
+		writeln!(
+			f,
+			"\t\t\t❌ Voted invalid: {}",
+			format!("[{}]", self.invalid_voters.iter().map(|(idx, _)| idx).join(", ")).bright_red()
+		)?;
+
 		if !self.initiators.is_empty() {
 			for (validator_idx, validator_address) in &self.initiators {
 				writeln!(
@@ -14,12 +14,14 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::types::XcmThroughput;
 use polkadot_introspector_essentials::{
 	api::subxt_wrapper::InherentData,
 	metadata::polkadot_primitives::{AvailabilityBitfield, BackedCandidate, DisputeStatement, DisputeStatementSet},
 	types::{AccountId32, Timestamp, H256},
 };
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
+use subxt::config::{substrate::BlakeTwo256, Hasher};
 
 /// Returns a time difference between optional timestamps
 pub(crate) fn time_diff(lhs: Option<u64>, rhs: Option<u64>) -> Option<Duration> {
@@ -122,16 +124,17 @@ mod test_extract_validator_addresses {
 	}
 }
 
+// Borrows the fields of the inherent data shared by the trackers, nothing is copied but the bitfield references
 pub(crate) fn extract_inherent_fields(
-	data: InherentData,
-) -> (Vec<AvailabilityBitfield>, Vec<BackedCandidate<H256>>, Vec<DisputeStatementSet>) {
+	data: &InherentData,
+) -> (Vec<&AvailabilityBitfield>, &[BackedCandidate<H256>], &[DisputeStatementSet]) {
 	let bitfields = data
 		.bitfields
-		.into_iter()
-		.map(|b| b.payload)
-		.collect::<Vec<AvailabilityBitfield>>();
+		.iter()
+		.map(|b| &b.payload)
+		.collect::<Vec<&AvailabilityBitfield>>();
 
-	(bitfields, data.backed_candidates, data.disputes)
+	(bitfields, &data.backed_candidates, &data.disputes)
 }
 
 #[cfg(test)]
@@ -141,9 +144,8 @@ mod test_extract_inherent_fields {
 
 	#[test]
 	fn test_returns_fields() {
-		let (bitfields, backed_candidates, disputes) = extract_inherent_fields(create_inherent_data(100));
-
-		println!("{:?}", matches!(bitfields.first().unwrap(), AvailabilityBitfield(_)));
+		let data = create_inherent_data(100);
+		let (bitfields, backed_candidates, disputes) = extract_inherent_fields(&data);
 
 		assert!(matches!(bitfields.first().unwrap(), AvailabilityBitfield(_)));
 		assert!(matches!(backed_candidates.first().unwrap(), BackedCandidate { .. }));
@@ -152,11 +154,11 @@ mod test_extract_inherent_fields {
 }
 
 pub(crate) fn backed_candidate(
-	backed_candidates: Vec<BackedCandidate<H256>>,
+	backed_candidates: &[BackedCandidate<H256>],
 	para_id: u32,
-) -> Option<BackedCandidate<H256>> {
+) -> Option<&BackedCandidate<H256>> {
 	backed_candidates
-		.into_iter()
+		.iter()
 		.find(|candidate| candidate.candidate.descriptor.para_id.0 == para_id)
 }
 
@@ -167,12 +169,157 @@ mod test_backed_candidate {
 
 	#[test]
 	fn test_returns_a_candidate() {
-		let found = backed_candidate(vec![create_backed_candidate(100), create_backed_candidate(200)], 100).unwrap();
+		let found = backed_candidate(&[create_backed_candidate(100), create_backed_candidate(200)], 100).unwrap();
 
 		assert_eq!(found.candidate.descriptor.para_id.0, 100);
 	}
 }
 
+// Calculates the hash of a backed candidate
+pub(crate) fn candidate_hash(candidate: &BackedCandidate<H256>) -> H256 {
+	let commitments_hash = BlakeTwo256::hash_of(&candidate.candidate.commitments);
+	BlakeTwo256::hash_of(&(&candidate.candidate.descriptor, commitments_hash))
+}
+
+#[cfg(test)]
+mod test_candidate_hash {
+	use super::*;
+	use crate::test_utils::create_backed_candidate;
+
+	#[test]
+	fn test_returns_different_hashes_for_different_candidates() {
+		let candidate = create_backed_candidate(100);
+
+		assert_eq!(candidate_hash(&candidate), candidate_hash(&candidate));
+		assert_ne!(candidate_hash(&candidate), candidate_hash(&create_backed_candidate(100)));
+	}
+}
+
+// Returns the number of validity votes and the size of the backing group for a backed candidate
+pub(crate) fn extract_backing_votes(candidate: &BackedCandidate<H256>) -> (u32, u32) {
+	(candidate.validity_votes.len() as u32, candidate.validator_indices.len() as u32)
+}
+
+#[cfg(test)]
+mod test_extract_backing_votes {
+	use super::*;
+	use crate::test_utils::create_backed_candidate;
+
+	#[test]
+	fn test_returns_votes_and_group_size() {
+		assert_eq!(extract_backing_votes(&create_backed_candidate(100)), (0, 1));
+	}
+}
+
+/// Validity votes of a candidate compared to the quorum needed to back it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BackingQuorum {
+	/// Too few votes, the runtime drops the candidate
+	Below,
+	/// Just enough votes
+	Minimum,
+	/// More votes than needed
+	Above,
+}
+
+// The quorum is the minimum number of backing votes, unless the backing group is smaller
+pub(crate) fn backing_quorum(votes: u32, group_size: u32, minimum_backing_votes: u32) -> BackingQuorum {
+	let quorum = minimum_backing_votes.min(group_size);
+	match votes.cmp(&quorum) {
+		std::cmp::Ordering::Less => BackingQuorum::Below,
+		std::cmp::Ordering::Equal => BackingQuorum::Minimum,
+		std::cmp::Ordering::Greater => BackingQuorum::Above,
+	}
+}
+
+#[cfg(test)]
+mod test_backing_quorum {
+	use super::*;
+
+	#[test]
+	fn test_compares_votes_with_quorum() {
+		assert_eq!(backing_quorum(1, 5, 2), BackingQuorum::Below);
+		assert_eq!(backing_quorum(2, 5, 2), BackingQuorum::Minimum);
+		assert_eq!(backing_quorum(3, 5, 2), BackingQuorum::Above);
+		// A smaller group needs all of its votes
+		assert_eq!(backing_quorum(1, 1, 2), BackingQuorum::Minimum);
+	}
+}
+
+// Counts messages of the parachain in the commitments of candidates backed in a relay chain block
+pub(crate) fn extract_xcm_throughput(backed_candidates: &[BackedCandidate<H256>], para_id: u32) -> XcmThroughput {
+	let mut xcm = XcmThroughput::default();
+	for candidate in backed_candidates {
+		let commitments = &candidate.candidate.commitments;
+		if candidate.candidate.descriptor.para_id.0 == para_id {
+			xcm.ump_messages += commitments.upward_messages.0.len() as u32;
+			xcm.ump_bytes += commitments.upward_messages.0.iter().map(|msg| msg.len() as u32).sum::<u32>();
+			xcm.dmp_processed += commitments.processed_downward_messages;
+			xcm.hrmp_sent += commitments.horizontal_messages.0.len() as u32;
+			xcm.hrmp_sent_bytes += commitments
+				.horizontal_messages
+				.0
+				.iter()
+				.map(|msg| msg.data.len() as u32)
+				.sum::<u32>();
+		}
+		for msg in commitments
+			.horizontal_messages
+			.0
+			.iter()
+			.filter(|msg| msg.recipient.0 == para_id)
+		{
+			xcm.hrmp_received += 1;
+			xcm.hrmp_received_bytes += msg.data.len() as u32;
+		}
+	}
+
+	xcm
+}
+
+#[cfg(test)]
+mod test_extract_xcm_throughput {
+	use super::*;
+	use crate::test_utils::create_backed_candidate;
+	use polkadot_introspector_essentials::metadata::polkadot::runtime_types::{
+		bounded_collections::bounded_vec::BoundedVec, polkadot_core_primitives::OutboundHrmpMessage,
+		polkadot_parachain::primitives::Id,
+	};
+
+	#[test]
+	fn test_counts_messages_of_parachain() {
+		let mut sender = create_backed_candidate(100);
+		sender.candidate.commitments.upward_messages = BoundedVec(vec![vec![0; 10], vec![0; 20]]);
+		sender.candidate.commitments.processed_downward_messages = 3;
+		sender.candidate.commitments.horizontal_messages =
+			BoundedVec(vec![OutboundHrmpMessage { recipient: Id(200), data: vec![0; 5] }]);
+		let mut other = create_backed_candidate(300);
+		other.candidate.commitments.horizontal_messages = BoundedVec(vec![
+			OutboundHrmpMessage { recipient: Id(100), data: vec![0; 7] },
+			OutboundHrmpMessage { recipient: Id(400), data: vec![0; 9] },
+		]);
+		let candidates = vec![sender, other];
+
+		assert_eq!(
+			extract_xcm_throughput(&candidates, 100),
+			XcmThroughput {
+				ump_messages: 2,
+				ump_bytes: 30,
+				dmp_processed: 3,
+				hrmp_sent: 1,
+				hrmp_sent_bytes: 5,
+				hrmp_received: 1,
+				hrmp_received_bytes: 7,
+			}
+		);
+		assert_eq!(
+			extract_xcm_throughput(&candidates, 200),
+			XcmThroughput { hrmp_received: 1, hrmp_received_bytes: 5, ..Default::default() }
+		);
+		assert!(extract_xcm_throughput(&candidates, 500).is_empty());
+	}
+}
+
 pub(crate) fn extract_misbehaving_validators(
 	session_keys: Option<&Vec<AccountId32>>,
 	info: &DisputeStatementSet,
@@ -205,6 +352,41 @@ mod test_extract_misbehaving_validators {
 	}
 }
 
+/// Returns validators voted that a disputed candidate is valid and invalid (index + identify)
+pub(crate) fn extract_voters(
+	session_keys: Option<&Vec<AccountId32>>,
+	info: &DisputeStatementSet,
+) -> (Vec<(u32, String)>, Vec<(u32, String)>) {
+	let mut valid = vec![];
+	let mut invalid = vec![];
+	for (statement, idx, _) in info.statements.iter() {
+		let voter = extract_validator_address(session_keys, idx.0);
+		if matches!(statement, DisputeStatement::Valid(_)) {
+			valid.push(voter)
+		} else {
+			invalid.push(voter)
+		}
+	}
+
+	(valid, invalid)
+}
+
+#[cfg(test)]
+mod test_extract_voters {
+	use super::*;
+	use crate::test_utils::create_dispute_statement_set;
+
+	#[test]
+	fn test_returns_voters() {
+		let session_keys = vec![AccountId32([0; 32]), AccountId32([1; 32]), AccountId32([2; 32])];
+		let (valid, invalid) = extract_voters(Some(&session_keys), &create_dispute_statement_set());
+
+		assert_eq!(valid, vec![(1, AccountId32([1; 32]).to_string())]);
+		assert_eq!(invalid.iter().map(|(idx, _)| *idx).collect::<Vec<_>>(), vec![2, 3]);
+		assert_eq!(invalid[0].1, AccountId32([2; 32]).to_string());
+	}
+}
+
 pub(crate) fn extract_votes(info: &DisputeStatementSet) -> (u32, u32) {
 	let voted_for = info
 		.statements
@@ -227,7 +409,7 @@ mod test_extract_votes {
 	}
 }
 
-pub(crate) fn extract_availability_bits_count(bitfields: Vec<AvailabilityBitfield>, core: u32) -> u32 {
+pub(crate) fn extract_availability_bits_count(bitfields: &[&AvailabilityBitfield], core: u32) -> u32 {
 	bitfields
 		.iter()
 		.map(|v| v.0.as_bits().get(core as usize).expect("core index must be in the bitfield") as u32)
@@ -242,7 +424,7 @@ mod test_extract_availability_bits_count {
 	#[test]
 	fn test_counts_availability_bits() {
 		assert_eq!(
-			extract_availability_bits_count(vec![AvailabilityBitfield(DecodedBits::from_iter([true, false, true]))], 0),
+			extract_availability_bits_count(&[&AvailabilityBitfield(DecodedBits::from_iter([true, false, true]))], 0),
 			1
 		);
 	}
@@ -268,3 +450,26 @@ mod test_format_ts {
 		assert_eq!(format_ts(Duration::from_millis(5999), 1694002836000), "2023-09-06T12:20:36.000000000Z +5999ms")
 	}
 }
+
+/// Returns the selected cores the parachain is assigned to
+pub(crate) fn assigned_cores(assignments: &BTreeMap<u32, Vec<u32>>, cores: &[u32], para_id: u32) -> Vec<u32> {
+	cores
+		.iter()
+		.filter(|core| assignments.get(core).map_or(false, |para_ids| para_ids.contains(&para_id)))
+		.copied()
+		.collect()
+}
+
+#[cfg(test)]
+mod test_assigned_cores {
+	use super::*;
+
+	#[test]
+	fn test_returns_selected_cores_of_the_parachain() {
+		let assignments = BTreeMap::from([(0, vec![100]), (1, vec![200, 100]), (2, vec![100]), (3, vec![])]);
+
+		assert_eq!(assigned_cores(&assignments, &[1, 2, 3], 100), vec![1, 2]);
+		assert_eq!(assigned_cores(&assignments, &[1, 2, 3], 200), vec![1]);
+		assert!(assigned_cores(&assignments, &[0, 3, 4], 200).is_empty());
+	}
+}
@@ -0,0 +1,384 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+//! This module computes the history of a single validator over a range of relay chain blocks.
+//!
+//! For due diligence on nominee validators, the range between `--from` and `--to` is replayed and the
+//! validator's bitfields, backing votes and dispute statements are counted per session. Validator indices
+//! change every session, so a stash account is resolved to its index with the session keys of each session,
+//! read at the replayed block, and the indices of the validators taking part in parachain consensus.
+
+use crate::observation::BlockObservation;
+use clap::{Args, ValueEnum};
+use log::warn;
+use polkadot_introspector_essentials::{
+	api::subxt_wrapper::{InherentData, RequestExecutor},
+	types::{AccountId32, H256},
+};
+use std::{
+	collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap},
+	fmt::{self, Display, Formatter, Write},
+	str::FromStr,
+};
+
+const CSV_COLUMNS: &[&str] = &[
+	"session_index",
+	"validator_index",
+	"blocks",
+	"bitfields",
+	"backing_seats",
+	"backing_votes",
+	"disputes",
+	"dispute_votes",
+];
+
+/// The validator given either by its index, the same in every session, or by its stash account
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ValidatorRef {
+	Index(u32),
+	Stash(AccountId32),
+}
+
+impl FromStr for ValidatorRef {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Ok(index) = s.parse() {
+			return Ok(Self::Index(index))
+		}
+		AccountId32::from_str(s)
+			.map(Self::Stash)
+			.map_err(|_| format!("`{}` is neither a validator index nor a SS58 account", s))
+	}
+}
+
+impl Display for ValidatorRef {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			Self::Index(index) => write!(f, "validator #{}", index),
+			Self::Stash(account) => write!(f, "validator {}", account),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum HistoryFormat {
+	#[default]
+	Table,
+	Csv,
+}
+
+#[derive(Clone, Debug, Args)]
+pub(crate) struct ValidatorHistoryOptions {
+	/// Index of the validator in every session, or its SS58 stash account
+	pub validator: ValidatorRef,
+	/// Format of the history printed when the range is replayed
+	#[clap(long = "history-format", value_enum, default_value = "table")]
+	pub format: HistoryFormat,
+}
+
+/// Participation of the validator in a session
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct SessionHistory {
+	/// Index of the validator in the session
+	validator_index: u32,
+	/// Number of relay chain blocks the validator was expected to sign a bitfield in
+	blocks: u32,
+	/// Number of relay chain blocks with a bitfield of the validator
+	bitfields: u32,
+	/// Number of backed candidates where the validator was a member of the backing group
+	backing_seats: u32,
+	/// Number of backed candidates with a validity vote of the validator
+	backing_votes: u32,
+	/// Number of disputes of candidates of the session
+	disputes: u32,
+	/// Number of disputes with a statement of the validator
+	dispute_votes: u32,
+}
+
+/// Counts the participation of the validator per session
+pub(crate) struct ValidatorHistory {
+	validator: ValidatorRef,
+	/// Index of the stash in known sessions, `None` if not a validator in the session
+	validator_indices: HashMap<u32, Option<u32>>,
+	/// Sessions the keys of which could not be read, so the stash could not be resolved in them
+	unresolved_sessions: BTreeSet<u32>,
+	/// Disputes seen by the session and candidate, and whether the validator has cast a statement
+	disputes: HashMap<(u32, H256), bool>,
+	sessions: BTreeMap<u32, SessionHistory>,
+}
+
+impl ValidatorHistory {
+	pub fn new(validator: ValidatorRef) -> Self {
+		Self {
+			validator,
+			validator_indices: Default::default(),
+			unresolved_sessions: Default::default(),
+			disputes: Default::default(),
+			sessions: Default::default(),
+		}
+	}
+
+	/// Returns the sessions of the block and its disputes, for which session keys are needed and not provided yet
+	pub fn unknown_sessions(&self, session_index: u32, inherent: &InherentData) -> Vec<u32> {
+		if matches!(self.validator, ValidatorRef::Index(_)) {
+			return vec![]
+		}
+		let mut sessions: Vec<u32> = std::iter::once(session_index)
+			.chain(inherent.disputes.iter().map(|dispute| dispute.session))
+			.filter(|session| !self.validator_indices.contains_key(session))
+			.collect();
+		sessions.sort_unstable();
+		sessions.dedup();
+
+		sessions
+	}
+
+	/// Saves the stash's index in a session. The account keys are ordered by the session indices of the validators,
+	/// bitfields and votes refer to the indices in `active_indices`, that are the session indices of the validators
+	/// taking part in parachain consensus
+	pub fn set_session_keys(&mut self, session_index: u32, keys: &[AccountId32], active_indices: &[u32]) {
+		if let ValidatorRef::Stash(ref stash) = self.validator {
+			let index = keys
+				.iter()
+				.position(|account| account == stash)
+				.and_then(|session_validator| {
+					active_indices.iter().position(|&index| index as usize == session_validator)
+				})
+				.map(|v| v as u32);
+			self.validator_indices.insert(session_index, index);
+		}
+	}
+
+	/// Saves a session the keys of which could not be read
+	pub fn set_unresolved_session(&mut self, session_index: u32) {
+		self.validator_indices.insert(session_index, None);
+		self.unresolved_sessions.insert(session_index);
+	}
+
+	/// Sessions the stash could not be resolved in
+	pub fn unresolved_sessions(&self) -> &BTreeSet<u32> {
+		&self.unresolved_sessions
+	}
+
+	/// Processes a relay chain block of the session
+	pub fn on_block(&mut self, session_index: u32, observation: &BlockObservation, inherent: &InherentData) {
+		if let Some(validator_index) = self.validator_index(session_index) {
+			let session = self.session_mut(session_index, validator_index);
+			if validator_index < observation.validators_count {
+				session.blocks += 1;
+				if inherent
+					.bitfields
+					.iter()
+					.any(|bitfield| bitfield.validator_index.0 == validator_index)
+				{
+					session.bitfields += 1;
+				}
+			}
+			for vote in observation
+				.candidates
+				.iter()
+				.flat_map(|candidate| candidate.votes.iter())
+				.filter(|vote| vote.validator_index == validator_index)
+			{
+				session.backing_seats += 1;
+				if vote.voted {
+					session.backing_votes += 1;
+				}
+			}
+		}
+
+		for dispute in inherent.disputes.iter() {
+			let Some(validator_index) = self.validator_index(dispute.session) else { continue };
+			let has_statement = dispute.statements.iter().any(|(_, index, _)| index.0 == validator_index);
+			let (is_new, is_first_statement) = match self.disputes.entry((dispute.session, dispute.candidate_hash.0)) {
+				Entry::Vacant(entry) => {
+					entry.insert(has_statement);
+					(true, has_statement)
+				},
+				Entry::Occupied(mut entry) => {
+					let is_first_statement = has_statement && !entry.get();
+					*entry.get_mut() |= has_statement;
+					(false, is_first_statement)
+				},
+			};
+			let session = self.session_mut(dispute.session, validator_index);
+			session.disputes += is_new as u32;
+			session.dispute_votes += is_first_statement as u32;
+		}
+	}
+
+	/// Renders the history of all sessions seen
+	pub fn render(&self, format: HistoryFormat) -> String {
+		let mut out = String::new();
+		match format {
+			HistoryFormat::Table => {
+				let _ = writeln!(out, "History of {}", self.validator);
+				let _ = writeln!(
+					out,
+					"{:>8} {:>7} {:>7} {:>17} {:>15} {:>15}",
+					"Session", "Index", "Blocks", "Bitfields", "Backing votes", "Dispute votes"
+				);
+				for (session_index, session) in self.sessions.iter() {
+					let _ = writeln!(
+						out,
+						"{:>8} {:>7} {:>7} {:>17} {:>15} {:>15}",
+						session_index,
+						session.validator_index,
+						session.blocks,
+						format!("{} ({:.1}%)", session.bitfields, percentage(session.bitfields, session.blocks)),
+						format!("{}/{}", session.backing_votes, session.backing_seats),
+						format!("{}/{}", session.dispute_votes, session.disputes)
+					);
+				}
+				if self.sessions.is_empty() && self.unresolved_sessions.is_empty() {
+					let _ = writeln!(out, "Not a validator in the sessions of the range");
+				}
+			},
+			HistoryFormat::Csv => {
+				let _ = writeln!(out, "{}", CSV_COLUMNS.join(","));
+				for (session_index, session) in self.sessions.iter() {
+					let _ = writeln!(
+						out,
+						"{},{},{},{},{},{},{},{}",
+						session_index,
+						session.validator_index,
+						session.blocks,
+						session.bitfields,
+						session.backing_seats,
+						session.backing_votes,
+						session.disputes,
+						session.dispute_votes
+					);
+				}
+			},
+		}
+
+		out
+	}
+
+	fn validator_index(&self, session_index: u32) -> Option<u32> {
+		match self.validator {
+			ValidatorRef::Index(index) => Some(index),
+			ValidatorRef::Stash(_) => self.validator_indices.get(&session_index).copied().flatten(),
+		}
+	}
+
+	fn session_mut(&mut self, session_index: u32, validator_index: u32) -> &mut SessionHistory {
+		self.sessions
+			.entry(session_index)
+			.or_insert_with(|| SessionHistory { validator_index, ..Default::default() })
+	}
+}
+
+/// Reads the account keys and the active validator indices of a session at a block,
+/// `None` if any of them is not available
+pub(crate) async fn fetch_session_keys(
+	executor: &mut RequestExecutor,
+	node: &str,
+	block_hash: H256,
+	session_index: u32,
+) -> Option<(Vec<AccountId32>, Vec<u32>)> {
+	let keys = executor.get_session_account_keys(node, block_hash, session_index).await;
+	let active_indices = executor.get_active_validator_indices(node, block_hash, session_index).await;
+	match (keys, active_indices) {
+		(Ok(Some(keys)), Ok(Some(active_indices))) => Some((keys, active_indices)),
+		(Err(e), _) | (_, Err(e)) => {
+			warn!("Cannot read the validators of session {} at {:?}: {:?}", session_index, block_hash, e);
+			None
+		},
+		_ => None,
+	}
+}
+
+fn percentage(value: u32, total: u32) -> f64 {
+	if total == 0 {
+		0.0
+	} else {
+		value as f64 * 100.0 / total as f64
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::create_inherent_data;
+	use polkadot_introspector_essentials::observation::{CandidateObservation, ValidityVote};
+
+	fn create_observation() -> BlockObservation {
+		BlockObservation {
+			validators_count: 3,
+			candidates: vec![CandidateObservation {
+				votes: vec![
+					ValidityVote { validator_index: 1, address: None, voted: true },
+					ValidityVote { validator_index: 2, address: None, voted: false },
+				],
+				..Default::default()
+			}],
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_counts_participation_per_session() {
+		let stash = AccountId32([1; 32]);
+		let mut history = ValidatorHistory::new(ValidatorRef::Stash(stash.clone()));
+		// The bitfield of the inherent is signed by the validator 1, the dispute has statements of 1, 2 and 3
+		let mut inherent = create_inherent_data(100);
+		inherent.disputes[0].session = 4;
+		assert_eq!(history.unknown_sessions(5, &inherent), vec![4, 5]);
+		history.set_session_keys(4, &[AccountId32([0; 32]), AccountId32([0; 32]), stash.clone()], &[0, 1, 2]);
+		// The stash is the validator 2 of the session, but the validator 1 taking part in parachain consensus
+		history.set_session_keys(5, &[AccountId32([0; 32]), AccountId32([0; 32]), stash], &[0, 2]);
+
+		history.on_block(5, &create_observation(), &inherent);
+		history.on_block(5, &create_observation(), &inherent);
+
+		assert_eq!(
+			history.sessions.get(&5),
+			Some(&SessionHistory {
+				validator_index: 1,
+				blocks: 2,
+				bitfields: 2,
+				backing_seats: 2,
+				backing_votes: 2,
+				..Default::default()
+			})
+		);
+		assert_eq!(
+			history.sessions.get(&4),
+			Some(&SessionHistory { validator_index: 2, disputes: 1, dispute_votes: 1, ..Default::default() })
+		);
+
+		let csv = history.render(HistoryFormat::Csv);
+		assert_eq!(csv.lines().nth(2), Some("5,1,2,2,2,2,0,0"));
+
+		history.set_unresolved_session(6);
+		assert!(history.unknown_sessions(6, &inherent).iter().all(|session| *session != 6));
+		history.on_block(6, &create_observation(), &inherent);
+		assert!(history.sessions.get(&6).is_none());
+		assert_eq!(history.unresolved_sessions().iter().collect::<Vec<_>>(), vec![&6]);
+	}
+
+	#[test]
+	fn test_parses_validator() {
+		assert_eq!(ValidatorRef::from_str("42"), Ok(ValidatorRef::Index(42)));
+		assert!(matches!(
+			ValidatorRef::from_str("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY"),
+			Ok(ValidatorRef::Stash(_))
+		));
+		assert!(ValidatorRef::from_str("alice").is_err());
+	}
+}
@@ -0,0 +1,406 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! WASM plugins implementing output sinks and anomaly detectors.
+//!
+//! Every `*.wasm` file of the plugin directory is loaded at startup. A plugin exports its `memory` and
+//! `alloc(len: i32) -> i32` returning a buffer the host writes the input to, and implements one or both roles:
+//! - a sink exports `sink_write(ptr: i32, len: i32) -> i32` and optionally `sink_flush() -> i32`, non-zero results
+//!   are errors
+//! - a detector exports `detect(ptr: i32, len: i32)` and reports anomalies with the imported
+//!   `env.report_anomaly(severity: i32, para_id: i64, ptr: i32, len: i32)`, where the severity is 0 for info,
+//!   1 for warning and 2 for critical, a negative `para_id` means no parachain and `ptr`, `len` is the UTF-8 description
+//!
+//! The input is a JSON encoded observation of the `observation` schema. Plugins can also log UTF-8 messages with
+//! `env.log(ptr: i32, len: i32)`, no other imports are provided, so a plugin has no access to the file system or
+//! the network. Calls are metered, a plugin running out of fuel fails the call instead of stalling the tracer, and
+//! the memory of a plugin is limited, so it can't grow it until the tracer runs out of memory.
+
+use crate::{observation::BlockObservation, sinks::OutputSink};
+use async_trait::async_trait;
+use color_eyre::eyre::eyre;
+use log::{debug, error};
+use polkadot_introspector_essentials::{
+	anomaly::{Anomaly, AnomalyDetector},
+	incidents::Severity,
+};
+use std::path::Path;
+use wasmi::{
+	Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+/// Fuel available to a single call of a plugin, roughly the number of executed instructions
+const MAX_FUEL: u64 = 10_000_000;
+/// Memory available to a plugin
+const MAX_MEMORY_BYTES: usize = 32 * 1024 * 1024;
+/// Elements available to the tables of a plugin
+const MAX_TABLE_ELEMENTS: u32 = 10_000;
+
+/// Anomaly reported by a detector plugin during the current call
+struct ReportedAnomaly {
+	severity: Severity,
+	para_id: Option<u32>,
+	description: String,
+}
+
+struct PluginState {
+	/// Anomalies reported during the current call
+	anomalies: Vec<ReportedAnomaly>,
+	limits: StoreLimits,
+}
+
+impl Default for PluginState {
+	fn default() -> Self {
+		Self {
+			anomalies: vec![],
+			limits: StoreLimitsBuilder::new()
+				.memory_size(MAX_MEMORY_BYTES)
+				.table_elements(MAX_TABLE_ELEMENTS)
+				.instances(1)
+				.memories(1)
+				.tables(1)
+				.build(),
+		}
+	}
+}
+
+fn read_string(caller: &Caller<'_, PluginState>, ptr: i32, len: i32) -> Option<String> {
+	let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+	let start = usize::try_from(ptr).ok()?;
+	let end = start.checked_add(usize::try_from(len).ok()?)?;
+	let bytes = memory.data(caller).get(start..end)?;
+	Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// An instance of a plugin with its own store
+struct PluginInstance {
+	/// Name of the plugin, the file name without extension
+	name: String,
+	store: Store<PluginState>,
+	instance: Instance,
+	memory: Memory,
+	alloc: TypedFunc<i32, i32>,
+}
+
+impl PluginInstance {
+	fn new(engine: &Engine, module: &Module, name: &str) -> color_eyre::Result<Self> {
+		let mut store = Store::new(engine, PluginState::default());
+		store.limiter(|state| &mut state.limits);
+		let mut linker = <Linker<PluginState>>::new(engine);
+		let log_name = name.to_string();
+		linker
+			.func_wrap("env", "log", move |caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+				if let Some(message) = read_string(&caller, ptr, len) {
+					debug!("{} plugin: {}", log_name, message);
+				}
+			})
+			.and_then(|linker| {
+				linker.func_wrap(
+					"env",
+					"report_anomaly",
+					|mut caller: Caller<'_, PluginState>, severity: i32, para_id: i64, ptr: i32, len: i32| {
+						let severity = match severity {
+							0 => Severity::Info,
+							1 => Severity::Warning,
+							_ => Severity::Critical,
+						};
+						let description = read_string(&caller, ptr, len).unwrap_or_default();
+						caller.data_mut().anomalies.push(ReportedAnomaly {
+							severity,
+							para_id: u32::try_from(para_id).ok(),
+							description,
+						});
+					},
+				)
+			})
+			.map_err(|e| eyre!("Cannot link plugin {}: {}", name, e))?;
+
+		let instance = linker
+			.instantiate(&mut store, module)
+			.and_then(|instance| instance.start(&mut store))
+			.map_err(|e| eyre!("Cannot instantiate plugin {}: {}", name, e))?;
+		let memory = instance
+			.get_memory(&store, "memory")
+			.ok_or_else(|| eyre!("Plugin {} does not export its memory", name))?;
+		let alloc = instance
+			.get_typed_func::<i32, i32>(&store, "alloc")
+			.map_err(|e| eyre!("Plugin {} does not export `alloc`: {}", name, e))?;
+
+		Ok(Self { name: name.to_string(), store, instance, memory, alloc })
+	}
+
+	fn has_export(&self, name: &str) -> bool {
+		self.instance.get_func(&self.store, name).is_some()
+	}
+
+	fn typed_func<Params: wasmi::WasmParams, Results: wasmi::WasmResults>(
+		&self,
+		name: &str,
+	) -> color_eyre::Result<TypedFunc<Params, Results>> {
+		self.instance
+			.get_typed_func::<Params, Results>(&self.store, name)
+			.map_err(|e| eyre!("Plugin {} has an invalid `{}` export: {}", self.name, name, e))
+	}
+
+	fn refuel(&mut self) -> color_eyre::Result<()> {
+		self.store
+			.set_fuel(MAX_FUEL)
+			.map_err(|e| eyre!("Cannot refuel plugin {}: {}", self.name, e))
+	}
+
+	/// Copies the observation to the plugin's memory, returns its pointer and length
+	fn write_observation(&mut self, observation: &BlockObservation) -> color_eyre::Result<(i32, i32)> {
+		let input = serde_json::to_vec(observation)?;
+		let len = i32::try_from(input.len())?;
+		self.refuel()?;
+		let ptr = self
+			.alloc
+			.call(&mut self.store, len)
+			.map_err(|e| eyre!("Plugin {} failed to allocate {} bytes: {}", self.name, len, e))?;
+		self.memory
+			.write(&mut self.store, usize::try_from(ptr)?, &input)
+			.map_err(|e| eyre!("Plugin {} returned an invalid buffer: {}", self.name, e))?;
+
+		Ok((ptr, len))
+	}
+}
+
+/// A sink implemented by a plugin
+pub struct WasmSink {
+	plugin: PluginInstance,
+	write: TypedFunc<(i32, i32), i32>,
+	flush: Option<TypedFunc<(), i32>>,
+}
+
+#[async_trait]
+impl OutputSink for WasmSink {
+	fn name(&self) -> &str {
+		&self.plugin.name
+	}
+
+	async fn write(&mut self, observation: &BlockObservation) -> color_eyre::Result<()> {
+		let (ptr, len) = self.plugin.write_observation(observation)?;
+		self.plugin.refuel()?;
+		match self.write.call(&mut self.plugin.store, (ptr, len)) {
+			Ok(0) => Ok(()),
+			Ok(code) => Err(eyre!("`sink_write` returned {}", code)),
+			Err(e) => Err(eyre!("`sink_write` failed: {}", e)),
+		}
+	}
+
+	async fn flush(&mut self) -> color_eyre::Result<()> {
+		let flush = match self.flush.as_ref() {
+			Some(flush) => flush,
+			None => return Ok(()),
+		};
+		self.plugin.refuel()?;
+		match flush.call(&mut self.plugin.store, ()) {
+			Ok(0) => Ok(()),
+			Ok(code) => Err(eyre!("`sink_flush` returned {}", code)),
+			Err(e) => Err(eyre!("`sink_flush` failed: {}", e)),
+		}
+	}
+}
+
+/// An anomaly detector implemented by a plugin
+pub struct WasmDetector {
+	plugin: PluginInstance,
+	detect: TypedFunc<(i32, i32), ()>,
+}
+
+impl AnomalyDetector for WasmDetector {
+	fn name(&self) -> &str {
+		&self.plugin.name
+	}
+
+	fn on_observation(&mut self, observation: &BlockObservation) -> Vec<Anomaly> {
+		let result = self.plugin.write_observation(observation).and_then(|(ptr, len)| {
+			self.plugin.refuel()?;
+			self.detect
+				.call(&mut self.plugin.store, (ptr, len))
+				.map_err(|e| eyre!("`detect` failed: {}", e))
+		});
+		let reported = std::mem::take(&mut self.plugin.store.data_mut().anomalies);
+		if let Err(e) = result {
+			error!("{} plugin failed to process block {}: {:?}", self.plugin.name, observation.block_number, e);
+			return vec![]
+		}
+
+		reported
+			.into_iter()
+			.map(|anomaly| Anomaly {
+				detector: self.plugin.name.clone(),
+				severity: anomaly.severity,
+				block_number: observation.block_number,
+				timestamp: observation.timestamp,
+				para_id: anomaly.para_id,
+				description: anomaly.description,
+			})
+			.collect()
+	}
+}
+
+/// Sinks and detectors implemented by the loaded plugins
+#[derive(Default)]
+pub struct WasmPlugins {
+	pub sinks: Vec<WasmSink>,
+	pub detectors: Vec<WasmDetector>,
+}
+
+impl WasmPlugins {
+	/// Loads all `*.wasm` files from the directory
+	pub fn load(dir: &Path) -> color_eyre::Result<Self> {
+		let mut paths: Vec<_> = std::fs::read_dir(dir)
+			.map_err(|e| eyre!("Cannot read plugin directory {}: {:?}", dir.display(), e))?
+			.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+			.filter(|path| path.extension().is_some_and(|extension| extension == "wasm"))
+			.collect();
+		// Plugins are run in a stable order
+		paths.sort();
+
+		let mut plugins = Self::default();
+		for path in paths {
+			let wasm = std::fs::read(&path).map_err(|e| eyre!("Cannot read plugin {}: {:?}", path.display(), e))?;
+			let name = path
+				.file_stem()
+				.map_or_else(|| path.display().to_string(), |v| v.to_string_lossy().into_owned());
+			plugins.add(&name, &wasm)?;
+		}
+
+		Ok(plugins)
+	}
+
+	/// Compiles a plugin and instantiates it for each role it implements
+	pub fn add(&mut self, name: &str, wasm: &[u8]) -> color_eyre::Result<()> {
+		let mut config = Config::default();
+		config.consume_fuel(true);
+		let engine = Engine::new(&config);
+		let module = Module::new(&engine, wasm).map_err(|e| eyre!("Cannot compile plugin {}: {}", name, e))?;
+
+		let mut plugin = Some(PluginInstance::new(&engine, &module, name)?);
+		let is_sink = plugin.as_ref().is_some_and(|plugin| plugin.has_export("sink_write"));
+		let is_detector = plugin.as_ref().is_some_and(|plugin| plugin.has_export("detect"));
+		if !is_sink && !is_detector {
+			return Err(eyre!("Plugin {} exports neither `sink_write` nor `detect`", name))
+		}
+
+		if is_sink {
+			let plugin = plugin.take().expect("instantiated above; qed");
+			let write = plugin.typed_func("sink_write")?;
+			let flush = if plugin.has_export("sink_flush") { Some(plugin.typed_func("sink_flush")?) } else { None };
+			self.sinks.push(WasmSink { plugin, write, flush });
+		}
+		if is_detector {
+			// Detectors and sinks don't share state
+			let plugin = match plugin.take() {
+				Some(plugin) => plugin,
+				None => PluginInstance::new(&engine, &module, name)?,
+			};
+			let detect = plugin.typed_func("detect")?;
+			self.detectors.push(WasmDetector { plugin, detect });
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Reports an anomaly for every block and counts written observations
+	const PLUGIN: &str = r#"
+		(module
+			(import "env" "report_anomaly" (func $report_anomaly (param i32 i64 i32 i32)))
+			(memory (export "memory") 1)
+			(data (i32.const 0) "no bitfields")
+			(global $written (export "written") (mut i32) (i32.const 0))
+			(func (export "alloc") (param i32) (result i32) (i32.const 1024))
+			(func (export "detect") (param i32 i32)
+				(call $report_anomaly (i32.const 1) (i64.const 2000) (i32.const 0) (i32.const 12)))
+			(func (export "sink_write") (param i32 i32) (result i32)
+				(global.set $written (i32.add (global.get $written) (i32.const 1)))
+				(i32.const 0)))
+	"#;
+
+	const LOOP: &str = r#"
+		(module
+			(memory (export "memory") 1)
+			(func (export "alloc") (param i32) (result i32) (i32.const 0))
+			(func (export "detect") (param i32 i32) (loop $forever (br $forever))))
+	"#;
+
+	#[tokio::test]
+	async fn test_runs_plugin_roles() {
+		let mut plugins = WasmPlugins::default();
+		plugins.add("test", &wat::parse_str(PLUGIN).unwrap()).unwrap();
+		assert_eq!(plugins.sinks.len(), 1);
+		assert_eq!(plugins.detectors.len(), 1);
+
+		let observation = BlockObservation { block_number: 42, timestamp: 1000, ..Default::default() };
+		let anomalies = plugins.detectors[0].on_observation(&observation);
+		assert_eq!(
+			anomalies,
+			vec![Anomaly {
+				detector: "test".to_string(),
+				severity: Severity::Warning,
+				block_number: 42,
+				timestamp: 1000,
+				para_id: Some(2000),
+				description: "no bitfields".to_string(),
+			}]
+		);
+
+		let sink = &mut plugins.sinks[0];
+		sink.write(&observation).await.unwrap();
+		sink.write(&observation).await.unwrap();
+		sink.flush().await.unwrap();
+		let written = sink.plugin.instance.get_global(&sink.plugin.store, "written").unwrap();
+		assert_eq!(written.get(&sink.plugin.store).i32(), Some(2));
+	}
+
+	#[test]
+	fn test_limits_plugin_fuel() {
+		let mut plugins = WasmPlugins::default();
+		plugins.add("loop", &wat::parse_str(LOOP).unwrap()).unwrap();
+		assert!(plugins.sinks.is_empty());
+
+		let observation = BlockObservation::default();
+		assert!(plugins.detectors[0].on_observation(&observation).is_empty());
+	}
+
+	#[test]
+	fn test_limits_plugin_memory() {
+		// Asks for 64 MiB of memory at the start
+		let wasm = wat::parse_str(
+			r#"(module
+				(memory (export "memory") 1024)
+				(func (export "alloc") (param i32) (result i32) (i32.const 0))
+				(func (export "detect") (param i32 i32)))"#,
+		)
+		.unwrap();
+		assert!(WasmPlugins::default().add("greedy", &wasm).is_err());
+	}
+
+	#[test]
+	fn test_rejects_plugins_without_roles() {
+		let wasm = wat::parse_str(
+			r#"(module (memory (export "memory") 1) (func (export "alloc") (param i32) (result i32) (i32.const 0)))"#,
+		)
+		.unwrap();
+		assert!(WasmPlugins::default().add("empty", &wasm).is_err());
+	}
+}
@@ -0,0 +1,212 @@
+// Copyright 2022 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Watch expressions deriving custom metrics from the parachain progress.
+//!
+//! An expression is defined as `name = expression`, e.g. `bitfield_ratio = current_availability_bits /
+//! max_availability_bits`, and evaluated with [Rhai](https://rhai.rs) on every progress of every traced parachain.
+//! The variables are the fields of the `progress` schema, nested objects are maps, e.g. `availability.bitfield_count`,
+//! and the optional fields are missing when not set, so expressions using them are skipped. The availability bits are also available as
+//! `current_availability_bits`, `max_availability_bits` and `bitfield_count`. Numbers are floating point, so ratios
+//! don't need conversions. A result is exported as the `pc_watch_expression` gauge labeled with the expression name,
+//! booleans are exported as 1 or 0. `print` and `debug` in expressions write to the log.
+
+use crate::{hooks::sandboxed_engine, progress_record::ProgressRecord};
+use color_eyre::eyre::eyre;
+use log::{debug, error};
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use serde_json::Value;
+use std::path::Path;
+
+/// Maximum number of operations in an evaluation of an expression
+const MAX_OPERATIONS: u64 = 10_000;
+/// Maximum depth of function calls in an expression
+const MAX_CALL_LEVELS: usize = 8;
+
+struct WatchExpression {
+	name: String,
+	ast: AST,
+}
+
+/// Compiled watch expressions and the engine to evaluate them
+pub struct WatchExpressions {
+	engine: Engine,
+	expressions: Vec<WatchExpression>,
+}
+
+impl WatchExpressions {
+	pub fn new() -> Self {
+		Self { engine: sandboxed_engine(MAX_OPERATIONS, MAX_CALL_LEVELS), expressions: vec![] }
+	}
+
+	/// Compiles the expressions given on the command line and the ones of the file, one per line,
+	/// skipping empty lines and `#` comments
+	pub fn load(definitions: &[String], path: Option<&Path>) -> color_eyre::Result<Self> {
+		let mut expressions = Self::new();
+		for definition in definitions {
+			expressions.add(definition)?;
+		}
+		if let Some(path) = path {
+			let source = std::fs::read_to_string(path)
+				.map_err(|e| eyre!("Cannot read watch expressions {}: {:?}", path.display(), e))?;
+			for line in source.lines().map(str::trim) {
+				if !line.is_empty() && !line.starts_with('#') {
+					expressions.add(line)?;
+				}
+			}
+		}
+
+		Ok(expressions)
+	}
+
+	/// Compiles an expression defined as `name = expression`
+	pub fn add(&mut self, definition: &str) -> color_eyre::Result<()> {
+		let (name, source) = definition
+			.split_once('=')
+			.map(|(name, source)| (name.trim(), source.trim()))
+			.ok_or_else(|| eyre!("Watch expression `{}` should be defined as `name = expression`", definition))?;
+		let is_valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') &&
+			name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+		if !is_valid_name {
+			return Err(eyre!("Watch expression name `{}` should be an identifier", name))
+		}
+		if self.expressions.iter().any(|expression| expression.name == name) {
+			return Err(eyre!("Watch expression `{}` is defined twice", name))
+		}
+		let ast = self
+			.engine
+			.compile_expression(source)
+			.map_err(|e| eyre!("Cannot compile watch expression {}: {}", name, e))?;
+
+		self.expressions.push(WatchExpression { name: name.to_owned(), ast });
+		Ok(())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.expressions.is_empty()
+	}
+
+	/// Evaluates the expressions on a progress record, returns the names and values of the ones that evaluated to
+	/// finite numbers or booleans
+	pub fn evaluate(&self, record: &ProgressRecord) -> Vec<(&str, f64)> {
+		let mut scope = match serde_json::to_value(record) {
+			Ok(Value::Object(fields)) => {
+				let mut scope = Scope::new();
+				scope.push_constant("current_availability_bits", record.availability.available_count as f64);
+				scope.push_constant("max_availability_bits", record.availability.max_bitfield_count as f64);
+				scope.push_constant("bitfield_count", record.availability.bitfield_count as f64);
+				for (name, value) in fields.into_iter().filter(|(_, value)| !value.is_null()) {
+					scope.push_constant_dynamic(name, to_dynamic(value));
+				}
+				scope
+			},
+			Ok(_) => unreachable!("progress records are objects; qed"),
+			Err(e) => {
+				error!("Cannot pass the progress to watch expressions: {:?}", e);
+				return vec![]
+			},
+		};
+
+		let mut values = vec![];
+		for expression in self.expressions.iter() {
+			let result = match self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, &expression.ast) {
+				Ok(v) => v,
+				Err(e) => {
+					debug!("Watch expression {} failed: {}", expression.name, e);
+					continue
+				},
+			};
+			let value = if let Ok(v) = result.as_float() {
+				v
+			} else if let Ok(v) = result.as_int() {
+				v as f64
+			} else if let Ok(v) = result.as_bool() {
+				if v {
+					1.0
+				} else {
+					0.0
+				}
+			} else {
+				debug!("Watch expression {} evaluated to {}, not a number", expression.name, result.type_name());
+				continue
+			};
+			if value.is_finite() {
+				values.push((expression.name.as_str(), value));
+			}
+		}
+
+		values
+	}
+}
+
+/// Converts a JSON value to Rhai, numbers become floating point
+fn to_dynamic(value: Value) -> Dynamic {
+	match value {
+		Value::Null => Dynamic::UNIT,
+		Value::Bool(v) => Dynamic::from_bool(v),
+		Value::Number(v) => Dynamic::from_float(v.as_f64().unwrap_or_default()),
+		Value::String(v) => Dynamic::from(v),
+		Value::Array(values) => Dynamic::from_array(values.into_iter().map(to_dynamic).collect::<Array>()),
+		Value::Object(fields) => Dynamic::from_map(
+			fields
+				.into_iter()
+				.map(|(name, value)| (name.into(), to_dynamic(value)))
+				.collect::<Map>(),
+		),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::types::{BitfieldsHealth, ParachainProgressUpdate};
+
+	fn record() -> ProgressRecord {
+		ProgressRecord::from(&ParachainProgressUpdate {
+			para_id: 100,
+			block_number: 42,
+			timestamp: 20000,
+			prev_timestamp: 8000,
+			bitfield_health: BitfieldsHealth { max_bitfield_count: 200, available_count: 150, bitfield_count: 190 },
+			..Default::default()
+		})
+	}
+
+	#[test]
+	fn test_evaluates_expressions() {
+		let mut expressions = WatchExpressions::new();
+		expressions
+			.add("bitfield_ratio = current_availability_bits / max_availability_bits")
+			.unwrap();
+		expressions.add("slow = block_time_ms > 6000").unwrap();
+		expressions.add("signed = availability.bitfield_count").unwrap();
+		// Fails on a missing field and is skipped
+		expressions.add("lag = finality_lag * 2").unwrap();
+
+		assert_eq!(expressions.evaluate(&record()), vec![("bitfield_ratio", 0.75), ("slow", 1.0), ("signed", 190.0)]);
+	}
+
+	#[test]
+	fn test_rejects_invalid_expressions() {
+		let mut expressions = WatchExpressions::new();
+		assert!(expressions.add("no definition").is_err());
+		assert!(expressions.add("1st = 1").is_err());
+		assert!(expressions.add("syntax = (1 +").is_err());
+		expressions.add("ratio = 1").unwrap();
+		assert!(expressions.add("ratio = 2").is_err());
+		assert!(!expressions.is_empty());
+	}
+}
@@ -0,0 +1,40 @@
+#!/usr/bin/env bash
+#
+# Downloads the metadata of the relay chains vendored in `essentials/assets` and regenerates the registry of
+# `essentials/assets/vendored_metadata.rs` with the spec versions it was fetched at.
+#
+# Requires `subxt-cli`, `curl` and `jq`.
+
+set -euo pipefail
+
+ASSETS="$(dirname "$0")/../essentials/assets"
+NETWORKS=(
+	"polkadot rpc.polkadot.io"
+	"kusama kusama-rpc.polkadot.io"
+	"westend westend-rpc.polkadot.io"
+)
+
+TABLE="$ASSETS/vendored_metadata.rs"
+{
+	echo "// Generated by scripts/update_metadata.sh, do not edit."
+	echo "["
+} > "$TABLE.tmp"
+
+for network in "${NETWORKS[@]}"; do
+	read -r name host <<< "$network"
+	subxt metadata --format bytes --url "wss://$host:443" > "$ASSETS/${name}_metadata.scale"
+	version=$(curl -sf -H "Content-Type: application/json" \
+		-d '{"id":1,"jsonrpc":"2.0","method":"state_getRuntimeVersion","params":[]}' "https://$host" |
+		jq -r '.result.specName + " " + (.result.specVersion | tostring)')
+	read -r spec_name spec_version <<< "$version"
+	cat >> "$TABLE.tmp" <<ENTRY
+	VendoredMetadata {
+		spec_name: "$spec_name",
+		spec_version: $spec_version,
+		path: "assets/${name}_metadata.scale",
+	},
+ENTRY
+done
+
+echo "]" >> "$TABLE.tmp"
+mv "$TABLE.tmp" "$TABLE"
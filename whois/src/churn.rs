@@ -0,0 +1,180 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Periodic node churn reports and Prometheus metrics
+
+use clap::Args;
+use color_eyre::Result;
+use polkadot_introspector_essentials::{
+	metrics::{self, MetricsRegistry},
+	output::{OutputFormat, OutputWriter},
+	telemetry_churn::{ChurnOptions, ChurnReport, ChurnTracker},
+	telemetry_subscription::TelemetryEvent,
+	types::Timestamp,
+};
+use polkadot_introspector_priority_channel::Receiver;
+use prometheus_endpoint::prometheus::{Gauge, IntCounter, IntGauge, Opts};
+use std::{
+	net::ToSocketAddrs,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Debug, Args)]
+pub struct ChurnCommandOptions {
+	/// Seconds between reports
+	#[clap(long, default_value = "60")]
+	pub report_interval: u64,
+	/// Window in seconds in which reconnections are counted to detect flapping nodes
+	#[clap(long, default_value = "3600")]
+	pub flap_window: u64,
+	/// Number of reconnections within the window after which a node is flapping
+	#[clap(long, default_value = "3")]
+	pub flap_threshold: usize,
+	/// Port to bind Prometheus listener, metrics are not exported if not set
+	#[clap(long)]
+	pub prometheus_port: Option<u16>,
+	/// Address to bind Prometheus listener
+	#[clap(long, default_value = "0.0.0.0")]
+	pub prometheus_address: String,
+}
+
+impl From<&ChurnCommandOptions> for ChurnOptions {
+	fn from(opts: &ChurnCommandOptions) -> Self {
+		Self {
+			flap_window: Duration::from_secs(opts.flap_window),
+			flap_threshold: opts.flap_threshold,
+			..Default::default()
+		}
+	}
+}
+
+struct ChurnMetrics {
+	live_nodes: IntGauge,
+	added: IntCounter,
+	removed: IntCounter,
+	added_last_hour: IntGauge,
+	removed_last_hour: IntGauge,
+	average_session: Gauge,
+	flapping_nodes: IntGauge,
+}
+
+impl ChurnMetrics {
+	fn on_report(&self, report: &ChurnReport) {
+		self.live_nodes.set(report.live_nodes as i64);
+		self.added.inc_by(report.added_total.saturating_sub(self.added.get()));
+		self.removed.inc_by(report.removed_total.saturating_sub(self.removed.get()));
+		self.added_last_hour.set(report.added_last_hour as i64);
+		self.removed_last_hour.set(report.removed_last_hour as i64);
+		if let Some(average) = report.average_session {
+			self.average_session.set(average.as_secs_f64());
+		}
+		self.flapping_nodes.set(report.flapping.len() as i64);
+	}
+}
+
+/// Registers the metrics of the `churn` command without exporting them, e.g. to list them
+pub fn describe_metrics(registry: &MetricsRegistry) -> Result<()> {
+	register_metrics(registry).map(|_| ())
+}
+
+fn register_metrics(registry: &MetricsRegistry) -> Result<ChurnMetrics> {
+	Ok(ChurnMetrics {
+		live_nodes: metrics::register(
+			IntGauge::new("telemetry_nodes_live", "Number of nodes connected to telemetry")?,
+			registry,
+		)?,
+		added: metrics::register(
+			IntCounter::new("telemetry_nodes_added_total", "Number of nodes connected to telemetry")?,
+			registry,
+		)?,
+		removed: metrics::register(
+			IntCounter::new("telemetry_nodes_removed_total", "Number of nodes disconnected from telemetry")?,
+			registry,
+		)?,
+		added_last_hour: metrics::register(
+			IntGauge::new("telemetry_nodes_added_last_hour", "Number of nodes connected within the last hour")?,
+			registry,
+		)?,
+		removed_last_hour: metrics::register(
+			IntGauge::new("telemetry_nodes_removed_last_hour", "Number of nodes disconnected within the last hour")?,
+			registry,
+		)?,
+		average_session: metrics::register(
+			Gauge::with_opts(Opts::new(
+				"telemetry_node_session_seconds_avg",
+				"Average time nodes stay connected to telemetry",
+			))?,
+			registry,
+		)?,
+		flapping_nodes: metrics::register(
+			IntGauge::new("telemetry_nodes_flapping", "Number of nodes reconnecting repeatedly")?,
+			registry,
+		)?,
+	})
+}
+
+fn run_prometheus_endpoint(opts: &ChurnCommandOptions, port: u16) -> Result<ChurnMetrics> {
+	let prometheus_registry = MetricsRegistry::new("introspector")?;
+	let metrics = register_metrics(&prometheus_registry)?;
+	let socket_addr_str = format!("{}:{}", opts.prometheus_address, port);
+	for addr in socket_addr_str.to_socket_addrs()? {
+		tokio::spawn(prometheus_endpoint::init_prometheus(addr, prometheus_registry.registry().clone()));
+	}
+
+	Ok(metrics)
+}
+
+fn now_ms() -> Timestamp {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |v| v.as_millis() as Timestamp)
+}
+
+/// Tracks node churn printing a report every `report_interval` seconds
+pub async fn watch_churn(
+	update: Receiver<TelemetryEvent>,
+	opts: ChurnCommandOptions,
+	output: OutputFormat,
+) -> Result<()> {
+	let writer = OutputWriter::new(output);
+	let metrics = match opts.prometheus_port {
+		Some(port) => Some(run_prometheus_endpoint(&opts, port)?),
+		None => None,
+	};
+	let mut tracker = ChurnTracker::new((&opts).into());
+	let mut interval = tokio::time::interval(Duration::from_secs(opts.report_interval.max(1)));
+	// The first tick completes immediately
+	interval.tick().await;
+
+	loop {
+		tokio::select! {
+			event = update.recv() => match event {
+				Ok(event) => tracker.on_event(&event, now_ms()),
+				Err(_) => break,
+			},
+			_ = interval.tick() => {
+				let report = tracker.report(now_ms());
+				writer.print(&report)?;
+				if let Some(metrics) = &metrics {
+					metrics.on_report(&report);
+				}
+			},
+		}
+	}
+	writer.print(&tracker.report(now_ms()))?;
+
+	Ok(())
+}
@@ -0,0 +1,149 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Composite health score of a chain printed periodically and exported to Prometheus
+
+use clap::Args;
+use color_eyre::Result;
+use polkadot_introspector_essentials::{
+	metrics::{self, MetricsRegistry},
+	output::{OutputFormat, OutputWriter},
+	telemetry_health::{HealthOptions, HealthReport, HealthTracker},
+	telemetry_subscription::TelemetryEvent,
+};
+use polkadot_introspector_priority_channel::Receiver;
+use prometheus_endpoint::prometheus::{GaugeVec, Opts};
+use std::{net::ToSocketAddrs, time::Duration};
+
+#[derive(Clone, Debug, Args)]
+pub struct HealthCommandOptions {
+	/// Seconds between updates of the score
+	#[clap(long, default_value = "10")]
+	pub report_interval: u64,
+	/// Propagation time in milliseconds after that a block is imported late
+	#[clap(long, default_value = "1000")]
+	pub max_propagation: u64,
+	/// Finality lag in blocks which is still healthy
+	#[clap(long, default_value = "3")]
+	pub healthy_finality_lag: u32,
+	/// Finality lag in blocks at which finality doesn't contribute to the score
+	#[clap(long, default_value = "30")]
+	pub max_finality_lag: u32,
+	/// Port to bind Prometheus listener, metrics are not exported if not set
+	#[clap(long)]
+	pub prometheus_port: Option<u16>,
+	/// Address to bind Prometheus listener
+	#[clap(long, default_value = "0.0.0.0")]
+	pub prometheus_address: String,
+}
+
+impl From<&HealthCommandOptions> for HealthOptions {
+	fn from(opts: &HealthCommandOptions) -> Self {
+		Self {
+			max_propagation: Duration::from_millis(opts.max_propagation),
+			healthy_finality_lag: opts.healthy_finality_lag,
+			max_finality_lag: opts.max_finality_lag,
+		}
+	}
+}
+
+struct HealthMetrics {
+	score: GaugeVec,
+	propagation: GaugeVec,
+	finality: GaugeVec,
+	stale_ratio: GaugeVec,
+	nodes: GaugeVec,
+}
+
+impl HealthMetrics {
+	fn on_report(&self, report: &HealthReport) {
+		let chain = [report.chain.as_deref().unwrap_or_default()];
+		self.score.with_label_values(&chain).set(report.score);
+		self.propagation.with_label_values(&chain).set(report.propagation);
+		self.finality.with_label_values(&chain).set(report.finality);
+		self.stale_ratio.with_label_values(&chain).set(report.stale_ratio);
+		self.nodes.with_label_values(&chain).set(report.nodes as f64);
+	}
+}
+
+fn register_gauge(registry: &MetricsRegistry, name: &str, help: &str) -> Result<GaugeVec> {
+	Ok(metrics::register(GaugeVec::new(Opts::new(name, help), &["chain"])?, registry)?)
+}
+
+/// Registers the metrics of the `health` command without exporting them, e.g. to list them
+pub fn describe_metrics(registry: &MetricsRegistry) -> Result<()> {
+	register_metrics(registry).map(|_| ())
+}
+
+fn register_metrics(registry: &MetricsRegistry) -> Result<HealthMetrics> {
+	Ok(HealthMetrics {
+		score: register_gauge(registry, "telemetry_health_score", "Composite health score of the chain, 0..100")?,
+		propagation: register_gauge(
+			registry,
+			"telemetry_health_propagation",
+			"Share of nodes which imported their last block in time",
+		)?,
+		finality: register_gauge(registry, "telemetry_health_finality", "Finality component of the health score")?,
+		stale_ratio: register_gauge(registry, "telemetry_health_stale_ratio", "Share of stale nodes")?,
+		nodes: register_gauge(registry, "telemetry_health_nodes", "Number of nodes the health score is computed over")?,
+	})
+}
+
+fn run_prometheus_endpoint(opts: &HealthCommandOptions, port: u16) -> Result<HealthMetrics> {
+	let prometheus_registry = MetricsRegistry::new("introspector")?;
+	let metrics = register_metrics(&prometheus_registry)?;
+	let socket_addr_str = format!("{}:{}", opts.prometheus_address, port);
+	for addr in socket_addr_str.to_socket_addrs()? {
+		tokio::spawn(prometheus_endpoint::init_prometheus(addr, prometheus_registry.registry().clone()));
+	}
+
+	Ok(metrics)
+}
+
+/// Tracks the health of a chain printing the score every `report_interval` seconds
+pub async fn watch_health(
+	update: Receiver<TelemetryEvent>,
+	opts: HealthCommandOptions,
+	output: OutputFormat,
+) -> Result<()> {
+	let writer = OutputWriter::new(output);
+	let metrics = match opts.prometheus_port {
+		Some(port) => Some(run_prometheus_endpoint(&opts, port)?),
+		None => None,
+	};
+	let mut tracker = HealthTracker::new((&opts).into());
+	let mut interval = tokio::time::interval(Duration::from_secs(opts.report_interval.max(1)));
+	// The first tick completes immediately
+	interval.tick().await;
+
+	loop {
+		tokio::select! {
+			event = update.recv() => match event {
+				Ok(event) => tracker.on_event(&event),
+				Err(_) => break,
+			},
+			_ = interval.tick() => {
+				let report = tracker.report();
+				writer.print(&report)?;
+				if let Some(metrics) = &metrics {
+					metrics.on_report(&report);
+				}
+			},
+		}
+	}
+
+	Ok(())
+}
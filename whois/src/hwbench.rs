@@ -0,0 +1,93 @@
+// Copyright 2023 Parity Technologies (UK) Ltd.
+// This file is part of polkadot-introspector.
+//
+// polkadot-introspector is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// polkadot-introspector is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Hardware benchmark distributions of a chain's nodes
+
+use clap::Args;
+use color_eyre::Result;
+use log::info;
+use polkadot_introspector_essentials::{
+	output::{OutputFormat, OutputWriter},
+	telemetry_hwbench::{HwBenchCollector, HwBenchThresholds, REFERENCE_HW_THRESHOLDS},
+	telemetry_subscription::TelemetryEvent,
+};
+use polkadot_introspector_priority_channel::Receiver;
+use std::{fs::File, io::BufWriter, path::PathBuf, time::Duration};
+
+#[derive(Clone, Debug, Args)]
+pub struct HwBenchCommandOptions {
+	/// Seconds to collect nodes from the feed before reporting
+	#[clap(long, default_value = "30")]
+	pub collect_for: u64,
+	/// Path to export benchmarks of all nodes as CSV
+	#[clap(long)]
+	pub csv: Option<PathBuf>,
+	/// Minimal CPU score (BLAKE2-256 hashing), MiB/s
+	#[clap(long, default_value_t = REFERENCE_HW_THRESHOLDS.cpu_hashrate_score)]
+	pub min_cpu: u64,
+	/// Minimal memory score (memcpy), MiB/s
+	#[clap(long, default_value_t = REFERENCE_HW_THRESHOLDS.memory_memcpy_score)]
+	pub min_memory: u64,
+	/// Minimal disk sequential write score, MiB/s
+	#[clap(long, default_value_t = REFERENCE_HW_THRESHOLDS.disk_sequential_write_score)]
+	pub min_disk_sequential_write: u64,
+	/// Minimal disk random write score, MiB/s
+	#[clap(long, default_value_t = REFERENCE_HW_THRESHOLDS.disk_random_write_score)]
+	pub min_disk_random_write: u64,
+}
+
+impl From<&HwBenchCommandOptions> for HwBenchThresholds {
+	fn from(opts: &HwBenchCommandOptions) -> Self {
+		Self {
+			cpu_hashrate_score: opts.min_cpu,
+			memory_memcpy_score: opts.min_memory,
+			disk_sequential_write_score: opts.min_disk_sequential_write,
+			disk_random_write_score: opts.min_disk_random_write,
+		}
+	}
+}
+
+/// Collects hardware benchmarks for `collect_for` seconds, then prints the report and exports CSV if requested
+pub async fn collect_hwbench(
+	update: Receiver<TelemetryEvent>,
+	opts: HwBenchCommandOptions,
+	output: OutputFormat,
+) -> Result<()> {
+	let mut collector = HwBenchCollector::new((&opts).into());
+	let deadline = tokio::time::sleep(Duration::from_secs(opts.collect_for));
+	tokio::pin!(deadline);
+
+	// Informational messages are logged to keep machine-readable output parseable
+	let print_info = |message: String| if output.is_pretty() { println!("{}", message) } else { info!("{}", message) };
+	print_info(format!("Collecting hardware benchmarks for {} seconds...", opts.collect_for));
+	loop {
+		tokio::select! {
+			event = update.recv() => match event {
+				Ok(event) => collector.on_event(&event),
+				Err(_) => break,
+			},
+			_ = &mut deadline => break,
+		}
+	}
+	OutputWriter::new(output).print(&collector.report())?;
+
+	if let Some(path) = &opts.csv {
+		collector.write_csv(BufWriter::new(File::create(path)?))?;
+		print_info(format!("Benchmarks exported to {}", path.display()));
+	}
+
+	Ok(())
+}
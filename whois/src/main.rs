@@ -14,11 +14,17 @@
 // You should have received a copy of the GNU General Public License
 // along with polkadot-introspector.  If not, see <http://www.gnu.org/licenses/>.
 
+use churn::ChurnCommandOptions;
 use clap::{Args, Parser, Subcommand};
+use health::HealthCommandOptions;
+use hwbench::HwBenchCommandOptions;
+use log::error;
 use polkadot_introspector_essentials::{
 	api::subxt_wrapper::{RequestExecutor, SubxtWrapperError},
 	consumer::{EventConsumerInit, EventStream},
 	init,
+	metrics::MetricsRegistry,
+	output::{OutputFormat, OutputOptions},
 	telemetry_feed::{AddedNode, TelemetryFeed},
 	telemetry_subscription::{TelemetryEvent, TelemetrySubscription},
 	types::{AccountId32, SessionKeys},
@@ -27,21 +33,27 @@ use polkadot_introspector_essentials::{
 use polkadot_introspector_priority_channel::Receiver;
 use std::str::FromStr;
 
+mod churn;
+mod health;
+mod hwbench;
+
 #[derive(Clone, Debug, Parser)]
 #[clap(author, version, about = "Simple telemetry feed")]
 struct TelemetryOptions {
 	#[clap(subcommand)]
 	command: WhoisCommand,
-	/// Web-Socket URLs of a relay chain node.
+	/// Web-Socket URLs of a relay chain node, required to look up validators.
 	#[clap(long)]
-	pub ws: String,
+	pub ws: Option<String>,
 	/// Web-Socket URL of a telemetry backend
-	#[clap(long)]
+	#[clap(long, default_value = "wss://feed.telemetry.polkadot.io/feed")]
 	pub feed: String,
 	/// Name of a chain to connect
 	#[clap(long)]
 	pub chain: Option<String>,
 	#[clap(flatten)]
+	pub output: OutputOptions,
+	#[clap(flatten)]
 	pub verbose: init::VerbosityOptions,
 	#[clap(flatten)]
 	pub retry: utils::RetryOptions,
@@ -51,6 +63,14 @@ struct TelemetryOptions {
 enum WhoisCommand {
 	Account(AccountOptions),
 	Session(SessionOptions),
+	/// Report node churn: connections, sessions and flapping nodes
+	Churn(ChurnCommandOptions),
+	/// Report hardware benchmark distributions and validators below the recommended hardware
+	Hwbench(HwBenchCommandOptions),
+	/// Report a composite health score of the chain: block propagation, finality lag and stale nodes
+	Health(HealthCommandOptions),
+	/// Print the Prometheus metrics exported by the commands and exit
+	ListMetrics,
 }
 
 #[derive(Clone, Debug, Args)]
@@ -73,6 +93,8 @@ pub enum WhoisError {
 	NoSessionKeys,
 	#[error("Validator with given index not found")]
 	NoValidator,
+	#[error("Relay chain node URL is not set")]
+	NoRelayChain,
 	#[error("Can't connect to relay chain")]
 	SubxtError(SubxtWrapperError),
 	#[error("Can't connect to telemetry feed")]
@@ -92,26 +114,43 @@ impl Whois {
 		self,
 		consumer_config: EventConsumerInit<TelemetryEvent>,
 	) -> color_eyre::Result<Vec<tokio::task::JoinHandle<()>>, WhoisError> {
-		let mut executor = RequestExecutor::new(self.opts.retry.clone());
+		let consumer_channels: Vec<Receiver<TelemetryEvent>> = consumer_config.into();
+		let ws = self.opts.ws.clone();
+		let mut executor = RequestExecutor::new(self.opts.retry.clone(), Default::default());
+		let output = self.opts.output.format;
 		let validator = match self.opts.command {
+			WhoisCommand::Churn(opts) => return Ok(Self::spawn_churn(consumer_channels, opts, output)),
+			WhoisCommand::Hwbench(opts) => return Ok(Self::spawn_hwbench(consumer_channels, opts, output)),
+			WhoisCommand::Health(opts) => return Ok(Self::spawn_health(consumer_channels, opts, output)),
+			WhoisCommand::ListMetrics => return Ok(vec![]),
 			WhoisCommand::Account(v) => v.validator,
-			WhoisCommand::Session(v) => match executor.get_session_account_keys(&self.opts.ws, v.session_index).await {
-				Ok(Some(validators)) => match validators.get(v.validator_index) {
-					Some(v) => v.clone(),
-					None => return Err(WhoisError::NoValidator),
-				},
-				Err(e) => return Err(WhoisError::SubxtError(e)),
-				_ => return Err(WhoisError::NoSessionKeys),
+			WhoisCommand::Session(v) => {
+				let url = ws.as_deref().ok_or(WhoisError::NoRelayChain)?;
+				let block_hash = match executor.get_finalized_block_hash(url).await {
+					Ok(Some(block_hash)) => block_hash,
+					Err(e) => return Err(WhoisError::SubxtError(e)),
+					_ => return Err(WhoisError::NoSessionKeys),
+				};
+				match executor.get_session_account_keys(url, block_hash, v.session_index).await {
+					Ok(Some(validators)) => match validators.get(v.validator_index) {
+						Some(v) => v.clone(),
+						None => return Err(WhoisError::NoValidator),
+					},
+					Err(e) => return Err(WhoisError::SubxtError(e)),
+					_ => return Err(WhoisError::NoSessionKeys),
+				}
 			},
 		};
-		let next_keys = match executor.get_session_next_keys(&self.opts.ws, validator.clone()).await {
+		let next_keys = match executor
+			.get_session_next_keys(ws.as_deref().ok_or(WhoisError::NoRelayChain)?, validator.clone())
+			.await
+		{
 			Ok(Some(v)) => v,
 			Err(e) => return Err(WhoisError::SubxtError(e)),
 			_ => return Err(WhoisError::NoNextKeys),
 		};
 		let authority_key = get_authority_key(next_keys);
 
-		let consumer_channels: Vec<Receiver<TelemetryEvent>> = consumer_config.into();
 		let futures = consumer_channels
 			.into_iter()
 			.map(|c: Receiver<TelemetryEvent>| tokio::spawn(Self::watch(c, authority_key.clone(), validator.clone())))
@@ -120,9 +159,72 @@ impl Whois {
 		Ok(futures)
 	}
 
+	fn spawn_churn(
+		consumer_channels: Vec<Receiver<TelemetryEvent>>,
+		opts: ChurnCommandOptions,
+		output: OutputFormat,
+	) -> Vec<tokio::task::JoinHandle<()>> {
+		consumer_channels
+			.into_iter()
+			.map(|c| {
+				let opts = opts.clone();
+				tokio::spawn(async move {
+					if let Err(e) = churn::watch_churn(c, opts, output).await {
+						error!("Cannot track node churn: {:?}", e);
+					}
+				})
+			})
+			.collect()
+	}
+
+	fn spawn_health(
+		consumer_channels: Vec<Receiver<TelemetryEvent>>,
+		opts: HealthCommandOptions,
+		output: OutputFormat,
+	) -> Vec<tokio::task::JoinHandle<()>> {
+		consumer_channels
+			.into_iter()
+			.map(|c| {
+				let opts = opts.clone();
+				tokio::spawn(async move {
+					if let Err(e) = health::watch_health(c, opts, output).await {
+						error!("Cannot track chain health: {:?}", e);
+					}
+				})
+			})
+			.collect()
+	}
+
+	fn spawn_hwbench(
+		consumer_channels: Vec<Receiver<TelemetryEvent>>,
+		opts: HwBenchCommandOptions,
+		output: OutputFormat,
+	) -> Vec<tokio::task::JoinHandle<()>> {
+		consumer_channels
+			.into_iter()
+			.map(|c| {
+				let opts = opts.clone();
+				tokio::spawn(async move {
+					match hwbench::collect_hwbench(c, opts, output).await {
+						Ok(()) => std::process::exit(0),
+						Err(e) => {
+							error!("Cannot collect hardware benchmarks: {:?}", e);
+							std::process::exit(1);
+						},
+					}
+				})
+			})
+			.collect()
+	}
+
 	async fn watch(update: Receiver<TelemetryEvent>, authority_key: AccountId32, validator: AccountId32) {
 		let mut count = 0_u32;
-		while let Ok(TelemetryEvent::NewMessage(message)) = update.recv().await {
+		while let Ok(event) = update.recv().await {
+			let message = match event {
+				TelemetryEvent::NewMessage(message) => message,
+				// Nodes are sent again after resubscription
+				TelemetryEvent::Gap { .. } => continue,
+			};
 			if count > 0 {
 				clear_last_two_lines();
 			}
@@ -166,13 +268,20 @@ fn clear_last_two_lines() {
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
 	let opts = TelemetryOptions::parse();
+	if let WhoisCommand::ListMetrics = opts.command {
+		let registry = MetricsRegistry::new("introspector")?;
+		churn::describe_metrics(&registry)?;
+		health::describe_metrics(&registry)?;
+		registry.print_catalog();
+		return Ok(())
+	}
 	init::init_cli(&opts.verbose)?;
 
 	let whois = Whois::new(opts.clone())?;
 	let shutdown_tx = init::init_shutdown();
 	let mut futures = vec![];
 
-	let mut sub = TelemetrySubscription::new(opts.ws.clone(), opts.chain.clone());
+	let mut sub = TelemetrySubscription::new(opts.feed.clone(), opts.chain.clone());
 	let consumer_init = sub.create_consumer();
 
 	futures.extend(whois.run(consumer_init).await?);